@@ -0,0 +1,119 @@
+//! chat_send latency with many sessions streaming at once: one global lock vs per-session locks
+//!
+//! Run with `cargo bench -p amp-orchestra --bench chat_send`. `SESSIONS` sessions
+//! each send `SENDS_PER_SESSION` messages concurrently. A send finds its session,
+//! resumes it, stores the prompt (simulated by `DB_WRITE`) and hands the line to
+//! the session's writer task. With the global lock the store happens while the
+//! whole map is locked, as chat_send used to do.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/session_map.rs"]
+mod session_map;
+
+use session_map::SessionMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+
+const SESSIONS: usize = 50;
+const SENDS_PER_SESSION: usize = 40;
+const DB_WRITE: Duration = Duration::from_micros(500);
+
+struct Session {
+    tx: mpsc::UnboundedSender<String>,
+}
+
+/// A writer task that drains the session's stdin, as a streaming CLI would
+fn spawn_session() -> Session {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    Session { tx }
+}
+
+async fn send_global(map: &Mutex<HashMap<String, Session>>, id: &str, line: String) {
+    let map = map.lock().await;
+    let session = map.get(id).unwrap();
+    tokio::time::sleep(DB_WRITE).await;
+    session.tx.send(line).unwrap();
+}
+
+async fn send_per_session(map: &SessionMap<Session>, id: &str, line: String) {
+    let tx = map.get(id).unwrap().lock().await.tx.clone();
+    tokio::time::sleep(DB_WRITE).await;
+    tx.send(line).unwrap();
+}
+
+fn report(label: &str, wall: Duration, mut latencies: Vec<Duration>) {
+    latencies.sort();
+    let at = |q: f64| latencies[((latencies.len() - 1) as f64 * q) as usize].as_secs_f64() * 1000.0;
+    println!(
+        "{:<20} {:>9.2} ms {:>9.2} ms {:>9.2} ms {:>10.1} ms",
+        label, at(0.5), at(0.99), at(1.0), wall.as_secs_f64() * 1000.0
+    );
+}
+
+async fn bench_global() {
+    let map = Arc::new(Mutex::new(HashMap::new()));
+    for session in 0..SESSIONS {
+        map.lock().await.insert(format!("session-{}", session), spawn_session());
+    }
+    let start = Instant::now();
+    let mut tasks = Vec::new();
+    for session in 0..SESSIONS {
+        let map = map.clone();
+        tasks.push(tokio::spawn(async move {
+            let id = format!("session-{}", session);
+            let mut latencies = Vec::new();
+            for send in 0..SENDS_PER_SESSION {
+                let sent = Instant::now();
+                send_global(&map, &id, format!("message {}", send)).await;
+                latencies.push(sent.elapsed());
+            }
+            latencies
+        }));
+    }
+    let mut latencies = Vec::new();
+    for task in tasks {
+        latencies.extend(task.await.unwrap());
+    }
+    report("global lock", start.elapsed(), latencies);
+}
+
+async fn bench_per_session() {
+    let map = Arc::new(SessionMap::new());
+    for session in 0..SESSIONS {
+        map.insert(format!("session-{}", session), spawn_session());
+    }
+    let start = Instant::now();
+    let mut tasks = Vec::new();
+    for session in 0..SESSIONS {
+        let map = map.clone();
+        tasks.push(tokio::spawn(async move {
+            let id = format!("session-{}", session);
+            let mut latencies = Vec::new();
+            for send in 0..SENDS_PER_SESSION {
+                let sent = Instant::now();
+                send_per_session(&map, &id, format!("message {}", send)).await;
+                latencies.push(sent.elapsed());
+            }
+            latencies
+        }));
+    }
+    let mut latencies = Vec::new();
+    for task in tasks {
+        latencies.extend(task.await.unwrap());
+    }
+    report("per-session locks", start.elapsed(), latencies);
+}
+
+#[tokio::main]
+async fn main() {
+    println!(
+        "{} sessions x {} sends, {} us simulated DB write per send\n",
+        SESSIONS, SENDS_PER_SESSION, DB_WRITE.as_micros()
+    );
+    println!("{:<20} {:>12} {:>12} {:>12} {:>13}", "case", "p50", "p99", "max", "wall");
+    bench_global().await;
+    bench_per_session().await;
+}
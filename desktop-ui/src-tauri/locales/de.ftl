@@ -0,0 +1,56 @@
+## Errors
+error-database-unavailable = Datenbank nicht verfügbar
+error-feature-disabled = Funktion '{ $name }' ist deaktiviert
+error-unsupported-locale = Keine Übersetzung für '{ $locale }' verfügbar
+
+## Notifications
+notify-session-stale = Sitzung { $session } ist seit { $minutes } Min. inaktiv
+notify-session-stale-suspended = Sitzung { $session } wurde nach { $minutes } Min. Inaktivität angehalten
+notify-session-stale-detached = Sitzung { $session } wurde nach { $minutes } Min. Inaktivität getrennt
+notify-session-gc = { $count } Branches gelöschter oder archivierter Sitzungen entfernt
+notify-session-awaiting-input = Sitzung { $session } wartet auf Ihre Eingabe
+notify-repository-moved = { $count } Repositories mit Sitzungs-Worktrees wurden verschoben; wählen Sie den neuen Ort, um sie zu reparieren
+notify-startup-reconciliation = Startprüfung: { $corrected } Sitzungen korrigiert, { $reaped } verbliebene Prozesse beendet, { $missing } Worktrees fehlen
+notify-memory-pressure = Speicher knapp: { $evicted } zwischengespeicherte Puffer verworfen, { $warned } weitere Stream-Puffer werden verworfen, falls das so bleibt
+notify-process-limit-memory = { $session } gestoppt: { $used } MB Speicher belegt, mehr als das Limit von { $limit } MB
+notify-process-limit-time = { $session } gestoppt: lief länger als das Limit von { $limit } s
+
+## Konflikte
+conflict-edit = Zeile { $id } in { $table } wurde auf diesem und einem anderen Gerät geändert
+conflict-delete = Sitzung { $session } wurde auf einem Gerät gelöscht und auf einem anderen geändert
+conflict-binding = Die zusammengeführten Sitzungen nutzten verschiedene Profile; { $session } behielt seine eigenen
+
+## Exports
+export-title = Amp-Sitzungsexport
+version-matrix-title = CLI-Versionsmatrix
+export-generated = Erstellt { $date }
+export-session-count = { $count } Sitzungen
+export-last-updated = zuletzt aktualisiert { $date }
+export-not-available = k. A.
+export-col-id = ID
+export-col-context = Kontext
+export-col-title = Titel
+export-col-agent-mode = Agentenmodus
+export-col-toolbox-path = Toolbox-Pfad
+export-col-tools-available = Verfügbare Tools
+export-col-tools-used = Verwendete Tools
+export-col-input-tokens = Eingabe-Tokens
+export-col-output-tokens = Ausgabe-Tokens
+export-col-duration = Dauer (ms)
+export-col-rating = Bewertung
+export-col-feedback = Feedback
+export-col-env-hash = Umgebungs-Hash
+export-col-created = Erstellt
+export-col-updated = Aktualisiert
+export-metrics = Kennzahlen
+export-transcript = Verlauf
+export-messages = Nachrichten
+export-turns = Runden
+export-generation-time = Generierungszeit (ms)
+export-cost = Kosten (USD)
+export-role-user = Benutzer
+export-role-assistant = Assistent
+export-role-system = System
+export-tool-call = Tool-Aufruf: { $name }
+export-tool-result = Tool-Ergebnis
+export-interrupted = abgebrochen
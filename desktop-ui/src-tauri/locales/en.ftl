@@ -0,0 +1,59 @@
+# Backend strings shown to users. Every other catalog translates these keys;
+# missing keys fall back to English.
+
+## Errors
+error-database-unavailable = Database not available
+error-feature-disabled = Feature '{ $name }' is disabled
+error-unsupported-locale = No translation available for '{ $locale }'
+
+## Notifications
+notify-session-stale = Session { $session } has been idle for { $minutes } min
+notify-session-stale-suspended = Session { $session } was suspended after { $minutes } min idle
+notify-session-stale-detached = Session { $session } was detached after { $minutes } min idle
+notify-session-gc = Cleaned up { $count } branches of deleted or archived sessions
+notify-session-awaiting-input = Session { $session } is waiting for your input
+notify-repository-moved = { $count } repositories with session worktrees have moved; choose their new location to repair them
+notify-startup-reconciliation = Startup check: { $corrected } sessions corrected, { $reaped } leftover processes stopped, { $missing } worktrees missing
+notify-memory-pressure = Memory is tight: dropped { $evicted } cached buffers, { $warned } more stream buffers will be dropped if it stays that way
+notify-process-limit-memory = Stopped { $session }: it used { $used } MB of memory, over the { $limit } MB limit
+notify-process-limit-time = Stopped { $session }: it ran longer than the { $limit }s limit
+
+## Conflicts
+conflict-edit = { $table } row { $id } was changed on this and another device
+conflict-delete = Session { $session } was deleted on one device and changed on another
+conflict-binding = Merged sessions used different profiles; { $session } kept its own
+
+## Exports
+export-title = Amp Session Export
+version-matrix-title = CLI Version Matrix
+export-generated = Generated { $date }
+export-session-count = { $count } sessions
+export-last-updated = last updated { $date }
+export-not-available = N/A
+export-col-id = ID
+export-col-context = Context
+export-col-title = Title
+export-col-agent-mode = Agent Mode
+export-col-toolbox-path = Toolbox Path
+export-col-tools-available = Tools Available
+export-col-tools-used = Tools Used
+export-col-input-tokens = Input Tokens
+export-col-output-tokens = Output Tokens
+export-col-duration = Duration (ms)
+export-col-rating = Rating
+export-col-feedback = Feedback
+export-col-env-hash = Environment Hash
+export-col-created = Created
+export-col-updated = Updated
+export-metrics = Metrics
+export-transcript = Transcript
+export-messages = Messages
+export-turns = Turns
+export-generation-time = Generation time (ms)
+export-cost = Cost (USD)
+export-role-user = User
+export-role-assistant = Assistant
+export-role-system = System
+export-tool-call = Tool call: { $name }
+export-tool-result = Tool result
+export-interrupted = interrupted
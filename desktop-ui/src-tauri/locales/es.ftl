@@ -0,0 +1,56 @@
+## Errors
+error-database-unavailable = Base de datos no disponible
+error-feature-disabled = La función '{ $name }' está desactivada
+error-unsupported-locale = No hay traducción disponible para '{ $locale }'
+
+## Notifications
+notify-session-stale = La sesión { $session } lleva { $minutes } min inactiva
+notify-session-stale-suspended = La sesión { $session } se suspendió tras { $minutes } min de inactividad
+notify-session-stale-detached = La sesión { $session } se desconectó tras { $minutes } min de inactividad
+notify-session-gc = Se eliminaron { $count } ramas de sesiones borradas o archivadas
+notify-session-awaiting-input = La sesión { $session } espera tu respuesta
+notify-repository-moved = Se han movido { $count } repositorios con worktrees de sesión; elige su nueva ubicación para repararlos
+notify-startup-reconciliation = Comprobación de inicio: { $corrected } sesiones corregidas, { $reaped } procesos sobrantes detenidos, { $missing } worktrees ausentes
+notify-memory-pressure = Memoria escasa: se descartaron { $evicted } búferes en caché; se descartarán { $warned } búferes de stream más si continúa así
+notify-process-limit-memory = Se detuvo { $session }: usó { $used } MB de memoria, más que el límite de { $limit } MB
+notify-process-limit-time = Se detuvo { $session }: se ejecutó más allá del límite de { $limit } s
+
+## Conflictos
+conflict-edit = La fila { $id } de { $table } se cambió en este y en otro dispositivo
+conflict-delete = La sesión { $session } se borró en un dispositivo y se cambió en otro
+conflict-binding = Las sesiones combinadas usaban perfiles distintos; { $session } conservó los suyos
+
+## Exports
+export-title = Exportación de sesiones de Amp
+version-matrix-title = Matriz de versiones de la CLI
+export-generated = Generado { $date }
+export-session-count = { $count } sesiones
+export-last-updated = última actualización { $date }
+export-not-available = N/D
+export-col-id = ID
+export-col-context = Contexto
+export-col-title = Título
+export-col-agent-mode = Modo del agente
+export-col-toolbox-path = Ruta del toolbox
+export-col-tools-available = Herramientas disponibles
+export-col-tools-used = Herramientas usadas
+export-col-input-tokens = Tokens de entrada
+export-col-output-tokens = Tokens de salida
+export-col-duration = Duración (ms)
+export-col-rating = Valoración
+export-col-feedback = Comentarios
+export-col-env-hash = Hash del entorno
+export-col-created = Creado
+export-col-updated = Actualizado
+export-metrics = Métricas
+export-transcript = Transcripción
+export-messages = Mensajes
+export-turns = Turnos
+export-generation-time = Tiempo de generación (ms)
+export-cost = Coste (USD)
+export-role-user = Usuario
+export-role-assistant = Asistente
+export-role-system = Sistema
+export-tool-call = Llamada a herramienta: { $name }
+export-tool-result = Resultado de herramienta
+export-interrupted = interrumpido
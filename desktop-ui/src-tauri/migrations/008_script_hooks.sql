@@ -0,0 +1,32 @@
+-- Migration 008: Add per-profile scripting hooks
+-- Stores user scripts triggered on session events, plus tags applied by those scripts
+
+CREATE TABLE IF NOT EXISTS script_hooks (
+    id TEXT PRIMARY KEY NOT NULL,
+    profile_id TEXT NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    event TEXT NOT NULL CHECK (event IN ('session_completed', 'tool_use', 'budget_exceeded')),
+    source TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    UNIQUE (profile_id, name)
+);
+
+-- Session tags can target chat sessions or threads, so no foreign key here
+CREATE TABLE IF NOT EXISTS session_tags (
+    session_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    PRIMARY KEY (session_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_hooks_profile_event ON script_hooks(profile_id, event);
+CREATE INDEX IF NOT EXISTS idx_session_tags_tag ON session_tags(tag);
+
+CREATE TRIGGER IF NOT EXISTS update_script_hooks_updated_at
+AFTER UPDATE ON script_hooks
+FOR EACH ROW
+BEGIN
+  UPDATE script_hooks SET updated_at = (datetime('now', 'utc') || 'Z') WHERE id = NEW.id;
+END;
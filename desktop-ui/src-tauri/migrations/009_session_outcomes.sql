@@ -0,0 +1,19 @@
+-- Migration 009: Add session outcome classification
+-- One row per finished chat session or thread, with the raw signals kept so
+-- outcomes can be recomputed when the classification rules change
+
+CREATE TABLE IF NOT EXISTS session_outcomes (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    outcome TEXT NOT NULL CHECK (outcome IN ('succeeded', 'failed', 'abandoned')),
+    decided_by TEXT NOT NULL,
+    agent_mode TEXT,
+    toolbox_profile_id INTEGER NULL,
+    errored BOOLEAN NOT NULL DEFAULT 0,
+    received_result BOOLEAN NOT NULL DEFAULT 0,
+    explicit_outcome TEXT NULL CHECK (explicit_outcome IN ('succeeded', 'failed', 'abandoned')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    classified_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_outcomes_agent_mode ON session_outcomes(agent_mode);
+CREATE INDEX IF NOT EXISTS idx_session_outcomes_toolbox_profile ON session_outcomes(toolbox_profile_id);
@@ -0,0 +1,16 @@
+-- Migration 010: Add user feedback per thread
+-- Ratings are linked to a hash of the environment (context, agent mode, toolbox)
+-- the thread ran with so quality can be compared across configurations
+
+CREATE TABLE IF NOT EXISTS thread_feedback (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id TEXT NOT NULL, -- thread id, or chat session id for legacy chats
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    comment TEXT,
+    env_hash TEXT NOT NULL,
+    agent_mode TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_feedback_thread_id ON thread_feedback(thread_id);
+CREATE INDEX IF NOT EXISTS idx_thread_feedback_env_hash ON thread_feedback(env_hash);
@@ -0,0 +1,16 @@
+-- Migration 011: Add recycle bin for destructive operations
+-- Deleted rows are kept as JSON (grouped by table, in restore order) and deleted
+-- worktrees are moved under the app data trash directory until they expire
+
+CREATE TABLE IF NOT EXISTS trash_items (
+    id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('session', 'profile', 'toolbox_profile', 'script_hook', 'worktree')),
+    label TEXT NOT NULL,
+    payload TEXT NOT NULL DEFAULT '[]',
+    metadata TEXT NOT NULL DEFAULT '{}',
+    trash_path TEXT NULL,
+    deleted_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_trash_items_expires_at ON trash_items(expires_at);
@@ -0,0 +1,9 @@
+-- Migration 012: Bind chat sessions and sessions to an Amp account profile
+-- Spawns resolve URL, CLI and auth from the bound profile rather than whichever
+-- profile is active, so sessions for different accounts can run side by side
+
+ALTER TABLE chat_sessions ADD COLUMN amp_profile_id TEXT NULL;
+ALTER TABLE sessions ADD COLUMN amp_profile_id TEXT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_chat_sessions_amp_profile_id ON chat_sessions(amp_profile_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_amp_profile_id ON sessions(amp_profile_id);
@@ -0,0 +1,8 @@
+-- Migration 013: Move large message payloads out of the database
+-- Rows with content_ref set keep an empty content column; the payload lives in a
+-- gzip blob file named after content_ref in the message_blobs directory
+
+ALTER TABLE messages ADD COLUMN content_ref TEXT NULL;
+ALTER TABLE messages ADD COLUMN content_size INTEGER NULL;
+
+CREATE INDEX IF NOT EXISTS idx_messages_content_ref ON messages(content_ref);
@@ -0,0 +1,12 @@
+-- Migration 014: Compressed message history for archived threads
+-- Once a thread is archived its messages are moved here as one gzip-compressed
+-- JSON array and restored to messages when the thread is unarchived
+
+CREATE TABLE IF NOT EXISTS thread_archives (
+    thread_id TEXT PRIMARY KEY NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+    codec TEXT NOT NULL DEFAULT 'gzip',
+    message_count INTEGER NOT NULL,
+    raw_size INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    compressed_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
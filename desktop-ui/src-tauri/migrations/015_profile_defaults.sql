@@ -0,0 +1,8 @@
+-- Migration 015: Per-profile defaults for new sessions and threads
+-- A session created under an Amp profile takes its toolbox profile, and its
+-- threads their agent mode and model, from the profile unless the request sets them
+
+ALTER TABLE profiles ADD COLUMN default_agent_mode TEXT NULL;
+ALTER TABLE profiles ADD COLUMN default_model TEXT NULL;
+ALTER TABLE profiles ADD COLUMN default_toolbox_profile_id INTEGER NULL REFERENCES toolbox_profiles(id) ON DELETE SET NULL;
+ALTER TABLE threads ADD COLUMN model TEXT NULL;
@@ -0,0 +1,7 @@
+-- Migration 016: Short human-friendly session codes
+-- Shown in the UI and used for worktree directories and branch names; existing
+-- sessions are given a code at startup
+
+ALTER TABLE sessions ADD COLUMN short_code TEXT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sessions_short_code ON sessions(short_code);
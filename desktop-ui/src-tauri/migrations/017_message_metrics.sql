@@ -0,0 +1,13 @@
+-- Migration 017: Per-message latency and token counts
+-- turn_id names the prompt message that started the turn a row belongs to;
+-- generation_ms and cost_usd are only set on prompt rows, once the turn's result arrives
+
+ALTER TABLE messages ADD COLUMN turn_id TEXT NULL;
+ALTER TABLE messages ADD COLUMN latency_ms INTEGER NULL;
+ALTER TABLE messages ADD COLUMN input_tokens INTEGER NULL;
+ALTER TABLE messages ADD COLUMN output_tokens INTEGER NULL;
+ALTER TABLE messages ADD COLUMN cache_read_tokens INTEGER NULL;
+ALTER TABLE messages ADD COLUMN generation_ms INTEGER NULL;
+ALTER TABLE messages ADD COLUMN cost_usd REAL NULL;
+
+CREATE INDEX IF NOT EXISTS idx_messages_turn_id ON messages(turn_id);
@@ -0,0 +1,24 @@
+-- Migration 018: Remote sync bookkeeping
+-- sync_sessions holds each synced session's vector clock (JSON object of device
+-- ID to counter); deleted marks sessions whose deletion has been pushed.
+-- sync_rows remembers every synced row as it was after the last sync, so local
+-- edits since then can be told apart from rows that came from other devices.
+
+CREATE TABLE IF NOT EXISTS sync_sessions (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    clock TEXT NOT NULL DEFAULT '{}',
+    deleted INTEGER NOT NULL DEFAULT 0,
+    synced_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE TABLE IF NOT EXISTS sync_rows (
+    kind TEXT NOT NULL CHECK (kind IN ('session', 'thread', 'message')),
+    id TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    stamp_ms INTEGER NOT NULL,
+    device TEXT NOT NULL,
+    PRIMARY KEY (kind, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_rows_session_id ON sync_rows(session_id);
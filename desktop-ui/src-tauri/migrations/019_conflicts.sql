@@ -0,0 +1,24 @@
+-- Migration 019: Conflicts awaiting a decision
+-- Sync and session merges resolve conflicts automatically so they never block,
+-- and record each one here with every option (options is a JSON array of
+-- {choice, value}) and the choice that was applied. A conflict stays open until
+-- the user confirms or changes that choice; resolution is the final choice, or
+-- 'superseded' when a newer conflict replaced it.
+
+CREATE TABLE IF NOT EXISTS conflicts (
+    id TEXT PRIMARY KEY NOT NULL,
+    source TEXT NOT NULL CHECK (source IN ('sync', 'merge')),
+    kind TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    item_table TEXT NULL,
+    item_id TEXT NULL,
+    summary TEXT NOT NULL,
+    options TEXT NOT NULL,
+    applied TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    resolved_at TEXT NULL,
+    resolution TEXT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_conflicts_open ON conflicts(resolved_at, created_at);
+CREATE INDEX IF NOT EXISTS idx_conflicts_session_id ON conflicts(session_id);
@@ -0,0 +1,18 @@
+-- Migration 020: Stream lines the CLI sent that failed validation
+-- byte_offset is where the line starts in the process's stdout; line holds at
+-- most the first 64 KiB, line_bytes the full length.
+
+CREATE TABLE IF NOT EXISTS quarantined_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT NOT NULL,
+    stream TEXT NOT NULL CHECK (stream IN ('chat', 'thread')),
+    byte_offset INTEGER NOT NULL,
+    cli_version TEXT NULL,
+    event_type TEXT NULL,
+    reason TEXT NOT NULL,
+    line TEXT NOT NULL,
+    line_bytes INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_quarantined_events_session_id ON quarantined_events(session_id);
@@ -0,0 +1,8 @@
+-- Migration 021: Priority labels for sessions and threads
+-- Sessions without a row are 'normal'.
+
+CREATE TABLE IF NOT EXISTS session_priorities (
+    session_id TEXT PRIMARY KEY,
+    priority TEXT NOT NULL CHECK (priority IN ('low', 'normal', 'high')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
@@ -0,0 +1,14 @@
+-- Migration 022: Where each session's worktree was created
+-- `worktrees` (005) is tied to chat_sessions, so worktrees of thread sessions are
+-- recorded here. Paths are absolute; relocate_repository rewrites them when the
+-- repository is moved.
+
+CREATE TABLE IF NOT EXISTS session_worktrees (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    repo_root TEXT NOT NULL,
+    worktree_path TEXT NOT NULL,
+    branch_name TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_worktrees_repo_root ON session_worktrees(repo_root);
@@ -0,0 +1,17 @@
+-- Migration 023: Agent processes started for chat sessions and threads
+-- A row stays 'running' until the process's output ends. Rows still 'running'
+-- at launch belong to a previous run that did not shut down cleanly; the startup
+-- scan reaps their process if it survived and corrects the status.
+
+CREATE TABLE IF NOT EXISTS session_processes (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('chat', 'thread')),
+    pid INTEGER NULL,
+    program TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'idle', 'error')),
+    error TEXT NULL,
+    started_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_processes_status ON session_processes(status);
@@ -0,0 +1,12 @@
+-- Migration 024: Secrets pulled from external managers at spawn time
+-- Each row names an environment variable and where its value comes from; the
+-- value itself is never stored.
+
+CREATE TABLE IF NOT EXISTS profile_secrets (
+    profile_id TEXT NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+    env_key TEXT NOT NULL,
+    provider TEXT NOT NULL CHECK (provider IN ('1password', 'pass', 'dotenv')),
+    reference TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    PRIMARY KEY (profile_id, env_key)
+);
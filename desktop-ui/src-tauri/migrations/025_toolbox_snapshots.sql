@@ -0,0 +1,19 @@
+-- Migration 025: Content digests of toolboxes each time a thread uses them
+-- A digest's file list is stored once; every use records a snapshot pointing at
+-- it. No foreign key to toolbox_profiles so history outlives deleted profiles.
+
+CREATE TABLE IF NOT EXISTS toolbox_digests (
+    digest TEXT PRIMARY KEY,
+    tools TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE TABLE IF NOT EXISTS toolbox_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    toolbox_profile_id INTEGER NULL,
+    thread_id TEXT NULL,
+    digest TEXT NOT NULL REFERENCES toolbox_digests(digest),
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_toolbox_snapshots_profile ON toolbox_snapshots(toolbox_profile_id, created_at);
@@ -0,0 +1,20 @@
+-- Migration 026: Usage quotas per Amp profile
+-- A NULL limit is unlimited. quota_usage records every session and batch case
+-- started under a profile, so deleting sessions does not give quota back.
+
+CREATE TABLE IF NOT EXISTS profile_quotas (
+    profile_id TEXT PRIMARY KEY NOT NULL,
+    sessions_per_day INTEGER NULL,
+    tokens_per_week INTEGER NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE TABLE IF NOT EXISTS quota_usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    profile_id TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('session', 'batch_case')),
+    amount INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_quota_usage_profile ON quota_usage(profile_id, created_at);
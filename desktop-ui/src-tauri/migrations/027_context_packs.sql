@@ -0,0 +1,24 @@
+-- Migration 027: Context packs
+-- A pack is a named set of files, globs and URLs injected into a thread's first
+-- prompt. Each injection records the definition used and exactly what went in.
+
+CREATE TABLE IF NOT EXISTS context_packs (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    definition TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE TABLE IF NOT EXISTS context_injections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id TEXT NOT NULL,
+    pack_id TEXT NULL,
+    definition TEXT NOT NULL,
+    manifest TEXT NOT NULL,
+    message_hash TEXT NOT NULL,
+    estimated_tokens INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_context_injections_thread ON context_injections(thread_id, created_at);
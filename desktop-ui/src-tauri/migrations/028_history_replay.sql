@@ -0,0 +1,24 @@
+-- Migration 028: Thread history replay on re-attach
+-- Each Amp profile chooses whether re-attached threads get their full history
+-- or the latest turns after a summary of the rest. thread_replays records what
+-- every re-attach sent, and keeps summaries for reuse.
+
+CREATE TABLE IF NOT EXISTS profile_replay_settings (
+    profile_id TEXT PRIMARY KEY NOT NULL,
+    strategy TEXT NOT NULL CHECK (strategy IN ('full', 'summarized')),
+    recent_turns INTEGER NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE TABLE IF NOT EXISTS thread_replays (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    replayed_messages INTEGER NOT NULL,
+    summarized_messages INTEGER NOT NULL DEFAULT 0,
+    summary_source TEXT NULL CHECK (summary_source IN ('model', 'heuristic')),
+    summary TEXT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_replays_thread ON thread_replays(thread_id, id);
@@ -0,0 +1,31 @@
+-- Migration 029: Sync rules between session worktrees and staging directories
+-- A rule mirrors the worktree files matching its source globs into a
+-- directory outside the repository, and copies edits made there back.
+-- sync_rule_files holds the content hash of every file as of the last sync,
+-- which is what tells a change on one side apart from a conflict.
+
+CREATE TABLE IF NOT EXISTS sync_rules (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    sources TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    on_checkpoint INTEGER NOT NULL DEFAULT 1,
+    on_completion INTEGER NOT NULL DEFAULT 1,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    status TEXT NOT NULL DEFAULT 'idle' CHECK (status IN ('idle', 'running', 'synced', 'conflicts', 'failed')),
+    last_head TEXT NULL,
+    last_trigger TEXT NULL,
+    last_run_at TEXT NULL,
+    last_result TEXT NULL,
+    last_error TEXT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_rules_session ON sync_rules(session_id);
+
+CREATE TABLE IF NOT EXISTS sync_rule_files (
+    rule_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    PRIMARY KEY (rule_id, path)
+);
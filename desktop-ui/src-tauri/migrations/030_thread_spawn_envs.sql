@@ -0,0 +1,14 @@
+-- Migration 030: Environment of every thread process spawn
+-- Each spawn attempt of a thread's CLI records the composed environment, with
+-- secret values replaced by fingerprints, so two spawns can be compared when a
+-- thread behaves differently after a restart.
+
+CREATE TABLE IF NOT EXISTS thread_spawn_envs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    thread_id TEXT NOT NULL,
+    reason TEXT NOT NULL CHECK (reason IN ('start', 'attach', 'refresh_env')),
+    env TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
+
+CREATE INDEX IF NOT EXISTS idx_thread_spawn_envs_thread ON thread_spawn_envs(thread_id, id);
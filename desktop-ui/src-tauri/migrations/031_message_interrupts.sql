@@ -0,0 +1,5 @@
+-- Migration 031: Interrupted assistant responses
+-- thread_interrupt stops a generation part way through; the last message the
+-- turn produced, or its prompt when it produced none, is stamped here.
+
+ALTER TABLE messages ADD COLUMN interrupted_at TEXT NULL;
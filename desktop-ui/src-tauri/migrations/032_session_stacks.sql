@@ -0,0 +1,15 @@
+-- Migration 032: Languages and frameworks detected for each session
+-- Detected from the repository's manifest files when the session is created.
+-- The detection picks the session's default toolbox profile, suggests context
+-- packs and supplies the verification commands run when the session completes.
+
+CREATE TABLE IF NOT EXISTS session_stacks (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    detection TEXT NOT NULL,
+    toolbox_profile_id INTEGER NULL,
+    suggested_packs TEXT NOT NULL DEFAULT '[]',
+    verify_commands TEXT NOT NULL DEFAULT '[]',
+    last_verification TEXT NULL,
+    verified_at TEXT NULL,
+    detected_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
@@ -0,0 +1,12 @@
+-- Migration 033: Strict environment isolation
+-- A profile or session in strict mode spawns its CLI with only the variables
+-- composed for it plus PATH, HOME and LANG. A session's own setting wins over
+-- its profile's; without either, the existing composition applies.
+
+CREATE TABLE IF NOT EXISTS env_isolation_settings (
+    scope TEXT NOT NULL CHECK (scope IN ('profile', 'session')),
+    scope_id TEXT NOT NULL,
+    strict INTEGER NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z'),
+    PRIMARY KEY (scope, scope_id)
+);
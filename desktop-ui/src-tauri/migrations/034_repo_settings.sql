@@ -0,0 +1,14 @@
+-- Migration 034: Per-repository settings
+-- Keyed by the canonical repository root. protected_paths is a JSON array of
+-- glob patterns; hooks are shell commands run in a session's worktree.
+-- relocate_repository rewrites repo_root when the repository is moved.
+
+CREATE TABLE IF NOT EXISTS repo_settings (
+    repo_root TEXT PRIMARY KEY NOT NULL,
+    base_branch TEXT NULL,
+    protected_paths TEXT NOT NULL DEFAULT '[]',
+    bootstrap_hook TEXT NULL,
+    verify_hook TEXT NULL,
+    branch_template TEXT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc') || 'Z')
+);
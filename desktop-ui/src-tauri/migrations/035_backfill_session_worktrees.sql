@@ -0,0 +1,7 @@
+-- Migration 035: Record the worktrees of chat sessions in session_worktrees
+-- Worktree lookups now go through session_worktrees alone, so worktrees only
+-- known to the legacy `worktrees` table (005) are copied over. Worktrees
+-- recorded nowhere are recorded when first found by directory convention.
+
+INSERT OR IGNORE INTO session_worktrees (session_id, repo_root, worktree_path, branch_name, created_at)
+SELECT session_id, repo_root, worktree_path, branch_name, created_at FROM worktrees;
@@ -0,0 +1,82 @@
+-- Migration 036: Versions of tables the read cache serves
+-- Every write to a cached table bumps its row, so cached reads are dropped
+-- whichever code path wrote.
+
+CREATE TABLE IF NOT EXISTS cache_versions (
+    table_name TEXT PRIMARY KEY NOT NULL,
+    version INTEGER NOT NULL DEFAULT 0
+);
+
+INSERT OR IGNORE INTO cache_versions (table_name) VALUES ('chat_sessions'), ('profiles'), ('toolbox_profiles'), ('toolbox_profile_paths');
+
+CREATE TRIGGER IF NOT EXISTS cache_version_chat_sessions_insert
+AFTER INSERT ON chat_sessions
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'chat_sessions';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_chat_sessions_update
+AFTER UPDATE ON chat_sessions
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'chat_sessions';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_chat_sessions_delete
+AFTER DELETE ON chat_sessions
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'chat_sessions';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_profiles_insert
+AFTER INSERT ON profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_profiles_update
+AFTER UPDATE ON profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_profiles_delete
+AFTER DELETE ON profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profiles_insert
+AFTER INSERT ON toolbox_profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profiles_update
+AFTER UPDATE ON toolbox_profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profiles_delete
+AFTER DELETE ON toolbox_profiles
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profiles';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profile_paths_insert
+AFTER INSERT ON toolbox_profile_paths
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profile_paths';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profile_paths_update
+AFTER UPDATE ON toolbox_profile_paths
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profile_paths';
+END;
+
+CREATE TRIGGER IF NOT EXISTS cache_version_toolbox_profile_paths_delete
+AFTER DELETE ON toolbox_profile_paths
+BEGIN
+  UPDATE cache_versions SET version = version + 1 WHERE table_name = 'toolbox_profile_paths';
+END;
@@ -0,0 +1,11 @@
+-- Migration 037: User copies of benchmark presets
+-- The bundled presets ship with the app and are never stored; a copy keeps
+-- the ID of the preset it was made from in `based_on`.
+
+CREATE TABLE IF NOT EXISTS benchmark_presets (
+    id TEXT PRIMARY KEY,
+    based_on TEXT,
+    definition TEXT NOT NULL, -- JSON BenchmarkPreset
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
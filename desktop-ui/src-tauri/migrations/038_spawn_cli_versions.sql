@@ -0,0 +1,5 @@
+-- Migration 038: CLI version of every thread process spawn
+-- Recorded next to the spawn environment so the version matrix can tell which
+-- CLI a thread ran on. Spawns recorded before this column existed stay NULL.
+
+ALTER TABLE thread_spawn_envs ADD COLUMN cli_version TEXT NULL;
@@ -0,0 +1,268 @@
+//! Application log: structured JSON lines under `<app data>/logs/`
+//!
+//! `install` makes this the process logger. Every record is written to
+//! `app.log` as one JSON object per line; once the file would pass
+//! `max_file_bytes` it becomes `app.1.log`, older files move up one number and
+//! only `max_files` files are kept. The latest entries are also held in memory
+//! for `get_recent_logs` and for crash reports. Level, directory and rotation
+//! come from `AppConfig::logging` once the config is loaded.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+use crate::app_state::{AppState, LoggingConfig};
+
+/// Entries kept in memory; older ones are dropped first
+pub const RECENT_ENTRIES: usize = 1000;
+/// Entries `get_recent_logs` returns when no count is given
+pub const DEFAULT_RECENT: usize = 200;
+const LOG_FILE: &str = "app.log";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    /// Module the record came from
+    pub target: String,
+    pub message: String,
+}
+
+/// The current log file and the rotation limits it is written with
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+impl LogFile {
+    fn open(dir: &Path, max_file_bytes: u64, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir: dir.to_path_buf(), file, size, max_file_bytes, max_files: max_files.max(1) })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("app.{}.log", n))
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_file_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let current = self.dir.join(LOG_FILE);
+        let _ = fs::remove_file(self.rotated(self.max_files - 1));
+        for n in (1..self.max_files - 1).rev() {
+            let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+        }
+        if self.max_files > 1 {
+            fs::rename(&current, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&current)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+struct AppLogger {
+    /// Directory used when the config names none
+    default_dir: Mutex<Option<PathBuf>>,
+    file: Mutex<Option<LogFile>>,
+    recent: Mutex<VecDeque<LogEntry>>,
+}
+
+impl log::Log for AppLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let entry = LogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        crate::crash_reports::remember_log(format!("{} {} {}: {}", entry.timestamp, entry.level, entry.target, entry.message));
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let line = serde_json::to_string(&entry).unwrap_or_default();
+                if let Err(e) = file.write_line(&line) {
+                    // Logging here would recurse
+                    eprintln!("Failed to write {}: {}", file.dir.join(LOG_FILE).display(), e);
+                }
+            }
+        }
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() >= RECENT_ENTRIES {
+                recent.pop_front();
+            }
+            recent.push_back(entry);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+static LOGGER: Lazy<AppLogger> = Lazy::new(|| AppLogger {
+    default_dir: Mutex::new(None),
+    file: Mutex::new(None),
+    recent: Mutex::new(VecDeque::new()),
+});
+
+pub fn log_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("logs"))
+}
+
+/// Start logging with the default settings into the app data directory
+pub fn install(app_handle: &AppHandle) {
+    if log::set_logger(&*LOGGER).is_err() {
+        return;
+    }
+    *LOGGER.default_dir.lock().unwrap() = log_dir(app_handle).ok();
+    configure(&LoggingConfig::default());
+}
+
+/// Apply level, directory and rotation settings; the file is reopened if they changed
+pub fn configure(config: &LoggingConfig) {
+    log::set_max_level(log::LevelFilter::from_str(&config.level).unwrap_or(log::LevelFilter::Info));
+    let dir = match config.dir.clone().or_else(|| LOGGER.default_dir.lock().unwrap().clone()) {
+        Some(dir) => dir,
+        None => return,
+    };
+    let mut file = LOGGER.file.lock().unwrap();
+    let unchanged = file.as_ref().is_some_and(|f| {
+        f.dir == dir && f.max_file_bytes == config.max_file_bytes && f.max_files == config.max_files.max(1)
+    });
+    if unchanged {
+        return;
+    }
+    match LogFile::open(&dir, config.max_file_bytes, config.max_files) {
+        Ok(opened) => *file = Some(opened),
+        Err(e) => eprintln!("Failed to open log file in {}: {}", dir.display(), e),
+    }
+}
+
+/// The last `limit` entries at `min_level` or more severe, oldest first
+fn select(entries: &VecDeque<LogEntry>, limit: usize, min_level: log::Level) -> Vec<LogEntry> {
+    let mut selected: Vec<LogEntry> = entries
+        .iter()
+        .rev()
+        .filter(|entry| log::Level::from_str(&entry.level).is_ok_and(|level| level <= min_level))
+        .take(limit)
+        .cloned()
+        .collect();
+    selected.reverse();
+    selected
+}
+
+#[tauri::command]
+pub async fn get_recent_logs(limit: Option<usize>, level: Option<String>) -> Result<Vec<LogEntry>, String> {
+    let min_level = match level {
+        Some(level) => log::Level::from_str(&level).map_err(|_| format!("Unknown log level: {}", level))?,
+        None => log::Level::Trace,
+    };
+    let recent = LOGGER.recent.lock().unwrap();
+    Ok(select(&recent, limit.unwrap_or(DEFAULT_RECENT), min_level))
+}
+
+#[tauri::command]
+pub async fn get_logging_config(app_state: State<'_, AppState>) -> Result<LoggingConfig, String> {
+    Ok(app_state.lock().unwrap().logging.clone())
+}
+
+#[tauri::command]
+pub async fn set_logging_config(config: LoggingConfig, app_state: State<'_, AppState>) -> Result<(), String> {
+    if log::LevelFilter::from_str(&config.level).is_err() {
+        return Err(format!("Unknown log level: {}", config.level));
+    }
+    if config.max_file_bytes == 0 {
+        return Err("Log files must be allowed at least one byte".to_string());
+    }
+    configure(&config);
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.logging = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: &str, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            level: level.to_string(),
+            target: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = LogFile::open(dir.path(), 20, 3).unwrap();
+        for n in 0..5 {
+            file.write_line(&format!("line {:011}", n)).unwrap();
+        }
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("app.log"), "line 00000000004\n");
+        assert_eq!(read("app.1.log"), "line 00000000003\n");
+        assert_eq!(read("app.2.log"), "line 00000000002\n");
+        assert!(!dir.path().join("app.3.log").exists());
+
+        // Reopening appends to the current file
+        let mut file = LogFile::open(dir.path(), 100, 3).unwrap();
+        file.write_line("more").unwrap();
+        assert_eq!(read("app.log"), "line 00000000004\nmore\n");
+    }
+
+    #[test]
+    fn test_select_filters_by_level_and_limit() {
+        let entries: VecDeque<LogEntry> = [
+            entry("INFO", "a"),
+            entry("ERROR", "b"),
+            entry("DEBUG", "c"),
+            entry("WARN", "d"),
+            entry("INFO", "e"),
+        ]
+        .into_iter()
+        .collect();
+        let messages = |selected: Vec<LogEntry>| selected.into_iter().map(|e| e.message).collect::<Vec<_>>();
+        assert_eq!(messages(select(&entries, 10, log::Level::Warn)), ["b", "d"]);
+        assert_eq!(messages(select(&entries, 2, log::Level::Info)), ["d", "e"]);
+        assert_eq!(messages(select(&entries, 10, log::Level::Trace)).len(), 5);
+    }
+}
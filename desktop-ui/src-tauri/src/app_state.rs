@@ -2,7 +2,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::fs;
+use unified_core::{GitIdentityConfig, ProcessLimits};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RuntimeConfig {
@@ -23,6 +25,222 @@ impl Default for RuntimeConfig {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StaleSessionConfig {
+    /// Seconds without stream output or user input before a session is flagged stale
+    pub idle_threshold_secs: u64,
+    /// Stop stale thread processes; they are re-spawned from history on `thread_attach`
+    pub auto_detach: bool,
+    /// Pause stale processes that are not detached; they continue on the next message
+    pub auto_suspend: bool,
+}
+
+impl Default for StaleSessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_threshold_secs: 30 * 60,
+            auto_detach: false,
+            auto_suspend: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionCodeConfig {
+    /// Base32 digits of the creation time (seconds), so codes sort roughly by age
+    pub timestamp_chars: usize,
+    /// Random base32 digits after the timestamp; more are added on repeated collisions
+    pub random_chars: usize,
+}
+
+impl Default for SessionCodeConfig {
+    fn default() -> Self {
+        Self {
+            timestamp_chars: 6,
+            random_chars: 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionGcConfig {
+    /// Collect on a schedule; `run_session_gc` works either way
+    pub scheduled: bool,
+    pub interval_secs: u64,
+    /// Days a deleted or archived session's worktree and branch are kept (0 = next run)
+    pub keep_days: u32,
+    /// Keep branches with commits that no other branch or remote contains
+    pub keep_unmerged: bool,
+    /// Repositories to collect in besides the one containing the working directory
+    pub repositories: Vec<String>,
+}
+
+impl Default for SessionGcConfig {
+    fn default() -> Self {
+        Self {
+            scheduled: true,
+            interval_secs: 6 * 60 * 60,
+            keep_days: 7,
+            keep_unmerged: true,
+            repositories: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Back up nightly; `run_backup` works either way
+    pub scheduled: bool,
+    /// Local hour (0-23) after which the nightly backup runs
+    pub hour: u32,
+    /// Where backups are written (None = `backups` in the app data directory)
+    pub directory: Option<String>,
+    /// Backups kept; older ones are deleted after each new backup
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            scheduled: true,
+            hour: 3,
+            directory: None,
+            keep: 7,
+        }
+    }
+}
+
+/// Where synced sessions are stored. Secrets (the S3 secret key or WebDAV
+/// password) are kept in the OS keychain, not here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncBackend {
+    /// S3-compatible bucket, addressed path-style as `<endpoint>/<bucket>/<prefix>`
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        #[serde(default)]
+        prefix: String,
+        access_key_id: String,
+    },
+    Webdav {
+        url: String,
+        #[serde(default)]
+        username: String,
+    },
+    /// A directory, e.g. one kept in step by a file sync client
+    Folder { path: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    pub backend: Option<SyncBackend>,
+    /// Base64 X25519 public key everything pushed is sealed to; its secret
+    /// half is kept in the keychain (see `remote_sync::create_sync_key`)
+    pub encryption_key: Option<String>,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 15 * 60,
+            backend: None,
+            encryption_key: None,
+        }
+    }
+}
+
+/// Localhost WebSocket server for external tools; its token is kept in the keychain
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47615,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Combined size of in-memory caches above which the least recently used are dropped
+    pub ceiling_mb: u64,
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            ceiling_mb: 256,
+            sweep_interval_secs: 60,
+        }
+    }
+}
+
+/// Limits on chat and thread processes, all off by default. Batch cases use
+/// their session's `ProcessLimits` instead.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessLimitsConfig {
+    /// Resident memory a chat or thread process may use, checked on Linux only (0 = no limit)
+    pub max_memory_mb: u64,
+    /// Open files a chat or thread process may have (0 = no limit)
+    pub max_open_files: usize,
+    /// How long a chat or thread process may run, not counting time suspended, before it is stopped (0 = no limit)
+    pub max_execution_secs: u64,
+}
+
+impl ProcessLimitsConfig {
+    pub fn limits(&self) -> ProcessLimits {
+        ProcessLimits {
+            max_memory_mb: self.max_memory_mb,
+            max_open_files: self.max_open_files,
+            max_execution_time: Duration::from_secs(self.max_execution_secs),
+            ..ProcessLimits::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Least severe level written: error, warn, info, debug or trace
+    pub level: String,
+    /// Where log files go (None = `<app data>/logs`)
+    pub dir: Option<PathBuf>,
+    /// Size at which `app.log` is rotated
+    pub max_file_bytes: u64,
+    /// Log files kept, including the current one
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            dir: None,
+            max_file_bytes: 5 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub amp_env: HashMap<String, String>,
@@ -33,6 +251,51 @@ pub struct AppConfig {
     pub runtime: RuntimeConfig,
     // Active toolbox profile ID for persistence
     pub active_toolbox_profile_id: Option<i64>,
+    // Directories session working directories must live under (empty = home and launch dir)
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    #[serde(default)]
+    pub stale_sessions: StaleSessionConfig,
+    // Author identity for agent commits in session worktrees (None = user's own)
+    #[serde(default = "default_git_identity")]
+    pub git_identity: Option<GitIdentityConfig>,
+    // Initialize submodules recursively in new session worktrees
+    #[serde(default = "default_true")]
+    pub init_submodules: bool,
+    // Runtime feature flag overrides; flags not listed use their default
+    #[serde(default)]
+    pub feature_flags: HashMap<String, bool>,
+    // Shape of the short codes shown for sessions and used in worktree names
+    #[serde(default)]
+    pub session_codes: SessionCodeConfig,
+    #[serde(default)]
+    pub session_gc: SessionGcConfig,
+    #[serde(default)]
+    pub backups: BackupConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+    // Base64 X25519 public keys webhook bodies are sealed to, by URL prefix
+    #[serde(default)]
+    pub webhook_keys: HashMap<String, String>,
+    // Language of backend-generated strings (None = follow the OS)
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub process_limits: ProcessLimitsConfig,
+}
+
+fn default_git_identity() -> Option<GitIdentityConfig> {
+    Some(GitIdentityConfig::default())
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Default for AppConfig {
@@ -47,6 +310,21 @@ impl Default for AppConfig {
             local_server_url: None,
             runtime: RuntimeConfig::default(),
             active_toolbox_profile_id: None,
+            allowed_roots: Vec::new(),
+            stale_sessions: StaleSessionConfig::default(),
+            git_identity: default_git_identity(),
+            init_submodules: true,
+            feature_flags: HashMap::new(),
+            session_codes: SessionCodeConfig::default(),
+            session_gc: SessionGcConfig::default(),
+            backups: BackupConfig::default(),
+            sync: SyncConfig::default(),
+            memory: MemoryConfig::default(),
+            bridge: BridgeConfig::default(),
+            webhook_keys: HashMap::new(),
+            locale: None,
+            logging: LoggingConfig::default(),
+            process_limits: ProcessLimitsConfig::default(),
         }
     }
 }
@@ -68,7 +346,7 @@ impl AppConfig {
                     if let Some(p) = self.custom_cli_path.clone() {
                         merged_env.insert("AMP_CLI_PATH".into(), p);
                     } else {
-                        merged_env.insert("AMP_CLI_PATH".into(), "/Users/sjarmak/amp/cli/dist/main.js".into());
+                        merged_env.insert("AMP_CLI_PATH".into(), default_dev_cli_path());
                     }
                 }
                 if !merged_env.contains_key("AMP_URL") {
@@ -93,18 +371,16 @@ impl AppConfig {
         let mac = dirs::home_dir().map(|mut p| { p.push("Library"); p.push("Application Support"); p.push("ampsm"); p.push("config.json"); p });
         let xdg = dirs::home_dir().map(|mut p| { p.push(".config"); p.push("ampsm"); p.push("config.json"); p });
         let candidates = [Some(primary.clone()), mac, xdg];
-        let _ = std::fs::create_dir_all("/Users/sjarmak/amp-orchestra/logs");
         for cand in candidates.iter().flatten() {
-            let _ = std::fs::OpenOptions::new().create(true).append(true).open("/Users/sjarmak/amp-orchestra/logs/startup-env.log").and_then(|mut f| std::io::Write::write_all(&mut f, format!("try load config: {:?}\n", cand).as_bytes()));
+            log::debug!("Trying config {:?}", cand);
             if let Ok(content) = fs::read_to_string(cand).await {
-                let _ = std::fs::OpenOptions::new().create(true).append(true).open("/Users/sjarmak/amp-orchestra/logs/startup-env.log").and_then(|mut f| std::io::Write::write_all(&mut f, format!("config content: {}\n", content).as_bytes()));
                 match serde_json::from_str::<AppConfig>(&content) {
                     Ok(config) => {
-                        let _ = std::fs::OpenOptions::new().create(true).append(true).open("/Users/sjarmak/amp-orchestra/logs/startup-env.log").and_then(|mut f| std::io::Write::write_all(&mut f, format!("parsed config: mode={:?} cli_path={:?}\n", config.connection_mode, config.custom_cli_path).as_bytes()));
+                        log::info!("Loaded config {:?}: mode={:?} cli_path={:?}", cand, config.connection_mode, config.custom_cli_path);
                         return config;
                     }
                     Err(e) => {
-                        let _ = std::fs::OpenOptions::new().create(true).append(true).open("/Users/sjarmak/amp-orchestra/logs/startup-env.log").and_then(|mut f| std::io::Write::write_all(&mut f, format!("parse error: {}\n", e).as_bytes()));
+                        log::warn!("Failed to parse config {:?}: {}", cand, e);
                     }
                 }
             }
@@ -199,6 +475,12 @@ pub async fn get_runtime_config(app_state: tauri::State<'_, AppState>) -> Result
 
 pub type AppState = Arc<Mutex<AppConfig>>;
 
+/// CLI used in local-cli mode when no path is configured: a development checkout in `~/amp`
+pub fn default_dev_cli_path() -> String {
+    let home = crate::platform::home_dir().unwrap_or_default();
+    crate::platform::dev_cli_path(&home).to_string_lossy().into_owned()
+}
+
 pub fn init_app_state() -> AppState {
     Arc::new(Mutex::new(AppConfig::default()))
 }
@@ -0,0 +1,282 @@
+//! Compressed storage for the message history of archived threads
+//!
+//! Archived threads are rarely read again but keep every stream event they ever
+//! produced. A background task moves their messages into `thread_archives` as a
+//! single gzip-compressed JSON array; repetitive stream JSON typically shrinks by
+//! more than ten times. Offloaded payloads are folded into the archive, so their
+//! blob files can be swept by message compaction. `get_thread_history` reads an
+//! archive in place, and unarchiving a thread restores its rows.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::io::{Read, Write};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::app_state::AppState;
+use crate::feature_flags::{is_enabled, ARCHIVE_COMPRESSION};
+use crate::message_content::{resolve_content, store_content};
+use crate::message_metrics::{MessageMetrics, METRIC_COLUMNS};
+use crate::profile_auth::ProfileManager;
+
+const CODEC: &str = "gzip";
+const SWEEP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Threads compressed per sweep, so a large backlog does not hold the database
+const SWEEP_BATCH: i64 = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ArchivedMessage {
+    pub id: String,
+    pub role: String,
+    pub content: String,
+    pub created_at: String,
+    /// Absent from archives written before message metrics existed
+    #[serde(default)]
+    #[sqlx(flatten)]
+    pub metrics: MessageMetrics,
+}
+
+/// A `messages` row, whose content may still be offloaded
+#[derive(sqlx::FromRow)]
+struct MessageRow {
+    #[sqlx(flatten)]
+    message: ArchivedMessage,
+    content_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveSweep {
+    pub threads_compressed: u64,
+    pub messages_compressed: u64,
+    pub raw_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+fn encode(messages: &[ArchivedMessage]) -> Result<(Vec<u8>, usize), String> {
+    let raw = serde_json::to_vec(messages).map_err(|e| e.to_string())?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&raw).map_err(|e| e.to_string())?;
+    Ok((encoder.finish().map_err(|e| e.to_string())?, raw.len()))
+}
+
+fn decode(codec: &str, data: &[u8]) -> Result<Vec<ArchivedMessage>, String> {
+    if codec != CODEC {
+        return Err(format!("Unsupported thread archive codec '{}'", codec));
+    }
+    let mut raw = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut raw).map_err(|e| format!("Corrupt thread archive: {}", e))?;
+    serde_json::from_slice(&raw).map_err(|e| format!("Corrupt thread archive: {}", e))
+}
+
+/// Messages held in a thread's archive, oldest first, or `None` when it has none
+pub async fn archived_messages(db: &SqlitePool, thread_id: &str) -> Result<Option<Vec<ArchivedMessage>>, String> {
+    let row = sqlx::query_as::<_, (String, Vec<u8>)>("SELECT codec, data FROM thread_archives WHERE thread_id = ?")
+        .bind(thread_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read thread archive: {}", e))?;
+    row.map(|(codec, data)| decode(&codec, &data)).transpose()
+}
+
+/// Move a thread's messages into its archive, appending to an existing one.
+/// Returns the (raw, compressed) size of the archive, or `None` when the thread
+/// had no messages to move.
+pub async fn compress_thread(db: &SqlitePool, thread_id: &str) -> Result<Option<(usize, usize)>, String> {
+    let rows = sqlx::query_as::<_, MessageRow>(&format!(
+        "SELECT id, role, content, created_at, {}, content_ref FROM messages
+         WHERE thread_id = ? ORDER BY created_at ASC, rowid ASC",
+        METRIC_COLUMNS
+    ))
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read messages: {}", e))?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut messages = archived_messages(db, thread_id).await?.unwrap_or_default();
+    messages.extend(rows.into_iter().map(|MessageRow { message, content_ref }| ArchivedMessage {
+        content: resolve_content(message.content, content_ref),
+        ..message
+    }));
+    let (data, raw_size) = encode(&messages)?;
+
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query(
+        "INSERT OR REPLACE INTO thread_archives (thread_id, codec, message_count, raw_size, data) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(thread_id)
+    .bind(CODEC)
+    .bind(messages.len() as i64)
+    .bind(raw_size as i64)
+    .bind(&data)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to write thread archive: {}", e))?;
+    sqlx::query("DELETE FROM messages WHERE thread_id = ?")
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to remove archived messages: {}", e))?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(Some((raw_size, data.len())))
+}
+
+/// Put an archived thread's messages back into `messages` and drop the archive
+pub async fn restore_thread(db: &SqlitePool, thread_id: &str) -> Result<usize, String> {
+    let Some(messages) = archived_messages(db, thread_id).await? else {
+        return Ok(0);
+    };
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    for message in &messages {
+        let stored = store_content(&message.content)?;
+        let metrics = &message.metrics;
+        sqlx::query(&format!(
+            "INSERT OR IGNORE INTO messages (id, thread_id, role, content, content_ref, content_size, created_at, {})
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            METRIC_COLUMNS
+        ))
+        .bind(&message.id)
+        .bind(thread_id)
+        .bind(&message.role)
+        .bind(&stored.content)
+        .bind(&stored.content_ref)
+        .bind(stored.content_size)
+        .bind(&message.created_at)
+        .bind(&metrics.turn_id)
+        .bind(metrics.latency_ms)
+        .bind(metrics.input_tokens)
+        .bind(metrics.output_tokens)
+        .bind(metrics.cache_read_tokens)
+        .bind(metrics.generation_ms)
+        .bind(metrics.cost_usd)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to restore message {}: {}", message.id, e))?;
+    }
+    sqlx::query("DELETE FROM thread_archives WHERE thread_id = ?")
+        .bind(thread_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to remove thread archive: {}", e))?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(messages.len())
+}
+
+/// Compress archived threads that still have rows in `messages`
+pub async fn compress_archived_threads(db: &SqlitePool) -> Result<ArchiveSweep, String> {
+    let thread_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT t.id FROM threads t
+         WHERE t.archived_at IS NOT NULL AND EXISTS (SELECT 1 FROM messages m WHERE m.thread_id = t.id)
+         LIMIT ?",
+    )
+    .bind(SWEEP_BATCH)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to list archived threads: {}", e))?;
+
+    let mut sweep = ArchiveSweep::default();
+    for thread_id in thread_ids {
+        let before: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE thread_id = ?")
+            .bind(&thread_id)
+            .fetch_one(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        match compress_thread(db, &thread_id).await {
+            Ok(Some((raw, compressed))) => {
+                sweep.threads_compressed += 1;
+                sweep.messages_compressed += before as u64;
+                sweep.raw_bytes += raw as u64;
+                sweep.compressed_bytes += compressed as u64;
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to compress archived thread {}: {}", thread_id, e),
+        }
+    }
+    Ok(sweep)
+}
+
+/// Compress archived threads in the background for the lifetime of the app
+pub fn spawn_compressor(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let enabled = app_handle
+                .try_state::<AppState>()
+                .is_some_and(|state| is_enabled(&state.lock().unwrap(), ARCHIVE_COMPRESSION));
+            if !enabled {
+                continue;
+            }
+            let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+                continue;
+            };
+            let db = profile_manager.db_pool.read().await;
+            let Some(db) = db.as_ref() else {
+                continue;
+            };
+            match compress_archived_threads(db).await {
+                Ok(sweep) if sweep.threads_compressed > 0 => log::info!(
+                    "Compressed {} archived threads ({} messages, {} -> {} bytes)",
+                    sweep.threads_compressed,
+                    sweep.messages_compressed,
+                    sweep.raw_bytes,
+                    sweep.compressed_bytes
+                ),
+                Ok(_) => {}
+                Err(e) => log::warn!("Archived thread compression failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_archived_thread_is_compressed_and_restored() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Refactor')").execute(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO threads (id, session_id, context, agent_mode, archived_at)
+             VALUES ('t1', 's1', 'production', NULL, '2024-01-01T00:00:00Z'), ('t2', 's1', 'production', NULL, NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for i in 0..200 {
+            let content = format!(r#"{{"type":"assistant","message":{{"content":[{{"type":"text","text":"step {}"}}]}}}}"#, i);
+            for thread in ["t1", "t2"] {
+                sqlx::query("INSERT INTO messages (id, thread_id, role, content, created_at) VALUES (?, ?, 'assistant', ?, ?)")
+                    .bind(format!("{}-{}", thread, i))
+                    .bind(thread)
+                    .bind(&content)
+                    .bind(format!("2024-01-01T00:{:02}:{:02}Z", i / 60, i % 60))
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let sweep = compress_archived_threads(&pool).await.unwrap();
+        assert_eq!((sweep.threads_compressed, sweep.messages_compressed), (1, 200));
+        assert!(sweep.compressed_bytes * 10 < sweep.raw_bytes);
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT DISTINCT thread_id FROM messages").fetch_all(&pool).await.unwrap();
+        assert_eq!(remaining, vec!["t2".to_string()]);
+
+        let archived = archived_messages(&pool, "t1").await.unwrap().unwrap();
+        assert_eq!(archived.len(), 200);
+        assert_eq!(archived[199].id, "t1-199");
+
+        assert_eq!(restore_thread(&pool, "t1").await.unwrap(), 200);
+        assert!(archived_messages(&pool, "t1").await.unwrap().is_none());
+        let restored: String = sqlx::query_scalar("SELECT content FROM messages WHERE id = 't1-7'").fetch_one(&pool).await.unwrap();
+        assert!(restored.contains("step 7"));
+    }
+}
@@ -0,0 +1,522 @@
+//! Scheduled backups and restore
+//!
+//! A backup is a `backup-<UTC time>` directory holding a consistent copy of the
+//! database taken with `VACUUM INTO`, the blobs its offloaded messages point to,
+//! JSONL dumps of sessions, threads and messages (with offloaded content
+//! inlined, so the dumps stand on their own) and a `manifest.json`. Backups are
+//! written under a `.partial` name and renamed once complete, so only finished
+//! backups are ever listed or pruned. The scheduler takes one backup a night
+//! after `BackupConfig::hour` and keeps the newest `keep`.
+//!
+//! `restore_from_backup` accepts a backup directory or a bare database file. It
+//! checks integrity and refuses databases from a newer schema version, backs up
+//! the current database, swaps the backup in and reopens it, which re-runs the
+//! migrations and brings an older backup up to date.
+
+use chrono::{DateTime, Duration as ChronoDuration, Local, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::{AppState, BackupConfig};
+use crate::message_content::{blob_dir, blob_file_name, resolve_content, BLOB_DIR_NAME};
+use crate::profile_auth::ProfileManager;
+use crate::schema::SCHEMA_VERSION;
+
+const BACKUP_PREFIX: &str = "backup-";
+const PARTIAL_SUFFIX: &str = ".partial";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+const DB_FILE: &str = "app.db";
+const MANIFEST_FILE: &str = "manifest.json";
+/// Tables dumped as JSONL, each to `<table>.jsonl`
+const DUMPED_TABLES: [&str; 3] = ["sessions", "threads", "messages"];
+const SCHEDULE_POLL: Duration = Duration::from_secs(60);
+/// Wait before retrying a scheduled backup that failed
+const RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Serializes backups so a manual run never races the scheduler
+static BACKUP_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub schema_version: i64,
+    pub app_version: String,
+    pub created_at: String,
+    /// Rows dumped per table
+    pub rows: BTreeMap<String, usize>,
+    pub blobs: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    #[serde(flatten)]
+    pub manifest: BackupManifest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub restored_from: String,
+    pub schema_version: i64,
+    /// Older backups are migrated when the database is reopened
+    pub migrated: bool,
+    /// Backup of the database that was replaced
+    pub previous: Option<BackupInfo>,
+}
+
+/// Directory backups are written to under `config`
+pub fn backup_dir(app_handle: &AppHandle, config: &BackupConfig) -> Result<PathBuf, String> {
+    match &config.directory {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => app_handle
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("backups"))
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e)),
+    }
+}
+
+/// Write `table` as one JSON object per row; returns the row count and the
+/// blobs referenced by offloaded message content
+async fn dump_table(db: &SqlitePool, table: &str, path: &Path) -> Result<(usize, HashSet<String>), String> {
+    let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read columns of {}: {}", table, e))?;
+    let fields: Vec<String> = columns.iter().map(|column| format!("'{0}', \"{0}\"", column)).collect();
+    let rows: Vec<String> = sqlx::query_scalar(&format!("SELECT json_object({}) FROM {} ORDER BY rowid", fields.join(", "), table))
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = std::io::BufWriter::new(file);
+    let mut blobs = HashSet::new();
+    for row in &rows {
+        let mut value: serde_json::Value = serde_json::from_str(row).map_err(|e| e.to_string())?;
+        if let Some(content_ref) = value.get("content_ref").and_then(|v| v.as_str()).map(str::to_string) {
+            let content = value["content"].as_str().unwrap_or_default().to_string();
+            value["content"] = serde_json::Value::String(resolve_content(content, Some(content_ref.clone())));
+            blobs.insert(content_ref);
+        }
+        writeln!(out, "{}", value).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    out.flush().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok((rows.len(), blobs))
+}
+
+async fn write_backup(db: &SqlitePool, dir: &Path, created_at: DateTime<Utc>) -> Result<BackupManifest, String> {
+    let db_file = dir.join(DB_FILE);
+    sqlx::query("VACUUM INTO ?")
+        .bind(db_file.to_string_lossy().to_string())
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to copy database: {}", e))?;
+    let schema_version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(db)
+        .await
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+    let mut rows = BTreeMap::new();
+    let mut referenced = HashSet::new();
+    for table in DUMPED_TABLES {
+        let (count, blobs) = dump_table(db, table, &dir.join(format!("{}.jsonl", table))).await?;
+        rows.insert(table.to_string(), count);
+        referenced.extend(blobs);
+    }
+
+    // The database copy only holds references to offloaded content
+    let mut blobs = 0;
+    if let (Some(source), false) = (blob_dir(), referenced.is_empty()) {
+        let target = dir.join(BLOB_DIR_NAME);
+        std::fs::create_dir_all(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+        for content_ref in &referenced {
+            let name = blob_file_name(content_ref);
+            match std::fs::copy(source.join(&name), target.join(&name)) {
+                Ok(_) => blobs += 1,
+                Err(e) => log::warn!("Backup is missing message blob {}: {}", name, e),
+            }
+        }
+    }
+
+    let manifest = BackupManifest {
+        schema_version,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: created_at.to_rfc3339(),
+        rows,
+        blobs,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(MANIFEST_FILE), json).map_err(|e| format!("Failed to write manifest: {}", e))?;
+    Ok(manifest)
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn read_backup(path: &Path) -> Option<BackupInfo> {
+    let manifest = std::fs::read(path.join(MANIFEST_FILE)).ok()?;
+    Some(BackupInfo {
+        path: path.to_string_lossy().to_string(),
+        size_bytes: dir_size(path),
+        manifest: serde_json::from_slice(&manifest).ok()?,
+    })
+}
+
+/// Finished backups in `dir`, newest first
+pub fn backups_in(dir: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            path.is_dir() && name.starts_with(BACKUP_PREFIX) && !name.ends_with(PARTIAL_SUFFIX)
+        })
+        .collect();
+    // Timestamps in the names sort chronologically
+    paths.sort();
+    paths.iter().rev().filter_map(|path| read_backup(path)).collect()
+}
+
+/// Delete all but the newest `keep` backups, and partial ones left by interrupted runs
+fn prune(dir: &Path, keep: usize) -> Vec<String> {
+    let mut removed = Vec::new();
+    let backups = backups_in(dir);
+    let mut doomed: Vec<PathBuf> = backups.iter().skip(keep.max(1)).map(|b| PathBuf::from(&b.path)).collect();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        doomed.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(PARTIAL_SUFFIX))),
+        );
+    }
+    for path in doomed {
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => removed.push(path.to_string_lossy().to_string()),
+            Err(e) => log::warn!("Failed to remove old backup {}: {}", path.display(), e),
+        }
+    }
+    removed
+}
+
+/// Take a backup into `dir` and prune it to `keep` backups
+pub async fn create_backup(db: &SqlitePool, dir: &Path, keep: usize) -> Result<BackupInfo, String> {
+    let _guard = BACKUP_LOCK.lock().await;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let created_at = Utc::now();
+    let name = format!("{}{}", BACKUP_PREFIX, created_at.format(TIMESTAMP_FORMAT));
+    let path = dir.join(&name);
+    if path.exists() {
+        return Err(format!("A backup named {} already exists", name));
+    }
+    let partial = dir.join(format!("{}{}", name, PARTIAL_SUFFIX));
+    std::fs::create_dir_all(&partial).map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+    if let Err(e) = write_backup(db, &partial, created_at).await {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, &path).map_err(|e| format!("Failed to finish backup {}: {}", path.display(), e))?;
+    prune(dir, keep);
+    read_backup(&path).ok_or_else(|| format!("Failed to read back {}", path.display()))
+}
+
+/// Whether the nightly backup is due: the newest backup predates the latest scheduled time
+pub fn is_due(last: Option<DateTime<Utc>>, now: DateTime<Local>, hour: u32) -> bool {
+    let Some(today) = now
+        .date_naive()
+        .and_hms_opt(hour.min(23), 0, 0)
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+    else {
+        return false;
+    };
+    let scheduled = if now >= today { today } else { today - ChronoDuration::days(1) };
+    last.is_none_or(|last| last < scheduled.with_timezone(&Utc))
+}
+
+fn created_at(backup: &BackupInfo) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&backup.manifest.created_at).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Database file of a backup directory, or the path itself for a bare database
+fn database_file(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(DB_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Check that `path` holds a restorable database; returns its schema version
+pub async fn validate_backup(path: &Path) -> Result<i64, String> {
+    let db_file = database_file(path);
+    if !db_file.is_file() {
+        return Err(format!("No database found at {}", db_file.display()));
+    }
+    let options = SqliteConnectOptions::new().filename(&db_file).read_only(true);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", db_file.display(), e))?;
+    let result = async {
+        let integrity: String = sqlx::query_scalar("PRAGMA integrity_check").fetch_one(&pool).await?;
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&pool).await?;
+        let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'").fetch_all(&pool).await?;
+        Ok::<_, sqlx::Error>((integrity, version, tables))
+    }
+    .await;
+    pool.close().await;
+    let (integrity, version, tables) = result.map_err(|e| format!("{} is not a readable database: {}", db_file.display(), e))?;
+
+    if integrity != "ok" {
+        return Err(format!("{} failed its integrity check: {}", db_file.display(), integrity));
+    }
+    if version == 0 {
+        return Err(format!("{} has no schema version and was not made by this app", db_file.display()));
+    }
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "{} has schema version {}, newer than the {} this version supports",
+            db_file.display(),
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+    if let Some(missing) = DUMPED_TABLES.iter().find(|table| !tables.iter().any(|t| t == *table)) {
+        return Err(format!("{} has no {} table", db_file.display(), missing));
+    }
+    Ok(version)
+}
+
+/// Copy a backup's blobs that the live blob directory no longer has
+fn restore_blobs(path: &Path) -> Result<usize, String> {
+    let (Some(target), true) = (blob_dir(), path.join(BLOB_DIR_NAME).is_dir()) else {
+        return Ok(0);
+    };
+    std::fs::create_dir_all(target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    let entries = std::fs::read_dir(path.join(BLOB_DIR_NAME)).map_err(|e| e.to_string())?;
+    let mut restored = 0;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let destination = target.join(entry.file_name());
+        if !destination.exists() {
+            std::fs::copy(entry.path(), &destination).map_err(|e| format!("Failed to restore {}: {}", destination.display(), e))?;
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+/// Replace `live` with `staged`, dropping the old database's WAL files
+fn swap_database(staged: &Path, live: &Path) -> Result<(), String> {
+    for suffix in ["-wal", "-shm"] {
+        let file = PathBuf::from(format!("{}{}", live.display(), suffix));
+        if let Err(e) = std::fs::remove_file(&file) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Failed to remove {}: {}", file.display(), e));
+            }
+        }
+    }
+    std::fs::rename(staged, live).map_err(|e| format!("Failed to replace {}: {}", live.display(), e))
+}
+
+/// Run the nightly backup for the lifetime of the app
+pub fn spawn_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut retry_at: Option<Instant> = None;
+        loop {
+            tokio::time::sleep(SCHEDULE_POLL).await;
+            let Some(config) = app_handle.try_state::<AppState>().map(|state| state.lock().unwrap().backups.clone()) else {
+                continue;
+            };
+            if !config.scheduled || retry_at.is_some_and(|at| Instant::now() < at) {
+                continue;
+            }
+            let Ok(dir) = backup_dir(&app_handle, &config) else {
+                continue;
+            };
+            let last = backups_in(&dir).first().and_then(created_at);
+            if !is_due(last, Local::now(), config.hour) {
+                continue;
+            }
+            let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+                continue;
+            };
+            let db = profile_manager.db_pool.read().await;
+            let Some(db) = db.as_ref() else {
+                continue;
+            };
+            match create_backup(db, &dir, config.keep).await {
+                Ok(backup) => {
+                    retry_at = None;
+                    log::info!("Backed up to {} ({} bytes)", backup.path, backup.size_bytes);
+                    let _ = app_handle.emit("backup_created", &backup);
+                }
+                Err(e) => {
+                    retry_at = Some(Instant::now() + RETRY_DELAY);
+                    log::warn!("Scheduled backup failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Back up now, whether or not backups are scheduled
+#[tauri::command]
+pub async fn run_backup(
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<BackupInfo, String> {
+    let config = app_state.lock().unwrap().backups.clone();
+    let dir = backup_dir(&profile_manager.app_handle, &config)?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    create_backup(db, &dir, config.keep).await
+}
+
+#[tauri::command]
+pub async fn list_backups(
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<BackupInfo>, String> {
+    let config = app_state.lock().unwrap().backups.clone();
+    Ok(backups_in(&backup_dir(&profile_manager.app_handle, &config)?))
+}
+
+/// Replace the database with a backup directory or database file. The current
+/// database is backed up first.
+#[tauri::command]
+pub async fn restore_from_backup(
+    path: String,
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<RestoreReport, String> {
+    let source = PathBuf::from(&path);
+    let schema_version = validate_backup(&source).await?;
+    let config = app_state.lock().unwrap().backups.clone();
+    let dir = backup_dir(&profile_manager.app_handle, &config)?;
+    let live = profile_manager
+        .app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?
+        .join(DB_FILE);
+
+    // Stage the copy first: pruning after the safety backup may delete the source
+    let staged = live.with_extension("db.restore");
+    std::fs::copy(database_file(&source), &staged).map_err(|e| format!("Failed to stage {}: {}", path, e))?;
+    restore_blobs(&source)?;
+
+    let mut db = profile_manager.db_pool.write().await;
+    let previous = match db.as_ref() {
+        Some(pool) => match create_backup(pool, &dir, config.keep.max(2)).await {
+            Ok(backup) => Some(backup),
+            Err(e) => {
+                let _ = std::fs::remove_file(&staged);
+                return Err(format!("Not restoring: failed to back up the current database: {}", e));
+            }
+        },
+        None => None,
+    };
+    if let Some(pool) = db.take() {
+        pool.close().await;
+    }
+    let swapped = swap_database(&staged, &live);
+    drop(db);
+    swapped?;
+
+    profile_manager.initialize_db().await?;
+    profile_manager.load_profiles().await?;
+    log::info!("Restored database from {} (schema version {})", path, schema_version);
+    Ok(RestoreReport { restored_from: path, schema_version, migrated: schema_version < SCHEMA_VERSION, previous })
+}
+
+#[tauri::command]
+pub async fn get_backup_config(app_state: State<'_, AppState>) -> Result<BackupConfig, String> {
+    Ok(app_state.lock().unwrap().backups.clone())
+}
+
+#[tauri::command]
+pub async fn set_backup_config(config: BackupConfig, app_state: State<'_, AppState>) -> Result<(), String> {
+    if config.hour > 23 {
+        return Err("Backup hour must be between 0 and 23".to_string());
+    }
+    if config.keep == 0 {
+        return Err("At least one backup must be kept".to_string());
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.backups = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool_with;
+    use sqlx::pool::PoolOptions;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_backup_prune_and_validate() {
+        // VACUUM INTO needs a file-backed source
+        let data = TempDir::new().unwrap();
+        let options = SqliteConnectOptions::new().filename(data.path().join(DB_FILE));
+        let pool = test_pool_with(PoolOptions::new(), options).await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('backup-s1', 'Session')").execute(&pool).await.unwrap();
+        sqlx::query("PRAGMA user_version = 1").execute(&pool).await.unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let first = create_backup(&pool, dir.path(), 2).await.unwrap();
+        assert_eq!(first.manifest.schema_version, 1);
+        assert_eq!(first.manifest.rows["sessions"], 1);
+        let dump = std::fs::read_to_string(Path::new(&first.path).join("sessions.jsonl")).unwrap();
+        let row: serde_json::Value = serde_json::from_str(dump.lines().next().unwrap()).unwrap();
+        assert_eq!(row["id"], "backup-s1");
+        assert_eq!(validate_backup(Path::new(&first.path)).await.unwrap(), 1);
+
+        // Leftovers of an interrupted run are pruned along with old backups
+        let partial = dir.path().join("backup-20000101-000000-000.partial");
+        std::fs::create_dir(&partial).unwrap();
+        for _ in 0..2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            create_backup(&pool, dir.path(), 2).await.unwrap();
+        }
+        assert!(!partial.exists());
+        let backups = backups_in(dir.path());
+        assert_eq!(backups.len(), 2);
+        assert!(!backups.iter().any(|b| b.path == first.path));
+        assert!(created_at(&backups[0]).unwrap() > created_at(&backups[1]).unwrap());
+
+        sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1)).execute(&pool).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let newer = create_backup(&pool, dir.path(), 5).await.unwrap();
+        assert!(validate_backup(Path::new(&newer.path)).await.unwrap_err().contains("newer"));
+        assert!(validate_backup(&dir.path().join("missing")).await.is_err());
+
+        let now = Local.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let last_night = Local.with_ymd_and_hms(2024, 3, 10, 3, 30, 0).unwrap().with_timezone(&Utc);
+        assert!(is_due(None, now, 3));
+        assert!(!is_due(Some(last_night), now, 3));
+        assert!(is_due(Some(last_night - ChronoDuration::days(1)), now, 3));
+        let afternoon = Local.with_ymd_and_hms(2024, 3, 10, 14, 0, 0).unwrap();
+        assert!(is_due(Some(last_night), afternoon, 13));
+    }
+}
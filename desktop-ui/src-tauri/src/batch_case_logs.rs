@@ -0,0 +1,201 @@
+//! Per-case logs of a batch run
+//!
+//! A batch started with `results_dir` writes one directory per case,
+//! `<results_dir>/<batch>/case-<index>/`, with everything needed to look into a
+//! failure offline: the prompt, the agent's environment (secrets masked), the full
+//! transcript as JSON lines, the diff it left, hook outputs, and `result.json`
+//! holding the case's `CaseResult`. `CaseResult::logs` gives the same files
+//! relative to `results_dir`, so a merged result file still points at them when
+//! the results directory is copied to another machine.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use unified_core::AgentEvent;
+
+use crate::batch_shards::CaseResult;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Files of one case, relative to the results directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaseLogPaths {
+    pub dir: PathBuf,
+    pub prompt: PathBuf,
+    pub environment: PathBuf,
+    pub transcript: PathBuf,
+    /// Absent when the case did not run in a git repository
+    pub diff: Option<PathBuf>,
+    pub hooks: PathBuf,
+    pub result: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookOutput {
+    pub name: String,
+    pub success: bool,
+    pub output: String,
+}
+
+/// What a case produced, before it is written out
+#[derive(Debug, Clone, Default)]
+pub struct CaseArtifacts {
+    pub prompt: String,
+    pub environment: HashMap<String, String>,
+    pub transcript: Vec<AgentEvent>,
+    pub diff: Option<String>,
+    pub hook_outputs: Vec<HookOutput>,
+}
+
+/// Same test as the app config's log redaction
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    ["TOKEN", "SECRET", "KEY", "PASSWORD"].iter().any(|word| key.contains(word))
+}
+
+/// The environment with secret values masked, sorted by name
+pub fn environment_snapshot(env: &HashMap<String, String>) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(key, value)| (key.clone(), if is_secret(key) { REDACTED.to_string() } else { value.clone() }))
+        .collect()
+}
+
+/// Directory name for a batch, safe on every platform
+pub fn batch_dir_name(batch_name: &str) -> String {
+    let name: String = batch_name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "batch".to_string(),
+        _ => name,
+    }
+}
+
+pub fn case_dir_name(case_index: usize) -> String {
+    format!("case-{:05}", case_index)
+}
+
+fn write_file(root: &Path, relative: &Path, content: &[u8]) -> std::io::Result<()> {
+    std::fs::write(root.join(relative), content)
+}
+
+/// Write everything but `result.json` for one case, replacing an earlier attempt's files
+pub fn write_case_artifacts(
+    results_dir: &Path,
+    batch_name: &str,
+    case_index: usize,
+    artifacts: &CaseArtifacts,
+) -> std::io::Result<CaseLogPaths> {
+    let dir = PathBuf::from(batch_dir_name(batch_name)).join(case_dir_name(case_index));
+    std::fs::create_dir_all(results_dir.join(&dir))?;
+    let paths = CaseLogPaths {
+        prompt: dir.join("prompt.txt"),
+        environment: dir.join("environment.json"),
+        transcript: dir.join("transcript.jsonl"),
+        diff: artifacts.diff.as_ref().map(|_| dir.join("diff.patch")),
+        hooks: dir.join("hooks.json"),
+        result: dir.join("result.json"),
+        dir,
+    };
+
+    write_file(results_dir, &paths.prompt, artifacts.prompt.as_bytes())?;
+    write_file(results_dir, &paths.environment, &serde_json::to_vec_pretty(&environment_snapshot(&artifacts.environment))?)?;
+    let mut transcript = std::io::BufWriter::new(std::fs::File::create(results_dir.join(&paths.transcript))?);
+    for event in &artifacts.transcript {
+        serde_json::to_writer(&mut transcript, event)?;
+        transcript.write_all(b"\n")?;
+    }
+    transcript.flush()?;
+    match (&paths.diff, &artifacts.diff) {
+        (Some(path), Some(diff)) => write_file(results_dir, path, diff.as_bytes())?,
+        _ => {
+            let _ = std::fs::remove_file(results_dir.join(&paths.dir).join("diff.patch"));
+        }
+    }
+    write_file(results_dir, &paths.hooks, &serde_json::to_vec_pretty(&artifacts.hook_outputs)?)?;
+    Ok(paths)
+}
+
+/// Write a case's `result.json` at the path its `logs` name
+pub fn write_case_result(results_dir: &Path, result: &CaseResult) -> std::io::Result<()> {
+    let logs = result.logs.as_ref().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Case {} has no log directory", result.case_index))
+    })?;
+    write_file(results_dir, &logs.result, &serde_json::to_vec_pretty(result)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_engine::SessionStatus;
+
+    #[test]
+    fn test_case_directory_layout() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let artifacts = CaseArtifacts {
+            prompt: "Fix the bug".to_string(),
+            environment: HashMap::from([
+                ("AMP_API_KEY".to_string(), "sk-live".to_string()),
+                ("AMP_URL".to_string(), "https://ampcode.com".to_string()),
+            ]),
+            transcript: vec![
+                AgentEvent::Json { event: serde_json::json!({ "type": "assistant" }) },
+                AgentEvent::Stderr { text: "warning".to_string() },
+            ],
+            diff: Some("+fixed\n".to_string()),
+            hook_outputs: vec![HookOutput { name: "verify".to_string(), success: true, output: "ok".to_string() }],
+        };
+
+        let paths = write_case_artifacts(temp.path(), "Nightly run / v2", 7, &artifacts).unwrap();
+        assert_eq!(paths.dir, PathBuf::from("Nightly_run___v2/case-00007"));
+
+        let result = CaseResult {
+            case_index: 7,
+            prompt: artifacts.prompt.clone(),
+            repository: "/work/repo".to_string(),
+            status: SessionStatus::Failed,
+            error_message: Some("amp exited with status 1".to_string()),
+            execution_time_ms: Some(1200),
+            metrics: None,
+            logs: Some(paths.clone()),
+        };
+        write_case_result(temp.path(), &result).unwrap();
+
+        let root = temp.path();
+        assert_eq!(std::fs::read_to_string(root.join(&paths.prompt)).unwrap(), "Fix the bug");
+        let env: BTreeMap<String, String> = serde_json::from_slice(&std::fs::read(root.join(&paths.environment)).unwrap()).unwrap();
+        assert_eq!(env["AMP_API_KEY"], REDACTED);
+        assert_eq!(env["AMP_URL"], "https://ampcode.com");
+        let transcript = std::fs::read_to_string(root.join(&paths.transcript)).unwrap();
+        let events: Vec<AgentEvent> = transcript.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(events, artifacts.transcript);
+        assert_eq!(std::fs::read_to_string(root.join(paths.diff.as_ref().unwrap())).unwrap(), "+fixed\n");
+        let stored: CaseResult = serde_json::from_slice(&std::fs::read(root.join(&paths.result)).unwrap()).unwrap();
+        assert_eq!(stored.logs, Some(paths));
+
+        // A retry outside a git repository drops the earlier diff
+        let retry = write_case_artifacts(root, "Nightly run / v2", 7, &CaseArtifacts { diff: None, ..artifacts }).unwrap();
+        assert_eq!(retry.diff, None);
+        assert!(!root.join(&retry.dir).join("diff.patch").exists());
+    }
+
+    #[test]
+    fn test_case_result_requires_logs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let result = CaseResult {
+            case_index: 0,
+            prompt: String::new(),
+            repository: String::new(),
+            status: SessionStatus::Completed,
+            error_message: None,
+            execution_time_ms: None,
+            metrics: None,
+            logs: None,
+        };
+        assert!(write_case_result(temp.path(), &result).is_err());
+        assert_eq!(batch_dir_name(".."), "batch");
+    }
+}
@@ -4,7 +4,14 @@ use std::sync::Arc;
 use tauri::{State, Window, Emitter};
 use tokio::sync::RwLock;
 
-use crate::batch_engine::{BatchConfig, BatchEngine, BatchHandle, BatchProgress, RetryPolicy};
+use crate::batch_engine::{
+    BatchConfig, BatchEngine, BatchHandle, BatchInternals, BatchProgress, BatchValidationReport, LimitExceededCase, RetryPolicy,
+};
+use crate::batch_dag::{BatchDag, BlockedCase, TaskDependency};
+use crate::batch_estimate::BatchSimulation;
+use crate::batch_results::{self, BatchSessionRecord, CaseResultEntry, ResultsPage};
+use crate::batch_shards::{merge_result_files, BatchResultFile, MergedBatchResults, ShardSpec};
+use crate::services::BatchService;
 use crate::session_manager::EnhancedSessionManager;
 
 // Global state for batch engine
@@ -25,6 +32,20 @@ pub struct StartBatchRequest {
     pub retry_policy: Option<RetryPolicyRequest>,
     pub agent_mode: Option<String>,
     pub toolbox_path: Option<String>,
+    /// Zero-based shard this instance runs; requires `shard_count`
+    #[serde(default)]
+    pub shard_index: Option<usize>,
+    #[serde(default)]
+    pub shard_count: Option<usize>,
+    /// Directory for per-case logs; cases are not logged when omitted
+    #[serde(default)]
+    pub results_dir: Option<String>,
+    /// Prompts that wait for other prompts on the same repository
+    #[serde(default)]
+    pub dependencies: Vec<TaskDependencyRequest>,
+    /// Priority of each prompt; higher starts first when several cases are ready
+    #[serde(default)]
+    pub priorities: Vec<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,6 +55,17 @@ pub struct RetryPolicyRequest {
     pub backoff_ms: u64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDependencyRequest {
+    /// Zero-based index of the waiting prompt
+    pub prompt: usize,
+    /// Zero-based index of the prompt it waits for
+    pub after: usize,
+    #[serde(default)]
+    pub include_diff: bool,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StartBatchResponse {
@@ -52,6 +84,10 @@ pub struct BatchProgressResponse {
     pub running_sessions: usize,
     pub progress_percent: f32,
     pub status: String,
+    /// Cases waiting for a dependency to succeed
+    pub blocked: Vec<BlockedCase>,
+    /// Cases stopped for passing a process limit
+    pub limit_exceeded: Vec<LimitExceededCase>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,6 +102,75 @@ pub struct GetBatchStatusRequest {
     pub batch_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResultsPageRequest {
+    pub batch_id: String,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Where a batch that is no longer loaded wrote its case logs
+    #[serde(default)]
+    pub results_dir: Option<String>,
+    /// Name the batch ran under, which names its directory in `results_dir`
+    #[serde(default)]
+    pub batch_name: Option<String>,
+    /// Include the start of each case's transcript
+    #[serde(default)]
+    pub include_transcripts: bool,
+    /// Transcript bytes per case; defaults to `DEFAULT_TRANSCRIPT_BYTES`
+    #[serde(default)]
+    pub max_transcript_bytes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSessionHistoryRequest {
+    pub batch_id: String,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteBatchResultsRequest {
+    pub batch_id: String,
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeBatchResultsRequest {
+    pub paths: Vec<String>,
+    /// Where to write the merged file; the merge is only returned when omitted
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssueResponse {
+    pub severity: String,
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchValidationResponse {
+    pub valid: bool,
+    pub total_sessions: usize,
+    pub dataset_cases: Option<usize>,
+    pub issues: Vec<ValidationIssueResponse>,
+    pub effective_concurrency: usize,
+    pub history_sample_size: usize,
+    pub estimated_wall_time_sec: Option<u64>,
+    pub estimated_total_tokens: Option<u64>,
+}
+
 // Convert internal types to response types
 impl From<BatchProgress> for BatchProgressResponse {
     fn from(progress: BatchProgress) -> Self {
@@ -77,6 +182,27 @@ impl From<BatchProgress> for BatchProgressResponse {
             running_sessions: progress.running_sessions,
             progress_percent: progress.progress_percent,
             status: format!("{:?}", progress.status),
+            blocked: progress.blocked,
+            limit_exceeded: progress.limit_exceeded,
+        }
+    }
+}
+
+impl From<BatchValidationReport> for BatchValidationResponse {
+    fn from(report: BatchValidationReport) -> Self {
+        Self {
+            valid: report.valid,
+            total_sessions: report.total_sessions,
+            dataset_cases: report.dataset_cases,
+            issues: report.issues.into_iter().map(|issue| ValidationIssueResponse {
+                severity: format!("{:?}", issue.severity),
+                field: issue.field,
+                message: issue.message,
+            }).collect(),
+            effective_concurrency: report.estimate.effective_concurrency,
+            history_sample_size: report.estimate.sample_size,
+            estimated_wall_time_sec: report.estimate.estimated_wall_time_secs,
+            estimated_total_tokens: report.estimate.estimated_total_tokens,
         }
     }
 }
@@ -95,60 +221,117 @@ impl From<StartBatchRequest> for BatchConfig {
             }),
             agent_mode: request.agent_mode,
             toolbox_path: request.toolbox_path.map(PathBuf::from),
+            shard: match (request.shard_index, request.shard_count) {
+                (None, None) => None,
+                (index, count) => Some(ShardSpec { index: index.unwrap_or(0), count: count.unwrap_or(1) }),
+            },
+            results_dir: request.results_dir.map(PathBuf::from),
+            dependencies: request.dependencies.into_iter().map(|d| TaskDependency {
+                prompt: d.prompt,
+                after: d.after,
+                include_diff: d.include_diff,
+            }).collect(),
+            priorities: request.priorities,
         }
     }
 }
 
+pub(crate) async fn batch_service(
+    state: &State<'_, BatchEngineState>,
+    profile_manager: &State<'_, crate::profile_auth::ProfileManager>,
+) -> BatchService {
+    BatchService::new(state.engine.clone(), profile_manager.db_pool.read().await.clone())
+}
+
 /// Start a new batch execution
 #[tauri::command]
 pub async fn start_batch(
     request: StartBatchRequest,
     state: State<'_, BatchEngineState>,
+    app_state: State<'_, crate::app_state::AppState>,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
     window: Window,
 ) -> Result<StartBatchResponse, String> {
-    let config = BatchConfig::from(request);
-    
-    match state.engine.start_batch(config).await {
-        Ok(mut handle) => {
-            let batch_id = handle.batch_id().to_string();
-            let total_sessions = handle.total_sessions();
-            
-            // Start progress monitoring in background
-            if let Some(mut progress_rx) = handle.take_progress_receiver() {
-                let window_clone = window.clone();
+    crate::feature_flags::require(&app_state, crate::feature_flags::BATCH_PROCESSING)?;
+    start_tracked_batch(BatchConfig::from(request), &state, &profile_manager, window).await
+}
+
+/// Start a batch, emit its progress to `window` and keep its handle for cancelling
+pub(crate) async fn start_tracked_batch(
+    config: BatchConfig,
+    state: &State<'_, BatchEngineState>,
+    profile_manager: &State<'_, crate::profile_auth::ProfileManager>,
+    window: Window,
+) -> Result<StartBatchResponse, String> {
+    // Each case runs in its own session, so it counts against the active profile's quota
+    let profile_id = profile_manager.active_profile_id.read().await.clone();
+    let service = batch_service(state, profile_manager).await;
+    let mut handle = service.start(config, profile_id.as_deref()).await?;
+    let batch_id = handle.batch_id().to_string();
+    let total_sessions = handle.total_sessions();
+
+    // Start progress monitoring in background
+    if let Some(mut progress_rx) = handle.take_progress_receiver() {
+        let window_clone = window.clone();
+        
+        tokio::spawn(async move {
+            let mut stopped = std::collections::HashSet::new();
+            while let Some(progress) = progress_rx.recv().await {
+                let progress_response = BatchProgressResponse::from(progress);
                 
-                tokio::spawn(async move {
-                    while let Some(progress) = progress_rx.recv().await {
-                        let progress_response = BatchProgressResponse::from(progress);
-                        
-                        // Emit progress event to frontend
-                        let _ = window_clone.emit("batch_progress", &progress_response);
-                        
-                        // If batch is completed or failed, break the loop
-                        if progress_response.status == "Completed" || 
-                           progress_response.status == "Failed" || 
-                           progress_response.status == "Cancelled" {
-                            let _ = window_clone.emit("batch_completed", &progress_response);
-                            break;
-                        }
-                    }
-                });
-            }
-            
-            // Store handle for potential cancellation
-            {
-                let mut handles = state.active_handles.write().await;
-                handles.insert(batch_id.clone(), handle);
+                // Emit progress event to frontend
+                let _ = window_clone.emit("batch_progress", &progress_response);
+                for case in progress_response.limit_exceeded.iter().filter(|case| stopped.insert(case.session_id.clone())) {
+                    let event = crate::process_limits::ProcessLimitExceededEvent::new(&case.session_id, "batch", case.exceeded.clone());
+                    let _ = window_clone.emit("process_limit_exceeded", &event);
+                }
+                
+                // If batch is completed or failed, break the loop
+                if progress_response.status == "Completed" || 
+                   progress_response.status == "Failed" || 
+                   progress_response.status == "Cancelled" {
+                    let _ = window_clone.emit("batch_completed", &progress_response);
+                    break;
+                }
             }
-            
-            Ok(StartBatchResponse {
-                batch_id,
-                total_sessions,
-                status: "Started".to_string(),
-            })
-        }
-        Err(e) => Err(format!("Failed to start batch: {}", e)),
+        });
+    }
+    
+    // Store handle for potential cancellation
+    {
+        let mut handles = state.active_handles.write().await;
+        handles.insert(batch_id.clone(), handle);
     }
+    
+    Ok(StartBatchResponse {
+        batch_id,
+        total_sessions,
+        status: "Started".to_string(),
+    })
+}
+
+/// Validate a batch definition without running it
+#[tauri::command]
+pub async fn validate_batch(
+    request: StartBatchRequest,
+    dataset_path: Option<String>,
+    state: State<'_, BatchEngineState>,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<BatchValidationResponse, String> {
+    let config = BatchConfig::from(request);
+    let dataset_path = dataset_path.map(PathBuf::from);
+    let report = batch_service(&state, &profile_manager).await.validate(&config, dataset_path.as_deref()).await?;
+    Ok(BatchValidationResponse::from(report))
+}
+
+/// Simulate a batch against past runs to predict its duration and token use
+#[tauri::command]
+pub async fn estimate_batch(
+    request: StartBatchRequest,
+    state: State<'_, BatchEngineState>,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<BatchSimulation, String> {
+    batch_service(&state, &profile_manager).await.estimate(BatchConfig::from(request)).await
 }
 
 /// Cancel a running batch
@@ -182,6 +365,29 @@ pub async fn get_batch_status(
     }
 }
 
+/// Cases of a batch as a graph: each case's dependencies, status and session
+#[tauri::command]
+pub async fn get_batch_dag(
+    batch_id: String,
+    state: State<'_, BatchEngineState>,
+) -> Result<BatchDag, String> {
+    state.engine.get_batch_dag(&batch_id)
+        .await
+        .map_err(|e| format!("Failed to get batch graph: {}", e))
+}
+
+/// Scheduler internals of a batch for the debug panel: cases with their
+/// executor, attempts and last error, and the slots they hold
+#[tauri::command]
+pub async fn get_batch_internals(
+    batch_id: String,
+    state: State<'_, BatchEngineState>,
+) -> Result<BatchInternals, String> {
+    state.engine.get_batch_internals(&batch_id)
+        .await
+        .map_err(|e| format!("Failed to get batch internals: {}", e))
+}
+
 /// List all active batches
 #[tauri::command]
 pub async fn list_active_batches(
@@ -206,12 +412,115 @@ pub async fn get_batch_results(
             successful_sessions: progress.completed_sessions,
             failed_sessions: progress.failed_sessions,
             status: format!("{:?}", progress.status),
-            session_results: vec![], // TODO: Implement detailed session results
+            session_results: vec![], // Per-case results are paged by get_batch_results_page
         }),
         Err(e) => Err(format!("Failed to get batch results: {}", e)),
     }
 }
 
+/// One page of a batch's case results, from the engine while the batch is
+/// loaded and from its results directory afterwards
+#[tauri::command]
+pub async fn get_batch_results_page(
+    request: BatchResultsPageRequest,
+    state: State<'_, BatchEngineState>,
+) -> Result<ResultsPage<CaseResultEntry>, String> {
+    let after = batch_results::parse_case_cursor(request.cursor.as_deref())?;
+    let limit = batch_results::page_limit(request.limit);
+
+    let (results, more, results_dir) = match state.engine.case_results_page(&request.batch_id, after, limit).await {
+        Ok(page) => page,
+        Err(_) => {
+            let (Some(results_dir), Some(batch_name)) = (request.results_dir, request.batch_name) else {
+                return Err(format!("Batch {} is not loaded; pass its results directory and name", request.batch_id));
+            };
+            let results_dir = PathBuf::from(results_dir);
+            let dir = results_dir.clone();
+            let (results, more) = tokio::task::spawn_blocking(move || batch_results::read_page_from_dir(&dir, &batch_name, after, limit))
+                .await
+                .map_err(|e| format!("Failed to read batch results: {}", e))??;
+            (results, more, Some(results_dir))
+        }
+    };
+
+    let next_cursor = if more { results.last().map(|r| r.case_index.to_string()) } else { None };
+    let transcripts_from = results_dir.filter(|_| request.include_transcripts);
+    let max_bytes = request.max_transcript_bytes.unwrap_or(batch_results::DEFAULT_TRANSCRIPT_BYTES);
+    let items = tokio::task::spawn_blocking(move || {
+        results
+            .into_iter()
+            .map(|result| {
+                let transcript = transcripts_from.as_ref().zip(result.logs.as_ref()).and_then(|(dir, logs)| {
+                    batch_results::read_transcript(&dir.join(&logs.transcript), max_bytes).ok()
+                });
+                CaseResultEntry { result, transcript }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to read transcripts: {}", e))?;
+    Ok(ResultsPage { items, next_cursor })
+}
+
+/// One page of a batch's sessions as recorded in the database
+#[tauri::command]
+pub async fn get_batch_session_history(
+    request: BatchSessionHistoryRequest,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<ResultsPage<BatchSessionRecord>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not initialized")?;
+    batch_results::load_session_page(db, &request.batch_id, request.cursor.as_deref(), batch_results::page_limit(request.limit)).await
+}
+
+/// Write this instance's per-case results so they can be merged with other shards
+#[tauri::command]
+pub async fn write_batch_results(
+    request: WriteBatchResultsRequest,
+    state: State<'_, BatchEngineState>,
+) -> Result<BatchResultFile, String> {
+    let results = state.engine.result_file(&request.batch_id)
+        .await
+        .map_err(|e| format!("Failed to collect batch results: {}", e))?;
+
+    // Streamed to the file rather than built up as one string
+    let file = std::fs::File::create(&request.path)
+        .map_err(|e| format!("Failed to write file {}: {}", request.path, e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, &results)
+        .map_err(|e| format!("Failed to serialize batch results: {}", e))?;
+    std::io::Write::flush(&mut writer)
+        .map_err(|e| format!("Failed to write file {}: {}", request.path, e))?;
+
+    Ok(results)
+}
+
+/// Merge result files written by the shards of one batch
+#[tauri::command]
+pub async fn merge_batch_results(
+    request: MergeBatchResultsRequest,
+) -> Result<MergedBatchResults, String> {
+    let mut files = Vec::with_capacity(request.paths.len());
+    for path in &request.paths {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        let file: BatchResultFile = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid batch result file {}: {}", path, e))?;
+        files.push(file);
+    }
+
+    let merged = merge_result_files(files)?;
+
+    if let Some(output_path) = &request.output_path {
+        let json = serde_json::to_string_pretty(&merged.merged)
+            .map_err(|e| format!("Failed to serialize merged results: {}", e))?;
+        std::fs::write(output_path, json)
+            .map_err(|e| format!("Failed to write file {}: {}", output_path, e))?;
+    }
+
+    Ok(merged)
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BatchResultsResponse {
@@ -290,6 +599,11 @@ mod tests {
             }),
             agent_mode: Some("geppetto:main".to_string()),
             toolbox_path: Some("/test/toolbox".to_string()),
+            shard_index: None,
+            shard_count: None,
+            results_dir: Some("/test/results".to_string()),
+            dependencies: Vec::new(),
+            priorities: vec![2],
         };
 
         let config = BatchConfig::from(request);
@@ -299,6 +613,7 @@ mod tests {
         assert_eq!(config.repositories.len(), 1);
         assert_eq!(config.concurrency, 2);
         assert_eq!(config.timeout_sec, 600);
+        assert_eq!(config.results_dir, Some(PathBuf::from("/test/results")));
         assert!(config.retry_policy.is_some());
         assert_eq!(config.agent_mode, Some("geppetto:main".to_string()));
         assert!(config.toolbox_path.is_some());
@@ -314,6 +629,12 @@ mod tests {
             running_sessions: 4,
             progress_percent: 60.0,
             status: crate::batch_engine::BatchStatus::Running,
+            blocked: vec![BlockedCase { case_index: 9, waiting_on: vec![3] }],
+            limit_exceeded: vec![LimitExceededCase {
+                case_index: 2,
+                session_id: "s2".to_string(),
+                exceeded: unified_core::LimitExceeded::ExecutionTime { limit_secs: 300 },
+            }],
         };
 
         let response = BatchProgressResponse::from(progress);
@@ -325,5 +646,7 @@ mod tests {
         assert_eq!(response.running_sessions, 4);
         assert_eq!(response.progress_percent, 60.0);
         assert_eq!(response.status, "Running");
+        assert_eq!(response.blocked[0].waiting_on, vec![3]);
+        assert_eq!(response.limit_exceeded[0].session_id, "s2");
     }
 }
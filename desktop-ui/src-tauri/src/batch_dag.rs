@@ -0,0 +1,410 @@
+//! Dependencies between the cases of a batch
+//!
+//! `BatchConfig::dependencies` makes one prompt wait for another on the same
+//! repository: the waiting case starts only after the case it depends on
+//! succeeded, and can be handed that case's diff in its prompt. When a case
+//! fails, every case depending on it, directly or not, is skipped. Cycles are
+//! rejected before the batch starts. A batch without dependencies is a graph
+//! with no edges, so every batch can be shown through `get_batch_dag`.
+//!
+//! `BatchConfig::priorities` orders the cases that are ready when a slot frees
+//! up, highest first. A case that others wait for takes on the highest priority
+//! among them, so a low-priority prerequisite does not hold back urgent work.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::batch_engine::BatchConfig;
+use crate::batch_shards;
+
+/// Diffs handed to a dependent prompt are cut to this size
+pub const MAX_FED_DIFF_BYTES: usize = 64 * 1024;
+
+/// Prompt `prompt` runs on a repository only once prompt `after` succeeded there
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskDependency {
+    pub prompt: usize,
+    pub after: usize,
+    /// Append the diff `after` left to the waiting prompt
+    #[serde(default)]
+    pub include_diff: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    Waiting,
+    Running,
+    Succeeded,
+    Failed,
+    /// A case it depends on did not succeed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DagNode {
+    pub case_index: usize,
+    pub prompt_index: usize,
+    pub repository_index: usize,
+    /// Case indexes this case waits for
+    pub depends_on: Vec<usize>,
+    pub status: NodeStatus,
+    pub session_id: Option<String>,
+    /// Length of the longest dependency chain leading here, for laying out the graph
+    pub depth: usize,
+    /// Ready cases start in this order, highest first: the prompt's priority,
+    /// raised to that of any case waiting on this one
+    #[serde(default)]
+    pub priority: i32,
+    /// For a skipped case, the failed case that caused it
+    pub skipped_because: Option<usize>,
+}
+
+/// A case that cannot start until the cases in `waiting_on` succeed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockedCase {
+    pub case_index: usize,
+    pub waiting_on: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DagEdge {
+    pub from: usize,
+    pub to: usize,
+    pub include_diff: bool,
+}
+
+/// What a finished case leaves for the cases that depend on it
+#[derive(Debug, Clone, Default)]
+pub struct CaseOutput {
+    pub diff: Option<String>,
+    /// The case's log directory, when the batch keeps logs
+    pub logs_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchDag {
+    /// Cases of this instance's shard, by case index
+    pub nodes: Vec<DagNode>,
+    pub edges: Vec<DagEdge>,
+    #[serde(skip)]
+    repository_count: usize,
+    #[serde(skip)]
+    outputs: HashMap<usize, CaseOutput>,
+}
+
+/// Check indexes and reject cycles, naming the prompts caught in one
+pub fn validate_dependencies(prompt_count: usize, dependencies: &[TaskDependency]) -> Result<(), String> {
+    for dependency in dependencies {
+        if dependency.prompt >= prompt_count || dependency.after >= prompt_count {
+            return Err(format!(
+                "Dependency of prompt {} on prompt {} refers to a prompt that does not exist",
+                dependency.prompt + 1,
+                dependency.after + 1
+            ));
+        }
+        if dependency.prompt == dependency.after {
+            return Err(format!("Prompt {} depends on itself", dependency.prompt + 1));
+        }
+    }
+    prompt_depths(prompt_count, dependencies).map(|_| ())
+}
+
+/// Depth of every prompt, or an error when the dependencies form a cycle
+fn prompt_depths(prompt_count: usize, dependencies: &[TaskDependency]) -> Result<Vec<usize>, String> {
+    let mut waiting_on = vec![0usize; prompt_count];
+    for dependency in dependencies {
+        waiting_on[dependency.prompt] += 1;
+    }
+    let mut depths = vec![0usize; prompt_count];
+    let mut queue: Vec<usize> = (0..prompt_count).filter(|p| waiting_on[*p] == 0).collect();
+    let mut visited = 0;
+    while let Some(prompt) = queue.pop() {
+        visited += 1;
+        for dependency in dependencies.iter().filter(|d| d.after == prompt) {
+            depths[dependency.prompt] = depths[dependency.prompt].max(depths[prompt] + 1);
+            waiting_on[dependency.prompt] -= 1;
+            if waiting_on[dependency.prompt] == 0 {
+                queue.push(dependency.prompt);
+            }
+        }
+    }
+    if visited == prompt_count {
+        return Ok(depths);
+    }
+    let cycle: Vec<String> = (0..prompt_count).filter(|p| waiting_on[*p] > 0).map(|p| (p + 1).to_string()).collect();
+    Err(format!("Dependencies form a cycle between prompts {}", cycle.join(", ")))
+}
+
+/// Each prompt's priority raised to the highest of the prompts waiting on it,
+/// directly or not
+fn inherited_priorities(priorities: &[i32], depths: &[usize], dependencies: &[TaskDependency]) -> Vec<i32> {
+    let mut inherited: Vec<i32> = (0..depths.len()).map(|p| priorities.get(p).copied().unwrap_or(0)).collect();
+    // Deepest first, so a prompt's dependents are final before it is visited
+    let mut order: Vec<usize> = (0..depths.len()).collect();
+    order.sort_by_key(|p| Reverse(depths[*p]));
+    for prompt in order {
+        for dependency in dependencies.iter().filter(|d| d.prompt == prompt) {
+            inherited[dependency.after] = inherited[dependency.after].max(inherited[prompt]);
+        }
+    }
+    inherited
+}
+
+impl BatchDag {
+    pub fn build(config: &BatchConfig) -> Result<Self, String> {
+        validate_dependencies(config.prompts.len(), &config.dependencies)?;
+        if config.priorities.len() > config.prompts.len() {
+            return Err(format!("{} priorities given for {} prompts", config.priorities.len(), config.prompts.len()));
+        }
+        let depths = prompt_depths(config.prompts.len(), &config.dependencies)?;
+        let priorities = inherited_priorities(&config.priorities, &depths, &config.dependencies);
+        let repository_count = config.repositories.len();
+        let total = config.prompts.len() * repository_count;
+        let shard = config.shard.as_ref();
+
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for case_index in (0..total).filter(|i| batch_shards::in_shard(shard, *i)) {
+            let (prompt_index, repository_index) = (case_index / repository_count, case_index % repository_count);
+            let mut depends_on = Vec::new();
+            for dependency in config.dependencies.iter().filter(|d| d.prompt == prompt_index) {
+                let from = dependency.after * repository_count + repository_index;
+                if !batch_shards::in_shard(shard, from) {
+                    return Err(format!(
+                        "Prompt {} depends on prompt {}, whose case {} runs in another shard",
+                        prompt_index + 1,
+                        dependency.after + 1,
+                        from
+                    ));
+                }
+                match edges.iter_mut().find(|e: &&mut DagEdge| e.from == from && e.to == case_index) {
+                    Some(edge) => edge.include_diff |= dependency.include_diff,
+                    None => {
+                        depends_on.push(from);
+                        edges.push(DagEdge { from, to: case_index, include_diff: dependency.include_diff });
+                    }
+                }
+            }
+            depends_on.sort_unstable();
+            nodes.push(DagNode {
+                case_index,
+                prompt_index,
+                repository_index,
+                depends_on,
+                status: NodeStatus::Waiting,
+                session_id: None,
+                depth: depths[prompt_index],
+                priority: priorities[prompt_index],
+                skipped_because: None,
+            });
+        }
+        Ok(Self { nodes, edges, repository_count, outputs: HashMap::new() })
+    }
+
+    fn node_mut(&mut self, case_index: usize) -> Option<&mut DagNode> {
+        let position = self.nodes.binary_search_by_key(&case_index, |n| n.case_index).ok()?;
+        self.nodes.get_mut(position)
+    }
+
+    fn status(&self, case_index: usize) -> Option<NodeStatus> {
+        let position = self.nodes.binary_search_by_key(&case_index, |n| n.case_index).ok()?;
+        Some(self.nodes[position].status)
+    }
+
+    /// Whether other cases wait for this one or it waits for others
+    pub fn in_chain(&self, case_index: usize) -> bool {
+        self.edges.iter().any(|e| e.from == case_index || e.to == case_index)
+    }
+
+    /// Mark up to `limit` waiting cases whose dependencies all succeeded as
+    /// running, highest priority first, and return them with the prompt to run
+    pub fn take_ready(&mut self, prompts: &[String], limit: usize) -> Vec<(usize, String)> {
+        let mut ready: Vec<(i32, usize)> = self
+            .nodes
+            .iter()
+            .filter(|n| n.status == NodeStatus::Waiting)
+            .filter(|n| n.depends_on.iter().all(|d| self.status(*d) == Some(NodeStatus::Succeeded)))
+            .map(|n| (n.priority, n.case_index))
+            .collect();
+        ready.sort_by_key(|(priority, case_index)| (Reverse(*priority), *case_index));
+        ready
+            .into_iter()
+            .take(limit)
+            .map(|(_, case_index)| case_index)
+            .map(|case_index| {
+                let prompt = self.prompt_for(case_index, prompts);
+                if let Some(node) = self.node_mut(case_index) {
+                    node.status = NodeStatus::Running;
+                }
+                (case_index, prompt)
+            })
+            .collect()
+    }
+
+    /// Waiting cases with a dependency that has not succeeded yet
+    pub fn blocked(&self) -> Vec<BlockedCase> {
+        self.nodes
+            .iter()
+            .filter(|n| n.status == NodeStatus::Waiting)
+            .filter_map(|n| {
+                let waiting_on: Vec<usize> =
+                    n.depends_on.iter().copied().filter(|d| self.status(*d) != Some(NodeStatus::Succeeded)).collect();
+                (!waiting_on.is_empty()).then_some(BlockedCase { case_index: n.case_index, waiting_on })
+            })
+            .collect()
+    }
+
+    pub fn set_session(&mut self, case_index: usize, session_id: &str) {
+        if let Some(node) = self.node_mut(case_index) {
+            node.session_id = Some(session_id.to_string());
+        }
+    }
+
+    /// Record a case's outcome. A failure skips the cases depending on it; their
+    /// indexes are returned.
+    pub fn finish(&mut self, case_index: usize, succeeded: bool, output: CaseOutput) -> Vec<usize> {
+        if let Some(node) = self.node_mut(case_index) {
+            node.status = if succeeded { NodeStatus::Succeeded } else { NodeStatus::Failed };
+        }
+        if succeeded {
+            self.outputs.insert(case_index, output);
+            return Vec::new();
+        }
+        let mut skipped = Vec::new();
+        let mut failed = vec![case_index];
+        while let Some(cause) = failed.pop() {
+            let dependents: Vec<usize> = self.edges.iter().filter(|e| e.from == cause).map(|e| e.to).collect();
+            for dependent in dependents {
+                if let Some(node) = self.node_mut(dependent).filter(|n| n.status == NodeStatus::Waiting) {
+                    node.status = NodeStatus::Skipped;
+                    node.skipped_because = Some(case_index);
+                    skipped.push(dependent);
+                    failed.push(dependent);
+                }
+            }
+        }
+        skipped
+    }
+
+    /// The case's prompt followed by the diffs of the cases it asked for
+    fn prompt_for(&self, case_index: usize, prompts: &[String]) -> String {
+        let repository_count = self.repository_count.max(1);
+        let mut prompt = prompts.get(case_index / repository_count).cloned().unwrap_or_default();
+        for edge in self.edges.iter().filter(|e| e.to == case_index && e.include_diff) {
+            let Some(output) = self.outputs.get(&edge.from) else {
+                continue;
+            };
+            let source = prompts.get(edge.from / repository_count).map_or("", String::as_str);
+            let title: String = source.lines().next().unwrap_or_default().chars().take(80).collect();
+            prompt.push_str(&format!("\n\nAn earlier task (\"{}\") ran on this repository first.", title));
+            if let Some(logs_dir) = &output.logs_dir {
+                prompt.push_str(&format!(" Its logs are in {}.", logs_dir.display()));
+            }
+            match output.diff.as_deref().filter(|d| !d.trim().is_empty()) {
+                Some(diff) => {
+                    let mut end = diff.len().min(MAX_FED_DIFF_BYTES);
+                    while !diff.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    let note = if end < diff.len() { "\n[diff truncated]" } else { "" };
+                    prompt.push_str(&format!(" It made these changes:\n\n```diff\n{}\n```{}", diff[..end].trim_end(), note));
+                }
+                None => prompt.push_str(" It made no changes."),
+            }
+        }
+        prompt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(prompts: usize, repositories: usize, dependencies: Vec<TaskDependency>) -> BatchConfig {
+        BatchConfig {
+            name: "Chain".to_string(),
+            prompts: (0..prompts).map(|p| format!("Prompt {}", p)).collect(),
+            repositories: (0..repositories).map(|r| PathBuf::from(format!("/repo/{}", r))).collect(),
+            concurrency: 2,
+            timeout_sec: 600,
+            retry_policy: None,
+            agent_mode: None,
+            toolbox_path: None,
+            shard: None,
+            results_dir: None,
+            dependencies,
+            priorities: Vec::new(),
+        }
+    }
+
+    fn after(prompt: usize, after: usize) -> TaskDependency {
+        TaskDependency { prompt, after, include_diff: true }
+    }
+
+    #[test]
+    fn test_build_rejects_bad_dependencies() {
+        assert!(BatchDag::build(&config(2, 1, vec![after(1, 1)])).is_err());
+        assert!(BatchDag::build(&config(2, 1, vec![after(2, 0)])).is_err());
+        let cycle = BatchDag::build(&config(3, 1, vec![after(1, 0), after(2, 1), after(1, 2)])).unwrap_err();
+        assert!(cycle.contains("prompts 2, 3"), "{}", cycle);
+
+        let mut sharded = config(2, 3, vec![after(1, 0)]);
+        sharded.shard = Some(batch_shards::ShardSpec { index: 0, count: 2 });
+        assert!(BatchDag::build(&sharded).is_err());
+
+        let dag = BatchDag::build(&config(3, 2, vec![after(1, 0), after(2, 1), after(2, 0)])).unwrap();
+        assert_eq!(dag.nodes.len(), 6);
+        assert_eq!(dag.nodes[5].depends_on, vec![1, 3]);
+        assert_eq!(dag.nodes.iter().map(|n| n.depth).collect::<Vec<_>>(), vec![0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_scheduling_feeds_diffs_and_skips_after_failure() {
+        let config = config(3, 2, vec![after(1, 0), after(2, 1)]);
+        let mut dag = BatchDag::build(&config).unwrap();
+
+        let ready: Vec<usize> = dag.take_ready(&config.prompts, usize::MAX).into_iter().map(|(case, _)| case).collect();
+        assert_eq!(ready, vec![0, 1]);
+        assert!(dag.take_ready(&config.prompts, usize::MAX).is_empty());
+
+        let output = CaseOutput { diff: Some("+fixed\n".to_string()), logs_dir: None };
+        assert!(dag.finish(0, true, output).is_empty());
+        let next = dag.take_ready(&config.prompts, usize::MAX);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].0, 2);
+        assert!(next[0].1.starts_with("Prompt 1\n\nAn earlier task (\"Prompt 0\")"));
+        assert!(next[0].1.contains("```diff\n+fixed\n```"));
+
+        assert_eq!(dag.finish(1, false, CaseOutput::default()), vec![3, 5]);
+        assert_eq!(dag.nodes[5].skipped_because, Some(1));
+        assert!(dag.in_chain(0) && dag.in_chain(4));
+        assert!(dag.take_ready(&config.prompts, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_ready_cases_start_by_priority() {
+        let mut config = config(4, 1, vec![after(3, 0)]);
+        config.priorities = vec![0, 1, 5, 9];
+        let mut dag = BatchDag::build(&config).unwrap();
+        // Prompt 1 inherits the priority of prompt 4, which waits on it
+        assert_eq!(dag.nodes.iter().map(|n| n.priority).collect::<Vec<_>>(), vec![9, 1, 5, 9]);
+
+        let prompts = config.prompts.clone();
+        let take = |dag: &mut BatchDag, limit| {
+            dag.take_ready(&prompts, limit).into_iter().map(|(case, _)| case).collect::<Vec<_>>()
+        };
+        assert_eq!(take(&mut dag, 2), vec![0, 2]);
+        assert_eq!(dag.blocked(), vec![BlockedCase { case_index: 3, waiting_on: vec![0] }]);
+        assert_eq!(take(&mut dag, 5), vec![1]);
+        dag.finish(0, true, CaseOutput::default());
+        assert!(dag.blocked().is_empty());
+        assert_eq!(take(&mut dag, 5), vec![3]);
+
+        config.priorities = vec![0; 5];
+        assert!(BatchDag::build(&config).is_err());
+    }
+}
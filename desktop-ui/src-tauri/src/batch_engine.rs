@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -7,8 +7,12 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, RwLock};
 use tokio::time::Instant;
 use uuid::Uuid;
-use unified_core::domain::AgentMode;
+use unified_core::domain::{AgentMode, ProcessLimits, Session};
+use unified_core::{AgentHarness, AmpHarness, HarnessSpec, LimitExceeded};
 
+use crate::batch_case_logs::{self, CaseArtifacts, CaseLogPaths};
+use crate::batch_dag::{BatchDag, BlockedCase, CaseOutput, NodeStatus, TaskDependency};
+use crate::batch_shards::{self, BatchResultFile, CaseResult, ShardSpec};
 use crate::session_manager::EnhancedSessionManager;
 
 pub type BatchId = String;
@@ -24,6 +28,19 @@ pub struct BatchConfig {
     pub retry_policy: Option<RetryPolicy>,
     pub agent_mode: Option<String>,
     pub toolbox_path: Option<PathBuf>,
+    /// Run only this instance's share of the cases
+    #[serde(default)]
+    pub shard: Option<ShardSpec>,
+    /// Write each case's transcript, diff and result under `<results_dir>/<batch>/case-<index>/`
+    #[serde(default)]
+    pub results_dir: Option<PathBuf>,
+    /// Prompts that wait for other prompts on the same repository
+    #[serde(default)]
+    pub dependencies: Vec<TaskDependency>,
+    /// Priority of each prompt's cases; ready cases start highest first and
+    /// prompts without an entry have 0
+    #[serde(default)]
+    pub priorities: Vec<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +58,12 @@ pub struct BatchProgress {
     pub running_sessions: usize,
     pub progress_percent: f32,
     pub status: BatchStatus,
+    /// Cases waiting for a dependency to succeed
+    #[serde(default)]
+    pub blocked: Vec<BlockedCase>,
+    /// Cases stopped for passing a process limit, in case order
+    #[serde(default)]
+    pub limit_exceeded: Vec<LimitExceededCase>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +88,9 @@ pub struct BatchResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchSessionResult {
     pub session_id: SessionId,
+    /// Position in prompts × repositories, stable across shards
+    #[serde(default)]
+    pub case_index: usize,
     pub status: SessionStatus,
     #[serde(skip)] // Skip serialization for now, use creation timestamps if needed
     pub start_time: Option<Instant>,
@@ -72,6 +98,20 @@ pub struct BatchSessionResult {
     pub end_time: Option<Instant>,
     pub error_message: Option<String>,
     pub metrics: Option<SessionMetrics>,
+    #[serde(default)]
+    pub logs: Option<CaseLogPaths>,
+    /// The limit the case's agent was stopped for passing
+    #[serde(default)]
+    pub limit_exceeded: Option<LimitExceeded>,
+}
+
+/// A case whose agent was stopped for passing one of its process limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitExceededCase {
+    pub case_index: usize,
+    pub session_id: SessionId,
+    pub exceeded: LimitExceeded,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +138,73 @@ pub struct BatchExecution {
     pub sessions: HashMap<SessionId, BatchSessionResult>,
     pub start_time: Option<Instant>,
     pub progress_tx: mpsc::UnboundedSender<BatchProgress>,
+    /// Order the cases run in and where each one stands
+    pub dag: BatchDag,
+    /// Slots of the batch's own concurrency, once it runs
+    pub semaphore: Option<Arc<tokio::sync::Semaphore>>,
+}
+
+/// Which path runs a case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseExecutor {
+    /// Run to completion through the Amp harness, keeping transcript and diff
+    Harness,
+    /// Started through the session manager
+    SessionManager,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// Cases it depends on have not succeeded yet
+    WaitingOnDependencies,
+    /// Ready, waiting for its session or a free slot
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Skipped,
+}
+
+/// The scheduler's view of one case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskInternals {
+    pub case_index: usize,
+    pub prompt_index: usize,
+    pub repository: String,
+    pub state: TaskState,
+    pub executor: CaseExecutor,
+    pub session_id: Option<String>,
+    /// Unfinished cases this one waits for
+    pub waiting_on: Vec<usize>,
+    /// Times the case was started; the engine does not retry cases, so at most 1
+    pub attempts: u32,
+    pub retries: u32,
+    pub running_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// The scheduler's view of a batch, for debugging stuck batches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchInternals {
+    pub batch_id: BatchId,
+    pub status: BatchStatus,
+    /// Concurrency the batch asked for
+    pub requested_concurrency: usize,
+    /// Concurrency it runs with, capped by the engine
+    pub concurrency: usize,
+    pub engine_limit: usize,
+    /// Free slots of the batch's own concurrency; `None` until it runs
+    pub permits_available: Option<usize>,
+    /// Process slots held by batch cases of all batches
+    pub batch_slots_in_use: usize,
+    /// Process slots held by live high-priority sessions, which batch cases yield to
+    pub high_priority_slots_in_use: usize,
+    pub max_attempts: u32,
+    pub tasks: Vec<TaskInternals>,
 }
 
 pub struct BatchEngine {
@@ -127,11 +234,17 @@ impl BatchEngine {
             return Err(BatchError::InvalidConfig("No repositories provided".to_string()));
         }
 
+        if let Some(shard) = &config.shard {
+            shard.validate().map_err(BatchError::InvalidConfig)?;
+        }
+
+        let dag = BatchDag::build(&config).map_err(BatchError::InvalidConfig)?;
+
         // Create progress channel
         let (progress_tx, progress_rx) = mpsc::unbounded_channel();
         
-        // Calculate total sessions (prompts × repositories)
-        let total_sessions = config.prompts.len() * config.repositories.len();
+        // Calculate total sessions (prompts × repositories, or this shard's share)
+        let total_sessions = batch_shards::shard_case_count(&config);
         
         // Create batch execution
         let batch_execution = BatchExecution {
@@ -141,6 +254,8 @@ impl BatchEngine {
             sessions: HashMap::new(),
             start_time: None,
             progress_tx: progress_tx.clone(),
+            dag,
+            semaphore: None,
         };
 
         // Store batch execution
@@ -177,131 +292,58 @@ impl BatchEngine {
             }
         };
 
+        let mut running = tokio::task::JoinSet::new();
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(config.concurrency.min(self.concurrency_limit)));
+
         // Update status to running
         {
             let mut batches = self.active_batches.write().await;
             if let Some(batch) = batches.get_mut(&batch_id) {
                 batch.status = BatchStatus::Running;
                 batch.start_time = Some(Instant::now());
+                batch.semaphore = Some(semaphore.clone());
             }
         }
 
-        let mut session_handles = Vec::new();
-        let semaphore = Arc::new(tokio::sync::Semaphore::new(config.concurrency.min(self.concurrency_limit)));
-
-        // Create sessions for each prompt/repository combination
-        for prompt in &config.prompts {
-            for repository in &config.repositories {
-                let session_id = Uuid::new_v4().to_string();
-                
-                // Create session using the enhanced session manager
-                let agent_mode = config.agent_mode.as_ref().map(|mode| {
-                    // Convert string to AgentMode enum
-                    match mode.as_str() {
-                        "geppetto:main" => AgentMode::Geppetto,
-                        "default" => AgentMode::Default,
-                        _ => AgentMode::Custom(mode.clone()),
-                    }
-                });
-
-                // Create session
-                match self.session_manager.create_session(
-                    format!("Batch_{}_Session", batch_id),
-                    prompt.clone(),
-                    repository.clone(),
-                    "main".to_string(),
-                    agent_mode,
-                ).await {
-                    Ok(_) => {
-                        // Track session in batch
-                        {
-                            let mut batches = self.active_batches.write().await;
-                            if let Some(batch) = batches.get_mut(&batch_id) {
-                                batch.sessions.insert(session_id.clone(), BatchSessionResult {
-                                    session_id: session_id.clone(),
-                                    status: SessionStatus::Pending,
-                                    start_time: None,
-                                    end_time: None,
-                                    error_message: None,
-                                    metrics: None,
-                                });
-                            }
-                        }
-
-                        // Create session execution task
-                        let permit = semaphore.clone().acquire_owned().await.unwrap();
-                        let batch_id_clone = batch_id.clone();
-                        let session_id_clone = session_id.clone();
-                        let session_manager = self.session_manager.clone();
-                        let active_batches = self.active_batches.clone();
-
-                        let handle = tokio::spawn(async move {
-                            let _permit = permit; // Hold permit until task completes
-                            
-                            let start_time = Instant::now();
-                            
-                            // Update session status to running
-                            {
-                                let mut batches = active_batches.write().await;
-                                if let Some(batch) = batches.get_mut(&batch_id_clone) {
-                                    if let Some(session) = batch.sessions.get_mut(&session_id_clone) {
-                                        session.status = SessionStatus::Running;
-                                        session.start_time = Some(start_time);
-                                    }
-                                }
-                            }
-
-                            // Execute session
-                            let result = session_manager.start_session(&session_id_clone).await;
-                            let end_time = Instant::now();
-
-                            // Update session result
-                            {
-                                let mut batches = active_batches.write().await;
-                                if let Some(batch) = batches.get_mut(&batch_id_clone) {
-                                    if let Some(session) = batch.sessions.get_mut(&session_id_clone) {
-                                        session.end_time = Some(end_time);
-                                        match &result {
-                                            Ok(_) => session.status = SessionStatus::Completed,
-                                            Err(e) => {
-                                                session.status = SessionStatus::Failed;
-                                                session.error_message = Some(e.to_string());
-                                            }
-                                        }
-                                    }
-                                    
-                                    // Send progress update
-                                    let progress = Self::calculate_progress(&batch_id_clone, batch);
-                                    let _ = batch.progress_tx.send(progress);
-                                }
-                            }
-
-                            (session_id_clone, result)
-                        });
-
-                        session_handles.push(handle);
-                    }
-                    Err(e) => {
-                        // Track failed session creation
-                        let mut batches = self.active_batches.write().await;
-                        if let Some(batch) = batches.get_mut(&batch_id) {
-                            batch.sessions.insert(session_id.clone(), BatchSessionResult {
-                                session_id: session_id.clone(),
-                                status: SessionStatus::Failed,
-                                start_time: None,
-                                end_time: None,
-                                error_message: Some(format!("Failed to create session: {}", e)),
-                                metrics: None,
-                            });
-                        }
+        // Start cases as their dependencies succeed, only as many as there are free
+        // slots so a case that becomes ready later can still go first by priority
+        loop {
+            let ready = {
+                let mut batches = self.active_batches.write().await;
+                match batches.get_mut(&batch_id) {
+                    Some(batch) if !matches!(batch.status, BatchStatus::Cancelled) => {
+                        batch.dag.take_ready(&config.prompts, semaphore.available_permits())
                     }
+                    _ => Vec::new(),
                 }
+            };
+            for (case_index, prompt) in ready {
+                self.start_case(&batch_id, &config, &semaphore, &mut running, case_index, prompt).await;
             }
-        }
 
-        // Wait for all sessions to complete
-        for handle in session_handles {
-            let _ = handle.await;
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let Ok((case_index, succeeded, output)) = joined else {
+                continue;
+            };
+            let mut batches = self.active_batches.write().await;
+            if let Some(batch) = batches.get_mut(&batch_id) {
+                for skipped in batch.dag.finish(case_index, succeeded, output) {
+                    let session_id = Uuid::new_v4().to_string();
+                    batch.sessions.insert(session_id.clone(), BatchSessionResult {
+                        session_id,
+                        case_index: skipped,
+                        status: SessionStatus::Failed,
+                        start_time: None,
+                        end_time: None,
+                        error_message: Some(format!("Skipped because case {} did not succeed", case_index)),
+                        metrics: None,
+                        logs: None,
+                        limit_exceeded: None,
+                    });
+                }
+            }
         }
 
         // Update final batch status
@@ -327,6 +369,157 @@ impl BatchEngine {
         Ok(())
     }
 
+    /// Create the session for a case and run it once a slot is free. The task
+    /// reports the case's outcome for the dependency graph.
+    async fn start_case(
+        &self,
+        batch_id: &BatchId,
+        config: &BatchConfig,
+        semaphore: &Arc<tokio::sync::Semaphore>,
+        running: &mut tokio::task::JoinSet<(usize, bool, CaseOutput)>,
+        case_index: usize,
+        prompt: String,
+    ) {
+        // Create session using the enhanced session manager
+        let agent_mode = config.agent_mode.as_ref().map(|mode| {
+            // Convert string to AgentMode enum
+            match mode.as_str() {
+                "geppetto:main" => AgentMode::Geppetto,
+                "default" => AgentMode::Default,
+                _ => AgentMode::Custom(mode.clone()),
+            }
+        });
+        let repository = &config.repositories[case_index % config.repositories.len()];
+
+        let session = match self.session_manager.create_session(
+            format!("Batch_{}_Session", batch_id),
+            prompt,
+            repository.clone(),
+            "main".to_string(),
+            agent_mode,
+        ).await {
+            Ok(session) => session,
+            Err(e) => {
+                // Track failed session creation
+                let session_id = Uuid::new_v4().to_string();
+                let mut batches = self.active_batches.write().await;
+                if let Some(batch) = batches.get_mut(batch_id) {
+                    batch.sessions.insert(session_id.clone(), BatchSessionResult {
+                        session_id,
+                        case_index,
+                        status: SessionStatus::Failed,
+                        start_time: None,
+                        end_time: None,
+                        error_message: Some(format!("Failed to create session: {}", e)),
+                        metrics: None,
+                        logs: None,
+                        limit_exceeded: None,
+                    });
+                }
+                running.spawn(async move { (case_index, false, CaseOutput::default()) });
+                return;
+            }
+        };
+
+        let session_id = session.id.clone();
+        // Track session in batch
+        let in_chain = {
+            let mut batches = self.active_batches.write().await;
+            match batches.get_mut(batch_id) {
+                Some(batch) => {
+                    batch.sessions.insert(session_id.clone(), BatchSessionResult {
+                        session_id: session_id.clone(),
+                        case_index,
+                        status: SessionStatus::Pending,
+                        start_time: None,
+                        end_time: None,
+                        error_message: None,
+                        metrics: None,
+                        logs: None,
+                        limit_exceeded: None,
+                    });
+                    batch.dag.set_session(case_index, &session_id);
+                    batch.dag.in_chain(case_index)
+                }
+                None => false,
+            }
+        };
+
+        // Create session execution task
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let batch_id_clone = batch_id.clone();
+        let session_id_clone = session_id;
+        let session_manager = self.session_manager.clone();
+        let active_batches = self.active_batches.clone();
+        let config = config.clone();
+        let slot_capacity = self.concurrency_limit;
+
+        running.spawn(async move {
+            let _permit = permit; // Hold permit until task completes
+            // Live high-priority sessions go ahead of queued cases
+            let _slot = crate::session_priority::PROCESS_SLOTS.acquire_batch(slot_capacity).await;
+
+            let start_time = Instant::now();
+
+            // Update session status to running
+            {
+                let mut batches = active_batches.write().await;
+                if let Some(batch) = batches.get_mut(&batch_id_clone) {
+                    if let Some(session) = batch.sessions.get_mut(&session_id_clone) {
+                        session.status = SessionStatus::Running;
+                        session.start_time = Some(start_time);
+                    }
+                }
+            }
+
+            // Execute session. Logged cases and cases in a dependency chain run to
+            // completion through the harness so their transcript and diff can be kept.
+            let (result, metrics, logs, diff, limit_exceeded) = if config.results_dir.is_some() || in_chain {
+                Self::run_harness_case(&session_manager, &session, &config, config.results_dir.as_deref(), case_index).await
+            } else {
+                (session_manager.start_session(&session_id_clone).await.map_err(|e| e.to_string()), None, None, None, None)
+            };
+            let end_time = Instant::now();
+            let output = CaseOutput {
+                diff,
+                logs_dir: config.results_dir.as_ref().zip(logs.as_ref()).map(|(dir, logs)| dir.join(&logs.dir)),
+            };
+
+            // Update session result
+            let mut case_result = None;
+            {
+                let mut batches = active_batches.write().await;
+                if let Some(batch) = batches.get_mut(&batch_id_clone) {
+                    if let Some(session) = batch.sessions.get_mut(&session_id_clone) {
+                        session.end_time = Some(end_time);
+                        session.metrics = metrics;
+                        session.logs = logs;
+                        session.limit_exceeded = limit_exceeded;
+                        match &result {
+                            Ok(_) => session.status = SessionStatus::Completed,
+                            Err(e) => {
+                                session.status = SessionStatus::Failed;
+                                session.error_message = Some(e.clone());
+                            }
+                        }
+                        case_result = Some(Self::case_result(&config, session));
+                    }
+
+                    // Send progress update
+                    let progress = Self::calculate_progress(&batch_id_clone, batch);
+                    let _ = batch.progress_tx.send(progress);
+                }
+            }
+            if let (Some(results_dir), Some(case_result)) = (&config.results_dir, case_result.filter(|c| c.logs.is_some())) {
+                if let Err(e) = batch_case_logs::write_case_result(results_dir, &case_result) {
+                    log::warn!("Failed to write result.json for case {}: {}", case_index, e);
+                }
+            }
+
+            (case_index, result.is_ok(), output)
+        });
+    }
+
     fn calculate_progress(batch_id: &str, batch: &BatchExecution) -> BatchProgress {
         let total_sessions = batch.sessions.len();
         let completed_sessions = batch.sessions.values()
@@ -339,6 +532,15 @@ impl BatchEngine {
             .filter(|s| matches!(s.status, SessionStatus::Running))
             .count();
 
+        let mut limit_exceeded: Vec<LimitExceededCase> = batch.sessions.values()
+            .filter_map(|s| Some(LimitExceededCase {
+                case_index: s.case_index,
+                session_id: s.session_id.clone(),
+                exceeded: s.limit_exceeded.clone()?,
+            }))
+            .collect();
+        limit_exceeded.sort_by_key(|c| c.case_index);
+
         let progress_percent = if total_sessions > 0 {
             ((completed_sessions + failed_sessions) as f32 / total_sessions as f32) * 100.0
         } else {
@@ -353,6 +555,8 @@ impl BatchEngine {
             running_sessions,
             progress_percent,
             status: batch.status.clone(),
+            blocked: batch.dag.blocked(),
+            limit_exceeded,
         }
     }
 
@@ -382,12 +586,478 @@ impl BatchEngine {
         }
     }
 
+    /// Cases of a batch with their dependencies and status
+    pub async fn get_batch_dag(&self, batch_id: &str) -> Result<BatchDag, BatchError> {
+        let batches = self.active_batches.read().await;
+        let batch = batches.get(batch_id).ok_or_else(|| BatchError::BatchNotFound(batch_id.to_string()))?;
+        Ok(batch.dag.clone())
+    }
+
+    /// Scheduler state of a batch: its cases, how they run and the slots they hold
+    pub async fn get_batch_internals(&self, batch_id: &str) -> Result<BatchInternals, BatchError> {
+        let batches = self.active_batches.read().await;
+        let batch = batches.get(batch_id).ok_or_else(|| BatchError::BatchNotFound(batch_id.to_string()))?;
+        let slots_in_use = crate::session_priority::PROCESS_SLOTS.in_use();
+        Ok(Self::internals(batch, self.concurrency_limit, slots_in_use, Instant::now()))
+    }
+
+    fn internals(batch: &BatchExecution, engine_limit: usize, slots_in_use: (usize, usize), now: Instant) -> BatchInternals {
+        let config = &batch.config;
+        // Sessions by case; a case has at most one as nothing is retried
+        let by_case: HashMap<usize, &BatchSessionResult> = batch.sessions.values().map(|s| (s.case_index, s)).collect();
+        let tasks = batch
+            .dag
+            .nodes
+            .iter()
+            .map(|node| {
+                let session = by_case.get(&node.case_index).copied();
+                let waiting_on: Vec<usize> = node
+                    .depends_on
+                    .iter()
+                    .copied()
+                    .filter(|d| batch.dag.nodes.iter().any(|n| n.case_index == *d && n.status != NodeStatus::Succeeded))
+                    .collect();
+                let state = match (node.status, session.map(|s| &s.status)) {
+                    (NodeStatus::Skipped, _) => TaskState::Skipped,
+                    (_, Some(SessionStatus::Running)) => TaskState::Running,
+                    (_, Some(SessionStatus::Completed)) => TaskState::Completed,
+                    (_, Some(SessionStatus::Failed)) => TaskState::Failed,
+                    (NodeStatus::Waiting, None) if !waiting_on.is_empty() => TaskState::WaitingOnDependencies,
+                    _ => TaskState::Queued,
+                };
+                let executor = if config.results_dir.is_some() || batch.dag.in_chain(node.case_index) {
+                    CaseExecutor::Harness
+                } else {
+                    CaseExecutor::SessionManager
+                };
+                let attempts = session.map_or(0, |s| u32::from(s.start_time.is_some()));
+                TaskInternals {
+                    case_index: node.case_index,
+                    prompt_index: node.prompt_index,
+                    repository: config
+                        .repositories
+                        .get(node.repository_index)
+                        .map(|r| r.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    state,
+                    executor,
+                    session_id: session.map(|s| s.session_id.clone()),
+                    waiting_on,
+                    attempts,
+                    retries: attempts.saturating_sub(1),
+                    running_ms: session
+                        .filter(|s| matches!(s.status, SessionStatus::Running))
+                        .and_then(|s| s.start_time)
+                        .map(|start| (now - start).as_millis() as u64),
+                    last_error: session.and_then(|s| s.error_message.clone()),
+                }
+            })
+            .collect();
+
+        BatchInternals {
+            batch_id: batch.id.clone(),
+            status: batch.status.clone(),
+            requested_concurrency: config.concurrency,
+            concurrency: config.concurrency.min(engine_limit),
+            engine_limit,
+            permits_available: batch.semaphore.as_ref().map(|s| s.available_permits()),
+            batch_slots_in_use: slots_in_use.0,
+            high_priority_slots_in_use: slots_in_use.1,
+            max_attempts: config.retry_policy.as_ref().map_or(1, |r| r.max_attempts),
+            tasks,
+        }
+    }
+
     pub async fn list_active_batches(&self) -> Vec<BatchProgress> {
         let batches = self.active_batches.read().await;
         batches.iter()
             .map(|(batch_id, batch)| Self::calculate_progress(batch_id, batch))
             .collect()
     }
+
+    fn case_result(config: &BatchConfig, session: &BatchSessionResult) -> CaseResult {
+        let repository_count = config.repositories.len().max(1);
+        CaseResult {
+            case_index: session.case_index,
+            prompt: config.prompts.get(session.case_index / repository_count).cloned().unwrap_or_default(),
+            repository: config.repositories
+                .get(session.case_index % repository_count)
+                .map(|r| r.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            status: session.status.clone(),
+            error_message: session.error_message.clone(),
+            execution_time_ms: session.start_time.zip(session.end_time).map(|(start, end)| (end - start).as_millis() as u64),
+            metrics: session.metrics.clone(),
+            logs: session.logs.clone(),
+        }
+    }
+
+    /// Run one case to completion through the Amp harness, writing its log
+    /// directory when the batch keeps logs. Also returns the diff it left, and
+    /// the limit its agent was stopped for passing: the batch's timeout or the
+    /// session's memory limit.
+    async fn run_harness_case(
+        session_manager: &EnhancedSessionManager,
+        session: &Session,
+        config: &BatchConfig,
+        results_dir: Option<&Path>,
+        case_index: usize,
+    ) -> (Result<(), String>, Option<SessionMetrics>, Option<CaseLogPaths>, Option<String>, Option<LimitExceeded>) {
+        let mut env = session_manager.process_env(session);
+        if let Some(mode) = &config.agent_mode {
+            env.insert("AMP_EXPERIMENTAL_AGENT_MODE".to_string(), mode.clone());
+        }
+        let working_dir = if session.worktree_path.exists() { session.worktree_path.clone() } else { session.repo_root.clone() };
+        let limits = ProcessLimits {
+            max_execution_time: Duration::from_secs(config.timeout_sec),
+            ..session.runtime_config.process_limits.clone()
+        };
+        let spec = HarnessSpec { prompt: session.prompt.clone(), working_dir, env, limits: Some(limits.clone()) };
+        let mut artifacts = CaseArtifacts { prompt: spec.prompt.clone(), environment: spec.env.clone(), ..Default::default() };
+
+        let start = Instant::now();
+        let outcome = async {
+            let mut process = AmpHarness.spawn(&spec).await?;
+            process.close_input();
+            let exceeded = process.drain_within(&limits).await;
+            Ok::<_, unified_core::HarnessError>((process.finish().await?, exceeded))
+        }
+        .await;
+
+        let (result, metrics, limit_exceeded) = match outcome {
+            Ok((outcome, exceeded)) => {
+                let metrics = SessionMetrics {
+                    iterations: outcome.metrics.iterations,
+                    tokens_used: outcome.metrics.tokens_used.min(u32::MAX as u64) as u32,
+                    tools_invoked: outcome.metrics.tools_used.values().sum(),
+                    execution_time_ms: start.elapsed().as_millis() as u64,
+                };
+                artifacts.transcript = outcome.transcript;
+                artifacts.diff = outcome.diff;
+                let result = match (&exceeded, outcome.error_message) {
+                    (Some(exceeded), _) => Err(exceeded.to_string()),
+                    (None, Some(error)) => Err(error),
+                    (None, None) => Ok(()),
+                };
+                (result, Some(metrics), exceeded)
+            }
+            Err(e) => (Err(e.to_string()), None, None),
+        };
+
+        let logs = results_dir.and_then(|results_dir| {
+            batch_case_logs::write_case_artifacts(results_dir, &config.name, case_index, &artifacts)
+                .map_err(|e| log::warn!("Failed to write logs for case {}: {}", case_index, e))
+                .ok()
+        });
+        (result, metrics, logs, artifacts.diff, limit_exceeded)
+    }
+
+    /// Per-case results of this instance's shard, in the form `merge_batch_results` reads
+    pub async fn result_file(&self, batch_id: &str) -> Result<BatchResultFile, BatchError> {
+        let batches = self.active_batches.read().await;
+        let batch = batches.get(batch_id).ok_or_else(|| BatchError::BatchNotFound(batch_id.to_string()))?;
+        let config = &batch.config;
+
+        let mut results: Vec<CaseResult> = batch.sessions.values().map(|session| Self::case_result(config, session)).collect();
+        results.sort_by_key(|r| r.case_index);
+
+        Ok(BatchResultFile {
+            version: batch_shards::RESULT_FILE_VERSION,
+            batch_name: config.name.clone(),
+            fingerprint: batch_shards::dataset_fingerprint(config),
+            total_cases: config.prompts.len() * config.repositories.len(),
+            shards: vec![config.shard.unwrap_or(ShardSpec { index: 0, count: 1 })],
+            results,
+        })
+    }
+
+    /// Up to `limit` case results after case `after`, whether more follow, and
+    /// the batch's results directory
+    pub async fn case_results_page(
+        &self,
+        batch_id: &str,
+        after: Option<usize>,
+        limit: usize,
+    ) -> Result<(Vec<CaseResult>, bool, Option<PathBuf>), BatchError> {
+        let batches = self.active_batches.read().await;
+        let batch = batches.get(batch_id).ok_or_else(|| BatchError::BatchNotFound(batch_id.to_string()))?;
+
+        let mut sessions: Vec<&BatchSessionResult> = batch
+            .sessions
+            .values()
+            .filter(|session| after.is_none_or(|after| session.case_index > after))
+            .collect();
+        sessions.sort_by_key(|session| session.case_index);
+        let more = sessions.len() > limit;
+        let results = sessions.into_iter().take(limit).map(|session| Self::case_result(&batch.config, session)).collect();
+        Ok((results, more, batch.config.results_dir.clone()))
+    }
+}
+
+/// Agent modes the Amp CLI is known to accept
+const KNOWN_AGENT_MODES: &[&str] = &["default", "geppetto", "geppetto:main", "claudetto", "claudetto:main", "gronk:fast", "bolt"];
+
+/// Cap on how many bad dataset lines are reported individually
+const MAX_REPORTED_DATASET_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ValidationSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub severity: ValidationSeverity,
+    pub field: String,
+    pub message: String,
+}
+
+/// Averages from previously completed batch sessions, used for estimates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalMetrics {
+    pub sample_size: usize,
+    pub avg_session_secs: Option<f64>,
+    pub avg_tokens_per_session: Option<f64>,
+}
+
+/// Rough projection of a batch run. Cost is expressed in tokens since
+/// per-model pricing is not tracked locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEstimate {
+    pub total_sessions: usize,
+    pub effective_concurrency: usize,
+    pub sample_size: usize,
+    pub estimated_wall_time_secs: Option<u64>,
+    pub estimated_total_tokens: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchValidationReport {
+    pub valid: bool,
+    pub total_sessions: usize,
+    pub dataset_cases: Option<usize>,
+    pub issues: Vec<ValidationIssue>,
+    pub estimate: BatchEstimate,
+}
+
+impl BatchValidationReport {
+    fn push(&mut self, severity: ValidationSeverity, field: &str, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            severity,
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+impl BatchEngine {
+    /// Sessions that will actually run at once: the requested concurrency capped
+    /// by the engine limit and the number of sessions
+    pub fn effective_concurrency(&self, config: &BatchConfig) -> usize {
+        let total_sessions = batch_shards::shard_case_count(config);
+        config.concurrency.min(self.concurrency_limit).min(total_sessions.max(1))
+    }
+
+    /// Check a batch definition without starting any sessions
+    pub fn validate_batch(
+        &self,
+        config: &BatchConfig,
+        dataset_path: Option<&Path>,
+        history: Option<&HistoricalMetrics>,
+    ) -> BatchValidationReport {
+        let total_sessions = batch_shards::shard_case_count(config);
+        let effective_concurrency = self.effective_concurrency(config);
+        let mut report = BatchValidationReport {
+            valid: true,
+            total_sessions,
+            dataset_cases: None,
+            issues: Vec::new(),
+            estimate: BatchEstimate {
+                total_sessions,
+                effective_concurrency,
+                sample_size: 0,
+                estimated_wall_time_secs: None,
+                estimated_total_tokens: None,
+            },
+        };
+
+        if config.name.trim().is_empty() {
+            report.push(ValidationSeverity::Warning, "name", "Batch has no name");
+        }
+
+        if config.prompts.is_empty() {
+            report.push(ValidationSeverity::Error, "prompts", "No prompts provided");
+        }
+        for (index, prompt) in config.prompts.iter().enumerate() {
+            if prompt.trim().is_empty() {
+                report.push(ValidationSeverity::Error, "prompts", format!("Prompt {} is empty", index + 1));
+            }
+        }
+
+        if config.repositories.is_empty() {
+            report.push(ValidationSeverity::Error, "repositories", "No repositories provided");
+        }
+        for repository in &config.repositories {
+            if !repository.is_dir() {
+                report.push(ValidationSeverity::Error, "repositories", format!("Repository not found: {}", repository.display()));
+            } else if !repository.join(".git").exists() {
+                report.push(ValidationSeverity::Error, "repositories", format!("Not a Git repository: {}", repository.display()));
+            }
+        }
+
+        if let Some(mode) = &config.agent_mode {
+            Self::validate_agent_mode(mode, &mut report);
+        }
+
+        if let Some(toolbox_path) = &config.toolbox_path {
+            if !toolbox_path.is_dir() {
+                report.push(ValidationSeverity::Error, "toolboxPath", format!("Toolbox directory not found: {}", toolbox_path.display()));
+            }
+        }
+
+        if let Some(dataset_path) = dataset_path {
+            match Self::validate_dataset(dataset_path) {
+                Ok((cases, errors)) => {
+                    report.dataset_cases = Some(cases);
+                    if cases == 0 {
+                        report.push(ValidationSeverity::Error, "dataset", "Dataset contains no cases");
+                    }
+                    for error in errors {
+                        report.push(ValidationSeverity::Error, "dataset", error);
+                    }
+                }
+                Err(e) => report.push(ValidationSeverity::Error, "dataset", e),
+            }
+        }
+
+        if config.concurrency == 0 {
+            report.push(ValidationSeverity::Error, "concurrency", "Concurrency must be at least 1");
+        } else if config.concurrency > self.concurrency_limit {
+            report.push(
+                ValidationSeverity::Warning,
+                "concurrency",
+                format!("Concurrency {} exceeds engine limit and will be capped at {}", config.concurrency, self.concurrency_limit),
+            );
+        } else if total_sessions > 0 && config.concurrency > total_sessions {
+            report.push(
+                ValidationSeverity::Warning,
+                "concurrency",
+                format!("Concurrency {} is higher than the {} sessions in this batch", config.concurrency, total_sessions),
+            );
+        }
+
+        if let Some(shard) = &config.shard {
+            if let Err(message) = shard.validate() {
+                report.push(ValidationSeverity::Error, "shard", message);
+            }
+        }
+
+        if !config.dependencies.is_empty() {
+            if let Err(message) = BatchDag::build(config) {
+                report.push(ValidationSeverity::Error, "dependencies", message);
+            }
+        }
+
+        if config.timeout_sec == 0 {
+            report.push(ValidationSeverity::Error, "timeoutSec", "Timeout must be greater than zero");
+        }
+
+        if let Some(retry) = &config.retry_policy {
+            if retry.max_attempts == 0 {
+                report.push(ValidationSeverity::Error, "retryPolicy", "Retry policy must allow at least one attempt");
+            }
+        }
+
+        if let Some(history) = history {
+            report.estimate.sample_size = history.sample_size;
+            if history.sample_size > 0 && effective_concurrency > 0 {
+                if let Some(avg_secs) = history.avg_session_secs {
+                    let waves = total_sessions.div_ceil(effective_concurrency);
+                    report.estimate.estimated_wall_time_secs = Some((waves as f64 * avg_secs).ceil() as u64);
+                    if avg_secs > config.timeout_sec as f64 {
+                        report.push(
+                            ValidationSeverity::Warning,
+                            "timeoutSec",
+                            format!("Sessions have historically taken {:.0}s, longer than the {}s timeout", avg_secs, config.timeout_sec),
+                        );
+                    }
+                }
+                if let Some(avg_tokens) = history.avg_tokens_per_session {
+                    report.estimate.estimated_total_tokens = Some((avg_tokens * total_sessions as f64).round() as u64);
+                }
+            }
+        }
+
+        report.valid = !report.issues.iter().any(|i| i.severity == ValidationSeverity::Error);
+        report
+    }
+
+    fn validate_agent_mode(mode: &str, report: &mut BatchValidationReport) {
+        let well_formed = !mode.is_empty()
+            && mode.split(':').count() <= 2
+            && mode.split(':').all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            });
+
+        if !well_formed {
+            report.push(ValidationSeverity::Error, "agentMode", format!("Invalid agent mode: '{}'", mode));
+        } else if !KNOWN_AGENT_MODES.contains(&mode) {
+            report.push(
+                ValidationSeverity::Warning,
+                "agentMode",
+                format!("Agent mode '{}' is not a known mode and will be passed through as custom", mode),
+            );
+        }
+    }
+
+    /// Parse a JSON array or JSONL dataset, returning the case count and per-case errors
+    fn validate_dataset(path: &Path) -> Result<(usize, Vec<String>), String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read dataset {}: {}", path.display(), e))?;
+
+        let mut errors = Vec::new();
+        let mut cases = 0;
+        let mut check_case = |label: String, case: &serde_json::Value, errors: &mut Vec<String>| {
+            let has_prompt = ["prompt", "problem_statement"]
+                .iter()
+                .any(|key| case.get(key).and_then(|v| v.as_str()).is_some_and(|s| !s.trim().is_empty()));
+            if !case.is_object() {
+                errors.push(format!("{}: case is not a JSON object", label));
+            } else if !has_prompt {
+                errors.push(format!("{}: case has no 'prompt' or 'problem_statement'", label));
+            } else {
+                cases += 1;
+            }
+        };
+
+        if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            let value: serde_json::Value = serde_json::from_str(&content)
+                .map_err(|e| format!("Dataset is not valid JSON: {}", e))?;
+            let items = value.as_array().ok_or("JSON dataset must be an array of cases")?;
+            for (index, item) in items.iter().enumerate() {
+                check_case(format!("case {}", index + 1), item, &mut errors);
+            }
+        } else {
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<serde_json::Value>(line) {
+                    Ok(item) => check_case(format!("line {}", index + 1), &item, &mut errors),
+                    Err(e) => errors.push(format!("line {}: invalid JSON ({})", index + 1, e)),
+                }
+            }
+        }
+
+        if errors.len() > MAX_REPORTED_DATASET_ERRORS {
+            let remaining = errors.len() - MAX_REPORTED_DATASET_ERRORS;
+            errors.truncate(MAX_REPORTED_DATASET_ERRORS);
+            errors.push(format!("...and {} more invalid cases", remaining));
+        }
+
+        Ok((cases, errors))
+    }
 }
 
 // Clone implementation for BatchEngine
@@ -462,6 +1132,10 @@ mod tests {
             retry_policy: None,
             agent_mode: None,
             toolbox_path: None,
+            shard: None,
+            results_dir: None,
+            dependencies: Vec::new(),
+            priorities: Vec::new(),
         };
 
         // Mock session manager
@@ -475,6 +1149,88 @@ mod tests {
         assert!(matches!(result.unwrap_err(), BatchError::InvalidConfig(_)));
     }
 
+    fn validation_engine() -> BatchEngine {
+        use crate::runtime_env::{AmpConfig, EnvKind, RuntimeEnvironment, ToolboxConfig};
+
+        let runtime_env = RuntimeEnvironment {
+            env_kind: EnvKind::Production,
+            amp_config: AmpConfig { server_url: None, cli_path: None, agent_mode: None },
+            toolbox_config: ToolboxConfig { toolbox_paths: vec![], max_file_count: 1000, max_total_size: 1024 },
+            agent_mode: None,
+            worktree_path: None,
+        };
+        BatchEngine::new(Arc::new(EnhancedSessionManager::new(Default::default(), runtime_env)))
+    }
+
+    fn validation_config(repo: PathBuf) -> BatchConfig {
+        BatchConfig {
+            name: "Validate".to_string(),
+            prompts: vec!["Fix the bug".to_string(), "Add tests".to_string()],
+            repositories: vec![repo],
+            concurrency: 2,
+            timeout_sec: 600,
+            retry_policy: None,
+            agent_mode: Some("geppetto:main".to_string()),
+            toolbox_path: None,
+            shard: None,
+            results_dir: None,
+            dependencies: Vec::new(),
+            priorities: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_batch_reports_errors() {
+        let engine = validation_engine();
+        let mut config = validation_config(PathBuf::from("/definitely/not/a/repo"));
+        config.agent_mode = Some("bad mode".to_string());
+        config.concurrency = 0;
+
+        let report = engine.validate_batch(&config, None, None);
+
+        assert!(!report.valid);
+        assert!(report.issues.iter().any(|i| i.field == "repositories"));
+        assert!(report.issues.iter().any(|i| i.field == "agentMode" && i.severity == ValidationSeverity::Error));
+        assert!(report.issues.iter().any(|i| i.field == "concurrency" && i.severity == ValidationSeverity::Error));
+    }
+
+    #[test]
+    fn test_validate_batch_dataset_and_estimate() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp.path().join(".git")).unwrap();
+        let dataset = temp.path().join("cases.jsonl");
+        std::fs::write(&dataset, "{\"prompt\": \"one\"}\n\n{\"problem_statement\": \"two\"}\nnot json\n{\"id\": 3}\n").unwrap();
+
+        let engine = validation_engine();
+        let config = validation_config(temp.path().to_path_buf());
+        let history = HistoricalMetrics {
+            sample_size: 5,
+            avg_session_secs: Some(120.0),
+            avg_tokens_per_session: Some(1500.0),
+        };
+
+        let report = engine.validate_batch(&config, Some(&dataset), Some(&history));
+
+        assert_eq!(report.dataset_cases, Some(2));
+        assert_eq!(report.issues.iter().filter(|i| i.field == "dataset").count(), 2);
+        assert_eq!(report.estimate.effective_concurrency, 2);
+        assert_eq!(report.estimate.estimated_wall_time_secs, Some(120));
+        assert_eq!(report.estimate.estimated_total_tokens, Some(3000));
+    }
+
+    #[test]
+    fn test_validate_batch_accepts_clean_config() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp.path().join(".git")).unwrap();
+
+        let report = validation_engine().validate_batch(&validation_config(temp.path().to_path_buf()), None, None);
+
+        assert!(report.valid);
+        assert!(report.issues.is_empty());
+        assert_eq!(report.total_sessions, 2);
+        assert_eq!(report.estimate.estimated_wall_time_secs, None);
+    }
+
     #[tokio::test]
     async fn test_batch_progress_calculation() {
         let batch_execution = BatchExecution {
@@ -488,37 +1244,115 @@ mod tests {
                 retry_policy: None,
                 agent_mode: None,
                 toolbox_path: None,
+                shard: None,
+                results_dir: None,
+                dependencies: Vec::new(),
+                priorities: Vec::new(),
             },
             status: BatchStatus::Running,
             sessions: {
                 let mut sessions = HashMap::new();
                 sessions.insert("session1".to_string(), BatchSessionResult {
                     session_id: "session1".to_string(),
+                    case_index: 0,
                     status: SessionStatus::Completed,
                     start_time: None,
                     end_time: None,
                     error_message: None,
                     metrics: None,
+                    logs: None,
+                    limit_exceeded: None,
                 });
                 sessions.insert("session2".to_string(), BatchSessionResult {
                     session_id: "session2".to_string(),
+                    case_index: 1,
                     status: SessionStatus::Running,
                     start_time: None,
                     end_time: None,
                     error_message: None,
                     metrics: None,
+                    logs: None,
+                    limit_exceeded: None,
+                });
+                sessions.insert("session3".to_string(), BatchSessionResult {
+                    session_id: "session3".to_string(),
+                    case_index: 2,
+                    status: SessionStatus::Failed,
+                    start_time: None,
+                    end_time: None,
+                    error_message: Some("Used 3000MB of memory, over the 2048MB limit".to_string()),
+                    metrics: None,
+                    logs: None,
+                    limit_exceeded: Some(LimitExceeded::Memory { used_mb: 3000, limit_mb: 2048 }),
                 });
                 sessions
             },
             start_time: Some(Instant::now()),
             progress_tx: mpsc::unbounded_channel().0,
+            dag: BatchDag::default(),
+            semaphore: None,
         };
 
         let progress = BatchEngine::calculate_progress("test", &batch_execution);
         
-        assert_eq!(progress.total_sessions, 2);
+        assert_eq!(progress.total_sessions, 3);
         assert_eq!(progress.completed_sessions, 1);
+        assert_eq!(progress.failed_sessions, 1);
         assert_eq!(progress.running_sessions, 1);
-        assert_eq!(progress.progress_percent, 50.0);
+        assert_eq!(progress.limit_exceeded.len(), 1);
+        assert_eq!((progress.limit_exceeded[0].case_index, progress.limit_exceeded[0].session_id.as_str()), (2, "session3"));
+    }
+
+    #[test]
+    fn test_batch_internals_report_scheduler_state() {
+        let mut config = validation_config(PathBuf::from("/repo"));
+        config.prompts = vec!["first".to_string(), "second".to_string(), "third".to_string()];
+        config.concurrency = 12;
+        config.dependencies = vec![TaskDependency { prompt: 1, after: 0, include_diff: false }];
+        let mut dag = BatchDag::build(&config).unwrap();
+        let ready: Vec<usize> = dag.take_ready(&config.prompts, usize::MAX).into_iter().map(|(case, _)| case).collect();
+        assert_eq!(ready, vec![0, 2]);
+        dag.finish(2, false, CaseOutput::default());
+
+        let start = Instant::now();
+        let session = |id: &str, case_index, status, error: Option<&str>| BatchSessionResult {
+            session_id: id.to_string(),
+            case_index,
+            status,
+            start_time: Some(start),
+            end_time: None,
+            error_message: error.map(str::to_string),
+            metrics: None,
+            logs: None,
+            limit_exceeded: None,
+        };
+        let batch = BatchExecution {
+            id: "batch".to_string(),
+            config,
+            status: BatchStatus::Running,
+            sessions: [
+                ("s0".to_string(), session("s0", 0, SessionStatus::Running, None)),
+                ("s2".to_string(), session("s2", 2, SessionStatus::Failed, Some("Timed out after 300s"))),
+            ]
+            .into(),
+            start_time: Some(start),
+            progress_tx: mpsc::unbounded_channel().0,
+            dag,
+            semaphore: Some(Arc::new(tokio::sync::Semaphore::new(8))),
+        };
+
+        let internals = BatchEngine::internals(&batch, 8, (1, 2), start + Duration::from_millis(1500));
+        assert_eq!((internals.requested_concurrency, internals.concurrency), (12, 8));
+        assert_eq!(internals.permits_available, Some(8));
+        assert_eq!((internals.batch_slots_in_use, internals.high_priority_slots_in_use), (1, 2));
+
+        let [first, second, third] = &internals.tasks[..] else { panic!("expected three tasks") };
+        assert_eq!((first.state, first.executor, first.attempts), (TaskState::Running, CaseExecutor::Harness, 1));
+        assert_eq!(first.running_ms, Some(1500));
+        assert_eq!((second.state, second.session_id.as_deref()), (TaskState::WaitingOnDependencies, None));
+        assert_eq!(second.waiting_on, vec![0]);
+        assert_eq!((third.state, third.executor), (TaskState::Failed, CaseExecutor::SessionManager));
+        assert_eq!(third.last_error.as_deref(), Some("Timed out after 300s"));
+        assert_eq!(third.running_ms, None);
     }
 }
@@ -0,0 +1,302 @@
+//! Simulating a batch before running it
+//!
+//! `estimate_batch` predicts how long a batch will take under its concurrency
+//! and how many tokens it will use, as ranges rather than a single average. Each
+//! case draws a duration and token count from completed batch sessions that
+//! resemble it. Those are sessions with the same agent mode and, where the case's
+//! repository size is known, on repositories of similar size. The draws are then
+//! scheduled onto the batch's workers, and this is repeated many times. The 10th,
+//! 50th and 90th percentiles of the simulated runs form the estimate.
+//!
+//! Repository size is the number of files git tracks. History does not record
+//! which repository a session ran on, so a past session counts as having run on a
+//! repository of its batch's average size.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::batch_engine::BatchConfig;
+use crate::batch_shards;
+
+/// Fewer matching sessions than this and the estimate widens to less similar ones
+pub const MIN_SAMPLES: usize = 3;
+
+/// Simulated runs per estimate
+pub const TRIALS: usize = 500;
+
+/// Past sessions read from history, newest first
+const MAX_HISTORY: i64 = 2000;
+
+/// Repositories within this factor of a case's size count as similar
+const SIZE_FACTOR: f64 = 2.0;
+
+/// One completed batch session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSample {
+    pub agent_mode: Option<String>,
+    pub secs: f64,
+    pub tokens: Option<f64>,
+    pub repo_files: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EstimateRange {
+    pub p10: u64,
+    pub p50: u64,
+    pub p90: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSimulation {
+    pub total_sessions: usize,
+    pub effective_concurrency: usize,
+    /// Past sessions the estimate drew from
+    pub sample_size: usize,
+    /// History had enough sessions with the batch's agent mode
+    pub agent_mode_matched: bool,
+    /// Cases estimated from sessions on similarly sized repositories
+    pub size_matched_cases: usize,
+    pub trials: usize,
+    /// `None` without any history
+    pub wall_time_secs: Option<EstimateRange>,
+    /// Tokens rather than money, as per-model pricing is not tracked locally
+    pub total_tokens: Option<EstimateRange>,
+}
+
+/// Small deterministic generator so an estimate is the same every time it is asked for
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn percentiles(mut values: Vec<f64>) -> EstimateRange {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize].round() as u64;
+    EstimateRange { p10: at(0.1), p50: at(0.5), p90: at(0.9) }
+}
+
+/// Simulate running one case per entry of `case_repo_files` (that case's
+/// repository size, if known) on `concurrency` workers
+pub fn simulate(
+    case_repo_files: &[Option<f64>],
+    agent_mode: Option<&str>,
+    concurrency: usize,
+    timeout_secs: u64,
+    samples: &[SessionSample],
+) -> BatchSimulation {
+    let mut simulation = BatchSimulation {
+        total_sessions: case_repo_files.len(),
+        effective_concurrency: concurrency,
+        sample_size: 0,
+        agent_mode_matched: false,
+        size_matched_cases: 0,
+        trials: 0,
+        wall_time_secs: None,
+        total_tokens: None,
+    };
+    if samples.is_empty() || case_repo_files.is_empty() || concurrency == 0 {
+        return simulation;
+    }
+
+    let same_mode: Vec<&SessionSample> = samples.iter().filter(|s| s.agent_mode.as_deref() == agent_mode).collect();
+    simulation.agent_mode_matched = same_mode.len() >= MIN_SAMPLES;
+    let mode_pool = if simulation.agent_mode_matched { same_mode } else { samples.iter().collect() };
+    simulation.sample_size = mode_pool.len();
+
+    // Pools are shared between cases on repositories of the same size
+    let mut pools: HashMap<Option<u64>, Vec<&SessionSample>> = HashMap::new();
+    let mut case_pools = Vec::with_capacity(case_repo_files.len());
+    for files in case_repo_files {
+        let key = files.map(f64::to_bits);
+        let pool = pools.entry(key).or_insert_with(|| {
+            let similar: Vec<&SessionSample> = match files {
+                Some(files) => mode_pool
+                    .iter()
+                    .copied()
+                    .filter(|s| s.repo_files.is_some_and(|r| r.max(1.0) / files.max(1.0) <= SIZE_FACTOR && files.max(1.0) / r.max(1.0) <= SIZE_FACTOR))
+                    .collect(),
+                None => Vec::new(),
+            };
+            if similar.len() >= MIN_SAMPLES { similar } else { mode_pool.clone() }
+        });
+        if pool.len() < mode_pool.len() {
+            simulation.size_matched_cases += 1;
+        }
+        case_pools.push(key);
+    }
+    let has_tokens = mode_pool.iter().any(|s| s.tokens.is_some());
+
+    let mut rng = SplitMix64(0x0a5c_ade5 ^ case_repo_files.len() as u64);
+    let mut wall_times = Vec::with_capacity(TRIALS);
+    let mut totals = Vec::with_capacity(TRIALS);
+    let mut workers = vec![0.0_f64; concurrency];
+    for _ in 0..TRIALS {
+        workers.iter_mut().for_each(|w| *w = 0.0);
+        let mut tokens = 0.0;
+        for key in &case_pools {
+            let pool = &pools[key];
+            let sample = pool[rng.below(pool.len())];
+            // Cases start in order on whichever worker frees up first
+            let worker = workers
+                .iter_mut()
+                .min_by(|a, b| a.total_cmp(b))
+                .expect("at least one worker");
+            *worker += sample.secs.min(timeout_secs as f64);
+            tokens += sample.tokens.unwrap_or(0.0);
+        }
+        wall_times.push(workers.iter().copied().fold(0.0, f64::max));
+        totals.push(tokens);
+    }
+
+    simulation.trials = TRIALS;
+    simulation.wall_time_secs = Some(percentiles(wall_times));
+    simulation.total_tokens = has_tokens.then(|| percentiles(totals));
+    simulation
+}
+
+/// Files git tracks in `repo`, or `None` outside a repository
+pub fn repo_file_count(repo: &Path) -> Option<f64> {
+    let output = Command::new("git").arg("-C").arg(repo).args(["ls-files", "-z"]).output().ok()?;
+    output.status.success().then(|| output.stdout.iter().filter(|b| **b == 0).count() as f64)
+}
+
+/// Size of each case's repository, in case order
+pub fn case_repo_files(config: &BatchConfig, sizes: &HashMap<PathBuf, Option<f64>>) -> Vec<Option<f64>> {
+    let total = config.prompts.len() * config.repositories.len();
+    (0..total)
+        .filter(|i| batch_shards::in_shard(config.shard.as_ref(), *i))
+        .map(|i| sizes.get(&config.repositories[i % config.repositories.len()]).copied().flatten())
+        .collect()
+}
+
+/// Completed batch sessions with the repositories their batch ran on
+pub async fn load_history(db: &sqlx::SqlitePool) -> Result<Vec<(SessionSample, Vec<PathBuf>)>, String> {
+    let rows = sqlx::query_as::<_, (Option<String>, Option<String>, f64, Option<f64>)>(
+        "SELECT json_extract(r.config_json, '$.agent_mode'),
+                json_extract(r.config_json, '$.repositories'),
+                (julianday(s.completed_at) - julianday(s.started_at)) * 86400.0,
+                CAST(json_extract(s.metrics_json, '$.tokens_used') AS REAL)
+         FROM batch_sessions s JOIN batch_runs r ON r.id = s.batch_id
+         WHERE s.status = 'completed' AND s.started_at IS NOT NULL AND s.completed_at IS NOT NULL
+         ORDER BY s.completed_at DESC LIMIT ?",
+    )
+    .bind(MAX_HISTORY)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load batch history: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|(_, _, secs, _)| secs.is_finite() && *secs >= 0.0)
+        .map(|(agent_mode, repositories, secs, tokens)| {
+            let repositories = repositories
+                .and_then(|r| serde_json::from_str::<Vec<PathBuf>>(&r).ok())
+                .unwrap_or_default();
+            (SessionSample { agent_mode, secs, tokens, repo_files: None }, repositories)
+        })
+        .collect())
+}
+
+/// Fill in each sample's repository size from its batch's repositories
+pub fn attach_repo_sizes(history: Vec<(SessionSample, Vec<PathBuf>)>, sizes: &HashMap<PathBuf, Option<f64>>) -> Vec<SessionSample> {
+    history
+        .into_iter()
+        .map(|(mut sample, repositories)| {
+            let known: Vec<f64> = repositories.iter().filter_map(|r| sizes.get(r).copied().flatten()).collect();
+            if !known.is_empty() {
+                sample.repo_files = Some(known.iter().sum::<f64>() / known.len() as f64);
+            }
+            sample
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(mode: &str, secs: f64, tokens: f64, repo_files: Option<f64>) -> SessionSample {
+        SessionSample { agent_mode: Some(mode.to_string()), secs, tokens: Some(tokens), repo_files }
+    }
+
+    #[test]
+    fn test_constant_history_schedules_onto_workers() {
+        let samples = vec![sample("default", 100.0, 1000.0, None); 4];
+        let estimate = simulate(&[None; 5], Some("default"), 2, 3600, &samples);
+        assert!(estimate.agent_mode_matched);
+        assert_eq!(estimate.trials, TRIALS);
+        // Five 100s cases on two workers take three rounds
+        assert_eq!(estimate.wall_time_secs, Some(EstimateRange { p10: 300, p50: 300, p90: 300 }));
+        assert_eq!(estimate.total_tokens, Some(EstimateRange { p10: 5000, p50: 5000, p90: 5000 }));
+
+        // Timeouts cap a case's duration
+        let capped = simulate(&[None; 2], Some("default"), 2, 30, &samples);
+        assert_eq!(capped.wall_time_secs.unwrap().p90, 30);
+        assert_eq!(simulate(&[None; 2], None, 2, 30, &[]).wall_time_secs, None);
+    }
+
+    #[test]
+    fn test_prefers_same_mode_and_similar_repositories() {
+        let mut samples = vec![sample("bolt", 10.0, 100.0, Some(50.0)); 3];
+        samples.extend(vec![sample("default", 60.0, 600.0, Some(100.0)); 3]);
+        samples.extend(vec![sample("default", 600.0, 6000.0, Some(10_000.0)); 3]);
+
+        let small = simulate(&[Some(120.0)], Some("default"), 1, 3600, &samples);
+        assert!(small.agent_mode_matched);
+        assert_eq!(small.sample_size, 6);
+        assert_eq!(small.size_matched_cases, 1);
+        assert_eq!(small.wall_time_secs.unwrap().p90, 60);
+
+        // An unknown size draws from every session of the mode, so the range widens
+        let unknown = simulate(&[None; 8], Some("default"), 8, 3600, &samples);
+        assert_eq!(unknown.size_matched_cases, 0);
+        let range = unknown.wall_time_secs.unwrap();
+        assert!(range.p10 <= range.p50 && range.p50 <= range.p90);
+        assert_eq!(range.p90, 600);
+
+        // Too few sessions with the mode falls back to all of history
+        let other = simulate(&[None], Some("geppetto"), 1, 3600, &samples);
+        assert!(!other.agent_mode_matched);
+        assert_eq!(other.sample_size, samples.len());
+    }
+
+    #[test]
+    fn test_case_repo_files_follows_case_order_and_shard() {
+        let (a, b) = (PathBuf::from("/a"), PathBuf::from("/b"));
+        let mut config = BatchConfig {
+            name: "b".to_string(),
+            prompts: vec!["one".to_string(), "two".to_string()],
+            repositories: vec![a.clone(), b.clone()],
+            concurrency: 2,
+            timeout_sec: 60,
+            retry_policy: None,
+            agent_mode: None,
+            toolbox_path: None,
+            shard: None,
+            results_dir: None,
+            dependencies: Vec::new(),
+            priorities: Vec::new(),
+        };
+        let sizes = HashMap::from([(a.clone(), Some(10.0)), (b.clone(), None)]);
+        assert_eq!(case_repo_files(&config, &sizes), vec![Some(10.0), None, Some(10.0), None]);
+        config.shard = Some(batch_shards::ShardSpec { index: 1, count: 2 });
+        assert_eq!(case_repo_files(&config, &sizes), vec![None, None]);
+
+        let history = vec![(sample("default", 1.0, 1.0, None), vec![a, b])];
+        assert_eq!(attach_repo_sizes(history, &sizes)[0].repo_files, Some(10.0));
+    }
+}
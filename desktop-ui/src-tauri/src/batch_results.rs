@@ -0,0 +1,202 @@
+//! Batch results a page at a time
+//!
+//! A large batch with transcripts does not fit in one response, so results are
+//! read in pages keyed by case index: from the engine while the batch is loaded,
+//! otherwise from the `result.json` files under its results directory, reading
+//! only the page's files. Sessions recorded in the database page by session id.
+//! Transcripts are read only when asked for and each is cut to a byte budget.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::batch_case_logs::{batch_dir_name, case_dir_name};
+use crate::batch_shards::CaseResult;
+
+/// Results per page when no limit is given
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+/// Transcript bytes included per case when no budget is given
+pub const DEFAULT_TRANSCRIPT_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultsPage<T> {
+    pub items: Vec<T>,
+    /// Pass back as `cursor` for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// The start of a case's transcript, cut at a line boundary
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InlineTranscript {
+    pub events: Vec<Value>,
+    /// Size of the whole transcript file
+    pub total_bytes: u64,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResultEntry {
+    #[serde(flatten)]
+    pub result: CaseResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<InlineTranscript>,
+}
+
+/// A batch session as recorded in the database
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct BatchSessionRecord {
+    pub session_id: String,
+    pub status: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub error_message: Option<String>,
+    pub metrics_json: Option<String>,
+}
+
+pub fn page_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Case index a case-keyed cursor points past
+pub fn parse_case_cursor(cursor: Option<&str>) -> Result<Option<usize>, String> {
+    cursor
+        .map(|c| c.parse().map_err(|_| format!("Invalid results cursor: {}", c)))
+        .transpose()
+}
+
+/// Case indexes with a `result.json` under `<results_dir>/<batch>/`, in order
+fn cases_on_disk(batch_dir: &Path) -> std::io::Result<Vec<usize>> {
+    let mut cases: Vec<usize> = std::fs::read_dir(batch_dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let index = name.to_str()?.strip_prefix("case-")?.parse().ok()?;
+            entry.path().join("result.json").is_file().then_some(index)
+        })
+        .collect();
+    cases.sort_unstable();
+    Ok(cases)
+}
+
+/// Up to `limit` results after case `after`, read from a batch's results
+/// directory, and whether more follow
+pub fn read_page_from_dir(
+    results_dir: &Path,
+    batch_name: &str,
+    after: Option<usize>,
+    limit: usize,
+) -> Result<(Vec<CaseResult>, bool), String> {
+    let batch_dir = results_dir.join(batch_dir_name(batch_name));
+    let cases = cases_on_disk(&batch_dir).map_err(|e| format!("Failed to read {}: {}", batch_dir.display(), e))?;
+    let mut remaining = cases.into_iter().filter(|index| after.is_none_or(|after| *index > after));
+    let mut results = Vec::with_capacity(limit);
+    for index in remaining.by_ref().take(limit) {
+        let path = batch_dir.join(case_dir_name(index)).join("result.json");
+        let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let result = serde_json::from_slice(&content).map_err(|e| format!("Invalid case result {}: {}", path.display(), e))?;
+        results.push(result);
+    }
+    Ok((results, remaining.next().is_some()))
+}
+
+/// Transcript events from the start of `path` until `max_bytes` would be passed
+pub fn read_transcript(path: &Path, max_bytes: usize) -> std::io::Result<InlineTranscript> {
+    let file = std::fs::File::open(path)?;
+    let total_bytes = file.metadata()?.len();
+    let mut transcript = InlineTranscript { events: Vec::new(), total_bytes, truncated: false };
+    let mut used = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if used + line.len() + 1 > max_bytes {
+            transcript.truncated = true;
+            break;
+        }
+        used += line.len() + 1;
+        if let Ok(event) = serde_json::from_str(&line) {
+            transcript.events.push(event);
+        }
+    }
+    Ok(transcript)
+}
+
+/// Up to `limit` database sessions of a batch after session `after`
+pub async fn load_session_page(
+    db: &sqlx::SqlitePool,
+    batch_id: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<ResultsPage<BatchSessionRecord>, String> {
+    let mut items = sqlx::query_as::<_, BatchSessionRecord>(
+        "SELECT session_id, status, started_at, completed_at, error_message, metrics_json
+         FROM batch_sessions WHERE batch_id = ? AND session_id > ?
+         ORDER BY session_id LIMIT ?",
+    )
+    .bind(batch_id)
+    .bind(after.unwrap_or(""))
+    .bind(limit as i64 + 1)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load batch sessions: {}", e))?;
+    let more = items.len() > limit;
+    items.truncate(limit);
+    let next_cursor = if more { items.last().map(|s| s.session_id.clone()) } else { None };
+    Ok(ResultsPage { items, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_case_logs::{write_case_artifacts, write_case_result, CaseArtifacts};
+    use crate::batch_engine::SessionStatus;
+
+    fn case(results_dir: &Path, case_index: usize) {
+        let artifacts = CaseArtifacts { prompt: format!("prompt {}", case_index), ..Default::default() };
+        let logs = write_case_artifacts(results_dir, "bench", case_index, &artifacts).unwrap();
+        let result = CaseResult {
+            case_index,
+            prompt: artifacts.prompt,
+            repository: "repo".to_string(),
+            status: SessionStatus::Completed,
+            error_message: None,
+            execution_time_ms: Some(10),
+            metrics: None,
+            logs: Some(logs),
+        };
+        write_case_result(results_dir, &result).unwrap();
+    }
+
+    #[test]
+    fn test_pages_from_results_dir() {
+        let temp = tempfile::TempDir::new().unwrap();
+        for index in [0, 1, 2, 5, 12] {
+            case(temp.path(), index);
+        }
+        // A case still running has no result yet
+        write_case_artifacts(temp.path(), "bench", 7, &CaseArtifacts::default()).unwrap();
+
+        let (page, more) = read_page_from_dir(temp.path(), "bench", None, 2).unwrap();
+        assert_eq!(page.iter().map(|r| r.case_index).collect::<Vec<_>>(), [0, 1]);
+        assert!(more);
+        let (page, more) = read_page_from_dir(temp.path(), "bench", Some(1), 3).unwrap();
+        assert_eq!(page.iter().map(|r| r.case_index).collect::<Vec<_>>(), [2, 5, 12]);
+        assert!(!more);
+        assert_eq!(parse_case_cursor(Some("12")).unwrap(), Some(12));
+        assert!(parse_case_cursor(Some("x")).is_err());
+    }
+
+    #[test]
+    fn test_transcript_cut_at_budget() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("transcript.jsonl");
+        let lines: Vec<String> = (0..10).map(|n| format!("{{\"n\":{}}}", n)).collect();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let whole = read_transcript(&path, 1024).unwrap();
+        assert_eq!((whole.events.len(), whole.truncated, whole.total_bytes), (10, false, 80));
+        let cut = read_transcript(&path, 20).unwrap();
+        assert_eq!(cut.events, [serde_json::json!({"n": 0}), serde_json::json!({"n": 1})]);
+        assert!(cut.truncated);
+    }
+}
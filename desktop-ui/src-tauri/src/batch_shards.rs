@@ -0,0 +1,254 @@
+//! Splitting one batch across several app instances
+//!
+//! Cases are numbered prompt-major over `prompts × repositories`. A batch given
+//! `shard: { index, count }` runs only the cases whose number is `index` modulo
+//! `count`, so N machines started with the same prompts and repositories cover
+//! the dataset exactly once. Each instance writes a `BatchResultFile` and
+//! `merge_batch_results` combines them, reporting cases that no shard produced.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::batch_case_logs::CaseLogPaths;
+use crate::batch_engine::{BatchConfig, SessionMetrics, SessionStatus};
+
+/// Version of the result file layout written by `write_batch_results`
+pub const RESULT_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ShardSpec {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl ShardSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.count == 0 {
+            return Err("Shard count must be at least 1".to_string());
+        }
+        if self.index >= self.count {
+            return Err(format!("Shard index {} is out of range for {} shards", self.index, self.count));
+        }
+        Ok(())
+    }
+
+    pub fn includes(&self, case_index: usize) -> bool {
+        case_index % self.count == self.index
+    }
+}
+
+/// Whether a case runs on this instance; an unsharded batch runs everything
+pub fn in_shard(shard: Option<&ShardSpec>, case_index: usize) -> bool {
+    shard.is_none_or(|s| s.includes(case_index))
+}
+
+/// Cases this instance will run
+pub fn shard_case_count(config: &BatchConfig) -> usize {
+    let total = config.prompts.len() * config.repositories.len();
+    (0..total).filter(|i| in_shard(config.shard.as_ref(), *i)).count()
+}
+
+/// Identifies the dataset independently of where repositories are checked out,
+/// so shards from different machines can be matched up
+pub fn dataset_fingerprint(config: &BatchConfig) -> String {
+    let repositories: Vec<String> = config
+        .repositories
+        .iter()
+        .map(|r| r.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default())
+        .collect();
+    let key = serde_json::json!({
+        "prompts": config.prompts,
+        "repositories": repositories,
+        "agent_mode": config.agent_mode,
+    });
+    blake3::hash(key.to_string().as_bytes()).to_hex().to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub case_index: usize,
+    pub prompt: String,
+    pub repository: String,
+    pub status: SessionStatus,
+    pub error_message: Option<String>,
+    pub execution_time_ms: Option<u64>,
+    pub metrics: Option<SessionMetrics>,
+    /// Case directory under the batch's `results_dir`, when it had one
+    #[serde(default)]
+    pub logs: Option<CaseLogPaths>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResultFile {
+    pub version: u32,
+    pub batch_name: String,
+    pub fingerprint: String,
+    pub total_cases: usize,
+    /// Shards whose cases are in `results`; an unsharded run counts as shard 0 of 1
+    pub shards: Vec<ShardSpec>,
+    pub results: Vec<CaseResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedBatchResults {
+    pub merged: BatchResultFile,
+    /// Shard indexes no file covered
+    pub missing_shards: Vec<usize>,
+    /// Cases with no result in any file
+    pub missing_cases: Vec<usize>,
+    /// Cases reported by more than one file; the completed result is kept
+    pub duplicate_cases: Vec<usize>,
+}
+
+fn is_completed(result: &CaseResult) -> bool {
+    matches!(result.status, SessionStatus::Completed)
+}
+
+/// Combine result files from the shards of one batch
+pub fn merge_result_files(files: Vec<BatchResultFile>) -> Result<MergedBatchResults, String> {
+    let first = files.first().ok_or("No result files to merge")?;
+    let (batch_name, fingerprint, total_cases) = (first.batch_name.clone(), first.fingerprint.clone(), first.total_cases);
+    let shard_count = first.shards.first().map_or(1, |s| s.count);
+
+    let mut shards = BTreeSet::new();
+    let mut results: BTreeMap<usize, CaseResult> = BTreeMap::new();
+    let mut duplicates = BTreeSet::new();
+
+    for file in files {
+        if file.version > RESULT_FILE_VERSION {
+            return Err(format!("Result file version {} is newer than supported version {}", file.version, RESULT_FILE_VERSION));
+        }
+        if file.fingerprint != fingerprint || file.total_cases != total_cases {
+            return Err(format!("'{}' was produced from a different dataset than '{}'", file.batch_name, batch_name));
+        }
+        if let Some(shard) = file.shards.iter().find(|s| s.count != shard_count) {
+            return Err(format!("Shard {} of {} does not match the other files' {} shards", shard.index, shard.count, shard_count));
+        }
+        shards.extend(file.shards);
+
+        for result in file.results {
+            if result.case_index >= total_cases {
+                return Err(format!("Case {} is out of range for {} cases", result.case_index, total_cases));
+            }
+            match results.get(&result.case_index) {
+                Some(existing) => {
+                    duplicates.insert(result.case_index);
+                    if !is_completed(existing) && is_completed(&result) {
+                        results.insert(result.case_index, result);
+                    }
+                }
+                None => {
+                    results.insert(result.case_index, result);
+                }
+            }
+        }
+    }
+
+    let covered: BTreeSet<usize> = shards.iter().map(|s| s.index).collect();
+    Ok(MergedBatchResults {
+        missing_shards: (0..shard_count).filter(|i| !covered.contains(i)).collect(),
+        missing_cases: (0..total_cases).filter(|i| !results.contains_key(i)).collect(),
+        duplicate_cases: duplicates.into_iter().collect(),
+        merged: BatchResultFile {
+            version: RESULT_FILE_VERSION,
+            batch_name,
+            fingerprint,
+            total_cases,
+            shards: shards.into_iter().collect(),
+            results: results.into_values().collect(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn config(shard: Option<ShardSpec>) -> BatchConfig {
+        BatchConfig {
+            name: "Shard".to_string(),
+            prompts: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            repositories: vec![PathBuf::from("/work/one"), PathBuf::from("/work/two")],
+            concurrency: 2,
+            timeout_sec: 600,
+            retry_policy: None,
+            agent_mode: None,
+            toolbox_path: None,
+            shard,
+            results_dir: None,
+            dependencies: Vec::new(),
+            priorities: Vec::new(),
+        }
+    }
+
+    fn result_file(config: &BatchConfig, status: SessionStatus) -> BatchResultFile {
+        let total = config.prompts.len() * config.repositories.len();
+        BatchResultFile {
+            version: RESULT_FILE_VERSION,
+            batch_name: config.name.clone(),
+            fingerprint: dataset_fingerprint(config),
+            total_cases: total,
+            shards: vec![config.shard.unwrap_or(ShardSpec { index: 0, count: 1 })],
+            results: (0..total)
+                .filter(|i| in_shard(config.shard.as_ref(), *i))
+                .map(|case_index| CaseResult {
+                    case_index,
+                    prompt: String::new(),
+                    repository: String::new(),
+                    status: status.clone(),
+                    error_message: None,
+                    execution_time_ms: None,
+                    metrics: None,
+                    logs: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_shards_partition_cases() {
+        let counts: Vec<usize> = (0..4).map(|index| shard_case_count(&config(Some(ShardSpec { index, count: 4 })))).collect();
+        assert_eq!(counts, vec![2, 2, 1, 1]);
+        assert_eq!(shard_case_count(&config(None)), 6);
+        assert!(ShardSpec { index: 4, count: 4 }.validate().is_err());
+        assert!(ShardSpec { index: 0, count: 0 }.validate().is_err());
+
+        // Checkout location does not change the fingerprint
+        let mut moved = config(None);
+        moved.repositories = vec![PathBuf::from("/other/one"), PathBuf::from("/other/two")];
+        assert_eq!(dataset_fingerprint(&moved), dataset_fingerprint(&config(None)));
+        moved.prompts.push("d".to_string());
+        assert_ne!(dataset_fingerprint(&moved), dataset_fingerprint(&config(None)));
+    }
+
+    #[test]
+    fn test_merge_reports_gaps_and_duplicates() {
+        let shard0 = config(Some(ShardSpec { index: 0, count: 3 }));
+        let shard2 = config(Some(ShardSpec { index: 2, count: 3 }));
+        let mut retry = result_file(&shard0, SessionStatus::Completed);
+        retry.results.truncate(1);
+
+        let merged = merge_result_files(vec![
+            result_file(&shard0, SessionStatus::Failed),
+            result_file(&shard2, SessionStatus::Completed),
+            retry,
+        ])
+        .unwrap();
+
+        assert_eq!(merged.missing_shards, vec![1]);
+        assert_eq!(merged.missing_cases, vec![1, 4]);
+        assert_eq!(merged.duplicate_cases, vec![0]);
+        let indexes: Vec<usize> = merged.merged.results.iter().map(|r| r.case_index).collect();
+        assert_eq!(indexes, vec![0, 2, 3, 5]);
+        // The completed retry replaced the failed run
+        assert!(is_completed(&merged.merged.results[0]));
+        assert!(!is_completed(&merged.merged.results[2]));
+
+        let mut other = config(Some(ShardSpec { index: 1, count: 3 }));
+        other.prompts.pop();
+        assert!(merge_result_files(vec![result_file(&shard0, SessionStatus::Completed), result_file(&other, SessionStatus::Completed)]).is_err());
+        let mismatched = config(Some(ShardSpec { index: 1, count: 2 }));
+        assert!(merge_result_files(vec![result_file(&shard0, SessionStatus::Completed), result_file(&mismatched, SessionStatus::Completed)]).is_err());
+    }
+}
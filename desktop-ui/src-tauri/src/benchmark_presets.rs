@@ -0,0 +1,339 @@
+//! Benchmark presets: batch definitions that can be started with one command
+//!
+//! A preset runs the same cases once per agent mode, as one batch per mode, so
+//! modes can be compared on identical work. Its cases are either its own
+//! prompts or the cases of a dataset given when it is started. The presets in
+//! `resources/benchmark_presets.json` are bundled with the app and read-only;
+//! `customize_benchmark` copies one into `benchmark_presets`, where it can be
+//! edited like any user preset.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use tauri::{State, Window};
+
+use crate::batch_commands::{batch_service, start_tracked_batch, BatchEngineState, StartBatchResponse};
+use crate::batch_engine::{BatchConfig, RetryPolicy};
+use crate::profile_auth::ProfileManager;
+
+const BUILTIN_PRESETS: &str = include_str!("resources/benchmark_presets.json");
+const RETRY_BACKOFF_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkPreset {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Bundled with the app; set when the presets are loaded
+    #[serde(default)]
+    pub builtin: bool,
+    /// Preset this one was copied from
+    #[serde(default)]
+    pub based_on: Option<String>,
+    /// One batch runs per mode
+    pub agent_modes: Vec<String>,
+    /// The cases, unless they come from a dataset
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    /// Cases are the prompts of the dataset given when the preset is started
+    #[serde(default)]
+    pub uses_dataset: bool,
+    /// Dataset cases run, from the first; all when unset
+    #[serde(default)]
+    pub max_cases: Option<usize>,
+    pub concurrency: usize,
+    pub timeout_sec: u64,
+    /// Attempts per case; failed cases are not retried when unset
+    #[serde(default)]
+    pub retry_attempts: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct PresetFile {
+    presets: Vec<BenchmarkPreset>,
+}
+
+/// The presets bundled with the app
+pub fn builtin_presets() -> Vec<BenchmarkPreset> {
+    let file: PresetFile = serde_json::from_str(BUILTIN_PRESETS).expect("bundled benchmark presets are valid");
+    file.presets.into_iter().map(|preset| BenchmarkPreset { builtin: true, ..preset }).collect()
+}
+
+impl BenchmarkPreset {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Preset name cannot be empty".to_string());
+        }
+        if self.agent_modes.is_empty() {
+            return Err("Choose at least one agent mode".to_string());
+        }
+        if self.concurrency == 0 || self.timeout_sec == 0 {
+            return Err("Concurrency and timeout must be greater than zero".to_string());
+        }
+        if !self.uses_dataset && self.prompts.iter().all(|prompt| prompt.trim().is_empty()) {
+            return Err("A preset needs prompts or a dataset".to_string());
+        }
+        Ok(())
+    }
+
+    /// The batch of each agent mode, run against `repositories`
+    pub fn batch_configs(&self, repositories: &[String], dataset: Option<&Path>) -> Result<Vec<BatchConfig>, String> {
+        self.validate()?;
+        let prompts = match (self.uses_dataset, dataset) {
+            (true, Some(dataset)) => {
+                let mut prompts = dataset_prompts(dataset)?;
+                prompts.truncate(self.max_cases.unwrap_or(usize::MAX));
+                prompts
+            }
+            (true, None) => return Err(format!("{} runs a dataset, choose one", self.name)),
+            (false, _) => self.prompts.clone(),
+        };
+        Ok(self
+            .agent_modes
+            .iter()
+            .map(|mode| BatchConfig {
+                name: format!("{} ({})", self.name, mode),
+                prompts: prompts.clone(),
+                repositories: repositories.iter().map(PathBuf::from).collect(),
+                concurrency: self.concurrency,
+                timeout_sec: self.timeout_sec,
+                retry_policy: self.retry_attempts.map(|max_attempts| RetryPolicy { max_attempts, backoff_ms: RETRY_BACKOFF_MS }),
+                agent_mode: Some(mode.clone()),
+                toolbox_path: None,
+                shard: None,
+                results_dir: None,
+                dependencies: Vec::new(),
+            priorities: Vec::new(),
+            })
+            .collect())
+    }
+}
+
+/// Prompts of a JSON array or JSONL dataset, from each case's `prompt` or `problem_statement`
+fn dataset_prompts(path: &Path) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read dataset {}: {}", path.display(), e))?;
+    let cases: Vec<serde_json::Value> = if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        serde_json::from_str(&content).map_err(|e| format!("Dataset must be a JSON array of cases: {}", e))?
+    } else {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| serde_json::from_str(line).map_err(|e| format!("line {}: invalid JSON ({})", index + 1, e)))
+            .collect::<Result<_, _>>()?
+    };
+    cases
+        .iter()
+        .enumerate()
+        .map(|(index, case)| {
+            ["prompt", "problem_statement"]
+                .iter()
+                .find_map(|key| case.get(key).and_then(|v| v.as_str()).filter(|s| !s.trim().is_empty()))
+                .map(str::to_string)
+                .ok_or_else(|| format!("case {}: no 'prompt' or 'problem_statement'", index + 1))
+        })
+        .collect()
+}
+
+pub struct BenchmarkPresetStore {
+    db: SqlitePool,
+}
+
+impl BenchmarkPresetStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// The user's presets
+    pub async fn list(&self) -> Result<Vec<BenchmarkPreset>, String> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT definition FROM benchmark_presets ORDER BY created_at, id")
+            .fetch_all(&self.db)
+            .await
+            .map_err(|e| format!("Failed to list benchmark presets: {}", e))?;
+        rows.iter()
+            .map(|definition| serde_json::from_str(definition).map_err(|e| format!("Corrupt benchmark preset: {}", e)))
+            .collect()
+    }
+
+    /// A bundled or user preset
+    pub async fn get(&self, id: &str) -> Result<BenchmarkPreset, String> {
+        if let Some(preset) = builtin_presets().into_iter().find(|preset| preset.id == id) {
+            return Ok(preset);
+        }
+        let definition: Option<String> = sqlx::query_scalar("SELECT definition FROM benchmark_presets WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await
+            .map_err(|e| format!("Failed to get benchmark preset: {}", e))?;
+        let definition = definition.ok_or_else(|| format!("No benchmark preset {}", id))?;
+        serde_json::from_str(&definition).map_err(|e| format!("Corrupt benchmark preset: {}", e))
+    }
+
+    /// Copy a preset into an editable user preset
+    pub async fn customize(&self, id: &str, name: Option<String>) -> Result<BenchmarkPreset, String> {
+        let source = self.get(id).await?;
+        let copy = BenchmarkPreset {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.unwrap_or_else(|| format!("{} (copy)", source.name)),
+            builtin: false,
+            based_on: Some(source.id.clone()),
+            ..source
+        };
+        self.save(copy).await
+    }
+
+    /// Create or update a user preset
+    pub async fn save(&self, preset: BenchmarkPreset) -> Result<BenchmarkPreset, String> {
+        if preset.builtin || builtin_presets().iter().any(|builtin| builtin.id == preset.id) {
+            return Err("Bundled presets cannot be changed, customize a copy instead".to_string());
+        }
+        preset.validate()?;
+        let definition = serde_json::to_string(&preset).map_err(|e| e.to_string())?;
+        sqlx::query(
+            "INSERT INTO benchmark_presets (id, based_on, definition) VALUES (?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET definition = excluded.definition, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(&preset.id)
+        .bind(&preset.based_on)
+        .bind(definition)
+        .execute(&self.db)
+        .await
+        .map_err(|e| format!("Failed to save benchmark preset: {}", e))?;
+        Ok(preset)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM benchmark_presets WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await
+            .map_err(|e| format!("Failed to delete benchmark preset: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+async fn preset_store(profile_manager: &ProfileManager) -> Result<BenchmarkPresetStore, String> {
+    let db = profile_manager.db_pool.read().await;
+    Ok(BenchmarkPresetStore::new(db.as_ref().ok_or("Database not available")?.clone()))
+}
+
+#[tauri::command]
+pub async fn list_builtin_benchmarks() -> Result<Vec<BenchmarkPreset>, String> {
+    Ok(builtin_presets())
+}
+
+#[tauri::command]
+pub async fn list_custom_benchmarks(profile_manager: State<'_, ProfileManager>) -> Result<Vec<BenchmarkPreset>, String> {
+    preset_store(&profile_manager).await?.list().await
+}
+
+/// Copy a bundled or user preset into a new user preset
+#[tauri::command]
+pub async fn customize_benchmark(
+    preset_id: String,
+    name: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<BenchmarkPreset, String> {
+    preset_store(&profile_manager).await?.customize(&preset_id, name).await
+}
+
+#[tauri::command]
+pub async fn save_custom_benchmark(
+    preset: BenchmarkPreset,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<BenchmarkPreset, String> {
+    preset_store(&profile_manager).await?.save(preset).await
+}
+
+#[tauri::command]
+pub async fn delete_custom_benchmark(id: String, profile_manager: State<'_, ProfileManager>) -> Result<bool, String> {
+    preset_store(&profile_manager).await?.delete(&id).await
+}
+
+/// Start a preset against `repositories`, one batch per agent mode. Every
+/// batch is validated before any starts.
+#[tauri::command]
+pub async fn start_benchmark(
+    preset_id: String,
+    repositories: Vec<String>,
+    dataset_path: Option<String>,
+    state: State<'_, BatchEngineState>,
+    app_state: State<'_, crate::app_state::AppState>,
+    profile_manager: State<'_, ProfileManager>,
+    window: Window,
+) -> Result<Vec<StartBatchResponse>, String> {
+    crate::feature_flags::require(&app_state, crate::feature_flags::BATCH_PROCESSING)?;
+    let preset = preset_store(&profile_manager).await?.get(&preset_id).await?;
+    let configs = preset.batch_configs(&repositories, dataset_path.as_deref().map(Path::new))?;
+
+    let service = batch_service(&state, &profile_manager).await;
+    for config in &configs {
+        let report = service.validate(config, None).await?;
+        if let Some(issue) = report.issues.iter().find(|issue| matches!(issue.severity, crate::batch_engine::ValidationSeverity::Error)) {
+            return Err(format!("{}: {}: {}", config.name, issue.field, issue.message));
+        }
+    }
+
+    let mut started = Vec::with_capacity(configs.len());
+    for config in configs {
+        started.push(start_tracked_batch(config, &state, &profile_manager, window.clone()).await?);
+    }
+    Ok(started)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin_presets_instantiate() {
+        let presets = builtin_presets();
+        assert!(presets.iter().all(|preset| preset.builtin && preset.validate().is_ok()));
+
+        let smoke = presets.iter().find(|preset| preset.id == "quick-smoke").unwrap();
+        let configs = smoke.batch_configs(&["/repo".to_string()], None).unwrap();
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].prompts.len(), 5);
+        assert_eq!(configs[1].agent_mode.as_deref(), Some("bolt"));
+        assert_eq!(configs[1].name, "Quick smoke (bolt)");
+
+        let comparison = presets.iter().find(|preset| preset.id == "mode-comparison").unwrap();
+        assert!(comparison.batch_configs(&["/repo".to_string()], None).unwrap_err().contains("choose one"));
+        let dir = TempDir::new().unwrap();
+        let dataset = dir.path().join("cases.jsonl");
+        let cases: Vec<String> = (0..25).map(|i| format!("{{\"problem_statement\": \"case {}\"}}", i)).collect();
+        std::fs::write(&dataset, cases.join("\n")).unwrap();
+        let configs = comparison.batch_configs(&["/repo".to_string()], Some(&dataset)).unwrap();
+        assert_eq!(configs.len(), 4);
+        assert_eq!(configs[3].prompts.len(), 20);
+        assert_eq!(configs[3].prompts[19], "case 19");
+
+        std::fs::write(&dataset, "{\"id\": 1}\n").unwrap();
+        assert!(comparison.batch_configs(&[], Some(&dataset)).unwrap_err().contains("case 1"));
+    }
+
+    #[tokio::test]
+    async fn test_customized_copies_are_stored_apart_from_builtins() {
+        let pool = test_pool().await;
+        let store = BenchmarkPresetStore::new(pool);
+
+        let mut copy = store.customize("nightly", None).await.unwrap();
+        assert_eq!((copy.name.as_str(), copy.based_on.as_deref(), copy.builtin), ("Nightly (copy)", Some("nightly"), false));
+        copy.agent_modes.push("bolt".to_string());
+        store.save(copy.clone()).await.unwrap();
+        assert_eq!(store.list().await.unwrap(), vec![copy.clone()]);
+        assert_eq!(store.get(&copy.id).await.unwrap().agent_modes, vec!["default", "bolt"]);
+        assert_eq!(store.get("nightly").await.unwrap().agent_modes, vec!["default"]);
+
+        let mut builtin = store.get("nightly").await.unwrap();
+        builtin.builtin = false;
+        assert!(store.save(builtin).await.unwrap_err().contains("customize a copy"));
+        assert!(store.delete(&copy.id).await.unwrap());
+        assert!(store.get(&copy.id).await.is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Fault injection for resilience testing
+//!
+//! Built only with the `chaos` feature; without it every hook below is a no-op.
+//! Faults fire at configured probabilities from a seeded generator, so a failing
+//! run can be replayed:
+//! - `maybe_kill` kills a session's agent process after a line of its output
+//! - `delay_db_write` stalls session bookkeeping and message writes
+//! - `drop_line` makes `StreamLines` lose a line of process output
+//! - `git_output` fails a Git command of worktree creation before it runs
+//!
+//! The app reads its configuration from `AMP_CHAOS` at startup, for example
+//! `AMP_CHAOS="seed=7,kill_child=0.01,drop_line=0.05,fail_git=0.1,delay_db_write=0.2,delay_db_ms=300"`.
+//! Tests use `scoped` instead, which applies to the calling thread only.
+
+use std::io;
+use std::process::{Command, Output};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    KillChild,
+    DelayDbWrite,
+    DropLine,
+    FailGit,
+}
+
+impl Fault {
+    #[cfg(feature = "chaos")]
+    const ALL: [Fault; 4] = [Fault::KillChild, Fault::DelayDbWrite, Fault::DropLine, Fault::FailGit];
+
+    #[cfg(feature = "chaos")]
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[cfg(feature = "chaos")]
+pub use enabled::*;
+
+#[cfg(feature = "chaos")]
+mod enabled {
+    use super::Fault;
+    use once_cell::sync::Lazy;
+    use std::cell::RefCell;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    const DEFAULT_DELAY_MS: u64 = 250;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ChaosConfig {
+        pub seed: u64,
+        /// Probability of each fault per opportunity, from 0 to 1
+        pub kill_child: f64,
+        pub delay_db_write: f64,
+        pub drop_line: f64,
+        pub fail_git: f64,
+        pub delay_db_ms: u64,
+    }
+
+    impl Default for ChaosConfig {
+        fn default() -> Self {
+            Self { seed: 1, kill_child: 0.0, delay_db_write: 0.0, drop_line: 0.0, fail_git: 0.0, delay_db_ms: DEFAULT_DELAY_MS }
+        }
+    }
+
+    impl ChaosConfig {
+        fn probability(&self, fault: Fault) -> f64 {
+            match fault {
+                Fault::KillChild => self.kill_child,
+                Fault::DelayDbWrite => self.delay_db_write,
+                Fault::DropLine => self.drop_line,
+                Fault::FailGit => self.fail_git,
+            }
+        }
+    }
+
+    /// `key=value` pairs separated by commas
+    impl FromStr for ChaosConfig {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, String> {
+            let mut config = ChaosConfig::default();
+            for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (key, value) = pair.split_once('=').ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+                let (key, value) = (key.trim(), value.trim());
+                let integer = || value.parse::<u64>().map_err(|_| format!("{} must be a whole number, got '{}'", key, value));
+                let probability = || match value.parse::<f64>() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+                    _ => Err(format!("{} must be a probability from 0 to 1, got '{}'", key, value)),
+                };
+                match key {
+                    "seed" => config.seed = integer()?,
+                    "delay_db_ms" => config.delay_db_ms = integer()?,
+                    "kill_child" => config.kill_child = probability()?,
+                    "delay_db_write" => config.delay_db_write = probability()?,
+                    "drop_line" => config.drop_line = probability()?,
+                    "fail_git" => config.fail_git = probability()?,
+                    _ => return Err(format!("Unknown chaos setting '{}'", key)),
+                }
+            }
+            Ok(config)
+        }
+    }
+
+    pub(super) struct Chaos {
+        config: ChaosConfig,
+        /// xorshift64* state
+        state: u64,
+        injected: [u64; Fault::ALL.len()],
+    }
+
+    impl Chaos {
+        fn new(config: ChaosConfig) -> Self {
+            Self { state: config.seed.max(1), config, injected: [0; Fault::ALL.len()] }
+        }
+
+        fn roll(&mut self, fault: Fault) -> bool {
+            let probability = self.config.probability(fault);
+            if probability <= 0.0 {
+                return false;
+            }
+            self.state ^= self.state >> 12;
+            self.state ^= self.state << 25;
+            self.state ^= self.state >> 27;
+            let sample = (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64;
+            let fires = sample < probability;
+            if fires {
+                self.injected[fault.index()] += 1;
+            }
+            fires
+        }
+    }
+
+    static GLOBAL: Lazy<Mutex<Option<Chaos>>> = Lazy::new(|| Mutex::new(None));
+
+    thread_local! {
+        static SCOPED: RefCell<Option<Chaos>> = const { RefCell::new(None) };
+    }
+
+    /// Turn on the faults configured in `AMP_CHAOS`, if set
+    pub fn init_from_env() {
+        let Ok(spec) = std::env::var("AMP_CHAOS") else {
+            return;
+        };
+        match spec.parse::<ChaosConfig>() {
+            Ok(config) => {
+                log::warn!("Fault injection enabled: {:?}", config);
+                *GLOBAL.lock().unwrap() = Some(Chaos::new(config));
+            }
+            Err(e) => log::warn!("Ignoring AMP_CHAOS: {}", e),
+        }
+    }
+
+    /// Faults for the calling thread until the guard drops, ahead of `AMP_CHAOS`
+    #[cfg(test)]
+    pub fn scoped(config: ChaosConfig) -> ChaosGuard {
+        SCOPED.with(|scoped| *scoped.borrow_mut() = Some(Chaos::new(config)));
+        ChaosGuard(())
+    }
+
+    #[cfg(test)]
+    pub struct ChaosGuard(());
+
+    #[cfg(test)]
+    impl ChaosGuard {
+        /// How often `fault` has fired under this guard
+        pub fn injected(&self, fault: Fault) -> u64 {
+            SCOPED.with(|scoped| scoped.borrow().as_ref().map_or(0, |chaos| chaos.injected[fault.index()]))
+        }
+    }
+
+    #[cfg(test)]
+    impl Drop for ChaosGuard {
+        fn drop(&mut self) {
+            SCOPED.with(|scoped| *scoped.borrow_mut() = None);
+        }
+    }
+
+    fn with_chaos<T>(f: impl Fn(&mut Chaos) -> T) -> Option<T> {
+        if let Some(value) = SCOPED.with(|scoped| scoped.borrow_mut().as_mut().map(&f)) {
+            return Some(value);
+        }
+        GLOBAL.lock().unwrap().as_mut().map(f)
+    }
+
+    pub(super) fn fires(fault: Fault) -> bool {
+        let fires = with_chaos(|chaos| chaos.roll(fault)).unwrap_or(false);
+        if fires {
+            log::warn!("Chaos: injecting {:?}", fault);
+        }
+        fires
+    }
+
+    pub(super) fn delay_ms() -> u64 {
+        with_chaos(|chaos| chaos.config.delay_db_ms).unwrap_or(0)
+    }
+}
+
+#[cfg(not(feature = "chaos"))]
+pub fn init_from_env() {}
+
+#[cfg(not(feature = "chaos"))]
+fn fires(_fault: Fault) -> bool {
+    false
+}
+
+#[cfg(not(feature = "chaos"))]
+fn delay_ms() -> u64 {
+    0
+}
+
+/// Kill a session's process, as if it had crashed
+pub fn maybe_kill(pid: Option<u32>) {
+    let Some(pid) = pid else {
+        return;
+    };
+    if fires(Fault::KillChild) {
+        if let Err(e) = crate::startup_reconciliation::kill_process(pid) {
+            log::warn!("Chaos: failed to kill process {}: {}", pid, e);
+        }
+    }
+}
+
+/// Stall before a database write
+pub async fn delay_db_write() {
+    if fires(Fault::DelayDbWrite) {
+        tokio::time::sleep(std::time::Duration::from_millis(delay_ms())).await;
+    }
+}
+
+/// Whether to lose the line of process output just read
+pub fn drop_line() -> bool {
+    fires(Fault::DropLine)
+}
+
+/// Run a Git command, unless an injected failure stops it first
+pub fn git_output(command: &mut Command) -> io::Result<Output> {
+    if fires(Fault::FailGit) {
+        return Err(io::Error::other("Injected Git failure"));
+    }
+    command.output()
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: ChaosConfig = "seed=7, kill_child=0.5,drop_line=1,delay_db_ms=20".parse().unwrap();
+        assert_eq!(
+            config,
+            ChaosConfig { seed: 7, kill_child: 0.5, drop_line: 1.0, delay_db_ms: 20, ..ChaosConfig::default() }
+        );
+        assert!("fail_git=1.5".parse::<ChaosConfig>().is_err());
+        assert!("kill_parent=0.1".parse::<ChaosConfig>().is_err());
+        assert!("seed".parse::<ChaosConfig>().is_err());
+    }
+
+    #[test]
+    fn test_faults_replay_from_seed() {
+        let run = |seed| {
+            let guard = scoped(ChaosConfig { seed, drop_line: 0.3, ..ChaosConfig::default() });
+            let drops: Vec<bool> = (0..200).map(|_| drop_line()).collect();
+            assert_eq!(guard.injected(Fault::DropLine), drops.iter().filter(|d| **d).count() as u64);
+            // Faults without a probability never fire
+            assert!(git_output(Command::new("git").arg("--version")).is_ok());
+            drops
+        };
+        let drops = run(42);
+        assert_eq!(drops, run(42));
+        assert_ne!(drops, run(43));
+        let fired = drops.iter().filter(|d| **d).count();
+        assert!((30..90).contains(&fired), "{} of 200 lines dropped", fired);
+        assert!(!drop_line());
+    }
+}
@@ -0,0 +1,148 @@
+//! Resilience of the session pipeline under injected faults: agent processes
+//! killed mid-stream, slow database writes, lost output lines and failing Git
+//! commands. Run with `cargo test --features chaos`.
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    use crate::chaos::{self, ChaosConfig, Fault};
+    use crate::message_content::{insert_message_with_metrics, resolve_content};
+    use crate::schema::test_pool;
+    use crate::startup_reconciliation::{process_started, reconcile};
+    use crate::stream_quarantine::{CliInfo, StreamLines, StreamValidator};
+    use crate::worktree::{create_with_options, path_for, CreateOptions};
+
+    const EVENTS: &[&str] = &[
+        r#"{"type":"system","subtype":"init","session_id":"s1","tools":["read_file"]}"#,
+        r#"{"type":"user","message":{"content":[{"type":"text","text":"Explain this repository"}]}}"#,
+        r#"{"type":"assistant","message":{"content":[{"type":"text","text":"It is a desktop app."}],"usage":{"input_tokens":10,"output_tokens":4}}}"#,
+        r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":1200,"total_cost_usd":0.01}"#,
+    ];
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reconciliation_recovers_killed_processes() {
+        let pool = test_pool().await;
+        let chaos = chaos::scoped(ChaosConfig { seed: 11, kill_child: 0.5, delay_db_write: 1.0, delay_db_ms: 5, ..ChaosConfig::default() });
+
+        let mut children = Vec::new();
+        for i in 0..8 {
+            let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+            let session_id = format!("chaos-{}", i);
+            process_started(Some(&pool), &session_id, "chat", Some(child.id()), "sleep").await;
+            let before = chaos.injected(Fault::KillChild);
+            chaos::maybe_kill(Some(child.id()));
+            let killed = chaos.injected(Fault::KillChild) > before;
+            if killed {
+                // Reap it so its PID stops showing up
+                child.wait().unwrap();
+            }
+            children.push((session_id, child, killed));
+        }
+        let killed = children.iter().filter(|(.., killed)| *killed).count();
+        assert!(killed > 0 && killed < children.len(), "{} of {} killed", killed, children.len());
+        assert_eq!(chaos.injected(Fault::DelayDbWrite), children.len() as u64);
+
+        // The app restarts: every session is corrected, and the survivors are reaped
+        let summary = reconcile(&pool, &HashSet::new()).await;
+        assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+        let corrected: HashSet<&str> = summary.corrected.iter().map(|c| c.session_id.as_str()).collect();
+        let reaped: HashSet<&str> = summary.reaped.iter().map(|r| r.session_id.as_str()).collect();
+        for (session_id, child, killed) in &mut children {
+            assert!(corrected.contains(session_id.as_str()), "{} not corrected", session_id);
+            assert_eq!(reaped.contains(session_id.as_str()), !*killed, "{}", session_id);
+            assert!(!child.wait().unwrap().success());
+        }
+
+        let again = reconcile(&pool, &HashSet::new()).await;
+        assert!(again.is_clean(), "{:?}", again);
+    }
+
+    #[tokio::test]
+    async fn test_lost_lines_never_corrupt_stored_messages() {
+        let pool = test_pool().await;
+        let chaos = chaos::scoped(ChaosConfig { seed: 5, drop_line: 0.3, delay_db_write: 0.5, delay_db_ms: 1, ..ChaosConfig::default() });
+        let rounds = 50;
+        let output = format!("{}\n", EVENTS.join("\n")).repeat(rounds);
+        let thread = "chaos-thread";
+
+        let mut lines = StreamLines::new(output.as_bytes());
+        let mut validator = StreamValidator::new("thread", thread, CliInfo::new("amp", &[]));
+        let (mut received, mut stored) = (0, Vec::new());
+        while let Some(line) = lines.next_line().await.unwrap() {
+            received += 1;
+            let event = validator.check(&line).expect("a lost line must not corrupt the next one");
+            let role = event["type"].as_str().unwrap().to_string();
+            if role == "user" || role == "assistant" {
+                let content = serde_json::to_string(&event).unwrap();
+                let metrics = crate::message_metrics::observe(thread, &event);
+                let id = format!("m{}", received);
+                insert_message_with_metrics(&pool, &id, thread, &role, &content, &metrics).await.unwrap();
+                stored.push((id, content));
+            }
+        }
+        crate::message_metrics::end_thread(thread);
+
+        let dropped = chaos.injected(Fault::DropLine) as usize;
+        assert!(dropped > 0);
+        assert_eq!(received + dropped, EVENTS.len() * rounds);
+        for (id, content) in stored {
+            let (saved, content_ref) =
+                sqlx::query_as::<_, (String, Option<String>)>("SELECT content, content_ref FROM messages WHERE id = ?")
+                    .bind(&id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(resolve_content(saved, content_ref), content);
+        }
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").current_dir(dir).args(args).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn test_repo() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().to_path_buf();
+        git(&repo, &["init"]);
+        git(&repo, &["config", "user.name", "Test User"]);
+        git(&repo, &["config", "user.email", "test@example.com"]);
+        std::fs::write(repo.join("README.md"), "# Test\n").unwrap();
+        git(&repo, &["add", "README.md"]);
+        git(&repo, &["commit", "-m", "Initial commit"]);
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_worktree_creation_recovers_from_git_failures() {
+        let (_dir, repo) = test_repo();
+        let chaos = chaos::scoped(ChaosConfig { seed: 3, fail_git: 0.4, ..ChaosConfig::default() });
+        let options = CreateOptions { identity: Some(Default::default()), ..Default::default() };
+        let session_id = "chaos-worktree-1234";
+
+        // Retrying after each failure must eventually work: no half-created
+        // worktree, registration or branch may be left in the way
+        let mut failures = 0;
+        let meta = loop {
+            match create_with_options(&repo, session_id, &options) {
+                Ok(meta) => break meta,
+                Err(e) => {
+                    failures += 1;
+                    assert!(failures < 50, "still failing after {} attempts: {}", failures, e);
+                    assert!(!path_for(&repo, session_id).exists());
+                }
+            }
+        };
+        assert!(failures > 0);
+        assert!(chaos.injected(Fault::FailGit) >= failures);
+        assert!(meta.path.join("README.md").exists());
+        assert_eq!(git(&meta.path, &["rev-parse", "--abbrev-ref", "HEAD"]).trim(), meta.branch);
+        assert_eq!(git(&repo, &["worktree", "list", "--porcelain"]).matches("worktree ").count(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+//! Read-only views of a session worktree at an earlier checkpoint
+//!
+//! Checkpoints are the commits on a session's branch. `mount_checkpoint` checks
+//! one out as a detached secondary worktree under `<app data>/checkpoint_mounts/`
+//! with its files made read-only, so any point of the agent's history can be
+//! browsed and diffed without touching the live worktree. A mount lasts until
+//! `release_checkpoint`, until the webview that made it closes, or until the
+//! next start, which clears mounts left behind by a crash.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State, Webview};
+
+use crate::commit_message::{git, session_worktree};
+
+const MOUNTS_DIR: &str = "checkpoint_mounts";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointMount {
+    pub mount_id: String,
+    pub session_id: String,
+    /// Full hash of the checkpoint commit
+    pub commit: String,
+    pub subject: String,
+    /// Read-only worktree holding the checkpoint's files
+    pub path: PathBuf,
+    /// The session worktree the mount was made from
+    pub source: PathBuf,
+    pub mounted_at: String,
+}
+
+/// Mounts in use, with the label of the webview that made each one
+#[derive(Clone, Default)]
+pub struct CheckpointMounts {
+    inner: Arc<Mutex<HashMap<String, (CheckpointMount, String)>>>,
+}
+
+impl CheckpointMounts {
+    pub fn insert(&self, mount: CheckpointMount, webview: &str) {
+        self.inner.lock().unwrap().insert(mount.mount_id.clone(), (mount, webview.to_string()));
+    }
+
+    pub fn remove(&self, mount_id: &str) -> Option<CheckpointMount> {
+        self.inner.lock().unwrap().remove(mount_id).map(|(mount, _)| mount)
+    }
+
+    /// Take the mounts of a closed webview
+    pub fn remove_webview(&self, webview: &str) -> Vec<CheckpointMount> {
+        let mut mounts = self.inner.lock().unwrap();
+        let ids: Vec<String> = mounts.iter().filter(|(_, (_, label))| label == webview).map(|(id, _)| id.clone()).collect();
+        ids.iter().filter_map(|id| mounts.remove(id)).map(|(mount, _)| mount).collect()
+    }
+
+    pub fn list(&self, session_id: Option<&str>) -> Vec<CheckpointMount> {
+        let mut mounts: Vec<CheckpointMount> = self
+            .inner
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(mount, _)| mount)
+            .filter(|mount| session_id.is_none_or(|id| mount.session_id == id))
+            .cloned()
+            .collect();
+        mounts.sort_by(|a, b| a.mounted_at.cmp(&b.mounted_at));
+        mounts
+    }
+}
+
+/// Resolve a checkpoint id, a full or abbreviated commit hash, to a commit on
+/// the worktree's branch, returning the hash and its subject
+pub fn resolve_checkpoint(worktree: &Path, checkpoint_id: &str) -> Result<(String, String), String> {
+    let checkpoint_id = checkpoint_id.trim();
+    if checkpoint_id.is_empty() || checkpoint_id.starts_with('-') {
+        return Err(format!("Invalid checkpoint id '{}'", checkpoint_id));
+    }
+    let commit = git(worktree, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", checkpoint_id)])
+        .map_err(|_| format!("Checkpoint {} not found", checkpoint_id))?
+        .trim()
+        .to_string();
+    if git(worktree, &["merge-base", "--is-ancestor", &commit, "HEAD"]).is_err() {
+        return Err(format!("Checkpoint {} is not in the session's history", checkpoint_id));
+    }
+    let subject = git(worktree, &["log", "-1", "--format=%s", &commit])?.trim().to_string();
+    Ok((commit, subject))
+}
+
+fn set_writable(path: &Path, writable: bool) -> std::io::Result<()> {
+    let mut permissions = std::fs::symlink_metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if writable { mode | 0o200 } else { mode & !0o222 });
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(!writable);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// Change the permissions of everything checked out under `root`. Symlinks are
+/// left alone since changing them would change their targets.
+fn set_tree_writable(root: &Path, writable: bool) -> std::io::Result<()> {
+    let entries = walkdir::WalkDir::new(root).into_iter().filter_entry(|e| e.depth() != 1 || e.file_name() != ".git");
+    for entry in entries {
+        let entry = entry.map_err(std::io::Error::other)?;
+        if !entry.file_type().is_symlink() {
+            set_writable(entry.path(), writable)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check `commit` out at `dest` as a detached, read-only worktree
+pub fn mount(source: &Path, commit: &str, dest: &Path) -> Result<(), String> {
+    let dest_str = dest.to_str().ok_or_else(|| format!("Mount path is not valid UTF-8: {}", dest.display()))?;
+    git(source, &["worktree", "add", "--detach", dest_str, commit])?;
+    if let Err(e) = set_tree_writable(dest, false) {
+        let _ = unmount(source, dest);
+        return Err(format!("Failed to make checkpoint read-only: {}", e));
+    }
+    Ok(())
+}
+
+/// Remove a mounted worktree and Git's record of it
+pub fn unmount(source: &Path, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        set_tree_writable(path, true).map_err(|e| format!("Failed to unlock {}: {}", path.display(), e))?;
+    }
+    let removed = path
+        .to_str()
+        .map(|p| git(source, &["worktree", "remove", "--force", p]))
+        .unwrap_or_else(|| Err("Mount path is not valid UTF-8".to_string()));
+    if removed.is_err() && path.exists() {
+        std::fs::remove_dir_all(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    let _ = git(source, &["worktree", "prune"]);
+    Ok(())
+}
+
+fn mounts_root(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join(MOUNTS_DIR))
+}
+
+/// Delete mounts left over from an earlier run. Their repositories forget them
+/// on the next `git worktree prune`.
+pub fn sweep_stale_mounts(app_handle: &AppHandle) {
+    let Ok(root) = mounts_root(app_handle) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(&root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let removed = set_tree_writable(&path, true).and_then(|_| std::fs::remove_dir_all(&path));
+        if let Err(e) = removed {
+            log::warn!("Failed to remove stale checkpoint mount {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Release the mounts of a closed webview
+pub fn release_webview_mounts(mounts: &CheckpointMounts, webview: &str) {
+    for mount in mounts.remove_webview(webview) {
+        if let Err(e) = unmount(&mount.source, &mount.path) {
+            log::warn!("Failed to release checkpoint mount {}: {}", mount.mount_id, e);
+        }
+    }
+}
+
+/// Materialize a session checkpoint as a read-only worktree
+#[tauri::command]
+pub async fn mount_checkpoint(
+    session_id: String,
+    checkpoint_id: String,
+    webview: Webview,
+    mounts: State<'_, CheckpointMounts>,
+    app_handle: AppHandle,
+) -> Result<CheckpointMount, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let source = session_worktree(&app_handle, &session_id).await?;
+    let root = mounts_root(&app_handle)?;
+
+    let mount_id = uuid::Uuid::new_v4().to_string();
+    let checkpoint = {
+        let (source, mount_id) = (source.clone(), mount_id.clone());
+        tokio::task::spawn_blocking(move || {
+            let (commit, subject) = resolve_checkpoint(&source, &checkpoint_id)?;
+            std::fs::create_dir_all(&root).map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+            let path = root.join(format!("{}-{}", &commit[..12], &mount_id[..8]));
+            mount(&source, &commit, &path)?;
+            Ok::<_, String>((commit, subject, path))
+        })
+        .await
+        .map_err(|e| format!("Failed to mount checkpoint: {}", e))??
+    };
+
+    let (commit, subject, path) = checkpoint;
+    let mount = CheckpointMount {
+        mount_id,
+        session_id,
+        commit,
+        subject,
+        path,
+        source,
+        mounted_at: chrono::Utc::now().to_rfc3339(),
+    };
+    mounts.insert(mount.clone(), webview.label());
+    Ok(mount)
+}
+
+/// Remove a checkpoint mount; false when it was already released
+#[tauri::command]
+pub async fn release_checkpoint(mount_id: String, mounts: State<'_, CheckpointMounts>) -> Result<bool, String> {
+    let Some(mount) = mounts.remove(&mount_id) else {
+        return Ok(false);
+    };
+    tokio::task::spawn_blocking(move || unmount(&mount.source, &mount.path))
+        .await
+        .map_err(|e| format!("Failed to release checkpoint: {}", e))??;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn list_checkpoint_mounts(
+    session_id: Option<String>,
+    mounts: State<'_, CheckpointMounts>,
+) -> Result<Vec<CheckpointMount>, String> {
+    let session_id = session_id.map(|id| crate::session_codes::resolve(&id));
+    Ok(mounts.list(session_id.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount_record(mount_id: &str, session_id: &str) -> CheckpointMount {
+        CheckpointMount {
+            mount_id: mount_id.to_string(),
+            session_id: session_id.to_string(),
+            commit: "abc".to_string(),
+            subject: String::new(),
+            path: PathBuf::from("/tmp/mount"),
+            source: PathBuf::from("/tmp/source"),
+            mounted_at: mount_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_mounts_follow_their_webview() {
+        let mounts = CheckpointMounts::default();
+        mounts.insert(mount_record("1", "s1"), "main");
+        mounts.insert(mount_record("2", "s2"), "main");
+        mounts.insert(mount_record("3", "s1"), "other");
+
+        assert_eq!(mounts.list(Some("s1")).len(), 2);
+        assert_eq!(mounts.remove_webview("main").len(), 2);
+        assert_eq!(mounts.list(None).iter().map(|m| m.mount_id.as_str()).collect::<Vec<_>>(), vec!["3"]);
+        assert!(mounts.remove("3").is_some());
+        assert!(mounts.remove("3").is_none());
+    }
+
+    #[test]
+    fn test_mount_checkpoint_read_only_and_release() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        for args in [vec!["init"], vec!["config", "user.name", "Test User"], vec!["config", "user.email", "test@example.com"]] {
+            git(&repo, &args).unwrap();
+        }
+        std::fs::create_dir(repo.join("src")).unwrap();
+        std::fs::write(repo.join("src/lib.rs"), "v1\n").unwrap();
+        git(&repo, &["add", "-A"]).unwrap();
+        git(&repo, &["commit", "-m", "first checkpoint"]).unwrap();
+        let first = git(&repo, &["rev-parse", "HEAD"]).unwrap().trim().to_string();
+        std::fs::write(repo.join("src/lib.rs"), "v2\n").unwrap();
+        git(&repo, &["commit", "-am", "second"]).unwrap();
+
+        let (commit, subject) = resolve_checkpoint(&repo, &first[..8]).unwrap();
+        assert_eq!((commit.as_str(), subject.as_str()), (first.as_str(), "first checkpoint"));
+        assert!(resolve_checkpoint(&repo, "--all").is_err());
+        assert!(resolve_checkpoint(&repo, "deadbeef").is_err());
+        git(&repo, &["checkout", "-q", "-b", "side", &first]).unwrap();
+        git(&repo, &["commit", "--allow-empty", "-m", "elsewhere"]).unwrap();
+        let elsewhere = git(&repo, &["rev-parse", "HEAD"]).unwrap().trim().to_string();
+        git(&repo, &["checkout", "-q", "-"]).unwrap();
+        assert!(resolve_checkpoint(&repo, &elsewhere).is_err());
+
+        let path = dir.path().join("mounts").join("first");
+        mount(&repo, &commit, &path).unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("src/lib.rs")).unwrap(), "v1\n");
+        assert!(std::fs::metadata(path.join("src/lib.rs")).unwrap().permissions().readonly());
+        assert_eq!(std::fs::read_to_string(repo.join("src/lib.rs")).unwrap(), "v2\n");
+
+        unmount(&repo, &path).unwrap();
+        assert!(!path.exists());
+        assert!(!git(&repo, &["worktree", "list"]).unwrap().contains("first"));
+    }
+}
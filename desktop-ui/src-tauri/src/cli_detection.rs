@@ -3,9 +3,12 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Duration;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
 use tokio::time::timeout;
 
+use crate::operations::OperationRegistry;
+use crate::platform;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CliProfile {
     pub name: String,
@@ -70,9 +73,9 @@ impl CliDetector {
     async fn detect_bundled(&self) -> Option<String> {
         if let Ok(resource_dir) = self.app_handle.path().resource_dir() {
             let candidates = vec![
-                resource_dir.join("bin").join(self.cli_binary_name()),
-                resource_dir.join("amp").join(self.cli_binary_name()),
-                resource_dir.join(self.cli_binary_name()),
+                resource_dir.join("bin").join(platform::cli_binary_name()),
+                resource_dir.join("amp").join(platform::cli_binary_name()),
+                resource_dir.join(platform::cli_binary_name()),
             ];
 
             for candidate in candidates {
@@ -86,17 +89,17 @@ impl CliDetector {
         None
     }
 
-    /// Detect globally installed CLI using `which amp` or `where amp`
+    /// Detect globally installed CLI using `which amp` or `where amp`, then the
+    /// usual package manager locations in case `PATH` is incomplete
     async fn detect_global(&self) -> Option<String> {
-        let which_cmd = if cfg!(windows) { "where" } else { "which" };
-        
-        match Command::new(which_cmd)
+        match Command::new(platform::which_command())
             .arg("amp")
             .output()
         {
             Ok(output) if output.status.success() => {
                 if let Ok(path) = String::from_utf8(output.stdout) {
-                    let path = path.trim();
+                    // `where` lists every match, one per line
+                    let path = path.lines().next().unwrap_or_default().trim();
                     if !path.is_empty() && Path::new(path).exists() {
                         return Some(path.to_string());
                     }
@@ -104,30 +107,14 @@ impl CliDetector {
             }
             _ => {}
         }
-        None
+        let home_dir = platform::home_dir()?;
+        first_file(platform::global_cli_locations(&home_dir))
     }
 
     /// Detect development CLI in ~/amp directory
     async fn detect_dev_home(&self) -> Option<String> {
-        if let Some(home_dir) = dirs::home_dir() {
-            let candidates = vec![
-                // Node.js development build
-                home_dir.join("amp").join("cli").join("dist").join("main.js"),
-                // Binary development build
-                home_dir.join("amp").join("bin").join(self.cli_binary_name()),
-                home_dir.join("amp").join("target").join("release").join(self.cli_binary_name()),
-                home_dir.join("amp").join("target").join("debug").join(self.cli_binary_name()),
-            ];
-
-            for candidate in candidates {
-                if candidate.exists() && candidate.is_file() {
-                    if let Some(path) = candidate.to_str() {
-                        return Some(path.to_string());
-                    }
-                }
-            }
-        }
-        None
+        let home_dir = platform::home_dir()?;
+        first_file(platform::dev_cli_locations(&home_dir))
     }
 
     /// Validate a CLI path by running `amp --version`
@@ -212,15 +199,6 @@ impl CliDetector {
         version.chars().any(|c| c.is_ascii_digit())
     }
 
-    /// Get the CLI binary name for the current platform
-    fn cli_binary_name(&self) -> String {
-        if cfg!(windows) {
-            "amp.exe".to_string()
-        } else {
-            "amp".to_string()
-        }
-    }
-
     /// Get default profile configurations
     pub fn get_default_profiles() -> Vec<HashMap<String, String>> {
         vec![
@@ -265,6 +243,13 @@ impl CliDetector {
     }
 }
 
+fn first_file(candidates: Vec<PathBuf>) -> Option<String> {
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .and_then(|candidate| candidate.to_str().map(str::to_string))
+}
+
 // Tauri commands
 #[tauri::command]
 pub async fn detect_cli_profiles(app: AppHandle) -> Result<Vec<CliProfile>, String> {
@@ -278,23 +263,28 @@ pub async fn validate_cli_path(app: AppHandle, path: String) -> Result<Validatio
     Ok(detector.validate_cli_path(&path).await)
 }
 
+/// Install the CLI with npm as a cancellable operation, returning its id
 #[tauri::command]
-pub async fn install_global_cli() -> Result<String, String> {
-    match Command::new("npm")
-        .args(&["install", "-g", "@sourcegraph/amp"])
-        .output()
-    {
-        Ok(output) if output.status.success() => {
-            Ok("CLI installed successfully".to_string())
-        }
-        Ok(output) => {
+pub async fn install_global_cli(
+    app: AppHandle,
+    operations: State<'_, OperationRegistry>,
+) -> Result<String, String> {
+    Ok(operations.spawn("install_global_cli", Some(app), |op| async move {
+        op.progress("Running npm install -g @sourcegraph/amp", None);
+        // Killed if the operation is cancelled
+        let output = tokio::process::Command::new("npm")
+            .args(["install", "-g", "@sourcegraph/amp"])
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run npm install: {}", e))?;
+        if output.status.success() {
+            Ok("CLI installed successfully".into())
+        } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
             Err(format!("Installation failed: {}", stderr))
         }
-        Err(e) => {
-            Err(format!("Failed to run npm install: {}", e))
-        }
-    }
+    }))
 }
 
 #[tauri::command]
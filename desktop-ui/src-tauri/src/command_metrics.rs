@@ -0,0 +1,247 @@
+//! Per-command execution metrics
+//!
+//! `time_commands` wraps the invoke handler and records, per command, a latency
+//! histogram and the size of the argument payload. Invocations slower than
+//! `SLOW_COMMAND_MS` are logged with their arguments redacted the same way crash
+//! reports redact them. The clock covers dispatch: argument parsing, state
+//! lookup and, for synchronous commands, the whole command. Async commands hand
+//! their future to the runtime during dispatch, so their figures show the IPC
+//! layer's share rather than the command's own work.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{Runtime, State};
+
+use crate::services::read_cache::{CacheStats, ReadCache};
+
+/// Invocations at or above this many milliseconds are logged
+pub const SLOW_COMMAND_MS: u64 = 250;
+/// Upper bounds of the latency buckets in milliseconds; one more bucket takes the rest
+const BUCKET_BOUNDS_MS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 5000];
+
+static METRICS: Lazy<DashMap<String, CommandStats>> = Lazy::new(DashMap::new);
+
+#[derive(Debug, Clone, Default)]
+struct CommandStats {
+    calls: u64,
+    slow_calls: u64,
+    total: Duration,
+    max: Duration,
+    buckets: [u64; BUCKET_BOUNDS_MS.len() + 1],
+    payload_bytes_total: u64,
+    payload_bytes_max: u64,
+    last_slow: Option<SlowInvocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowInvocation {
+    pub duration_ms: f64,
+    /// Arguments with secrets masked and long strings cut
+    pub payload: Value,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    /// Upper bound in milliseconds, `None` for the overflow bucket
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandMetrics {
+    pub command: String,
+    pub calls: u64,
+    pub slow_calls: u64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    /// Estimated from the histogram: upper bound of the bucket holding the quantile
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub histogram: Vec<HistogramBucket>,
+    pub payload_bytes_mean: u64,
+    pub payload_bytes_max: u64,
+    pub last_slow: Option<SlowInvocation>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_nanos() as f64 / 1_000_000.0
+}
+
+/// Counts serialized bytes without keeping them
+struct ByteCounter(u64);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn payload_bytes(payload: &InvokeBody) -> u64 {
+    match payload {
+        InvokeBody::Json(value) => {
+            let mut counter = ByteCounter(0);
+            let _ = serde_json::to_writer(&mut counter, value);
+            counter.0
+        }
+        InvokeBody::Raw(bytes) => bytes.len() as u64,
+    }
+}
+
+fn redacted_payload(payload: &InvokeBody) -> Value {
+    match payload {
+        InvokeBody::Json(value) => crate::crash_reports::redact_payload(value),
+        InvokeBody::Raw(bytes) => Value::String(format!("[{} raw bytes]", bytes.len())),
+    }
+}
+
+fn record(
+    metrics: &DashMap<String, CommandStats>,
+    command: &str,
+    elapsed: Duration,
+    payload_bytes: u64,
+    payload: Value,
+) {
+    let mut stats = metrics.entry(command.to_string()).or_default();
+    stats.calls += 1;
+    stats.total += elapsed;
+    stats.max = stats.max.max(elapsed);
+    let ms = millis(elapsed);
+    let bucket = BUCKET_BOUNDS_MS.iter().position(|&bound| ms <= bound as f64).unwrap_or(BUCKET_BOUNDS_MS.len());
+    stats.buckets[bucket] += 1;
+    stats.payload_bytes_total += payload_bytes;
+    stats.payload_bytes_max = stats.payload_bytes_max.max(payload_bytes);
+    if elapsed >= Duration::from_millis(SLOW_COMMAND_MS) {
+        stats.slow_calls += 1;
+        log::warn!("Slow command {} took {:.1}ms ({} payload bytes): {}", command, ms, payload_bytes, payload);
+        stats.last_slow = Some(SlowInvocation { duration_ms: ms, payload, timestamp: chrono::Utc::now().to_rfc3339() });
+    }
+}
+
+fn quantile(stats: &CommandStats, q: f64) -> f64 {
+    let rank = (stats.calls as f64 * q).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (i, count) in stats.buckets.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            let bound = BUCKET_BOUNDS_MS.get(i).map_or(f64::INFINITY, |&b| b as f64);
+            return bound.min(millis(stats.max));
+        }
+    }
+    millis(stats.max)
+}
+
+fn summarize(command: &str, stats: &CommandStats) -> CommandMetrics {
+    let calls = stats.calls.max(1);
+    CommandMetrics {
+        command: command.to_string(),
+        calls: stats.calls,
+        slow_calls: stats.slow_calls,
+        total_ms: millis(stats.total),
+        mean_ms: millis(stats.total) / calls as f64,
+        max_ms: millis(stats.max),
+        p50_ms: quantile(stats, 0.5),
+        p95_ms: quantile(stats, 0.95),
+        histogram: stats
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| HistogramBucket { le_ms: BUCKET_BOUNDS_MS.get(i).copied(), count })
+            .collect(),
+        payload_bytes_mean: stats.payload_bytes_total / calls,
+        payload_bytes_max: stats.payload_bytes_max,
+        last_slow: stats.last_slow.clone(),
+    }
+}
+
+/// Metrics by command, most total time first
+fn snapshot(metrics: &DashMap<String, CommandStats>) -> Vec<CommandMetrics> {
+    let mut summaries: Vec<CommandMetrics> =
+        metrics.iter().map(|entry| summarize(entry.key(), entry.value())).collect();
+    summaries.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms).then_with(|| a.command.cmp(&b.command)));
+    summaries
+}
+
+/// Wrap the app's invoke handler so every dispatch is timed
+pub fn time_commands<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        let bytes = payload_bytes(invoke.message.payload());
+        let payload = redacted_payload(invoke.message.payload());
+        let started = Instant::now();
+        let handled = handler(invoke);
+        record(&METRICS, &command, started.elapsed(), bytes, payload);
+        handled
+    }
+}
+
+/// Latency and payload metrics for every command invoked so far; `reset`
+/// clears them after reading
+#[tauri::command]
+pub fn get_command_metrics(reset: Option<bool>) -> Result<Vec<CommandMetrics>, String> {
+    let metrics = snapshot(&METRICS);
+    if reset.unwrap_or(false) {
+        METRICS.clear();
+    }
+    Ok(metrics)
+}
+
+/// Hit rates of the read cache behind the polled lists; `reset` starts the counts over
+#[tauri::command]
+pub fn get_read_cache_stats(reset: Option<bool>, read_cache: State<'_, ReadCache>) -> Result<Vec<CacheStats>, String> {
+    Ok(read_cache.stats(reset.unwrap_or(false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_and_quantiles() {
+        let metrics = DashMap::new();
+        for ms in [0, 3, 3, 8, 40, 40, 40, 90, 120, 2000] {
+            record(&metrics, "list_sessions", Duration::from_millis(ms), 10, Value::Null);
+        }
+        record(&metrics, "get_app_config", Duration::from_millis(1), 4, Value::Null);
+
+        let summaries = snapshot(&metrics);
+        assert_eq!(summaries[0].command, "list_sessions");
+        let list = &summaries[0];
+        assert_eq!((list.calls, list.slow_calls), (10, 1));
+        let counts: Vec<u64> = list.histogram.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 2, 1, 0, 3, 1, 1, 0, 0, 1, 0]);
+        assert_eq!(list.histogram.last().unwrap().le_ms, None);
+        assert_eq!((list.p50_ms, list.p95_ms), (50.0, 2000.0));
+        assert_eq!((list.max_ms, list.mean_ms), (2000.0, 234.4));
+        assert_eq!((list.payload_bytes_mean, list.payload_bytes_max), (10, 10));
+        assert_eq!(summaries[1].p95_ms, 1.0);
+    }
+
+    #[test]
+    fn test_slow_invocation_keeps_redacted_payload() {
+        let metrics = DashMap::new();
+        let body = InvokeBody::Json(serde_json::json!({ "sessionId": "s1", "apiKey": "sk-123" }));
+        let bytes = payload_bytes(&body);
+        assert_eq!(bytes, serde_json::to_vec(&serde_json::json!({ "sessionId": "s1", "apiKey": "sk-123" })).unwrap().len() as u64);
+
+        record(&metrics, "session_create", Duration::from_millis(10), bytes, redacted_payload(&body));
+        assert!(snapshot(&metrics)[0].last_slow.is_none());
+
+        record(&metrics, "session_create", Duration::from_millis(SLOW_COMMAND_MS), bytes, redacted_payload(&body));
+        let slow = snapshot(&metrics)[0].last_slow.clone().unwrap();
+        assert_eq!(slow.duration_ms, SLOW_COMMAND_MS as f64);
+        assert_eq!((&slow.payload["sessionId"], &slow.payload["apiKey"]), (&Value::from("s1"), &Value::from("[REDACTED]")));
+    }
+}
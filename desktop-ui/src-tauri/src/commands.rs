@@ -1,51 +1,14 @@
 use tauri::{AppHandle, Emitter};
 use tauri_plugin_shell::ShellExt;
 use tokio::fs;
-use std::path::PathBuf;
+use std::path::Path;
 use std::process::Command;
 use serde_json::json;
 
+use crate::directory_listing::{list_directory_page, DirectoryPage, ListDirectoryOptions};
+use crate::safe_write::{write_file_safe, WriteOutcome};
+use crate::worktree_paths::WorktreePathResolver;
 
-/// Generate the worktree path for a given session ID
-fn path_for(repo_path: &std::path::Path, session_id: &str) -> std::path::PathBuf {
-    let short_sid = &session_id[..session_id.len().min(8)];
-    repo_path.join(".amp-worktrees").join(short_sid)
-}
-
-/// Helper function to get session worktree path
-/// Falls back to user-provided cwd if session worktree cannot be determined
-async fn get_session_worktree_path(session_id: Option<&str>, fallback_cwd: &str) -> String {
-    if let Some(session_id) = session_id {
-        // Try to find the repository root from fallback cwd
-        let fallback_path = PathBuf::from(fallback_cwd);
-        if let Ok(repo_path) = find_repo_root(&fallback_path) {
-            let worktree_path = path_for(&repo_path, session_id);
-            if worktree_path.exists() {
-                return worktree_path.to_string_lossy().to_string();
-            }
-            // If worktree doesn't exist, return repo root
-            return repo_path.to_string_lossy().to_string();
-        }
-    }
-    // Default fallback to provided cwd
-    fallback_cwd.to_string()
-}
-
-/// Find the Git repository root starting from a given path
-fn find_repo_root(start_path: &std::path::Path) -> Result<std::path::PathBuf, String> {
-    let mut current_path = start_path;
-    
-    loop {
-        if current_path.join(".git").exists() {
-            return Ok(current_path.to_path_buf());
-        }
-        
-        match current_path.parent() {
-            Some(parent) => current_path = parent,
-            None => return Err("No Git repository found".to_string()),
-        }
-    }
-}
 
 #[tauri::command]
 pub async fn get_current_branch(path: String) -> Result<String, String> {
@@ -91,25 +54,28 @@ pub async fn get_file_diff(path: String) -> Result<String, String> {
     }
 }
 
+/// Atomic write, see `safe_write::write_file_safe`
 #[tauri::command]
-pub async fn write_file(path: String, content: String) -> Result<(), String> {
-    use std::path::Path;
-    
-    // Ensure parent directories exist
-    if let Some(parent) = Path::new(&path).parent() {
-        fs::create_dir_all(parent).await
-            .map_err(|e| format!("Failed to create parent directories: {}", e))?;
-    }
-    
-    fs::write(&path, content).await
-        .map_err(|e| format!("Failed to write file: {}", e))?;
-        
-    Ok(())
+pub async fn write_file(
+    path: String,
+    content: String,
+    expected_hash: Option<String>,
+    session_id: Option<String>,
+    app_handle: AppHandle,
+) -> Result<WriteOutcome, String> {
+    write_file_safe(path, content, expected_hash, session_id, app_handle).await
 }
 
+/// Editor saves; written like `write_file`
 #[tauri::command]
-pub async fn save_file(path: String, contents: String) -> Result<(), String> {
-    fs::write(path, contents).await.map_err(|e| e.to_string())
+pub async fn save_file(
+    path: String,
+    contents: String,
+    expected_hash: Option<String>,
+    session_id: Option<String>,
+    app_handle: AppHandle,
+) -> Result<WriteOutcome, String> {
+    write_file_safe(path, contents, expected_hash, session_id, app_handle).await
 }
 
 #[tauri::command]
@@ -120,7 +86,7 @@ pub async fn read_file(path: String) -> Result<String, String> {
 #[tauri::command]
 pub async fn spawn_terminal(app: AppHandle, cmd: String, cwd: String, session_id: Option<String>) -> Result<String, String> {
     // Get session worktree path for command execution, fallback to provided cwd
-    let working_dir = get_session_worktree_path(session_id.as_deref(), &cwd).await;
+    let working_dir = WorktreePathResolver::of(&app).working_dir(session_id.as_deref(), Path::new(&cwd)).await;
     
     // For now, let's just execute a simple command and return a mock PID
     // This will allow the UI to work while we implement proper streaming
@@ -149,19 +115,10 @@ pub async fn spawn_terminal(app: AppHandle, cmd: String, cwd: String, session_id
     Ok(pid)
 }
 
+/// Paged listing with metadata, see `directory_listing::list_page`
 #[tauri::command]
-pub async fn list_directory(path: String) -> Result<Vec<String>, String> {
-    let path = PathBuf::from(path);
-    let mut entries = fs::read_dir(&path).await.map_err(|e| e.to_string())?;
-    let mut files = Vec::new();
-    
-    while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
-        if let Some(name) = entry.file_name().to_str() {
-            files.push(name.to_string());
-        }
-    }
-    
-    Ok(files)
+pub async fn list_directory(path: String, options: Option<ListDirectoryOptions>) -> Result<DirectoryPage, String> {
+    list_directory_page(path, options).await
 }
 
 #[tauri::command]
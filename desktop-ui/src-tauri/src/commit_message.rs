@@ -0,0 +1,371 @@
+//! Commit message drafts for session worktrees
+//!
+//! `generate_commit_message` collects the worktree's uncommitted diff and the last
+//! few messages of the session's threads and asks the model, through the Amp
+//! proxy, for a conventional-commit message. When the model is unavailable or
+//! answers with something unusable, a message is derived from the changed paths
+//! and the latest user prompt instead. The result is a draft: callers show it to
+//! the user for editing before committing.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Manager, State};
+
+use crate::amp_proxy::{amp_proxy, ProxyRequest};
+use crate::app_state::AppState;
+use crate::file_locks::FileLockService;
+use crate::git_lfs::NON_LFS_PATHSPECS;
+use crate::submodules::{submodule_status, SubmoduleState, SubmoduleStatus};
+use crate::profile_auth::ProfileManager;
+use crate::worktree_paths::WorktreePathResolver;
+
+/// Proxy path of the completion endpoint used for drafts
+const COMPLETION_PATH: &str = "/api/completions";
+/// Diff text sent to the model; larger diffs are cut and only the stat is complete
+const DIFF_LIMIT_BYTES: usize = 16 * 1024;
+const CONTEXT_MESSAGES: i64 = 8;
+const MESSAGE_EXCERPT_CHARS: usize = 600;
+const SUBJECT_MAX_CHARS: usize = 72;
+const CONVENTIONAL_TYPES: &[&str] = &["feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorktreeChanges {
+    pub stat: String,
+    pub diff: String,
+    pub diff_truncated: bool,
+    /// `(status, path)` pairs, status as in `git status --porcelain`
+    pub files: Vec<(String, String)>,
+    pub submodules: Vec<SubmoduleStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftSource {
+    Model,
+    Heuristic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitMessageDraft {
+    pub subject: String,
+    pub body: String,
+    /// Subject and body joined, ready for `git commit -F`
+    pub message: String,
+    pub source: DraftSource,
+    pub worktree_path: String,
+    pub changed_files: Vec<String>,
+}
+
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git {}: {}", args.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn truncate_chars(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((idx, _)) => &s[..idx],
+        None => s,
+    }
+}
+
+/// Changes to describe: the staged files when anything is staged, since those
+/// are the ones approved to land, otherwise everything uncommitted
+pub fn collect_changes(worktree: &Path) -> Result<WorktreeChanges, String> {
+    let changed = crate::worktree_selection::changed_files(worktree)?;
+    let staged_only = changed.iter().any(|c| c.staged);
+    let files: Vec<(String, String)> = changed
+        .into_iter()
+        .filter(|c| !staged_only || c.staged)
+        .map(|c| (c.status.trim().to_string(), c.path))
+        .collect();
+    let base = if staged_only { "--cached" } else { "HEAD" };
+    // LFS files are listed in `files` but their pointer or binary diffs are left out
+    let mut stat_args = vec!["diff", base, "--stat", "--"];
+    stat_args.extend(NON_LFS_PATHSPECS);
+    let stat = git(worktree, &stat_args)?;
+    let mut diff_args = vec!["diff", base, "--"];
+    diff_args.extend(NON_LFS_PATHSPECS);
+    let full = git(worktree, &diff_args)?;
+    let diff_truncated = full.len() > DIFF_LIMIT_BYTES;
+    let mut cut = DIFF_LIMIT_BYTES.min(full.len());
+    while !full.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let submodules = submodule_status(worktree)?;
+    Ok(WorktreeChanges { stat, diff: full[..cut].to_string(), diff_truncated, files, submodules })
+}
+
+/// `(role, content)` of the session's most recent messages, oldest first
+async fn recent_messages(db: &SqlitePool, session_id: &str) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, Option<String>)>(
+        "SELECT m.role, m.content, m.content_ref FROM messages m
+         JOIN threads t ON t.id = m.thread_id
+         WHERE t.session_id = ? OR t.id = ?
+         ORDER BY m.created_at DESC
+         LIMIT ?",
+    )
+    .bind(session_id)
+    .bind(session_id)
+    .bind(CONTEXT_MESSAGES)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .rev()
+        .map(|(role, content, content_ref)| (role, crate::message_content::resolve_content(content, content_ref)))
+        .collect())
+}
+
+pub fn build_prompt(changes: &WorktreeChanges, context: &[(String, String)]) -> String {
+    let mut prompt = String::from(
+        "Write a git commit message for the changes below using the Conventional Commits format: \
+         a subject line `type(optional scope): summary` of at most 72 characters, a blank line, \
+         then a short body explaining what changed and why. Reply with the message only.\n\n",
+    );
+    if !context.is_empty() {
+        prompt.push_str("Recent conversation with the coding agent:\n");
+        for (role, content) in context {
+            prompt.push_str(&format!("[{}] {}\n", role, truncate_chars(content.trim(), MESSAGE_EXCERPT_CHARS)));
+        }
+        prompt.push('\n');
+    }
+    prompt.push_str("Diff stat:\n");
+    prompt.push_str(&changes.stat);
+    let unusual: Vec<&SubmoduleStatus> = changes.submodules.iter().filter(|s| s.state != SubmoduleState::Current).collect();
+    if !unusual.is_empty() {
+        prompt.push_str("\nSubmodules not at their recorded commit:\n");
+        for submodule in unusual {
+            prompt.push_str(&format!("{} {:?} {}\n", submodule.path, submodule.state, submodule.commit));
+        }
+    }
+    prompt.push_str("\nDiff:\n");
+    prompt.push_str(&changes.diff);
+    if changes.diff_truncated {
+        prompt.push_str("\n[diff truncated]\n");
+    }
+    prompt
+}
+
+fn is_conventional(subject: &str) -> bool {
+    let Some((head, rest)) = subject.split_once(':') else {
+        return false;
+    };
+    let kind = head.split('(').next().unwrap_or(head).trim_end_matches('!');
+    CONVENTIONAL_TYPES.contains(&kind) && !rest.trim().is_empty()
+}
+
+/// Split a model reply into subject and body, dropping code fences and quotes.
+/// `None` when the reply has no conventional-commit subject line.
+pub fn parse_commit_message(reply: &str) -> Option<(String, String)> {
+    let lines: Vec<&str> = reply
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect();
+    let start = lines.iter().position(|line| !line.trim().is_empty())?;
+    let subject = lines[start].trim().trim_matches(|c| c == '"' || c == '`').trim();
+    if !is_conventional(subject) {
+        return None;
+    }
+    let body = lines[start + 1..].join("\n").trim().to_string();
+    Some((truncate_chars(subject, SUBJECT_MAX_CHARS).trim_end().to_string(), body))
+}
+
+fn change_type(files: &[(String, String)]) -> &'static str {
+    let paths: Vec<&str> = files.iter().map(|(_, p)| p.as_str()).collect();
+    let is_doc = |p: &str| p.ends_with(".md") || p.starts_with("docs/");
+    let is_test = |p: &str| p.contains("test");
+    if !paths.is_empty() && paths.iter().all(|p| is_doc(p)) {
+        "docs"
+    } else if !paths.is_empty() && paths.iter().all(|p| is_test(p)) {
+        "test"
+    } else if files.iter().any(|(status, _)| status == "A" || status == "??") {
+        "feat"
+    } else {
+        "chore"
+    }
+}
+
+/// Message derived from the changed paths and the latest user prompt
+pub fn heuristic_message(changes: &WorktreeChanges, context: &[(String, String)]) -> (String, String) {
+    let kind = change_type(&changes.files);
+    let summary = context
+        .iter()
+        .rev()
+        .find(|(role, _)| role == "user")
+        .and_then(|(_, content)| content.lines().find(|l| !l.trim().is_empty()))
+        .map(|line| {
+            let line = line.trim().trim_end_matches('.');
+            let mut chars = line.chars();
+            chars.next().map(|c| c.to_lowercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .unwrap_or_else(|| match changes.files.len() {
+            1 => format!("update {}", changes.files[0].1),
+            n => format!("update {} files", n),
+        });
+    let subject = format!("{}: {}", kind, summary);
+    let body = changes
+        .files
+        .iter()
+        .take(20)
+        .map(|(status, path)| format!("- {} {}", status, path))
+        .collect::<Vec<_>>()
+        .join("\n");
+    (truncate_chars(&subject, SUBJECT_MAX_CHARS).trim_end().to_string(), body)
+}
+
+pub(crate) fn completion_text(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(value) => ["text", "completion", "content", "message"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(|v| v.as_str()).map(str::to_string))
+            .unwrap_or_default(),
+        Err(_) => body.to_string(),
+    }
+}
+
+/// The worktree of a session, as its file locks or the worktree record have it
+pub(crate) async fn session_worktree(app_handle: &AppHandle, session_id: &str) -> Result<PathBuf, String> {
+    let session_id = &crate::session_codes::resolve(session_id);
+    if let Some(root) = app_handle.try_state::<FileLockService>().and_then(|s| s.session_root(session_id)) {
+        return Ok(root);
+    }
+    WorktreePathResolver::of(app_handle)
+        .worktree(session_id, None)
+        .await
+        .ok_or_else(|| format!("No worktree found for session {}", session_id))
+}
+
+/// Draft a commit message for a session's uncommitted worktree changes
+#[tauri::command]
+pub async fn generate_commit_message(
+    session_id: String,
+    profile: Option<String>,
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<CommitMessageDraft, String> {
+    let worktree = session_worktree(&app_handle, &session_id).await?;
+    let changes = collect_changes(&worktree)?;
+    if changes.files.is_empty() {
+        return Err(format!("Session {} has no uncommitted changes", session_id));
+    }
+
+    let context = {
+        let db = profile_manager.db_pool.read().await;
+        match db.as_ref() {
+            Some(db) => recent_messages(db, &session_id).await.map_err(|e| e.to_string())?,
+            None => Vec::new(),
+        }
+    };
+
+    let request = ProxyRequest {
+        method: "POST".to_string(),
+        path: COMPLETION_PATH.to_string(),
+        body: Some(serde_json::json!({ "prompt": build_prompt(&changes, &context), "max_tokens": 400 }).to_string()),
+        headers: None,
+    };
+    let drafted = match amp_proxy(request, profile, app_state).await {
+        Ok(response) if response.status < 400 => parse_commit_message(&completion_text(&response.body)),
+        Ok(response) => {
+            log::warn!("Commit message request failed with HTTP {}", response.status);
+            None
+        }
+        Err(e) => {
+            log::warn!("Commit message request failed: {}", e);
+            None
+        }
+    };
+
+    let (subject, body, source) = match drafted {
+        Some((subject, body)) => (subject, body, DraftSource::Model),
+        None => {
+            let (subject, body) = heuristic_message(&changes, &context);
+            (subject, body, DraftSource::Heuristic)
+        }
+    };
+    let message = if body.is_empty() { subject.clone() } else { format!("{}\n\n{}", subject, body) };
+
+    Ok(CommitMessageDraft {
+        subject,
+        body,
+        message,
+        source,
+        worktree_path: worktree.to_string_lossy().to_string(),
+        changed_files: changes.files.into_iter().map(|(_, path)| path).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(files: &[(&str, &str)]) -> WorktreeChanges {
+        WorktreeChanges {
+            files: files.iter().map(|(s, p)| (s.to_string(), p.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_model_reply() {
+        let reply = "```\nfeat(worktree): add per-session git identity\n\nCommits made by agents are\nattributed to the session.\n```";
+        let (subject, body) = parse_commit_message(reply).unwrap();
+        assert_eq!(subject, "feat(worktree): add per-session git identity");
+        assert_eq!(body, "Commits made by agents are\nattributed to the session.");
+
+        assert!(parse_commit_message("Here is a commit message for you").is_none());
+        assert!(parse_commit_message("fix:").is_none());
+        assert!(parse_commit_message("refactor!: drop the legacy bridge").is_some());
+        let long = format!("chore: {}", "x".repeat(100));
+        assert_eq!(parse_commit_message(&long).unwrap().0.chars().count(), SUBJECT_MAX_CHARS);
+    }
+
+    #[test]
+    fn test_heuristic_message() {
+        let context = vec![
+            ("user".to_string(), "Add retry support to the batch runner.\nUse backoff.".to_string()),
+            ("assistant".to_string(), "Done".to_string()),
+        ];
+        let (subject, body) = heuristic_message(&changes(&[("M", "src/batch.rs"), ("??", "src/retry.rs")]), &context);
+        assert_eq!(subject, "feat: add retry support to the batch runner");
+        assert_eq!(body, "- M src/batch.rs\n- ?? src/retry.rs");
+
+        let (subject, _) = heuristic_message(&changes(&[("M", "README.md")]), &[]);
+        assert_eq!(subject, "docs: update README.md");
+        let (subject, _) = heuristic_message(&changes(&[("M", "src/a.rs"), ("M", "src/b.rs")]), &[]);
+        assert_eq!(subject, "chore: update 2 files");
+    }
+
+    #[test]
+    fn test_collect_changes_from_worktree() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = dir.path();
+        for args in [
+            vec!["init"],
+            vec!["config", "user.name", "Test User"],
+            vec!["config", "user.email", "test@example.com"],
+        ] {
+            git(repo, &args).unwrap();
+        }
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        git(repo, &["add", "a.txt"]).unwrap();
+        git(repo, &["commit", "-m", "init"]).unwrap();
+        std::fs::write(repo.join("a.txt"), "two\n").unwrap();
+        std::fs::write(repo.join("b.txt"), "new\n").unwrap();
+
+        let changes = collect_changes(repo).unwrap();
+        assert_eq!(changes.files, vec![("M".to_string(), "a.txt".to_string()), ("??".to_string(), "b.txt".to_string())]);
+        assert!(changes.diff.contains("+two"));
+        assert!(!changes.diff_truncated);
+        assert!(build_prompt(&changes, &[]).contains("a.txt"));
+    }
+}
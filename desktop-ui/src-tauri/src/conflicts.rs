@@ -0,0 +1,332 @@
+//! Conflicts waiting for the user
+//!
+//! Sync and session merges never stop at a conflict. They apply a default (the
+//! later write for a row edited on two devices, the edit for a session deleted
+//! on one device and changed on another, the target's profiles for merged
+//! sessions) and record the conflict here with every option. The frontend lists
+//! open conflicts and answers each with `resolve_conflict`: picking the option
+//! that was applied closes the conflict, picking another writes it in place of
+//! the default. Open conflicts are stored in the database and survive restarts
+//! until answered; a newer conflict on the same item supersedes an older one.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{SqliteConnection, SqlitePool};
+use tauri::State;
+
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictSource {
+    Sync,
+    Merge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// A row changed on this and another device; options `local` and `remote`
+    Edit,
+    /// A session deleted on one device and changed on another; options `keep` and `delete`
+    Delete,
+    /// Merged sessions bound to different profiles; options `target` and `source`
+    Binding,
+}
+
+fn as_str<T: Serialize>(value: T) -> String {
+    serde_json::to_value(value).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictOption {
+    pub choice: String,
+    /// What choosing it writes: a row, a profile binding, or null for `delete` and `keep`
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conflict {
+    pub id: String,
+    pub source: ConflictSource,
+    pub kind: ConflictKind,
+    pub session_id: String,
+    /// Table and ID of the conflicting row, for edits
+    pub item_table: Option<String>,
+    pub item_id: Option<String>,
+    pub summary: String,
+    pub options: Vec<ConflictOption>,
+    /// Choice in effect until the conflict is resolved
+    pub applied: String,
+    pub created_at: String,
+    pub resolved_at: Option<String>,
+    pub resolution: Option<String>,
+}
+
+impl Conflict {
+    pub fn new(
+        source: ConflictSource,
+        kind: ConflictKind,
+        session_id: &str,
+        item: Option<(&str, &str)>,
+        options: Vec<ConflictOption>,
+        applied: &str,
+    ) -> Self {
+        let summary = match (kind, item) {
+            (ConflictKind::Edit, Some((table, id))) => tr!("conflict-edit", table = table, id = id),
+            (ConflictKind::Delete, _) => tr!("conflict-delete", session = session_id),
+            _ => tr!("conflict-binding", session = session_id),
+        };
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            source,
+            kind,
+            session_id: session_id.to_string(),
+            item_table: item.map(|(table, _)| table.to_string()),
+            item_id: item.map(|(_, id)| id.to_string()),
+            summary,
+            options,
+            applied: applied.to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            resolved_at: None,
+            resolution: None,
+        }
+    }
+
+    pub fn option(choice: &str, value: Value) -> ConflictOption {
+        ConflictOption { choice: choice.to_string(), value }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ConflictRow {
+    id: String,
+    source: String,
+    kind: String,
+    session_id: String,
+    item_table: Option<String>,
+    item_id: Option<String>,
+    summary: String,
+    options: String,
+    applied: String,
+    created_at: String,
+    resolved_at: Option<String>,
+    resolution: Option<String>,
+}
+
+impl TryFrom<ConflictRow> for Conflict {
+    type Error = sqlx::Error;
+
+    fn try_from(row: ConflictRow) -> Result<Self, Self::Error> {
+        let decode = |e: serde_json::Error| sqlx::Error::Decode(Box::new(e));
+        Ok(Self {
+            id: row.id,
+            source: serde_json::from_value(Value::String(row.source)).map_err(decode)?,
+            kind: serde_json::from_value(Value::String(row.kind)).map_err(decode)?,
+            session_id: row.session_id,
+            item_table: row.item_table,
+            item_id: row.item_id,
+            summary: row.summary,
+            options: serde_json::from_str(&row.options).map_err(decode)?,
+            applied: row.applied,
+            created_at: row.created_at,
+            resolved_at: row.resolved_at,
+            resolution: row.resolution,
+        })
+    }
+}
+
+/// Store a conflict, superseding any open one on the same item. Takes a
+/// connection so callers can record inside their own transaction.
+pub async fn record(conn: &mut SqliteConnection, conflict: &Conflict) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE conflicts SET resolved_at = ?, resolution = 'superseded'
+         WHERE resolved_at IS NULL AND session_id = ? AND kind = ? AND item_table IS ? AND item_id IS ?",
+    )
+    .bind(&conflict.created_at)
+    .bind(&conflict.session_id)
+    .bind(as_str(conflict.kind))
+    .bind(&conflict.item_table)
+    .bind(&conflict.item_id)
+    .execute(&mut *conn)
+    .await?;
+    let options = serde_json::to_string(&conflict.options).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    sqlx::query(
+        "INSERT INTO conflicts (id, source, kind, session_id, item_table, item_id, summary, options, applied, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&conflict.id)
+    .bind(as_str(conflict.source))
+    .bind(as_str(conflict.kind))
+    .bind(&conflict.session_id)
+    .bind(&conflict.item_table)
+    .bind(&conflict.item_id)
+    .bind(&conflict.summary)
+    .bind(options)
+    .bind(&conflict.applied)
+    .bind(&conflict.created_at)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub struct ConflictStore {
+    db: SqlitePool,
+}
+
+impl ConflictStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Open conflicts oldest first, followed by resolved ones when asked for
+    pub async fn list(&self, include_resolved: bool) -> Result<Vec<Conflict>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, ConflictRow>(
+            "SELECT * FROM conflicts WHERE resolved_at IS NULL OR ?
+             ORDER BY resolved_at IS NOT NULL, created_at, id",
+        )
+        .bind(include_resolved)
+        .fetch_all(&self.db)
+        .await?;
+        rows.into_iter().map(Conflict::try_from).collect()
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Conflict>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ConflictRow>("SELECT * FROM conflicts WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await?;
+        row.map(Conflict::try_from).transpose()
+    }
+
+    async fn mark_resolved(&self, id: &str, choice: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE conflicts SET resolved_at = ?, resolution = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(choice)
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Write a choice other than the applied one
+async fn apply_choice(db: &SqlitePool, conflict: &Conflict, option: &ConflictOption) -> Result<(), String> {
+    match (conflict.kind, option.choice.as_str(), &option.value) {
+        (ConflictKind::Edit, _, Value::Object(row)) => {
+            let table = conflict.item_table.as_deref().ok_or("Conflict has no table")?;
+            crate::remote_sync::write_row(db, table, row).await
+        }
+        (ConflictKind::Delete, "delete", _) => crate::remote_sync::trash_session(db, &conflict.session_id).await,
+        (ConflictKind::Binding, _, binding) => {
+            sqlx::query("UPDATE sessions SET profile_id = ?, amp_profile_id = ? WHERE id = ?")
+                .bind(binding.get("profile_id").and_then(|v| v.as_i64()))
+                .bind(binding.get("amp_profile_id").and_then(|v| v.as_str()))
+                .bind(&conflict.session_id)
+                .execute(db)
+                .await
+                .map_err(|e| format!("Failed to update session {}: {}", conflict.session_id, e))?;
+            Ok(())
+        }
+        _ => Err(format!("Choice '{}' cannot be applied to conflict {}", option.choice, conflict.id)),
+    }
+}
+
+/// Settle a conflict with one of its options
+pub async fn resolve(db: &SqlitePool, id: &str, choice: &str) -> Result<Conflict, String> {
+    let store = ConflictStore::new(db.clone());
+    let read_error = |e: sqlx::Error| format!("Failed to read conflict {}: {}", id, e);
+    let conflict = store.get(id).await.map_err(read_error)?.ok_or_else(|| format!("Conflict {} not found", id))?;
+    if conflict.resolved_at.is_some() {
+        return Err(format!("Conflict {} is already resolved", id));
+    }
+    let option = conflict.options.iter().find(|o| o.choice == choice).ok_or_else(|| {
+        let choices: Vec<&str> = conflict.options.iter().map(|o| o.choice.as_str()).collect();
+        format!("'{}' is not an option for conflict {}; choose one of {}", choice, id, choices.join(", "))
+    })?;
+    if choice != conflict.applied {
+        apply_choice(db, &conflict, option).await?;
+    }
+    store.mark_resolved(id, choice).await.map_err(|e| format!("Failed to resolve conflict {}: {}", id, e))?;
+    store.get(id).await.map_err(read_error)?.ok_or_else(|| format!("Conflict {} not found", id))
+}
+
+#[tauri::command]
+pub async fn list_conflicts(
+    include_resolved: Option<bool>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<Conflict>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    ConflictStore::new(db.clone())
+        .list(include_resolved.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to list conflicts: {}", e))
+}
+
+/// Answer a conflict with the `choice` of one of its options
+#[tauri::command]
+pub async fn resolve_conflict(
+    id: String,
+    choice: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Conflict, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    resolve(db, &id, &choice).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use serde_json::json;
+
+    fn binding_conflict() -> Conflict {
+        Conflict::new(
+            ConflictSource::Merge,
+            ConflictKind::Binding,
+            "s1",
+            None,
+            vec![
+                Conflict::option("target", json!({ "profile_id": null, "amp_profile_id": null })),
+                Conflict::option("source", json!({ "profile_id": null, "amp_profile_id": "dev" })),
+            ],
+            "target",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_conflicts_persist_until_resolved() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Merged')").execute(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let stale = binding_conflict();
+        record(&mut conn, &stale).await.unwrap();
+        let conflict = binding_conflict();
+        record(&mut conn, &conflict).await.unwrap();
+        drop(conn);
+
+        // A fresh store sees what an earlier run recorded
+        let store = ConflictStore::new(pool.clone());
+        assert_eq!(store.list(false).await.unwrap(), vec![conflict.clone()]);
+        let superseded = store.get(&stale.id).await.unwrap().unwrap();
+        assert_eq!(superseded.resolution.as_deref(), Some("superseded"));
+
+        let err = resolve(&pool, &conflict.id, "neither").await.unwrap_err();
+        assert!(err.contains("target, source"), "{}", err);
+        let resolved = resolve(&pool, &conflict.id, "source").await.unwrap();
+        assert_eq!(resolved.resolution.as_deref(), Some("source"));
+        let binding: Option<String> = sqlx::query_scalar("SELECT amp_profile_id FROM sessions WHERE id = 's1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(binding.as_deref(), Some("dev"));
+
+        assert!(store.list(false).await.unwrap().is_empty());
+        assert_eq!(store.list(true).await.unwrap().len(), 2);
+        assert!(resolve(&pool, &conflict.id, "target").await.is_err());
+    }
+}
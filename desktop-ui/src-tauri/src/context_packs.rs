@@ -0,0 +1,765 @@
+//! Context packs: files and documents injected into a thread's first prompt
+//!
+//! A pack lists files, globs and URLs relative to the thread's working
+//! directory. Building it reads the files, shares the pack's token budget
+//! between them (small files leave their unused share to the rest) and cuts
+//! larger ones down to their beginning and end. The result goes between
+//! `--- BEGIN CONTEXT ---` and `--- END CONTEXT ---` ahead of the user's prompt,
+//! one fenced block per file. URLs are fetched as text by `url_ingest` and
+//! share the budget like files; those that cannot be fetched are listed for
+//! the agent to look up.
+//!
+//! Packs are saved by name as reusable templates. Every send records the
+//! definition used and, per source, what was included with a hash of the file
+//! it came from, so a session's starting context can be reconstructed.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::{AppHandle, Manager, State};
+
+use futures_util::future::join_all;
+
+use crate::directory_listing::glob_to_regex;
+use crate::editor_rpc::fence_for;
+use crate::profile_auth::ProfileManager;
+use crate::url_ingest::{self, FetchedDocument};
+
+pub const DEFAULT_TOKEN_BUDGET: usize = 8_000;
+pub const MAX_TOKEN_BUDGET: usize = 100_000;
+pub const MAX_SOURCES: usize = 50;
+/// Files a pack may include after expanding globs
+pub const MAX_FILES: usize = 200;
+/// Larger files are skipped rather than read
+pub const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// Rough size of a token, for budgeting
+const BYTES_PER_TOKEN: usize = 4;
+/// A file whose share of the budget is smaller is listed but not included
+const MIN_FILE_BYTES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ContextSource {
+    /// A file relative to the working directory
+    File { path: String },
+    /// Files matching a glob relative to the working directory; `**` matches any depth
+    Glob { pattern: String },
+    Url { url: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextPackDefinition {
+    pub sources: Vec<ContextSource>,
+    /// Estimated tokens the file contents may take; `DEFAULT_TOKEN_BUDGET` when unset
+    #[serde(default)]
+    pub token_budget: Option<usize>,
+}
+
+impl ContextPackDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sources.is_empty() || self.sources.len() > MAX_SOURCES {
+            return Err(format!("A context pack needs 1 to {} sources", MAX_SOURCES));
+        }
+        if self.token_budget.is_some_and(|budget| budget == 0 || budget > MAX_TOKEN_BUDGET) {
+            return Err(format!("Token budget must be between 1 and {}", MAX_TOKEN_BUDGET));
+        }
+        for source in &self.sources {
+            match source {
+                ContextSource::File { path: relative } | ContextSource::Glob { pattern: relative } => {
+                    let path = Path::new(relative);
+                    if relative.trim().is_empty()
+                        || path.is_absolute()
+                        || path.components().any(|c| matches!(c, std::path::Component::ParentDir))
+                    {
+                        return Err(format!("'{}' must be a path inside the working directory", relative));
+                    }
+                }
+                ContextSource::Url { url } => {
+                    if !(url.starts_with("https://") || url.starts_with("http://")) {
+                        return Err(format!("'{}' is not an http(s) URL", url));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn budget_bytes(&self) -> usize {
+        self.token_budget.unwrap_or(DEFAULT_TOKEN_BUDGET) * BYTES_PER_TOKEN
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    Included,
+    Truncated,
+    /// Listed by name only, for lack of budget
+    Omitted,
+    Skipped,
+    /// A URL listed in the prompt without its content, as it could not be fetched
+    Referenced,
+}
+
+/// What one file or URL contributed to the prompt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InjectedItem {
+    /// Path relative to the working directory, or the URL
+    pub source: String,
+    pub status: ItemStatus,
+    pub bytes: u64,
+    pub included_bytes: u64,
+    /// Hash of the whole file as read
+    pub content_hash: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl InjectedItem {
+    fn skipped(source: &str, reason: impl Into<String>) -> Self {
+        Self { source: source.to_string(), status: ItemStatus::Skipped, bytes: 0, included_bytes: 0, content_hash: None, reason: Some(reason.into()) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuiltContext {
+    /// The first user message: context followed by the prompt
+    pub message: String,
+    pub items: Vec<InjectedItem>,
+    pub estimated_tokens: usize,
+}
+
+pub(crate) enum GlobSegment {
+    /// `**`
+    AnyDepth,
+    Component(Regex),
+}
+
+pub(crate) fn compile_glob(pattern: &str) -> Result<Vec<GlobSegment>, String> {
+    pattern
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .map(|part| match part {
+            "**" => Ok(GlobSegment::AnyDepth),
+            _ => glob_to_regex(part)
+                .map(GlobSegment::Component)
+                .map_err(|e| format!("Invalid pattern {}: {}", pattern, e)),
+        })
+        .collect()
+}
+
+pub(crate) fn glob_matches(segments: &[GlobSegment], parts: &[&str]) -> bool {
+    match segments.split_first() {
+        None => parts.is_empty(),
+        Some((GlobSegment::AnyDepth, rest)) => (0..=parts.len()).any(|skip| glob_matches(rest, &parts[skip..])),
+        Some((GlobSegment::Component(re), rest)) => {
+            parts.split_first().is_some_and(|(part, tail)| re.is_match(part) && glob_matches(rest, tail))
+        }
+    }
+}
+
+/// Files under `root` as relative paths: what Git tracks or would track, or
+/// everything outside `.git` when `root` is not a repository
+pub(crate) fn candidate_files(root: &Path) -> Vec<String> {
+    let output = Command::new("git")
+        .current_dir(root)
+        .args(["ls-files", "-z", "--cached", "--others", "--exclude-standard"])
+        .output();
+    let mut files: Vec<String> = match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| Some(entry.path().strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/")))
+            .collect(),
+    };
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Files named by the sources, in order and without repeats, plus the sources that matched nothing
+fn resolve_files(root: &Path, sources: &[ContextSource]) -> Result<(Vec<String>, Vec<InjectedItem>), String> {
+    let (mut files, mut problems) = (Vec::<String>::new(), Vec::new());
+    let mut candidates = None;
+    for source in sources {
+        match source {
+            ContextSource::File { path } => {
+                let path = path.trim_start_matches("./").to_string();
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+            ContextSource::Glob { pattern } => {
+                let segments = compile_glob(pattern)?;
+                let candidates = candidates.get_or_insert_with(|| candidate_files(root));
+                let before = files.len();
+                for candidate in candidates.iter() {
+                    let parts: Vec<&str> = candidate.split('/').collect();
+                    if glob_matches(&segments, &parts) && !files.contains(candidate) {
+                        files.push(candidate.clone());
+                    }
+                }
+                if files.len() == before {
+                    problems.push(InjectedItem::skipped(pattern, "No files match"));
+                }
+            }
+            ContextSource::Url { .. } => {}
+        }
+    }
+    if files.len() > MAX_FILES {
+        for extra in files.split_off(MAX_FILES) {
+            problems.push(InjectedItem::skipped(&extra, format!("Packs are limited to {} files", MAX_FILES)));
+        }
+    }
+    Ok((files, problems))
+}
+
+/// Contents of a file inside `root`, refusing anything that resolves outside it
+fn read_text(root: &Path, relative: &str) -> Result<(String, Vec<u8>), String> {
+    let root = root.canonicalize().map_err(|e| format!("Working directory unavailable: {}", e))?;
+    let path = root.join(relative).canonicalize().map_err(|_| "File not found".to_string())?;
+    if !path.starts_with(&root) {
+        return Err("Resolves outside the working directory".to_string());
+    }
+    let metadata = std::fs::metadata(&path).map_err(|e| e.to_string())?;
+    if !metadata.is_file() {
+        return Err("Not a file".to_string());
+    }
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!("Larger than {} bytes", MAX_FILE_BYTES));
+    }
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    if bytes.iter().take(8000).any(|b| *b == 0) {
+        return Err("Binary file".to_string());
+    }
+    Ok((String::from_utf8_lossy(&bytes).into_owned(), bytes))
+}
+
+/// Split `budget` bytes between files of the given sizes: each gets an equal
+/// share, and what smaller files leave over goes to the larger ones
+fn allocate(sizes: &[usize], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| sizes[i]);
+    let mut allocation = vec![0; sizes.len()];
+    let mut remaining = budget;
+    for (n, &i) in order.iter().enumerate() {
+        allocation[i] = sizes[i].min(remaining / (sizes.len() - n));
+        remaining -= allocation[i];
+    }
+    allocation
+}
+
+fn floor_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Keep the first two thirds and the last third of `max_bytes` of `text`, cut at line breaks
+fn truncate_middle(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let head_end = floor_boundary(text, max_bytes * 2 / 3);
+    let head_end = text[..head_end].rfind('\n').map_or(head_end, |i| i + 1);
+    let tail_start = floor_boundary(text, text.len() - (max_bytes - head_end));
+    let tail_start = text[tail_start..].find('\n').map_or(tail_start, |i| tail_start + i + 1);
+    let tail_start = tail_start.max(head_end);
+    let omitted = text[head_end..tail_start].lines().count();
+    format!("{}[... {} lines omitted ...]\n{}", &text[..head_end], omitted, &text[tail_start..])
+}
+
+/// A fetched URL, or why it could not be fetched
+pub type UrlDocument = (String, Result<FetchedDocument, String>);
+
+/// Fetch the pack's URLs
+pub async fn fetch_documents(definition: &ContextPackDefinition) -> Vec<UrlDocument> {
+    let urls: Vec<String> = definition
+        .sources
+        .iter()
+        .filter_map(|source| match source {
+            ContextSource::Url { url } => Some(url.clone()),
+            _ => None,
+        })
+        .collect();
+    let documents = join_all(urls.iter().map(|url| url_ingest::fetch(url))).await;
+    urls.into_iter().zip(documents).collect()
+}
+
+/// Build the first message of a thread working in `root`, with the pack's
+/// URLs already fetched into `documents`
+pub fn build(
+    root: &Path,
+    definition: &ContextPackDefinition,
+    prompt: &str,
+    documents: &[UrlDocument],
+) -> Result<BuiltContext, String> {
+    definition.validate()?;
+    let (files, mut problems) = resolve_files(root, &definition.sources)?;
+
+    // (item, text, fence language)
+    let mut entries = Vec::new();
+    for relative in files {
+        match read_text(root, &relative) {
+            Ok((text, bytes)) => {
+                let language = Path::new(&relative).extension().and_then(|e| e.to_str()).unwrap_or("").to_string();
+                let item = InjectedItem {
+                    source: relative,
+                    status: ItemStatus::Included,
+                    bytes: bytes.len() as u64,
+                    included_bytes: 0,
+                    content_hash: Some(crate::safe_write::content_hash(&bytes)),
+                    reason: None,
+                };
+                entries.push((item, text, language));
+            }
+            Err(reason) => problems.push(InjectedItem::skipped(&relative, reason)),
+        }
+    }
+    let mut unfetched = Vec::new();
+    for (url, document) in documents {
+        match document {
+            Ok(document) => {
+                let item = InjectedItem {
+                    source: url.clone(),
+                    status: ItemStatus::Included,
+                    bytes: document.text.len() as u64,
+                    included_bytes: 0,
+                    content_hash: Some(crate::safe_write::content_hash(document.text.as_bytes())),
+                    reason: document.truncated.then(|| format!("Download stopped at {} bytes", document.bytes)),
+                };
+                entries.push((item, document.text.clone(), document.language().to_string()));
+            }
+            Err(reason) => {
+                unfetched.push(url.as_str());
+                problems.push(InjectedItem {
+                    source: url.clone(),
+                    status: ItemStatus::Referenced,
+                    bytes: 0,
+                    included_bytes: 0,
+                    content_hash: None,
+                    reason: Some(reason.clone()),
+                });
+            }
+        }
+    }
+
+    let sizes: Vec<usize> = entries.iter().map(|(_, text, _)| text.len()).collect();
+    let allocation = allocate(&sizes, definition.budget_bytes());
+    let mut blocks = Vec::new();
+    let mut omitted = Vec::new();
+    for ((item, text, language), share) in entries.iter_mut().zip(allocation) {
+        if share < text.len().min(MIN_FILE_BYTES) {
+            item.status = ItemStatus::Omitted;
+            item.reason = Some("Token budget exhausted".to_string());
+            omitted.push(format!("`{}`", item.source));
+            continue;
+        }
+        let content = truncate_middle(text, share);
+        let header = if content.len() < text.len() {
+            item.status = ItemStatus::Truncated;
+            format!("`{}` (truncated, {} of {} bytes):", item.source, share, text.len())
+        } else {
+            format!("`{}`:", item.source)
+        };
+        item.included_bytes = share.min(text.len()) as u64;
+        let fence = fence_for(&content);
+        blocks.push(format!("{}\n{}{}\n{}\n{}", header, fence, language, content.trim_end_matches('\n'), fence));
+    }
+
+    let mut context = String::from("--- BEGIN CONTEXT ---\nThe following was attached for reference. Truncated files keep their beginning and end.\n");
+    for block in &blocks {
+        context.push('\n');
+        context.push_str(block);
+        context.push('\n');
+    }
+    if !omitted.is_empty() {
+        context.push_str(&format!("\nAlso relevant, but left out for space: {}\n", omitted.join(", ")));
+    }
+    if !unfetched.is_empty() {
+        context.push_str("\nReferenced documents:\n");
+        for url in &unfetched {
+            context.push_str(&format!("- {}\n", url));
+        }
+    }
+    context.push_str("--- END CONTEXT ---\n\n");
+    let message = context + prompt.trim();
+
+    let mut items: Vec<InjectedItem> = entries.into_iter().map(|(item, ..)| item).collect();
+    items.extend(problems);
+    Ok(BuiltContext { estimated_tokens: message.len().div_ceil(BYTES_PER_TOKEN), message, items })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextPack {
+    pub id: String,
+    pub name: String,
+    pub definition: ContextPackDefinition,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextInjection {
+    pub id: i64,
+    pub thread_id: String,
+    /// The saved pack used, if any
+    pub pack_id: Option<String>,
+    pub definition: ContextPackDefinition,
+    pub items: Vec<InjectedItem>,
+    /// Hash of the message as sent
+    pub message_hash: String,
+    pub estimated_tokens: i64,
+    pub created_at: String,
+}
+
+type PackRow = (String, String, String, String, String);
+
+fn pack_from_row((id, name, definition, created_at, updated_at): PackRow) -> Result<ContextPack, String> {
+    let definition = serde_json::from_str(&definition).map_err(|e| format!("Context pack {} is unreadable: {}", name, e))?;
+    Ok(ContextPack { id, name, definition, created_at, updated_at })
+}
+
+/// Create a pack, or replace the definition of the one with this name
+pub async fn save_pack(db: &SqlitePool, name: &str, definition: &ContextPackDefinition) -> Result<ContextPack, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Context pack name cannot be empty".to_string());
+    }
+    definition.validate()?;
+    let json = serde_json::to_string(definition).map_err(|e| e.to_string())?;
+    let row = sqlx::query_as::<_, PackRow>(
+        "INSERT INTO context_packs (id, name, definition) VALUES (?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET definition = excluded.definition, updated_at = (datetime('now', 'utc') || 'Z')
+         RETURNING id, name, definition, created_at, updated_at",
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(name)
+    .bind(json)
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to save context pack: {}", e))?;
+    pack_from_row(row)
+}
+
+pub async fn get_pack(db: &SqlitePool, id: &str) -> Result<Option<ContextPack>, String> {
+    sqlx::query_as::<_, PackRow>("SELECT id, name, definition, created_at, updated_at FROM context_packs WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read context pack: {}", e))?
+        .map(pack_from_row)
+        .transpose()
+}
+
+pub async fn list_packs(db: &SqlitePool) -> Result<Vec<ContextPack>, String> {
+    sqlx::query_as::<_, PackRow>("SELECT id, name, definition, created_at, updated_at FROM context_packs ORDER BY name")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to list context packs: {}", e))?
+        .into_iter()
+        .map(pack_from_row)
+        .collect()
+}
+
+pub async fn record_injection(
+    db: &SqlitePool,
+    thread_id: &str,
+    pack_id: Option<&str>,
+    definition: &ContextPackDefinition,
+    built: &BuiltContext,
+) -> Result<ContextInjection, String> {
+    let message_hash = crate::safe_write::content_hash(built.message.as_bytes());
+    let (id, created_at): (i64, String) = sqlx::query_as(
+        "INSERT INTO context_injections (thread_id, pack_id, definition, manifest, message_hash, estimated_tokens)
+         VALUES (?, ?, ?, ?, ?, ?) RETURNING id, created_at",
+    )
+    .bind(thread_id)
+    .bind(pack_id)
+    .bind(serde_json::to_string(definition).map_err(|e| e.to_string())?)
+    .bind(serde_json::to_string(&built.items).map_err(|e| e.to_string())?)
+    .bind(&message_hash)
+    .bind(built.estimated_tokens as i64)
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to record context injection: {}", e))?;
+    Ok(ContextInjection {
+        id,
+        thread_id: thread_id.to_string(),
+        pack_id: pack_id.map(str::to_string),
+        definition: definition.clone(),
+        items: built.items.clone(),
+        message_hash,
+        estimated_tokens: built.estimated_tokens as i64,
+        created_at,
+    })
+}
+
+pub async fn injections(db: &SqlitePool, thread_id: &str) -> Result<Vec<ContextInjection>, String> {
+    let rows = sqlx::query_as::<_, (i64, Option<String>, String, String, String, i64, String)>(
+        "SELECT id, pack_id, definition, manifest, message_hash, estimated_tokens, created_at
+         FROM context_injections WHERE thread_id = ? ORDER BY id",
+    )
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read context injections: {}", e))?;
+    rows.into_iter()
+        .map(|(id, pack_id, definition, manifest, message_hash, estimated_tokens, created_at)| {
+            Ok(ContextInjection {
+                id,
+                thread_id: thread_id.to_string(),
+                pack_id,
+                definition: serde_json::from_str(&definition).map_err(|e| e.to_string())?,
+                items: serde_json::from_str(&manifest).map_err(|e| e.to_string())?,
+                message_hash,
+                estimated_tokens,
+                created_at,
+            })
+        })
+        .collect()
+}
+
+/// Working directory of a running thread, else its session's recorded worktree
+async fn thread_root(app_handle: &AppHandle, db: &SqlitePool, thread_id: &str) -> Result<PathBuf, String> {
+    if let Some(root) = app_handle
+        .try_state::<crate::file_locks::FileLockService>()
+        .and_then(|locks| locks.session_root(thread_id))
+    {
+        return Ok(root);
+    }
+    let session_id: Option<String> = sqlx::query_scalar("SELECT session_id FROM threads WHERE id = ?")
+        .bind(thread_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to get thread: {}", e))?;
+    match session_id {
+        Some(session_id) => crate::repo_relocation::recorded_worktree(db, &session_id)
+            .await
+            .ok_or_else(|| format!("No working directory found for thread {}", thread_id)),
+        None => Err(format!("Thread {} not found", thread_id)),
+    }
+}
+
+/// A saved pack's definition, or the one given inline
+async fn resolve_definition(
+    db: &SqlitePool,
+    pack_id: Option<String>,
+    definition: Option<ContextPackDefinition>,
+) -> Result<(Option<String>, ContextPackDefinition), String> {
+    match (pack_id, definition) {
+        (Some(pack_id), None) => {
+            let pack = get_pack(db, &pack_id).await?.ok_or_else(|| format!("Context pack {} not found", pack_id))?;
+            Ok((Some(pack.id), pack.definition))
+        }
+        (None, Some(definition)) => Ok((None, definition)),
+        _ => Err("Give either a saved pack or a definition".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn save_context_pack(
+    name: String,
+    definition: ContextPackDefinition,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ContextPack, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    save_pack(db, &name, &definition).await
+}
+
+#[tauri::command]
+pub async fn list_context_packs(profile_manager: State<'_, ProfileManager>) -> Result<Vec<ContextPack>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    list_packs(db).await
+}
+
+/// Returns whether the pack existed
+#[tauri::command]
+pub async fn delete_context_pack(pack_id: String, profile_manager: State<'_, ProfileManager>) -> Result<bool, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let result = sqlx::query("DELETE FROM context_packs WHERE id = ?")
+        .bind(&pack_id)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to delete context pack: {}", e))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The message a pack would produce for a thread, without sending it
+#[tauri::command]
+pub async fn preview_context_pack(
+    thread_id: String,
+    prompt: String,
+    pack_id: Option<String>,
+    definition: Option<ContextPackDefinition>,
+    app_handle: AppHandle,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<BuiltContext, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let (_, definition) = resolve_definition(db, pack_id, definition).await?;
+    let root = thread_root(&app_handle, db, &thread_id).await?;
+    build(&root, &definition, &prompt, &fetch_documents(&definition).await)
+}
+
+/// Send `prompt` to a thread with the pack's context ahead of it, and record
+/// what was injected
+#[tauri::command]
+pub async fn send_with_context_pack(
+    thread_id: String,
+    prompt: String,
+    pack_id: Option<String>,
+    definition: Option<ContextPackDefinition>,
+    app_handle: AppHandle,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ContextInjection, String> {
+    let db = profile_manager.db_pool.read().await.clone().ok_or("Database not available")?;
+    let (pack_id, definition) = resolve_definition(&db, pack_id, definition).await?;
+    let root = thread_root(&app_handle, &db, &thread_id).await?;
+    let built = build(&root, &definition, &prompt, &fetch_documents(&definition).await)?;
+    crate::thread_session_commands::thread_send_message(
+        thread_id.clone(),
+        built.message.clone(),
+        app_handle.state(),
+        app_handle.state(),
+        app_handle.state(),
+    )
+    .await?;
+    record_injection(&db, &thread_id, pack_id.as_deref(), &definition, &built).await
+}
+
+/// Every pack injected into a thread, oldest first
+#[tauri::command]
+pub async fn get_context_injections(
+    thread_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<ContextInjection>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    injections(db, &thread_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    fn file(path: &str) -> ContextSource {
+        ContextSource::File { path: path.to_string() }
+    }
+
+    #[test]
+    fn test_build_fits_files_to_budget() {
+        let dir = TempDir::new().unwrap();
+        let root = &dir.path().join("repo");
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(root.join("README.md"), "# Project\nUse ```make``` to build.\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+        std::fs::write(root.join("src/nested/big.rs"), (0..2000).map(|i| format!("// line {}\n", i)).collect::<String>()).unwrap();
+        std::fs::write(root.join("src/data.bin"), [0u8, 1, 2]).unwrap();
+        std::fs::write(dir.path().join("outside.txt"), "secret").unwrap();
+
+        let definition = ContextPackDefinition {
+            sources: vec![
+                file("README.md"),
+                ContextSource::Glob { pattern: "src/**/*.rs".to_string() },
+                file("src/data.bin"),
+                file("missing.txt"),
+                ContextSource::Glob { pattern: "docs/*.md".to_string() },
+                ContextSource::Url { url: "https://example.com/guide".to_string() },
+                ContextSource::Url { url: "https://example.com/down".to_string() },
+            ],
+            token_budget: Some(500),
+        };
+        let guide = FetchedDocument {
+            url: "https://example.com/guide".to_string(),
+            title: Some("Guide".to_string()),
+            text: "## Building\n\nRun make.\n".to_string(),
+            content_type: "text/html".to_string(),
+            bytes: 120,
+            truncated: false,
+            fetched_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        let documents = vec![
+            ("https://example.com/guide".to_string(), Ok(guide.clone())),
+            ("https://example.com/down".to_string(), Err("https://example.com/down timed out".to_string())),
+        ];
+        let built = build(root, &definition, "Fix the build", &documents).unwrap();
+        let status = |source: &str| built.items.iter().find(|i| i.source == source).map(|i| i.status);
+
+        assert_eq!(status("README.md"), Some(ItemStatus::Included));
+        assert_eq!(status("src/lib.rs"), Some(ItemStatus::Included));
+        assert_eq!(status("src/nested/big.rs"), Some(ItemStatus::Truncated));
+        assert_eq!(status("src/data.bin"), Some(ItemStatus::Skipped));
+        assert_eq!(status("missing.txt"), Some(ItemStatus::Skipped));
+        assert_eq!(status("docs/*.md"), Some(ItemStatus::Skipped));
+        assert_eq!(status("https://example.com/guide"), Some(ItemStatus::Included));
+        assert_eq!(status("https://example.com/down"), Some(ItemStatus::Referenced));
+
+        let included: u64 = built.items.iter().map(|i| i.included_bytes).sum();
+        assert!(included <= 500 * BYTES_PER_TOKEN as u64, "{} bytes included", included);
+        assert!(built.message.starts_with("--- BEGIN CONTEXT ---"));
+        assert!(built.message.ends_with("--- END CONTEXT ---\n\nFix the build"));
+        assert!(built.message.contains("````md\n# Project"));
+        assert!(built.message.contains("// line 0\n") && built.message.contains("// line 1999\n"));
+        assert!(built.message.contains("lines omitted ..."));
+        assert!(built.message.contains("`https://example.com/guide`:\n```md\n## Building\n\nRun make.\n```"));
+        assert!(built.message.contains("Referenced documents:\n- https://example.com/down\n"));
+        assert_eq!(
+            built.items[0].content_hash.as_deref(),
+            Some(crate::safe_write::content_hash(b"# Project\nUse ```make``` to build.\n").as_str())
+        );
+
+        // Nothing may be read from outside the working directory
+        assert!(build(root, &ContextPackDefinition { sources: vec![file("../outside.txt")], token_budget: None }, "", &[]).is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), root.join("escape")).unwrap();
+            let built = build(root, &ContextPackDefinition { sources: vec![file("escape/outside.txt")], token_budget: None }, "", &[]).unwrap();
+            assert_eq!(built.items[0].status, ItemStatus::Skipped);
+        }
+
+        // A tiny budget lists files without their contents
+        let tight = ContextPackDefinition { sources: vec![file("src/nested/big.rs")], token_budget: Some(10) };
+        let built = build(root, &tight, "Go", &[]).unwrap();
+        assert_eq!(built.items[0].status, ItemStatus::Omitted);
+        assert!(built.message.contains("left out for space: `src/nested/big.rs`"));
+    }
+
+    #[tokio::test]
+    async fn test_packs_and_injections_round_trip() {
+        let pool = test_pool().await;
+
+        let definition = ContextPackDefinition { sources: vec![file("README.md")], token_budget: None };
+        let pack = save_pack(&pool, "Docs", &definition).await.unwrap();
+        let updated = ContextPackDefinition { token_budget: Some(2000), ..definition.clone() };
+        let replaced = save_pack(&pool, "Docs", &updated).await.unwrap();
+        assert_eq!(replaced.id, pack.id);
+        assert_eq!(list_packs(&pool).await.unwrap(), vec![replaced.clone()]);
+        assert_eq!(get_pack(&pool, &pack.id).await.unwrap().unwrap().definition, updated);
+        assert!(save_pack(&pool, "Bad", &ContextPackDefinition { sources: vec![], token_budget: None }).await.is_err());
+
+        let built = BuiltContext {
+            message: "--- BEGIN CONTEXT ---\n--- END CONTEXT ---\n\nHi".to_string(),
+            items: vec![InjectedItem::skipped("README.md", "File not found")],
+            estimated_tokens: 12,
+        };
+        let recorded = record_injection(&pool, "t1", Some(&pack.id), &updated, &built).await.unwrap();
+        assert_eq!(recorded.message_hash, crate::safe_write::content_hash(built.message.as_bytes()));
+        assert_eq!(injections(&pool, "t1").await.unwrap(), vec![recorded]);
+        assert!(injections(&pool, "t2").await.unwrap().is_empty());
+    }
+}
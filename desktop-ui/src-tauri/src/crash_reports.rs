@@ -0,0 +1,264 @@
+//! Crash reports for panics in the backend
+//!
+//! `install` sets a panic hook that writes a JSON report to
+//! `<app data>/crash_reports/` and emits `backend_crash` with its path. A report
+//! holds the backtrace, the latest log lines and the latest command invocations
+//! with their payloads redacted. When the panic happens while a command is being
+//! dispatched on the same thread, that invocation is the report's `command`;
+//! async commands run on runtime workers, so for those `recent_commands` is what
+//! points at the trigger.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+/// Log lines kept for the next report
+pub const RECENT_LOG_LINES: usize = 200;
+/// Command invocations kept for the next report
+pub const RECENT_COMMANDS: usize = 20;
+/// Longer payload strings are cut to this many characters
+const MAX_PAYLOAD_STRING: usize = 256;
+const REDACTED: &str = "[REDACTED]";
+
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static RECENT_INVOKES: Mutex<VecDeque<InvokeRecord>> = Mutex::new(VecDeque::new());
+
+thread_local! {
+    static CURRENT_COMMAND: RefCell<Option<InvokeRecord>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvokeRecord {
+    pub command: String,
+    /// Arguments with secrets masked and long strings cut
+    pub payload: Value,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub id: String,
+    pub timestamp: String,
+    pub app_version: String,
+    pub thread: String,
+    pub message: String,
+    /// `file:line:column` of the panic
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// Command being dispatched on the panicking thread
+    pub command: Option<InvokeRecord>,
+    /// Latest invocations, oldest first
+    pub recent_commands: Vec<InvokeRecord>,
+    pub recent_logs: Vec<String>,
+}
+
+/// Keep a log line for the next report; called by the app logger
+pub fn remember_log(line: String) {
+    if let Ok(mut logs) = RECENT_LOGS.lock() {
+        if logs.len() >= RECENT_LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(line);
+    }
+}
+
+/// A command payload fit for a report: secret fields masked, long strings cut
+pub fn redact_payload(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| {
+                    let value = if crate::batch_case_logs::is_secret(key) && !value.is_null() {
+                        Value::from(REDACTED)
+                    } else {
+                        redact_payload(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_payload).collect()),
+        Value::String(s) if s.chars().count() > MAX_PAYLOAD_STRING => {
+            let cut: String = s.chars().take(MAX_PAYLOAD_STRING).collect();
+            Value::String(format!("{}... [{} bytes]", cut, s.len()))
+        }
+        other => other.clone(),
+    }
+}
+
+/// Clears the thread's current command once dispatch returns
+struct CommandScope;
+
+impl Drop for CommandScope {
+    fn drop(&mut self) {
+        CURRENT_COMMAND.with(|current| current.borrow_mut().take());
+    }
+}
+
+fn enter_command(command: &str, payload: &InvokeBody) -> CommandScope {
+    let payload = match payload {
+        InvokeBody::Json(value) => redact_payload(value),
+        InvokeBody::Raw(bytes) => Value::String(format!("[{} raw bytes]", bytes.len())),
+    };
+    let record = InvokeRecord { command: command.to_string(), payload, timestamp: chrono::Utc::now().to_rfc3339() };
+    if let Ok(mut invokes) = RECENT_INVOKES.lock() {
+        if invokes.len() >= RECENT_COMMANDS {
+            invokes.pop_front();
+        }
+        invokes.push_back(record.clone());
+    }
+    CURRENT_COMMAND.with(|current| *current.borrow_mut() = Some(record));
+    CommandScope
+}
+
+/// Wrap the app's invoke handler so crash reports know which commands ran
+pub fn track_commands<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let _scope = enter_command(invoke.message.command(), invoke.message.payload());
+        handler(invoke)
+    }
+}
+
+fn snapshot<T: Clone>(buffer: &Mutex<VecDeque<T>>) -> Vec<T> {
+    // The panic may have happened while the buffer was locked
+    match buffer.try_lock() {
+        Ok(items) => items.iter().cloned().collect(),
+        Err(std::sync::TryLockError::Poisoned(items)) => items.into_inner().iter().cloned().collect(),
+        Err(std::sync::TryLockError::WouldBlock) => Vec::new(),
+    }
+}
+
+fn build_report(info: &std::panic::PanicHookInfo) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    let thread = std::thread::current();
+    CrashReport {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        thread: thread.name().map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+        message,
+        location: info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        command: CURRENT_COMMAND.try_with(|current| current.try_borrow().ok()?.clone()).ok().flatten(),
+        recent_commands: snapshot(&RECENT_INVOKES),
+        recent_logs: snapshot(&RECENT_LOGS),
+    }
+}
+
+/// Write a report into `dir`, returning its path
+pub fn write_report(dir: &Path, report: &CrashReport) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let path = dir.join(format!("crash-{}-{}.json", stamp, &report.id[..8]));
+    let json = serde_json::to_vec_pretty(report).map_err(std::io::Error::other)?;
+    std::fs::write(&path, json)?;
+    Ok(path)
+}
+
+pub fn crash_report_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("crash_reports"))
+}
+
+/// Write a report for every panic. The previous hook still runs afterwards,
+/// so panics are printed as before.
+pub fn install(app_handle: AppHandle) {
+    let dir = match crash_report_dir(&app_handle) {
+        Ok(dir) => dir,
+        Err(e) => {
+            log::warn!("Crash reports disabled: {}", e);
+            return;
+        }
+    };
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = build_report(info);
+        match write_report(&dir, &report) {
+            Ok(path) => {
+                let event = serde_json::json!({
+                    "report_path": path,
+                    "message": report.message,
+                    "thread": report.thread,
+                    "command": report.command.as_ref().map(|c| &c.command),
+                });
+                // Emit off the panicking thread, which may hold locks the event loop needs
+                let app_handle = app_handle.clone();
+                let _ = std::thread::Builder::new()
+                    .name("crash-report".to_string())
+                    .spawn(move || {
+                        let _ = app_handle.emit("backend_crash", event);
+                    });
+            }
+            Err(e) => eprintln!("Failed to write crash report: {}", e),
+        }
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_payload() {
+        let payload = serde_json::json!({
+            "sessionId": "s1",
+            "apiKey": "sk-123",
+            "config": { "env": { "AMP_TOKEN": "secret", "AMP_URL": "https://ampcode.com" }, "token": null },
+            "content": "x".repeat(1000),
+        });
+        let redacted = redact_payload(&payload);
+        assert_eq!(redacted["sessionId"], "s1");
+        assert_eq!(redacted["apiKey"], REDACTED);
+        assert_eq!(redacted["config"]["env"]["AMP_TOKEN"], REDACTED);
+        assert_eq!(redacted["config"]["env"]["AMP_URL"], "https://ampcode.com");
+        assert!(redacted["config"]["token"].is_null());
+        let content = redacted["content"].as_str().unwrap();
+        assert!(content.len() < 300 && content.ends_with("[1000 bytes]"));
+    }
+
+    #[test]
+    fn test_panic_report_names_current_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let reports = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| sink.lock().unwrap().push(build_report(info))));
+
+        let result = std::thread::spawn(|| {
+            let _scope = enter_command("session_create", &InvokeBody::Json(serde_json::json!({ "token": "t" })));
+            panic!("boom");
+        })
+        .join();
+        std::panic::set_hook(previous);
+        assert!(result.is_err());
+
+        // Other tests may panic while the hook is set
+        let report = reports.lock().unwrap().iter().find(|r| r.message == "boom").cloned().unwrap();
+        assert_eq!(report.message, "boom");
+        let command = report.command.clone().unwrap();
+        assert_eq!((command.command.as_str(), &command.payload["token"]), ("session_create", &Value::from(REDACTED)));
+        assert!(report.recent_commands.iter().any(|c| c.command == "session_create"));
+        assert!(report.location.as_deref().unwrap().contains("crash_reports.rs"));
+
+        let path = write_report(dir.path(), &report).unwrap();
+        let written: CrashReport = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written.id, report.id);
+    }
+}
@@ -0,0 +1,393 @@
+//! Paged and streamed directory listings for the file explorer
+//!
+//! Reading a whole folder at once does not scale to large directories.
+//! `list_directory_page` (also behind `list_directory`) sorts and filters once,
+//! then only stats the requested page. `list_directory_stream` skips sorting and sends
+//! entries in chunks as `directory_entries` events, for folders with tens of
+//! thousands of entries.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+pub const DEFAULT_PAGE_SIZE: usize = 500;
+pub const MAX_PAGE_SIZE: usize = 5000;
+/// Entries per `directory_entries` event in streaming mode
+pub const STREAM_CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ListDirectoryOptions {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// Glob matched against entry names: `*`, `?`, `[abc]` and `{a,b}` are supported
+    pub pattern: Option<String>,
+    pub include_hidden: bool,
+    pub include_git_status: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    pub modified: Option<String>,
+    /// Git status of the file, or of any change under a directory
+    pub git_status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryPage {
+    pub entries: Vec<DirectoryEntry>,
+    /// Number of entries matching the filters, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub has_more: bool,
+}
+
+/// Translate a glob into an anchored regex matching a single path component
+pub fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    let mut in_class = false;
+    let mut brace_depth = 0usize;
+    for c in glob.chars() {
+        match c {
+            '*' if !in_class => re.push_str("[^/]*"),
+            '?' if !in_class => re.push_str("[^/]"),
+            '[' if !in_class => {
+                in_class = true;
+                re.push('[');
+            }
+            ']' if in_class => {
+                in_class = false;
+                re.push(']');
+            }
+            '!' if in_class && re.ends_with('[') => re.push('^'),
+            '{' if !in_class => {
+                brace_depth += 1;
+                re.push_str("(?:");
+            }
+            '}' if !in_class && brace_depth > 0 => {
+                brace_depth -= 1;
+                re.push(')');
+            }
+            ',' if !in_class && brace_depth > 0 => re.push('|'),
+            c if in_class => {
+                if c == '\\' {
+                    re.push('\\');
+                }
+                re.push(c);
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
+
+fn git_status_label(code: &str) -> &'static str {
+    match code {
+        "??" => "untracked",
+        c if c.contains('U') || c == "AA" || c == "DD" => "conflicted",
+        c if c.contains('A') => "added",
+        c if c.contains('D') => "deleted",
+        c if c.contains('R') => "renamed",
+        _ => "modified",
+    }
+}
+
+/// Map `git status --porcelain=v1 -z` output to statuses of the direct children of
+/// `prefix` (the listed directory relative to the repo root, with a trailing slash)
+pub fn parse_git_status(output: &str, prefix: &str) -> HashMap<String, String> {
+    let mut statuses: HashMap<String, String> = HashMap::new();
+    let mut records = output.split('\0').filter(|r| !r.is_empty());
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let (code, path) = (&record[..2], &record[3..]);
+        if code.starts_with('R') || code.starts_with('C') {
+            // Renames and copies are followed by the original path
+            records.next();
+        }
+        let Some(relative) = path.strip_prefix(prefix) else { continue };
+        let Some(child) = relative.split('/').find(|c| !c.is_empty()) else { continue };
+        let is_nested = relative.trim_end_matches('/').contains('/');
+        let label = git_status_label(code);
+
+        statuses
+            .entry(child.to_string())
+            .and_modify(|existing| {
+                // A directory with mixed changes is just "modified"
+                if existing != label {
+                    *existing = "modified".to_string();
+                }
+            })
+            .or_insert_with(|| if is_nested && label != "untracked" { "modified" } else { label }.to_string());
+    }
+    statuses
+}
+
+/// Statuses of the children of `dir`, or an empty map outside a git repository
+fn git_statuses(dir: &Path) -> HashMap<String, String> {
+    let prefix = std::process::Command::new("git")
+        .current_dir(dir)
+        .args(["rev-parse", "--show-prefix"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    let Some(prefix) = prefix else { return HashMap::new() };
+
+    std::process::Command::new("git")
+        .current_dir(dir)
+        .args(["status", "--porcelain=v1", "-z", "--untracked-files=normal", "--", "."])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| parse_git_status(&String::from_utf8_lossy(&o.stdout), &prefix))
+        .unwrap_or_default()
+}
+
+struct RawEntry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+    is_symlink: bool,
+}
+
+struct EntryFilter {
+    pattern: Option<Regex>,
+    include_hidden: bool,
+}
+
+impl EntryFilter {
+    fn new(options: &ListDirectoryOptions) -> Result<Self, String> {
+        let pattern = options
+            .pattern
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| glob_to_regex(p).map_err(|e| format!("Invalid pattern {}: {}", p, e)))
+            .transpose()?;
+        Ok(Self { pattern, include_hidden: options.include_hidden })
+    }
+
+    fn raw_entry(&self, entry: std::fs::DirEntry) -> Option<RawEntry> {
+        let name = entry.file_name().to_str()?.to_string();
+        if !self.include_hidden && name.starts_with('.') {
+            return None;
+        }
+        if self.pattern.as_ref().is_some_and(|re| !re.is_match(&name)) {
+            return None;
+        }
+        let file_type = entry.file_type().ok()?;
+        // Follow symlinks so links to directories sort and open as directories
+        let is_dir = file_type.is_dir() || (file_type.is_symlink() && entry.path().is_dir());
+        Some(RawEntry { name, path: entry.path(), is_dir, is_symlink: file_type.is_symlink() })
+    }
+}
+
+fn with_metadata(raw: RawEntry, git: &HashMap<String, String>) -> DirectoryEntry {
+    let metadata = std::fs::metadata(&raw.path).ok();
+    DirectoryEntry {
+        git_status: git.get(&raw.name).cloned(),
+        size: metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len()).unwrap_or(0),
+        modified: metadata
+            .and_then(|m| m.modified().ok())
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+        path: raw.path.to_string_lossy().to_string(),
+        name: raw.name,
+        is_dir: raw.is_dir,
+        is_symlink: raw.is_symlink,
+    }
+}
+
+/// One page of a directory, directories first and then by name
+pub fn list_page(dir: &Path, options: &ListDirectoryOptions) -> Result<DirectoryPage, String> {
+    let filter = EntryFilter::new(options)?;
+    let limit = options.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let read = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut raw: Vec<RawEntry> = read.flatten().filter_map(|e| filter.raw_entry(e)).collect();
+    raw.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+
+    let total = raw.len();
+    let git = if options.include_git_status { git_statuses(dir) } else { HashMap::new() };
+    let entries: Vec<DirectoryEntry> = raw
+        .into_iter()
+        .skip(options.offset)
+        .take(limit)
+        .map(|r| with_metadata(r, &git))
+        .collect();
+
+    Ok(DirectoryPage {
+        has_more: options.offset + entries.len() < total,
+        offset: options.offset,
+        total,
+        entries,
+    })
+}
+
+/// Walk a directory in read order, handing each chunk of entries to `emit`.
+/// `offset` and `limit` are ignored. Returns the number of entries sent.
+pub fn stream_entries(
+    dir: &Path,
+    options: &ListDirectoryOptions,
+    mut emit: impl FnMut(Vec<DirectoryEntry>),
+) -> Result<usize, String> {
+    let filter = EntryFilter::new(options)?;
+    let read = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let git = if options.include_git_status { git_statuses(dir) } else { HashMap::new() };
+
+    let mut total = 0;
+    let mut chunk = Vec::with_capacity(STREAM_CHUNK_SIZE);
+    for raw in read.flatten().filter_map(|e| filter.raw_entry(e)) {
+        chunk.push(with_metadata(raw, &git));
+        if chunk.len() == STREAM_CHUNK_SIZE {
+            total += chunk.len();
+            emit(std::mem::replace(&mut chunk, Vec::with_capacity(STREAM_CHUNK_SIZE)));
+        }
+    }
+    if !chunk.is_empty() {
+        total += chunk.len();
+        emit(chunk);
+    }
+    Ok(total)
+}
+
+#[tauri::command]
+pub async fn list_directory_page(
+    path: String,
+    options: Option<ListDirectoryOptions>,
+) -> Result<DirectoryPage, String> {
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || list_page(Path::new(&path), &options))
+        .await
+        .map_err(|e| format!("Failed to list directory: {}", e))?
+}
+
+/// Start streaming a directory. Entries arrive as `directory_entries` events tagged
+/// with `stream_id`; the last event has `done: true` and the total count.
+#[tauri::command]
+pub async fn list_directory_stream(
+    path: String,
+    stream_id: String,
+    options: Option<ListDirectoryOptions>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    // Fail fast on bad input before handing off to the background task
+    EntryFilter::new(&options)?;
+    if !Path::new(&path).is_dir() {
+        return Err(format!("Not a directory: {}", path));
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let result = stream_entries(Path::new(&path), &options, |entries| {
+            let _ = app_handle.emit("directory_entries", serde_json::json!({
+                "stream_id": stream_id,
+                "entries": entries,
+                "done": false,
+            }));
+        });
+        let (total, error) = match result {
+            Ok(total) => (total, None),
+            Err(e) => (0, Some(e)),
+        };
+        let _ = app_handle.emit("directory_entries", serde_json::json!({
+            "stream_id": stream_id,
+            "entries": [],
+            "done": true,
+            "total": total,
+            "error": error,
+        }));
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for name in ["b.rs", "a.rs", "README.md", ".env", "C.txt"] {
+            std::fs::write(dir.path().join(name), "content").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let re = glob_to_regex("*.{rs,toml}").unwrap();
+        assert!(re.is_match("main.rs") && re.is_match("Cargo.toml"));
+        assert!(!re.is_match("main.rs.bak"));
+        assert!(glob_to_regex("file?.[!a]").unwrap().is_match("file1.b"));
+        assert!(!glob_to_regex("file?.[!a]").unwrap().is_match("file1.a"));
+        assert!(glob_to_regex("a+b(1).txt").unwrap().is_match("a+b(1).txt"));
+    }
+
+    #[test]
+    fn test_page_sorting_and_pagination() {
+        let dir = sample_dir();
+
+        let first = list_page(dir.path(), &ListDirectoryOptions { limit: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(first.total, 5);
+        assert!(first.has_more);
+        let names: Vec<&str> = first.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["src", "a.rs"]);
+        assert!(first.entries[0].is_dir);
+        assert_eq!(first.entries[1].size, 7);
+        assert!(first.entries[1].modified.is_some());
+
+        let last = list_page(dir.path(), &ListDirectoryOptions { offset: 4, limit: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(last.entries.len(), 1);
+        assert!(!last.has_more);
+    }
+
+    #[test]
+    fn test_filters() {
+        let dir = sample_dir();
+
+        let rust = list_page(dir.path(), &ListDirectoryOptions { pattern: Some("*.rs".into()), ..Default::default() }).unwrap();
+        assert_eq!(rust.total, 2);
+
+        let hidden = list_page(dir.path(), &ListDirectoryOptions { include_hidden: true, ..Default::default() }).unwrap();
+        assert!(hidden.entries.iter().any(|e| e.name == ".env"));
+
+        assert!(list_page(dir.path(), &ListDirectoryOptions { pattern: Some("[".into()), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_stream_entries_chunks() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..(STREAM_CHUNK_SIZE + 5) {
+            std::fs::write(dir.path().join(format!("f{}.txt", i)), "").unwrap();
+        }
+
+        let mut chunks = Vec::new();
+        let total = stream_entries(dir.path(), &ListDirectoryOptions::default(), |c| chunks.push(c.len())).unwrap();
+        assert_eq!(total, STREAM_CHUNK_SIZE + 5);
+        assert_eq!(chunks, vec![STREAM_CHUNK_SIZE, 5]);
+    }
+
+    #[test]
+    fn test_parse_git_status() {
+        let output = " M src/lib.rs\0?? src/new/\0A  src/app/mod.rs\0R  src/b.rs\0src/a.rs\0 D other/x.rs\0";
+        let statuses = parse_git_status(output, "src/");
+        assert_eq!(statuses.get("lib.rs").map(String::as_str), Some("modified"));
+        assert_eq!(statuses.get("new").map(String::as_str), Some("untracked"));
+        assert_eq!(statuses.get("app").map(String::as_str), Some("modified"));
+        assert_eq!(statuses.get("b.rs").map(String::as_str), Some("renamed"));
+        assert!(!statuses.contains_key("a.rs"));
+        assert!(!statuses.contains_key("x.rs"));
+    }
+}
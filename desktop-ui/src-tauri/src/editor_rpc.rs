@@ -0,0 +1,508 @@
+//! JSON-RPC endpoint for editor extensions
+//!
+//! Frames on the WebSocket bridge that carry `"jsonrpc": "2.0"` are answered
+//! here instead of by the bridge's own request format, so an extension only
+//! needs the bridge token and a JSON-RPC client. The methods are shaped for an
+//! extension working in one workspace folder:
+//!
+//! - `editor/openSession` maps the folder to its repository and returns the
+//!   session for it with a running thread. A folder inside a session worktree
+//!   maps to that session, a repository maps to its most recently updated
+//!   session, and when there is none a session, worktree and thread are created
+//!   (unless `create` is false). The folder must pass the same checks as a
+//!   session working directory.
+//! - `editor/sendSelection` sends a prompt with selected code attached, paths
+//!   made relative to the session worktree.
+//! - `editor/watchDiff` starts `editor/diff` notifications listing the files
+//!   changed in the session worktree, sent whenever that list or its line counts
+//!   change; `editor/unwatchDiff` stops them.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::app_state::AppState;
+use crate::commit_message::git;
+use crate::profile_auth::ProfileManager;
+use crate::session_commands::AmpSessionMap;
+use crate::thread_session_commands::{SessionCreateRequest, ThreadAttachRequest, ThreadStartRequest};
+use crate::worktree_selection::ChangedFile;
+
+/// How often a watched worktree is checked for changes
+pub const DIFF_POLL: Duration = Duration::from_secs(2);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Failures of the operation itself
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self::new(SERVER_ERROR, message)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenSessionParams {
+    workspace_folder: String,
+    #[serde(default = "default_create")]
+    create: bool,
+}
+
+fn default_create() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Selection {
+    /// Absolute, or relative to the workspace folder
+    pub path: String,
+    /// 1-based and inclusive
+    pub start_line: u32,
+    pub end_line: u32,
+    pub text: String,
+    pub language_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendSelectionParams {
+    thread_id: String,
+    prompt: String,
+    #[serde(default)]
+    selections: Vec<Selection>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WatchDiffParams {
+    session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorSession {
+    pub session_id: String,
+    pub short_code: Option<String>,
+    pub repo_root: String,
+    pub worktree_path: String,
+    pub thread_id: String,
+    /// Whether the session was created for this call
+    pub created: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFile {
+    #[serde(flatten)]
+    pub change: ChangedFile,
+    /// Unset for binary and untracked files
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+}
+
+struct DiffWatch {
+    session_id: String,
+    worktree: PathBuf,
+    /// Files sent in the last notification, serialized
+    last: Option<String>,
+}
+
+/// Per-connection state of an editor client
+#[derive(Default)]
+pub struct EditorConnection {
+    watch: Option<DiffWatch>,
+}
+
+impl EditorConnection {
+    pub fn watching(&self) -> bool {
+        self.watch.is_some()
+    }
+
+    /// An `editor/diff` notification when the watched worktree's changes differ
+    /// from the last one sent
+    pub async fn poll_diff(&mut self) -> Option<String> {
+        let watch = self.watch.as_mut()?;
+        let worktree = watch.worktree.clone();
+        let files = match tauri::async_runtime::spawn_blocking(move || diff_files(&worktree)).await {
+            Ok(Ok(files)) => files,
+            Ok(Err(e)) => {
+                log::debug!("Diff of {} failed: {}", watch.worktree.display(), e);
+                return None;
+            }
+            Err(_) => return None,
+        };
+        let fingerprint = serde_json::to_string(&files).ok()?;
+        if watch.last.as_ref() == Some(&fingerprint) {
+            return None;
+        }
+        watch.last = Some(fingerprint);
+        Some(notification(
+            "editor/diff",
+            serde_json::json!({ "sessionId": watch.session_id, "worktreePath": watch.worktree, "files": files }),
+        ))
+    }
+}
+
+/// Whether a bridge frame is a JSON-RPC message
+pub fn is_rpc(text: &str) -> bool {
+    serde_json::from_str::<Value>(text).is_ok_and(|value| value.get("jsonrpc").is_some())
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+    .to_string()
+}
+
+fn notification(method: &str, params: Value) -> String {
+    serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string()
+}
+
+fn to_value<T: Serialize>(result: Result<T, RpcError>) -> Result<Value, RpcError> {
+    result.map(|value| serde_json::to_value(value).unwrap_or_default())
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// Changed files of a worktree with their line counts against HEAD
+fn diff_files(worktree: &Path) -> Result<Vec<DiffFile>, String> {
+    let changes = crate::worktree_selection::changed_files(worktree)?;
+    let mut counts: HashMap<String, (Option<u64>, Option<u64>)> = HashMap::new();
+    for line in git(worktree, &["diff", "HEAD", "--numstat"])?.lines() {
+        let mut fields = line.splitn(3, '\t');
+        if let (Some(added), Some(deleted), Some(path)) = (fields.next(), fields.next(), fields.next()) {
+            counts.insert(path.to_string(), (added.parse().ok(), deleted.parse().ok()));
+        }
+    }
+    Ok(changes
+        .into_iter()
+        .map(|change| {
+            let (additions, deletions) = counts.get(&change.path).copied().unwrap_or_default();
+            DiffFile { change, additions, deletions }
+        })
+        .collect())
+}
+
+/// A longer backtick fence than any run of backticks in `text`
+pub(crate) fn fence_for(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// The prompt followed by each selection as a fenced block under its location
+pub fn compose_prompt(prompt: &str, selections: &[Selection], worktree: Option<&Path>) -> String {
+    let mut message = prompt.trim_end().to_string();
+    for selection in selections {
+        let path = Path::new(&selection.path);
+        let path = worktree
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .to_string_lossy();
+        let lines = if selection.start_line == selection.end_line {
+            format!("line {}", selection.start_line)
+        } else {
+            format!("lines {}-{}", selection.start_line, selection.end_line)
+        };
+        let fence = fence_for(&selection.text);
+        message.push_str(&format!(
+            "\n\n`{}` {}:\n{}{}\n{}\n{}",
+            path,
+            lines,
+            fence,
+            selection.language_id.as_deref().unwrap_or(""),
+            selection.text.trim_end_matches('\n'),
+            fence
+        ));
+    }
+    message
+}
+
+/// The session for a repository path: the one whose worktree it is, otherwise
+/// the repository's most recently updated session. Returns the session ID,
+/// repository root and worktree path.
+pub async fn find_session(db: &SqlitePool, path: &Path) -> Result<Option<(String, String, String)>, String> {
+    sqlx::query_as::<_, (String, String, String)>(
+        "SELECT w.session_id, w.repo_root, w.worktree_path FROM session_worktrees w
+         JOIN sessions s ON s.id = w.session_id
+         WHERE w.worktree_path = ?1 OR w.repo_root = ?1
+         ORDER BY w.worktree_path = ?1 DESC, s.updated_at DESC
+         LIMIT 1",
+    )
+    .bind(path.to_string_lossy())
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to look up workspace session: {}", e))
+}
+
+async fn db_pool(app_handle: &AppHandle) -> Result<SqlitePool, RpcError> {
+    let profile_manager = app_handle.state::<ProfileManager>();
+    let db = profile_manager.db_pool.read().await;
+    Ok(db.as_ref().ok_or("Database not available".to_string())?.clone())
+}
+
+/// A running thread of the session, attaching to or starting one when needed
+async fn running_thread(app_handle: &AppHandle, db: &SqlitePool, session_id: &str) -> Result<String, RpcError> {
+    let threads: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM threads WHERE session_id = ? AND archived_at IS NULL ORDER BY updated_at DESC",
+    )
+    .bind(session_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to list threads: {}", e))?;
+    {
+        let running = app_handle.state::<AmpSessionMap>();
+        if let Some(id) = threads.iter().find(|id| running.contains(id)) {
+            return Ok(id.clone());
+        }
+    }
+    let thread = match threads.into_iter().next() {
+        Some(thread_id) => {
+            crate::thread_session_commands::thread_attach(
+                ThreadAttachRequest { thread_id },
+                app_handle.clone(),
+                app_handle.state(),
+                app_handle.state(),
+                app_handle.state(),
+            )
+            .await?
+        }
+        None => {
+            let request = ThreadStartRequest {
+                session_id: session_id.to_string(),
+                context: "production".to_string(),
+                agent_mode: None,
+                model: None,
+                server: None,
+            };
+            crate::thread_session_commands::thread_start(
+                request,
+                app_handle.clone(),
+                app_handle.state(),
+                app_handle.state(),
+                app_handle.state(),
+            )
+            .await?
+        }
+    };
+    Ok(thread.id)
+}
+
+/// A new session with a worktree of `repo_root`
+async fn create_session(app_handle: &AppHandle, db: &SqlitePool, repo_root: &Path) -> Result<(String, String), RpcError> {
+    let request = SessionCreateRequest { profile_id: None, amp_profile_id: None };
+    let session =
+        crate::thread_session_commands::new_session_create(request, app_handle.state(), app_handle.state()).await?;
+    let created = crate::worktree_commands::create_git_worktree(
+        repo_root.to_string_lossy().to_string(),
+        session.id.clone(),
+        None,
+        app_handle.state(),
+        app_handle.state(),
+    )
+    .await;
+    match created {
+        Ok(meta) => Ok((session.id, meta.path.to_string_lossy().to_string())),
+        Err(e) => {
+            // Without a worktree the session would run in the wrong repository
+            let _ = sqlx::query("DELETE FROM sessions WHERE id = ?").bind(&session.id).execute(db).await;
+            Err(RpcError::from(e))
+        }
+    }
+}
+
+async fn open_session(app_handle: &AppHandle, params: OpenSessionParams) -> Result<EditorSession, RpcError> {
+    let folder = crate::working_dir::validate_with_state(&params.workspace_folder, &app_handle.state::<AppState>())
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+    let toplevel = git(&folder, &["rev-parse", "--show-toplevel"])
+        .map_err(|_| RpcError::new(INVALID_PARAMS, format!("{} is not in a git repository", folder.display())))?;
+    let toplevel = PathBuf::from(toplevel.trim());
+    let toplevel = toplevel.canonicalize().unwrap_or(toplevel);
+
+    let db = db_pool(app_handle).await?;
+    let (session_id, repo_root, worktree_path, created) = match find_session(&db, &toplevel).await? {
+        Some((session_id, repo_root, worktree_path)) => (session_id, repo_root, worktree_path, false),
+        None if params.create => {
+            let (session_id, worktree_path) = create_session(app_handle, &db, &toplevel).await?;
+            (session_id, toplevel.to_string_lossy().to_string(), worktree_path, true)
+        }
+        None => return Err(format!("No session for {}", toplevel.display()).into()),
+    };
+    let thread_id = running_thread(app_handle, &db, &session_id).await?;
+    Ok(EditorSession {
+        short_code: crate::session_codes::code_for(&session_id),
+        session_id,
+        repo_root,
+        worktree_path,
+        thread_id,
+        created,
+    })
+}
+
+async fn send_selection(app_handle: &AppHandle, params: SendSelectionParams) -> Result<(), RpcError> {
+    let db = db_pool(app_handle).await?;
+    let session_id: Option<String> = sqlx::query_scalar("SELECT session_id FROM threads WHERE id = ?")
+        .bind(&params.thread_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|e| format!("Failed to get thread: {}", e))?;
+    let worktree = match &session_id {
+        Some(session_id) => crate::repo_relocation::recorded_worktree(&db, session_id).await,
+        None => None,
+    };
+    let message = compose_prompt(&params.prompt, &params.selections, worktree.as_deref());
+    crate::thread_session_commands::thread_send_message(
+        params.thread_id,
+        message,
+        app_handle.state(),
+        app_handle.state(),
+        app_handle.state(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn watch_diff(app_handle: &AppHandle, params: WatchDiffParams, connection: &mut EditorConnection) -> Result<Value, RpcError> {
+    let session_id = crate::session_codes::resolve(&params.session_id);
+    let worktree = crate::commit_message::session_worktree(app_handle, &session_id).await?;
+    let result = serde_json::json!({ "sessionId": session_id, "worktreePath": worktree });
+    // The first poll sends the current changes
+    connection.watch = Some(DiffWatch { session_id, worktree, last: None });
+    Ok(result)
+}
+
+async fn dispatch(
+    app_handle: &AppHandle,
+    method: &str,
+    request_params: Value,
+    connection: &mut EditorConnection,
+) -> Result<Value, RpcError> {
+    match method {
+        "editor/openSession" => to_value(open_session(app_handle, params(request_params)?).await),
+        "editor/sendSelection" => to_value(send_selection(app_handle, params(request_params)?).await),
+        "editor/watchDiff" => watch_diff(app_handle, params(request_params)?, connection).await,
+        "editor/unwatchDiff" => {
+            connection.watch = None;
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method '{}' not found", method))),
+    }
+}
+
+/// Parse a frame into `(id, method, params)`; errors come with the response to send
+fn parse(text: &str) -> Result<(Option<Value>, String, Value), String> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| response(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))))?;
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: RpcRequest = serde_json::from_value(value)
+        .map_err(|e| response(id.clone(), Err(RpcError::new(INVALID_REQUEST, e.to_string()))))?;
+    if request.jsonrpc != "2.0" {
+        return Err(response(id, Err(RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"))));
+    }
+    Ok((request.id, request.method, request.params))
+}
+
+/// Handle one JSON-RPC frame; `None` when nothing should be sent back
+pub async fn handle(app_handle: &AppHandle, text: &str, connection: &mut EditorConnection) -> Option<String> {
+    let (id, method, request_params) = match parse(text) {
+        Ok(request) => request,
+        Err(response) => return Some(response),
+    };
+    let result = dispatch(app_handle, &method, request_params, connection).await;
+    if let Err(e) = &result {
+        log::debug!("Editor request {} failed: {}", method, e.message);
+    }
+    id.map(|id| response(id, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    fn selection(path: &str, start_line: u32, end_line: u32, text: &str) -> Selection {
+        Selection { path: path.to_string(), start_line, end_line, text: text.to_string(), language_id: Some("rust".to_string()) }
+    }
+
+    #[test]
+    fn test_compose_prompt_and_parse() {
+        let selections = [
+            selection("/work/repo/src/main.rs", 3, 5, "fn main() {\n    run();\n}\n"),
+            selection("docs/notes.md", 7, 7, "see ```this```"),
+        ];
+        let message = compose_prompt("Why does this fail?\n", &selections, Some(Path::new("/work/repo")));
+        assert_eq!(
+            message,
+            "Why does this fail?\n\n`src/main.rs` lines 3-5:\n```rust\nfn main() {\n    run();\n}\n```\n\n`docs/notes.md` line 7:\n````rust\nsee ```this```\n````"
+        );
+
+        let (id, method, _) = parse(r#"{"jsonrpc": "2.0", "id": 4, "method": "editor/unwatchDiff"}"#).unwrap();
+        assert_eq!((id, method.as_str()), (Some(Value::from(4)), "editor/unwatchDiff"));
+        assert!(parse(r#"{"jsonrpc": "2.0", "method": "editor/unwatchDiff"}"#).unwrap().0.is_none());
+        let error: Value = serde_json::from_str(&parse(r#"{"jsonrpc": "1.0", "id": 1, "method": "x"}"#).unwrap_err()).unwrap();
+        assert_eq!((error["id"].clone(), error["error"]["code"].clone()), (Value::from(1), Value::from(INVALID_REQUEST)));
+        let error: Value = serde_json::from_str(&parse("{").unwrap_err()).unwrap();
+        assert_eq!(error["error"]["code"], PARSE_ERROR);
+        assert!(is_rpc(r#"{"jsonrpc": "2.0", "method": "x"}"#) && !is_rpc(r#"{"id": 1, "method": "list_sessions"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_find_session_prefers_worktree_then_latest() {
+        let db = test_pool().await;
+        for (id, updated_at, worktree) in [("s-old", "2026-01-01", "/repo/.amp-worktrees/old"), ("s-new", "2026-02-01", "/repo/.amp-worktrees/new")] {
+            sqlx::query("INSERT INTO sessions (id, updated_at) VALUES (?, ?)").bind(id).bind(updated_at).execute(&db).await.unwrap();
+            sqlx::query("INSERT INTO session_worktrees (session_id, repo_root, worktree_path, branch_name) VALUES (?, '/repo', ?, 'b')")
+                .bind(id)
+                .bind(worktree)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        let found = |path: &'static str| {
+            let db = db.clone();
+            async move { find_session(&db, Path::new(path)).await.unwrap().map(|(id, _, _)| id) }
+        };
+        assert_eq!(found("/repo").await.as_deref(), Some("s-new"));
+        assert_eq!(found("/repo/.amp-worktrees/old").await.as_deref(), Some("s-old"));
+        assert_eq!(found("/elsewhere").await, None);
+    }
+}
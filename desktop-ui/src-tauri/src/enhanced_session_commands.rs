@@ -3,6 +3,7 @@ use tauri::{AppHandle, State, Manager, Emitter};
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
 use unified_core::domain::{Session, SessionStatus, AgentMode};
+use unified_core::env_schema::EnvProblem;
 
 use crate::session_manager::{EnhancedSessionManager, SessionManagerConfig, SessionMetrics};
 use crate::runtime_env::{RuntimeEnvironment, EnvKind};
@@ -95,8 +96,10 @@ pub async fn init_enhanced_session_manager(
 pub async fn enhanced_session_create(
     request: CreateEnhancedSessionRequest,
     enhanced_manager_state: State<'_, EnhancedSessionManagerState>,
+    app_state: State<'_, crate::app_state::AppState>,
     app_handle: AppHandle,
 ) -> Result<SessionResponse, String> {
+    crate::feature_flags::require(&app_state, crate::feature_flags::ENHANCED_SESSIONS)?;
     let manager_guard = enhanced_manager_state.read().await;
     let manager = manager_guard.as_ref().ok_or("Session manager not initialized")?;
 
@@ -140,8 +143,10 @@ pub async fn enhanced_session_create(
 pub async fn enhanced_session_start(
     session_id: String,
     enhanced_manager_state: State<'_, EnhancedSessionManagerState>,
+    app_state: State<'_, crate::app_state::AppState>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
+    crate::feature_flags::require(&app_state, crate::feature_flags::ENHANCED_SESSIONS)?;
     let manager_guard = enhanced_manager_state.read().await;
     let manager = manager_guard.as_ref().ok_or("Session manager not initialized")?;
 
@@ -215,6 +220,7 @@ pub async fn enhanced_session_list(
         "initializing" => SessionStatus::Initializing,
         "idle" => SessionStatus::Idle,
         "running" => SessionStatus::Running,
+        "suspended" => SessionStatus::Suspended,
         "awaiting_input" => SessionStatus::AwaitingInput,
         "evaluating" => SessionStatus::Evaluating,
         "completed" => SessionStatus::Completed,
@@ -246,6 +252,24 @@ pub async fn enhanced_session_status(
     Ok(SessionStatusResponse { session_id, status })
 }
 
+/// Check a session's `runtime_config.environment_variables` against the known
+/// `AMP_*` variables, reporting unknown names and values of the wrong type
+#[tauri::command]
+pub async fn lint_environment(
+    session_id: String,
+    enhanced_manager_state: State<'_, EnhancedSessionManagerState>,
+) -> Result<Vec<EnvProblem>, String> {
+    let manager_guard = enhanced_manager_state.read().await;
+    let manager = manager_guard.as_ref().ok_or("Session manager not initialized")?;
+
+    let session = manager
+        .get_session(&session_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(session.runtime_config.lint_environment())
+}
+
 /// Get enhanced session metrics
 #[tauri::command]
 pub async fn enhanced_session_metrics(
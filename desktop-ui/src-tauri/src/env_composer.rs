@@ -74,9 +74,14 @@ fn compose_runtime_env_internal(
 ) -> Result<EnvComposeResult> {
     use log::info;
     use std::path::PathBuf;
-    use crate::toolbox_resolver::resolve_toolboxes;
+    use crate::toolbox_resolver::resolve_toolboxes_in;
     
     let mut guard: Option<ToolboxGuard> = None;
+    let context_str = match context {
+        SpawnContext::Chat => "chat",
+        SpawnContext::Tui => "tui",
+        SpawnContext::ExternalTool => "external_tool",
+    };
 
     // Set toolbox profile environment if provided
     if let Some(profile) = profile {
@@ -85,6 +90,18 @@ fn compose_runtime_env_internal(
         env.insert("AMP_ACTIVE_TOOLBOX_PROFILE".into(), profile.name.clone());
     }
 
+    // Misspelled or malformed AMP_* variables are otherwise silently ignored
+    for problem in unified_core::env_schema::lint_env(env) {
+        log::warn!("env_composer.{}: {}", context_str, problem.message);
+    }
+
+    // Temp files and toolbox resolutions stay inside the session's profile directory
+    let profile_dirs = crate::profile_dirs::ProfileDirs::for_env(env);
+    profile_dirs
+        .ensure()
+        .map_err(|e| anyhow::anyhow!("create profile directory {:?}: {}", profile_dirs.root, e))?;
+    env.insert("TMPDIR".into(), profile_dirs.tmp.to_string_lossy().to_string());
+
     // Check if toolboxes are enabled - respecting AMP_ENABLE_TOOLBOXES flag
     let toolboxes_enabled = env.get("AMP_ENABLE_TOOLBOXES")
         .map(|v| v != "0" && v.to_lowercase() != "false")
@@ -103,27 +120,21 @@ fn compose_runtime_env_internal(
             .map(PathBuf::from)
             .collect();
             
+        for root in &roots {
+            profile_dirs.check_access(root).map_err(|e| anyhow::anyhow!("toolbox path refused: {}", e))?;
+        }
+
         if !roots.is_empty() {
-            let mut resolved = resolve_toolboxes(&roots, false)?;
+            let mut resolved = resolve_toolboxes_in(&profile_dirs.toolboxes(), &roots, false)?;
             
             // Compose PATH with toolbox bin directory
             let prev_path = env.get("PATH").cloned().unwrap_or_default();
-            let new_path = if prev_path.is_empty() {
-                resolved.bin.to_string_lossy().to_string()
-            } else {
-                format!("{}:{}", resolved.bin.to_string_lossy(), prev_path)
-            };
+            let new_path = crate::platform::prepend_env_path(&resolved.bin.to_string_lossy(), &prev_path);
             
             env.insert("PATH".into(), new_path);
             env.insert("AMP_TOOLBOX".into(), resolved.root.to_string_lossy().to_string());
             
             // Context-specific logging
-            let context_str = match context {
-                SpawnContext::Chat => "chat",
-                SpawnContext::Tui => "tui",
-                SpawnContext::ExternalTool => "external_tool",
-            };
-            
             if let Some(profile_name) = env.get("AMP_ACTIVE_TOOLBOX_PROFILE") {
                 info!("env_composer.{}: toolbox profile '{}' enabled files_count={} bytes={} copy_mode={}", 
                       context_str, profile_name, resolved.manifest.files_count, resolved.manifest.bytes_total, resolved.manifest.copy_mode);
@@ -141,12 +152,7 @@ fn compose_runtime_env_internal(
 
 /// Utility function to split paths by platform-appropriate separators
 pub fn split_paths(s: &str) -> Vec<String> {
-    if cfg!(windows) {
-        s.split(';').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
-    } else {
-        // Support both ':' and ',' separators for convenience
-        s.split(|c| c == ':' || c == ',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
-    }
+    crate::platform::split_env_paths(s)
 }
 
 /// Factory function to create the appropriate composer for different spawn contexts
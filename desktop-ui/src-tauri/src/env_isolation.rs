@@ -0,0 +1,156 @@
+//! Strict environment isolation of spawned CLI processes
+//!
+//! Spawns compose their environment from app settings, the Amp profile and the
+//! runtime composer, but some paths also pick up variables of the app's own
+//! environment. In strict mode a process gets only the composed variables plus
+//! `unified_core::ISOLATED_ENV_ALLOWLIST`, and is spawned with a cleared
+//! environment. Strictness is set per Amp profile and per session, the
+//! session's own setting winning.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tauri::State;
+
+use crate::profile_auth::ProfileManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IsolationScope {
+    Profile,
+    Session,
+}
+
+impl IsolationScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            IsolationScope::Profile => "profile",
+            IsolationScope::Session => "session",
+        }
+    }
+}
+
+/// Strictness set directly on a profile or session, if any
+pub async fn get_setting(db: &SqlitePool, scope: IsolationScope, scope_id: &str) -> Result<Option<bool>, String> {
+    sqlx::query_scalar::<_, bool>("SELECT strict FROM env_isolation_settings WHERE scope = ? AND scope_id = ?")
+        .bind(scope.as_str())
+        .bind(scope_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read isolation setting: {}", e))
+}
+
+/// Set strictness, or with `None` fall back to the profile (or the default) again
+pub async fn set_setting(db: &SqlitePool, scope: IsolationScope, scope_id: &str, strict: Option<bool>) -> Result<(), String> {
+    let result = match strict {
+        Some(strict) => {
+            sqlx::query(
+                "INSERT INTO env_isolation_settings (scope, scope_id, strict) VALUES (?, ?, ?)
+                 ON CONFLICT(scope, scope_id) DO UPDATE SET strict = excluded.strict,
+                     updated_at = (datetime('now', 'utc') || 'Z')",
+            )
+            .bind(scope.as_str())
+            .bind(scope_id)
+            .bind(strict)
+            .execute(db)
+            .await
+        }
+        None => {
+            sqlx::query("DELETE FROM env_isolation_settings WHERE scope = ? AND scope_id = ?")
+                .bind(scope.as_str())
+                .bind(scope_id)
+                .execute(db)
+                .await
+        }
+    };
+    result.map_err(|e| format!("Failed to save isolation setting: {}", e))?;
+    Ok(())
+}
+
+/// Whether a session's processes are strictly isolated; unreadable settings count as not strict
+pub async fn is_strict(db: &SqlitePool, session_id: &str, amp_profile_id: Option<&str>) -> bool {
+    if let Ok(Some(strict)) = get_setting(db, IsolationScope::Session, session_id).await {
+        return strict;
+    }
+    match amp_profile_id {
+        Some(profile_id) => matches!(get_setting(db, IsolationScope::Profile, profile_id).await, Ok(Some(true))),
+        None => false,
+    }
+}
+
+/// Restrict a composed environment to itself and the allowlist when the session is strict.
+/// Returns whether it is.
+pub async fn isolate(db: &SqlitePool, session_id: &str, amp_profile_id: Option<&str>, env: &mut HashMap<String, String>) -> bool {
+    let strict = is_strict(db, session_id, amp_profile_id).await;
+    if strict {
+        *env = unified_core::isolated_env(env);
+    }
+    strict
+}
+
+#[tauri::command]
+pub async fn get_env_isolation(
+    scope: IsolationScope,
+    scope_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Option<bool>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    get_setting(db, scope, &scope_id).await
+}
+
+#[tauri::command]
+pub async fn set_env_isolation(
+    scope: IsolationScope,
+    scope_id: String,
+    strict: Option<bool>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<(), String> {
+    if scope == IsolationScope::Profile && !profile_manager.profiles.contains_key(&scope_id) {
+        return Err(format!("Profile '{}' not found", scope_id));
+    }
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    set_setting(db, scope, &scope_id, strict).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_session_setting_wins_over_profile() {
+        let pool = test_pool().await;
+        assert!(!is_strict(&pool, "s1", Some("p1")).await);
+
+        set_setting(&pool, IsolationScope::Profile, "p1", Some(true)).await.unwrap();
+        assert!(is_strict(&pool, "s1", Some("p1")).await);
+        assert!(!is_strict(&pool, "s1", None).await);
+
+        set_setting(&pool, IsolationScope::Session, "s1", Some(false)).await.unwrap();
+        assert!(!is_strict(&pool, "s1", Some("p1")).await);
+        assert!(is_strict(&pool, "s2", Some("p1")).await);
+
+        set_setting(&pool, IsolationScope::Session, "s1", None).await.unwrap();
+        assert_eq!(get_setting(&pool, IsolationScope::Session, "s1").await.unwrap(), None);
+        assert!(is_strict(&pool, "s1", Some("p1")).await);
+    }
+
+    #[tokio::test]
+    async fn test_isolate_keeps_only_composed_and_allowlisted_variables() {
+        let pool = test_pool().await;
+        let composed: HashMap<String, String> = [("AMP_MODEL".to_string(), "smart".to_string())].into();
+
+        let mut env = composed.clone();
+        assert!(!isolate(&pool, "s1", None, &mut env).await);
+        assert_eq!(env, composed);
+
+        set_setting(&pool, IsolationScope::Session, "s1", Some(true)).await.unwrap();
+        assert!(isolate(&pool, "s1", None, &mut env).await);
+        assert_eq!(env.get("AMP_MODEL").map(String::as_str), Some("smart"));
+        assert!(env
+            .keys()
+            .all(|key| key == "AMP_MODEL" || unified_core::ISOLATED_ENV_ALLOWLIST.contains(&key.as_str())));
+    }
+}
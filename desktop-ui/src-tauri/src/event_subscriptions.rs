@@ -0,0 +1,206 @@
+//! Per-webview subscriptions for high-volume session events
+//!
+//! `chat_stream`, `thread_stream` and `process_output` are emitted through
+//! `emit_session_event`. A webview that never called `subscribe_events` keeps
+//! getting every event, as before. Once it subscribes, it only gets events that
+//! match one of its filters, and an event nobody wants is never serialized.
+//! Filtered events are addressed to the subscribing webviews, so listeners
+//! should be registered on the current webview rather than with the global
+//! `listen`, which receives events for any target.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, EventTarget, Manager, State, Webview};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Event names to receive; empty means every gated event
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    /// Chat session or thread ids to receive events for; empty means all sessions
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &str, session_id: &str) -> bool {
+        (self.kinds.is_empty() || self.kinds.iter().any(|k| k == event))
+            && (self.session_ids.is_empty() || self.session_ids.iter().any(|s| s == session_id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSubscription {
+    pub id: String,
+    #[serde(flatten)]
+    pub filter: EventFilter,
+}
+
+#[derive(Clone, Default)]
+pub struct EventSubscriptions {
+    /// Webview label -> subscription id -> filter
+    inner: Arc<Mutex<HashMap<String, HashMap<String, EventFilter>>>>,
+}
+
+impl EventSubscriptions {
+    pub fn subscribe(&self, webview: &str, filter: EventFilter) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.inner
+            .lock()
+            .unwrap()
+            .entry(webview.to_string())
+            .or_default()
+            .insert(id.clone(), filter);
+        id
+    }
+
+    /// Remove one subscription, or all of a webview's subscriptions when `id` is
+    /// `None`. The webview stays opted in and receives nothing until it subscribes again.
+    pub fn unsubscribe(&self, webview: &str, id: Option<&str>) -> usize {
+        let mut subs = self.inner.lock().unwrap();
+        let Some(filters) = subs.get_mut(webview) else {
+            return 0;
+        };
+        match id {
+            Some(id) => filters.remove(id).map_or(0, |_| 1),
+            None => {
+                let removed = filters.len();
+                filters.clear();
+                removed
+            }
+        }
+    }
+
+    /// Forget a closed webview
+    pub fn remove_webview(&self, webview: &str) {
+        self.inner.lock().unwrap().remove(webview);
+    }
+
+    pub fn list(&self, webview: &str) -> Vec<EventSubscription> {
+        let subs = self.inner.lock().unwrap();
+        let mut list: Vec<EventSubscription> = subs
+            .get(webview)
+            .map(|filters| {
+                filters
+                    .iter()
+                    .map(|(id, filter)| EventSubscription { id: id.clone(), filter: filter.clone() })
+                    .collect()
+            })
+            .unwrap_or_default();
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        list
+    }
+
+    /// Webviews among `open` that want this event. `None` when one of them never
+    /// subscribed, so the event has to go to everyone.
+    pub fn targets(&self, open: &[String], event: &str, session_id: &str) -> Option<Vec<String>> {
+        let subs = self.inner.lock().unwrap();
+        let mut wanted = Vec::new();
+        for label in open {
+            let filters = subs.get(label)?;
+            if filters.values().any(|f| f.matches(event, session_id)) {
+                wanted.push(label.clone());
+            }
+        }
+        Some(wanted)
+    }
+}
+
+/// Emit a session-scoped event to the webviews subscribed to it, and to
+/// WebSocket bridge clients (see `ws_bridge`)
+pub fn emit_session_event<S: Serialize + Clone>(app_handle: &AppHandle, event: &str, session_id: &str, payload: S) {
+    if let Some(bridge) = app_handle.try_state::<crate::ws_bridge::WsBridge>() {
+        bridge.publish(event, session_id, &payload);
+    }
+    let Some(subs) = app_handle.try_state::<EventSubscriptions>() else {
+        let _ = app_handle.emit(event, payload);
+        return;
+    };
+    let open: Vec<String> = app_handle.webview_windows().into_keys().collect();
+    match subs.targets(&open, event, session_id) {
+        None => {
+            let _ = app_handle.emit(event, payload);
+        }
+        Some(labels) if labels.is_empty() => {}
+        Some(labels) => {
+            let _ = app_handle.emit_filter(event, payload, |target| match target {
+                EventTarget::Webview { label }
+                | EventTarget::WebviewWindow { label }
+                | EventTarget::Window { label }
+                | EventTarget::AnyLabel { label } => labels.contains(label),
+                _ => false,
+            });
+        }
+    }
+}
+
+/// Start receiving only matching `chat_stream`, `thread_stream` and `process_output`
+/// events in the calling webview. Returns the subscription id.
+#[tauri::command]
+pub async fn subscribe_events(
+    kinds: Option<Vec<String>>,
+    session_ids: Option<Vec<String>>,
+    webview: Webview,
+    subs: State<'_, EventSubscriptions>,
+) -> Result<String, String> {
+    let filter = EventFilter { kinds: kinds.unwrap_or_default(), session_ids: session_ids.unwrap_or_default() };
+    Ok(subs.subscribe(webview.label(), filter))
+}
+
+/// Drop a subscription, or every subscription of the calling webview when
+/// `subscription_id` is omitted. Returns how many were removed.
+#[tauri::command]
+pub async fn unsubscribe_events(
+    subscription_id: Option<String>,
+    webview: Webview,
+    subs: State<'_, EventSubscriptions>,
+) -> Result<usize, String> {
+    Ok(subs.unsubscribe(webview.label(), subscription_id.as_deref()))
+}
+
+#[tauri::command]
+pub async fn list_event_subscriptions(
+    webview: Webview,
+    subs: State<'_, EventSubscriptions>,
+) -> Result<Vec<EventSubscription>, String> {
+    Ok(subs.list(webview.label()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_targets_match_kind_and_session() {
+        let subs = EventSubscriptions::default();
+        subs.subscribe("main", EventFilter { kinds: labels(&["chat_stream"]), session_ids: labels(&["s1"]) });
+        subs.subscribe("inspector", EventFilter { kinds: vec![], session_ids: labels(&["s2"]) });
+        let open = labels(&["main", "inspector"]);
+
+        assert_eq!(subs.targets(&open, "chat_stream", "s1"), Some(labels(&["main"])));
+        assert_eq!(subs.targets(&open, "thread_stream", "s2"), Some(labels(&["inspector"])));
+        assert_eq!(subs.targets(&open, "thread_stream", "s1"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_unsubscribed_webviews_get_everything() {
+        let subs = EventSubscriptions::default();
+        let id = subs.subscribe("main", EventFilter { kinds: labels(&["chat_stream"]), session_ids: vec![] });
+
+        // "other" never subscribed, so events are broadcast
+        assert_eq!(subs.targets(&labels(&["main", "other"]), "process_output", "s1"), None);
+
+        assert_eq!(subs.unsubscribe("main", Some(&id)), 1);
+        assert!(subs.list("main").is_empty());
+        // Still opted in, so it no longer receives anything
+        assert_eq!(subs.targets(&labels(&["main"]), "chat_stream", "s1"), Some(vec![]));
+
+        subs.remove_webview("main");
+        assert_eq!(subs.targets(&labels(&["main"]), "chat_stream", "s1"), None);
+    }
+}
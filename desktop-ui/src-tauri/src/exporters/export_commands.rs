@@ -1,65 +1,337 @@
-use tauri::State;
-use crate::exporters::{SessionExportData, ExportFormat, export_sessions_to_string, enhance_session_data};
+use tauri::{AppHandle, State};
+use crate::exporters::{SessionExportData, TranscriptMessage, ExportFormat, ExportHeader, ExportSnapshot, EXPORT_SCHEMA_VERSION, export_snapshot_to_string, enhance_session_data, create_exporter, RedactionProfile};
+use serde::Serialize;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqliteConnection, SqlitePool};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use crate::operations::OperationRegistry;
+
+pub(crate) fn parse_format(format: &str) -> Result<ExportFormat, String> {
+    match format.to_lowercase().as_str() {
+        "html" => Ok(ExportFormat::Html),
+        "csv" => Ok(ExportFormat::Csv),
+        "jsonl" => Ok(ExportFormat::Jsonl),
+        "markdown" | "md" => Ok(ExportFormat::Markdown),
+        _ => Err("Invalid export format. Supported formats: html, csv, jsonl, markdown".to_string()),
+    }
+}
 
 #[tauri::command]
 pub async fn export_sessions(
     format: String,
+    redaction: Option<RedactionProfile>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<String, String> {
-    // Parse export format
-    let export_format = match format.to_lowercase().as_str() {
-        "html" => ExportFormat::Html,
-        "csv" => ExportFormat::Csv,
-        "jsonl" => ExportFormat::Jsonl,
-        _ => return Err("Invalid export format. Supported formats: html, csv, jsonl".to_string()),
-    };
+    let export_format = parse_format(&format)?;
 
     // Get sessions data from database
     if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        use sqlx::Row;
-        let rows = sqlx::query("SELECT id, context, title, last_snippet, agent_mode, toolbox_path, created_at, updated_at FROM chat_sessions ORDER BY updated_at DESC")
-            .fetch_all(db)
+        let snapshot = load_export_snapshot(db, &export_format, redaction.unwrap_or_default())
             .await
             .map_err(|e| format!("Database error: {}", e))?;
-        
-        let sessions: Vec<SessionExportData> = rows.into_iter().map(|r| {
-            let base_session = serde_json::json!({
-                "id": r.try_get::<String, _>("id").unwrap_or_default(),
-                "context": r.try_get::<String, _>("context").unwrap_or_default(),
-                "title": r.try_get::<String, _>("title").ok(),
-                "last_snippet": r.try_get::<String, _>("last_snippet").ok(),
-                "agent_mode": r.try_get::<String, _>("agent_mode").ok(),
-                "toolbox_path": r.try_get::<String, _>("toolbox_path").ok(),
-                "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
-                "updated_at": r.try_get::<String, _>("updated_at").unwrap_or_default(),
-            });
-            
-            // Get toolbox info if available (placeholder for future integration)
-            let toolbox_info = get_toolbox_info_for_session(&base_session);
-            
-            enhance_session_data(base_session, toolbox_info)
-        }).collect();
-
-        export_sessions_to_string(&sessions, export_format)
+
+        export_snapshot_to_string(&snapshot, export_format)
             .map_err(|e| format!("Export error: {}", e))
     } else {
         Err("Database not available".to_string())
     }
 }
 
+/// Sessions read and formatted per step of a file export; memory use is
+/// bounded by a few chunks of this size
+pub(crate) const EXPORT_CHUNK_SIZE: usize = 500;
+
+const SESSION_QUERY: &str =
+    "SELECT c.id, c.context, c.title, c.last_snippet, c.agent_mode, c.toolbox_path, c.created_at, c.updated_at,
+            f.rating, f.comment AS feedback_comment, f.env_hash
+     FROM chat_sessions c
+     LEFT JOIN thread_feedback f ON f.id = (SELECT MAX(id) FROM thread_feedback WHERE thread_id = c.id)";
+
+fn session_from_row(r: &SqliteRow) -> serde_json::Value {
+    serde_json::json!({
+        "id": r.try_get::<String, _>("id").unwrap_or_default(),
+        "context": r.try_get::<String, _>("context").unwrap_or_default(),
+        "title": r.try_get::<String, _>("title").ok(),
+        "last_snippet": r.try_get::<String, _>("last_snippet").ok(),
+        "agent_mode": r.try_get::<String, _>("agent_mode").ok(),
+        "toolbox_path": r.try_get::<String, _>("toolbox_path").ok(),
+        "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
+        "updated_at": r.try_get::<String, _>("updated_at").unwrap_or_default(),
+        "rating": r.try_get::<i64, _>("rating").ok(),
+        "feedback_comment": r.try_get::<String, _>("feedback_comment").ok(),
+        "env_hash": r.try_get::<String, _>("env_hash").ok(),
+    })
+}
+
+// Messages of a session's threads, oldest first. Threads are matched on their
+// session or, for sessions that are a single thread, on their own id.
+const TRANSCRIPT_QUERY: &str =
+    "SELECT m.role, m.content, m.content_ref, m.created_at, m.input_tokens, m.output_tokens,
+            m.generation_ms, m.cost_usd, m.interrupted_at
+     FROM messages m
+     JOIN threads t ON t.id = m.thread_id
+     WHERE t.session_id = ? OR t.id = ?
+     ORDER BY m.created_at, m.rowid";
+
+fn transcript_message_from_row(r: &SqliteRow) -> TranscriptMessage {
+    let content = r.try_get::<String, _>("content").unwrap_or_default();
+    let content_ref = r.try_get::<Option<String>, _>("content_ref").ok().flatten();
+    TranscriptMessage {
+        role: r.try_get::<String, _>("role").unwrap_or_default(),
+        content: crate::message_content::resolve_content(content, content_ref),
+        created_at: r.try_get::<String, _>("created_at").unwrap_or_default(),
+        input_tokens: r.try_get::<i64, _>("input_tokens").ok().map(|t| t as u64),
+        output_tokens: r.try_get::<i64, _>("output_tokens").ok().map(|t| t as u64),
+        generation_ms: r.try_get::<i64, _>("generation_ms").ok().map(|t| t as u64),
+        cost_usd: r.try_get::<f64, _>("cost_usd").ok(),
+        interrupted: r.try_get::<Option<String>, _>("interrupted_at").ok().flatten().is_some(),
+    }
+}
+
+/// Add each session's messages to its row as `transcript`
+async fn attach_transcripts(conn: &mut SqliteConnection, sessions: &mut [serde_json::Value]) -> Result<(), sqlx::Error> {
+    for session in sessions {
+        let id = session["id"].as_str().unwrap_or_default().to_string();
+        let rows = sqlx::query(TRANSCRIPT_QUERY).bind(&id).bind(&id).fetch_all(&mut *conn).await?;
+        let transcript: Vec<TranscriptMessage> = rows.iter().map(transcript_message_from_row).collect();
+        session["transcript"] = serde_json::to_value(transcript).unwrap_or_default();
+    }
+    Ok(())
+}
+
+// Whether an export in `format` under `redaction` shows conversations
+fn wants_transcripts(format: &ExportFormat, redaction: RedactionProfile) -> bool {
+    format.includes_transcript() && redaction.includes("transcript")
+}
+
+fn to_export_data(base_session: serde_json::Value) -> SessionExportData {
+    // Get toolbox info if available (placeholder for future integration)
+    let toolbox_info = get_toolbox_info_for_session(&base_session);
+    enhance_session_data(base_session, toolbox_info)
+}
+
+/// Header figures for the sessions `conn` can see
+async fn read_export_header(conn: &mut SqliteConnection, redaction: RedactionProfile) -> Result<ExportHeader, sqlx::Error> {
+    let generated_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let (session_count, last_updated_at): (i64, Option<String>) =
+        sqlx::query_as("SELECT COUNT(*), MAX(updated_at) FROM chat_sessions")
+            .fetch_one(conn)
+            .await?;
+    Ok(ExportHeader {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        generated_at,
+        session_count: session_count as usize,
+        last_updated_at,
+        redaction,
+    })
+}
+
+/// Read the sessions and the header figures in one read transaction. The database
+/// runs in WAL mode, so the transaction sees a single snapshot: sessions that are
+/// streaming while the export runs appear either before or after each write, never
+/// half-updated, and the header describes exactly the rows that were exported.
+/// Formats that show conversations get each session's messages from the same snapshot.
+pub(crate) async fn load_export_snapshot(db: &SqlitePool, format: &ExportFormat, redaction: RedactionProfile) -> Result<ExportSnapshot, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let rows = sqlx::query(&format!("{} ORDER BY c.updated_at DESC, c.id DESC", SESSION_QUERY))
+        .fetch_all(&mut *tx)
+        .await?;
+    let mut sessions: Vec<serde_json::Value> = rows.iter().map(session_from_row).collect();
+    if wants_transcripts(format, redaction) {
+        attach_transcripts(&mut tx, &mut sessions).await?;
+    }
+    let header = read_export_header(&mut tx, redaction).await?;
+    tx.commit().await?;
+
+    let sessions = sessions.into_iter().map(to_export_data).collect();
+    Ok(ExportSnapshot { header, sessions })
+}
+
+/// How far a streamed export has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ExportProgress {
+    pub sessions_written: usize,
+    pub session_count: usize,
+    pub bytes_written: u64,
+}
+
+/// Build and format a chunk of sessions on several threads, keeping their order
+fn format_chunk(chunk: Vec<serde_json::Value>, format: ExportFormat, redaction: RedactionProfile) -> Result<Vec<u8>, String> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let part = chunk.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = chunk
+            .chunks(part)
+            .map(|sessions| {
+                let format = format.clone();
+                scope.spawn(move || {
+                    let sessions: Vec<SessionExportData> = sessions.iter().cloned().map(to_export_data).collect();
+                    let mut bytes = Vec::new();
+                    create_exporter(format, redaction)
+                        .write_rows(&sessions, &mut bytes)
+                        .map_err(|e| format!("Export error: {}", e))?;
+                    Ok::<_, String>(bytes)
+                })
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        for worker in workers {
+            bytes.extend(worker.join().map_err(|_| "Export worker panicked".to_string())??);
+        }
+        Ok(bytes)
+    })
+}
+
+/// Stream every session to `writer`, `chunk_size` at a time. Reading runs one
+/// chunk ahead of formatting, and all reads share one transaction so the file
+/// matches a single snapshot just as `load_export_snapshot` does.
+pub(crate) async fn export_to_writer<W: AsyncWrite + Unpin>(
+    db: &SqlitePool,
+    format: ExportFormat,
+    redaction: RedactionProfile,
+    chunk_size: usize,
+    writer: &mut W,
+    mut on_progress: impl FnMut(ExportProgress),
+) -> Result<ExportProgress, String> {
+    let db_error = |e: sqlx::Error| format!("Database error: {}", e);
+    let write_error = |e: std::io::Error| format!("Failed to write export: {}", e);
+    let chunk_size = chunk_size.max(1);
+    let transcripts = wants_transcripts(&format, redaction);
+    let mut tx = db.begin().await.map_err(db_error)?;
+    let header = read_export_header(&mut tx, redaction).await.map_err(db_error)?;
+    let mut progress = ExportProgress { session_count: header.session_count, ..Default::default() };
+
+    let mut prologue = Vec::new();
+    create_exporter(format.clone(), redaction)
+        .write_prologue(Some(&header), &mut prologue)
+        .map_err(|e| format!("Export error: {}", e))?;
+    writer.write_all(&prologue).await.map_err(write_error)?;
+    progress.bytes_written += prologue.len() as u64;
+    on_progress(progress);
+
+    let (chunk_tx, mut chunk_rx) = mpsc::channel::<Vec<serde_json::Value>>(1);
+    let read = async move {
+        // Keyset paging on the export order keeps each read cheap however far in it is
+        let mut after: Option<(String, String)> = None;
+        loop {
+            let query = match &after {
+                Some(_) => format!(
+                    "{} WHERE c.updated_at < ? OR (c.updated_at = ? AND c.id < ?) ORDER BY c.updated_at DESC, c.id DESC LIMIT ?",
+                    SESSION_QUERY
+                ),
+                None => format!("{} ORDER BY c.updated_at DESC, c.id DESC LIMIT ?", SESSION_QUERY),
+            };
+            let mut query = sqlx::query(&query);
+            if let Some((updated_at, id)) = &after {
+                query = query.bind(updated_at).bind(updated_at).bind(id);
+            }
+            let rows = query.bind(chunk_size as i64).fetch_all(&mut *tx).await.map_err(db_error)?;
+            let mut chunk: Vec<serde_json::Value> = rows.iter().map(session_from_row).collect();
+            let done = chunk.len() < chunk_size;
+            after = chunk.last().map(|s| (s["updated_at"].as_str().unwrap_or("").to_string(), s["id"].as_str().unwrap_or("").to_string()));
+            if transcripts {
+                attach_transcripts(&mut tx, &mut chunk).await.map_err(db_error)?;
+            }
+            if !chunk.is_empty() && chunk_tx.send(chunk).await.is_err() {
+                break;
+            }
+            if done {
+                break;
+            }
+        }
+        tx.commit().await.map_err(db_error)
+    };
+    let write = async {
+        while let Some(chunk) = chunk_rx.recv().await {
+            let count = chunk.len();
+            let format = format.clone();
+            let bytes = tokio::task::spawn_blocking(move || format_chunk(chunk, format, redaction))
+                .await
+                .map_err(|e| format!("Export error: {}", e))??;
+            writer.write_all(&bytes).await.map_err(write_error)?;
+            progress.sessions_written += count;
+            progress.bytes_written += bytes.len() as u64;
+            on_progress(progress);
+        }
+        let mut epilogue = Vec::new();
+        create_exporter(format.clone(), redaction)
+            .write_epilogue(&mut epilogue)
+            .map_err(|e| format!("Export error: {}", e))?;
+        writer.write_all(&epilogue).await.map_err(write_error)?;
+        writer.flush().await.map_err(write_error)?;
+        progress.bytes_written += epilogue.len() as u64;
+        Ok::<_, String>(progress)
+    };
+    let ((), progress) = tokio::try_join!(read, write)?;
+    Ok(progress)
+}
+
+/// Temporary export file, removed unless it is moved into place
+struct PartialFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartialFile {
+    async fn persist(mut self, to: &str) -> std::io::Result<()> {
+        tokio::fs::rename(&self.path, to).await?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Export to a file as a cancellable operation, returning its id. Sessions are
+/// streamed in chunks with progress reported after each one. The file is written
+/// to a temporary name and renamed, so a cancelled or failed export leaves no
+/// partial file behind.
+/// Fields are left out as `redaction` says, full by default.
 #[tauri::command]
 pub async fn export_sessions_to_file(
     format: String,
     file_path: String,
+    redaction: Option<RedactionProfile>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
-) -> Result<(), String> {
-    let export_data = export_sessions(format, profile_manager).await?;
-    
-    std::fs::write(&file_path, export_data)
-        .map_err(|e| format!("Failed to write file {}: {}", file_path, e))?;
-    
-    Ok(())
+    operations: State<'_, OperationRegistry>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let export_format = parse_format(&format)?;
+    let redaction = redaction.unwrap_or_default();
+    let db = profile_manager.db_pool.read().await.clone().ok_or("Database not available")?;
+
+    Ok(operations.spawn("export_sessions_to_file", Some(app), |op| async move {
+        let partial = PartialFile { path: PathBuf::from(format!("{}.partial", file_path)), persisted: false };
+        let file = tokio::fs::File::create(&partial.path)
+            .await
+            .map_err(|e| format!("Failed to write file {}: {}", file_path, e))?;
+        let mut writer = tokio::io::BufWriter::new(file);
+        let progress = export_to_writer(&db, export_format, redaction, EXPORT_CHUNK_SIZE, &mut writer, |p| {
+            let fraction = (p.session_count > 0).then(|| p.sessions_written as f32 / p.session_count as f32);
+            let detail = serde_json::to_value(p).unwrap_or_default();
+            op.progress_detail(format!("Exported {} of {} sessions", p.sessions_written, p.session_count), fraction, detail);
+        })
+        .await?;
+        drop(writer);
+        partial
+            .persist(&file_path)
+            .await
+            .map_err(|e| format!("Failed to write file {}: {}", file_path, e))?;
+        Ok(serde_json::json!({
+            "file_path": file_path,
+            "sessions_written": progress.sessions_written,
+            "bytes_written": progress.bytes_written,
+        }))
+    }))
 }
 
 // Helper function to get toolbox information for a session
@@ -71,12 +343,7 @@ fn get_toolbox_info_for_session(session: &serde_json::Value) -> Option<HashMap<S
         return None;
     }
     
-    // Parse toolbox paths (they might be colon/semicolon separated)
-    let paths: Vec<&str> = if cfg!(windows) {
-        toolbox_path.split(';').collect()
-    } else {
-        toolbox_path.split(':').collect()
-    };
+    let paths = crate::platform::split_env_paths(toolbox_path);
     
     let mut toolbox_info = HashMap::new();
     
@@ -85,7 +352,7 @@ fn get_toolbox_info_for_session(session: &serde_json::Value) -> Option<HashMap<S
     let mut available_tools = Vec::new();
     
     for path in paths {
-        if let Ok(entries) = std::fs::read_dir(path.trim()) {
+        if let Ok(entries) = std::fs::read_dir(&path) {
             for entry in entries.flatten() {
                 if let Ok(file_type) = entry.file_type() {
                     if file_type.is_file() {
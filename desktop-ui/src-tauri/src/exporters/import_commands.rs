@@ -0,0 +1,44 @@
+use tauri::State;
+use crate::exporters::ExportFormat;
+use crate::exporters::importers::{parse_import, ImportReport, ImportStore, ImportStrategy};
+
+fn parse_import_format(format: &str) -> Result<ExportFormat, String> {
+    match format.to_lowercase().as_str() {
+        "csv" => Ok(ExportFormat::Csv),
+        "jsonl" => Ok(ExportFormat::Jsonl),
+        _ => Err("Invalid import format. Supported formats: csv, jsonl".to_string()),
+    }
+}
+
+/// Import sessions from CSV or JSONL produced by `export_sessions`. `strategy`
+/// decides what happens to sessions whose id already exists.
+#[tauri::command]
+pub async fn import_sessions(
+    format: String,
+    data: String,
+    strategy: ImportStrategy,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<ImportReport, String> {
+    let import_format = parse_import_format(&format)?;
+    let parsed = parse_import(&data, import_format).map_err(|e| e.to_command_error())?;
+
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    ImportStore::new(db.clone())
+        .import(parsed, strategy)
+        .await
+        .map_err(|e| e.to_command_error())
+}
+
+#[tauri::command]
+pub async fn import_sessions_from_file(
+    format: String,
+    file_path: String,
+    strategy: ImportStrategy,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<ImportReport, String> {
+    let data = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
+
+    import_sessions(format, data, strategy, profile_manager).await
+}
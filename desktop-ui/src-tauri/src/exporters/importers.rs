@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::exporters::{ExportFormat, ExportHeader, SessionExportData, EXPORT_SCHEMA_VERSION};
+
+// What to do with an imported session whose id already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStrategy {
+    Skip,
+    Overwrite,
+    DuplicateAsNew,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ImportError {
+    #[error("Export schema version {found} is newer than the supported version {supported}")]
+    UnsupportedSchemaVersion { found: u32, supported: u32 },
+
+    #[error("Invalid import file: {reason}")]
+    InvalidFormat { reason: String },
+
+    #[error("Database error: {reason}")]
+    Database { reason: String },
+}
+
+impl From<sqlx::Error> for ImportError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database { reason: e.to_string() }
+    }
+}
+
+impl ImportError {
+    fn invalid(reason: impl Into<String>) -> Self {
+        Self::InvalidFormat { reason: reason.into() }
+    }
+
+    /// JSON form returned to the frontend: the error fields plus a readable `message`
+    pub fn to_command_error(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("message".to_string(), serde_json::Value::String(self.to_string()));
+        }
+        value.to_string()
+    }
+}
+
+// A record that could not be imported; the rest of the file still is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportRowError {
+    // 1-based line (JSONL) or record (CSV, header excluded) number
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParsedImport {
+    // None for exports made before the header existed
+    pub schema_version: Option<u32>,
+    pub sessions: Vec<(usize, SessionExportData)>,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub schema_version: Option<u32>,
+    pub inserted: usize,
+    pub overwritten: usize,
+    pub duplicated: usize,
+    pub skipped: usize,
+    pub feedback_imported: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+fn check_schema_version(version: u32) -> Result<u32, ImportError> {
+    if version > EXPORT_SCHEMA_VERSION {
+        return Err(ImportError::UnsupportedSchemaVersion { found: version, supported: EXPORT_SCHEMA_VERSION });
+    }
+    Ok(version)
+}
+
+fn validate(session: &SessionExportData) -> Result<(), String> {
+    if session.id.trim().is_empty() {
+        return Err("Missing session id".to_string());
+    }
+    if session.context.trim().is_empty() {
+        return Err("Missing context".to_string());
+    }
+    if let Some(rating) = session.rating {
+        if !(1..=5).contains(&rating) {
+            return Err(format!("Rating {} is outside 1-5", rating));
+        }
+    }
+    Ok(())
+}
+
+fn parse_jsonl(data: &str) -> Result<ParsedImport, ImportError> {
+    let mut parsed = ParsedImport::default();
+    for (index, line) in data.lines().enumerate() {
+        let row = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => {
+                parsed.errors.push(ImportRowError { row, reason: format!("Invalid JSON: {}", e) });
+                continue;
+            }
+        };
+        if let Some(header) = value.get("export_header") {
+            let header: ExportHeader = serde_json::from_value(header.clone())
+                .map_err(|e| ImportError::invalid(format!("Invalid export header: {}", e)))?;
+            parsed.schema_version = Some(check_schema_version(header.schema_version)?);
+            continue;
+        }
+        match serde_json::from_value::<SessionExportData>(value) {
+            Ok(session) => match validate(&session) {
+                Ok(()) => parsed.sessions.push((row, session)),
+                Err(reason) => parsed.errors.push(ImportRowError { row, reason }),
+            },
+            Err(e) => parsed.errors.push(ImportRowError { row, reason: e.to_string() }),
+        }
+    }
+    Ok(parsed)
+}
+
+// Split CSV text into records. Handles quoted fields with `""` escapes and
+// newlines inside quotes.
+fn csv_records(data: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+fn csv_header_version(line: &str) -> Result<Option<u32>, ImportError> {
+    let Some(meta) = line.strip_prefix('#') else {
+        return Ok(None);
+    };
+    for pair in meta.split_whitespace() {
+        if let Some(value) = pair.strip_prefix("schema_version=") {
+            let version = value
+                .parse::<u32>()
+                .map_err(|_| ImportError::invalid(format!("Invalid schema_version '{}'", value)))?;
+            return Ok(Some(check_schema_version(version)?));
+        }
+    }
+    Ok(None)
+}
+
+fn parse_csv(data: &str) -> Result<ParsedImport, ImportError> {
+    let mut parsed = ParsedImport::default();
+    let mut body = data;
+    // Metadata comment lines come before the column header
+    while body.starts_with('#') {
+        let (line, rest) = body.split_once('\n').unwrap_or((body, ""));
+        if let Some(version) = csv_header_version(line.trim_end())? {
+            parsed.schema_version = Some(version);
+        }
+        body = rest;
+    }
+
+    let mut records = csv_records(body).into_iter();
+    let columns = records.next().ok_or_else(|| ImportError::invalid("Missing CSV header row"))?;
+    let position = |name: &str| columns.iter().position(|c| c.trim() == name);
+    let (Some(id_col), Some(context_col)) = (position("id"), position("context")) else {
+        return Err(ImportError::invalid("CSV header must include id and context columns"));
+    };
+    // Other columns are optional and looked up by name, so older exports still load
+    let col = |name: &str| position(name);
+
+    for (index, record) in records.enumerate() {
+        let row = index + 1;
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let get = |position: Option<usize>| {
+            position
+                .and_then(|p| record.get(p))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let number = |name: &str| -> Result<Option<u64>, String> {
+            get(col(name)).map(|v| v.parse::<u64>().map_err(|_| format!("Invalid {} '{}'", name, v))).transpose()
+        };
+
+        let session = (|| -> Result<SessionExportData, String> {
+            Ok(SessionExportData {
+                id: get(Some(id_col)).unwrap_or_default(),
+                context: get(Some(context_col)).unwrap_or_default(),
+                title: get(col("title")),
+                last_snippet: get(col("last_snippet")),
+                agent_mode: get(col("agent_mode")),
+                toolbox_path: get(col("toolbox_path")),
+                tools_available_count: number("tools_available_count")?.map(|c| c as u32),
+                tools_used: get(col("tools_used")).map(|v| v.split(';').map(|t| t.to_string()).collect()),
+                created_at: get(col("created_at")).unwrap_or_default(),
+                updated_at: get(col("updated_at")).unwrap_or_default(),
+                input_tokens: number("input_tokens")?,
+                output_tokens: number("output_tokens")?,
+                inference_duration_ms: number("inference_duration_ms")?,
+                service_tier: None,
+                rating: get(col("rating"))
+                    .map(|v| v.parse::<i64>().map_err(|_| format!("Invalid rating '{}'", v)))
+                    .transpose()?,
+                feedback_comment: get(col("feedback_comment")),
+                env_hash: get(col("env_hash")),
+                transcript: None,
+            })
+        })()
+        .and_then(|session| validate(&session).map(|()| session));
+
+        match session {
+            Ok(session) => parsed.sessions.push((row, session)),
+            Err(reason) => parsed.errors.push(ImportRowError { row, reason }),
+        }
+    }
+    Ok(parsed)
+}
+
+pub fn parse_import(data: &str, format: ExportFormat) -> Result<ParsedImport, ImportError> {
+    match format {
+        ExportFormat::Jsonl => parse_jsonl(data),
+        ExportFormat::Csv => parse_csv(data),
+        ExportFormat::Html => Err(ImportError::invalid("HTML exports cannot be imported; use CSV or JSONL")),
+        ExportFormat::Markdown => Err(ImportError::invalid("Markdown exports cannot be imported; use CSV or JSONL")),
+    }
+}
+
+enum Applied {
+    Inserted,
+    Overwritten,
+    Duplicated,
+}
+
+pub struct ImportStore {
+    pub db: SqlitePool,
+}
+
+impl ImportStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Insert parsed sessions into `chat_sessions` in one transaction. Rows that
+    /// fail are reported and skipped; the rest of the file is still imported.
+    pub async fn import(&self, parsed: ParsedImport, strategy: ImportStrategy) -> Result<ImportReport, ImportError> {
+        let mut report = ImportReport {
+            schema_version: parsed.schema_version,
+            errors: parsed.errors,
+            ..Default::default()
+        };
+        let mut tx = self.db.begin().await?;
+
+        for (row, session) in parsed.sessions {
+            let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM chat_sessions WHERE id = ?")
+                .bind(&session.id)
+                .fetch_one(&mut *tx)
+                .await?
+                > 0;
+
+            let result = match (exists, strategy) {
+                (true, ImportStrategy::Skip) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                // The update trigger sets updated_at to the import time
+                (true, ImportStrategy::Overwrite) => sqlx::query(
+                    "UPDATE chat_sessions SET context = ?, title = ?, last_snippet = ?, agent_mode = ?, toolbox_path = ?,
+                            created_at = COALESCE(NULLIF(?, ''), created_at)
+                     WHERE id = ?",
+                )
+                .bind(&session.context)
+                .bind(&session.title)
+                .bind(&session.last_snippet)
+                .bind(&session.agent_mode)
+                .bind(&session.toolbox_path)
+                .bind(&session.created_at)
+                .bind(&session.id)
+                .execute(&mut *tx)
+                .await
+                .map(|_| (session.id.clone(), Applied::Overwritten)),
+                (true, ImportStrategy::DuplicateAsNew) | (false, _) => {
+                    let id = if exists { uuid::Uuid::new_v4().to_string() } else { session.id.clone() };
+                    sqlx::query(
+                        "INSERT INTO chat_sessions (id, context, title, last_snippet, agent_mode, toolbox_path, created_at, updated_at)
+                         VALUES (?, ?, ?, ?, ?, ?, COALESCE(NULLIF(?, ''), CURRENT_TIMESTAMP), COALESCE(NULLIF(?, ''), CURRENT_TIMESTAMP))",
+                    )
+                    .bind(&id)
+                    .bind(&session.context)
+                    .bind(&session.title)
+                    .bind(&session.last_snippet)
+                    .bind(&session.agent_mode)
+                    .bind(&session.toolbox_path)
+                    .bind(&session.created_at)
+                    .bind(&session.updated_at)
+                    .execute(&mut *tx)
+                    .await
+                    .map(|_| (id, if exists { Applied::Duplicated } else { Applied::Inserted }))
+                }
+            };
+
+            let session_id = match result {
+                Ok((session_id, applied)) => {
+                    match applied {
+                        Applied::Inserted => report.inserted += 1,
+                        Applied::Overwritten => report.overwritten += 1,
+                        Applied::Duplicated => report.duplicated += 1,
+                    }
+                    session_id
+                }
+                Err(e) => {
+                    report.errors.push(ImportRowError { row, reason: e.to_string() });
+                    continue;
+                }
+            };
+
+            // Feedback needs the environment hash it was given against
+            if let (Some(rating), Some(env_hash)) = (session.rating, session.env_hash.as_deref()) {
+                let already = sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM thread_feedback
+                     WHERE thread_id = ? AND rating = ? AND env_hash = ? AND comment IS ?",
+                )
+                .bind(&session_id)
+                .bind(rating)
+                .bind(env_hash)
+                .bind(&session.feedback_comment)
+                .fetch_one(&mut *tx)
+                .await?;
+                if already == 0 {
+                    sqlx::query(
+                        "INSERT INTO thread_feedback (thread_id, rating, comment, env_hash, agent_mode) VALUES (?, ?, ?, ?, ?)",
+                    )
+                    .bind(&session_id)
+                    .bind(rating)
+                    .bind(&session.feedback_comment)
+                    .bind(env_hash)
+                    .bind(&session.agent_mode)
+                    .execute(&mut *tx)
+                    .await?;
+                    report.feedback_imported += 1;
+                }
+            }
+        }
+
+        tx.commit().await?;
+        report.errors.sort_by_key(|e| e.row);
+        Ok(report)
+    }
+}
@@ -2,9 +2,15 @@ use serde::{Deserialize, Serialize};
 use std::io::Write;
 use std::collections::HashMap;
 
+use crate::i18n::tr;
+
 pub mod export_commands;
+pub mod import_commands;
+pub mod importers;
 #[cfg(test)]
 mod test_exporters;
+#[cfg(test)]
+mod test_importers;
 
 // Session data structure enhanced with M1.4 fields
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +30,96 @@ pub struct SessionExportData {
     pub output_tokens: Option<u64>,
     pub inference_duration_ms: Option<u64>,
     pub service_tier: Option<String>,
+    // Latest user feedback for the session
+    pub rating: Option<i64>,
+    pub feedback_comment: Option<String>,
+    pub env_hash: Option<String>,
+    // Full conversation, only loaded for formats that show it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<Vec<TranscriptMessage>>,
+}
+
+// One stored message of a session, with the metrics recorded on it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranscriptMessage {
+    pub role: String,
+    // As stored: a CLI stream event as JSON, or plain text
+    pub content: String,
+    pub created_at: String,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub generation_ms: Option<u64>,
+    pub cost_usd: Option<f64>,
+    #[serde(default)]
+    pub interrupted: bool,
+}
+
+/// Which session fields an export includes, chosen per export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionProfile {
+    /// Everything
+    #[default]
+    Full,
+    /// For the team: prompts stay, token counts and the cost tier are left out
+    Internal,
+    /// For sharing outside: also leaves out prompts, feedback text and local paths
+    Public,
+}
+
+impl RedactionProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Internal => "internal",
+            Self::Public => "public",
+        }
+    }
+
+    // Session fields left out of exports under this profile
+    pub fn hidden_fields(self) -> &'static [&'static str] {
+        match self {
+            Self::Full => &[],
+            Self::Internal => &["input_tokens", "output_tokens", "service_tier"],
+            Self::Public => &[
+                "title",
+                "transcript",
+                "last_snippet",
+                "feedback_comment",
+                "toolbox_path",
+                "input_tokens",
+                "output_tokens",
+                "service_tier",
+            ],
+        }
+    }
+
+    pub fn includes(self, field: &str) -> bool {
+        !self.hidden_fields().contains(&field)
+    }
+}
+
+/// Bumped whenever exported columns change, so importers can reject files they don't understand
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+// Written before the rows: when the export was taken and what it covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub schema_version: u32,
+    pub generated_at: String,
+    pub session_count: usize,
+    // Newest updated_at in the snapshot; every row is at or before it
+    pub last_updated_at: Option<String>,
+    // Exports made before redaction profiles existed are full
+    #[serde(default)]
+    pub redaction: RedactionProfile,
+}
+
+// Sessions read in one database snapshot, together with their header
+#[derive(Debug, Clone)]
+pub struct ExportSnapshot {
+    pub header: ExportHeader,
+    pub sessions: Vec<SessionExportData>,
 }
 
 // Export format enum
@@ -32,20 +128,97 @@ pub enum ExportFormat {
     Html,
     Csv,
     Jsonl,
+    Markdown,
+}
+
+impl ExportFormat {
+    // Whether sessions need their messages loaded
+    pub fn includes_transcript(&self) -> bool {
+        matches!(self, Self::Markdown)
+    }
 }
 
-// Generic exporter trait
+// Generic exporter trait. A document is a prologue, rows written in any number
+// of batches and an epilogue, so large exports can be written a chunk at a time.
 pub trait Exporter {
-    fn export_sessions(&mut self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>>;
+    // Everything before the rows, with the snapshot header in the format's own metadata slot if given
+    fn write_prologue(&self, header: Option<&ExportHeader>, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>>;
+    fn write_rows(&self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>>;
+    fn write_epilogue(&self, _writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn export_sessions(&mut self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_prologue(None, writer)?;
+        self.write_rows(sessions, writer)?;
+        self.write_epilogue(writer)
+    }
+
+    // Same output with the snapshot header in the format's own metadata slot
+    fn export_snapshot(&mut self, snapshot: &ExportSnapshot, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_prologue(Some(&snapshot.header), writer)?;
+        self.write_rows(&snapshot.sessions, writer)?;
+        self.write_epilogue(writer)
+    }
 }
 
 // HTML Exporter
-pub struct HtmlExporter;
+#[derive(Default)]
+pub struct HtmlExporter {
+    pub redaction: RedactionProfile,
+}
+
+/// Session fields shown as HTML and CSV columns, in column order, with the
+/// catalog key of each HTML heading
+const COLUMNS: [(&str, &str); 15] = [
+    ("id", "export-col-id"),
+    ("context", "export-col-context"),
+    ("title", "export-col-title"),
+    ("agent_mode", "export-col-agent-mode"),
+    ("toolbox_path", "export-col-toolbox-path"),
+    ("tools_available_count", "export-col-tools-available"),
+    ("tools_used", "export-col-tools-used"),
+    ("input_tokens", "export-col-input-tokens"),
+    ("output_tokens", "export-col-output-tokens"),
+    ("inference_duration_ms", "export-col-duration"),
+    ("rating", "export-col-rating"),
+    ("feedback_comment", "export-col-feedback"),
+    ("env_hash", "export-col-env-hash"),
+    ("created_at", "export-col-created"),
+    ("updated_at", "export-col-updated"),
+];
+
+// Columns the profile lets through
+fn visible_columns(redaction: RedactionProfile) -> impl Iterator<Item = (&'static str, &'static str)> {
+    COLUMNS.into_iter().filter(move |(field, _)| redaction.includes(field))
+}
+
+// A column's value as text, None when the session has none
+fn column_value(session: &SessionExportData, field: &str, list_separator: &str) -> Option<String> {
+    match field {
+        "id" => Some(session.id.clone()),
+        "context" => Some(session.context.clone()),
+        "title" => session.title.clone(),
+        "agent_mode" => session.agent_mode.clone(),
+        "toolbox_path" => session.toolbox_path.clone(),
+        "tools_available_count" => session.tools_available_count.map(|c| c.to_string()),
+        "tools_used" => session.tools_used.as_ref().map(|tools| tools.join(list_separator)),
+        "input_tokens" => session.input_tokens.map(|t| t.to_string()),
+        "output_tokens" => session.output_tokens.map(|t| t.to_string()),
+        "inference_duration_ms" => session.inference_duration_ms.map(|d| d.to_string()),
+        "rating" => session.rating.map(|r| r.to_string()),
+        "feedback_comment" => session.feedback_comment.clone(),
+        "env_hash" => session.env_hash.clone(),
+        "created_at" => Some(session.created_at.clone()),
+        "updated_at" => Some(session.updated_at.clone()),
+        _ => None,
+    }
+}
 
 impl Exporter for HtmlExporter {
-    fn export_sessions(&mut self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    fn write_prologue(&self, header: Option<&ExportHeader>, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
         write!(writer, "<!DOCTYPE html>\n<html>\n<head>\n")?;
-        write!(writer, "<title>Amp Session Export</title>\n")?;
+        write!(writer, "<title>{}</title>\n", tr!("export-title"))?;
         write!(writer, "<style>\n")?;
         write!(writer, "table {{ border-collapse: collapse; width: 100%; }}\n")?;
         write!(writer, "th, td {{ border: 1px solid #ddd; padding: 8px; text-align: left; }}\n")?;
@@ -54,18 +227,33 @@ impl Exporter for HtmlExporter {
         write!(writer, ".context-development {{ background-color: #fff3cd; }}\n")?;
         write!(writer, "</style>\n")?;
         write!(writer, "</head>\n<body>\n")?;
-        write!(writer, "<h1>Amp Session Export</h1>\n")?;
+        write!(writer, "<h1>{}</h1>\n", tr!("export-title"))?;
+        if let Some(header) = header {
+            let not_available = tr!("export-not-available");
+            writeln!(
+                writer,
+                "<p class=\"export-meta\" data-schema-version=\"{}\" data-redaction=\"{}\">{} &middot; {} &middot; {}</p>",
+                header.schema_version,
+                header.redaction.as_str(),
+                tr!("export-generated", date = header.generated_at),
+                tr!("export-session-count", count = header.session_count),
+                tr!("export-last-updated", date = header.last_updated_at.as_deref().unwrap_or(&not_available))
+            )?;
+        }
         write!(writer, "<table>\n")?;
         
-        // Header
+        // Column headings
         write!(writer, "<tr>\n")?;
-        write!(writer, "<th>ID</th><th>Context</th><th>Title</th><th>Agent Mode</th>\n")?;
-        write!(writer, "<th>Toolbox Path</th><th>Tools Available</th><th>Tools Used</th>\n")?;
-        write!(writer, "<th>Input Tokens</th><th>Output Tokens</th><th>Duration (ms)</th>\n")?;
-        write!(writer, "<th>Created</th><th>Updated</th>\n")?;
+        for (_, key) in visible_columns(self.redaction) {
+            write!(writer, "<th>{}</th>", tr!(key))?;
+        }
+        writeln!(writer)?;
         write!(writer, "</tr>\n")?;
-        
-        // Data rows
+        Ok(())
+    }
+
+    fn write_rows(&self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        let not_available = tr!("export-not-available");
         for session in sessions {
             let context_class = match session.context.as_str() {
                 "production" => "context-production",
@@ -73,83 +261,356 @@ impl Exporter for HtmlExporter {
                 _ => "",
             };
             write!(writer, "<tr class=\"{}\">\n", context_class)?;
-            write!(writer, "<td>{}</td>", session.id)?;
-            write!(writer, "<td>{}</td>", session.context)?;
-            write!(writer, "<td>{}</td>", session.title.as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.agent_mode.as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.toolbox_path.as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.tools_available_count.map(|c| c.to_string()).as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.tools_used.as_ref().map(|tools| tools.join(", ")).as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.input_tokens.map(|t| t.to_string()).as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.output_tokens.map(|t| t.to_string()).as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.inference_duration_ms.map(|d| d.to_string()).as_deref().unwrap_or("N/A"))?;
-            write!(writer, "<td>{}</td>", session.created_at)?;
-            write!(writer, "<td>{}</td>", session.updated_at)?;
+            for (field, _) in visible_columns(self.redaction) {
+                write!(writer, "<td>{}</td>", column_value(session, field, ", ").as_deref().unwrap_or(&not_available))?;
+            }
             write!(writer, "</tr>\n")?;
         }
-        
+        Ok(())
+    }
+
+    fn write_epilogue(&self, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
         write!(writer, "</table>\n</body>\n</html>\n")?;
         Ok(())
     }
 }
 
 // CSV Exporter  
-pub struct CsvExporter;
+#[derive(Default)]
+pub struct CsvExporter {
+    pub redaction: RedactionProfile,
+}
+
+// Free-text columns, always quoted
+const CSV_QUOTED: [&str; 4] = ["title", "toolbox_path", "tools_used", "feedback_comment"];
+
+// Double embedded quotes so quoted fields survive a round trip
+fn csv_escape(value: &str) -> String {
+    value.replace('"', "\"\"")
+}
 
 impl Exporter for CsvExporter {
-    fn export_sessions(&mut self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
-        // Header
-        writeln!(writer, "id,context,title,agent_mode,toolbox_path,tools_available_count,tools_used,input_tokens,output_tokens,inference_duration_ms,created_at,updated_at")?;
-        
-        // Data rows
+    fn write_prologue(&self, header: Option<&ExportHeader>, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(header) = header {
+            writeln!(
+                writer,
+                "# schema_version={} generated_at={} session_count={} last_updated_at={} redaction={}",
+                header.schema_version,
+                header.generated_at,
+                header.session_count,
+                header.last_updated_at.as_deref().unwrap_or(""),
+                header.redaction.as_str()
+            )?;
+        }
+        let columns: Vec<&str> = visible_columns(self.redaction).map(|(field, _)| field).collect();
+        writeln!(writer, "{}", columns.join(","))?;
+        Ok(())
+    }
+
+    fn write_rows(&self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
         for session in sessions {
-            write!(writer, "{},", session.id)?;
-            write!(writer, "{},", session.context)?;
-            write!(writer, "\"{}\",", session.title.as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.agent_mode.as_deref().unwrap_or(""))?;
-            write!(writer, "\"{}\",", session.toolbox_path.as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.tools_available_count.map(|c| c.to_string()).as_deref().unwrap_or(""))?;
-            write!(writer, "\"{}\",", session.tools_used.as_ref().map(|tools| tools.join(";")).as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.input_tokens.map(|t| t.to_string()).as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.output_tokens.map(|t| t.to_string()).as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.inference_duration_ms.map(|d| d.to_string()).as_deref().unwrap_or(""))?;
-            write!(writer, "{},", session.created_at)?;
-            writeln!(writer, "{}", session.updated_at)?;
+            let values: Vec<String> = visible_columns(self.redaction)
+                .map(|(field, _)| {
+                    let value = column_value(session, field, ";").unwrap_or_default();
+                    if CSV_QUOTED.contains(&field) {
+                        format!("\"{}\"", csv_escape(&value))
+                    } else {
+                        value
+                    }
+                })
+                .collect();
+            writeln!(writer, "{}", values.join(","))?;
         }
         Ok(())
     }
 }
 
 // JSONL Exporter
-pub struct JsonlExporter;
+#[derive(Default)]
+pub struct JsonlExporter {
+    pub redaction: RedactionProfile,
+}
 
 impl Exporter for JsonlExporter {
-    fn export_sessions(&mut self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    fn write_prologue(&self, header: Option<&ExportHeader>, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(header) = header {
+            writeln!(writer, "{}", serde_json::json!({ "export_header": header }))?;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        let hidden = self.redaction.hidden_fields();
         for session in sessions {
-            let json_line = serde_json::to_string(session)?;
+            let json_line = if hidden.is_empty() {
+                serde_json::to_string(session)?
+            } else {
+                let mut value = serde_json::to_value(session)?;
+                if let Some(fields) = value.as_object_mut() {
+                    fields.retain(|field, _| !hidden.contains(&field.as_str()));
+                }
+                value.to_string()
+            };
             writeln!(writer, "{}", json_line)?;
         }
         Ok(())
     }
 }
 
+// Markdown Exporter: a readable report with each session's metrics and full transcript
+#[derive(Default)]
+pub struct MarkdownExporter {
+    pub redaction: RedactionProfile,
+}
+
+// `text` in a fenced code block, fenced with more backticks than it contains in a row
+fn md_fenced(text: &str, language: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, text.trim_end(), fence)
+}
+
+// Text of a tool result, whose content is a string or a list of text parts
+fn tool_result_text(part: &serde_json::Value) -> String {
+    match part.get("content") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+// The stream event a message holds, None for plain text
+fn stored_event(message: &TranscriptMessage) -> Option<serde_json::Value> {
+    serde_json::from_str::<serde_json::Value>(&message.content).ok().filter(|event| event.is_object())
+}
+
+// Markdown blocks of a message: text as written, tool calls and results as code.
+// Stored events without message content (results, system notices) have none.
+fn message_blocks(message: &TranscriptMessage) -> Vec<String> {
+    let Some(event) = stored_event(message) else {
+        return vec![message.content.trim().to_string()];
+    };
+    match event.get("message").and_then(|m| m.get("content")) {
+        Some(serde_json::Value::String(text)) => vec![text.trim().to_string()],
+        Some(serde_json::Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+                Some("text") => part.get("text").and_then(|t| t.as_str()).map(|t| t.trim().to_string()),
+                Some("tool_use") => {
+                    let name = part.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+                    let input = part.get("input").map(|i| serde_json::to_string_pretty(i).unwrap_or_default()).unwrap_or_default();
+                    Some(format!("**{}**\n\n{}", tr!("export-tool-call", name = name), md_fenced(&input, "json")))
+                }
+                Some("tool_result") => Some(format!(
+                    "<details>\n<summary>{}</summary>\n\n{}\n\n</details>",
+                    tr!("export-tool-result"),
+                    md_fenced(&tool_result_text(part), "")
+                )),
+                _ => None,
+            })
+            .filter(|block| !block.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Whether a message starts a turn: a user prompt rather than a tool result
+fn is_turn_start(message: &TranscriptMessage) -> bool {
+    message.role == "user" && stored_event(message).is_none_or(|event| crate::history_replay::is_prompt(&event))
+}
+
+// Sum of a metric over the messages that have it, None when none do
+fn metric_total<T: std::iter::Sum<T>>(transcript: &[TranscriptMessage], metric: impl Fn(&TranscriptMessage) -> Option<T>) -> Option<T> {
+    let values: Vec<T> = transcript.iter().filter_map(metric).collect();
+    (!values.is_empty()).then(|| values.into_iter().sum())
+}
+
+impl MarkdownExporter {
+    fn write_metrics(&self, session: &SessionExportData, transcript: &[TranscriptMessage], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        let mut lines = vec![
+            (tr!("export-messages"), Some(transcript.len().to_string())),
+            (tr!("export-turns"), Some(transcript.iter().filter(|m| is_turn_start(m)).count().to_string())),
+        ];
+        // Token counts and cost are left out with the fields they belong with
+        if self.redaction.includes("input_tokens") {
+            let total = metric_total(transcript, |m| m.input_tokens).or(session.input_tokens);
+            lines.push((tr!("export-col-input-tokens"), total.map(|t| t.to_string())));
+        }
+        if self.redaction.includes("output_tokens") {
+            let total = metric_total(transcript, |m| m.output_tokens).or(session.output_tokens);
+            lines.push((tr!("export-col-output-tokens"), total.map(|t| t.to_string())));
+        }
+        let generation = metric_total(transcript, |m| m.generation_ms).or(session.inference_duration_ms);
+        lines.push((tr!("export-generation-time"), generation.map(|t| t.to_string())));
+        if self.redaction.includes("service_tier") {
+            lines.push((tr!("export-cost"), metric_total(transcript, |m| m.cost_usd).map(|c| format!("{:.4}", c))));
+        }
+
+        writeln!(writer, "### {}\n", tr!("export-metrics"))?;
+        for (label, value) in lines {
+            if let Some(value) = value {
+                writeln!(writer, "- **{}:** {}", label, value)?;
+            }
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    fn write_transcript(&self, transcript: &[TranscriptMessage], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(writer, "### {}\n", tr!("export-transcript"))?;
+        for message in transcript {
+            let blocks = message_blocks(message);
+            if blocks.is_empty() {
+                continue;
+            }
+            let role = match message.role.as_str() {
+                "user" => tr!("export-role-user"),
+                "assistant" => tr!("export-role-assistant"),
+                _ => tr!("export-role-system"),
+            };
+            let interrupted = if message.interrupted { format!(" ({})", tr!("export-interrupted")) } else { String::new() };
+            writeln!(writer, "#### {} · {}{}\n", role, message.created_at, interrupted)?;
+            for block in blocks {
+                writeln!(writer, "{}\n", block)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Exporter for MarkdownExporter {
+    fn write_prologue(&self, header: Option<&ExportHeader>, writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(writer, "# {}\n", tr!("export-title"))?;
+        if let Some(header) = header {
+            let not_available = tr!("export-not-available");
+            writeln!(
+                writer,
+                "<!-- schema_version={} redaction={} -->\n_{} · {} · {}_\n",
+                header.schema_version,
+                header.redaction.as_str(),
+                tr!("export-generated", date = header.generated_at),
+                tr!("export-session-count", count = header.session_count),
+                tr!("export-last-updated", date = header.last_updated_at.as_deref().unwrap_or(&not_available))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&self, sessions: &[SessionExportData], writer: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+        for session in sessions {
+            let heading = if self.redaction.includes("title") { session.title.as_deref() } else { None };
+            writeln!(writer, "---\n\n## {}\n", heading.unwrap_or(&session.id).replace('\n', " "))?;
+            for (field, key) in visible_columns(self.redaction) {
+                if let Some(value) = column_value(session, field, ", ") {
+                    writeln!(writer, "- **{}:** {}", tr!(key), value.replace('\n', " "))?;
+                }
+            }
+            writeln!(writer)?;
+
+            let transcript = session.transcript.as_deref().filter(|_| self.redaction.includes("transcript")).unwrap_or_default();
+            self.write_metrics(session, transcript, writer)?;
+            if !transcript.is_empty() {
+                self.write_transcript(transcript, writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 // Factory function to create exporter
-pub fn create_exporter(format: ExportFormat) -> Box<dyn Exporter> {
+pub fn create_exporter(format: ExportFormat, redaction: RedactionProfile) -> Box<dyn Exporter> {
     match format {
-        ExportFormat::Html => Box::new(HtmlExporter),
-        ExportFormat::Csv => Box::new(CsvExporter),
-        ExportFormat::Jsonl => Box::new(JsonlExporter),
+        ExportFormat::Html => Box::new(HtmlExporter { redaction }),
+        ExportFormat::Csv => Box::new(CsvExporter { redaction }),
+        ExportFormat::Jsonl => Box::new(JsonlExporter { redaction }),
+        ExportFormat::Markdown => Box::new(MarkdownExporter { redaction }),
     }
 }
 
 // Helper function to export sessions with a specific format
 pub fn export_sessions_to_string(sessions: &[SessionExportData], format: ExportFormat) -> Result<String, Box<dyn std::error::Error>> {
     let mut buffer = Vec::new();
-    let mut exporter = create_exporter(format);
+    let mut exporter = create_exporter(format, RedactionProfile::Full);
     exporter.export_sessions(sessions, &mut buffer)?;
     Ok(String::from_utf8(buffer)?)
 }
 
+// Helper function to export a snapshot, header included, redacted as its header says
+pub fn export_snapshot_to_string(snapshot: &ExportSnapshot, format: ExportFormat) -> Result<String, Box<dyn std::error::Error>> {
+    let mut buffer = Vec::new();
+    let mut exporter = create_exporter(format, snapshot.header.redaction);
+    exporter.export_snapshot(snapshot, &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+// A report other than the session list, such as the version matrix: named
+// columns and one cell per column in each row, None where there is no value
+#[derive(Debug, Clone, Default)]
+pub struct ReportTable {
+    pub title: String,
+    pub generated_at: String,
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+// A CSV field, quoted only when it has to be
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", csv_escape(value))
+    } else {
+        value.to_string()
+    }
+}
+
+// Write a report in any export format
+pub fn export_report_to_string(report: &ReportTable, format: ExportFormat) -> Result<String, Box<dyn std::error::Error>> {
+    let not_available = tr!("export-not-available");
+    let mut out = Vec::new();
+    match format {
+        ExportFormat::Html => {
+            writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<title>{}</title>\n</head>\n<body>", report.title)?;
+            writeln!(out, "<h1>{}</h1>", report.title)?;
+            writeln!(out, "<p class=\"export-meta\">{}</p>", tr!("export-generated", date = report.generated_at))?;
+            writeln!(out, "<table>\n<tr>{}</tr>", report.columns.iter().map(|c| format!("<th>{}</th>", c)).collect::<String>())?;
+            for row in &report.rows {
+                let cells: String = row.iter().map(|v| format!("<td>{}</td>", v.as_deref().unwrap_or(&not_available))).collect();
+                writeln!(out, "<tr>{}</tr>", cells)?;
+            }
+            writeln!(out, "</table>\n</body>\n</html>")?;
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "# generated_at={}", report.generated_at)?;
+            writeln!(out, "{}", report.columns.join(","))?;
+            for row in &report.rows {
+                let values: Vec<String> = row.iter().map(|v| csv_field(v.as_deref().unwrap_or_default())).collect();
+                writeln!(out, "{}", values.join(","))?;
+            }
+        }
+        ExportFormat::Jsonl => {
+            writeln!(out, "{}", serde_json::json!({ "report_header": { "title": report.title, "generated_at": report.generated_at } }))?;
+            for row in &report.rows {
+                let fields: serde_json::Map<String, serde_json::Value> =
+                    report.columns.iter().zip(row).map(|(column, value)| (column.to_string(), serde_json::json!(value))).collect();
+                writeln!(out, "{}", serde_json::Value::Object(fields))?;
+            }
+        }
+        ExportFormat::Markdown => {
+            writeln!(out, "# {}\n\n_{}_\n", report.title, tr!("export-generated", date = report.generated_at))?;
+            writeln!(out, "| {} |", report.columns.join(" | "))?;
+            writeln!(out, "|{}", " --- |".repeat(report.columns.len()))?;
+            for row in &report.rows {
+                let cells: Vec<String> = row.iter().map(|v| v.as_deref().unwrap_or(&not_available).replace('|', "\\|")).collect();
+                writeln!(out, "| {} |", cells.join(" | "))?;
+            }
+        }
+    }
+    Ok(String::from_utf8(out)?)
+}
+
 // Helper to enhance session data with M1.4 fields
 pub fn enhance_session_data(base_session: serde_json::Value, toolbox_info: Option<HashMap<String, serde_json::Value>>) -> SessionExportData {
     let toolbox_path = base_session.get("toolbox_path")
@@ -187,5 +648,9 @@ pub fn enhance_session_data(base_session: serde_json::Value, toolbox_info: Optio
         output_tokens: None,
         inference_duration_ms: None,
         service_tier: None,
+        rating: base_session.get("rating").and_then(|v| v.as_i64()),
+        feedback_comment: base_session.get("feedback_comment").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        env_hash: base_session.get("env_hash").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        transcript: base_session.get("transcript").and_then(|v| serde_json::from_value(v.clone()).ok()),
     }
 }
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use crate::exporters::{SessionExportData, HtmlExporter, CsvExporter, JsonlExporter, ExportFormat, Exporter, export_sessions_to_string, enhance_session_data};
+    use crate::exporters::{SessionExportData, TranscriptMessage, HtmlExporter, CsvExporter, JsonlExporter, MarkdownExporter, ExportFormat, Exporter, ExportHeader, ExportSnapshot, EXPORT_SCHEMA_VERSION, RedactionProfile, export_sessions_to_string, export_snapshot_to_string, enhance_session_data};
+    use crate::schema::test_pool;
 
     fn create_test_sessions() -> Vec<SessionExportData> {
         vec![
@@ -19,6 +20,10 @@ mod tests {
                 output_tokens: Some(2300),
                 inference_duration_ms: Some(1200),
                 service_tier: Some("premium".to_string()),
+                rating: Some(4),
+                feedback_comment: Some("Fixed the bug first try".to_string()),
+                env_hash: Some("a1b2c3".to_string()),
+                transcript: None,
             },
             SessionExportData {
                 id: "session2".to_string(),
@@ -35,6 +40,10 @@ mod tests {
                 output_tokens: Some(1200),
                 inference_duration_ms: Some(950),
                 service_tier: None,
+                rating: None,
+                feedback_comment: None,
+                env_hash: None,
+                transcript: None,
             },
         ]
     }
@@ -43,7 +52,7 @@ mod tests {
     fn test_html_exporter() {
         let sessions = create_test_sessions();
         let mut buffer = Vec::new();
-        let mut exporter = HtmlExporter;
+        let mut exporter = HtmlExporter::default();
         
         let result = exporter.export_sessions(&sessions, &mut buffer);
         assert!(result.is_ok(), "HTML export should succeed");
@@ -68,7 +77,7 @@ mod tests {
     fn test_csv_exporter() {
         let sessions = create_test_sessions();
         let mut buffer = Vec::new();
-        let mut exporter = CsvExporter;
+        let mut exporter = CsvExporter::default();
         
         let result = exporter.export_sessions(&sessions, &mut buffer);
         assert!(result.is_ok(), "CSV export should succeed");
@@ -83,6 +92,8 @@ mod tests {
         assert!(lines[2].contains("session2"));
         assert!(csv_output.contains("geppetto:main"));
         assert!(csv_output.contains("/usr/local/bin:/home/user/tools"));
+        assert!(lines[0].contains("rating,feedback_comment,env_hash"));
+        assert!(lines[1].contains("4,\"Fixed the bug first try\",a1b2c3"));
         
         println!("CSV Export Preview:\n{}", csv_output);
     }
@@ -91,7 +102,7 @@ mod tests {
     fn test_jsonl_exporter() {
         let sessions = create_test_sessions();
         let mut buffer = Vec::new();
-        let mut exporter = JsonlExporter;
+        let mut exporter = JsonlExporter::default();
         
         let result = exporter.export_sessions(&sessions, &mut buffer);
         assert!(result.is_ok(), "JSONL export should succeed");
@@ -111,6 +122,51 @@ mod tests {
         println!("JSONL Export Preview:\n{}", jsonl_output);
     }
 
+    #[test]
+    fn test_markdown_exporter() {
+        let message = |role: &str, content: serde_json::Value, tokens: Option<u64>| TranscriptMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: "2024-01-15T10:00:00Z".to_string(),
+            input_tokens: tokens,
+            output_tokens: tokens.map(|t| t * 2),
+            cost_usd: tokens.map(|_| 0.01),
+            ..Default::default()
+        };
+        let mut sessions = create_test_sessions();
+        sessions[0].transcript = Some(vec![
+            message("user", serde_json::json!({ "type": "user", "message": { "content": "Fix the login bug" } }), None),
+            message("assistant", serde_json::json!({ "type": "assistant", "message": { "content": [
+                { "type": "text", "text": "Looking at ```auth.rs```" },
+                { "type": "tool_use", "name": "edit_file", "input": { "path": "auth.rs" } }
+            ] } }), Some(100)),
+            message("user", serde_json::json!({ "type": "user", "message": { "content": [{ "type": "tool_result", "content": "ok" }] } }), None),
+            message("assistant", serde_json::json!({ "type": "result", "subtype": "success" }), Some(50)),
+        ]);
+
+        let mut buffer = Vec::new();
+        MarkdownExporter::default().export_sessions(&sessions, &mut buffer).unwrap();
+        let markdown = String::from_utf8(buffer).unwrap();
+        assert!(markdown.starts_with("# Amp Session Export\n"));
+        assert!(markdown.contains("## Test Session 1\n"));
+        assert!(markdown.contains("- **Agent Mode:** geppetto:main"));
+        assert!(markdown.contains("- **Messages:** 4\n- **Turns:** 1\n- **Input Tokens:** 150\n- **Output Tokens:** 300"));
+        assert!(markdown.contains("- **Cost (USD):** 0.0200"));
+        assert!(markdown.contains("#### User · 2024-01-15T10:00:00Z\n\nFix the login bug"));
+        assert!(markdown.contains("**Tool call: edit_file**\n\n```json\n{\n  \"path\": \"auth.rs\"\n}\n```"));
+        assert!(markdown.contains("<summary>Tool result</summary>\n\n```\nok\n```"));
+        assert_eq!(markdown.matches("#### Assistant").count(), 1, "Events without message content are left out");
+        // No transcript loaded: metrics fall back to the session's own
+        assert!(markdown.contains("## Dev Session\n"));
+        assert!(markdown.contains("- **Input Tokens:** 800"));
+
+        let mut buffer = Vec::new();
+        MarkdownExporter { redaction: RedactionProfile::Public }.export_sessions(&sessions, &mut buffer).unwrap();
+        let public = String::from_utf8(buffer).unwrap();
+        assert!(public.contains("## session1\n"));
+        assert!(!public.contains("Fix the login bug") && !public.contains("Test Session 1") && !public.contains("Input Tokens"));
+    }
+
     #[test]
     fn test_export_formats() {
         let sessions = create_test_sessions();
@@ -124,6 +180,75 @@ mod tests {
         
         let jsonl_result = export_sessions_to_string(&sessions, ExportFormat::Jsonl);
         assert!(jsonl_result.is_ok());
+
+        let markdown_result = export_sessions_to_string(&sessions, ExportFormat::Markdown);
+        assert!(markdown_result.is_ok());
+    }
+
+    #[test]
+    fn test_snapshot_header_in_each_format() {
+        let snapshot = ExportSnapshot {
+            header: ExportHeader {
+                schema_version: EXPORT_SCHEMA_VERSION,
+                generated_at: "2024-01-15T13:00:00.000Z".to_string(),
+                session_count: 2,
+                last_updated_at: Some("2024-01-15T12:15:00Z".to_string()),
+                redaction: RedactionProfile::Full,
+            },
+            sessions: create_test_sessions(),
+        };
+
+        let jsonl = export_snapshot_to_string(&snapshot, ExportFormat::Jsonl).unwrap();
+        let lines: Vec<&str> = jsonl.lines().collect();
+        assert_eq!(lines.len(), 3, "Header line + 2 sessions");
+        let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(header["export_header"]["schema_version"], EXPORT_SCHEMA_VERSION);
+        assert_eq!(header["export_header"]["generated_at"], "2024-01-15T13:00:00.000Z");
+        assert_eq!(header["export_header"]["session_count"], 2);
+
+        let csv = export_snapshot_to_string(&snapshot, ExportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].starts_with("# schema_version=1 generated_at=2024-01-15T13:00:00.000Z session_count=2"));
+        assert!(lines[1].starts_with("id,context,title"));
+
+        let html = export_snapshot_to_string(&snapshot, ExportFormat::Html).unwrap();
+        assert!(html.contains("class=\"export-meta\""));
+        assert!(html.contains("Generated 2024-01-15T13:00:00.000Z"));
+    }
+
+    #[test]
+    fn test_redaction_profiles() {
+        let snapshot = |redaction| ExportSnapshot {
+            header: ExportHeader {
+                schema_version: EXPORT_SCHEMA_VERSION,
+                generated_at: "2024-01-15T13:00:00.000Z".to_string(),
+                session_count: 2,
+                last_updated_at: None,
+                redaction,
+            },
+            sessions: create_test_sessions(),
+        };
+
+        let public = snapshot(RedactionProfile::Public);
+        let csv = export_snapshot_to_string(&public, ExportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].ends_with("redaction=public"));
+        assert_eq!(lines[1], "id,context,agent_mode,tools_available_count,tools_used,inference_duration_ms,rating,env_hash,created_at,updated_at");
+        assert!(!csv.contains("Test Session 1") && !csv.contains("/usr/local/bin") && !csv.contains("Fixed the bug"));
+        let html = export_snapshot_to_string(&public, ExportFormat::Html).unwrap();
+        assert!(html.contains("data-redaction=\"public\""));
+        assert!(!html.contains("Test Session 1") && !html.contains("/usr/local/bin") && !html.contains("2300"));
+
+        let internal = export_snapshot_to_string(&snapshot(RedactionProfile::Internal), ExportFormat::Jsonl).unwrap();
+        let row: serde_json::Value = serde_json::from_str(internal.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(row["title"], "Test Session 1");
+        assert_eq!(row["toolbox_path"], "/usr/local/bin:/home/user/tools");
+        for hidden in ["input_tokens", "output_tokens", "service_tier"] {
+            assert!(row.get(hidden).is_none(), "{} should be left out", hidden);
+        }
+
+        let full = export_snapshot_to_string(&snapshot(RedactionProfile::Full), ExportFormat::Jsonl).unwrap();
+        assert!(full.contains("\"input_tokens\":1500") && full.contains("\"redaction\":\"full\""));
     }
 
     #[test]
@@ -133,6 +258,8 @@ mod tests {
             "context": "production",
             "title": "Test",
             "toolbox_path": "/usr/local/bin",
+            "rating": 2,
+            "env_hash": "a1b2c3",
             "created_at": "2024-01-15T10:00:00Z",
             "updated_at": "2024-01-15T11:00:00Z"
         });
@@ -148,5 +275,90 @@ mod tests {
         assert_eq!(enhanced.toolbox_path, Some("/usr/local/bin".to_string()));
         assert_eq!(enhanced.tools_available_count, Some(5));
         assert_eq!(enhanced.tools_used, Some(vec!["grep".to_string(), "awk".to_string()]));
+        assert_eq!(enhanced.rating, Some(2));
+        assert_eq!(enhanced.feedback_comment, None);
+        assert_eq!(enhanced.env_hash, Some("a1b2c3".to_string()));
+    }
+
+    async fn sessions_db(count: usize) -> sqlx::SqlitePool {
+        let pool = test_pool().await;
+        for i in 0..count {
+            // Pairs share an updated_at so paging has to break ties on the id
+            sqlx::query("INSERT INTO chat_sessions (id, context, title, updated_at) VALUES (?, 'production', ?, ?)")
+                .bind(format!("s{}", i))
+                .bind(format!("Session {}", i))
+                .bind(format!("2024-01-15T10:{:02}:00Z", i / 2))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO thread_feedback (thread_id, rating, env_hash) VALUES ('s3', 5, 'abc')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO threads (id, session_id, context) VALUES ('t3', 's3', 'production');
+             INSERT INTO messages (id, thread_id, role, content, created_at) VALUES ('m1', 't3', 'user', 'Rename the crate', '2024-01-15T10:01:00Z');
+             INSERT INTO messages (id, thread_id, role, content, created_at, output_tokens, interrupted_at)
+                 VALUES ('m2', 't3', 'assistant', 'Renamed it', '2024-01-15T10:01:30Z', 42, '2024-01-15T10:01:31Z');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_streamed_export_matches_snapshot() {
+        use crate::exporters::export_commands::{export_to_writer, load_export_snapshot, ExportProgress};
+
+        let pool = sessions_db(7).await;
+        for format in [ExportFormat::Jsonl, ExportFormat::Html, ExportFormat::Markdown] {
+            let snapshot = load_export_snapshot(&pool, &format, RedactionProfile::Full).await.unwrap();
+            let mut out = Vec::new();
+            let mut reports: Vec<ExportProgress> = Vec::new();
+            let progress = export_to_writer(&pool, format.clone(), RedactionProfile::Full, 3, &mut out, |p| reports.push(p)).await.unwrap();
+            let streamed = String::from_utf8(out).unwrap();
+            let whole = export_snapshot_to_string(&snapshot, format.clone()).unwrap();
+
+            // Only the generation time differs
+            let strip = |doc: &str| doc.lines().filter(|l| !l.to_lowercase().contains("generated")).map(String::from).collect::<Vec<_>>();
+            assert_eq!(strip(&streamed), strip(&whole));
+            assert_eq!(progress.sessions_written, 7);
+            assert_eq!(progress.bytes_written, streamed.len() as u64);
+            let written: Vec<usize> = reports.iter().map(|p| p.sessions_written).collect();
+            assert_eq!(written, vec![0, 3, 6, 7]);
+        }
+        let snapshot = load_export_snapshot(&pool, &ExportFormat::Markdown, RedactionProfile::Full).await.unwrap();
+        let transcript = snapshot.sessions.iter().find(|s| s.id == "s3").unwrap().transcript.clone().unwrap();
+        assert_eq!(transcript.iter().map(|m| m.role.as_str()).collect::<Vec<_>>(), ["user", "assistant"]);
+        assert!(transcript[1].interrupted && transcript[1].output_tokens == Some(42));
+        let markdown = export_snapshot_to_string(&snapshot, ExportFormat::Markdown).unwrap();
+        assert!(markdown.contains("#### Assistant · 2024-01-15T10:01:30Z (interrupted)\n\nRenamed it"));
+
+        let snapshot = load_export_snapshot(&pool, &ExportFormat::Jsonl, RedactionProfile::Full).await.unwrap();
+        assert!(snapshot.sessions.iter().all(|s| s.transcript.is_none()));
+        assert_eq!(snapshot.sessions[0].id, "s6");
+        assert_eq!(snapshot.sessions.iter().find(|s| s.id == "s3").unwrap().rating, Some(5));
+    }
+
+    #[test]
+    fn test_report_exports_in_every_format() {
+        use crate::exporters::{export_report_to_string, ReportTable};
+        let report = ReportTable {
+            title: "Versions".to_string(),
+            generated_at: "2026-01-01T00:00:00Z".to_string(),
+            columns: vec!["cli_version", "note"],
+            rows: vec![vec![Some("0.1.0".to_string()), Some("a, \"b\" | c".to_string())], vec![None, None]],
+        };
+        let csv = export_report_to_string(&report, ExportFormat::Csv).unwrap();
+        assert_eq!(csv.lines().skip(1).collect::<Vec<_>>(), ["cli_version,note", "0.1.0,\"a, \"\"b\"\" | c\"", ","]);
+        let jsonl = export_report_to_string(&report, ExportFormat::Jsonl).unwrap();
+        let rows: Vec<serde_json::Value> = jsonl.lines().skip(1).map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(rows[1], serde_json::json!({ "cli_version": null, "note": null }));
+        let markdown = export_report_to_string(&report, ExportFormat::Markdown).unwrap();
+        assert!(markdown.contains("| cli_version | note |\n| --- | --- |\n| 0.1.0 | a, \"b\" \\| c |"));
+        let html = export_report_to_string(&report, ExportFormat::Html).unwrap();
+        assert!(html.contains("<tr><th>cli_version</th><th>note</th></tr>") && html.contains("<td>N/A</td>"));
     }
 }
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::exporters::importers::{parse_import, ImportError, ImportStore, ImportStrategy};
+    use crate::exporters::{ExportFormat, ExportHeader, ExportSnapshot, SessionExportData, EXPORT_SCHEMA_VERSION, export_snapshot_to_string};
+    use crate::schema::test_pool;
+
+    fn session(id: &str, title: &str) -> SessionExportData {
+        SessionExportData {
+            id: id.to_string(),
+            context: "production".to_string(),
+            title: Some(title.to_string()),
+            last_snippet: None,
+            agent_mode: Some("default".to_string()),
+            toolbox_path: None,
+            tools_available_count: None,
+            tools_used: None,
+            created_at: "2024-01-15T10:00:00Z".to_string(),
+            updated_at: "2024-01-15T11:00:00Z".to_string(),
+            input_tokens: None,
+            output_tokens: None,
+            inference_duration_ms: None,
+            service_tier: None,
+            rating: Some(4),
+            feedback_comment: Some("Quoted \"title\", with comma".to_string()),
+            env_hash: Some("a1b2c3".to_string()),
+            transcript: None,
+        }
+    }
+
+    fn snapshot(sessions: Vec<SessionExportData>) -> ExportSnapshot {
+        ExportSnapshot {
+            header: ExportHeader {
+                schema_version: EXPORT_SCHEMA_VERSION,
+                generated_at: "2024-01-15T12:00:00.000Z".to_string(),
+                session_count: sessions.len(),
+                last_updated_at: Some("2024-01-15T11:00:00Z".to_string()),
+                redaction: Default::default(),
+            },
+            sessions,
+        }
+    }
+
+    #[test]
+    fn test_round_trips_exports() {
+        let snapshot = snapshot(vec![session("s1", "First"), session("s2", "Second")]);
+
+        for format in [ExportFormat::Jsonl, ExportFormat::Csv] {
+            let data = export_snapshot_to_string(&snapshot, format.clone()).unwrap();
+            let parsed = parse_import(&data, format.clone()).unwrap();
+            assert_eq!(parsed.schema_version, Some(EXPORT_SCHEMA_VERSION), "{:?}", format);
+            assert!(parsed.errors.is_empty(), "{:?}: {:?}", format, parsed.errors);
+            let ids: Vec<&str> = parsed.sessions.iter().map(|(_, s)| s.id.as_str()).collect();
+            assert_eq!(ids, vec!["s1", "s2"]);
+            assert_eq!(parsed.sessions[0].1.title.as_deref(), Some("First"));
+            assert_eq!(parsed.sessions[0].1.rating, Some(4));
+            assert_eq!(parsed.sessions[0].1.created_at, "2024-01-15T10:00:00Z");
+        }
+    }
+
+    #[test]
+    fn test_schema_version_and_bad_rows() {
+        let newer = format!("{{\"export_header\":{{\"schema_version\":{},\"generated_at\":\"x\",\"session_count\":0,\"last_updated_at\":null}}}}\n", EXPORT_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            parse_import(&newer, ExportFormat::Jsonl),
+            Err(ImportError::UnsupportedSchemaVersion { .. })
+        ));
+        assert!(matches!(
+            parse_import("# schema_version=99\nid,context\n", ExportFormat::Csv),
+            Err(ImportError::UnsupportedSchemaVersion { found: 99, .. })
+        ));
+
+        // Exports from before the header existed still load
+        let legacy = "id,context,title,rating,created_at,updated_at\ns1,development,\"Old\",,2023-01-01,2023-01-02\n,production,\"No id\",,,\ns3,production,\"Bad rating\",7,,\n";
+        let parsed = parse_import(legacy, ExportFormat::Csv).unwrap();
+        assert_eq!(parsed.schema_version, None);
+        assert_eq!(parsed.sessions.len(), 1);
+        assert_eq!(parsed.sessions[0].1.agent_mode, None);
+        assert_eq!(parsed.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![2, 3]);
+
+        let parsed = parse_import("{\"id\":\"a\",\"context\":\"production\",\"created_at\":\"\",\"updated_at\":\"\",\"rating\":9}\nnot json\n", ExportFormat::Jsonl).unwrap();
+        assert!(parsed.sessions.is_empty());
+        assert_eq!(parsed.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_conflict_strategies() {
+        let db = test_pool().await;
+        let store = ImportStore::new(db.clone());
+        let data = export_snapshot_to_string(&snapshot(vec![session("s1", "Imported")]), ExportFormat::Jsonl).unwrap();
+        let parsed = || parse_import(&data, ExportFormat::Jsonl).unwrap();
+
+        let first = store.import(parsed(), ImportStrategy::Skip).await.unwrap();
+        assert_eq!((first.inserted, first.feedback_imported), (1, 1));
+        let created: String = sqlx::query_scalar("SELECT created_at FROM chat_sessions WHERE id = 's1'")
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(created, "2024-01-15T10:00:00Z");
+
+        sqlx::query("UPDATE chat_sessions SET title = 'Local edit' WHERE id = 's1'").execute(&db).await.unwrap();
+        let skipped = store.import(parsed(), ImportStrategy::Skip).await.unwrap();
+        assert_eq!((skipped.inserted, skipped.skipped), (0, 1));
+
+        let overwritten = store.import(parsed(), ImportStrategy::Overwrite).await.unwrap();
+        assert_eq!(overwritten.overwritten, 1);
+        // Same feedback is not imported twice
+        assert_eq!(overwritten.feedback_imported, 0);
+        let title: String = sqlx::query_scalar("SELECT title FROM chat_sessions WHERE id = 's1'")
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(title, "Imported");
+
+        let duplicated = store.import(parsed(), ImportStrategy::DuplicateAsNew).await.unwrap();
+        assert_eq!((duplicated.duplicated, duplicated.feedback_imported), (1, 1));
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM chat_sessions WHERE title = 'Imported'")
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! Runtime feature flags
+//!
+//! Cargo features decide what is compiled in; these flags decide whether a
+//! compiled-in subsystem is used. Overrides are kept in the app config, so
+//! experimental subsystems can be switched per user without a rebuild. A flag
+//! whose code was not compiled in reads as disabled and cannot be enabled.
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::app_state::{AppConfig, AppState};
+use crate::i18n::tr;
+
+pub const WORKTREE_MANAGER: &str = "worktree_manager";
+pub const ENHANCED_SESSIONS: &str = "enhanced_sessions";
+pub const BATCH_PROCESSING: &str = "batch_processing";
+pub const ARCHIVE_COMPRESSION: &str = "archive_compression";
+
+struct FlagSpec {
+    name: &'static str,
+    description: &'static str,
+    default: bool,
+    compiled: bool,
+}
+
+const FLAGS: &[FlagSpec] = &[
+    FlagSpec {
+        name: WORKTREE_MANAGER,
+        description: "Create a managed worktree for each new thread",
+        default: true,
+        compiled: cfg!(feature = "worktree-manager"),
+    },
+    FlagSpec {
+        name: ENHANCED_SESSIONS,
+        description: "Enhanced session commands backed by the unified core",
+        default: false,
+        compiled: cfg!(feature = "worktree-manager"),
+    },
+    FlagSpec {
+        name: BATCH_PROCESSING,
+        description: "Run prompts across repositories as batches",
+        default: true,
+        compiled: true,
+    },
+    FlagSpec {
+        name: ARCHIVE_COMPRESSION,
+        description: "Compress the message history of archived threads in the background",
+        default: true,
+        compiled: true,
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    pub default: bool,
+    /// Whether the subsystem is part of this build
+    pub available: bool,
+}
+
+fn spec(name: &str) -> Option<&'static FlagSpec> {
+    FLAGS.iter().find(|spec| spec.name == name)
+}
+
+/// Whether a flag is on under `config`; unknown flags are off
+pub fn is_enabled(config: &AppConfig, name: &str) -> bool {
+    spec(name).is_some_and(|spec| spec.compiled && config.feature_flags.get(name).copied().unwrap_or(spec.default))
+}
+
+/// Error for command handlers when a flag is off
+pub fn require(app_state: &AppState, name: &str) -> Result<(), String> {
+    if is_enabled(&app_state.lock().unwrap(), name) {
+        Ok(())
+    } else {
+        Err(tr!("error-feature-disabled", name = name))
+    }
+}
+
+pub fn feature_flags(config: &AppConfig) -> Vec<FeatureFlag> {
+    FLAGS
+        .iter()
+        .map(|spec| FeatureFlag {
+            name: spec.name.to_string(),
+            description: spec.description.to_string(),
+            enabled: is_enabled(config, spec.name),
+            default: spec.default,
+            available: spec.compiled,
+        })
+        .collect()
+}
+
+/// Record an override, dropping it when it matches the default
+pub fn set_flag(config: &mut AppConfig, name: &str, enabled: bool) -> Result<(), String> {
+    let spec = spec(name).ok_or_else(|| format!("Unknown feature flag '{}'", name))?;
+    if enabled && !spec.compiled {
+        return Err(format!("Feature '{}' is not available in this build", name));
+    }
+    if enabled == spec.default {
+        config.feature_flags.remove(name);
+    } else {
+        config.feature_flags.insert(name.to_string(), enabled);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_feature_flags(app_state: State<'_, AppState>) -> Result<Vec<FeatureFlag>, String> {
+    Ok(feature_flags(&app_state.lock().unwrap()))
+}
+
+#[tauri::command]
+pub async fn set_feature_flag(
+    name: String,
+    enabled: bool,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<FeatureFlag>, String> {
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        set_flag(&mut state, &name, enabled)?;
+        state.clone()
+    };
+    to_save.save().await?;
+    Ok(feature_flags(&to_save))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_and_unknown_flags_are_rejected() {
+        let mut config = AppConfig::default();
+        assert!(is_enabled(&config, BATCH_PROCESSING));
+
+        set_flag(&mut config, BATCH_PROCESSING, false).unwrap();
+        assert!(!is_enabled(&config, BATCH_PROCESSING));
+        set_flag(&mut config, BATCH_PROCESSING, true).unwrap();
+        assert!(is_enabled(&config, BATCH_PROCESSING));
+        // Setting a flag back to its default leaves nothing in the config
+        assert!(config.feature_flags.is_empty());
+
+        assert!(set_flag(&mut config, "no_such_flag", true).is_err());
+        assert!(!is_enabled(&config, "no_such_flag"));
+        if !cfg!(feature = "worktree-manager") {
+            assert!(set_flag(&mut config, ENHANCED_SESSIONS, true).is_err());
+            assert!(!is_enabled(&config, WORKTREE_MANAGER));
+        }
+    }
+}
@@ -0,0 +1,726 @@
+//! Advisory file locks for worktrees shared between the user and the agent
+//!
+//! When the stream shows the agent starting a file write (an `edit_file` or
+//! `create_file` style tool call), that file is locked to the session. The
+//! directories holding locked files are watched, and every change is compared
+//! with the content the agent last wrote. A mismatch means the user edited the
+//! file underneath the agent: a `file_conflict` event is emitted listing the
+//! available resolutions (keep the user's version, restore the agent's, or a
+//! three-way merge against the content from before the agent touched it).
+
+use chrono::{DateTime, Utc};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::path_scope::{read_scope, report_violation, PathScope};
+
+/// Tool names whose calls write to the file named in their input
+pub const WRITE_TOOLS: &[&str] = &["edit_file", "create_file", "undo_edit", "format_file", "Write", "Edit", "MultiEdit"];
+pub const PATH_KEYS: &[&str] = &["path", "file_path"];
+
+/// Locks without agent activity for this long are released
+pub const LOCK_IDLE_SECS: i64 = 300;
+/// Files larger than this are locked but not snapshotted, so only "keep user" is offered
+const MAX_SNAPSHOT_BYTES: u64 = 1024 * 1024;
+
+/// A file write announced by a tool call in the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteIntent {
+    pub tool_use_id: String,
+    pub path: String,
+}
+
+/// Write tool calls in an assistant event
+pub fn write_intents(event: &Value) -> Vec<WriteIntent> {
+    if event.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+        return Vec::new();
+    }
+    let Some(content) = event.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+
+    content
+        .iter()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        .filter(|part| part.get("name").and_then(|n| n.as_str()).is_some_and(|n| WRITE_TOOLS.contains(&n)))
+        .filter_map(|part| {
+            let input = part.get("input")?;
+            let path = PATH_KEYS.iter().find_map(|k| input.get(*k).and_then(|p| p.as_str()))?;
+            Some(WriteIntent {
+                tool_use_id: part.get("id").and_then(|i| i.as_str()).unwrap_or_default().to_string(),
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Ids of tool calls whose results arrived in a user event
+pub fn completed_tool_ids(event: &Value) -> Vec<String> {
+    if event.get("type").and_then(|t| t.as_str()) != Some("user") {
+        return Vec::new();
+    }
+    event
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .map(|content| {
+            content
+                .iter()
+                .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+                .filter_map(|part| part.get("tool_use_id").and_then(|i| i.as_str()).map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Keep the file as the user left it
+    KeepUser,
+    /// Restore the content the agent last wrote
+    KeepAgent,
+    /// Three-way merge of both versions against the pre-agent content
+    Merge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileConflict {
+    pub id: String,
+    pub session_id: String,
+    pub path: String,
+    pub detected_at: String,
+    pub options: Vec<ConflictResolution>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLockInfo {
+    pub path: String,
+    pub session_id: String,
+    pub locked_at: String,
+    pub last_write_at: String,
+    /// The agent has started a write whose result has not arrived yet
+    pub pending: bool,
+    pub conflict_id: Option<String>,
+}
+
+struct FileLock {
+    session_id: String,
+    locked_at: DateTime<Utc>,
+    last_write_at: DateTime<Utc>,
+    pending_tool_id: Option<String>,
+    /// Content before the agent's first write under this lock (`Some(empty)` for new files)
+    base: Option<Vec<u8>>,
+    agent_content: Option<Vec<u8>>,
+    agent_hash: Option<blake3::Hash>,
+    conflict_id: Option<String>,
+}
+
+impl FileLock {
+    fn info(&self, path: &Path) -> FileLockInfo {
+        FileLockInfo {
+            path: path.to_string_lossy().to_string(),
+            session_id: self.session_id.clone(),
+            locked_at: self.locked_at.to_rfc3339(),
+            last_write_at: self.last_write_at.to_rfc3339(),
+            pending: self.pending_tool_id.is_some(),
+            conflict_id: self.conflict_id.clone(),
+        }
+    }
+}
+
+/// Read a file for snapshotting. Missing files read as empty, oversized ones as `None`.
+fn read_snapshot(path: &Path) -> Option<Vec<u8>> {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.len() > MAX_SNAPSHOT_BYTES => None,
+        Ok(_) => std::fs::read(path).ok(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Some(Vec::new()),
+        Err(_) => None,
+    }
+}
+
+/// Canonical form of a path that may not exist yet, so watcher paths and tool paths compare equal
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent().and_then(|p| p.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Lock bookkeeping, kept free of Tauri so it can be exercised directly
+#[derive(Default)]
+pub struct LockTable {
+    roots: HashMap<String, PathBuf>,
+    scopes: HashMap<String, PathScope>,
+    locks: HashMap<PathBuf, FileLock>,
+    conflicts: HashMap<String, FileConflict>,
+}
+
+impl LockTable {
+    /// Directory relative tool paths of a session resolve against
+    pub fn set_root(&mut self, session_id: &str, root: PathBuf) {
+        self.roots.insert(session_id.to_string(), root);
+    }
+
+    pub fn root(&self, session_id: &str) -> Option<PathBuf> {
+        self.roots.get(session_id).cloned()
+    }
+
+    /// Directories within the root a session is expected to write to
+    pub fn set_scope(&mut self, session_id: &str, scope: PathScope) {
+        self.scopes.insert(session_id.to_string(), scope);
+    }
+
+    /// The session's scope when `path` lies outside it
+    pub fn scope_violated(&self, session_id: &str, path: &Path) -> Option<&PathScope> {
+        let scope = self.scopes.get(session_id)?;
+        let root = self.roots.get(session_id)?;
+        let canonical_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+        let inside = scope.contains_under(root, path) || scope.contains_under(&canonical_root, path);
+        (!inside).then_some(scope)
+    }
+
+    fn resolve_path(&self, session_id: &str, raw: &str) -> PathBuf {
+        let path = Path::new(raw);
+        if path.is_absolute() {
+            return normalize(path);
+        }
+        let root = self
+            .roots
+            .get(session_id)
+            .cloned()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        normalize(&root.join(path))
+    }
+
+    /// Lock a file the agent is about to write. Returns the normalized path.
+    pub fn begin_write(&mut self, session_id: &str, raw_path: &str, tool_use_id: &str, now: DateTime<Utc>) -> PathBuf {
+        let path = self.resolve_path(session_id, raw_path);
+        let lock = self.locks.entry(path.clone()).or_insert_with(|| FileLock {
+            session_id: session_id.to_string(),
+            locked_at: now,
+            last_write_at: now,
+            pending_tool_id: None,
+            base: read_snapshot(&path),
+            agent_content: None,
+            agent_hash: None,
+            conflict_id: None,
+        });
+        if lock.session_id != session_id {
+            log::warn!("{} locked by session {} is now being written by {}", path.display(), lock.session_id, session_id);
+            lock.session_id = session_id.to_string();
+        }
+        lock.pending_tool_id = Some(tool_use_id.to_string());
+        lock.last_write_at = now;
+        path
+    }
+
+    /// Record the file content once the agent's write has completed
+    pub fn finish_write(&mut self, tool_use_id: &str, now: DateTime<Utc>) {
+        for (path, lock) in self.locks.iter_mut() {
+            if lock.pending_tool_id.as_deref() == Some(tool_use_id) {
+                lock.pending_tool_id = None;
+                lock.last_write_at = now;
+                lock.agent_content = read_snapshot(path);
+                lock.agent_hash = lock.agent_content.as_deref().map(blake3::hash);
+            }
+        }
+    }
+
+    /// Check a filesystem change against the lock on that path. Returns a new conflict
+    /// when the file no longer holds what the agent wrote.
+    pub fn on_change(&mut self, path: &Path, now: DateTime<Utc>) -> Option<FileConflict> {
+        let path = normalize(path);
+        let lock = self.locks.get_mut(&path)?;
+        if lock.pending_tool_id.is_some() || lock.conflict_id.is_some() {
+            return None;
+        }
+        let agent_hash = lock.agent_hash?;
+        let current = read_snapshot(&path);
+        if current.as_deref().map(blake3::hash) == Some(agent_hash) {
+            return None;
+        }
+
+        let mut options = vec![ConflictResolution::KeepUser];
+        if lock.agent_content.is_some() {
+            options.push(ConflictResolution::KeepAgent);
+            if lock.base.is_some() && current.is_some() {
+                options.push(ConflictResolution::Merge);
+            }
+        }
+
+        let conflict = FileConflict {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: lock.session_id.clone(),
+            path: path.to_string_lossy().to_string(),
+            detected_at: now.to_rfc3339(),
+            options,
+        };
+        lock.conflict_id = Some(conflict.id.clone());
+        self.conflicts.insert(conflict.id.clone(), conflict.clone());
+        Some(conflict)
+    }
+
+    /// Drop locks idle for longer than `LOCK_IDLE_SECS`. Pending and conflicted locks are kept.
+    pub fn release_expired(&mut self, now: DateTime<Utc>) -> Vec<PathBuf> {
+        let expired: Vec<PathBuf> = self
+            .locks
+            .iter()
+            .filter(|(_, l)| l.pending_tool_id.is_none() && l.conflict_id.is_none())
+            .filter(|(_, l)| (now - l.last_write_at).num_seconds() > LOCK_IDLE_SECS)
+            .map(|(p, _)| p.clone())
+            .collect();
+        for path in &expired {
+            self.locks.remove(path);
+        }
+        expired
+    }
+
+    /// Drop a finished session's locks. Locks with an open conflict stay until it is resolved.
+    pub fn release_session(&mut self, session_id: &str) -> Vec<PathBuf> {
+        self.roots.remove(session_id);
+        self.scopes.remove(session_id);
+        let released: Vec<PathBuf> = self
+            .locks
+            .iter()
+            .filter(|(_, l)| l.session_id == session_id && l.conflict_id.is_none())
+            .map(|(p, _)| p.clone())
+            .collect();
+        for path in &released {
+            self.locks.remove(path);
+        }
+        released
+    }
+
+    pub fn locks(&self, session_id: Option<&str>) -> Vec<FileLockInfo> {
+        let mut locks: Vec<FileLockInfo> = self
+            .locks
+            .iter()
+            .filter(|(_, l)| session_id.is_none_or(|s| l.session_id == s))
+            .map(|(p, l)| l.info(p))
+            .collect();
+        locks.sort_by(|a, b| a.path.cmp(&b.path));
+        locks
+    }
+
+    pub fn lock_for(&self, path: &Path) -> Option<FileLockInfo> {
+        let path = normalize(path);
+        self.locks.get(&path).map(|l| l.info(&path))
+    }
+
+    pub fn conflicts(&self) -> Vec<FileConflict> {
+        let mut conflicts: Vec<FileConflict> = self.conflicts.values().cloned().collect();
+        conflicts.sort_by(|a, b| a.detected_at.cmp(&b.detected_at));
+        conflicts
+    }
+
+    /// Apply a resolution to an open conflict and release the file's lock
+    pub fn resolve(&mut self, conflict_id: &str, resolution: ConflictResolution) -> Result<FileConflict, String> {
+        let conflict = self
+            .conflicts
+            .get(conflict_id)
+            .cloned()
+            .ok_or_else(|| format!("Conflict {} not found", conflict_id))?;
+        if !conflict.options.contains(&resolution) {
+            return Err(format!("Resolution {:?} is not available for {}", resolution, conflict.path));
+        }
+        let path = PathBuf::from(&conflict.path);
+        let lock = self.locks.get(&path).ok_or_else(|| format!("No lock held on {}", conflict.path))?;
+
+        match resolution {
+            ConflictResolution::KeepUser => {}
+            ConflictResolution::KeepAgent => {
+                let content = lock.agent_content.as_deref().unwrap_or_default();
+                std::fs::write(&path, content).map_err(|e| format!("Failed to restore {}: {}", conflict.path, e))?;
+            }
+            ConflictResolution::Merge => {
+                let user = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", conflict.path, e))?;
+                let merged = merge_file(
+                    &user,
+                    lock.base.as_deref().unwrap_or_default(),
+                    lock.agent_content.as_deref().unwrap_or_default(),
+                )?;
+                std::fs::write(&path, merged).map_err(|e| format!("Failed to write {}: {}", conflict.path, e))?;
+            }
+        }
+
+        self.locks.remove(&path);
+        self.conflicts.remove(conflict_id);
+        Ok(conflict)
+    }
+}
+
+/// Three-way merge with `git merge-file`. Overlapping hunks are left as conflict markers.
+fn merge_file(user: &[u8], base: &[u8], agent: &[u8]) -> Result<Vec<u8>, String> {
+    let dir = std::env::temp_dir().join(format!("amp-merge-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create merge directory: {}", e))?;
+
+    let result = (|| {
+        let files = [("user", user), ("base", base), ("agent", agent)];
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).map_err(|e| format!("Failed to write merge input: {}", e))?;
+        }
+        let output = std::process::Command::new("git")
+            .current_dir(&dir)
+            .args(["merge-file", "-p", "-L", "user", "-L", "base", "-L", "agent", "user", "base", "agent"])
+            .output()
+            .map_err(|e| format!("Failed to run git merge-file: {}", e))?;
+        // Exit codes up to 127 count conflicting hunks; errors such as binary input
+        // exit 255 with nothing on stdout, which must not replace the user's file
+        match output.status.code() {
+            Some(0..=127) => Ok(output.stdout),
+            _ => Err(format!("git merge-file failed: {}", String::from_utf8_lossy(&output.stderr).trim())),
+        }
+    })();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+#[derive(Default)]
+struct WatchState {
+    watcher: Option<RecommendedWatcher>,
+    dirs: HashSet<PathBuf>,
+}
+
+/// Managed state shared by the stream readers, the watcher callback and the commands
+#[derive(Clone, Default)]
+pub struct FileLockService {
+    table: Arc<Mutex<LockTable>>,
+    watch: Arc<Mutex<WatchState>>,
+}
+
+impl FileLockService {
+    pub fn register_root(&self, session_id: &str, root: PathBuf) {
+        let scope = read_scope(&root);
+        let mut table = self.table.lock().unwrap();
+        if let Some(scope) = scope {
+            table.set_scope(session_id, scope);
+        }
+        table.set_root(session_id, root);
+    }
+
+    pub fn session_root(&self, session_id: &str) -> Option<PathBuf> {
+        self.table.lock().unwrap().root(session_id)
+    }
+
+    /// Update locks from one parsed stream event
+    pub fn observe(&self, app: &AppHandle, session_id: &str, event: &Value) {
+        let intents = write_intents(event);
+        let completed = completed_tool_ids(event);
+        if intents.is_empty() && completed.is_empty() {
+            return;
+        }
+
+        let now = Utc::now();
+        let (locked, expired, violations) = {
+            let mut table = self.table.lock().unwrap();
+            let locked: Vec<PathBuf> = intents
+                .iter()
+                .map(|i| table.begin_write(session_id, &i.path, &i.tool_use_id, now))
+                .collect();
+            for id in &completed {
+                table.finish_write(id, now);
+            }
+            let violations: Vec<(PathBuf, PathScope)> = locked
+                .iter()
+                .filter_map(|path| table.scope_violated(session_id, path).map(|scope| (path.clone(), scope.clone())))
+                .collect();
+            (locked, table.release_expired(now), violations)
+        };
+
+        for (path, scope) in &violations {
+            report_violation(app, session_id, path, scope);
+        }
+
+        for path in &locked {
+            if let Some(dir) = path.parent() {
+                self.watch_dir(app, dir);
+            }
+        }
+        self.unwatch_unused(&expired);
+    }
+
+    pub fn release_session(&self, session_id: &str) {
+        let released = self.table.lock().unwrap().release_session(session_id);
+        self.unwatch_unused(&released);
+    }
+
+    fn watch_dir(&self, app: &AppHandle, dir: &Path) {
+        let mut watch = self.watch.lock().unwrap();
+        if watch.dirs.contains(dir) {
+            return;
+        }
+        if watch.watcher.is_none() {
+            match self.create_watcher(app.clone()) {
+                Ok(watcher) => watch.watcher = Some(watcher),
+                Err(e) => {
+                    log::error!("Failed to start file lock watcher: {}", e);
+                    return;
+                }
+            }
+        }
+        if let Some(watcher) = watch.watcher.as_mut() {
+            match watcher.watch(dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    watch.dirs.insert(dir.to_path_buf());
+                }
+                Err(e) => log::warn!("Failed to watch {}: {}", dir.display(), e),
+            }
+        }
+    }
+
+    /// Stop watching directories that no longer hold a locked file
+    fn unwatch_unused(&self, released: &[PathBuf]) {
+        if released.is_empty() {
+            return;
+        }
+        let still_locked: HashSet<PathBuf> = {
+            let table = self.table.lock().unwrap();
+            table.locks.keys().filter_map(|p| p.parent().map(Path::to_path_buf)).collect()
+        };
+        let mut watch = self.watch.lock().unwrap();
+        for dir in released.iter().filter_map(|p| p.parent()) {
+            if still_locked.contains(dir) || !watch.dirs.remove(dir) {
+                continue;
+            }
+            if let Some(watcher) = watch.watcher.as_mut() {
+                let _ = watcher.unwatch(dir);
+            }
+        }
+    }
+
+    fn create_watcher(&self, app: AppHandle) -> notify::Result<RecommendedWatcher> {
+        let table = self.table.clone();
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("File lock watcher error: {}", e);
+                    return;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                return;
+            }
+            let now = Utc::now();
+            let conflicts: Vec<FileConflict> = {
+                let mut table = table.lock().unwrap();
+                event.paths.iter().filter_map(|p| table.on_change(p, now)).collect()
+            };
+            for conflict in conflicts {
+                log::warn!("User edit to {} collided with session {}", conflict.path, conflict.session_id);
+                let _ = app.emit("file_conflict", &conflict);
+            }
+        })
+    }
+}
+
+/// Register the directory a session's relative tool paths resolve against
+pub fn register_session_root(app: &AppHandle, session_id: &str, root: PathBuf) {
+    if let Some(service) = app.try_state::<FileLockService>() {
+        service.register_root(session_id, root);
+    }
+}
+
+/// Feed a parsed stream event to the lock service
+pub fn observe_stream_event(app: &AppHandle, session_id: &str, event: &Value) {
+    if let Some(service) = app.try_state::<FileLockService>() {
+        service.observe(app, session_id, event);
+    }
+}
+
+/// Release the locks of a session whose stream has ended
+pub fn release_session_locks(app: &AppHandle, session_id: &str) {
+    if let Some(service) = app.try_state::<FileLockService>() {
+        service.release_session(session_id);
+    }
+}
+
+#[tauri::command]
+pub async fn list_file_locks(
+    session_id: Option<String>,
+    file_locks: State<'_, FileLockService>,
+) -> Result<Vec<FileLockInfo>, String> {
+    Ok(file_locks.table.lock().unwrap().locks(session_id.as_deref()))
+}
+
+/// Check whether a file is locked before the user saves it
+#[tauri::command]
+pub async fn check_file_lock(
+    path: String,
+    file_locks: State<'_, FileLockService>,
+) -> Result<Option<FileLockInfo>, String> {
+    Ok(file_locks.table.lock().unwrap().lock_for(Path::new(&path)))
+}
+
+#[tauri::command]
+pub async fn list_file_conflicts(
+    file_locks: State<'_, FileLockService>,
+) -> Result<Vec<FileConflict>, String> {
+    Ok(file_locks.table.lock().unwrap().conflicts())
+}
+
+#[tauri::command]
+pub async fn resolve_file_conflict(
+    conflict_id: String,
+    resolution: ConflictResolution,
+    app_handle: AppHandle,
+    file_locks: State<'_, FileLockService>,
+) -> Result<(), String> {
+    let conflict = file_locks.table.lock().unwrap().resolve(&conflict_id, resolution)?;
+    file_locks.unwatch_unused(&[PathBuf::from(&conflict.path)]);
+
+    let _ = app_handle.emit("file_conflict_resolved", serde_json::json!({
+        "conflict_id": conflict.id,
+        "path": conflict.path,
+        "resolution": resolution,
+    }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn edit_event(id: &str, path: &str) -> Value {
+        json!({
+            "type": "assistant",
+            "message": { "content": [
+                { "type": "text", "text": "Updating the file" },
+                { "type": "tool_use", "id": id, "name": "edit_file", "input": { "path": path, "old_str": "a", "new_str": "b" } },
+                { "type": "tool_use", "id": "read", "name": "read_file", "input": { "path": path } }
+            ]}
+        })
+    }
+
+    #[test]
+    fn test_stream_parsing() {
+        let intents = write_intents(&edit_event("t1", "src/main.rs"));
+        assert_eq!(intents, vec![WriteIntent { tool_use_id: "t1".to_string(), path: "src/main.rs".to_string() }]);
+
+        let result = json!({
+            "type": "user",
+            "message": { "content": [{ "type": "tool_result", "tool_use_id": "t1", "content": "ok" }] }
+        });
+        assert_eq!(completed_tool_ids(&result), vec!["t1".to_string()]);
+        assert!(write_intents(&result).is_empty());
+    }
+
+    #[test]
+    fn test_user_edit_after_agent_write_conflicts() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "one\n").unwrap();
+
+        let mut table = LockTable::default();
+        table.set_root("s1", dir.path().to_path_buf());
+        let now = Utc::now();
+
+        let path = table.begin_write("s1", "notes.txt", "t1", now);
+        std::fs::write(&file, "one\ntwo\n").unwrap();
+        // The agent's own write lands while the tool call is pending
+        assert!(table.on_change(&path, now).is_none());
+        table.finish_write("t1", now);
+        assert!(table.on_change(&path, now).is_none());
+
+        std::fs::write(&file, "zero\none\ntwo\n").unwrap();
+        let conflict = table.on_change(&file, now).unwrap();
+        assert_eq!(conflict.session_id, "s1");
+        assert_eq!(conflict.options, vec![ConflictResolution::KeepUser, ConflictResolution::KeepAgent, ConflictResolution::Merge]);
+        // Reported once per lock
+        assert!(table.on_change(&file, now).is_none());
+        assert_eq!(table.lock_for(&file).unwrap().conflict_id, Some(conflict.id.clone()));
+
+        table.resolve(&conflict.id, ConflictResolution::Merge).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "zero\none\ntwo\n");
+        assert!(table.lock_for(&file).is_none());
+        assert!(table.conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_keep_agent_restores_content() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("new.txt");
+        let mut table = LockTable::default();
+        let now = Utc::now();
+
+        table.begin_write("s1", file.to_str().unwrap(), "t1", now);
+        std::fs::write(&file, "agent\n").unwrap();
+        table.finish_write("t1", now);
+        std::fs::write(&file, "user\n").unwrap();
+
+        let conflict = table.on_change(&file, now).unwrap();
+        table.resolve(&conflict.id, ConflictResolution::KeepAgent).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "agent\n");
+    }
+
+    #[test]
+    fn test_failed_merge_keeps_user_content() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("image.bin");
+        let mut table = LockTable::default();
+        let now = Utc::now();
+
+        std::fs::write(&file, b"base\0").unwrap();
+        table.begin_write("s1", file.to_str().unwrap(), "t1", now);
+        std::fs::write(&file, b"agent\0").unwrap();
+        table.finish_write("t1", now);
+        std::fs::write(&file, b"user\0").unwrap();
+
+        let conflict = table.on_change(&file, now).unwrap();
+        assert!(table.resolve(&conflict.id, ConflictResolution::Merge).is_err());
+        assert_eq!(std::fs::read(&file).unwrap(), b"user\0");
+        assert!(table.lock_for(&file).is_some());
+    }
+
+    #[test]
+    fn test_release_keeps_conflicted_locks() {
+        let dir = TempDir::new().unwrap();
+        let quiet = dir.path().join("quiet.txt");
+        let edited = dir.path().join("edited.txt");
+        let mut table = LockTable::default();
+        let start = Utc::now();
+
+        for (id, file) in [("t1", &quiet), ("t2", &edited)] {
+            table.begin_write("s1", file.to_str().unwrap(), id, start);
+            std::fs::write(file, "agent\n").unwrap();
+            table.finish_write(id, start);
+        }
+        std::fs::write(&edited, "user\n").unwrap();
+        table.on_change(&edited, start).unwrap();
+
+        let later = start + chrono::Duration::seconds(LOCK_IDLE_SECS + 1);
+        assert_eq!(table.release_expired(later).len(), 1);
+        assert!(table.lock_for(&quiet).is_none());
+        assert!(table.release_session("s1").is_empty());
+        assert_eq!(table.locks(Some("s1")).len(), 1);
+    }
+
+    #[test]
+    fn test_writes_outside_scope_are_flagged() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("services/api")).unwrap();
+        let mut table = LockTable::default();
+        table.set_root("s1", dir.path().to_path_buf());
+        table.set_scope("s1", PathScope::new(&["services/api".to_string()]).unwrap());
+        let now = Utc::now();
+
+        let inside = table.begin_write("s1", "services/api/main.rs", "t1", now);
+        let outside = table.begin_write("s1", "services/web/main.rs", "t2", now);
+        assert!(table.scope_violated("s1", &inside).is_none());
+        assert!(table.scope_violated("s1", &outside).is_some());
+        assert!(table.scope_violated("s2", &outside).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+//! Git LFS handling for session worktrees
+//!
+//! Files tracked by LFS are found through their `filter=lfs` attribute, which
+//! works whether or not git-lfs is installed. A new worktree is hydrated with
+//! `git lfs fetch` and `git lfs checkout` so the agent sees real content rather
+//! than pointer files, and diffs used for drafts and statistics leave LFS files
+//! out, since their text diff is either a pointer or binary noise.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+use crate::commit_message::git;
+
+/// Pathspec matching every LFS-tracked path
+pub const LFS_PATHSPEC: &str = ":(attr:filter=lfs)";
+/// Pathspecs limiting a diff to files outside LFS
+pub const NON_LFS_PATHSPECS: &[&str] = &[".", ":(exclude,attr:filter=lfs)"];
+
+const POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+/// Pointer files are small; anything larger is real content
+const MAX_POINTER_BYTES: u64 = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LfsStatus {
+    /// Whether any tracked file has the `filter=lfs` attribute
+    pub uses_lfs: bool,
+    pub lfs_installed: bool,
+    pub tracked_files: usize,
+    /// LFS files still holding a pointer instead of their content
+    pub pointer_files: Vec<String>,
+}
+
+pub fn lfs_installed() -> bool {
+    Command::new("git").args(["lfs", "version"]).output().is_ok_and(|o| o.status.success())
+}
+
+pub fn lfs_files(worktree: &Path) -> Result<Vec<String>, String> {
+    let listed = git(worktree, &["ls-files", "--", LFS_PATHSPEC])?;
+    Ok(listed.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+}
+
+pub fn is_pointer_file(path: &Path) -> bool {
+    if !path.metadata().is_ok_and(|m| m.is_file() && m.len() <= MAX_POINTER_BYTES) {
+        return false;
+    }
+    std::fs::read(path).is_ok_and(|content| content.starts_with(POINTER_PREFIX))
+}
+
+pub fn lfs_status(worktree: &Path) -> Result<LfsStatus, String> {
+    let files = lfs_files(worktree)?;
+    let pointer_files = files.iter().filter(|f| is_pointer_file(&worktree.join(f))).cloned().collect();
+    Ok(LfsStatus {
+        uses_lfs: !files.is_empty(),
+        lfs_installed: lfs_installed(),
+        tracked_files: files.len(),
+        pointer_files,
+    })
+}
+
+/// Replace pointer files in a fresh worktree with their content. Objects already
+/// in the repository's LFS store are reused; the rest are fetched from `origin`.
+pub fn hydrate(worktree: &Path) -> Result<LfsStatus, String> {
+    let status = lfs_status(worktree)?;
+    if status.pointer_files.is_empty() {
+        return Ok(status);
+    }
+    if !status.lfs_installed {
+        return Err(format!(
+            "{} files are stored in Git LFS but git-lfs is not installed; the worktree holds pointer files",
+            status.pointer_files.len()
+        ));
+    }
+    if git(worktree, &["remote", "get-url", "origin"]).is_ok() {
+        if let Err(e) = git(worktree, &["lfs", "fetch", "origin", "HEAD"]) {
+            log::warn!("git lfs fetch failed in {}: {}", worktree.display(), e);
+        }
+    }
+    git(worktree, &["lfs", "checkout"])?;
+    lfs_status(worktree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lfs_files_and_pointers() {
+        let dir = TempDir::new().unwrap();
+        for args in [vec!["init"], vec!["config", "user.name", "Test User"], vec!["config", "user.email", "test@example.com"]] {
+            git(dir.path(), &args).unwrap();
+        }
+        std::fs::write(dir.path().join(".gitattributes"), "*.bin filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+        std::fs::write(dir.path().join("model.bin"), "version https://git-lfs.github.com/spec/v1\noid sha256:00\nsize 4\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "notes\n").unwrap();
+        // Commit without running the LFS filter so the pointer is stored as-is
+        git(dir.path(), &["-c", "filter.lfs.clean=cat", "-c", "filter.lfs.smudge=cat", "add", "."]).unwrap();
+        git(dir.path(), &["commit", "-m", "init"]).unwrap();
+
+        assert_eq!(lfs_files(dir.path()).unwrap(), vec!["model.bin".to_string()]);
+        assert!(is_pointer_file(&dir.path().join("model.bin")));
+        assert!(!is_pointer_file(&dir.path().join("notes.txt")));
+        let status = lfs_status(dir.path()).unwrap();
+        assert!(status.uses_lfs);
+        assert_eq!(status.pointer_files, vec!["model.bin".to_string()]);
+
+        std::fs::write(dir.path().join("model.bin"), "changed").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "more notes\n").unwrap();
+        let mut args = vec!["diff", "--name-only", "HEAD", "--"];
+        args.extend(NON_LFS_PATHSPECS);
+        assert_eq!(git(dir.path(), &args).unwrap().trim(), "notes.txt");
+    }
+}
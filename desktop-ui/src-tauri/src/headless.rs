@@ -0,0 +1,216 @@
+//! Running a batch without the desktop window
+//!
+//! `amp-orchestra --headless <batch.json>` starts the batch described by a JSON
+//! or TOML file with the fields of `start_batch`'s request, prints a line per
+//! progress update and, once every case has finished, writes the result file
+//! `merge_batch_results` reads. It exits with 0 when every case succeeded, 1
+//! when any failed and 2 when the batch could not be started. There is no
+//! database, so no quotas apply and estimates have no history.
+
+use std::path::{Path, PathBuf};
+
+use crate::batch_commands::{init_batch_engine_state, StartBatchRequest};
+use crate::batch_engine::{BatchConfig, BatchProgress, BatchStatus, SessionStatus, ValidationSeverity};
+use crate::batch_shards::ShardSpec;
+use crate::services::BatchService;
+
+pub const USAGE: &str = "Usage: amp-orchestra --headless <batch.json|batch.toml> [--output <results.json>] [--shard <index>/<count>] [--results-dir <dir>]";
+
+#[derive(Debug, PartialEq)]
+pub struct HeadlessArgs {
+    pub config: PathBuf,
+    /// Where the result file is written; printed to stdout when omitted
+    pub output: Option<PathBuf>,
+    /// Overrides the config's shard, so one config serves every CI job
+    pub shard: Option<ShardSpec>,
+    /// Overrides the config's directory for per-case logs
+    pub results_dir: Option<PathBuf>,
+}
+
+impl HeadlessArgs {
+    /// Parse the arguments after the program name; `None` without `--headless`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let args: Vec<String> = args.into_iter().collect();
+        if !args.iter().any(|arg| arg == "--headless") {
+            return Ok(None);
+        }
+
+        let (mut config, mut output, mut shard, mut results_dir) = (None, None, None, None);
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {}
+                "--output" | "--shard" | "--results-dir" => {
+                    let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                    match arg.as_str() {
+                        "--output" => output = Some(PathBuf::from(value)),
+                        "--shard" => shard = Some(parse_shard(&value)?),
+                        _ => results_dir = Some(PathBuf::from(value)),
+                    }
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ if config.is_none() => config = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+
+        let config = config.ok_or("No batch config given")?;
+        Ok(Some(Self { config, output, shard, results_dir }))
+    }
+}
+
+/// `<index>/<count>`, with a zero-based index
+fn parse_shard(value: &str) -> Result<ShardSpec, String> {
+    let invalid = || format!("Invalid shard {}, expected <index>/<count>", value);
+    let (index, count) = value.split_once('/').ok_or_else(invalid)?;
+    let shard = ShardSpec {
+        index: index.trim().parse().map_err(|_| invalid())?,
+        count: count.trim().parse().map_err(|_| invalid())?,
+    };
+    shard.validate()?;
+    Ok(shard)
+}
+
+/// Read a batch config, as TOML when the file ends in `.toml` and as JSON otherwise
+pub fn load_config(path: &Path) -> Result<BatchConfig, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read file {}: {}", path.display(), e))?;
+    let request: StartBatchRequest = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| e.to_string()),
+        _ => serde_json::from_str(&content).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("Invalid batch config {}: {}", path.display(), e))?;
+    Ok(BatchConfig::from(request))
+}
+
+/// Run the batch to completion and return the process's exit code
+pub fn run(args: HeadlessArgs) -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start runtime: {}", e);
+            return 2;
+        }
+    };
+    match runtime.block_on(run_batch(args)) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+/// Whether every case succeeded
+async fn run_batch(args: HeadlessArgs) -> Result<bool, String> {
+    let mut config = load_config(&args.config)?;
+    if args.shard.is_some() {
+        config.shard = args.shard;
+    }
+    if args.results_dir.is_some() {
+        config.results_dir = args.results_dir;
+    }
+
+    let engine = init_batch_engine_state().engine;
+    let service = BatchService::new(engine.clone(), None);
+    let report = service.validate(&config, None).await?;
+    for issue in &report.issues {
+        let severity = match issue.severity {
+            ValidationSeverity::Error => "error",
+            ValidationSeverity::Warning => "warning",
+        };
+        eprintln!("{}: {}: {}", severity, issue.field, issue.message);
+    }
+    if !report.valid {
+        return Err(format!("Batch config {} is invalid", args.config.display()));
+    }
+
+    let mut handle = service.start(config, None).await?;
+    let total = handle.total_sessions();
+    println!("Started batch {} with {} cases", handle.batch_id(), total);
+    if let Some(mut progress_rx) = handle.take_progress_receiver() {
+        while let Some(progress) = progress_rx.recv().await {
+            println!("{}", progress_line(&progress, total));
+            if !matches!(progress.status, BatchStatus::Pending | BatchStatus::Running) {
+                break;
+            }
+        }
+    }
+
+    let results = engine
+        .result_file(handle.batch_id())
+        .await
+        .map_err(|e| format!("Failed to collect batch results: {}", e))?;
+    let json = serde_json::to_string_pretty(&results)
+        .map_err(|e| format!("Failed to serialize batch results: {}", e))?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, json).map_err(|e| format!("Failed to write file {}: {}", path.display(), e))?;
+            println!("Wrote results to {}", path.display());
+        }
+        None => println!("{}", json),
+    }
+    Ok(results.results.iter().all(|case| matches!(case.status, SessionStatus::Completed)))
+}
+
+fn progress_line(progress: &BatchProgress, total: usize) -> String {
+    format!(
+        "[{:?}] {}/{} cases finished, {} failed, {} running",
+        progress.status,
+        progress.completed_sessions + progress.failed_sessions,
+        total,
+        progress.failed_sessions,
+        progress.running_sessions,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn args(args: &[&str]) -> Result<Option<HeadlessArgs>, String> {
+        HeadlessArgs::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_headless_args() {
+        assert_eq!(args(&[]), Ok(None));
+        assert_eq!(
+            args(&["--headless", "swe.toml", "--shard", "1/4", "--output", "out.json"]),
+            Ok(Some(HeadlessArgs {
+                config: PathBuf::from("swe.toml"),
+                output: Some(PathBuf::from("out.json")),
+                shard: Some(ShardSpec { index: 1, count: 4 }),
+                results_dir: None,
+            }))
+        );
+        assert_eq!(args(&["--headless"]), Err("No batch config given".to_string()));
+        assert!(args(&["--headless", "swe.toml", "--shard", "4/4"]).unwrap_err().contains("out of range"));
+        assert_eq!(args(&["--headless", "swe.toml", "--verbose"]), Err("Unknown option --verbose".to_string()));
+    }
+
+    #[test]
+    fn test_load_config_from_json_or_toml() {
+        let dir = TempDir::new().unwrap();
+        let toml_path = dir.path().join("batch.toml");
+        std::fs::write(
+            &toml_path,
+            "name = \"SWE\"\nprompts = [\"Fix the bug\"]\nrepositories = [\"/repo\"]\ntimeoutSec = 60\n\n[[dependencies]]\nprompt = 0\nafter = 0\n",
+        )
+        .unwrap();
+        let config = load_config(&toml_path).unwrap();
+        assert_eq!(config.name, "SWE");
+        assert_eq!(config.timeout_sec, 60);
+        assert_eq!(config.concurrency, 4);
+        assert_eq!(config.dependencies.len(), 1);
+
+        let json_path = dir.path().join("batch.json");
+        std::fs::write(&json_path, r#"{"name": "SWE", "prompts": ["Fix the bug"], "repositories": ["/repo"], "shardIndex": 1, "shardCount": 2}"#).unwrap();
+        assert_eq!(load_config(&json_path).unwrap().shard, Some(ShardSpec { index: 1, count: 2 }));
+
+        std::fs::write(&json_path, "name = \"SWE\"").unwrap();
+        assert!(load_config(&json_path).unwrap_err().starts_with("Invalid batch config"));
+    }
+}
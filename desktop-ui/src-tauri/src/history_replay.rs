@@ -0,0 +1,475 @@
+//! Thread history replay on re-attach
+//!
+//! A re-attached thread runs in a fresh CLI process that only knows what it is
+//! sent. Replaying every stored event costs tokens and time on long threads, so
+//! by default only the last `recent_turns` turns are replayed as they were,
+//! after one message summarizing the turns before them. The summary is asked of
+//! the model through the Amp proxy and put together from the turns' text when
+//! the model is unavailable; it is reused until more turns age out of the
+//! window. Each Amp profile can choose full replay instead, and every replay is
+//! recorded on the thread.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use tauri::State;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::amp_proxy::{amp_proxy, ProxyRequest};
+use crate::app_state::AppState;
+use crate::profile_auth::ProfileManager;
+use crate::stream_text::truncate;
+
+pub const DEFAULT_RECENT_TURNS: i64 = 10;
+pub const MAX_RECENT_TURNS: i64 = 200;
+/// Proxy path of the completion endpoint used for summaries
+const COMPLETION_PATH: &str = "/api/completions";
+/// Transcript sent to the model; older text is dropped first
+const SUMMARY_INPUT_BYTES: usize = 24 * 1024;
+/// Longest text kept of one message in a transcript or heuristic summary
+const EXCERPT_BYTES: usize = 400;
+const HEURISTIC_SUMMARY_BYTES: usize = 6 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayStrategy {
+    /// Every stored event
+    Full,
+    /// A summary of older turns, then the latest turns
+    #[default]
+    Summarized,
+}
+
+impl ReplayStrategy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReplayStrategy::Full => "full",
+            ReplayStrategy::Summarized => "summarized",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "full" => ReplayStrategy::Full,
+            _ => ReplayStrategy::Summarized,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplaySettings {
+    pub strategy: ReplayStrategy,
+    /// Turns replayed verbatim under `Summarized`
+    pub recent_turns: i64,
+}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        Self { strategy: ReplayStrategy::default(), recent_turns: DEFAULT_RECENT_TURNS }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummarySource {
+    Model,
+    Heuristic,
+}
+
+impl SummarySource {
+    fn as_str(self) -> &'static str {
+        match self {
+            SummarySource::Model => "model",
+            SummarySource::Heuristic => "heuristic",
+        }
+    }
+}
+
+/// What one re-attach sent to the CLI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadReplay {
+    pub id: i64,
+    pub thread_id: String,
+    /// `Full` also when the thread was too short to summarize
+    pub strategy: ReplayStrategy,
+    pub replayed_messages: i64,
+    pub summarized_messages: i64,
+    pub summary_source: Option<SummarySource>,
+    pub summary: Option<String>,
+    pub created_at: String,
+}
+
+/// Whether an event starts a turn: a user event with text, not a tool result
+pub(crate) fn is_prompt(event: &Value) -> bool {
+    event.get("type").and_then(Value::as_str) == Some("user")
+        && match event.get("message").and_then(|m| m.get("content")) {
+            Some(Value::String(_)) => true,
+            Some(Value::Array(parts)) => parts.iter().any(|p| p.get("type").and_then(Value::as_str) == Some("text")),
+            _ => false,
+        }
+}
+
+/// Readable text of an event: its text parts, with tool calls named
+fn event_text(event: &Value) -> String {
+    match event.get("message").and_then(|m| m.get("content")) {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(Value::as_str) {
+                Some("text") => part.get("text").and_then(Value::as_str).map(str::to_string),
+                Some("tool_use") => part.get("name").and_then(Value::as_str).map(|name| format!("[used {}]", name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+/// Index of the first event replayed verbatim; the events before it are summarized
+pub fn split_point(events: &[Value], settings: &ReplaySettings) -> usize {
+    if settings.strategy == ReplayStrategy::Full {
+        return 0;
+    }
+    let starts: Vec<usize> = events.iter().enumerate().filter(|(_, e)| is_prompt(e)).map(|(i, _)| i).collect();
+    let keep = settings.recent_turns.max(1) as usize;
+    if starts.len() <= keep {
+        0
+    } else {
+        starts[starts.len() - keep]
+    }
+}
+
+/// `[role] text` lines of the events that have text
+fn transcript(events: &[Value]) -> Vec<String> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let text = event_text(event);
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let role = event.get("type").and_then(Value::as_str).unwrap_or("event");
+            (!text.is_empty()).then(|| format!("[{}] {}", role, truncate(&text, EXCERPT_BYTES)))
+        })
+        .collect()
+}
+
+/// The latest lines of `lines` that fit in `max` bytes, with a note of how many were dropped
+fn latest_within(lines: &[String], max: usize) -> String {
+    let mut kept = Vec::new();
+    let mut size = 0;
+    for line in lines.iter().rev() {
+        if size + line.len() + 1 > max {
+            break;
+        }
+        size += line.len() + 1;
+        kept.push(line.as_str());
+    }
+    kept.reverse();
+    let dropped = lines.len() - kept.len();
+    let mut text = if dropped > 0 { format!("[{} earlier messages left out]\n", dropped) } else { String::new() };
+    text.push_str(&kept.join("\n"));
+    text
+}
+
+pub fn summary_prompt(events: &[Value]) -> String {
+    format!(
+        "Summarize the earlier part of a conversation with a coding agent so that the agent can continue \
+         the work without it. Keep the goals, decisions, files touched and anything still left to do. \
+         Reply with the summary only.\n\n{}",
+        latest_within(&transcript(events), SUMMARY_INPUT_BYTES)
+    )
+}
+
+/// Summary put together from the transcript when the model is unavailable
+pub fn heuristic_summary(events: &[Value]) -> String {
+    latest_within(&transcript(events), HEURISTIC_SUMMARY_BYTES)
+}
+
+/// The user event carrying a summary of `turns` earlier turns
+pub fn summary_event(summary: &str, turns: usize) -> Value {
+    unified_core::AmpHarness::user_message(&format!(
+        "This conversation is being resumed. Its first {} turns are not repeated; this is a summary of them:\n\n{}",
+        turns,
+        summary.trim()
+    ))
+}
+
+pub async fn get_settings(db: &SqlitePool, profile_id: &str) -> Result<ReplaySettings, String> {
+    let row = sqlx::query_as::<_, (String, i64)>("SELECT strategy, recent_turns FROM profile_replay_settings WHERE profile_id = ?")
+        .bind(profile_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read replay settings: {}", e))?;
+    Ok(row
+        .map(|(strategy, recent_turns)| ReplaySettings { strategy: ReplayStrategy::parse(&strategy), recent_turns })
+        .unwrap_or_default())
+}
+
+pub async fn set_settings(db: &SqlitePool, profile_id: &str, settings: &ReplaySettings) -> Result<(), String> {
+    if !(1..=MAX_RECENT_TURNS).contains(&settings.recent_turns) {
+        return Err(format!("Recent turns must be between 1 and {}", MAX_RECENT_TURNS));
+    }
+    sqlx::query(
+        "INSERT INTO profile_replay_settings (profile_id, strategy, recent_turns) VALUES (?, ?, ?)
+         ON CONFLICT(profile_id) DO UPDATE SET strategy = excluded.strategy, recent_turns = excluded.recent_turns,
+             updated_at = (datetime('now', 'utc') || 'Z')",
+    )
+    .bind(profile_id)
+    .bind(settings.strategy.as_str())
+    .bind(settings.recent_turns)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save replay settings: {}", e))?;
+    Ok(())
+}
+
+/// A summary recorded for the same number of leading messages, which must then be the same messages
+async fn cached_summary(db: &SqlitePool, thread_id: &str, summarized_messages: i64) -> Option<(String, SummarySource)> {
+    let row = sqlx::query_as::<_, (String, String)>(
+        "SELECT summary, summary_source FROM thread_replays
+         WHERE thread_id = ? AND summarized_messages = ? AND summary IS NOT NULL
+         ORDER BY id DESC LIMIT 1",
+    )
+    .bind(thread_id)
+    .bind(summarized_messages)
+    .fetch_optional(db)
+    .await
+    .ok()??;
+    let source = if row.1 == "model" { SummarySource::Model } else { SummarySource::Heuristic };
+    Some((row.0, source))
+}
+
+pub async fn record_replay(
+    db: &SqlitePool,
+    thread_id: &str,
+    strategy: ReplayStrategy,
+    replayed_messages: i64,
+    summarized_messages: i64,
+    summary: Option<(&str, SummarySource)>,
+) -> Result<ThreadReplay, String> {
+    let (id, created_at): (i64, String) = sqlx::query_as(
+        "INSERT INTO thread_replays (thread_id, strategy, replayed_messages, summarized_messages, summary_source, summary)
+         VALUES (?, ?, ?, ?, ?, ?) RETURNING id, created_at",
+    )
+    .bind(thread_id)
+    .bind(strategy.as_str())
+    .bind(replayed_messages)
+    .bind(summarized_messages)
+    .bind(summary.map(|(_, source)| source.as_str()))
+    .bind(summary.map(|(text, _)| text))
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to record replay: {}", e))?;
+    Ok(ThreadReplay {
+        id,
+        thread_id: thread_id.to_string(),
+        strategy,
+        replayed_messages,
+        summarized_messages,
+        summary_source: summary.map(|(_, source)| source),
+        summary: summary.map(|(text, _)| text.to_string()),
+        created_at,
+    })
+}
+
+pub async fn replays(db: &SqlitePool, thread_id: &str) -> Result<Vec<ThreadReplay>, String> {
+    let rows = sqlx::query_as::<_, (i64, String, i64, i64, Option<String>, Option<String>, String)>(
+        "SELECT id, strategy, replayed_messages, summarized_messages, summary_source, summary, created_at
+         FROM thread_replays WHERE thread_id = ? ORDER BY id",
+    )
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read replays: {}", e))?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, strategy, replayed_messages, summarized_messages, source, summary, created_at)| ThreadReplay {
+            id,
+            thread_id: thread_id.to_string(),
+            strategy: ReplayStrategy::parse(&strategy),
+            replayed_messages,
+            summarized_messages,
+            summary_source: source.map(|s| if s == "model" { SummarySource::Model } else { SummarySource::Heuristic }),
+            summary,
+            created_at,
+        })
+        .collect())
+}
+
+async fn summarize(events: &[Value], app_state: State<'_, AppState>) -> (String, SummarySource) {
+    let request = ProxyRequest {
+        method: "POST".to_string(),
+        path: COMPLETION_PATH.to_string(),
+        body: Some(serde_json::json!({ "prompt": summary_prompt(events), "max_tokens": 800 }).to_string()),
+        headers: None,
+    };
+    match amp_proxy(request, None, app_state).await {
+        Ok(response) if response.status < 400 => {
+            let text = crate::commit_message::completion_text(&response.body);
+            if !text.trim().is_empty() {
+                return (text.trim().to_string(), SummarySource::Model);
+            }
+        }
+        Ok(response) => log::warn!("History summary request failed with HTTP {}", response.status),
+        Err(e) => log::warn!("History summary request failed: {}", e),
+    }
+    (heuristic_summary(events), SummarySource::Heuristic)
+}
+
+/// Send a thread's stored history to its new CLI process through `tx`, as the
+/// settings of the session's Amp profile ask
+pub async fn replay(
+    db: &SqlitePool,
+    thread_id: &str,
+    profile_id: Option<&str>,
+    app_state: State<'_, AppState>,
+    tx: &UnboundedSender<String>,
+) -> Result<ThreadReplay, String> {
+    let messages = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT content, content_ref FROM messages WHERE thread_id = ? ORDER BY created_at ASC",
+    )
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to get thread history: {}", e))?;
+    let events: Vec<Value> = messages
+        .into_iter()
+        .filter_map(|(content, content_ref)| {
+            serde_json::from_str(&crate::message_content::resolve_content(content, content_ref)).ok()
+        })
+        .collect();
+
+    let settings = match profile_id {
+        Some(profile_id) => get_settings(db, profile_id).await?,
+        None => ReplaySettings::default(),
+    };
+    let split = split_point(&events, &settings);
+    let (older, recent) = events.split_at(split);
+
+    let summary = if older.is_empty() {
+        None
+    } else {
+        match cached_summary(db, thread_id, older.len() as i64).await {
+            Some(summary) => Some(summary),
+            None => Some(summarize(older, app_state).await),
+        }
+    };
+    if let Some((text, _)) = &summary {
+        let turns = older.iter().filter(|e| is_prompt(e)).count();
+        let _ = tx.send(summary_event(text, turns).to_string());
+    }
+    for event in recent {
+        let _ = tx.send(event.to_string());
+    }
+
+    let strategy = if summary.is_some() { ReplayStrategy::Summarized } else { ReplayStrategy::Full };
+    record_replay(
+        db,
+        thread_id,
+        strategy,
+        recent.len() as i64,
+        older.len() as i64,
+        summary.as_ref().map(|(text, source)| (text.as_str(), *source)),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_replay_settings(profile_id: String, profile_manager: State<'_, ProfileManager>) -> Result<ReplaySettings, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    get_settings(db, &profile_id).await
+}
+
+#[tauri::command]
+pub async fn set_replay_settings(
+    profile_id: String,
+    settings: ReplaySettings,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ReplaySettings, String> {
+    if !profile_manager.profiles.contains_key(&profile_id) {
+        return Err(format!("Profile '{}' not found", profile_id));
+    }
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    set_settings(db, &profile_id, &settings).await?;
+    Ok(settings)
+}
+
+/// Every re-attach of a thread, oldest first
+#[tauri::command]
+pub async fn get_thread_replays(thread_id: String, profile_manager: State<'_, ProfileManager>) -> Result<Vec<ThreadReplay>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    replays(db, &thread_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use serde_json::json;
+
+    fn prompt(text: &str) -> Value {
+        unified_core::AmpHarness::user_message(text)
+    }
+
+    fn answer(text: &str) -> Value {
+        json!({ "type": "assistant", "message": { "content": [
+            { "type": "text", "text": text },
+            { "type": "tool_use", "name": "edit_file", "input": {} }
+        ] } })
+    }
+
+    fn tool_result() -> Value {
+        json!({ "type": "user", "message": { "content": [{ "type": "tool_result", "content": "ok" }] } })
+    }
+
+    #[test]
+    fn test_split_keeps_recent_turns() {
+        let events: Vec<Value> = (0..5)
+            .flat_map(|i| vec![prompt(&format!("Task {}", i)), answer(&format!("Done {}", i)), tool_result()])
+            .collect();
+        let summarized = ReplaySettings { strategy: ReplayStrategy::Summarized, recent_turns: 2 };
+
+        // Tool results do not start turns
+        assert_eq!(split_point(&events, &summarized), 9);
+        assert_eq!(split_point(&events, &ReplaySettings { strategy: ReplayStrategy::Full, recent_turns: 2 }), 0);
+        assert_eq!(split_point(&events, &ReplaySettings { recent_turns: 5, ..summarized }), 0);
+
+        let summary = heuristic_summary(&events[..9]);
+        assert_eq!(
+            summary,
+            "[user] Task 0\n[assistant] Done 0 [used edit_file]\n[user] Task 1\n[assistant] Done 1 [used edit_file]\n[user] Task 2\n[assistant] Done 2 [used edit_file]"
+        );
+        let long: Vec<Value> = (0..100).map(|i| prompt(&format!("{} {}", i, "word ".repeat(80)))).collect();
+        let summary = heuristic_summary(&long);
+        assert!(summary.len() <= HEURISTIC_SUMMARY_BYTES + 64);
+        assert!(summary.starts_with("[") && summary.contains("earlier messages left out") && summary.contains("[user] 99 word"));
+        assert!(summary_prompt(&events[..9]).ends_with("[assistant] Done 2 [used edit_file]"));
+
+        let event = summary_event("They fixed the build.", 3);
+        assert!(is_prompt(&event));
+        assert!(event_text(&event).contains("first 3 turns") && event_text(&event).ends_with("They fixed the build."));
+    }
+
+    #[tokio::test]
+    async fn test_settings_and_replays_round_trip() {
+        let pool = test_pool().await;
+
+        assert_eq!(get_settings(&pool, "p1").await.unwrap(), ReplaySettings::default());
+        let full = ReplaySettings { strategy: ReplayStrategy::Full, recent_turns: 4 };
+        set_settings(&pool, "p1", &full).await.unwrap();
+        assert_eq!(get_settings(&pool, "p1").await.unwrap(), full);
+        assert!(set_settings(&pool, "p1", &ReplaySettings { recent_turns: 0, ..full }).await.is_err());
+
+        let first = record_replay(&pool, "t1", ReplayStrategy::Summarized, 6, 20, Some(("Earlier work", SummarySource::Model)))
+            .await
+            .unwrap();
+        let second = record_replay(&pool, "t1", ReplayStrategy::Full, 8, 0, None).await.unwrap();
+        assert_eq!(replays(&pool, "t1").await.unwrap(), vec![first, second]);
+        assert_eq!(cached_summary(&pool, "t1", 20).await, Some(("Earlier work".to_string(), SummarySource::Model)));
+        assert_eq!(cached_summary(&pool, "t1", 21).await, None);
+        assert!(replays(&pool, "t2").await.unwrap().is_empty());
+    }
+}
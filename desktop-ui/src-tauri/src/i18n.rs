@@ -0,0 +1,230 @@
+//! Localized backend strings
+//!
+//! Errors, notifications and export headers the backend produces for people to
+//! read are looked up by key in the catalogs under `locales/`, which use the
+//! `key = value` subset of Fluent with `{ $name }` placeables. The active locale
+//! is the user's `AppConfig::locale` if set, otherwise the best match for the OS
+//! languages, otherwise English; keys missing from a catalog fall back to
+//! English. Until `init` runs at startup everything renders in English, which
+//! keeps tests independent of the machine's locale.
+//!
+//! Machine-readable output (CSV headers, JSON fields, event names) is never
+//! localized.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tauri::State;
+
+use crate::app_state::AppState;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Embedded catalogs, English first
+const SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+    ("es", include_str!("../locales/es.ftl")),
+];
+
+static CATALOGS: Lazy<HashMap<&'static str, HashMap<String, String>>> =
+    Lazy::new(|| SOURCES.iter().map(|(locale, source)| (*locale, parse(source))).collect());
+
+static CURRENT: RwLock<&'static str> = RwLock::new(DEFAULT_LOCALE);
+
+/// Translate `key` in the active locale: `tr!("export-title")` or
+/// `tr!("error-feature-disabled", name = flag)`
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::i18n::translate($crate::i18n::current(), $key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($crate::i18n::current(), $key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+pub(crate) use tr;
+
+/// Messages of a catalog; comments, blank lines and lines without `=` are skipped
+pub fn parse(source: &str) -> HashMap<String, String> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Fill `{ $name }` placeables; unknown names are left in place
+fn format(pattern: &str, args: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeable = &rest[start..start + len + 1];
+        let name = placeable[1..placeable.len() - 1].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(placeable),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `key` in `locale`, falling back to English and then to the key itself
+pub fn translate(locale: &str, key: &str, args: &[(&str, String)]) -> String {
+    let pattern = [locale, DEFAULT_LOCALE]
+        .iter()
+        .find_map(|locale| CATALOGS.get(locale).and_then(|catalog| catalog.get(key)));
+    match pattern {
+        Some(pattern) => format(pattern, args),
+        None => key.to_string(),
+    }
+}
+
+pub fn available() -> Vec<&'static str> {
+    SOURCES.iter().map(|(locale, _)| *locale).collect()
+}
+
+pub fn current() -> &'static str {
+    *CURRENT.read().unwrap()
+}
+
+/// `de_DE.UTF-8` or `de-de@euro` as `de-de`
+fn normalize(tag: &str) -> String {
+    let tag = tag.split(['.', '@']).next().unwrap_or_default();
+    tag.trim().replace('_', "-").to_lowercase()
+}
+
+/// First supported catalog for the requested tags, by exact tag and then by language
+pub fn negotiate<S: AsRef<str>>(requested: &[S]) -> Option<&'static str> {
+    let supported = available();
+    requested.iter().map(|tag| normalize(tag.as_ref())).find_map(|tag| {
+        let language = tag.split('-').next().unwrap_or_default().to_string();
+        supported
+            .iter()
+            .find(|locale| **locale == tag)
+            .or_else(|| supported.iter().find(|locale| **locale == language))
+            .copied()
+    })
+}
+
+/// The OS's preferred languages, most preferred first
+pub fn os_locales() -> Vec<String> {
+    let mut locales: Vec<String> = Vec::new();
+    if let Ok(languages) = std::env::var("LANGUAGE") {
+        locales.extend(languages.split(':').map(str::to_string));
+    }
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(value) = std::env::var(var) {
+            locales.push(value);
+        }
+    }
+    // GUI apps on macOS and Windows usually start without locale variables
+    let command = if cfg!(target_os = "macos") {
+        Some(("defaults", vec!["read", "-g", "AppleLocale"]))
+    } else if cfg!(windows) {
+        Some(("powershell", vec!["-NoProfile", "-Command", "(Get-Culture).Name"]))
+    } else {
+        None
+    };
+    if let Some((program, args)) = command {
+        if let Ok(output) = std::process::Command::new(program).args(&args).output() {
+            if output.status.success() {
+                locales.push(String::from_utf8_lossy(&output.stdout).trim().to_string());
+            }
+        }
+    }
+    locales.retain(|tag| !tag.is_empty() && tag != "C" && tag != "POSIX" && !tag.starts_with("C."));
+    locales
+}
+
+/// Make the user's preference, or the OS's languages without one, the active locale
+pub fn init(preference: Option<&str>) -> &'static str {
+    let mut requested: Vec<String> = preference.map(str::to_string).into_iter().collect();
+    requested.extend(os_locales());
+    let locale = negotiate(&requested).unwrap_or(DEFAULT_LOCALE);
+    *CURRENT.write().unwrap() = locale;
+    log::info!("Using locale {} for backend strings", locale);
+    locale
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocaleInfo {
+    /// Catalog in use
+    pub locale: String,
+    /// Saved preference; None follows the OS
+    pub preference: Option<String>,
+    pub os_locales: Vec<String>,
+    pub available: Vec<String>,
+}
+
+fn locale_info(app_state: &AppState) -> LocaleInfo {
+    LocaleInfo {
+        locale: current().to_string(),
+        preference: app_state.lock().unwrap().locale.clone(),
+        os_locales: os_locales(),
+        available: available().into_iter().map(str::to_string).collect(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_locale(app_state: State<'_, AppState>) -> Result<LocaleInfo, String> {
+    Ok(locale_info(&app_state))
+}
+
+/// Choose the language of backend strings; None goes back to following the OS
+#[tauri::command]
+pub async fn set_locale(locale: Option<String>, app_state: State<'_, AppState>) -> Result<LocaleInfo, String> {
+    if let Some(tag) = &locale {
+        if negotiate(&[tag]).is_none() {
+            return Err(tr!("error-unsupported-locale", locale = tag));
+        }
+    }
+    init(locale.as_deref());
+    let to_save = {
+        let mut config = app_state.lock().unwrap();
+        config.locale = locale;
+        config.clone()
+    };
+    to_save.save().await?;
+    Ok(locale_info(&app_state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs_translate_and_negotiate() {
+        let catalog = parse("# comment\n\nexport-title = Export\nbroken line\nsum = { $a } + {$b} = { $c }\n");
+        assert_eq!(catalog.len(), 2);
+        let args = [("a", "1".to_string()), ("b", "2".to_string())];
+        assert_eq!(format(&catalog["sum"], &args), "1 + 2 = { $c }");
+
+        assert_eq!(translate("en", "export-generated", &[("date", "today".to_string())]), "Generated today");
+        assert_eq!(translate("de", "export-title", &[]), "Amp-Sitzungsexport");
+        assert_eq!(translate("fr", "export-title", &[]), "Amp Session Export");
+        assert_eq!(translate("de", "no-such-key", &[]), "no-such-key");
+        assert_eq!(current(), DEFAULT_LOCALE);
+
+        assert_eq!(negotiate(&["de_AT.UTF-8", "en"]), Some("de"));
+        assert_eq!(negotiate(&["fr-FR", "es-MX"]), Some("es"));
+        assert_eq!(negotiate(&["EN-us"]), Some("en"));
+        assert_eq!(negotiate(&["fr"]), None);
+
+        // Translations cover every English message
+        let english = &CATALOGS[DEFAULT_LOCALE];
+        for locale in available() {
+            for key in english.keys() {
+                assert!(CATALOGS[locale].contains_key(key), "{} is missing {}", locale, key);
+            }
+        }
+    }
+}
@@ -3,6 +3,22 @@ use tauri::window::Color;
 
 mod cli_detection;
 mod commands;
+// Only the paged listing is used here
+#[allow(dead_code)]
+mod directory_listing;
+mod operations;
+// Only the CLI lookups are used here
+#[allow(dead_code)]
+mod platform;
+// Only the atomic writes are used here
+#[allow(dead_code)]
+mod safe_write;
+// Only to set up test databases
+#[cfg(test)]
+mod schema;
+// Only looked up here; recording worktrees is up to the app
+#[allow(dead_code)]
+mod worktree_paths;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -14,6 +30,7 @@ fn greet(name: &str) -> String {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(operations::OperationRegistry::default())
         .invoke_handler(tauri::generate_handler![
             greet,
             commands::save_file,
@@ -26,7 +43,9 @@ pub fn run() {
             cli_detection::validate_cli_path,
             cli_detection::install_global_cli,
             cli_detection::get_default_profiles,
-            cli_detection::health_check_profiles
+            cli_detection::health_check_profiles,
+            operations::cancel_operation,
+            operations::list_operations
         ])
         .setup(|app| {
             let main_window = app.get_webview_window("main").unwrap();
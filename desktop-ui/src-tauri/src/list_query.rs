@@ -0,0 +1,391 @@
+//! Paging, filtering and sorting for list commands
+//!
+//! List commands take an optional `ListQuery` and answer with a `ListResult`.
+//! Each command declares a `ListSpec` naming the fields a client may filter and
+//! sort on and the column behind each, so client input only ever picks from that
+//! list and reaches SQL as bound values. `fetch_page` turns a query into
+//! `WHERE`, `ORDER BY` and `LIMIT` clauses; lists that do not come from the
+//! database, such as git worktrees, go through `ListQuery::apply` instead.
+//!
+//! Without a `page_size` the whole list is returned, so existing callers keep
+//! working. `next_cursor` is the page to ask for next and can be passed back as
+//! `cursor`.
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, SqlitePool};
+
+pub const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    /// Case-insensitive substring match
+    Contains,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    IsNull,
+    NotNull,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListFilter {
+    pub field: String,
+    pub op: FilterOp,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListSort {
+    pub field: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListQuery {
+    /// Zero-based page, ignored when `cursor` is set
+    pub page: u32,
+    pub page_size: Option<u32>,
+    pub cursor: Option<String>,
+    /// Applied in order; the command's default sort breaks remaining ties
+    pub sort: Vec<ListSort>,
+    /// All must match
+    pub filters: Vec<ListFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListResult<T> {
+    pub items: Vec<T>,
+    /// Matching items across all pages
+    pub total: i64,
+    pub page: u32,
+    pub page_size: Option<u32>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> ListResult<T> {
+    pub fn empty() -> Self {
+        Self { items: Vec::new(), total: 0, page: 0, page_size: None, next_cursor: None }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> ListResult<U> {
+        ListResult {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            page_size: self.page_size,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// Fields of one list a client may filter and sort on
+pub struct ListSpec {
+    /// Field name and the SQL expression behind it
+    pub fields: &'static [(&'static str, &'static str)],
+    pub default_sort: &'static [(&'static str, SortDirection)],
+}
+
+impl ListSpec {
+    fn column(&self, field: &str) -> Result<&'static str, String> {
+        self.fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let known: Vec<&str> = self.fields.iter().map(|(name, _)| *name).collect();
+                format!("Unknown list field '{}', expected one of: {}", field, known.join(", "))
+            })
+    }
+}
+
+/// Clauses and bind values for one query
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqlClauses {
+    pub conditions: Vec<String>,
+    pub order_by: String,
+    pub binds: Vec<String>,
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+impl ListQuery {
+    pub fn effective_page(&self) -> Result<u32, String> {
+        match &self.cursor {
+            Some(cursor) => cursor.parse().map_err(|_| format!("Invalid list cursor '{}'", cursor)),
+            None => Ok(self.page),
+        }
+    }
+
+    fn limit(&self) -> Option<u32> {
+        self.page_size.map(|size| size.clamp(1, MAX_PAGE_SIZE))
+    }
+
+    fn sorts<'a>(&'a self, spec: &'a ListSpec) -> impl Iterator<Item = (&'a str, SortDirection)> {
+        self.sort
+            .iter()
+            .map(|s| (s.field.as_str(), s.direction))
+            .chain(spec.default_sort.iter().copied())
+    }
+
+    /// Translate filters and sorts into SQL using only the spec's columns
+    pub fn to_sql(&self, spec: &ListSpec) -> Result<SqlClauses, String> {
+        let mut clauses = SqlClauses::default();
+        for filter in &self.filters {
+            let column = spec.column(&filter.field)?;
+            let value = || {
+                filter
+                    .value
+                    .clone()
+                    .ok_or_else(|| format!("Filter on '{}' needs a value", filter.field))
+            };
+            let condition = match filter.op {
+                FilterOp::IsNull => format!("{} IS NULL", column),
+                FilterOp::NotNull => format!("{} IS NOT NULL", column),
+                FilterOp::Contains => {
+                    clauses.binds.push(format!("%{}%", escape_like(&value()?)));
+                    format!("{} LIKE ? ESCAPE '\\'", column)
+                }
+                op => {
+                    clauses.binds.push(value()?);
+                    let operator = match op {
+                        FilterOp::Eq => "=",
+                        FilterOp::Ne => "!=",
+                        FilterOp::Gt => ">",
+                        FilterOp::Gte => ">=",
+                        FilterOp::Lt => "<",
+                        _ => "<=",
+                    };
+                    format!("{} {} ?", column, operator)
+                }
+            };
+            clauses.conditions.push(condition);
+        }
+
+        let mut order = Vec::new();
+        for (field, direction) in self.sorts(spec) {
+            let column = spec.column(field)?;
+            order.push(format!("{} {}", column, if direction == SortDirection::Desc { "DESC" } else { "ASC" }));
+        }
+        clauses.order_by = order.join(", ");
+        Ok(clauses)
+    }
+
+    fn envelope<T>(&self, items: Vec<T>, total: i64, page: u32) -> ListResult<T> {
+        let page_size = self.limit();
+        let next_cursor = page_size
+            .filter(|size| (page as i64 + 1) * (*size as i64) < total)
+            .map(|_| (page + 1).to_string());
+        ListResult { items, total, page, page_size, next_cursor }
+    }
+
+    /// Filter, sort and page a list held in memory. `field` returns an item's
+    /// value for a spec field name, compared as text.
+    pub fn apply<T>(
+        &self,
+        items: Vec<T>,
+        spec: &ListSpec,
+        field: impl Fn(&T, &str) -> Option<String>,
+    ) -> Result<ListResult<T>, String> {
+        for name in self.filters.iter().map(|f| f.field.as_str()).chain(self.sorts(spec).map(|(f, _)| f)) {
+            spec.column(name)?;
+        }
+        let page = self.effective_page()?;
+
+        let mut matching = Vec::new();
+        for item in items {
+            let mut keep = true;
+            for filter in &self.filters {
+                let actual = field(&item, &filter.field);
+                let wanted = filter.value.as_deref();
+                keep &= match (filter.op, actual.as_deref(), wanted) {
+                    (FilterOp::IsNull, actual, _) => actual.is_none(),
+                    (FilterOp::NotNull, actual, _) => actual.is_some(),
+                    (_, _, None) => return Err(format!("Filter on '{}' needs a value", filter.field)),
+                    (_, None, Some(_)) => false,
+                    (FilterOp::Contains, Some(a), Some(w)) => a.to_lowercase().contains(&w.to_lowercase()),
+                    (FilterOp::Eq, Some(a), Some(w)) => a == w,
+                    (FilterOp::Ne, Some(a), Some(w)) => a != w,
+                    (FilterOp::Gt, Some(a), Some(w)) => a > w,
+                    (FilterOp::Gte, Some(a), Some(w)) => a >= w,
+                    (FilterOp::Lt, Some(a), Some(w)) => a < w,
+                    (FilterOp::Lte, Some(a), Some(w)) => a <= w,
+                };
+            }
+            if keep {
+                matching.push(item);
+            }
+        }
+
+        let sorts: Vec<(&str, SortDirection)> = self.sorts(spec).collect();
+        matching.sort_by(|a, b| {
+            sorts
+                .iter()
+                .map(|(name, direction)| {
+                    let ordering = field(a, name).cmp(&field(b, name));
+                    if *direction == SortDirection::Desc { ordering.reverse() } else { ordering }
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let total = matching.len() as i64;
+        let items = match self.limit() {
+            Some(size) => matching.into_iter().skip((page as usize) * (size as usize)).take(size as usize).collect(),
+            None => matching,
+        };
+        Ok(self.envelope(items, total, page))
+    }
+}
+
+/// Run a paged query. `base` holds conditions the command always applies, such
+/// as the parent session, and the values for their placeholders.
+pub async fn fetch_page<R>(
+    db: &SqlitePool,
+    columns: &str,
+    from: &str,
+    base: (&[&str], &[String]),
+    query: &ListQuery,
+    spec: &ListSpec,
+) -> Result<ListResult<R>, String>
+where
+    R: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let clauses = query.to_sql(spec)?;
+    let page = query.effective_page()?;
+    let (base_conditions, base_binds) = base;
+    let conditions: Vec<&str> = base_conditions.iter().copied().chain(clauses.conditions.iter().map(String::as_str)).collect();
+    let where_sql = if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) };
+    let binds: Vec<&String> = base_binds.iter().chain(clauses.binds.iter()).collect();
+
+    let count_sql = format!("SELECT COUNT(*) FROM {}{}", from, where_sql);
+    let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+    for bind in &binds {
+        count = count.bind(*bind);
+    }
+    let total = count.fetch_one(db).await.map_err(|e| format!("Failed to count list: {}", e))?;
+
+    let mut items_sql = format!("SELECT {} FROM {}{} ORDER BY {}", columns, from, where_sql, clauses.order_by);
+    if let Some(size) = query.limit() {
+        items_sql.push_str(&format!(" LIMIT {} OFFSET {}", size, page as u64 * size as u64));
+    }
+    let mut items = sqlx::query_as::<_, R>(&items_sql);
+    for bind in &binds {
+        items = items.bind(*bind);
+    }
+    let items = items.fetch_all(db).await.map_err(|e| format!("Failed to list: {}", e))?;
+    Ok(query.envelope(items, total, page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions};
+    use std::str::FromStr;
+
+    const SPEC: ListSpec = ListSpec {
+        fields: &[("title", "title"), ("rank", "rank"), ("created_at", "created_at")],
+        default_sort: &[("created_at", SortDirection::Desc)],
+    };
+
+    fn filter(field: &str, op: FilterOp, value: Option<&str>) -> ListFilter {
+        ListFilter { field: field.to_string(), op, value: value.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_to_sql_uses_only_spec_columns() {
+        let query = ListQuery {
+            sort: vec![ListSort { field: "title".to_string(), direction: SortDirection::Asc }],
+            filters: vec![filter("title", FilterOp::Contains, Some("50%")), filter("rank", FilterOp::IsNull, None)],
+            ..Default::default()
+        };
+        let clauses = query.to_sql(&SPEC).unwrap();
+        assert_eq!(clauses.conditions, vec!["title LIKE ? ESCAPE '\\'", "rank IS NULL"]);
+        assert_eq!(clauses.binds, vec!["%50\\%%"]);
+        assert_eq!(clauses.order_by, "title ASC, created_at DESC");
+
+        let injected = ListQuery { filters: vec![filter("1=1; DROP TABLE x", FilterOp::Eq, Some("a"))], ..Default::default() };
+        assert!(injected.to_sql(&SPEC).is_err());
+        let missing_value = ListQuery { filters: vec![filter("title", FilterOp::Eq, None)], ..Default::default() };
+        assert!(missing_value.to_sql(&SPEC).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_page_pages_filters_and_counts() {
+        let options = SqliteConnectOptions::from_str(":memory:").unwrap().disable_statement_logging();
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query("CREATE TABLE items (id INTEGER PRIMARY KEY, owner TEXT, title TEXT, rank INTEGER, created_at TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for i in 0..7 {
+            sqlx::query("INSERT INTO items (owner, title, rank, created_at) VALUES (?, ?, ?, ?)")
+                .bind(if i == 6 { "other" } else { "me" })
+                .bind(format!("item {}", i))
+                .bind(i)
+                .bind(format!("2024-01-0{}", i + 1))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let binds = ["me".to_string()];
+        let base: (&[&str], &[String]) = (&["owner = ?"], &binds);
+
+        let query = ListQuery { page_size: Some(4), ..Default::default() };
+        let first: ListResult<(String,)> = fetch_page(&pool, "title", "items", base, &query, &SPEC).await.unwrap();
+        assert_eq!(first.total, 6);
+        assert_eq!(first.items.iter().map(|(t,)| t.as_str()).collect::<Vec<_>>(), vec!["item 5", "item 4", "item 3", "item 2"]);
+        assert_eq!(first.next_cursor.as_deref(), Some("1"));
+
+        let query = ListQuery { cursor: first.next_cursor, ..query };
+        let second: ListResult<(String,)> = fetch_page(&pool, "title", "items", base, &query, &SPEC).await.unwrap();
+        assert_eq!(second.items.len(), 2);
+        assert_eq!(second.next_cursor, None);
+
+        let query = ListQuery { filters: vec![filter("rank", FilterOp::Gte, Some("4"))], ..Default::default() };
+        let ranked: ListResult<(String,)> = fetch_page(&pool, "title", "items", base, &query, &SPEC).await.unwrap();
+        assert_eq!(ranked.total, 2);
+        assert_eq!(ranked.page_size, None);
+    }
+
+    #[test]
+    fn test_apply_in_memory() {
+        let items = vec![("b", Some("2")), ("a", None), ("c", Some("1"))];
+        let field = |item: &(&str, Option<&str>), name: &str| match name {
+            "title" => Some(item.0.to_string()),
+            "rank" => item.1.map(str::to_string),
+            _ => None,
+        };
+        let query = ListQuery {
+            page_size: Some(1),
+            sort: vec![ListSort { field: "title".to_string(), direction: SortDirection::Asc }],
+            filters: vec![filter("rank", FilterOp::NotNull, None)],
+            ..Default::default()
+        };
+        let result = query.apply(items.clone(), &SPEC, field).unwrap();
+        assert_eq!(result.items, vec![("b", Some("2"))]);
+        assert_eq!((result.total, result.next_cursor.as_deref()), (2, Some("1")));
+
+        let bad_sort = ListQuery { sort: vec![ListSort { field: "nope".to_string(), direction: SortDirection::Asc }], ..Default::default() };
+        assert!(bad_sort.apply(items, &SPEC, field).is_err());
+    }
+}
@@ -7,6 +7,7 @@ mod thread_session_commands;
 mod amp_auth;
 mod app_state;
 mod profile_auth;
+mod schema;
 mod keychain_auth;
 mod cli_detection;
 mod cli_auth;
@@ -23,15 +24,99 @@ mod session_manager;
 #[cfg(feature = "worktree-manager")]
 mod enhanced_session_commands;
 mod batch_engine;
+mod batch_dag;
+mod batch_shards;
+mod batch_estimate;
+mod batch_case_logs;
+mod batch_results;
 mod batch_commands;
 mod worktree;
 mod worktree_commands;
+mod script_hooks;
+mod session_analytics;
+mod thread_feedback;
+mod file_locks;
+mod working_dir;
+mod directory_listing;
+mod safe_write;
+mod trash;
+mod session_merge;
+mod stream_buffer;
+mod session_activity;
+mod process_suspend;
+mod event_subscriptions;
+mod commit_message;
+mod worktree_selection;
+mod merge_back;
+mod path_scope;
+mod git_lfs;
+mod session_diagnostics;
+mod submodules;
+mod message_content;
+mod archive_compression;
+mod feature_flags;
+mod self_test;
+mod session_codes;
+mod session_gc;
+mod message_metrics;
+mod i18n;
+mod backups;
+mod remote_sync;
+mod conflicts;
+mod stream_quarantine;
+mod stream_text;
+mod session_priority;
+mod session_snapshot;
+mod repo_relocation;
+mod startup;
+mod startup_reconciliation;
+mod profile_dirs;
+mod secret_providers;
+mod toolbox_snapshots;
+mod memory_manager;
+mod list_query;
+mod transact;
+mod operations;
+mod thread_server;
+mod session_log;
+mod crash_reports;
+mod session_diff;
+mod checkpoint_mounts;
+mod command_metrics;
+mod ws_bridge;
+mod editor_rpc;
+mod profile_quotas;
+mod chaos;
+mod ui_state;
+mod context_packs;
+mod url_ingest;
+mod history_replay;
+mod staging_sync;
+mod spawn_env_audit;
+mod stack_detection;
+mod env_isolation;
+mod repo_settings;
+mod services;
+mod worktree_paths;
+mod headless;
+mod sealed_box;
+mod benchmark_presets;
+mod session_map;
+mod platform;
+mod app_log;
+mod watch_mode;
+mod version_matrix;
+mod process_limits;
 #[cfg(test)]
 mod runtime_env_tests;
 #[cfg(test)]
 mod env_composer_tests;
 #[cfg(test)]
 mod toolbox_resolver_tests;
+#[cfg(test)]
+mod stream_fuzz_tests;
+#[cfg(all(test, feature = "chaos"))]
+mod chaos_tests;
 
 use tauri::{Window, Manager, Emitter};
 use commands::*;
@@ -45,8 +130,63 @@ use cli_auth::*;
 use amp_proxy::*;
 use terminal::*;
 use exporters::export_commands::*;
+use exporters::import_commands::*;
 use batch_commands::*;
+use benchmark_presets::{customize_benchmark, delete_custom_benchmark, list_builtin_benchmarks, list_custom_benchmarks, save_custom_benchmark, start_benchmark};
 use worktree_commands::*;
+use script_hooks::*;
+use session_analytics::*;
+use thread_feedback::*;
+use file_locks::*;
+use working_dir::*;
+use directory_listing::*;
+use safe_write::*;
+use trash::*;
+use session_merge::*;
+use stream_buffer::*;
+use session_activity::*;
+use process_suspend::*;
+use event_subscriptions::*;
+use commit_message::*;
+use worktree_selection::*;
+use merge_back::*;
+use path_scope::*;
+use session_diagnostics::*;
+use message_content::*;
+use feature_flags::{get_feature_flags, set_feature_flag};
+use self_test::run_self_test;
+use session_gc::{get_session_gc_config, run_session_gc, set_session_gc_config};
+use message_metrics::get_thread_metrics;
+use i18n::{get_locale, set_locale};
+use backups::{get_backup_config, list_backups, restore_from_backup, run_backup, set_backup_config};
+use remote_sync::{create_sync_key, get_sync_config, import_sync_key, run_sync, set_sync_config};
+use conflicts::{list_conflicts, resolve_conflict};
+use stream_quarantine::get_quarantined_events;
+use session_priority::{get_attention_queue, get_session_priority, set_session_priority};
+use session_snapshot::get_session_snapshot;
+use repo_relocation::{detect_moved_repositories, relocate_repository};
+use startup::get_startup_report;
+use startup_reconciliation::get_startup_reconciliation;
+use secret_providers::{delete_profile_secret, list_profile_secrets, save_profile_secret, test_profile_secrets};
+use toolbox_snapshots::{diff_toolbox_snapshots, list_toolbox_snapshots};
+use memory_manager::{get_memory_config, get_memory_stats, set_memory_config};
+use transact::transact;
+use operations::{cancel_operation, list_operations};
+use session_log::{tail_session_log, stop_session_log_tail};
+use session_diff::diff_sessions;
+use checkpoint_mounts::{list_checkpoint_mounts, mount_checkpoint, release_checkpoint};
+use command_metrics::{get_command_metrics, get_read_cache_stats};
+use ws_bridge::{get_bridge_status, get_bridge_token, rotate_bridge_token, set_bridge_config};
+use profile_quotas::{get_quota_status, set_profile_quota};
+use ui_state::{ui_state_get, ui_state_set, ui_state_subscribe, ui_state_unsubscribe};
+use url_ingest::fetch_context_url;
+use history_replay::{get_replay_settings, set_replay_settings, get_thread_replays};
+use staging_sync::{create_sync_rule, list_sync_rules, delete_sync_rule, set_sync_rule_enabled, run_sync_rule};
+use spawn_env_audit::{list_thread_spawn_envs, diff_thread_spawn_envs};
+use stack_detection::{get_session_stack, set_session_verify_commands};
+use env_isolation::{get_env_isolation, set_env_isolation};
+use repo_settings::{delete_repo_settings, get_repo_settings, set_repo_settings};
+use context_packs::{save_context_pack, list_context_packs, delete_context_pack, preview_context_pack, send_with_context_pack, get_context_injections};
 
 #[tauri::command]
 async fn spawn_orchestrator() -> Result<String, String> {
@@ -74,6 +214,15 @@ async fn toggle_maximize(window: Window) {
 }
 
 fn main() {
+    match headless::HeadlessArgs::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => std::process::exit(headless::run(args)),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}\n{}", e, headless::USAGE);
+            std::process::exit(2);
+        }
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
@@ -122,11 +271,197 @@ fn main() {
                         description: "add_threads_architecture",
                         sql: include_str!("../migrations/007_add_threads_architecture.sql"),
                         kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 8,
+                        description: "add_script_hooks",
+                        sql: include_str!("../migrations/008_script_hooks.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 9,
+                        description: "add_session_outcomes",
+                        sql: include_str!("../migrations/009_session_outcomes.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 10,
+                        description: "add_thread_feedback",
+                        sql: include_str!("../migrations/010_thread_feedback.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 11,
+                        description: "add_trash",
+                        sql: include_str!("../migrations/011_trash.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 12,
+                        description: "add_session_amp_profiles",
+                        sql: include_str!("../migrations/012_session_amp_profiles.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 13,
+                        description: "add_message_content_offload",
+                        sql: include_str!("../migrations/013_message_content_offload.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 14,
+                        description: "add_thread_archives",
+                        sql: include_str!("../migrations/014_thread_archives.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 15,
+                        description: "add_profile_defaults",
+                        sql: include_str!("../migrations/015_profile_defaults.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 16,
+                        description: "add_session_codes",
+                        sql: include_str!("../migrations/016_session_codes.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 17,
+                        description: "add_message_metrics",
+                        sql: include_str!("../migrations/017_message_metrics.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 18,
+                        description: "add_remote_sync",
+                        sql: include_str!("../migrations/018_remote_sync.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 19,
+                        description: "add_conflicts",
+                        sql: include_str!("../migrations/019_conflicts.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 20,
+                        description: "add_quarantined_events",
+                        sql: include_str!("../migrations/020_quarantined_events.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 21,
+                        description: "add_session_priorities",
+                        sql: include_str!("../migrations/021_session_priorities.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 22,
+                        description: "add_session_worktrees",
+                        sql: include_str!("../migrations/022_session_worktrees.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 23,
+                        description: "add_session_processes",
+                        sql: include_str!("../migrations/023_session_processes.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 24,
+                        description: "add_profile_secrets",
+                        sql: include_str!("../migrations/024_profile_secrets.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 25,
+                        description: "add_toolbox_snapshots",
+                        sql: include_str!("../migrations/025_toolbox_snapshots.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 26,
+                        description: "add_profile_quotas",
+                        sql: include_str!("../migrations/026_profile_quotas.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 27,
+                        description: "add_context_packs",
+                        sql: include_str!("../migrations/027_context_packs.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 28,
+                        description: "add_history_replay",
+                        sql: include_str!("../migrations/028_history_replay.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 29,
+                        description: "add_staging_sync",
+                        sql: include_str!("../migrations/029_staging_sync.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 30,
+                        description: "add_thread_spawn_envs",
+                        sql: include_str!("../migrations/030_thread_spawn_envs.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 31,
+                        description: "add_message_interrupts",
+                        sql: include_str!("../migrations/031_message_interrupts.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 32,
+                        description: "add_session_stacks",
+                        sql: include_str!("../migrations/032_session_stacks.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 33,
+                        description: "add_env_isolation",
+                        sql: include_str!("../migrations/033_env_isolation.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 34,
+                        description: "add_repo_settings",
+                        sql: include_str!("../migrations/034_repo_settings.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 35,
+                        description: "backfill_session_worktrees",
+                        sql: include_str!("../migrations/035_backfill_session_worktrees.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 36,
+                        description: "add_cache_versions",
+                        sql: include_str!("../migrations/036_cache_versions.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 37,
+                        description: "add_benchmark_presets",
+                        sql: include_str!("../migrations/037_benchmark_presets.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
+                    },
+                    tauri_plugin_sql::Migration {
+                        version: 38,
+                        description: "add_spawn_cli_versions",
+                        sql: include_str!("../migrations/038_spawn_cli_versions.sql"),
+                        kind: tauri_plugin_sql::MigrationKind::Up,
                     }
                 ])
                 .build()
         )
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(command_metrics::time_commands(crash_reports::track_commands(tauri::generate_handler![
             spawn_orchestrator, 
             close_window, 
             minimize_window, 
@@ -134,10 +469,14 @@ fn main() {
             save_file,
             read_file,
             write_file,
+            read_file_with_hash,
+            write_file_safe,
             get_current_branch,
             get_file_diff,
             spawn_terminal,
             list_directory,
+            list_directory_page,
+            list_directory_stream,
             open_file_in_vscode,
             parse_file_url,
             auth_status,
@@ -206,9 +545,14 @@ fn main() {
             cmd_write_stdin,
             cmd_resize,
             cmd_kill,
-            // Export commands
+            terminal_sync_cwd,
+            terminal_set_follow,
+            terminal_active_session_changed,
+            // Export and import commands
             export_sessions,
             export_sessions_to_file,
+            import_sessions,
+            import_sessions_from_file,
             // Thread-based session management commands
             new_session_create,
             thread_start,
@@ -217,7 +561,9 @@ fn main() {
             thread_session_commands::list_sessions,
             list_threads,
             thread_send_message,
+            thread_interrupt,
             thread_archive,
+            thread_unarchive,
             get_thread_history,
             // Enhanced session management commands (feature-gated)
             #[cfg(feature = "worktree-manager")]
@@ -232,28 +578,264 @@ fn main() {
             enhanced_session_commands::enhanced_session_status,
             #[cfg(feature = "worktree-manager")]
             enhanced_session_commands::enhanced_session_metrics,
+            #[cfg(feature = "worktree-manager")]
+            enhanced_session_commands::lint_environment,
+            #[cfg(feature = "worktree-manager")]
+            worktree_manager::estimate_session_worktree,
+            #[cfg(feature = "worktree-manager")]
+            worktree_manager::cancel_session_worktree,
             // Batch processing commands
             start_batch,
+            validate_batch,
+            estimate_batch,
             cancel_batch,
             get_batch_status,
+            get_batch_dag,
+            get_batch_internals,
             list_active_batches,
             get_batch_results,
+            get_batch_results_page,
+            get_batch_session_history,
+            write_batch_results,
+            merge_batch_results,
+            // Benchmark preset commands
+            list_builtin_benchmarks,
+            list_custom_benchmarks,
+            customize_benchmark,
+            save_custom_benchmark,
+            delete_custom_benchmark,
+            start_benchmark,
             // Git worktree management commands
             create_git_worktree,
             remove_git_worktree,
+            get_git_identity_config,
+            set_git_identity_config,
+            set_init_submodules,
             get_worktree_path,
             check_repository_clean,
-            list_git_worktrees
-        ])
+            list_git_worktrees,
+            // Commit message commands
+            generate_commit_message,
+            // Worktree change selection commands
+            list_worktree_changes,
+            stage_files,
+            unstage_files,
+            discard_files,
+            // Merge-back commands
+            check_branch_protection,
+            merge_back,
+            // Path scope commands
+            get_session_path_scope,
+            // Session diagnostics commands
+            diagnose_session,
+            // Message storage commands
+            compact_message_storage,
+            // Message metrics commands
+            get_thread_metrics,
+            // Localization commands
+            get_locale,
+            set_locale,
+            // Feature flag commands
+            get_feature_flags,
+            set_feature_flag,
+            // Self test commands
+            run_self_test,
+            // Session GC commands
+            run_session_gc,
+            get_session_gc_config,
+            set_session_gc_config,
+            // Backup commands
+            run_backup,
+            list_backups,
+            restore_from_backup,
+            get_backup_config,
+            set_backup_config,
+            // Sync commands
+            run_sync,
+            get_sync_config,
+            set_sync_config,
+            create_sync_key,
+            import_sync_key,
+            // Conflict commands
+            list_conflicts,
+            resolve_conflict,
+            // Stream quarantine commands
+            get_quarantined_events,
+            set_session_priority,
+            get_session_priority,
+            get_attention_queue,
+            get_session_snapshot,
+            detect_moved_repositories,
+            relocate_repository,
+            get_startup_reconciliation,
+            get_startup_report,
+            list_profile_secrets,
+            save_profile_secret,
+            delete_profile_secret,
+            test_profile_secrets,
+            list_toolbox_snapshots,
+            diff_toolbox_snapshots,
+            get_memory_stats,
+            get_memory_config,
+            set_memory_config,
+            app_log::get_recent_logs,
+            app_log::get_logging_config,
+            app_log::set_logging_config,
+            transact,
+            cancel_operation,
+            list_operations,
+            tail_session_log,
+            stop_session_log_tail,
+            diff_sessions,
+            mount_checkpoint,
+            release_checkpoint,
+            list_checkpoint_mounts,
+            get_command_metrics,
+            get_read_cache_stats,
+            get_bridge_status,
+            set_bridge_config,
+            get_bridge_token,
+            rotate_bridge_token,
+            get_quota_status,
+            set_profile_quota,
+            ui_state_get,
+            ui_state_set,
+            ui_state_subscribe,
+            ui_state_unsubscribe,
+            save_context_pack,
+            list_context_packs,
+            delete_context_pack,
+            preview_context_pack,
+            send_with_context_pack,
+            get_context_injections,
+            fetch_context_url,
+            get_replay_settings,
+            set_replay_settings,
+            get_thread_replays,
+            create_sync_rule,
+            list_sync_rules,
+            delete_sync_rule,
+            set_sync_rule_enabled,
+            run_sync_rule,
+            list_thread_spawn_envs,
+            version_matrix::get_version_matrix,
+            version_matrix::export_version_matrix,
+            process_limits::get_process_limits_config,
+            process_limits::set_process_limits_config,
+            diff_thread_spawn_envs,
+            get_session_stack,
+            set_session_verify_commands,
+            get_env_isolation,
+            set_env_isolation,
+            get_repo_settings,
+            set_repo_settings,
+            delete_repo_settings,
+            // Script hook commands
+            list_script_hooks,
+            save_script_hook,
+            delete_script_hook,
+            test_script_hook,
+            trigger_script_hooks,
+            get_session_tags,
+            list_webhook_keys,
+            set_webhook_key,
+            // Session analytics commands
+            get_session_outcome,
+            set_session_outcome,
+            get_session_outcome_stats,
+            get_classification_config,
+            set_classification_config,
+            // Thread feedback commands
+            thread_rate,
+            get_thread_feedback,
+            get_feedback_summary,
+            // File lock commands
+            list_file_locks,
+            check_file_lock,
+            list_file_conflicts,
+            resolve_file_conflict,
+            // Watch mode commands
+            watch_mode::add_watch_rule,
+            watch_mode::set_watch_rule_enabled,
+            watch_mode::remove_watch_rule,
+            watch_mode::list_watch_rules,
+            // Working directory commands
+            validate_working_directory,
+            get_allowed_roots,
+            set_allowed_roots,
+            // Trash commands
+            session_delete,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            // Session merge commands
+            thread_move,
+            session_merge,
+            // Stream replay commands
+            resume_stream,
+            // Session activity commands
+            get_session_activity,
+            get_stale_session_config,
+            set_stale_session_config,
+            // Process suspend commands
+            session_suspend,
+            session_resume,
+            session_status,
+            // Event subscription commands
+            subscribe_events,
+            unsubscribe_events,
+            list_event_subscriptions
+        ])))
         .manage(init_session_manager())
         .manage(init_process_manager())
         .manage(session_commands::init_amp_sessions())
         .manage(batch_commands::init_batch_engine_state())
+        .manage(services::ReadCache::default())
+        .manage(file_locks::FileLockService::default())
+        .manage(watch_mode::WatchModeService::default())
+        .manage(stream_buffer::StreamBuffers::default())
+        .manage(session_activity::ActivityTracker::default())
+        .manage(event_subscriptions::EventSubscriptions::default())
+        .manage(memory_manager::MemoryManager::default())
+        .manage(operations::OperationRegistry::default())
+        .manage(session_log::SessionLogs::default())
+        .manage(checkpoint_mounts::CheckpointMounts::default())
+        .manage(ws_bridge::WsBridge::default())
+        .manage(ui_state::UiStateSubscriptions::default())
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { .. } = event {
+                ui_state::save_window(window);
+            }
+            if let tauri::WindowEvent::Destroyed = event {
+                if let Some(subs) = window.try_state::<event_subscriptions::EventSubscriptions>() {
+                    subs.remove_webview(window.label());
+                }
+                if let Some(subs) = window.try_state::<ui_state::UiStateSubscriptions>() {
+                    subs.remove_webview(window.label());
+                }
+                if let Some(logs) = window.try_state::<session_log::SessionLogs>() {
+                    logs.remove_webview(window.label());
+                }
+                if let Some(mounts) = window.try_state::<checkpoint_mounts::CheckpointMounts>() {
+                    let (mounts, label) = (mounts.inner().clone(), window.label().to_string());
+                    tauri::async_runtime::spawn_blocking(move || checkpoint_mounts::release_webview_mounts(&mounts, &label));
+                }
+            }
+        })
         .setup(|app| { 
-            // Initialize app state with loaded configuration
+            app_log::install(app.handle());
+            crash_reports::install(app.handle().clone());
+            chaos::init_from_env();
+
             let config_state = init_app_state();
-            
-            tauri::async_runtime::block_on(async {
+            app.manage(config_state.clone());
+            let profile_manager = init_profile_manager(app.handle().clone());
+            app.manage(worktree_paths::WorktreePathResolver::new(profile_manager.db_pool.clone()));
+            app.manage(profile_manager);
+
+            // Each subsystem starts once what it needs is ready; see `startup`
+            let mut plan = startup::StartupPlan::new();
+            plan.add("config", &[], move || async move {
                 let mut config = AppConfig::load().await;
                 // Ensure default production environment when not set
                 let needs_default = config.connection_mode.is_none() && config.amp_env.is_empty();
@@ -262,102 +844,74 @@ fn main() {
                     config.amp_env.insert("AMP_BIN".to_string(), "amp".to_string());
                     let _ = config.save().await;
                 }
-                // Debug dump to logs/startup-env.log
-                if let Err(e) = std::fs::create_dir_all("/Users/sjarmak/amp-orchestra/logs") { eprintln!("[setup] failed to create logs dir: {}", e); }
-                let dump = format!("loaded config mode: {:?} amp_env: {:?}\n", config.connection_mode, config.amp_env);
-                if let Err(e) = std::fs::OpenOptions::new().create(true).append(true).open("/Users/sjarmak/amp-orchestra/logs/startup-env.log").and_then(|mut f| std::io::Write::write_all(&mut f, dump.as_bytes())) { eprintln!("[setup] failed to write startup-env.log: {}", e); }
+                app_log::configure(&config.logging);
+                log::debug!("Startup env: mode={:?} amp_env keys={:?}", config.connection_mode, config.amp_env.keys().collect::<Vec<_>>());
+                i18n::init(config.locale.as_deref());
                 if let Ok(mut state) = config_state.lock() {
                     *state = config;
                 }
+                Ok(())
             });
-            
-            app.manage(config_state);
-            
-            // Initialize profile manager
-            log::info!("setup: Initializing profile manager");
-            let profile_manager = init_profile_manager(app.handle().clone());
-            app.manage(profile_manager);
-            log::debug!("setup: Profile manager created and managed");
 
-            // Initialize worktree manager if feature enabled
+            let app_handle = app.handle().clone();
+            plan.add("database", &[], move || async move {
+                app_handle.state::<ProfileManager>().initialize_db().await.inspect_err(|e| {
+                    // The app still provides basic functionality without profiles
+                    log::warn!("setup: Application will continue without database functionality");
+                    let _ = app_handle.emit("database_error", e);
+                })
+            });
+            let app_handle = app.handle().clone();
+            plan.add("profiles", &["database"], move || async move {
+                app_handle.state::<ProfileManager>().load_profiles().await
+            });
+            // Reading window geometry needs the event loop, which setup holds up
+            let app_handle = app.handle().clone();
+            plan.defer("windows", &["database"], move || async move {
+                ui_state::restore_windows(&app_handle).await;
+                Ok(())
+            });
+            // Nothing waits on single toolbox paths being turned into profiles
+            let app_handle = app.handle().clone();
+            plan.defer("toolbox_migration", &["database"], move || async move {
+                use crate::toolbox_profiles::ToolboxProfileStore;
+                let db = app_handle.state::<ProfileManager>().db_pool.read().await.clone().ok_or("Database not available")?;
+                ToolboxProfileStore::new(db).migrate_single_paths().await.map_err(|e| e.to_string())
+            });
+
+            // The app can function without the worktree and enhanced session managers
             #[cfg(feature = "worktree-manager")]
             {
-                log::info!("setup: Initializing worktree manager");
-                match tauri::async_runtime::block_on(worktree_manager::init_worktree_manager()) {
-                    Ok(wt_manager) => {
-                        app.manage(wt_manager);
-                        log::info!("setup: Worktree manager initialized successfully");
-                    }
-                    Err(e) => {
-                        log::error!("setup: Failed to initialize worktree manager: {}", e);
-                        // Continue startup - app can function without worktree manager
-                    }
-                }
-
-                // Initialize enhanced session manager
-                log::info!("setup: Initializing enhanced session manager");
-                match tauri::async_runtime::block_on(enhanced_session_commands::init_enhanced_session_manager(app.handle())) {
-                    Ok(()) => {
-                        log::info!("setup: Enhanced session manager initialized successfully");
-                    }
-                    Err(e) => {
-                        log::error!("setup: Failed to initialize enhanced session manager: {}", e);
-                        // Continue startup - app can function without enhanced session manager
-                    }
-                }
-            }
-            
-            // Initialize database synchronously to prevent race conditions
-            log::debug!("setup: Starting blocking database initialization");
-            let app_handle = app.handle().clone();
-            if let Some(manager) = app_handle.try_state::<ProfileManager>() {
-                log::debug!("setup: Profile manager state acquired");
-                
-                // Attempt database initialization with graceful error handling
-                match tauri::async_runtime::block_on(manager.initialize_db()) {
-                    Ok(()) => {
-                        log::info!("setup: Database initialization completed successfully");
-                        
-                        // Load profiles after successful database initialization
-                        if let Err(e) = tauri::async_runtime::block_on(manager.load_profiles()) {
-                            log::error!("setup: Failed to load profiles: {}", e);
-                            // Continue startup even if profile loading fails
-                            // The app can still function without existing profiles
-                        } else {
-                            log::debug!("setup: Profiles loaded successfully");
-                        }
-                        
-                        // Run toolbox profile migration
-                        if let Some(db) = tauri::async_runtime::block_on(manager.db_pool.read()).as_ref() {
-                            use crate::toolbox_profiles::ToolboxProfileStore;
-                            let store = ToolboxProfileStore::new(db.clone());
-                            match tauri::async_runtime::block_on(store.migrate_single_paths()) {
-                                Ok(()) => log::info!("setup: Toolbox profile migration completed"),
-                                Err(e) => log::warn!("setup: Toolbox profile migration failed: {}", e),
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("setup: Database initialization failed: {}", e);
-                        log::warn!("setup: Application will continue without database functionality");
-                        
-                        // Emit an error event to the frontend so user knows about the issue
-                        let _ = app_handle.emit("database_error", &e);
-                        
-                        // Don't fail the entire app startup due to database issues
-                        // The app can still provide basic functionality without profiles
-                    }
-                }
-            } else {
-                log::error!("setup: Failed to acquire ProfileManager state");
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other, 
-                    "Failed to acquire ProfileManager state"
-                )));
+                let app_handle = app.handle().clone();
+                plan.add("session_managers", &[], move || async move {
+                    let worktrees = worktree_manager::init_worktree_manager().await.map(|wt_manager| {
+                        app_handle.manage(wt_manager);
+                    });
+                    // Sessions work without worktrees, so their manager starts either way
+                    enhanced_session_commands::init_enhanced_session_manager(&app_handle).await.map_err(|e| e.to_string())?;
+                    worktrees.map_err(|e| format!("worktree manager: {}", e))
+                });
             }
-            
+
+            tauri::async_runtime::block_on(plan.run(startup::publish));
+
             // Auto-start orchestrator on app launch
             tauri::async_runtime::spawn(spawn_orchestrator());
+            session_activity::spawn_monitor(app.handle().clone());
+            archive_compression::spawn_compressor(app.handle().clone());
+            session_gc::spawn_collector(app.handle().clone());
+            backups::spawn_scheduler(app.handle().clone());
+            remote_sync::spawn_syncer(app.handle().clone());
+            staging_sync::spawn_watcher(app.handle().clone());
+            ws_bridge::spawn_bridge(app.handle().clone());
+            repo_relocation::spawn_startup_check(app.handle().clone());
+            startup_reconciliation::spawn_startup_scan(app.handle().clone());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || checkpoint_mounts::sweep_stale_mounts(&handle));
+            let memory = app.state::<memory_manager::MemoryManager>();
+            memory.register(std::sync::Arc::new(app.state::<stream_buffer::StreamBuffers>().inner().clone()));
+            memory.register(std::sync::Arc::new(app.state::<session_log::SessionLogs>().inner().clone()));
+            memory_manager::spawn_monitor(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,319 @@
+//! Reclaiming idle in-memory caches
+//!
+//! Long-running instances keep accumulating cached data, most of it in the
+//! stream replay buffers. Subsystems register a `ReclaimableCache` listing their
+//! cached units (a session's buffer, say) with their size and last use. A
+//! background sweep adds them up and, above `MemoryConfig::ceiling_mb`, drops the
+//! least recently used until the total fits again.
+//!
+//! Units that can be rebuilt are dropped straight away. Replayable units, whose
+//! loss the UI would notice, are first announced in a `memory_pressure` event and
+//! only dropped on a later sweep if memory is still tight.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::{AppState, MemoryConfig};
+use crate::i18n::tr;
+
+/// Something a cache holds that can be dropped on its own
+#[derive(Debug, Clone)]
+pub struct CacheUnit {
+    pub key: String,
+    pub bytes: usize,
+    pub entries: usize,
+    pub last_used: Instant,
+    /// Dropping it loses data a client may still ask to replay
+    pub replayable: bool,
+}
+
+pub trait ReclaimableCache: Send + Sync {
+    fn subsystem(&self) -> &'static str;
+    fn units(&self) -> Vec<CacheUnit>;
+    /// Drop a unit, returning the bytes freed
+    fn evict(&self, key: &str) -> usize;
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubsystemStats {
+    pub subsystem: String,
+    pub bytes: usize,
+    pub units: usize,
+    pub entries: usize,
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub total_bytes: usize,
+    pub ceiling_bytes: usize,
+    pub subsystems: Vec<SubsystemStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReclaimedUnit {
+    pub subsystem: String,
+    pub key: String,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepOutcome {
+    pub evicted: Vec<ReclaimedUnit>,
+    /// Replayable units that will be dropped next sweep unless memory frees up
+    pub warned: Vec<ReclaimedUnit>,
+    pub total_bytes: usize,
+    pub ceiling_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryPressureEvent {
+    #[serde(flatten)]
+    pub outcome: SweepOutcome,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+#[derive(Default)]
+struct ManagerState {
+    caches: Vec<Arc<dyn ReclaimableCache>>,
+    warned: HashSet<(&'static str, String)>,
+    reclaimed: HashMap<&'static str, u64>,
+}
+
+#[derive(Clone, Default)]
+pub struct MemoryManager {
+    inner: Arc<Mutex<ManagerState>>,
+}
+
+impl MemoryManager {
+    pub fn register(&self, cache: Arc<dyn ReclaimableCache>) {
+        self.inner.lock().unwrap().caches.push(cache);
+    }
+
+    pub fn stats(&self, ceiling_bytes: usize) -> MemoryStats {
+        let state = self.inner.lock().unwrap();
+        let subsystems: Vec<SubsystemStats> = state
+            .caches
+            .iter()
+            .map(|cache| {
+                let units = cache.units();
+                SubsystemStats {
+                    subsystem: cache.subsystem().to_string(),
+                    bytes: units.iter().map(|u| u.bytes).sum(),
+                    units: units.len(),
+                    entries: units.iter().map(|u| u.entries).sum(),
+                    reclaimed_bytes: state.reclaimed.get(cache.subsystem()).copied().unwrap_or(0),
+                }
+            })
+            .collect();
+        MemoryStats { total_bytes: subsystems.iter().map(|s| s.bytes).sum(), ceiling_bytes, subsystems }
+    }
+
+    /// Drop least recently used units until the caches fit under `ceiling_bytes`.
+    /// A replayable unit is only dropped if the previous sweep warned about it.
+    pub fn sweep(&self, ceiling_bytes: usize) -> SweepOutcome {
+        let mut state = self.inner.lock().unwrap();
+        let mut units: Vec<(Arc<dyn ReclaimableCache>, CacheUnit)> = state
+            .caches
+            .iter()
+            .flat_map(|cache| cache.units().into_iter().map(move |unit| (cache.clone(), unit)))
+            .collect();
+        let mut total: usize = units.iter().map(|(_, u)| u.bytes).sum();
+        let mut outcome = SweepOutcome { ceiling_bytes, ..Default::default() };
+        let previously_warned = std::mem::take(&mut state.warned);
+        if total <= ceiling_bytes {
+            outcome.total_bytes = total;
+            return outcome;
+        }
+        units.sort_by_key(|(_, unit)| unit.last_used);
+
+        // Rebuildable data goes first, then replayable data that was already announced
+        let mut projected = total;
+        for replayable in [false, true] {
+            for (cache, unit) in units.iter().filter(|(_, u)| u.replayable == replayable) {
+                if projected <= ceiling_bytes {
+                    break;
+                }
+                let subsystem = cache.subsystem();
+                let id = (subsystem, unit.key.clone());
+                let reclaimed = ReclaimedUnit { subsystem: subsystem.to_string(), key: unit.key.clone(), bytes: unit.bytes };
+                if replayable && !previously_warned.contains(&id) {
+                    state.warned.insert(id);
+                    outcome.warned.push(reclaimed);
+                } else {
+                    let freed = cache.evict(&unit.key);
+                    total -= freed.min(total);
+                    *state.reclaimed.entry(subsystem).or_default() += freed as u64;
+                    outcome.evicted.push(reclaimed);
+                }
+                projected -= unit.bytes.min(projected);
+            }
+        }
+        outcome.total_bytes = total;
+        outcome
+    }
+}
+
+fn ceiling_bytes(config: &MemoryConfig) -> usize {
+    (config.ceiling_mb as usize).saturating_mul(1024 * 1024)
+}
+
+/// Sweep the registered caches for the lifetime of the app
+pub fn spawn_monitor(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = app_handle
+                .try_state::<AppState>()
+                .map(|state| state.lock().unwrap().memory.clone())
+                .unwrap_or_default();
+            tokio::time::sleep(Duration::from_secs(config.sweep_interval_secs.max(1))).await;
+            let Some(manager) = app_handle.try_state::<MemoryManager>() else {
+                continue;
+            };
+            let outcome = manager.sweep(ceiling_bytes(&config));
+            if outcome.evicted.is_empty() && outcome.warned.is_empty() {
+                continue;
+            }
+            let message = tr!(
+                "notify-memory-pressure",
+                evicted = outcome.evicted.len(),
+                warned = outcome.warned.len()
+            );
+            log::warn!("{}", message);
+            let _ = app_handle.emit("memory_pressure", MemoryPressureEvent { outcome, message });
+        }
+    });
+}
+
+/// Size of each subsystem's caches against the configured ceiling
+#[tauri::command]
+pub async fn get_memory_stats(
+    manager: State<'_, MemoryManager>,
+    app_state: State<'_, AppState>,
+) -> Result<MemoryStats, String> {
+    let config = app_state.lock().unwrap().memory.clone();
+    Ok(manager.stats(ceiling_bytes(&config)))
+}
+
+#[tauri::command]
+pub async fn get_memory_config(app_state: State<'_, AppState>) -> Result<MemoryConfig, String> {
+    Ok(app_state.lock().unwrap().memory.clone())
+}
+
+#[tauri::command]
+pub async fn set_memory_config(config: MemoryConfig, app_state: State<'_, AppState>) -> Result<(), String> {
+    if config.ceiling_mb == 0 {
+        return Err("Memory ceiling must be at least 1 MB".to_string());
+    }
+    if config.sweep_interval_secs == 0 {
+        return Err("Sweep interval must be at least one second".to_string());
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.memory = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeCache {
+        replayable: bool,
+        units: Mutex<Vec<(String, usize, Instant)>>,
+    }
+
+    impl FakeCache {
+        fn add(&self, key: &str, bytes: usize, age_secs: u64) {
+            let last_used = Instant::now() - Duration::from_secs(age_secs);
+            self.units.lock().unwrap().push((key.to_string(), bytes, last_used));
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.units.lock().unwrap().iter().map(|(k, _, _)| k.clone()).collect()
+        }
+    }
+
+    impl ReclaimableCache for FakeCache {
+        fn subsystem(&self) -> &'static str {
+            if self.replayable { "replayable" } else { "rebuildable" }
+        }
+
+        fn units(&self) -> Vec<CacheUnit> {
+            self.units
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(key, bytes, last_used)| CacheUnit {
+                    key: key.clone(),
+                    bytes: *bytes,
+                    entries: 1,
+                    last_used: *last_used,
+                    replayable: self.replayable,
+                })
+                .collect()
+        }
+
+        fn evict(&self, key: &str) -> usize {
+            let mut units = self.units.lock().unwrap();
+            let freed = units.iter().filter(|(k, _, _)| k == key).map(|(_, b, _)| *b).sum();
+            units.retain(|(k, _, _)| k != key);
+            freed
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_rebuildable_first() {
+        let manager = MemoryManager::default();
+        let rebuildable = Arc::new(FakeCache::default());
+        let replayable = Arc::new(FakeCache { replayable: true, ..Default::default() });
+        rebuildable.add("old", 40, 300);
+        rebuildable.add("new", 40, 10);
+        replayable.add("stream", 40, 600);
+        manager.register(rebuildable.clone());
+        manager.register(replayable.clone());
+
+        assert!(manager.sweep(200).evicted.is_empty());
+        let outcome = manager.sweep(90);
+        assert_eq!(outcome.evicted.iter().map(|u| u.key.as_str()).collect::<Vec<_>>(), vec!["old"]);
+        assert!(outcome.warned.is_empty());
+        assert_eq!(outcome.total_bytes, 80);
+        assert_eq!(rebuildable.keys(), vec!["new"]);
+        assert_eq!(replayable.keys(), vec!["stream"]);
+
+        let stats = manager.stats(90);
+        assert_eq!(stats.total_bytes, 80);
+        assert_eq!(stats.subsystems[0].reclaimed_bytes, 40);
+    }
+
+    #[test]
+    fn test_warns_before_dropping_replayable_data() {
+        let manager = MemoryManager::default();
+        let replayable = Arc::new(FakeCache { replayable: true, ..Default::default() });
+        replayable.add("s1", 50, 600);
+        replayable.add("s2", 50, 10);
+        manager.register(replayable.clone());
+
+        let first = manager.sweep(60);
+        assert!(first.evicted.is_empty());
+        assert_eq!(first.warned.iter().map(|u| u.key.as_str()).collect::<Vec<_>>(), vec!["s1"]);
+        assert_eq!(replayable.keys().len(), 2);
+
+        let second = manager.sweep(60);
+        assert_eq!(second.evicted.iter().map(|u| u.key.as_str()).collect::<Vec<_>>(), vec!["s1"]);
+        assert_eq!(replayable.keys(), vec!["s2"]);
+
+        // A warning lapses once memory is no longer tight
+        replayable.add("s3", 50, 700);
+        manager.sweep(60);
+        assert!(manager.sweep(1000).warned.is_empty());
+        assert!(manager.sweep(60).evicted.is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Offloading large message payloads to compressed blob files
+//!
+//! Stream events are stored whole in `messages.content`, and a single tool result
+//! can run to megabytes. Payloads above `OFFLOAD_THRESHOLD_BYTES` are written
+//! gzip-compressed to `message_blobs/<hash>.gz` in the app data directory and the
+//! row keeps only the hash in `content_ref`. Blobs are content-addressed, so
+//! identical payloads share a file. Readers go through `resolve_content`, which
+//! returns inline content as-is and loads offloaded content from its blob.
+//! `compact_message_storage` offloads rows written before this existed and
+//! removes blob files nothing refers to any more.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::State;
+
+use crate::message_metrics::{MessageMetrics, METRIC_COLUMNS};
+use crate::profile_auth::ProfileManager;
+use crate::safe_write::{content_hash, write_atomic};
+
+pub const BLOB_DIR_NAME: &str = "message_blobs";
+/// Payloads larger than this are moved out of the database
+pub const OFFLOAD_THRESHOLD_BYTES: usize = 32 * 1024;
+/// Rows offloaded per transaction during compaction
+const COMPACTION_BATCH: i64 = 200;
+/// Blobs written more recently than this are never swept: their message row
+/// may not be inserted yet
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60);
+
+static BLOB_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Set the blob directory; called once the database is open
+pub fn init_blob_dir(dir: PathBuf) {
+    if BLOB_DIR.set(dir).is_err() {
+        log::debug!("message blob directory already set");
+    }
+}
+
+/// Directory offloaded content is stored in, once set
+pub fn blob_dir() -> Option<&'static Path> {
+    BLOB_DIR.get().map(PathBuf::as_path)
+}
+
+/// File holding the blob for `content_ref`
+pub fn blob_file_name(content_ref: &str) -> String {
+    format!("{}.gz", content_ref)
+}
+
+/// Column values for a message row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredContent {
+    pub content: String,
+    pub content_ref: Option<String>,
+    pub content_size: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub rows_offloaded: u64,
+    /// Uncompressed bytes moved out of the database
+    pub bytes_offloaded: u64,
+    pub orphan_blobs_removed: u64,
+}
+
+fn blob_path(dir: &Path, content_ref: &str) -> PathBuf {
+    dir.join(blob_file_name(content_ref))
+}
+
+/// Decide how to store `content`, writing its blob when it is offloaded
+pub fn prepare(dir: &Path, content: &str) -> Result<StoredContent, String> {
+    let content_size = content.len() as i64;
+    if content.len() <= OFFLOAD_THRESHOLD_BYTES {
+        return Ok(StoredContent { content: content.to_string(), content_ref: None, content_size });
+    }
+    let content_ref = content_hash(content.as_bytes());
+    let path = blob_path(dir, &content_ref);
+    if path.exists() {
+        // Reusing a blob counts as writing it, so a sweep running now keeps it
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .map_err(|e| format!("Failed to refresh {}: {}", path.display(), e))?;
+    } else {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+        let compressed = encoder.finish().map_err(|e| e.to_string())?;
+        write_atomic(&path, &compressed, None, None).map_err(|e| e.to_string())?;
+    }
+    Ok(StoredContent { content: String::new(), content_ref: Some(content_ref), content_size })
+}
+
+/// Payload of a row given its `content` and `content_ref` columns
+pub fn load(dir: &Path, content: String, content_ref: Option<&str>) -> Result<String, String> {
+    let Some(content_ref) = content_ref else {
+        return Ok(content);
+    };
+    let path = blob_path(dir, content_ref);
+    let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut payload = String::new();
+    GzDecoder::new(file)
+        .read_to_string(&mut payload)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(payload)
+}
+
+/// `load` against the app's blob directory. A missing blob is logged and read
+/// as empty content so one lost file does not hide the rest of a thread.
+pub fn resolve_content(content: String, content_ref: Option<String>) -> String {
+    let Some(content_ref) = content_ref else {
+        return content;
+    };
+    let Some(dir) = BLOB_DIR.get() else {
+        log::warn!("message blob {} requested before the blob directory was set", content_ref);
+        return content;
+    };
+    load(dir, content, Some(&content_ref)).unwrap_or_else(|e| {
+        log::warn!("{}", e);
+        String::new()
+    })
+}
+
+/// `prepare` against the app's blob directory, storing inline when it is not set
+pub fn store_content(content: &str) -> Result<StoredContent, String> {
+    match BLOB_DIR.get() {
+        Some(dir) => prepare(dir, content),
+        None => Ok(StoredContent { content: content.to_string(), content_ref: None, content_size: content.len() as i64 }),
+    }
+}
+
+/// Insert a message, offloading its content when it is large
+pub async fn insert_message(db: &SqlitePool, id: &str, thread_id: &str, role: &str, content: &str) -> Result<(), String> {
+    insert_message_with_metrics(db, id, thread_id, role, content, &MessageMetrics::default()).await
+}
+
+/// `insert_message` with the message's latency and token counts
+pub async fn insert_message_with_metrics(
+    db: &SqlitePool,
+    id: &str,
+    thread_id: &str,
+    role: &str,
+    content: &str,
+    metrics: &MessageMetrics,
+) -> Result<(), String> {
+    let stored = store_content(content)?;
+    crate::chaos::delay_db_write().await;
+    sqlx::query(&format!(
+        "INSERT INTO messages (id, thread_id, role, content, content_ref, content_size, {}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        METRIC_COLUMNS
+    ))
+    .bind(id)
+    .bind(thread_id)
+    .bind(role)
+    .bind(&stored.content)
+    .bind(&stored.content_ref)
+    .bind(stored.content_size)
+    .bind(&metrics.turn_id)
+    .bind(metrics.latency_ms)
+    .bind(metrics.input_tokens)
+    .bind(metrics.output_tokens)
+    .bind(metrics.cache_read_tokens)
+    .bind(metrics.generation_ms)
+    .bind(metrics.cost_usd)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to store message: {}", e))?;
+    Ok(())
+}
+
+/// Offload inline rows above the threshold, then delete unreferenced blob files.
+/// Blobs named in a trash payload are kept so restored messages still resolve,
+/// as are blobs younger than `ORPHAN_MIN_AGE`.
+pub async fn compact(db: &SqlitePool, dir: &Path) -> Result<CompactionReport, String> {
+    let mut report = CompactionReport::default();
+    loop {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT id, content FROM messages WHERE content_ref IS NULL AND length(content) > ? LIMIT ?",
+        )
+        .bind(OFFLOAD_THRESHOLD_BYTES as i64)
+        .bind(COMPACTION_BATCH)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read messages: {}", e))?;
+        if rows.is_empty() {
+            break;
+        }
+        let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+        for (id, content) in rows {
+            let stored = prepare(dir, &content)?;
+            sqlx::query("UPDATE messages SET content = ?, content_ref = ?, content_size = ? WHERE id = ?")
+                .bind(&stored.content)
+                .bind(&stored.content_ref)
+                .bind(stored.content_size)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to update message {}: {}", id, e))?;
+            report.rows_offloaded += 1;
+            report.bytes_offloaded += stored.content_size as u64;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+
+    // Taken before the references are read: newer blobs may be missing from them
+    let cutoff = SystemTime::now() - ORPHAN_MIN_AGE;
+    let referenced: HashSet<String> = sqlx::query_scalar::<_, String>("SELECT DISTINCT content_ref FROM messages WHERE content_ref IS NOT NULL")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read blob references: {}", e))?
+        .into_iter()
+        .collect();
+    let trash_payloads: Vec<String> = sqlx::query_scalar("SELECT payload FROM trash_items")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read trash payloads: {}", e))?;
+
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let Some(content_ref) = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.strip_suffix(".gz")) else {
+            continue;
+        };
+        if referenced.contains(content_ref) || trash_payloads.iter().any(|p| p.contains(content_ref)) {
+            continue;
+        }
+        let old = entry.metadata().and_then(|meta| meta.modified()).is_ok_and(|modified| modified < cutoff);
+        if !old {
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            report.orphan_blobs_removed += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// Move large message payloads out of the database and drop unused blob files
+#[tauri::command]
+pub async fn compact_message_storage(profile_manager: State<'_, ProfileManager>) -> Result<CompactionReport, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let dir = BLOB_DIR.get().ok_or("Message blob directory not initialized")?;
+    compact(db, dir).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn test_pool() -> SqlitePool {
+        let pool = crate::schema::test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Refactor')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, agent_mode) VALUES ('t1', 's1', 'production', NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    #[test]
+    fn test_prepare_and_load_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let small = prepare(dir.path(), "hello").unwrap();
+        assert_eq!((small.content.as_str(), small.content_ref.as_deref()), ("hello", None));
+
+        let large = "tool output line\n".repeat(OFFLOAD_THRESHOLD_BYTES / 8);
+        let stored = prepare(dir.path(), &large).unwrap();
+        assert!(stored.content.is_empty());
+        assert_eq!(stored.content_size, large.len() as i64);
+        let blob = blob_path(dir.path(), stored.content_ref.as_deref().unwrap());
+        assert!(std::fs::metadata(&blob).unwrap().len() < large.len() as u64 / 10);
+        assert_eq!(load(dir.path(), stored.content, stored.content_ref.as_deref()).unwrap(), large);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_offloads_and_sweeps() {
+        let pool = test_pool().await;
+        let dir = TempDir::new().unwrap();
+        let large = format!("{{\"type\":\"user\",\"output\":\"{}\"}}", "x".repeat(OFFLOAD_THRESHOLD_BYTES));
+        for (id, content) in [("m1", "small"), ("m2", large.as_str())] {
+            sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES (?, 't1', 'user', ?)")
+                .bind(id)
+                .bind(content)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let orphan = blob_path(dir.path(), "orphan");
+        std::fs::write(&orphan, b"stale").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options().write(true).open(&orphan).unwrap().set_modified(an_hour_ago).unwrap();
+        // Just written, for a message about to be inserted
+        let pending = blob_path(dir.path(), "pending");
+        std::fs::write(&pending, b"new").unwrap();
+
+        let report = compact(&pool, dir.path()).await.unwrap();
+        assert_eq!((report.rows_offloaded, report.orphan_blobs_removed), (1, 1));
+        assert_eq!(report.bytes_offloaded, large.len() as u64);
+
+        let (content, content_ref): (String, Option<String>) =
+            sqlx::query_as("SELECT content, content_ref FROM messages WHERE id = 'm2'").fetch_one(&pool).await.unwrap();
+        assert!(content.is_empty());
+        assert_eq!(load(dir.path(), content, content_ref.as_deref()).unwrap(), large);
+        assert!(!orphan.exists() && pending.exists());
+
+        // A second run finds nothing to do
+        let report = compact(&pool, dir.path()).await.unwrap();
+        assert_eq!((report.rows_offloaded, report.orphan_blobs_removed), (0, 0));
+
+        // Without the trash to check against, nothing is swept
+        sqlx::query("DROP TABLE trash_items").execute(&pool).await.unwrap();
+        assert!(compact(&pool, dir.path()).await.unwrap_err().contains("trash"));
+    }
+}
@@ -0,0 +1,338 @@
+//! Per-message latency and token counts
+//!
+//! `thread_send_message` starts a turn clock for the thread. Every stream event
+//! stored while the turn is open records how long after the prompt it arrived
+//! and the token usage it reported; the turn closes on the `result` event,
+//! which stamps the prompt row with the total generation time and cost, or
+//! stays incomplete when `thread_interrupt` stops it early. The CLI emits
+//! whole messages rather than tokens, so time to first token is the latency of
+//! the turn's first assistant message. `get_thread_metrics` groups the rows
+//! back into turns.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use std::time::Instant;
+use tauri::State;
+
+use crate::profile_auth::ProfileManager;
+
+/// Metric columns of `messages`, in `MessageMetrics` field order
+pub const METRIC_COLUMNS: &str = "turn_id, latency_ms, input_tokens, output_tokens, cache_read_tokens, generation_ms, cost_usd";
+
+/// Open turns by thread: the prompt's message ID and when it was sent
+static TURNS: Lazy<DashMap<String, (String, Instant)>> = Lazy::new(DashMap::new);
+
+/// Metric columns of a message row
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct MessageMetrics {
+    /// Prompt message that started the turn
+    pub turn_id: Option<String>,
+    /// Milliseconds from the prompt to this message
+    pub latency_ms: Option<i64>,
+    pub input_tokens: Option<i64>,
+    pub output_tokens: Option<i64>,
+    pub cache_read_tokens: Option<i64>,
+    /// Prompt rows only: milliseconds from the prompt to the turn's result
+    pub generation_ms: Option<i64>,
+    /// Prompt rows only: cost reported with the turn's result
+    pub cost_usd: Option<f64>,
+}
+
+impl MessageMetrics {
+    fn with_usage(mut self, usage: Option<&serde_json::Value>) -> Self {
+        if let Some(usage) = usage {
+            let tokens = |key: &str| usage.get(key).and_then(|v| v.as_i64());
+            self.input_tokens = tokens("input_tokens");
+            self.output_tokens = tokens("output_tokens");
+            self.cache_read_tokens = tokens("cache_read_input_tokens");
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MessageTiming {
+    pub id: String,
+    pub role: String,
+    pub created_at: String,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub metrics: MessageMetrics,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnMetrics {
+    pub prompt_id: String,
+    pub started_at: String,
+    pub first_token_ms: Option<i64>,
+    /// Until the result, or the last message of a turn that never finished
+    pub generation_ms: Option<i64>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cost_usd: Option<f64>,
+    pub completed: bool,
+    pub messages: Vec<MessageTiming>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadMetrics {
+    pub thread_id: String,
+    pub turns: Vec<TurnMetrics>,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: f64,
+}
+
+/// Open a turn for a prompt about to be sent; returns the prompt row's metrics
+pub fn start_turn(thread_id: &str, prompt_id: &str) -> MessageMetrics {
+    TURNS.insert(thread_id.to_string(), (prompt_id.to_string(), Instant::now()));
+    MessageMetrics { turn_id: Some(prompt_id.to_string()), ..Default::default() }
+}
+
+/// Metrics for a stream event stored as a message
+pub fn observe(thread_id: &str, event: &serde_json::Value) -> MessageMetrics {
+    let metrics = match TURNS.get(thread_id) {
+        Some(turn) => MessageMetrics {
+            turn_id: Some(turn.0.clone()),
+            latency_ms: Some(turn.1.elapsed().as_millis() as i64),
+            ..Default::default()
+        },
+        None => MessageMetrics::default(),
+    };
+    metrics.with_usage(event.get("message").and_then(|m| m.get("usage")))
+}
+
+/// Close the thread's turn on its `result` event, recording totals on the prompt row.
+/// Usage reported with the result replaces the per-message sums.
+pub async fn finish_turn(db: &SqlitePool, thread_id: &str, result: &serde_json::Value) -> Result<(), String> {
+    let Some((_, (prompt_id, started))) = TURNS.remove(thread_id) else {
+        return Ok(());
+    };
+    let totals = MessageMetrics::default().with_usage(result.get("usage"));
+    let cost = result.get("total_cost_usd").or_else(|| result.get("cost_usd")).and_then(|v| v.as_f64());
+    sqlx::query(
+        "UPDATE messages SET generation_ms = ?, cost_usd = ?,
+             input_tokens = COALESCE(?, input_tokens), output_tokens = COALESCE(?, output_tokens),
+             cache_read_tokens = COALESCE(?, cache_read_tokens)
+         WHERE id = ?",
+    )
+    .bind(started.elapsed().as_millis() as i64)
+    .bind(cost)
+    .bind(totals.input_tokens)
+    .bind(totals.output_tokens)
+    .bind(totals.cache_read_tokens)
+    .bind(&prompt_id)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to record turn metrics: {}", e))?;
+    Ok(())
+}
+
+/// Prompt message of the thread's open turn, if a response is being generated
+pub fn open_turn(thread_id: &str) -> Option<String> {
+    TURNS.get(thread_id).map(|turn| turn.0.clone())
+}
+
+/// Close the thread's open turn without a result, marking its last message
+/// interrupted; the turn stays incomplete in the metrics. Returns that
+/// message's ID, or `None` when no turn was open.
+pub async fn interrupt_turn(db: &SqlitePool, thread_id: &str) -> Result<Option<String>, String> {
+    let Some((_, (prompt_id, _))) = TURNS.remove(thread_id) else {
+        return Ok(None);
+    };
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let message_id = sqlx::query_scalar::<_, String>(
+        "SELECT id FROM messages WHERE thread_id = ? AND turn_id = ? ORDER BY created_at DESC, rowid DESC LIMIT 1",
+    )
+    .bind(thread_id)
+    .bind(&prompt_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to find the interrupted message: {}", e))?
+    .unwrap_or(prompt_id);
+    sqlx::query("UPDATE messages SET interrupted_at = (datetime('now', 'utc') || 'Z') WHERE id = ?")
+        .bind(&message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to mark the message interrupted: {}", e))?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(Some(message_id))
+}
+
+/// Forget a thread's open turn once its process has exited
+pub fn end_thread(thread_id: &str) {
+    TURNS.remove(thread_id);
+}
+
+/// Group rows, oldest first, into turns; rows outside any turn are left out
+pub fn build_turns(rows: Vec<MessageTiming>) -> Vec<TurnMetrics> {
+    let mut turns: Vec<TurnMetrics> = Vec::new();
+    for row in rows {
+        let Some(turn_id) = row.metrics.turn_id.clone() else {
+            continue;
+        };
+        if row.id == turn_id {
+            turns.push(TurnMetrics {
+                prompt_id: turn_id,
+                started_at: row.created_at.clone(),
+                generation_ms: row.metrics.generation_ms,
+                cost_usd: row.metrics.cost_usd,
+                completed: row.metrics.generation_ms.is_some(),
+                messages: vec![row],
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(turn) = turns.iter_mut().rev().find(|t| t.prompt_id == turn_id) else {
+            continue;
+        };
+        if row.role == "assistant" && turn.first_token_ms.is_none() {
+            turn.first_token_ms = row.metrics.latency_ms;
+        }
+        turn.messages.push(row);
+    }
+
+    for turn in &mut turns {
+        let (prompt, replies) = turn.messages.split_first().unwrap();
+        let sum = |f: fn(&MessageMetrics) -> Option<i64>| replies.iter().filter_map(|m| f(&m.metrics)).sum::<i64>();
+        let input_tokens = prompt.metrics.input_tokens.unwrap_or_else(|| sum(|m| m.input_tokens));
+        let output_tokens = prompt.metrics.output_tokens.unwrap_or_else(|| sum(|m| m.output_tokens));
+        let cache_read_tokens = prompt.metrics.cache_read_tokens.unwrap_or_else(|| sum(|m| m.cache_read_tokens));
+        turn.input_tokens = input_tokens;
+        turn.output_tokens = output_tokens;
+        turn.cache_read_tokens = cache_read_tokens;
+        if !turn.completed {
+            turn.generation_ms = turn.messages.iter().filter_map(|m| m.metrics.latency_ms).max();
+        }
+    }
+    turns
+}
+
+/// Per-turn breakdown of a thread, including messages held in its archive
+pub async fn thread_metrics(db: &SqlitePool, thread_id: &str) -> Result<ThreadMetrics, String> {
+    let mut rows: Vec<MessageTiming> = crate::archive_compression::archived_messages(db, thread_id)
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|m| MessageTiming { id: m.id, role: m.role, created_at: m.created_at, metrics: m.metrics })
+        .collect();
+    let live = sqlx::query_as::<_, MessageTiming>(&format!(
+        "SELECT id, role, created_at, {} FROM messages WHERE thread_id = ? ORDER BY created_at ASC, rowid ASC",
+        METRIC_COLUMNS
+    ))
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read message metrics: {}", e))?;
+    rows.extend(live);
+
+    let turns = build_turns(rows);
+    Ok(ThreadMetrics {
+        thread_id: thread_id.to_string(),
+        input_tokens: turns.iter().map(|t| t.input_tokens).sum(),
+        output_tokens: turns.iter().map(|t| t.output_tokens).sum(),
+        cost_usd: turns.iter().filter_map(|t| t.cost_usd).sum(),
+        turns,
+    })
+}
+
+/// Latency, token and cost breakdown of a thread, one entry per prompt
+#[tauri::command]
+pub async fn get_thread_metrics(
+    thread_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ThreadMetrics, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    thread_metrics(db, &thread_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_content::insert_message_with_metrics;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_turns_record_latency_and_usage() {
+        let pool = test_pool().await;
+
+        let thread = "metrics-t1";
+        // Output before any prompt is stored without metrics
+        let stray = observe(thread, &serde_json::json!({ "type": "system" }));
+        insert_message_with_metrics(&pool, "m0", thread, "system", "{}", &stray).await.unwrap();
+
+        for (prompt, reply, result_usage) in [("p1", "a1", true), ("p2", "a2", false)] {
+            let metrics = start_turn(thread, prompt);
+            insert_message_with_metrics(&pool, prompt, thread, "user", "{}", &metrics).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            let event = serde_json::json!({
+                "type": "assistant",
+                "message": { "usage": { "input_tokens": 100, "output_tokens": 40, "cache_read_input_tokens": 60 } }
+            });
+            let metrics = observe(thread, &event);
+            assert!(metrics.latency_ms.unwrap() >= 20);
+            insert_message_with_metrics(&pool, reply, thread, "assistant", "{}", &metrics).await.unwrap();
+            if result_usage {
+                let result = serde_json::json!({
+                    "type": "result",
+                    "total_cost_usd": 0.25,
+                    "usage": { "input_tokens": 150, "output_tokens": 50 }
+                });
+                finish_turn(&pool, thread, &result).await.unwrap();
+            }
+        }
+        end_thread(thread);
+
+        let metrics = thread_metrics(&pool, thread).await.unwrap();
+        assert_eq!(metrics.turns.len(), 2);
+        let (first, second) = (&metrics.turns[0], &metrics.turns[1]);
+        assert!(first.completed && !second.completed);
+        assert_eq!((first.input_tokens, first.output_tokens, first.cache_read_tokens), (150, 50, 60));
+        assert_eq!((second.input_tokens, second.output_tokens), (100, 40));
+        assert!(first.generation_ms.unwrap() >= first.first_token_ms.unwrap());
+        assert_eq!(second.generation_ms, second.first_token_ms);
+        assert_eq!(first.messages.len(), 2);
+        assert_eq!((metrics.input_tokens, metrics.cost_usd), (250, 0.25));
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_marks_last_message_of_open_turn() {
+        let pool = test_pool().await;
+        let interrupted = |id: &'static str| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_scalar::<_, Option<String>>("SELECT interrupted_at FROM messages WHERE id = ?")
+                    .bind(id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap()
+                    .is_some()
+            }
+        };
+
+        let thread = "interrupt-t1";
+        assert_eq!(interrupt_turn(&pool, thread).await.unwrap(), None);
+
+        let metrics = start_turn(thread, "p1");
+        insert_message_with_metrics(&pool, "p1", thread, "user", "{}", &metrics).await.unwrap();
+        assert_eq!(open_turn(thread).as_deref(), Some("p1"));
+        for reply in ["a1", "a2"] {
+            let metrics = observe(thread, &serde_json::json!({ "type": "assistant" }));
+            insert_message_with_metrics(&pool, reply, thread, "assistant", "{}", &metrics).await.unwrap();
+        }
+        assert_eq!(interrupt_turn(&pool, thread).await.unwrap().as_deref(), Some("a2"));
+        assert!(interrupted("a2").await && !interrupted("a1").await);
+        assert_eq!(open_turn(thread), None);
+        assert!(!thread_metrics(&pool, thread).await.unwrap().turns[0].completed);
+
+        // With nothing generated yet the prompt itself is marked
+        let metrics = start_turn(thread, "p2");
+        insert_message_with_metrics(&pool, "p2", thread, "user", "{}", &metrics).await.unwrap();
+        assert_eq!(interrupt_turn(&pool, thread).await.unwrap().as_deref(), Some("p2"));
+        assert!(interrupted("p2").await);
+    }
+}
@@ -0,0 +1,232 @@
+//! Long-running commands that can be cancelled
+//!
+//! A long-running command registers an operation, returns its id straight away
+//! and does the work in the background. Progress and the final result arrive as
+//! `operation_progress` events carrying that id. `cancel_operation` trips the
+//! operation's `CancellationToken`: the work is dropped at its next await point,
+//! which kills any child process it spawned, and the token is handed to the core
+//! APIs the work calls so they can stop on their own.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationInfo {
+    pub id: String,
+    /// Command that started the operation, such as `install_global_cli`
+    pub kind: String,
+    pub status: OperationStatus,
+    pub message: Option<String>,
+    /// Fraction done between 0 and 1, when the work can tell
+    pub progress: Option<f32>,
+    /// Figures specific to the work, such as rows and bytes written
+    pub detail: Option<Value>,
+    /// Set once completed
+    pub result: Option<Value>,
+    /// Set once failed
+    pub error: Option<String>,
+    pub started_at: String,
+}
+
+struct Entry {
+    info: OperationInfo,
+    token: CancellationToken,
+}
+
+/// Operations still running
+#[derive(Clone, Default)]
+pub struct OperationRegistry {
+    inner: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// A running operation's handle, given to its work
+#[derive(Clone)]
+pub struct Operation {
+    pub id: String,
+    pub token: CancellationToken,
+    registry: OperationRegistry,
+    app_handle: Option<AppHandle>,
+}
+
+impl Operation {
+    fn emit(&self, info: &OperationInfo) {
+        if let Some(app_handle) = &self.app_handle {
+            let _ = app_handle.emit("operation_progress", info);
+        }
+    }
+
+    /// Report what the work is doing
+    pub fn progress(&self, message: impl Into<String>, progress: Option<f32>) {
+        self.update(message.into(), progress, None);
+    }
+
+    /// Report progress along with figures specific to the work
+    pub fn progress_detail(&self, message: impl Into<String>, progress: Option<f32>, detail: Value) {
+        self.update(message.into(), progress, Some(detail));
+    }
+
+    fn update(&self, message: String, progress: Option<f32>, detail: Option<Value>) {
+        let info = {
+            let mut operations = self.registry.inner.lock().unwrap();
+            let Some(entry) = operations.get_mut(&self.id) else {
+                return;
+            };
+            entry.info.message = Some(message);
+            entry.info.progress = progress.map(|p| p.clamp(0.0, 1.0));
+            if detail.is_some() {
+                entry.info.detail = detail;
+            }
+            entry.info.clone()
+        };
+        self.emit(&info);
+    }
+
+    /// Record the outcome and drop the operation from the registry. A result that
+    /// arrives after cancellation is reported as cancelled.
+    pub fn finish(&self, result: Result<Value, String>) -> Option<OperationInfo> {
+        let mut info = self.registry.inner.lock().unwrap().remove(&self.id)?.info;
+        match result {
+            _ if self.token.is_cancelled() => info.status = OperationStatus::Cancelled,
+            Ok(value) => {
+                info.status = OperationStatus::Completed;
+                info.progress = Some(1.0);
+                info.result = Some(value);
+            }
+            Err(e) => {
+                info.status = OperationStatus::Failed;
+                info.error = Some(e);
+            }
+        }
+        self.emit(&info);
+        Some(info)
+    }
+}
+
+impl OperationRegistry {
+    pub fn start(&self, kind: &str, app_handle: Option<AppHandle>) -> Operation {
+        let id = Uuid::new_v4().to_string();
+        let token = CancellationToken::new();
+        let info = OperationInfo {
+            id: id.clone(),
+            kind: kind.to_string(),
+            status: OperationStatus::Running,
+            message: None,
+            progress: None,
+            detail: None,
+            result: None,
+            error: None,
+            started_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.inner.lock().unwrap().insert(id.clone(), Entry { info: info.clone(), token: token.clone() });
+        let operation = Operation { id, token, registry: self.clone(), app_handle };
+        operation.emit(&info);
+        operation
+    }
+
+    /// Run `work` in the background as a new operation and return its id
+    pub fn spawn<F, Fut>(&self, kind: &str, app_handle: Option<AppHandle>, work: F) -> String
+    where
+        F: FnOnce(Operation) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Value, String>> + Send + 'static,
+    {
+        let operation = self.start(kind, app_handle);
+        let id = operation.id.clone();
+        tauri::async_runtime::spawn(async move {
+            let token = operation.token.clone();
+            let result = tokio::select! {
+                result = work(operation.clone()) => result,
+                _ = token.cancelled() => Err("Cancelled".to_string()),
+            };
+            operation.finish(result);
+        });
+        id
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let operations = self.inner.lock().unwrap();
+        let entry = operations.get(id).ok_or_else(|| format!("Operation {} is not running", id))?;
+        entry.token.cancel();
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<OperationInfo> {
+        let mut infos: Vec<OperationInfo> = self.inner.lock().unwrap().values().map(|e| e.info.clone()).collect();
+        infos.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        infos
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_operation(op_id: String, operations: State<'_, OperationRegistry>) -> Result<(), String> {
+    operations.cancel(&op_id)
+}
+
+#[tauri::command]
+pub async fn list_operations(operations: State<'_, OperationRegistry>) -> Result<Vec<OperationInfo>, String> {
+    Ok(operations.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress_and_finish() {
+        let registry = OperationRegistry::default();
+        let op = registry.start("export", None);
+        op.progress("Reading", Some(1.5));
+        let listed = registry.list();
+        assert_eq!((listed[0].message.as_deref(), listed[0].progress), (Some("Reading"), Some(1.0)));
+        op.progress_detail("Writing", Some(0.5), serde_json::json!({ "bytes": 10 }));
+        op.progress("Still writing", Some(0.6));
+        assert_eq!(registry.list()[0].detail, Some(serde_json::json!({ "bytes": 10 })));
+
+        let info = op.finish(Ok(Value::from("done"))).unwrap();
+        assert_eq!(info.status, OperationStatus::Completed);
+        assert!(registry.list().is_empty());
+        assert!(op.finish(Ok(Value::Null)).is_none());
+        assert!(registry.cancel(&op.id).is_err());
+
+        let failed = registry.start("install", None);
+        assert_eq!(failed.finish(Err("npm missing".into())).unwrap().error.as_deref(), Some("npm missing"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_spawned_work() {
+        let registry = OperationRegistry::default();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let id = registry.spawn("slow", None, |_op| async move {
+            let _dropped_on_cancel = tx;
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(Value::Null)
+        });
+        assert_eq!(registry.list()[0].status, OperationStatus::Running);
+
+        registry.cancel(&id).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), rx).await.unwrap().unwrap_err();
+        for _ in 0..50 {
+            if registry.list().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+//! Restricting a session to part of a monorepo
+//!
+//! A session created with a path scope gets a cone-mode sparse checkout holding
+//! only those directories, so the agent sees a small tree. The sparse-checkout
+//! list is the stored form of the scope: `read_scope` recovers it from any
+//! worktree. Staging and merge-back refuse files outside the scope, and a tool
+//! call writing outside it emits a `scope_violation` event.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+use tauri::{AppHandle, Emitter};
+
+use crate::commit_message::git;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathScope {
+    paths: Vec<String>,
+}
+
+impl PathScope {
+    /// Normalize a list of repository-relative directories
+    pub fn new(paths: &[String]) -> Result<Self, String> {
+        let mut normalized = Vec::new();
+        for raw in paths {
+            let trimmed = raw.trim().trim_start_matches("./").trim_end_matches('/');
+            let path = Path::new(trimmed);
+            if trimmed.is_empty() || path.is_absolute() || path.components().any(|c| !matches!(c, Component::Normal(_))) {
+                return Err(format!("Scope paths must be directories inside the repository: {}", raw));
+            }
+            let portable = path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            normalized.push(portable);
+        }
+        if normalized.is_empty() {
+            return Err("Path scope needs at least one directory".to_string());
+        }
+        normalized.sort();
+        normalized.dedup();
+        Ok(Self { paths: normalized })
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Whether a repository-relative path lies inside one of the scope directories
+    pub fn contains(&self, relative: &Path) -> bool {
+        self.paths.iter().any(|dir| relative.starts_with(dir))
+    }
+
+    /// Whether `path`, absolute or relative to `root`, lies inside the scope
+    pub fn contains_under(&self, root: &Path, path: &Path) -> bool {
+        let absolute = if path.is_absolute() { path.to_path_buf() } else { root.join(path) };
+        absolute.strip_prefix(root).is_ok_and(|relative| self.contains(relative))
+    }
+
+    /// Paths outside the scope, in input order
+    pub fn outside<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        paths.into_iter().filter(|p| !self.contains(Path::new(p))).map(str::to_string).collect()
+    }
+}
+
+/// Limit a freshly added worktree's checkout to the scope. The worktree must have
+/// been added with `--no-checkout`; the scoped files are checked out here.
+pub fn apply_sparse_checkout(worktree: &Path, scope: &PathScope) -> Result<(), String> {
+    let mut args = vec!["sparse-checkout", "set", "--cone", "--"];
+    args.extend(scope.paths().iter().map(String::as_str));
+    git(worktree, &args)?;
+    git(worktree, &["reset", "--quiet", "--hard", "HEAD"])?;
+    Ok(())
+}
+
+/// Scope of a worktree, or `None` when it has a full checkout
+pub fn read_scope(worktree: &Path) -> Option<PathScope> {
+    let listed = git(worktree, &["sparse-checkout", "list"]).ok()?;
+    let paths: Vec<String> = listed.lines().map(str::to_string).filter(|l| !l.is_empty()).collect();
+    PathScope::new(&paths).ok()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeViolation {
+    pub session_id: String,
+    pub path: String,
+    pub scope: Vec<String>,
+    pub detected_at: String,
+}
+
+/// Warn about an agent write outside the session's scope
+pub fn report_violation(app: &AppHandle, session_id: &str, path: &Path, scope: &PathScope) {
+    log::warn!("Session {} is writing {} outside its path scope {:?}", session_id, path.display(), scope.paths());
+    let violation = ScopeViolation {
+        session_id: session_id.to_string(),
+        path: path.to_string_lossy().to_string(),
+        scope: scope.paths().to_vec(),
+        detected_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let _ = app.emit("scope_violation", &violation);
+}
+
+/// Directories a session's worktree is limited to; `None` for a full checkout
+#[tauri::command]
+pub async fn get_session_path_scope(session_id: String, app_handle: AppHandle) -> Result<Option<Vec<String>>, String> {
+    let worktree = crate::commit_message::session_worktree(&app_handle, &session_id).await?;
+    Ok(read_scope(&worktree).map(|scope| scope.paths().to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(paths: &[&str]) -> PathScope {
+        PathScope::new(&paths.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_scope_normalizes_and_matches() {
+        let scope = scope(&["./services/api/", "libs/shared", "libs/shared"]);
+        assert_eq!(scope.paths(), ["libs/shared", "services/api"]);
+        assert!(scope.contains(Path::new("services/api/src/main.rs")));
+        assert!(!scope.contains(Path::new("services/api-gateway/main.rs")));
+        assert!(!scope.contains(Path::new("package.json")));
+
+        let root = Path::new("/work/repo");
+        assert!(scope.contains_under(root, Path::new("/work/repo/libs/shared/x.ts")));
+        assert!(scope.contains_under(root, Path::new("libs/shared/x.ts")));
+        assert!(!scope.contains_under(root, Path::new("/elsewhere/libs/shared/x.ts")));
+        assert_eq!(scope.outside(["libs/shared/a", "web/b"]), vec!["web/b".to_string()]);
+
+        for bad in ["../outside", "/abs", "a/../b", " "] {
+            assert!(PathScope::new(&[bad.to_string()]).is_err(), "{}", bad);
+        }
+        assert!(PathScope::new(&[]).is_err());
+    }
+}
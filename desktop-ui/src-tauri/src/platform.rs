@@ -0,0 +1,162 @@
+//! Platform differences in paths, environment variables, shells and CLI locations
+//!
+//! Everything that differs between Windows and Unix lives here so callers can
+//! stay free of `cfg` blocks: the `PATH` list separator, where the home
+//! directory and shell profiles are, which shell a terminal starts, and
+//! where an Amp CLI is usually installed.
+
+use std::path::{Path, PathBuf};
+
+/// Separator between entries of `PATH`-like variables
+pub const ENV_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+/// Split a `PATH`-like list, dropping empty entries. Unix also accepts `,` for
+/// convenience; on Windows `:` is part of drive letters so only `;` splits.
+pub fn split_env_paths(s: &str) -> Vec<String> {
+    s.split(|c| c == ENV_SEPARATOR || (!cfg!(windows) && c == ','))
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+pub fn join_env_paths<S: AsRef<str>>(paths: &[S]) -> String {
+    paths.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(&ENV_SEPARATOR.to_string())
+}
+
+/// Put `dir` first in a `PATH`-like list
+pub fn prepend_env_path(dir: &str, list: &str) -> String {
+    if list.is_empty() {
+        dir.to_string()
+    } else {
+        format!("{}{}{}", dir, ENV_SEPARATOR, list)
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    dirs::home_dir().or_else(|| std::env::var_os(var).map(PathBuf::from))
+}
+
+/// Shell a terminal starts when none is configured
+pub fn default_shell() -> String {
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
+    }
+}
+
+/// Shell profiles that may export variables, most specific first
+pub fn shell_config_files(home: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let documents = dirs::document_dir().unwrap_or_else(|| home.join("Documents"));
+        vec![
+            documents.join("PowerShell").join("Microsoft.PowerShell_profile.ps1"),
+            documents.join("WindowsPowerShell").join("Microsoft.PowerShell_profile.ps1"),
+        ]
+    } else {
+        // macOS zsh users commonly put exports in .zprofile
+        [".zprofile", ".zshrc", ".bashrc", ".bash_profile", ".profile"]
+            .iter()
+            .map(|name| home.join(name))
+            .collect()
+    }
+}
+
+/// Value `line` assigns to `var`, for `export VAR=value` (sh) and
+/// `$env:VAR = "value"` (PowerShell), with surrounding quotes removed
+pub fn shell_export_value<'a>(line: &'a str, var: &str) -> Option<&'a str> {
+    let line = line.trim();
+    let value = if let Some(rest) = line.strip_prefix("export ") {
+        rest.trim_start().strip_prefix(var)?.strip_prefix('=')?
+    } else {
+        let rest = line.strip_prefix("$env:")?.strip_prefix(var)?.trim_start();
+        rest.strip_prefix('=')?.trim_start()
+    };
+    Some(value.trim_end().trim_matches('"').trim_matches('\''))
+}
+
+pub fn cli_binary_name() -> &'static str {
+    if cfg!(windows) {
+        "amp.exe"
+    } else {
+        "amp"
+    }
+}
+
+/// Command that prints where a program on `PATH` lives
+pub fn which_command() -> &'static str {
+    if cfg!(windows) {
+        "where"
+    } else {
+        "which"
+    }
+}
+
+/// Node entry point of a development checkout in `~/amp`
+pub fn dev_cli_path(home: &Path) -> PathBuf {
+    home.join("amp").join("cli").join("dist").join("main.js")
+}
+
+/// Development builds in `~/amp`, Node entry point first
+pub fn dev_cli_locations(home: &Path) -> Vec<PathBuf> {
+    let amp = home.join("amp");
+    vec![
+        dev_cli_path(home),
+        amp.join("bin").join(cli_binary_name()),
+        amp.join("target").join("release").join(cli_binary_name()),
+        amp.join("target").join("debug").join(cli_binary_name()),
+    ]
+}
+
+/// Where package managers put a global `amp` when it is not on `PATH`
+pub fn global_cli_locations(home: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let npm = dirs::data_dir().unwrap_or_else(|| home.join("AppData").join("Roaming")).join("npm");
+        vec![npm.join("amp.cmd"), npm.join("amp.exe")]
+    } else {
+        vec![
+            home.join(".local").join("bin").join("amp"),
+            home.join(".npm-global").join("bin").join("amp"),
+            PathBuf::from("/usr/local/bin/amp"),
+            PathBuf::from("/opt/homebrew/bin/amp"),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_export_value() {
+        assert_eq!(shell_export_value("export AMP_API_KEY=\"abc\"", "AMP_API_KEY"), Some("abc"));
+        assert_eq!(shell_export_value("  export AMP_API_KEY='abc'", "AMP_API_KEY"), Some("abc"));
+        assert_eq!(shell_export_value("$env:AMP_API_KEY = \"abc\"", "AMP_API_KEY"), Some("abc"));
+        assert_eq!(shell_export_value("export AMP_API_KEY_OLD=abc", "AMP_API_KEY"), None);
+        assert_eq!(shell_export_value("# export AMP_API_KEY=abc", "AMP_API_KEY"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_paths() {
+        assert_eq!(split_env_paths("/a/bin:/b/bin,/c::"), ["/a/bin", "/b/bin", "/c"]);
+        assert_eq!(join_env_paths(&["/a", "/b"]), "/a:/b");
+        assert_eq!(prepend_env_path("/tb/bin", "/usr/bin"), "/tb/bin:/usr/bin");
+        assert_eq!(prepend_env_path("/tb/bin", ""), "/tb/bin");
+        let home = Path::new("/home/u");
+        assert!(shell_config_files(home).contains(&home.join(".zshrc")));
+        assert_eq!(dev_cli_locations(home)[0], Path::new("/home/u/amp/cli/dist/main.js"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_paths() {
+        assert_eq!(split_env_paths("C:\\a\\bin;D:\\b;;"), ["C:\\a\\bin", "D:\\b"]);
+        assert_eq!(join_env_paths(&["C:\\a", "C:\\b"]), "C:\\a;C:\\b");
+        assert_eq!(prepend_env_path("C:\\tb\\bin", "C:\\Windows"), "C:\\tb\\bin;C:\\Windows");
+        let home = Path::new("C:\\Users\\u");
+        assert!(shell_config_files(home).iter().all(|p| p.extension().is_some_and(|e| e == "ps1")));
+        assert!(dev_cli_locations(home)[1].ends_with("amp.exe"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Resource limits on chat session and thread processes
+//!
+//! Each process starts with the open-file limit from `AppConfig::process_limits`
+//! and is watched for its memory and execution-time limits while its output is
+//! read; all three are off unless configured. Time suspended doesn't count, and
+//! memory is only checked on Linux. One that passes a limit is dropped from the
+//! session map and killed; its session then reports `SessionStatus::Error` and
+//! `process_limit_exceeded` is emitted. Batch cases are held to their batch's
+//! timeout and their session's limits by the batch engine, which lists the cases
+//! it stopped in its progress.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use unified_core::{LimitExceeded, ProcessLimits};
+
+use crate::app_state::{AppState, ProcessLimitsConfig};
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+use crate::session_activity::{ActivityState, ActivityTracker};
+use crate::session_commands::AmpSessionMap;
+use crate::session_log::{LogCategory, LogLevel};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessLimitExceededEvent {
+    pub session_id: String,
+    /// "chat", "thread" or "batch"
+    pub kind: String,
+    #[serde(flatten)]
+    pub exceeded: LimitExceeded,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+impl ProcessLimitExceededEvent {
+    pub fn new(session_id: &str, kind: &str, exceeded: LimitExceeded) -> Self {
+        let session = crate::session_codes::code_for(session_id).unwrap_or_else(|| session_id.to_string());
+        let message = match &exceeded {
+            LimitExceeded::Memory { used_mb, limit_mb } => {
+                tr!("notify-process-limit-memory", session = session, used = used_mb, limit = limit_mb)
+            }
+            LimitExceeded::ExecutionTime { limit_secs } => tr!("notify-process-limit-time", session = session, limit = limit_secs),
+        };
+        Self { session_id: session_id.to_string(), kind: kind.to_string(), exceeded, message }
+    }
+}
+
+fn configured(app_handle: &AppHandle) -> ProcessLimits {
+    app_handle
+        .try_state::<AppState>()
+        .map(|state| state.lock().unwrap().process_limits.clone())
+        .unwrap_or_default()
+        .limits()
+}
+
+/// Set the configured open-file limit on a chat or thread process about to be spawned
+pub fn apply(app_handle: &AppHandle, command: &mut Command) {
+    unified_core::process_limits::apply_rlimits(command, &configured(app_handle));
+}
+
+/// Stop process `pid` of a chat session or thread once it passes the configured
+/// limits. Abort the returned task when the process's output ends.
+pub fn watch(app_handle: &AppHandle, session_id: &str, kind: &'static str, pid: Option<u32>) -> JoinHandle<()> {
+    let limits = configured(app_handle);
+    let tracker = app_handle.try_state::<ActivityTracker>().map(|tracker| tracker.inner().clone());
+    let app_handle = app_handle.clone();
+    let session_id = session_id.to_string();
+    tokio::spawn(async move {
+        let suspended = || tracker.as_ref().is_some_and(|tracker| tracker.state(&session_id) == Some(ActivityState::Suspended));
+        let exceeded = unified_core::process_limits::watch_unless_paused(pid, &limits, suspended).await;
+        stop(&app_handle, &session_id, kind, pid, exceeded).await;
+    })
+}
+
+async fn stop(app_handle: &AppHandle, session_id: &str, kind: &str, pid: Option<u32>, exceeded: LimitExceeded) {
+    let error = exceeded.to_string();
+    // The kill comes last: it ends the output, whose reader then aborts this task
+    if let Some(profile_manager) = app_handle.try_state::<ProfileManager>() {
+        crate::startup_reconciliation::process_failed(profile_manager.db_pool.read().await.as_ref(), session_id, pid, &error).await;
+    }
+    crate::session_log::record(
+        app_handle,
+        session_id,
+        LogCategory::Spawn,
+        LogLevel::Error,
+        error,
+        serde_json::to_value(&exceeded).unwrap_or_default(),
+    );
+    let event = ProcessLimitExceededEvent::new(session_id, kind, exceeded);
+    log::warn!("{}", event.message);
+    let _ = app_handle.emit("process_limit_exceeded", &event);
+
+    // A session restarted since runs another process, which is left alone
+    if let Some(sessions) = app_handle.try_state::<AmpSessionMap>() {
+        let same_process = match sessions.get(session_id) {
+            Some(session) => session.lock().await.child.id() == pid,
+            None => false,
+        };
+        if same_process {
+            sessions.remove(session_id);
+        }
+    }
+    if let Some(pid) = pid {
+        if let Err(e) = crate::startup_reconciliation::kill_process(pid) {
+            log::warn!("Failed to stop process {} of session {}: {}", pid, session_id, e);
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_process_limits_config(app_state: State<'_, AppState>) -> Result<ProcessLimitsConfig, String> {
+    Ok(app_state.lock().unwrap().process_limits.clone())
+}
+
+/// Limits for processes started from now on
+#[tauri::command]
+pub async fn set_process_limits_config(config: ProcessLimitsConfig, app_state: State<'_, AppState>) -> Result<(), String> {
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.process_limits = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_config_converts_to_limits() {
+        let config = ProcessLimitsConfig { max_memory_mb: 512, max_open_files: 256, max_execution_secs: 0 };
+        let limits = config.limits();
+        assert_eq!((limits.max_memory_mb, limits.max_open_files), (512, 256));
+        assert_eq!(limits.max_execution_time, Duration::ZERO);
+
+        // Interactive processes run unlimited unless configured
+        let defaults = ProcessLimitsConfig::default().limits();
+        assert_eq!((defaults.max_memory_mb, defaults.max_open_files), (0, 0));
+        assert_eq!(defaults.max_execution_time, Duration::ZERO);
+        let parsed: ProcessLimitsConfig = serde_json::from_str(r#"{"max_memory_mb": 1024}"#).unwrap();
+        assert_eq!((parsed.max_memory_mb, parsed.max_open_files, parsed.max_execution_secs), (1024, 0, 0));
+    }
+
+    #[test]
+    fn test_event_carries_limit_and_message() {
+        let event = ProcessLimitExceededEvent::new("thread-1", "thread", LimitExceeded::Memory { used_mb: 3000, limit_mb: 2048 });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["limit"], "memory");
+        assert_eq!(json["used_mb"], 3000);
+        assert_eq!(json["kind"], "thread");
+        assert!(event.message.contains("2048"));
+    }
+}
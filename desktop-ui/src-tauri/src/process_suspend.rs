@@ -0,0 +1,242 @@
+//! Pause and resume session processes in place
+//!
+//! `session_suspend` stops a session's amp process with SIGSTOP (NtSuspendProcess
+//! on Windows). It then uses no CPU but keeps its memory, open connections and
+//! conversation state. `session_resume`, or the next message sent to the session,
+//! continues it. Suspension is recorded in the `ActivityTracker`, so the stale
+//! session monitor skips suspended sessions and `session_status` reports them.
+//! `interrupt_session` stops only the response being generated: SIGINT makes the
+//! CLI abort its turn, and where there are no signals an interrupt control
+//! message is written to its stdin instead.
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use unified_core::domain::SessionStatus;
+
+use crate::session_activity::{ActivityState, ActivityTracker};
+use crate::session_commands::{AmpSession, AmpSessionMap};
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: kill only reads its arguments
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(unix)]
+pub fn suspend_pid(pid: u32) -> std::io::Result<()> {
+    send_signal(pid, libc::SIGSTOP)
+}
+
+#[cfg(unix)]
+pub fn resume_pid(pid: u32) -> std::io::Result<()> {
+    send_signal(pid, libc::SIGCONT)
+}
+
+#[cfg(unix)]
+pub fn interrupt_pid(pid: u32) -> std::io::Result<()> {
+    send_signal(pid, libc::SIGINT)
+}
+
+#[cfg(windows)]
+mod win {
+    use std::ffi::c_void;
+
+    const PROCESS_SUSPEND_RESUME: u32 = 0x0800;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut c_void;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    #[link(name = "ntdll")]
+    extern "system" {
+        fn NtSuspendProcess(handle: *mut c_void) -> i32;
+        fn NtResumeProcess(handle: *mut c_void) -> i32;
+    }
+
+    pub fn with_process(pid: u32, resume: bool) -> std::io::Result<()> {
+        // SAFETY: the handle is checked for null and closed before returning
+        unsafe {
+            let handle = OpenProcess(PROCESS_SUSPEND_RESUME, 0, pid);
+            if handle.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let status = if resume { NtResumeProcess(handle) } else { NtSuspendProcess(handle) };
+            CloseHandle(handle);
+            if status < 0 {
+                return Err(std::io::Error::other(format!("NTSTATUS {:#x}", status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+pub fn suspend_pid(pid: u32) -> std::io::Result<()> {
+    win::with_process(pid, false)
+}
+
+#[cfg(windows)]
+pub fn resume_pid(pid: u32) -> std::io::Result<()> {
+    win::with_process(pid, true)
+}
+
+fn session_pid(session: &AmpSession, session_id: &str) -> Result<u32, String> {
+    session
+        .child
+        .id()
+        .ok_or_else(|| format!("Session {} has already exited", session_id))
+}
+
+/// Stdin line asking the CLI to abort its current turn
+pub const INTERRUPT_MESSAGE: &str = r#"{"type":"control","subtype":"interrupt"}"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptMethod {
+    Signal,
+    ControlMessage,
+}
+
+/// Stop the response a session is generating, leaving its process running
+pub fn interrupt_session(session: &AmpSession, session_id: &str) -> Result<InterruptMethod, String> {
+    let error = |e: String| format!("Failed to interrupt session {}: {}", session_id, e);
+    match session.child.id() {
+        #[cfg(unix)]
+        Some(pid) => {
+            interrupt_pid(pid).map_err(|e| error(e.to_string()))?;
+            Ok(InterruptMethod::Signal)
+        }
+        _ => {
+            session.tx.send(INTERRUPT_MESSAGE.to_string()).map_err(|e| error(e.to_string()))?;
+            Ok(InterruptMethod::ControlMessage)
+        }
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+pub fn suspend_session(session: &AmpSession, tracker: &ActivityTracker, session_id: &str) -> Result<(), String> {
+    if tracker.state(session_id) == Some(ActivityState::Suspended) {
+        return Ok(());
+    }
+    let pid = session_pid(session, session_id)?;
+    suspend_pid(pid).map_err(|e| format!("Failed to suspend session {}: {}", session_id, e))?;
+    tracker.set_state(session_id, ActivityState::Suspended, now_ms());
+    Ok(())
+}
+
+pub fn resume_session(session: &AmpSession, tracker: &ActivityTracker, session_id: &str) -> Result<(), String> {
+    if tracker.state(session_id) != Some(ActivityState::Suspended) {
+        return Ok(());
+    }
+    let pid = session_pid(session, session_id)?;
+    resume_pid(pid).map_err(|e| format!("Failed to resume session {}: {}", session_id, e))?;
+    // Resuming counts as activity so the session is not immediately stale again
+    tracker.set_state(session_id, ActivityState::Active, now_ms());
+    tracker.touch(session_id, now_ms());
+    Ok(())
+}
+
+/// Status of a chat session or thread process as seen by the activity tracker
+pub fn status_of(live: bool, state: Option<ActivityState>) -> SessionStatus {
+    match (live, state) {
+        (true, Some(ActivityState::Suspended)) => SessionStatus::Suspended,
+        (true, _) => SessionStatus::Running,
+        (false, Some(ActivityState::Detached)) => SessionStatus::Idle,
+        (false, _) => SessionStatus::Completed,
+    }
+}
+
+/// Pause a session's process. It keeps its state and continues on `session_resume`
+/// or the next message sent to it.
+#[tauri::command]
+pub async fn session_suspend(
+    session_id: String,
+    amp_sessions: State<'_, AmpSessionMap>,
+    tracker: State<'_, ActivityTracker>,
+) -> Result<SessionStatus, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let session = amp_sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session {} not found or not active", session_id))?;
+    suspend_session(&*session.lock().await, &tracker, &session_id)?;
+    Ok(SessionStatus::Suspended)
+}
+
+#[tauri::command]
+pub async fn session_resume(
+    session_id: String,
+    amp_sessions: State<'_, AmpSessionMap>,
+    tracker: State<'_, ActivityTracker>,
+) -> Result<SessionStatus, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let session = amp_sessions
+        .get(&session_id)
+        .ok_or_else(|| format!("Session {} not found or not active", session_id))?;
+    resume_session(&*session.lock().await, &tracker, &session_id)?;
+    Ok(SessionStatus::Running)
+}
+
+#[tauri::command]
+pub async fn session_status(
+    session_id: String,
+    amp_sessions: State<'_, AmpSessionMap>,
+    tracker: State<'_, ActivityTracker>,
+) -> Result<SessionStatus, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let live = amp_sessions.contains(&session_id);
+    if !live {
+        if let Some(status) = crate::startup_reconciliation::corrected_status(&session_id) {
+            return Ok(status);
+        }
+    }
+    Ok(status_of(live, tracker.state(&session_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_reflects_tracker_state() {
+        assert_eq!(status_of(true, Some(ActivityState::Suspended)), SessionStatus::Suspended);
+        assert_eq!(status_of(true, Some(ActivityState::Stale)), SessionStatus::Running);
+        assert_eq!(status_of(true, None), SessionStatus::Running);
+        assert_eq!(status_of(false, Some(ActivityState::Detached)), SessionStatus::Idle);
+        assert_eq!(status_of(false, Some(ActivityState::Suspended)), SessionStatus::Completed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_and_continue_process() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let proc_state = || {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid))
+                .ok()
+                .and_then(|s| s.rsplit(')').next().and_then(|rest| rest.split_whitespace().next().map(str::to_string)))
+        };
+
+        suspend_pid(pid).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        if let Some(state) = proc_state() {
+            assert_eq!(state, "T");
+        }
+        resume_pid(pid).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        if let Some(state) = proc_state() {
+            assert_ne!(state, "T");
+        }
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
@@ -6,10 +6,12 @@ use tauri::{AppHandle, Manager, State};
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use chrono::Utc;
-use sqlx::sqlite::SqlitePool;
+use sqlx::sqlite::{SqliteConnection, SqlitePool};
 
 use crate::amp_auth::{ensure_auth, AuthStatus, ResolvedConfig};
 use crate::keychain_auth::{KeychainAuth, TokenType};
+use crate::schema::run_migrations;
+use crate::trash::{Capture, TrashKind, TrashStore};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -23,6 +25,12 @@ pub struct ProfileRow {
     pub last_used_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Agent mode for threads started without one
+    pub default_agent_mode: Option<String>,
+    /// Model passed to the CLI as `AMP_MODEL` unless a thread overrides it
+    pub default_model: Option<String>,
+    /// Toolbox profile for sessions created without one
+    pub default_toolbox_profile_id: Option<i64>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,32 +42,48 @@ pub struct ProfileCtx {
     pub cancellation_token: CancellationToken,
 }
 
+/// Environment keys that carry credentials; a profile with its own tokens replaces all of them
+const TOKEN_ENV_KEYS: &[&str] = &["AMP_TOKEN", "AMP_REFRESH_TOKEN", "AMP_API_KEY"];
+
+/// Apply a profile's URL, CLI selection, TLS and namespace settings on top of `env_vars`
+pub fn apply_profile_settings(profile: &ProfileRow, env_vars: &mut HashMap<String, String>) {
+    env_vars.insert("AMP_URL".to_string(), profile.api_url.clone());
+    
+    if let Some(cli_path) = &profile.cli_path {
+        if cli_path == "amp" {
+            // Use system binary
+            env_vars.insert("AMP_BIN".to_string(), "amp".to_string());
+            env_vars.remove("AMP_CLI_PATH");
+        } else {
+            // Use custom CLI path
+            env_vars.insert("AMP_CLI_PATH".to_string(), cli_path.clone());
+            env_vars.remove("AMP_BIN");
+        }
+    }
+    
+    if profile.tls_insecure {
+        env_vars.insert("NODE_TLS_REJECT_UNAUTHORIZED".to_string(), "0".to_string());
+    } else {
+        env_vars.remove("NODE_TLS_REJECT_UNAUTHORIZED");
+    }
+    
+    match &profile.db_namespace {
+        Some(namespace) => env_vars.insert("AMP_DB_NAMESPACE".to_string(), namespace.clone()),
+        None => env_vars.remove("AMP_DB_NAMESPACE"),
+    };
+    
+    // Without a default the model configured in app settings, if any, is used
+    if let Some(model) = &profile.default_model {
+        env_vars.insert("AMP_MODEL".to_string(), model.clone());
+    }
+}
+
 impl ProfileCtx {
     pub fn new(profile: ProfileRow) -> Self {
         let mut env_vars: HashMap<String, String> = std::env::vars().collect();
         
         // Set up environment variables based on profile
-        env_vars.insert("AMP_URL".to_string(), profile.api_url.clone());
-        
-        if let Some(cli_path) = &profile.cli_path {
-            if cli_path == "amp" {
-                // Use system binary
-                env_vars.insert("AMP_BIN".to_string(), "amp".to_string());
-                env_vars.remove("AMP_CLI_PATH");
-            } else {
-                // Use custom CLI path
-                env_vars.insert("AMP_CLI_PATH".to_string(), cli_path.clone());
-                env_vars.remove("AMP_BIN");
-            }
-        }
-        
-        if profile.tls_insecure {
-            env_vars.insert("NODE_TLS_REJECT_UNAUTHORIZED".to_string(), "0".to_string());
-        }
-        
-        if let Some(namespace) = &profile.db_namespace {
-            env_vars.insert("AMP_DB_NAMESPACE".to_string(), namespace.clone());
-        }
+        apply_profile_settings(&profile, &mut env_vars);
         
         let http_client = reqwest::Client::builder()
             .danger_accept_invalid_certs(profile.tls_insecure)
@@ -212,43 +236,27 @@ impl ProfileManager {
             })?;
         
         log::debug!("initialize_db: Database connection test successful");
-        
+
+        // WAL lets exports read a consistent snapshot while streams keep writing
+        if let Err(e) = sqlx::query("PRAGMA journal_mode = WAL").execute(&pool).await {
+            log::warn!("initialize_db: Failed to enable WAL journal mode: {}", e);
+        }
+
         // Run migrations manually since we can't use sqlx::migrate! with tauri
         log::debug!("initialize_db: Running database migrations");
-        
-        let migrations = vec![
-            ("001_initial.sql", include_str!("../migrations/001_initial.sql")),
-            ("002_chat_sessions.sql", include_str!("../migrations/002_chat_sessions.sql")),
-            ("003_chat_sessions_agent_mode.sql", include_str!("../migrations/003_chat_sessions_agent_mode.sql")),
-            ("004_add_toolbox_profiles.sql", include_str!("../migrations/004_add_toolbox_profiles.sql")),
-            ("005_add_worktrees_support.sql", include_str!("../migrations/005_add_worktrees_support.sql")),
-            ("006_batch_processing.sql", include_str!("../migrations/006_batch_processing.sql")),
-            ("007_add_threads_architecture.sql", include_str!("../migrations/007_add_threads_architecture.sql")),
-        ];
-        
-        for (name, migration_sql) in migrations {
-            log::debug!("initialize_db: Running migration {}, SQL length: {} characters", name, migration_sql.len());
-            
-            // Execute migration with better error handling
-            match sqlx::query(migration_sql).execute(&pool).await {
-                Ok(result) => {
-                    log::debug!("initialize_db: Migration {} executed successfully, rows affected: {}", name, result.rows_affected());
-                },
-                Err(e) => {
-                    // Check if error is due to tables already existing (not a critical error)
-                    let error_str = e.to_string();
-                    if error_str.contains("already exists") || error_str.contains("duplicate column name") {
-                        log::debug!("initialize_db: Migration {} - tables already exist, skipping", name);
-                    } else {
-                        log::error!("initialize_db: Failed to run migration {}: {}", name, e);
-                        return Err(format!("Failed to run migration {}: {}", name, e));
-                    }
-                }
-            }
-        }
-        
+        run_migrations(&pool).await?;
         log::debug!("initialize_db: Migrations completed successfully");
         
+        crate::message_content::init_blob_dir(app_data_dir.join(crate::message_content::BLOB_DIR_NAME));
+        match crate::session_codes::load(&pool, &crate::app_state::SessionCodeConfig::default()).await {
+            Ok(0) => {}
+            Ok(assigned) => log::info!("initialize_db: Assigned short codes to {} existing sessions", assigned),
+            Err(e) => log::warn!("initialize_db: Failed to load session codes: {}", e),
+        }
+        if let Err(e) = crate::session_priority::load(&pool).await {
+            log::warn!("initialize_db: {}", e);
+        }
+
         // Store the pool
         *self.db_pool.write().await = Some(pool);
         log::info!("initialize_db: Database initialization completed successfully");
@@ -297,12 +305,27 @@ impl ProfileManager {
             return Err(format!("Profile '{}' not found", profile_id));
         }
         
+        {
+            let db_pool_guard = self.db_pool.read().await;
+            let db = db_pool_guard
+                .as_ref()
+                .ok_or("Database not initialized")?;
+            let mut conn = db.acquire().await.map_err(|e| format!("Failed to update active profile: {}", e))?;
+            record_activation(&mut conn, &profile_id).await?;
+        }
+        
+        self.apply_activation(&profile_id).await
+    }
+    
+    /// Make an existing profile the active one in memory, loading its tokens.
+    /// The database side is `record_activation`.
+    pub async fn apply_activation(&self, profile_id: &str) -> Result<(), String> {
         // Deactivate current profile
         self.deactivate_current().await?;
         
         // Load tokens from keychain and update profile context
-        let tokens = self.load_profile_tokens(&profile_id).await?;
-        if let Some(profile_entry) = self.profiles.get(&profile_id) {
+        let tokens = self.load_profile_tokens(profile_id).await?;
+        if let Some(profile_entry) = self.profiles.get(profile_id) {
             let mut profile_ctx = profile_entry.write().await;
             // Apply loaded tokens to environment variables
             for (key, value) in tokens {
@@ -311,47 +334,76 @@ impl ProfileManager {
         }
         
         // Set new active profile
-        *self.active_profile_id.write().await = Some(profile_id.clone());
-        
-        // Update database
-        let db_pool_guard = self.db_pool.read().await;
-        let db = db_pool_guard
-            .as_ref()
-            .ok_or("Database not initialized")?;
-        
-        sqlx::query("INSERT OR REPLACE INTO ui_state (key, value) VALUES ('active_profile_id', ?)")
-            .bind(&profile_id)
-            .execute(db)
-            .await
-            .map_err(|e| format!("Failed to update active profile: {}", e))?;
-        
-        // Update last_used_at for the profile
-        let now = Utc::now().to_rfc3339();
-        sqlx::query("UPDATE profiles SET last_used_at = ? WHERE id = ?")
-            .bind(&now)
-            .bind(&profile_id)
-            .execute(db)
-            .await
-            .map_err(|e| format!("Failed to update profile last_used_at: {}", e))?;
-        
+        *self.active_profile_id.write().await = Some(profile_id.to_string());
         Ok(())
     }
     
+    /// Clear the active profile. Sessions bound to it keep running; its operations
+    /// are only cancelled when the profile itself is deleted.
     pub async fn deactivate_current(&self) -> Result<(), String> {
-        let current_id = self.active_profile_id.read().await.clone();
+        *self.active_profile_id.write().await = None;
+        Ok(())
+    }
+    
+    /// Profile a new session binds to: the requested one, otherwise the active profile
+    /// unless an explicit connection mode in app state should take precedence
+    pub async fn resolve_session_profile(&self, requested: Option<&str>, prefer_app_state: bool) -> Result<Option<String>, String> {
+        match requested {
+            Some(id) if self.profiles.contains_key(id) => Ok(Some(id.to_string())),
+            Some(id) => Err(format!("Profile '{}' not found", id)),
+            None if prefer_app_state => Ok(None),
+            None => Ok(self.active_profile_id.read().await.clone()),
+        }
+    }
+    
+    /// Layer a profile's settings and keychain tokens onto a spawn environment.
+    /// Tokens are read at call time, so concurrent sessions of different profiles
+    /// each get their own credentials regardless of which profile is active.
+    pub async fn apply_profile_env(&self, profile_id: &str, env_vars: &mut HashMap<String, String>) -> Result<(), String> {
+        let profile_ctx = self
+            .profiles
+            .get(profile_id)
+            .map(|entry| entry.clone())
+            .ok_or_else(|| format!("Profile '{}' not found", profile_id))?;
+        apply_profile_settings(&profile_ctx.read().await.profile, env_vars);
+        env_vars.insert(crate::profile_dirs::PROFILE_ID_ENV.to_string(), profile_id.to_string());
         
-        if let Some(profile_id) = current_id {
-            // Cancel any ongoing operations for this profile
-            if let Some(profile_entry) = self.profiles.get(&profile_id) {
-                let profile_ctx = profile_entry.read().await;
-                profile_ctx.cancellation_token.cancel();
+        let tokens = self.load_profile_tokens(profile_id).await?;
+        if !tokens.is_empty() {
+            for key in TOKEN_ENV_KEYS {
+                env_vars.remove(*key);
             }
+            env_vars.extend(tokens);
         }
         
-        *self.active_profile_id.write().await = None;
+        // Secrets from external managers win over the keychain
+        let secrets = match self.db_pool.read().await.as_ref() {
+            Some(db) => crate::secret_providers::profile_secrets(db, profile_id).await?,
+            None => HashMap::new(),
+        };
+        if secrets.keys().any(|key| TOKEN_ENV_KEYS.contains(&key.as_str())) {
+            for key in TOKEN_ENV_KEYS {
+                env_vars.remove(*key);
+            }
+        }
+        env_vars.extend(secrets);
         Ok(())
     }
     
+    /// Auth config for a profile without activating it
+    pub async fn resolved_config_for(&self, profile_id: &str) -> Result<ResolvedConfig, String> {
+        let mut env_vars: HashMap<String, String> = std::env::vars().collect();
+        self.apply_profile_env(profile_id, &mut env_vars).await?;
+        Ok(ResolvedConfig::from_env_with_overrides(env_vars))
+    }
+    
+    /// Current settings of a loaded profile
+    pub async fn profile_row(&self, profile_id: &str) -> Option<ProfileRow> {
+        let profile_ctx = self.profiles.get(profile_id).map(|entry| entry.clone())?;
+        let row = profile_ctx.read().await.profile.clone();
+        Some(row)
+    }
+    
     pub async fn get_active_profile(&self) -> Option<Arc<RwLock<ProfileCtx>>> {
         let active_id = self.active_profile_id.read().await.clone()?;
         self.profiles.get(&active_id).map(|entry| entry.clone())
@@ -418,6 +470,9 @@ pub struct ProfileInfo {
     pub last_used_at: Option<String>,
     pub is_active: bool,
     pub has_stored_tokens: bool,
+    pub default_agent_mode: Option<String>,
+    pub default_model: Option<String>,
+    pub default_toolbox_profile_id: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -435,6 +490,9 @@ pub struct CreateProfileRequest {
     pub cli_path: Option<String>,
     pub token: Option<String>,
     pub tls_enabled: Option<bool>,
+    pub default_agent_mode: Option<String>,
+    pub default_model: Option<String>,
+    pub default_toolbox_profile_id: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -449,10 +507,13 @@ pub struct AmpProfile {
     pub is_active: bool,
     pub created_at: i64,
     pub updated_at: i64,
+    pub default_agent_mode: Option<String>,
+    pub default_model: Option<String>,
+    pub default_toolbox_profile_id: Option<i64>,
 }
 
 impl ProfileRow {
-    fn to_amp_profile(&self, is_active: bool) -> AmpProfile {
+    pub(crate) fn to_amp_profile(&self, is_active: bool) -> AmpProfile {
         // Extract connection_type from api_url/cli_path
         let connection_type = if self.cli_path.is_some() {
             "local-cli".to_string()
@@ -481,98 +542,80 @@ impl ProfileRow {
             is_active,
             created_at,
             updated_at,
+            default_agent_mode: self.default_agent_mode.clone(),
+            default_model: self.default_model.clone(),
+            default_toolbox_profile_id: self.default_toolbox_profile_id,
         }
     }
 }
 
-// Tauri Commands
-#[tauri::command]
-pub async fn profiles_list(
-    profile_manager: State<'_, ProfileManager>,
-) -> Result<Vec<AmpProfile>, String> {
-    let db_pool_guard = profile_manager.db_pool.read().await;
-    let db = db_pool_guard
-        .as_ref()
-        .ok_or("Database not initialized")?;
-    
-    let active_id = profile_manager.active_profile_id.read().await.clone();
-    
-    // Load all profiles from database
-    let profiles = sqlx::query_as::<_, ProfileRow>("SELECT * FROM profiles ORDER BY name")
-        .fetch_all(db)
+/// Reject a default toolbox profile that does not exist
+async fn check_default_toolbox_profile<'e, E>(db: E, toolbox_profile_id: Option<i64>) -> Result<(), String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let Some(id) = toolbox_profile_id else {
+        return Ok(());
+    };
+    let exists: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM toolbox_profiles WHERE id = ?")
+        .bind(id)
+        .fetch_one(db)
         .await
-        .map_err(|e| format!("Failed to load profiles: {}", e))?;
-    
-    let mut result = Vec::new();
-    for profile_row in profiles {
-        let is_active = active_id.as_ref() == Some(&profile_row.id);
-        result.push(profile_row.to_amp_profile(is_active));
+        .map_err(|e| format!("Failed to get toolbox profile: {}", e))?;
+    if exists > 0 {
+        Ok(())
+    } else {
+        Err(format!("Toolbox profile {} not found", id))
     }
-    
-    Ok(result)
 }
 
-#[tauri::command]
-pub async fn profile_create(
-    profile: CreateProfileRequest,
-    profile_manager: State<'_, ProfileManager>,
-) -> Result<AmpProfile, String> {
-    log::debug!("profile_create: Starting profile creation with data: {:?}", profile);
-    
-    // Check if ProfileManager is initialized
-    log::debug!("profile_create: Acquiring database pool lock");
-    let db_pool_guard = profile_manager.db_pool.read().await;
-    let db = db_pool_guard
-        .as_ref()
-        .ok_or_else(|| {
-            log::error!("profile_create: Database not initialized - this should not happen after blocking initialization");
-            "Database not initialized. Please restart the application.".to_string()
-        })?;
-    
-    log::debug!("profile_create: Database pool acquired successfully");
+/// Persist the active profile and its last use
+pub(crate) async fn record_activation(conn: &mut SqliteConnection, profile_id: &str) -> Result<(), String> {
+    sqlx::query("INSERT OR REPLACE INTO ui_state (key, value) VALUES ('active_profile_id', ?)")
+        .bind(profile_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to update active profile: {}", e))?;
     
+    // Update last_used_at for the profile
+    let now = Utc::now().to_rfc3339();
+    sqlx::query("UPDATE profiles SET last_used_at = ? WHERE id = ?")
+        .bind(&now)
+        .bind(profile_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to update profile last_used_at: {}", e))?;
+    Ok(())
+}
+
+/// Validate and insert a new profile. Tokens and the in-memory context are left
+/// to the caller.
+pub(crate) async fn insert_profile(conn: &mut SqliteConnection, profile: &CreateProfileRequest) -> Result<ProfileRow, String> {
     let profile_id = Uuid::new_v4().to_string();
-    log::debug!("profile_create: Generated profile ID: {}", profile_id);
-    
     let now = Utc::now().to_rfc3339();
-    log::debug!("profile_create: Generated timestamp: {}", now);
     
     // Determine API URL based on connection type
     log::debug!("profile_create: Determining API URL for connection type: {}", profile.connection_type);
     let api_url = match profile.connection_type.as_str() {
-        "production" => {
-            log::debug!("profile_create: Using production URL");
-            "https://ampcode.com".to_string()
-        }
-        "local-server" => {
-            let url = profile.api_url.unwrap_or_else(|| "https://localhost:7002".to_string());
-            log::debug!("profile_create: Using local server URL: {}", url);
-            url
-        }
-        "local-cli" => {
-            log::debug!("profile_create: Using local CLI with default production URL");
-            "https://ampcode.com".to_string() // Default for CLI
-        }
+        "production" => "https://ampcode.com".to_string(),
+        "local-server" => profile.api_url.clone().unwrap_or_else(|| "https://localhost:7002".to_string()),
+        "local-cli" => "https://ampcode.com".to_string(), // Default for CLI
         _ => {
             log::error!("profile_create: Invalid connection type: {}", profile.connection_type);
             return Err("Invalid connection type".to_string());
         }
     };
     
+    check_default_toolbox_profile(&mut *conn, profile.default_toolbox_profile_id).await?;
+    
     // Check if profile name already exists
-    log::debug!("profile_create: Checking for existing profile with name: {}", profile.name);
     let existing_count = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM profiles WHERE name = ?"
     )
     .bind(&profile.name)
-    .fetch_one(db)
+    .fetch_one(&mut *conn)
     .await
-    .map_err(|e| {
-        log::error!("profile_create: Failed to check for duplicate profile names: {}", e);
-        format!("Failed to check for duplicate profile names: {}", e)
-    })?;
-    
-    log::debug!("profile_create: Found {} existing profiles with name '{}'", existing_count, profile.name);
+    .map_err(|e| format!("Failed to check for duplicate profile names: {}", e))?;
     
     if existing_count > 0 {
         log::warn!("profile_create: Profile name '{}' already exists", profile.name);
@@ -582,38 +625,26 @@ pub async fn profile_create(
         ));
     }
     
-    // Insert profile into database
-    log::debug!("profile_create: Inserting profile into database");
-    log::debug!("profile_create: Insert parameters - id: {}, name: {}, api_url: {}, cli_path: {:?}, tls_insecure: {}", 
-        profile_id, profile.name, api_url, profile.cli_path, 
-        profile.tls_enabled.map(|enabled| !enabled).unwrap_or(false));
-    
-    let insert_result = sqlx::query(
-        "INSERT INTO profiles (id, name, api_url, cli_path, tls_insecure, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+    let tls_insecure = profile.tls_enabled.map(|enabled| !enabled).unwrap_or(false);
+    sqlx::query(
+        "INSERT INTO profiles (id, name, api_url, cli_path, tls_insecure, created_at, updated_at, default_agent_mode, default_model, default_toolbox_profile_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
     )
     .bind(&profile_id)
     .bind(&profile.name)
     .bind(&api_url)
     .bind(&profile.cli_path)
-    .bind(profile.tls_enabled.map(|enabled| !enabled).unwrap_or(false))
+    .bind(tls_insecure)
     .bind(&now)
     .bind(&now)
-    .execute(db)
-    .await;
-    
-    match &insert_result {
-        Ok(result) => {
-            log::debug!("profile_create: Database insert successful, rows affected: {}", result.rows_affected());
-        }
-        Err(e) => {
-            log::error!("profile_create: Database insert failed: {}", e);
-        }
-    }
-    
-    insert_result.map_err(|e| {
+    .bind(&profile.default_agent_mode)
+    .bind(&profile.default_model)
+    .bind(profile.default_toolbox_profile_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| {
         // Handle SQLite unique constraint violation specifically
         if e.to_string().contains("UNIQUE constraint failed") {
-            log::warn!("profile_create: Unique constraint violation for profile name: {}", profile.name);
             format!("A profile named '{}' already exists. Please choose a different name.", profile.name)
         } else {
             log::error!("profile_create: Database insert error: {}", e);
@@ -621,6 +652,79 @@ pub async fn profile_create(
         }
     })?;
     
+    Ok(ProfileRow {
+        id: profile_id,
+        name: profile.name.clone(),
+        api_url,
+        cli_path: profile.cli_path.clone(),
+        tls_insecure,
+        db_namespace: None,
+        last_used_at: None,
+        created_at: now.clone(),
+        updated_at: now,
+        default_agent_mode: profile.default_agent_mode.clone(),
+        default_model: profile.default_model.clone(),
+        default_toolbox_profile_id: profile.default_toolbox_profile_id,
+    })
+}
+
+// Tauri Commands
+#[tauri::command]
+pub async fn profiles_list(
+    profile_manager: State<'_, ProfileManager>,
+    read_cache: State<'_, crate::services::ReadCache>,
+) -> Result<Vec<AmpProfile>, String> {
+    let db_pool_guard = profile_manager.db_pool.read().await;
+    let db = db_pool_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+    
+    let active_id = profile_manager.active_profile_id.read().await.clone();
+    
+    // Load all profiles from database; which one is active is not part of the cached rows
+    let profiles: Vec<ProfileRow> = read_cache
+        .get_or_load(db, crate::services::CachedQuery::Profiles, "", || async move {
+            sqlx::query_as::<_, ProfileRow>("SELECT * FROM profiles ORDER BY name")
+                .fetch_all(db)
+                .await
+                .map_err(|e| format!("Failed to load profiles: {}", e))
+        })
+        .await?;
+    
+    let mut result = Vec::new();
+    for profile_row in profiles {
+        let is_active = active_id.as_ref() == Some(&profile_row.id);
+        result.push(profile_row.to_amp_profile(is_active));
+    }
+    
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn profile_create(
+    profile: CreateProfileRequest,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<AmpProfile, String> {
+    log::debug!("profile_create: Starting profile creation with data: {:?}", profile);
+    
+    // Check if ProfileManager is initialized
+    log::debug!("profile_create: Acquiring database pool lock");
+    let db_pool_guard = profile_manager.db_pool.read().await;
+    let db = db_pool_guard
+        .as_ref()
+        .ok_or_else(|| {
+            log::error!("profile_create: Database not initialized - this should not happen after blocking initialization");
+            "Database not initialized. Please restart the application.".to_string()
+        })?;
+    
+    log::debug!("profile_create: Database pool acquired successfully");
+    
+    let mut conn = db.acquire().await.map_err(|e| format!("Failed to create profile: {}", e))?;
+    let profile_row = insert_profile(&mut conn, &profile).await?;
+    drop(conn);
+    let profile_id = profile_row.id.clone();
+    log::debug!("profile_create: Inserted profile with ID: {}", profile_id);
+    
     // Store token in keychain if provided
     if let Some(ref token) = profile.token {
         log::debug!("profile_create: Storing token in keychain for profile: {}", profile_id);
@@ -634,20 +738,6 @@ pub async fn profile_create(
         log::debug!("profile_create: No token provided, skipping keychain storage");
     }
     
-    // Load the created profile and add to context
-    log::debug!("profile_create: Creating profile row structure");
-    let profile_row = ProfileRow {
-        id: profile_id.clone(),
-        name: profile.name.clone(),
-        api_url: api_url.clone(),
-        cli_path: profile.cli_path.clone(),
-        tls_insecure: profile.tls_enabled.map(|enabled| !enabled).unwrap_or(false),
-        db_namespace: None,
-        last_used_at: None,
-        created_at: now.clone(),
-        updated_at: now,
-    };
-    
     log::debug!("profile_create: Creating profile context and adding to manager");
     let profile_ctx = Arc::new(RwLock::new(ProfileCtx::new(profile_row.clone())));
     profile_manager.profiles.insert(profile_id.clone(), profile_ctx);
@@ -681,6 +771,8 @@ pub async fn profile_update(
         _ => return Err("Invalid connection type".to_string()),
     };
     
+    check_default_toolbox_profile(db, updates.default_toolbox_profile_id).await?;
+    
     // Check if profile name already exists (excluding current profile)
     let existing_count = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM profiles WHERE name = ? AND id != ?"
@@ -700,13 +792,17 @@ pub async fn profile_update(
 
     // Update profile in database
     sqlx::query(
-        "UPDATE profiles SET name = ?, api_url = ?, cli_path = ?, tls_insecure = ?, updated_at = ? WHERE id = ?"
+        "UPDATE profiles SET name = ?, api_url = ?, cli_path = ?, tls_insecure = ?, updated_at = ?,
+             default_agent_mode = ?, default_model = ?, default_toolbox_profile_id = ? WHERE id = ?"
     )
     .bind(&updates.name)
     .bind(&api_url)
     .bind(&updates.cli_path)
     .bind(updates.tls_enabled.map(|enabled| !enabled).unwrap_or(false))
     .bind(&now)
+    .bind(&updates.default_agent_mode)
+    .bind(&updates.default_model)
+    .bind(updates.default_toolbox_profile_id)
     .bind(&id)
     .execute(db)
     .await
@@ -735,6 +831,9 @@ pub async fn profile_update(
         profile_ctx.profile.cli_path = updates.cli_path.clone();
         profile_ctx.profile.tls_insecure = updates.tls_enabled.map(|enabled| !enabled).unwrap_or(false);
         profile_ctx.profile.updated_at = now.clone();
+        profile_ctx.profile.default_agent_mode = updates.default_agent_mode.clone();
+        profile_ctx.profile.default_model = updates.default_model.clone();
+        profile_ctx.profile.default_toolbox_profile_id = updates.default_toolbox_profile_id;
     }
     
     // Get active status
@@ -752,6 +851,9 @@ pub async fn profile_update(
         last_used_at: None,
         created_at: now.clone(), // We don't have the original created_at here
         updated_at: now,
+        default_agent_mode: updates.default_agent_mode,
+        default_model: updates.default_model,
+        default_toolbox_profile_id: updates.default_toolbox_profile_id,
     };
     
     Ok(profile_row.to_amp_profile(is_active))
@@ -773,21 +875,38 @@ pub async fn profile_delete(
         return Err("Cannot delete the active profile".to_string());
     }
     
-    // Delete from database
-    sqlx::query("DELETE FROM profiles WHERE id = ?")
+    // Move the profile and its hooks to the trash
+    let captures = [
+        Capture::new("profiles", "id = ?", &id),
+        Capture::new("script_hooks", "profile_id = ?", &id),
+        Capture::new("profile_secrets", "profile_id = ?", &id),
+    ];
+    let label: String = sqlx::query_scalar("SELECT name FROM profiles WHERE id = ?")
         .bind(&id)
-        .execute(db)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to delete profile: {}", e))?
+        .unwrap_or_else(|| id.clone());
+    TrashStore::new(db.clone())
+        .trash_rows(TrashKind::Profile, &label, &captures, serde_json::json!({ "profile_id": id }))
         .await
         .map_err(|e| format!("Failed to delete profile: {}", e))?;
     
-    // Clear tokens from keychain
+    // Clear tokens from keychain; they are not kept in the trash
     let keychain = KeychainAuth::new();
     if let Err(e) = keychain.clear_profile_tokens(&id) {
         log::warn!("Failed to clear tokens for deleted profile: {}", e);
     }
     
-    // Remove from in-memory context
-    profile_manager.profiles.remove(&id);
+    // Temp files and logs are not kept in the trash either
+    if let Err(e) = crate::profile_dirs::remove_profile(&id) {
+        log::warn!("Failed to remove runtime directory for deleted profile: {}", e);
+    }
+    
+    // Remove from in-memory context and cancel any ongoing operations for this profile
+    if let Some((_, profile_ctx)) = profile_manager.profiles.remove(&id) {
+        profile_ctx.read().await.cancellation_token.cancel();
+    }
     
     Ok(())
 }
@@ -831,6 +950,9 @@ pub async fn list_profiles(
             last_used_at: ctx.profile.last_used_at.clone(),
             is_active,
             has_stored_tokens,
+            default_agent_mode: ctx.profile.default_agent_mode.clone(),
+            default_model: ctx.profile.default_model.clone(),
+            default_toolbox_profile_id: ctx.profile.default_toolbox_profile_id,
         });
     }
     
@@ -868,6 +990,9 @@ pub async fn get_active_profile(
                 last_used_at: ctx.profile.last_used_at.clone(),
                 is_active: true,
                 has_stored_tokens,
+                default_agent_mode: ctx.profile.default_agent_mode.clone(),
+                default_model: ctx.profile.default_model.clone(),
+                default_toolbox_profile_id: ctx.profile.default_toolbox_profile_id,
             }))
         }
         None => Ok(None),
@@ -943,3 +1068,64 @@ pub async fn logout(
 pub fn init_profile_manager(app_handle: AppHandle) -> ProfileManager {
     ProfileManager::new(app_handle)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(cli_path: Option<&str>, tls_insecure: bool) -> ProfileRow {
+        ProfileRow {
+            id: "dev".to_string(),
+            name: "Local dev".to_string(),
+            api_url: "https://localhost:7002".to_string(),
+            cli_path: cli_path.map(str::to_string),
+            tls_insecure,
+            db_namespace: None,
+            last_used_at: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+            default_agent_mode: None,
+            default_model: None,
+            default_toolbox_profile_id: None,
+        }
+    }
+
+    #[test]
+    fn test_profile_settings_override_spawn_env() {
+        let mut env: HashMap<String, String> = [
+            ("AMP_URL", "https://ampcode.com"),
+            ("AMP_BIN", "amp"),
+            ("AMP_DB_NAMESPACE", "prod"),
+            ("PATH", "/usr/bin"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        apply_profile_settings(&profile(Some("/src/amp/cli/dist/main.js"), true), &mut env);
+        assert_eq!(env["AMP_URL"], "https://localhost:7002");
+        assert_eq!(env["AMP_CLI_PATH"], "/src/amp/cli/dist/main.js");
+        assert!(!env.contains_key("AMP_BIN"));
+        assert_eq!(env["NODE_TLS_REJECT_UNAUTHORIZED"], "0");
+        // Settings the profile doesn't have are cleared, not inherited
+        assert!(!env.contains_key("AMP_DB_NAMESPACE"));
+        assert_eq!(env["PATH"], "/usr/bin");
+
+        // A second profile applied to the same env fully replaces the first
+        apply_profile_settings(&profile(Some("amp"), false), &mut env);
+        assert_eq!(env["AMP_BIN"], "amp");
+        assert!(!env.contains_key("AMP_CLI_PATH"));
+        assert!(!env.contains_key("NODE_TLS_REJECT_UNAUTHORIZED"));
+    }
+
+    #[test]
+    fn test_profile_default_model_overrides_app_model() {
+        let mut env: HashMap<String, String> = HashMap::from([("AMP_MODEL".to_string(), "app-model".to_string())]);
+        apply_profile_settings(&profile(None, false), &mut env);
+        assert_eq!(env["AMP_MODEL"], "app-model");
+
+        let with_model = ProfileRow { default_model: Some("profile-model".to_string()), ..profile(None, false) };
+        apply_profile_settings(&with_model, &mut env);
+        assert_eq!(env["AMP_MODEL"], "profile-model");
+    }
+}
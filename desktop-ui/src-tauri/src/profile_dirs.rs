@@ -0,0 +1,182 @@
+//! Per-profile runtime directories
+//!
+//! Toolbox resolutions, the CLI's temp files and session logs used to land in
+//! locations shared by every Amp profile. Each profile now has its own directory
+//! under `~/.amp-orchestra/profiles` holding `tmp` and `logs`, created owner-only
+//! (0700). Sessions without a profile use `default`. The directory is removed when
+//! its profile is deleted, and `check_access` refuses paths that lie inside
+//! another profile's directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Set in a spawn environment by `ProfileManager::apply_profile_env`
+pub const PROFILE_ID_ENV: &str = "AMP_PROFILE_ID";
+
+const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileDirs {
+    base: PathBuf,
+    pub root: PathBuf,
+    pub tmp: PathBuf,
+    pub logs: PathBuf,
+}
+
+fn base_dir() -> PathBuf {
+    dirs::home_dir()
+        .or_else(|| std::env::var("HOME").ok().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".amp-orchestra")
+        .join("profiles")
+}
+
+/// Directory name for a profile. Ids that are not plain names are hashed so
+/// they can neither escape the base directory nor collide after sanitizing.
+fn dir_name(profile_id: Option<&str>) -> String {
+    match profile_id {
+        None => DEFAULT_PROFILE.to_string(),
+        Some(id) if !id.is_empty() && id != DEFAULT_PROFILE && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => {
+            id.to_string()
+        }
+        Some(id) => format!("p-{}", &blake3::hash(id.as_bytes()).to_hex()[..16]),
+    }
+}
+
+fn create_private(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl ProfileDirs {
+    pub fn under(base: &Path, profile_id: Option<&str>) -> Self {
+        let root = base.join(dir_name(profile_id));
+        Self { base: base.to_path_buf(), tmp: root.join("tmp"), logs: root.join("logs"), root }
+    }
+
+    pub fn for_profile(profile_id: Option<&str>) -> Self {
+        Self::under(&base_dir(), profile_id)
+    }
+
+    /// Directories of the profile a spawn environment was built for
+    pub fn for_env(env: &HashMap<String, String>) -> Self {
+        Self::for_profile(env.get(PROFILE_ID_ENV).map(String::as_str))
+    }
+
+    /// Create the directories, tightening permissions on ones that already exist
+    pub fn ensure(&self) -> io::Result<()> {
+        create_private(&self.base)?;
+        for dir in [&self.root, &self.tmp, &self.logs] {
+            create_private(dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn toolboxes(&self) -> PathBuf {
+        self.tmp.join("runtime_toolboxes")
+    }
+
+    /// Err when `path` is inside another profile's directory
+    pub fn check_access(&self, path: &Path) -> Result<(), String> {
+        let path = canonical(path);
+        let base = canonical(&self.base);
+        if path.starts_with(&base) && !path.starts_with(canonical(&self.root)) {
+            return Err(format!("{} belongs to another profile", path.display()));
+        }
+        Ok(())
+    }
+
+    /// Append to a log file only the owner can read
+    pub fn append_log(&self, name: &str, text: &str) -> io::Result<()> {
+        self.ensure()?;
+        let mut options = fs::OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(self.logs.join(name))?.write_all(text.as_bytes())
+    }
+}
+
+/// Delete a profile's temp files and logs
+pub fn remove_profile(profile_id: &str) -> io::Result<()> {
+    remove_profile_under(&base_dir(), profile_id)
+}
+
+fn remove_profile_under(base: &Path, profile_id: &str) -> io::Result<()> {
+    let root = ProfileDirs::under(base, Some(profile_id)).root;
+    match fs::remove_dir_all(&root) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profiles_get_separate_private_directories() {
+        let temp = TempDir::new().unwrap();
+        let work = ProfileDirs::under(temp.path(), Some("work"));
+        let personal = ProfileDirs::under(temp.path(), Some("personal"));
+        work.ensure().unwrap();
+        personal.ensure().unwrap();
+        assert_ne!(work.root, personal.root);
+        assert_eq!(ProfileDirs::under(temp.path(), None).root, temp.path().join("default"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for dir in [&work.root, &work.tmp, &work.logs] {
+                assert_eq!(fs::metadata(dir).unwrap().permissions().mode() & 0o777, 0o700);
+            }
+            work.append_log("ui.log", "line\n").unwrap();
+            assert_eq!(fs::metadata(work.logs.join("ui.log")).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_unsafe_ids_cannot_escape_or_collide() {
+        let temp = TempDir::new().unwrap();
+        for id in ["../other", "a/b", "default", ""] {
+            let dirs = ProfileDirs::under(temp.path(), Some(id));
+            assert_eq!(dirs.root.parent(), Some(temp.path()), "{}", id);
+            assert_ne!(dirs.root, temp.path().join("default"), "{}", id);
+        }
+        assert_ne!(dir_name(Some("a/b")), dir_name(Some("a_b")));
+    }
+
+    #[test]
+    fn test_check_access_and_removal() {
+        let temp = TempDir::new().unwrap();
+        let work = ProfileDirs::under(temp.path(), Some("work"));
+        let personal = ProfileDirs::under(temp.path(), Some("personal"));
+        work.ensure().unwrap();
+        personal.ensure().unwrap();
+
+        assert!(work.check_access(&work.toolboxes()).is_ok());
+        assert!(work.check_access(&personal.tmp).is_err());
+        assert!(work.check_access(&personal.logs.join("../tmp")).is_err());
+        assert!(work.check_access(Path::new("/usr/local/bin")).is_ok());
+
+        remove_profile_under(temp.path(), "personal").unwrap();
+        assert!(!personal.root.exists());
+        assert!(work.root.exists());
+        remove_profile_under(temp.path(), "personal").unwrap();
+    }
+}
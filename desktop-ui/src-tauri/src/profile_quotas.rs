@@ -0,0 +1,323 @@
+//! Usage quotas per Amp profile
+//!
+//! A quota caps how many sessions a profile may start per day and how many
+//! tokens its threads may use per week, both over rolling windows. Sessions are
+//! counted from `quota_usage`, which records each new session and each batch
+//! case under the profile it runs as, so deleting sessions does not give quota
+//! back. Tokens are the input and output tokens recorded on the profile's thread
+//! messages. Creating a session or starting a batch is refused while either
+//! limit is reached; sessions without a profile are never limited.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::State;
+
+use crate::profile_auth::ProfileManager;
+
+/// Length of the rolling windows in days
+const SESSION_WINDOW_DAYS: i64 = 1;
+const TOKEN_WINDOW_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageKind {
+    Session,
+    BatchCase,
+}
+
+impl UsageKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            UsageKind::Session => "session",
+            UsageKind::BatchCase => "batch_case",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProfileQuota {
+    /// `None` is unlimited
+    pub sessions_per_day: Option<i64>,
+    pub tokens_per_week: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaStatus {
+    pub profile_id: String,
+    #[serde(flatten)]
+    pub quota: ProfileQuota,
+    /// Sessions and batch cases started in the last 24 hours
+    pub sessions_used: i64,
+    /// Tokens used in the last 7 days
+    pub tokens_used: i64,
+    pub sessions_remaining: Option<i64>,
+    pub tokens_remaining: Option<i64>,
+    /// When the oldest counted usage leaves its window, freeing quota
+    pub sessions_reset_at: Option<String>,
+    pub tokens_reset_at: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum QuotaError {
+    #[error("Profile {profile_id} has started {used} of its {limit} sessions for today; asked for {requested} more{}", resets(.resets_at))]
+    SessionsPerDay { profile_id: String, limit: i64, used: i64, requested: i64, resets_at: Option<String> },
+
+    #[error("Profile {profile_id} has used {used} of its {limit} tokens for this week{}", resets(.resets_at))]
+    TokensPerWeek { profile_id: String, limit: i64, used: i64, resets_at: Option<String> },
+
+    #[error("Failed to check quota: {0}")]
+    Database(String),
+}
+
+fn resets(at: &Option<String>) -> String {
+    at.as_ref().map(|at| format!(" (more becomes available at {})", at)).unwrap_or_default()
+}
+
+impl QuotaError {
+    /// JSON form returned to the frontend: the error fields plus a readable `message`
+    pub fn to_command_error(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("message".to_string(), serde_json::Value::String(self.to_string()));
+        }
+        value.to_string()
+    }
+}
+
+pub async fn get_quota(db: &SqlitePool, profile_id: &str) -> Result<ProfileQuota, String> {
+    let quota = sqlx::query_as::<_, ProfileQuota>(
+        "SELECT sessions_per_day, tokens_per_week FROM profile_quotas WHERE profile_id = ?",
+    )
+    .bind(profile_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to read quota: {}", e))?;
+    Ok(quota.unwrap_or_default())
+}
+
+/// Replace a profile's quota; removing both limits deletes it
+pub async fn set_quota(db: &SqlitePool, profile_id: &str, quota: &ProfileQuota) -> Result<(), String> {
+    if quota.sessions_per_day.is_some_and(|n| n < 0) || quota.tokens_per_week.is_some_and(|n| n < 0) {
+        return Err("Quota limits cannot be negative".to_string());
+    }
+    let result = if quota == &ProfileQuota::default() {
+        sqlx::query("DELETE FROM profile_quotas WHERE profile_id = ?").bind(profile_id).execute(db).await
+    } else {
+        sqlx::query(
+            "INSERT INTO profile_quotas (profile_id, sessions_per_day, tokens_per_week) VALUES (?, ?, ?)
+             ON CONFLICT(profile_id) DO UPDATE SET sessions_per_day = excluded.sessions_per_day,
+                 tokens_per_week = excluded.tokens_per_week, updated_at = (datetime('now', 'utc') || 'Z')",
+        )
+        .bind(profile_id)
+        .bind(quota.sessions_per_day)
+        .bind(quota.tokens_per_week)
+        .execute(db)
+        .await
+    };
+    result.map_err(|e| format!("Failed to save quota: {}", e))?;
+    Ok(())
+}
+
+/// Record sessions or batch cases started under a profile
+pub async fn record(db: &SqlitePool, profile_id: &str, kind: UsageKind, amount: i64) -> Result<(), String> {
+    sqlx::query("INSERT INTO quota_usage (profile_id, kind, amount) VALUES (?, ?, ?)")
+        .bind(profile_id)
+        .bind(kind.as_str())
+        .bind(amount)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to record quota usage: {}", e))?;
+    Ok(())
+}
+
+/// Usage in the current windows with the time each one next frees quota
+async fn usage(db: &SqlitePool, profile_id: &str) -> Result<(i64, Option<String>, i64, Option<String>), String> {
+    let (sessions, sessions_reset_at) = sqlx::query_as::<_, (i64, Option<String>)>(
+        "SELECT COALESCE(SUM(amount), 0), MIN(created_at) FROM quota_usage
+         WHERE profile_id = ? AND created_at >= datetime('now', 'utc', ?) || 'Z'",
+    )
+    .bind(profile_id)
+    .bind(format!("-{} days", SESSION_WINDOW_DAYS))
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to count sessions: {}", e))?;
+    let (tokens, tokens_reset_at) = sqlx::query_as::<_, (i64, Option<String>)>(
+        "SELECT COALESCE(SUM(COALESCE(m.input_tokens, 0) + COALESCE(m.output_tokens, 0)), 0), MIN(m.created_at)
+         FROM messages m
+         JOIN threads t ON t.id = m.thread_id
+         JOIN sessions s ON s.id = t.session_id
+         WHERE s.amp_profile_id = ? AND m.created_at >= datetime('now', 'utc', ?) || 'Z'
+           AND (m.input_tokens IS NOT NULL OR m.output_tokens IS NOT NULL)",
+    )
+    .bind(profile_id)
+    .bind(format!("-{} days", TOKEN_WINDOW_DAYS))
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to count tokens: {}", e))?;
+    Ok((
+        sessions,
+        window_end(sessions_reset_at, SESSION_WINDOW_DAYS),
+        tokens,
+        window_end(tokens_reset_at, TOKEN_WINDOW_DAYS),
+    ))
+}
+
+/// When usage stamped `start` leaves a window of `days`
+fn window_end(start: Option<String>, days: i64) -> Option<String> {
+    let start = chrono::NaiveDateTime::parse_from_str(start?.trim_end_matches('Z'), "%Y-%m-%d %H:%M:%S").ok()?;
+    Some(format!("{}Z", (start + chrono::Duration::days(days)).format("%Y-%m-%d %H:%M:%S")))
+}
+
+pub async fn status(db: &SqlitePool, profile_id: &str) -> Result<QuotaStatus, String> {
+    let quota = get_quota(db, profile_id).await?;
+    let (sessions_used, sessions_reset_at, tokens_used, tokens_reset_at) = usage(db, profile_id).await?;
+    Ok(QuotaStatus {
+        profile_id: profile_id.to_string(),
+        sessions_remaining: quota.sessions_per_day.map(|limit| (limit - sessions_used).max(0)),
+        tokens_remaining: quota.tokens_per_week.map(|limit| (limit - tokens_used).max(0)),
+        quota,
+        sessions_used,
+        tokens_used,
+        sessions_reset_at,
+        tokens_reset_at,
+    })
+}
+
+/// Refuse starting `sessions` more sessions under the profile when they would
+/// exceed its daily limit or its weekly tokens are used up
+pub async fn check(db: &SqlitePool, profile_id: Option<&str>, sessions: i64) -> Result<(), QuotaError> {
+    let Some(profile_id) = profile_id else {
+        return Ok(());
+    };
+    let status = status(db, profile_id).await.map_err(QuotaError::Database)?;
+    if let Some(limit) = status.quota.sessions_per_day {
+        if status.sessions_used + sessions > limit {
+            return Err(QuotaError::SessionsPerDay {
+                profile_id: profile_id.to_string(),
+                limit,
+                used: status.sessions_used,
+                requested: sessions,
+                resets_at: status.sessions_reset_at,
+            });
+        }
+    }
+    if let Some(limit) = status.quota.tokens_per_week {
+        if status.tokens_used >= limit {
+            return Err(QuotaError::TokensPerWeek {
+                profile_id: profile_id.to_string(),
+                limit,
+                used: status.tokens_used,
+                resets_at: status.tokens_reset_at,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Limits and current usage of a profile
+#[tauri::command]
+pub async fn get_quota_status(profile_id: String, profile_manager: State<'_, ProfileManager>) -> Result<QuotaStatus, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    status(db, &profile_id).await
+}
+
+/// Set a profile's limits; leave both out to remove its quota
+#[tauri::command]
+pub async fn set_profile_quota(
+    profile_id: String,
+    quota: ProfileQuota,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<QuotaStatus, String> {
+    if !profile_manager.profiles.contains_key(&profile_id) {
+        return Err(format!("Profile '{}' not found", profile_id));
+    }
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    set_quota(db, &profile_id, &quota).await?;
+    status(db, &profile_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_session_quota_counts_rolling_day() {
+        let db = test_pool().await;
+        let quota = ProfileQuota { sessions_per_day: Some(3), tokens_per_week: None };
+        set_quota(&db, "lab", &quota).await.unwrap();
+        assert!(set_quota(&db, "lab", &ProfileQuota { sessions_per_day: Some(-1), tokens_per_week: None }).await.is_err());
+
+        record(&db, "lab", UsageKind::Session, 1).await.unwrap();
+        // Outside the window
+        sqlx::query("INSERT INTO quota_usage (profile_id, kind, amount, created_at) VALUES ('lab', 'batch_case', 5, datetime('now', 'utc', '-2 days') || 'Z')")
+            .execute(&db)
+            .await
+            .unwrap();
+        check(&db, Some("lab"), 2).await.unwrap();
+
+        record(&db, "lab", UsageKind::BatchCase, 2).await.unwrap();
+        let status = status(&db, "lab").await.unwrap();
+        assert_eq!((status.sessions_used, status.sessions_remaining), (3, Some(0)));
+        assert!(status.sessions_reset_at.is_some());
+        match check(&db, Some("lab"), 1).await.unwrap_err() {
+            QuotaError::SessionsPerDay { limit, used, requested, .. } => assert_eq!((limit, used, requested), (3, 3, 1)),
+            other => panic!("unexpected error {:?}", other),
+        }
+        let error: serde_json::Value =
+            serde_json::from_str(&check(&db, Some("lab"), 1).await.unwrap_err().to_command_error()).unwrap();
+        assert_eq!(error["code"], "sessions_per_day");
+        assert!(error["message"].as_str().unwrap().contains("3 of its 3 sessions"));
+
+        // No profile, or no quota, is never limited
+        check(&db, None, 100).await.unwrap();
+        check(&db, Some("other"), 100).await.unwrap();
+        set_quota(&db, "lab", &ProfileQuota::default()).await.unwrap();
+        check(&db, Some("lab"), 100).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_quota_sums_profile_messages() {
+        let db = test_pool().await;
+        set_quota(&db, "lab", &ProfileQuota { sessions_per_day: None, tokens_per_week: Some(1000) }).await.unwrap();
+        for (session, profile) in [("s-lab", "lab"), ("s-other", "other")] {
+            sqlx::query("INSERT INTO sessions (id, amp_profile_id) VALUES (?, ?)").bind(session).bind(profile).execute(&db).await.unwrap();
+            sqlx::query("INSERT INTO threads (id, session_id, context) VALUES (?, ?, 'production')")
+                .bind(format!("t-{}", session))
+                .bind(session)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        for (id, thread, input, output, age) in [
+            ("m1", "t-s-lab", 300, 200, "-1 hours"),
+            ("m2", "t-s-lab", 400, 50, "-3 days"),
+            ("m3", "t-s-lab", 5000, 0, "-8 days"),
+            ("m4", "t-s-other", 9000, 0, "-1 hours"),
+        ] {
+            sqlx::query(
+                "INSERT INTO messages (id, thread_id, role, content, input_tokens, output_tokens, created_at)
+                 VALUES (?, ?, 'assistant', '{}', ?, ?, datetime('now', 'utc', ?) || 'Z')",
+            )
+            .bind(id)
+            .bind(thread)
+            .bind(input)
+            .bind(output)
+            .bind(age)
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let status = status(&db, "lab").await.unwrap();
+        assert_eq!((status.tokens_used, status.tokens_remaining), (950, Some(50)));
+        check(&db, Some("lab"), 1).await.unwrap();
+
+        sqlx::query("UPDATE messages SET output_tokens = 100 WHERE id = 'm2'").execute(&db).await.unwrap();
+        let error = check(&db, Some("lab"), 1).await.unwrap_err();
+        assert!(matches!(error, QuotaError::TokensPerWeek { limit: 1000, used: 1000, .. }));
+        assert!(error.to_string().contains("more becomes available at"));
+    }
+}
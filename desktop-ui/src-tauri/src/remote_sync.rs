@@ -0,0 +1,1180 @@
+//! Session sync across devices through user-provided storage
+//!
+//! Every session is stored remotely as one gzip-compressed JSON document,
+//! `sessions/<id>.json.gz`, holding its row, its threads and their messages.
+//! `index.json` maps session IDs to vector clocks so a sync only downloads the
+//! sessions another device has changed. A device bumps its own entry in a
+//! session's clock whenever it changed the session since the last sync: when
+//! one side's clock contains the other's, that side wins outright, and when the
+//! two are concurrent the rows are merged last-writer-wins on the time and
+//! device of each row's last change. A deleted session is pushed as a tombstone
+//! that moves the session to the trash on other devices, unless they edited it
+//! concurrently, in which case the edit wins. Both kinds of decision are
+//! recorded as conflicts (see `conflicts`) for the user to confirm or reverse.
+//! Deleting single threads or messages is not synced.
+//!
+//! Columns that only make sense on one device (short codes, profile references
+//! and where message content is stored) never leave it. Backends are an
+//! S3-compatible bucket, a WebDAV collection or a plain folder.
+//!
+//! With a sync key set, every document and the index are sealed to it (see
+//! `sealed_box`) before they leave the device, so the storage provider only
+//! ever holds ciphertext. Each device needs the key's secret half, created on
+//! one device and imported on the others. Data that isn't sealed is then refused,
+//! since anyone able to write to the storage could plant it, so an encrypted
+//! sync needs storage holding nothing pushed in the clear.
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::{AppState, SessionCodeConfig, SyncBackend, SyncConfig};
+use crate::conflicts::{self, Conflict, ConflictKind, ConflictSource};
+use crate::keychain_auth::{KeychainAuth, TokenType};
+use crate::message_content::{resolve_content, store_content};
+use crate::profile_auth::ProfileManager;
+use crate::sealed_box::{self, SecretKey};
+use crate::trash::{bind_json, row_to_json, session_captures, TrashKind, TrashStore};
+
+/// Keychain entry holding the backend's secret
+const KEYCHAIN_ID: &str = "remote-sync";
+const KEY_KEYCHAIN_ID: &str = "remote-sync-key";
+const INDEX_KEY: &str = "index.json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const SCHEDULE_POLL: Duration = Duration::from_secs(60);
+/// Hash recorded for a session whose tombstone has been synced
+const TOMBSTONE: &str = "";
+/// Columns each table keeps to itself
+const LOCAL_COLUMNS: [(&str, &[&str]); 2] = [
+    ("sessions", &["short_code", "profile_id", "amp_profile_id"]),
+    ("messages", &["content_ref", "content_size"]),
+];
+
+pub type VectorClock = BTreeMap<String, u64>;
+type Row = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockOrder {
+    Equal,
+    Before,
+    After,
+    Concurrent,
+}
+
+/// How clock `a` relates to clock `b`
+pub fn compare(a: &VectorClock, b: &VectorClock) -> ClockOrder {
+    let (mut behind, mut ahead) = (false, false);
+    for device in a.keys().chain(b.keys()).collect::<BTreeSet<_>>() {
+        let (x, y) = (a.get(device).copied().unwrap_or(0), b.get(device).copied().unwrap_or(0));
+        behind |= x < y;
+        ahead |= x > y;
+    }
+    match (behind, ahead) {
+        (false, false) => ClockOrder::Equal,
+        (true, false) => ClockOrder::Before,
+        (false, true) => ClockOrder::After,
+        (true, true) => ClockOrder::Concurrent,
+    }
+}
+
+fn merge_clocks(a: &VectorClock, b: &VectorClock) -> VectorClock {
+    let mut merged = a.clone();
+    for (device, counter) in b {
+        let entry = merged.entry(device.clone()).or_default();
+        *entry = (*entry).max(*counter);
+    }
+    merged
+}
+
+/// When and where a row last changed; later stamps win, ties go to the higher device ID
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Stamp {
+    pub ms: i64,
+    pub device: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stamped {
+    pub row: Row,
+    pub stamp: Stamp,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionDoc {
+    pub clock: VectorClock,
+    /// None for a deleted session
+    pub session: Option<Stamped>,
+    pub threads: BTreeMap<String, Stamped>,
+    pub messages: BTreeMap<String, Stamped>,
+    /// When the session was deleted, for tombstones
+    pub deleted: Option<Stamp>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub pushed: Vec<String>,
+    pub pulled: Vec<String>,
+    /// Conflicts settled automatically, which stay open for review until resolved
+    pub conflicts: Vec<Conflict>,
+    /// Sessions moved to the trash because another device deleted them
+    pub deleted: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Session,
+    Thread,
+    Message,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Session => "session",
+            Kind::Thread => "thread",
+            Kind::Message => "message",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Kind::Session => "sessions",
+            Kind::Thread => "threads",
+            Kind::Message => "messages",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Storage backends
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// SigV4 signing key for a day, region and service
+pub fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+/// Headers signing an S3 request without a query string
+fn s3_headers(
+    method: &str,
+    url: &reqwest::Url,
+    region: &str,
+    access_key_id: &str,
+    secret: &str,
+    payload: &[u8],
+    now: chrono::DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex::encode(Sha256::digest(payload));
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method,
+        url.path(),
+        host,
+        payload_hash,
+        amz_date,
+        signed_headers,
+        payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signature = hex::encode(hmac(&signing_key(secret, &date, region, "s3"), &string_to_sign));
+    vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload_hash),
+        (
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key_id, scope, signed_headers, signature
+            ),
+        ),
+    ]
+}
+
+pub enum Storage {
+    S3 {
+        client: reqwest::Client,
+        /// `<endpoint>/<bucket>/<prefix>`, without a trailing slash
+        base: String,
+        region: String,
+        access_key_id: String,
+        secret: String,
+    },
+    Webdav {
+        client: reqwest::Client,
+        url: String,
+        username: String,
+        password: String,
+    },
+    Folder(PathBuf),
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        base.trim_end_matches('/').to_string()
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), path)
+    }
+}
+
+impl Storage {
+    pub fn open(backend: &SyncBackend, secret: Option<String>) -> Result<Self, String> {
+        let client = || reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| e.to_string());
+        Ok(match backend {
+            SyncBackend::S3 { endpoint, bucket, region, prefix, access_key_id } => Storage::S3 {
+                client: client()?,
+                base: join_url(&join_url(endpoint, bucket), prefix),
+                region: region.clone(),
+                access_key_id: access_key_id.clone(),
+                secret: secret.ok_or("No S3 secret key is stored for sync")?,
+            },
+            SyncBackend::Webdav { url, username } => Storage::Webdav {
+                client: client()?,
+                url: url.clone(),
+                username: username.clone(),
+                password: secret.unwrap_or_default(),
+            },
+            SyncBackend::Folder { path } => Storage::Folder(PathBuf::from(path)),
+        })
+    }
+
+    async fn send(&self, method: reqwest::Method, key: &str, body: Vec<u8>) -> Result<reqwest::Response, String> {
+        let request = match self {
+            Storage::S3 { client, base, region, access_key_id, secret } => {
+                let url = reqwest::Url::parse(&join_url(base, key)).map_err(|e| format!("Invalid S3 URL: {}", e))?;
+                let headers = s3_headers(method.as_str(), &url, region, access_key_id, secret, &body, Utc::now());
+                headers.into_iter().fold(client.request(method, url), |request, (name, value)| request.header(name, value))
+            }
+            Storage::Webdav { client, url, username, password } => client
+                .request(method, join_url(url, key))
+                .basic_auth(username, (!password.is_empty()).then_some(password)),
+            Storage::Folder(_) => unreachable!("folders are not accessed over HTTP"),
+        };
+        request.body(body).send().await.map_err(|e| format!("Sync request for {} failed: {}", key, e))
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        if let Storage::Folder(root) = self {
+            return match std::fs::read(root.join(key)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("Failed to read {}: {}", root.join(key).display(), e)),
+            };
+        }
+        let response = self.send(reqwest::Method::GET, key, Vec::new()).await?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => response.bytes().await.map(|b| Some(b.to_vec())).map_err(|e| e.to_string()),
+            status => Err(format!("Reading {} failed with {}", key, status)),
+        }
+    }
+
+    pub async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String> {
+        if let Storage::Folder(root) = self {
+            let path = root.join(key);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            return crate::safe_write::write_atomic(&path, &data, None, None).map(|_| ()).map_err(|e| e.to_string());
+        }
+        let mut status = self.send(reqwest::Method::PUT, key, data.clone()).await?.status();
+        // WebDAV refuses to write into collections that do not exist yet
+        if status == reqwest::StatusCode::CONFLICT && matches!(self, Storage::Webdav { .. }) {
+            let mkcol = reqwest::Method::from_bytes(b"MKCOL").expect("valid method");
+            let segments: Vec<&str> = key.split('/').collect();
+            for depth in 1..segments.len() {
+                self.send(mkcol.clone(), &format!("{}/", segments[..depth].join("/")), Vec::new()).await?;
+            }
+            status = self.send(reqwest::Method::PUT, key, data).await?.status();
+        }
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(format!("Writing {} failed with {}", key, status))
+        }
+    }
+}
+
+fn doc_key(session_id: &str) -> String {
+    format!("sessions/{}.json.gz", session_id)
+}
+
+fn encode_doc(doc: &SessionDoc) -> Result<Vec<u8>, String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, doc).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+fn decode_doc(data: &[u8]) -> Result<SessionDoc, String> {
+    let mut raw = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut raw).map_err(|e| format!("Corrupt session document: {}", e))?;
+    serde_json::from_slice(&raw).map_err(|e| format!("Corrupt session document: {}", e))
+}
+
+/// What is pushed for `data`: sealed to `key`, or as it is without one
+fn seal(key: Option<&SecretKey>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    match key {
+        Some(key) => sealed_box::seal(&key.public_key(), &data),
+        None => Ok(data),
+    }
+}
+
+/// Whether `data` is a document or index as pushed without a key: gzip or JSON
+fn pushed_in_clear(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b]) || data.starts_with(b"{")
+}
+
+/// The plaintext of pulled data, which must be sealed to `key` if there is one
+fn unseal(key: Option<&SecretKey>, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    match key {
+        Some(key) => sealed_box::open(key, &data).map_err(|e| match pushed_in_clear(&data) {
+            true => "Synced data is not encrypted and was refused; encrypted sync needs storage with nothing pushed in the clear".to_string(),
+            false => format!("{}; is the sync key the one the other devices use?", e),
+        }),
+        None if !pushed_in_clear(&data) => Err("Synced data is encrypted, import the sync key to read it".to_string()),
+        None => Ok(data),
+    }
+}
+
+async fn load_index(storage: &Storage, key: Option<&SecretKey>) -> Result<BTreeMap<String, VectorClock>, String> {
+    match storage.get(INDEX_KEY).await? {
+        Some(data) => serde_json::from_slice(&unseal(key, data)?).map_err(|e| format!("Corrupt sync index: {}", e)),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Local state
+
+fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Drop the columns a table keeps to itself
+fn strip_local(table: &str, row: &mut Row) {
+    for (local_table, columns) in LOCAL_COLUMNS {
+        if local_table == table {
+            for column in columns {
+                row.remove(*column);
+            }
+        }
+    }
+}
+
+/// Content hash of a row, ignoring `updated_at`, which triggers rewrite on every update
+fn row_hash(row: &Row) -> String {
+    let mut row = row.clone();
+    row.remove("updated_at");
+    blake3::hash(Value::Object(row).to_string().as_bytes()).to_hex().to_string()
+}
+
+fn row_id(row: &Row) -> String {
+    row.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string()
+}
+
+/// A session's rows as they are synced: the session row (None when it does not
+/// exist here), its threads, and their messages with content inlined. Also
+/// returns the IDs of messages held in thread archives.
+async fn read_rows(db: &SqlitePool, session_id: &str) -> Result<(Option<Row>, Vec<Row>, Vec<Row>, HashSet<String>), String> {
+    let select = |sql: &'static str| async move {
+        let rows = sqlx::query(sql).bind(session_id).fetch_all(db).await?;
+        rows.iter().map(row_to_json).collect::<Result<Vec<Row>, sqlx::Error>>()
+    };
+    let read_error = |e: sqlx::Error| format!("Failed to read session {}: {}", session_id, e);
+    let mut session = select("SELECT * FROM sessions WHERE id = ?").await.map_err(read_error)?.pop();
+    let mut threads = select("SELECT * FROM threads WHERE session_id = ? ORDER BY created_at, id").await.map_err(read_error)?;
+    let mut messages = select(
+        "SELECT * FROM messages WHERE thread_id IN (SELECT id FROM threads WHERE session_id = ?) ORDER BY created_at, rowid",
+    )
+    .await
+    .map_err(read_error)?;
+
+    if let Some(row) = session.as_mut() {
+        strip_local("sessions", row);
+    }
+    for message in &mut messages {
+        let content_ref = message.get("content_ref").and_then(|v| v.as_str()).map(str::to_string);
+        if content_ref.is_some() {
+            let content = message.get("content").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            message.insert("content".to_string(), Value::String(resolve_content(content, content_ref)));
+        }
+        strip_local("messages", message);
+    }
+
+    let mut archived = HashSet::new();
+    for thread in &mut threads {
+        strip_local("threads", thread);
+        let thread_id = row_id(thread);
+        for message in crate::archive_compression::archived_messages(db, &thread_id).await?.unwrap_or_default() {
+            let Ok(Value::Object(mut row)) = serde_json::to_value(&message) else {
+                continue;
+            };
+            row.insert("thread_id".to_string(), Value::String(thread_id.clone()));
+            archived.insert(message.id);
+            messages.push(row);
+        }
+    }
+    Ok((session, threads, messages, archived))
+}
+
+/// A session as this device has it, stamped against what it last synced
+struct LocalState {
+    doc: SessionDoc,
+    /// Clock as of the last sync, before counting changes made since
+    synced_clock: VectorClock,
+    changed: bool,
+    /// Whether the session has synced from or to this device before
+    known: bool,
+    hashes: HashMap<(Kind, String), String>,
+    /// Rows changed here since the last sync
+    edited: HashSet<(Kind, String)>,
+    /// Hash and stamp of every row as of the last sync, by kind name and ID
+    bases: HashMap<(String, String), (String, Stamp)>,
+    archived: HashSet<String>,
+}
+
+async fn local_state(db: &SqlitePool, session_id: &str, device: &str) -> Result<Option<LocalState>, String> {
+    let (session, threads, messages, archived) = read_rows(db, session_id).await?;
+    let stored: Option<(String, bool)> = sqlx::query_as("SELECT clock, deleted FROM sync_sessions WHERE session_id = ?")
+        .bind(session_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read sync state: {}", e))?;
+    let bases: HashMap<(String, String), (String, Stamp)> =
+        sqlx::query_as::<_, (String, String, String, i64, String)>(
+            "SELECT kind, id, hash, stamp_ms, device FROM sync_rows WHERE session_id = ?",
+        )
+        .bind(session_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read sync state: {}", e))?
+        .into_iter()
+        .map(|(kind, id, hash, ms, device)| ((kind, id), (hash, Stamp { ms, device })))
+        .collect();
+
+    let known = stored.is_some();
+    if session.is_none() && !known {
+        return Ok(None);
+    }
+    let synced_clock: VectorClock = stored.as_ref().and_then(|(clock, _)| serde_json::from_str(clock).ok()).unwrap_or_default();
+    let mut state = LocalState {
+        doc: SessionDoc { clock: synced_clock.clone(), ..Default::default() },
+        synced_clock,
+        changed: false,
+        known,
+        hashes: HashMap::new(),
+        edited: HashSet::new(),
+        bases: HashMap::new(),
+        archived,
+    };
+    let now = Stamp { ms: now_ms(), device: device.to_string() };
+    let session_base = bases.get(&(Kind::Session.as_str().to_string(), session_id.to_string()));
+
+    match session {
+        Some(row) => {
+            let mut stamp_row = |kind: Kind, row: Row| {
+                let id = row_id(&row);
+                let hash = row_hash(&row);
+                let stamp = match bases.get(&(kind.as_str().to_string(), id.clone())) {
+                    Some((base_hash, stamp)) if *base_hash == hash => stamp.clone(),
+                    _ => {
+                        state.changed = true;
+                        state.edited.insert((kind, id.clone()));
+                        now.clone()
+                    }
+                };
+                state.hashes.insert((kind, id.clone()), hash);
+                (id, Stamped { row, stamp })
+            };
+            let (_, session) = stamp_row(Kind::Session, row);
+            let threads: Vec<_> = threads.into_iter().map(|row| stamp_row(Kind::Thread, row)).collect();
+            let messages: Vec<_> = messages.into_iter().map(|row| stamp_row(Kind::Message, row)).collect();
+            state.doc.session = Some(session);
+            state.doc.threads = threads.into_iter().collect();
+            state.doc.messages = messages.into_iter().collect();
+        }
+        None => match session_base {
+            Some((hash, stamp)) if hash == TOMBSTONE => state.doc.deleted = Some(stamp.clone()),
+            _ => {
+                // Deleted here since the last sync
+                state.changed = true;
+                state.doc.deleted = Some(now.clone());
+            }
+        },
+    }
+    if state.changed {
+        *state.doc.clock.entry(device.to_string()).or_default() += 1;
+    }
+    state.bases = bases;
+    Ok(Some(state))
+}
+
+/// Union of two row sets, keeping the later write of rows both have
+fn last_writer_wins(a: &BTreeMap<String, Stamped>, b: &BTreeMap<String, Stamped>) -> BTreeMap<String, Stamped> {
+    let mut merged = a.clone();
+    for (id, row) in b {
+        if merged.get(id).is_none_or(|current| current.stamp < row.stamp) {
+            merged.insert(id.clone(), row.clone());
+        }
+    }
+    merged
+}
+
+/// Merge what this device has with the remote document, given how their clocks relate
+fn merge_docs(local: &SessionDoc, remote: &SessionDoc, order: ClockOrder, device: &str) -> SessionDoc {
+    match order {
+        ClockOrder::Equal | ClockOrder::After => local.clone(),
+        // Remote has seen everything here; rows it lacks are kept since deletes of rows are not synced
+        ClockOrder::Before => {
+            let mut threads = local.threads.clone();
+            threads.extend(remote.threads.clone());
+            let mut messages = local.messages.clone();
+            messages.extend(remote.messages.clone());
+            SessionDoc { clock: remote.clock.clone(), session: remote.session.clone(), threads, messages, deleted: remote.deleted.clone() }
+        }
+        ClockOrder::Concurrent => {
+            let session = match (&local.session, &remote.session) {
+                (Some(a), Some(b)) => Some(if a.stamp >= b.stamp { a.clone() } else { b.clone() }),
+                // An edit on one side beats a concurrent delete on the other
+                (Some(a), None) => Some(a.clone()),
+                (None, Some(b)) => Some(b.clone()),
+                (None, None) => None,
+            };
+            let deleted = if session.is_none() { local.deleted.clone().max(remote.deleted.clone()) } else { None };
+            let mut clock = merge_clocks(&local.clock, &remote.clock);
+            *clock.entry(device.to_string()).or_default() += 1;
+            SessionDoc {
+                clock,
+                session,
+                threads: last_writer_wins(&local.threads, &remote.threads),
+                messages: last_writer_wins(&local.messages, &remote.messages),
+                deleted,
+            }
+        }
+    }
+}
+
+/// What a concurrent merge decided on the user's behalf: rows edited here that
+/// the other device changed too, and deletes overruled by an edit
+fn detect_conflicts(session_id: &str, local: &LocalState, remote: &SessionDoc, merged: &SessionDoc) -> Vec<Conflict> {
+    let session_rows = |doc: &SessionDoc| -> BTreeMap<String, Stamped> {
+        doc.session.iter().map(|session| (session_id.to_string(), session.clone())).collect()
+    };
+    let (ours, theirs, kept) = (session_rows(&local.doc), session_rows(remote), session_rows(merged));
+    let sets = [
+        (Kind::Session, &ours, &theirs, &kept),
+        (Kind::Thread, &local.doc.threads, &remote.threads, &merged.threads),
+        (Kind::Message, &local.doc.messages, &remote.messages, &merged.messages),
+    ];
+
+    let mut conflicts = Vec::new();
+    for (kind, ours, theirs, kept) in sets {
+        for (id, mine) in ours {
+            let Some(other) = theirs.get(id) else {
+                continue;
+            };
+            let base = local.bases.get(&(kind.as_str().to_string(), id.clone())).map(|(_, stamp)| stamp);
+            let changed_there = base != Some(&other.stamp);
+            if !local.edited.contains(&(kind, id.clone())) || !changed_there || row_hash(&mine.row) == row_hash(&other.row) {
+                continue;
+            }
+            let applied = if kept.get(id).is_some_and(|row| row.stamp == mine.stamp) { "local" } else { "remote" };
+            let options = vec![
+                Conflict::option("local", Value::Object(mine.row.clone())),
+                Conflict::option("remote", Value::Object(other.row.clone())),
+            ];
+            conflicts.push(Conflict::new(ConflictSource::Sync, ConflictKind::Edit, session_id, Some((kind.table(), id)), options, applied));
+        }
+    }
+    if merged.session.is_some() && (local.doc.session.is_none() || remote.session.is_none()) {
+        let options = vec![Conflict::option("keep", Value::Null), Conflict::option("delete", Value::Null)];
+        conflicts.push(Conflict::new(ConflictSource::Sync, ConflictKind::Delete, session_id, None, options, "keep"));
+    }
+    conflicts
+}
+
+/// Columns each synced table has here; rows from newer or older schemas are cut to them
+async fn table_columns(db: &SqlitePool) -> Result<HashMap<&'static str, HashSet<String>>, String> {
+    let mut tables = HashMap::new();
+    for kind in [Kind::Session, Kind::Thread, Kind::Message] {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(kind.table())
+            .fetch_all(db)
+            .await
+            .map_err(|e| format!("Failed to read columns of {}: {}", kind.table(), e))?;
+        tables.insert(kind.table(), columns.into_iter().collect());
+    }
+    Ok(tables)
+}
+
+async fn upsert(tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>, table: &str, row: &Row, columns: &HashSet<String>) -> Result<(), String> {
+    let names: Vec<&String> = row.keys().filter(|name| columns.contains(*name)).collect();
+    let quoted: Vec<String> = names.iter().map(|name| format!("\"{}\"", name.replace('"', "\"\""))).collect();
+    let updates: Vec<String> = quoted.iter().filter(|q| *q != "\"id\"").map(|q| format!("{0} = excluded.{0}", q)).collect();
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT(id) DO UPDATE SET {}",
+        table,
+        quoted.join(", "),
+        vec!["?"; quoted.len()].join(", "),
+        updates.join(", ")
+    );
+    let query = names.iter().fold(sqlx::query(&sql), |query, name| bind_json(query, &row[name.as_str()]));
+    query.execute(&mut **tx).await.map_err(|e| format!("Failed to write {} row {}: {}", table, row_id(row), e))?;
+    Ok(())
+}
+
+/// A synced message row with its content stored the way this device stores it
+fn with_stored_content(message: &Row) -> Result<Row, String> {
+    let mut row = message.clone();
+    let stored = store_content(row.get("content").and_then(|v| v.as_str()).unwrap_or_default())?;
+    row.insert("content".to_string(), Value::String(stored.content));
+    row.insert("content_ref".to_string(), stored.content_ref.map(Value::String).unwrap_or(Value::Null));
+    row.insert("content_size".to_string(), Value::from(stored.content_size));
+    Ok(row)
+}
+
+/// Move a session another device deleted to the trash
+pub(crate) async fn trash_session(db: &SqlitePool, session_id: &str) -> Result<(), String> {
+    let [_, captures] = session_captures(session_id);
+    let metadata = serde_json::json!({ "deleted_by_sync": true });
+    TrashStore::new(db.clone())
+        .trash_rows(TrashKind::Session, session_id, &captures, metadata)
+        .await
+        .map_err(|e| format!("Failed to trash session {}: {}", session_id, e))?;
+    Ok(())
+}
+
+/// Write a single row in its synced form, e.g. the version a conflict resolution picked
+pub(crate) async fn write_row(db: &SqlitePool, table: &str, row: &Row) -> Result<(), String> {
+    let columns = table_columns(db).await?;
+    let table_columns = columns.get(table).ok_or_else(|| format!("{} rows are not synced", table))?;
+    let row = if table == "messages" { with_stored_content(row)? } else { row.clone() };
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    upsert(&mut tx, table, &row, table_columns).await?;
+    tx.commit().await.map_err(|e| format!("Failed to write {} row {}: {}", table, row_id(&row), e))
+}
+
+/// Write the merged document's rows that differ from this device's. Returns
+/// whether the session was moved to the trash.
+async fn apply(
+    db: &SqlitePool,
+    session_id: &str,
+    doc: &SessionDoc,
+    local: Option<&LocalState>,
+    columns: &HashMap<&'static str, HashSet<String>>,
+    codes: &SessionCodeConfig,
+) -> Result<bool, String> {
+    let exists_here = local.is_some_and(|l| l.doc.session.is_some());
+    let Some(session) = &doc.session else {
+        if !exists_here {
+            return Ok(false);
+        }
+        trash_session(db, session_id).await?;
+        return Ok(true);
+    };
+
+    let differs = |kind: Kind, row: &Stamped| local.and_then(|l| l.hashes.get(&(kind, row_id(&row.row)))) != Some(&row_hash(&row.row));
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    if differs(Kind::Session, session) {
+        upsert(&mut tx, "sessions", &session.row, &columns["sessions"]).await?;
+    }
+    for thread in doc.threads.values().filter(|thread| differs(Kind::Thread, thread)) {
+        upsert(&mut tx, "threads", &thread.row, &columns["threads"]).await?;
+    }
+    for (id, message) in &doc.messages {
+        // Archived history stays in its archive
+        if local.is_some_and(|l| l.archived.contains(id)) || !differs(Kind::Message, message) {
+            continue;
+        }
+        upsert(&mut tx, "messages", &with_stored_content(&message.row)?, &columns["messages"]).await?;
+    }
+    tx.commit().await.map_err(|e| format!("Failed to apply synced session {}: {}", session_id, e))?;
+
+    if !exists_here {
+        crate::session_codes::assign(db, session_id, codes).await?;
+    }
+    Ok(false)
+}
+
+/// Remember the session as synced: its clock, and every row as now stored here
+async fn save_state(db: &SqlitePool, session_id: &str, doc: &SessionDoc, device: &str) -> Result<(), String> {
+    let mut bases: Vec<(Kind, String, String, Stamp)> = Vec::new();
+    match (&doc.session, &doc.deleted) {
+        (None, deleted) => {
+            let stamp = deleted.clone().unwrap_or(Stamp { ms: now_ms(), device: device.to_string() });
+            bases.push((Kind::Session, session_id.to_string(), TOMBSTONE.to_string(), stamp));
+        }
+        (Some(session), _) => {
+            // Hash rows as read back, so values the database normalizes do not look like local edits
+            let (row, threads, messages, _) = read_rows(db, session_id).await?;
+            let stamps = [(Kind::Thread, &doc.threads), (Kind::Message, &doc.messages)];
+            if let Some(row) = row {
+                bases.push((Kind::Session, session_id.to_string(), row_hash(&row), session.stamp.clone()));
+            }
+            for (kind, rows) in [(Kind::Thread, threads), (Kind::Message, messages)] {
+                let synced = stamps.iter().find(|(k, _)| *k == kind).map(|(_, rows)| *rows).unwrap();
+                for row in rows {
+                    let id = row_id(&row);
+                    if let Some(stamped) = synced.get(&id) {
+                        bases.push((kind, id, row_hash(&row), stamped.stamp.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    let clock = serde_json::to_string(&doc.clock).map_err(|e| e.to_string())?;
+    let save_error = |e: sqlx::Error| format!("Failed to save sync state of {}: {}", session_id, e);
+    let mut tx = db.begin().await.map_err(save_error)?;
+    sqlx::query(
+        "INSERT INTO sync_sessions (session_id, clock, deleted, synced_at) VALUES (?, ?, ?, datetime('now', 'utc') || 'Z')
+         ON CONFLICT(session_id) DO UPDATE SET clock = excluded.clock, deleted = excluded.deleted, synced_at = excluded.synced_at",
+    )
+    .bind(session_id)
+    .bind(&clock)
+    .bind(doc.session.is_none())
+    .execute(&mut *tx)
+    .await
+    .map_err(save_error)?;
+    sqlx::query("DELETE FROM sync_rows WHERE session_id = ?").bind(session_id).execute(&mut *tx).await.map_err(save_error)?;
+    for (kind, id, hash, stamp) in bases {
+        sqlx::query("INSERT INTO sync_rows (kind, id, session_id, hash, stamp_ms, device) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(kind.as_str())
+            .bind(&id)
+            .bind(session_id)
+            .bind(&hash)
+            .bind(stamp.ms)
+            .bind(&stamp.device)
+            .execute(&mut *tx)
+            .await
+            .map_err(save_error)?;
+    }
+    tx.commit().await.map_err(save_error)
+}
+
+/// Sync one session; returns its clock afterwards, if it exists anywhere
+#[allow(clippy::too_many_arguments)]
+async fn sync_session(
+    db: &SqlitePool,
+    storage: &Storage,
+    key: Option<&SecretKey>,
+    session_id: &str,
+    indexed: Option<&VectorClock>,
+    device: &str,
+    columns: &HashMap<&'static str, HashSet<String>>,
+    codes: &SessionCodeConfig,
+    report: &mut SyncReport,
+) -> Result<Option<VectorClock>, String> {
+    let local = local_state(db, session_id, device).await?;
+    let unseen = match (indexed, &local) {
+        (Some(clock), Some(local)) => matches!(compare(clock, &local.synced_clock), ClockOrder::After | ClockOrder::Concurrent),
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    let remote = match unseen {
+        true => storage.get(&doc_key(session_id)).await?.map(|data| decode_doc(&unseal(key, data)?)).transpose()?,
+        false => None,
+    };
+
+    let (merged, order) = match (&local, &remote) {
+        (None, None) => return Ok(None),
+        (Some(local), None) => {
+            if !local.changed && indexed.is_some() {
+                return Ok(Some(local.doc.clock.clone()));
+            }
+            (local.doc.clone(), ClockOrder::After)
+        }
+        (None, Some(remote)) => (remote.clone(), ClockOrder::Before),
+        (Some(local), Some(remote)) => {
+            let order = compare(&local.doc.clock, &remote.clock);
+            (merge_docs(&local.doc, remote, order, device), order)
+        }
+    };
+
+    if remote.as_ref().is_none_or(|remote| remote.clock != merged.clock) {
+        storage.put(&doc_key(session_id), seal(key, encode_doc(&merged)?)?).await?;
+        report.pushed.push(session_id.to_string());
+    }
+    if matches!(order, ClockOrder::Before | ClockOrder::Concurrent) {
+        let trashed = apply(db, session_id, &merged, local.as_ref(), columns, codes).await?;
+        if trashed {
+            report.deleted.push(session_id.to_string());
+        } else if merged.session.is_some() {
+            report.pulled.push(session_id.to_string());
+        }
+    }
+    if local.as_ref().is_some_and(|l| l.known) || merged.session.is_some() || remote.is_some() {
+        save_state(db, session_id, &merged, device).await?;
+    }
+    if let (ClockOrder::Concurrent, Some(local), Some(remote)) = (order, &local, &remote) {
+        let mut conn = db.acquire().await.map_err(|e| e.to_string())?;
+        for conflict in detect_conflicts(session_id, local, remote, &merged) {
+            conflicts::record(&mut conn, &conflict).await.map_err(|e| format!("Failed to record conflict: {}", e))?;
+            report.conflicts.push(conflict);
+        }
+    }
+    Ok(Some(merged.clock))
+}
+
+/// This device's sync ID, created on first use
+pub async fn device_id(db: &SqlitePool) -> Result<String, String> {
+    let existing: Option<String> = sqlx::query_scalar("SELECT value FROM ui_state WHERE key = 'sync_device_id'")
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read sync device ID: {}", e))?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO ui_state (key, value) VALUES ('sync_device_id', ?)")
+        .bind(&id)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to store sync device ID: {}", e))?;
+    Ok(id)
+}
+
+/// Push and pull every session once, sealing what is pushed to `key` if given
+pub async fn sync_once(db: &SqlitePool, storage: &Storage, key: Option<&SecretKey>, device: &str, codes: &SessionCodeConfig) -> SyncReport {
+    let mut report = SyncReport::default();
+    let setup = async {
+        let index = load_index(storage, key).await?;
+        let columns = table_columns(db).await?;
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM sessions UNION SELECT session_id FROM sync_sessions")
+            .fetch_all(db)
+            .await
+            .map_err(|e| format!("Failed to list sessions: {}", e))?;
+        Ok::<_, String>((index, columns, ids))
+    };
+    let (mut index, columns, ids) = match setup.await {
+        Ok(setup) => setup,
+        Err(e) => {
+            report.errors.push(e);
+            return report;
+        }
+    };
+
+    let all: BTreeSet<String> = ids.into_iter().chain(index.keys().cloned()).collect();
+    for session_id in &all {
+        match sync_session(db, storage, key, session_id, index.get(session_id), device, &columns, codes, &mut report).await {
+            Ok(Some(clock)) => {
+                index.insert(session_id.clone(), clock);
+            }
+            Ok(None) => {}
+            Err(e) => report.errors.push(format!("{}: {}", session_id, e)),
+        }
+    }
+
+    if !report.pushed.is_empty() {
+        // Keep entries other devices pushed while this sync ran
+        let result = async {
+            for (session_id, clock) in load_index(storage, key).await? {
+                let merged = merge_clocks(index.get(&session_id).unwrap_or(&VectorClock::new()), &clock);
+                index.insert(session_id, merged);
+            }
+            storage.put(INDEX_KEY, seal(key, serde_json::to_vec(&index).map_err(|e| e.to_string())?)?).await
+        };
+        if let Err(e) = result.await {
+            report.errors.push(format!("Failed to update the sync index: {}", e));
+        }
+    }
+    report
+}
+
+fn open_storage(config: &SyncConfig) -> Result<Storage, String> {
+    let backend = config.backend.as_ref().ok_or("No sync storage is configured")?;
+    let secret = KeychainAuth::new().get_token(KEYCHAIN_ID, &TokenType::ApiKey).ok();
+    Storage::open(backend, secret)
+}
+
+/// The secret half of the configured sync key, which must be in this device's keychain
+fn sync_key(config: &SyncConfig) -> Result<Option<SecretKey>, String> {
+    let Some(public_key) = &config.encryption_key else {
+        return Ok(None);
+    };
+    let missing = || "Sync is encrypted but this device has no sync key, import it first".to_string();
+    let stored = KeychainAuth::new().get_token(KEY_KEYCHAIN_ID, &TokenType::ApiKey).map_err(|_| missing())?;
+    let key = SecretKey::from_base64(&stored)?;
+    if key.public_key().to_base64() != *public_key {
+        return Err(missing());
+    }
+    Ok(Some(key))
+}
+
+async fn run(app_state: &AppState, profile_manager: &ProfileManager) -> Result<SyncReport, String> {
+    let (config, codes) = {
+        let state = app_state.lock().unwrap();
+        (state.sync.clone(), state.session_codes.clone())
+    };
+    let storage = open_storage(&config)?;
+    let key = sync_key(&config)?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let device = device_id(db).await?;
+    Ok(sync_once(db, &storage, key.as_ref(), &device, &codes).await)
+}
+
+/// Sync on `SyncConfig`'s interval while sync is enabled
+pub fn spawn_syncer(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run: Option<std::time::Instant> = None;
+        loop {
+            tokio::time::sleep(SCHEDULE_POLL).await;
+            let (Some(app_state), Some(profile_manager)) = (app_handle.try_state::<AppState>(), app_handle.try_state::<ProfileManager>()) else {
+                continue;
+            };
+            let config = app_state.lock().unwrap().sync.clone();
+            if !config.enabled || last_run.is_some_and(|at| at.elapsed() < Duration::from_secs(config.interval_secs)) {
+                continue;
+            }
+            last_run = Some(std::time::Instant::now());
+            match run(&app_state, &profile_manager).await {
+                Ok(report) => {
+                    for error in &report.errors {
+                        log::warn!("Sync: {}", error);
+                    }
+                    if !(report.pulled.is_empty() && report.deleted.is_empty() && report.conflicts.is_empty()) {
+                        let _ = app_handle.emit("sync_completed", &report);
+                    }
+                }
+                Err(e) => log::warn!("Sync skipped: {}", e),
+            }
+        }
+    });
+}
+
+/// Sync now, whether or not scheduled sync is enabled
+#[tauri::command]
+pub async fn run_sync(
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SyncReport, String> {
+    run(&app_state, &profile_manager).await
+}
+
+#[tauri::command]
+pub async fn get_sync_config(app_state: State<'_, AppState>) -> Result<SyncConfig, String> {
+    Ok(app_state.lock().unwrap().sync.clone())
+}
+
+/// Save the sync settings. `secret` (S3 secret key or WebDAV password) goes to
+/// the keychain; leave it out to keep the stored one.
+#[tauri::command]
+pub async fn set_sync_config(
+    config: SyncConfig,
+    secret: Option<String>,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    if config.interval_secs == 0 {
+        return Err("Sync interval must be greater than zero".to_string());
+    }
+    if config.enabled && config.backend.is_none() {
+        return Err("Choose where to sync before enabling sync".to_string());
+    }
+    if let Some(public_key) = &config.encryption_key {
+        sealed_box::PublicKey::from_base64(public_key)?;
+    }
+    if let Some(secret) = secret {
+        KeychainAuth::new().store_token(KEYCHAIN_ID, TokenType::ApiKey, &secret)?;
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.sync = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncKeyExport {
+    pub public_key: String,
+    pub secret_key: String,
+}
+
+/// Create a sync key and seal everything pushed from now on to it. The secret
+/// key is only returned here, for importing on the other devices.
+#[tauri::command]
+pub async fn create_sync_key(app_state: State<'_, AppState>) -> Result<SyncKeyExport, String> {
+    let key = SecretKey::generate()?;
+    use_sync_key(&app_state, &key).await?;
+    Ok(SyncKeyExport {
+        public_key: key.public_key().to_base64(),
+        secret_key: key.to_base64(),
+    })
+}
+
+/// Use the sync key another device created; returns its public key
+#[tauri::command]
+pub async fn import_sync_key(secret_key: String, app_state: State<'_, AppState>) -> Result<String, String> {
+    let key = SecretKey::from_base64(&secret_key)?;
+    use_sync_key(&app_state, &key).await?;
+    Ok(key.public_key().to_base64())
+}
+
+async fn use_sync_key(app_state: &AppState, key: &SecretKey) -> Result<(), String> {
+    KeychainAuth::new().store_token(KEY_KEYCHAIN_ID, TokenType::ApiKey, &key.to_base64())?;
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.sync.encryption_key = Some(key.public_key().to_base64());
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    async fn content(pool: &SqlitePool, id: &str) -> Option<String> {
+        sqlx::query_scalar("SELECT content FROM messages WHERE id = ?").bind(id).fetch_optional(pool).await.unwrap()
+    }
+
+    #[test]
+    fn test_clocks_and_signing_key() {
+        let clock = |entries: &[(&str, u64)]| entries.iter().map(|(d, c)| (d.to_string(), *c)).collect::<VectorClock>();
+        assert_eq!(compare(&clock(&[("a", 1)]), &clock(&[("a", 1)])), ClockOrder::Equal);
+        assert_eq!(compare(&clock(&[("a", 1)]), &clock(&[("a", 1), ("b", 1)])), ClockOrder::Before);
+        assert_eq!(compare(&clock(&[("a", 2), ("b", 1)]), &clock(&[("b", 1)])), ClockOrder::After);
+        assert_eq!(compare(&clock(&[("a", 2)]), &clock(&[("a", 1), ("b", 1)])), ClockOrder::Concurrent);
+        assert_eq!(merge_clocks(&clock(&[("a", 2)]), &clock(&[("a", 1), ("b", 1)])), clock(&[("a", 2), ("b", 1)]));
+
+        // Example from the AWS Signature Version 4 documentation
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex::encode(key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    #[tokio::test]
+    async fn test_two_devices_converge() {
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::Folder(dir.path().to_path_buf());
+        let codes = SessionCodeConfig::default();
+        let (laptop, desktop) = (test_pool().await, test_pool().await);
+
+        sqlx::query("INSERT INTO sessions (id, title, short_code) VALUES ('s1', 'Refactor', 'laptop-code')").execute(&laptop).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context) VALUES ('t1', 's1', 'production')").execute(&laptop).await.unwrap();
+        sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES ('m1', 't1', 'user', 'hi')").execute(&laptop).await.unwrap();
+
+        let report = sync_once(&laptop, &storage, None, "laptop", &codes).await;
+        assert_eq!((report.pushed.clone(), report.errors.clone()), (vec!["s1".to_string()], vec![]));
+        let report = sync_once(&desktop, &storage, None, "desktop", &codes).await;
+        assert_eq!(report.pulled, vec!["s1".to_string()]);
+        assert_eq!(content(&desktop, "m1").await.as_deref(), Some("hi"));
+        let code: Option<String> = sqlx::query_scalar("SELECT short_code FROM sessions WHERE id = 's1'").fetch_one(&desktop).await.unwrap();
+        assert_ne!(code.as_deref(), Some("laptop-code"));
+        // Nothing changed since, so nothing moves
+        let report = sync_once(&desktop, &storage, None, "desktop", &codes).await;
+        assert!(report.pushed.is_empty() && report.pulled.is_empty(), "{:?}", report);
+
+        // Both edit before syncing again: the later message write wins, other rows merge
+        sqlx::query("UPDATE messages SET content = 'laptop edit' WHERE id = 'm1'").execute(&laptop).await.unwrap();
+        sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES ('m2', 't1', 'assistant', 'from laptop')")
+            .execute(&laptop).await.unwrap();
+        sync_once(&laptop, &storage, None, "laptop", &codes).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        sqlx::query("UPDATE messages SET content = 'desktop edit' WHERE id = 'm1'").execute(&desktop).await.unwrap();
+        let report = sync_once(&desktop, &storage, None, "desktop", &codes).await;
+        assert_eq!(report.conflicts.len(), 1, "{:?}", report);
+        let conflict = &report.conflicts[0];
+        assert_eq!((conflict.item_id.as_deref(), conflict.applied.as_str()), (Some("m1"), "local"));
+        sync_once(&laptop, &storage, None, "laptop", &codes).await;
+        for db in [&laptop, &desktop] {
+            assert_eq!(content(db, "m1").await.as_deref(), Some("desktop edit"));
+            assert_eq!(content(db, "m2").await.as_deref(), Some("from laptop"));
+        }
+
+        // Picking the version that lost makes it a new edit, which then syncs like any other
+        conflicts::resolve(&desktop, &conflict.id, "remote").await.unwrap();
+        assert!(sync_once(&desktop, &storage, None, "desktop", &codes).await.conflicts.is_empty());
+        sync_once(&laptop, &storage, None, "laptop", &codes).await;
+        for db in [&laptop, &desktop] {
+            assert_eq!(content(db, "m1").await.as_deref(), Some("laptop edit"));
+        }
+
+        // A delete reaches the other device as a trashed session
+        let [_, captures] = session_captures("s1");
+        TrashStore::new(laptop.clone()).trash_rows(TrashKind::Session, "s1", &captures, Value::Null).await.unwrap();
+        assert_eq!(sync_once(&laptop, &storage, None, "laptop", &codes).await.pushed, vec!["s1".to_string()]);
+        let report = sync_once(&desktop, &storage, None, "desktop", &codes).await;
+        assert_eq!(report.deleted, vec!["s1".to_string()]);
+        assert_eq!(content(&desktop, "m1").await, None);
+        let trashed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM trash_items").fetch_one(&desktop).await.unwrap();
+        assert_eq!(trashed, 1);
+        let report = sync_once(&laptop, &storage, None, "laptop", &codes).await;
+        assert!(report.pushed.is_empty() && report.errors.is_empty(), "{:?}", report);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_sync() {
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::Folder(dir.path().to_path_buf());
+        let codes = SessionCodeConfig::default();
+        let (laptop, desktop) = (test_pool().await, test_pool().await);
+        let key = SecretKey::generate().unwrap();
+
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Refactor')").execute(&laptop).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context) VALUES ('t1', 's1', 'production')").execute(&laptop).await.unwrap();
+        sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES ('m1', 't1', 'user', 'before the key')").execute(&laptop).await.unwrap();
+        sync_once(&laptop, &storage, None, "laptop", &codes).await;
+
+        // Once a key is set, what was pushed in the clear is refused rather than merged
+        let report = sync_once(&laptop, &storage, Some(&key), "laptop", &codes).await;
+        assert!(report.pushed.is_empty() && report.errors[0].contains("not encrypted"), "{:?}", report);
+
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::Folder(dir.path().to_path_buf());
+        sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES ('m2', 't1', 'user', 'after the key')").execute(&laptop).await.unwrap();
+        let report = sync_once(&laptop, &storage, Some(&key), "laptop", &codes).await;
+        assert_eq!((report.pushed, report.errors), (vec!["s1".to_string()], vec![]));
+        for stored in [INDEX_KEY.to_string(), doc_key("s1")] {
+            let data = std::fs::read(dir.path().join(stored)).unwrap();
+            assert!(!pushed_in_clear(&data) && sealed_box::open(&key, &data).is_ok());
+        }
+
+        let report = sync_once(&desktop, &storage, None, "desktop", &codes).await;
+        assert!(report.pulled.is_empty() && report.errors[0].contains("import the sync key"), "{:?}", report);
+        let other = SecretKey::generate().unwrap();
+        assert!(sync_once(&desktop, &storage, Some(&other), "desktop", &codes).await.errors[0].contains("sync key"));
+        let report = sync_once(&desktop, &storage, Some(&key), "desktop", &codes).await;
+        assert_eq!(report.pulled, vec!["s1".to_string()]);
+        assert_eq!(content(&desktop, "m1").await.as_deref(), Some("before the key"));
+        assert_eq!(content(&desktop, "m2").await.as_deref(), Some("after the key"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Relocating a repository that was moved or renamed
+//!
+//! Session worktrees are recorded with absolute paths (`session_worktrees`, the
+//! legacy `worktrees` table and trashed worktrees' metadata), and git links each
+//! worktree to its repository by absolute path in both directions. Moving the
+//! repository breaks all of them. `relocate_repository` rewrites the stored paths
+//! under the old root, runs `git worktree repair` from the new root, and checks
+//! that every worktree resolves to the new repository again. On startup,
+//! recorded repositories that no longer exist are reported with a
+//! `repository_moved` event so the UI can offer the repair.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::AppState;
+use crate::commit_message::git;
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+use crate::worktree::WorktreeMeta;
+
+/// A recorded repository that is no longer at its path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedRepository {
+    pub repo_root: String,
+    pub worktrees: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryMovedEvent {
+    pub repositories: Vec<MovedRepository>,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+/// Whether a relocated worktree resolves to the new repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorktreeCheck {
+    pub session_id: String,
+    pub worktree_path: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelocationReport {
+    pub old_root: String,
+    pub new_root: String,
+    /// Database rows whose paths were rewritten
+    pub rows_updated: u64,
+    pub worktrees: Vec<WorktreeCheck>,
+}
+
+/// `path` moved from under `old_root` to under `new_root`, or `None` when it was not under it
+pub fn rebase(path: &str, old_root: &Path, new_root: &Path) -> Option<String> {
+    let relative = Path::new(path).strip_prefix(old_root).ok()?;
+    Some(new_root.join(relative).to_string_lossy().to_string())
+}
+
+/// Record where a session's worktree was created
+pub async fn record_worktree(db: &SqlitePool, repo_root: &Path, meta: &WorktreeMeta) -> Result<(), String> {
+    crate::worktree_paths::record(db, &meta.session_id, repo_root, &meta.path, Some(&meta.branch)).await
+}
+
+/// The recorded worktree of a session, if it still exists
+pub async fn recorded_worktree(db: &SqlitePool, session_id: &str) -> Option<PathBuf> {
+    crate::worktree_paths::recorded(db, session_id).await
+}
+
+pub async fn forget_worktree(db: &SqlitePool, worktree_path: &Path) -> Result<(), String> {
+    sqlx::query("DELETE FROM session_worktrees WHERE worktree_path = ?")
+        .bind(worktree_path.to_string_lossy())
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to forget worktree: {}", e))?;
+    Ok(())
+}
+
+/// Recorded repositories whose root no longer exists
+pub async fn detect_moved(db: &SqlitePool) -> Result<Vec<MovedRepository>, String> {
+    let roots = sqlx::query_as::<_, (String, i64)>(
+        "SELECT repo_root, COUNT(*) FROM (
+             SELECT repo_root FROM session_worktrees
+             UNION ALL SELECT repo_root FROM worktrees
+         ) GROUP BY repo_root ORDER BY repo_root",
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to list recorded repositories: {}", e))?;
+    Ok(roots
+        .into_iter()
+        .filter(|(root, _)| !Path::new(root).exists())
+        .map(|(repo_root, worktrees)| MovedRepository { repo_root, worktrees: worktrees as usize })
+        .collect())
+}
+
+/// Rewrite the stored paths under `old_root`; returns the rows changed and the
+/// (session, worktree path) pairs of the repository, rewritten or not
+async fn rewrite_paths(db: &SqlitePool, old_root: &Path, new_root: &Path) -> Result<(u64, Vec<(String, String)>), String> {
+    let old = old_root.to_string_lossy();
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let mut rows_updated = 0;
+    let mut worktrees = Vec::new();
+
+    for table in ["session_worktrees", "worktrees"] {
+        let rows = sqlx::query_as::<_, (String, String, String)>(&format!(
+            "SELECT session_id, repo_root, worktree_path FROM {} WHERE repo_root = ? OR worktree_path LIKE ? || '%'",
+            table
+        ))
+        .bind(old.as_ref())
+        .bind(old.as_ref())
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+
+        for (session_id, repo_root, worktree_path) in rows {
+            let new_repo = rebase(&repo_root, old_root, new_root);
+            let new_path = rebase(&worktree_path, old_root, new_root);
+            if new_repo.is_none() && new_path.is_none() {
+                // Shares the old root's string prefix without being under it
+                continue;
+            }
+            let worktree_path = new_path.unwrap_or(worktree_path);
+            let result = sqlx::query(&format!(
+                "UPDATE {} SET repo_root = ?, worktree_path = ? WHERE session_id = ?",
+                table
+            ))
+            .bind(new_repo.unwrap_or(repo_root))
+            .bind(&worktree_path)
+            .bind(&session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update {}: {}", table, e))?;
+            rows_updated += result.rows_affected();
+            if !worktrees.iter().any(|(_, path)| path == &worktree_path) {
+                worktrees.push((session_id, worktree_path));
+            }
+        }
+    }
+
+    let result = sqlx::query("UPDATE repo_settings SET repo_root = ? WHERE repo_root = ?")
+        .bind(crate::repo_settings::repo_key(new_root))
+        .bind(old.as_ref())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to update repository settings: {}", e))?;
+    rows_updated += result.rows_affected();
+
+    let trashed = sqlx::query_as::<_, (String, String)>("SELECT id, metadata FROM trash_items WHERE kind = 'worktree'")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to read trash: {}", e))?;
+    for (id, metadata) in trashed {
+        let Ok(mut metadata) = serde_json::from_str::<serde_json::Value>(&metadata) else {
+            continue;
+        };
+        let mut changed = false;
+        for key in ["original_path", "repo_path"] {
+            let rebased = metadata.get(key).and_then(|v| v.as_str()).and_then(|p| rebase(p, old_root, new_root));
+            if let Some(rebased) = rebased {
+                metadata[key] = serde_json::Value::String(rebased);
+                changed = true;
+            }
+        }
+        if changed {
+            let result = sqlx::query("UPDATE trash_items SET metadata = ? WHERE id = ?")
+                .bind(metadata.to_string())
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to update trash item: {}", e))?;
+            rows_updated += result.rows_affected();
+        }
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok((rows_updated, worktrees))
+}
+
+/// Whether `worktree` is a checkout of the repository at `repo_root`
+fn check_worktree(repo_root: &Path, worktree: &Path) -> Result<(), String> {
+    if !worktree.exists() {
+        return Err(format!("Worktree not found: {}", worktree.display()));
+    }
+    let common_dir = git(worktree, &["rev-parse", "--git-common-dir"])?;
+    let common_dir = worktree.join(common_dir.trim()).canonicalize().map_err(|e| e.to_string())?;
+    let expected = repo_root.join(".git").canonicalize().map_err(|e| e.to_string())?;
+    if common_dir != expected {
+        return Err(format!("Worktree belongs to {}", common_dir.display()));
+    }
+    Ok(())
+}
+
+/// Point stored paths and git's worktree links at the repository's new location
+pub async fn relocate(db: &SqlitePool, old_root: &Path, new_root: &Path) -> Result<RelocationReport, String> {
+    if old_root == new_root {
+        return Err("The new repository root is the same as the old one".to_string());
+    }
+    if !new_root.join(".git").is_dir() {
+        return Err(format!("No git repository at {}", new_root.display()));
+    }
+
+    let (rows_updated, worktrees) = rewrite_paths(db, old_root, new_root).await?;
+    let existing: Vec<&str> = worktrees
+        .iter()
+        .map(|(_, path)| path.as_str())
+        .filter(|path| Path::new(path).exists())
+        .collect();
+    if !existing.is_empty() {
+        let args: Vec<&str> = ["worktree", "repair"].into_iter().chain(existing).collect();
+        if let Err(e) = git(new_root, &args) {
+            log::warn!("relocate_repository: {}", e);
+        }
+    }
+
+    let worktrees = worktrees
+        .into_iter()
+        .map(|(session_id, worktree_path)| {
+            let error = check_worktree(new_root, Path::new(&worktree_path)).err();
+            WorktreeCheck { session_id, worktree_path, ok: error.is_none(), error }
+        })
+        .collect();
+
+    Ok(RelocationReport {
+        old_root: old_root.to_string_lossy().to_string(),
+        new_root: new_root.to_string_lossy().to_string(),
+        rows_updated,
+        worktrees,
+    })
+}
+
+/// Report moved repositories once the database is up
+pub fn spawn_startup_check(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+            return;
+        };
+        let db = profile_manager.db_pool.read().await;
+        let Some(db) = db.as_ref() else {
+            return;
+        };
+        match detect_moved(db).await {
+            Ok(repositories) if !repositories.is_empty() => {
+                let message = tr!("notify-repository-moved", count = repositories.len());
+                let _ = app_handle.emit("repository_moved", RepositoryMovedEvent { repositories, message });
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Moved repository check failed: {}", e),
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn detect_moved_repositories(profile_manager: State<'_, ProfileManager>) -> Result<Vec<MovedRepository>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    detect_moved(db).await
+}
+
+/// Move everything recorded under `old_root` to `new_root`, including the GC repository list
+#[tauri::command]
+pub async fn relocate_repository(
+    old_root: String,
+    new_root: String,
+    profile_manager: State<'_, ProfileManager>,
+    app_state: State<'_, AppState>,
+) -> Result<RelocationReport, String> {
+    let old_root = PathBuf::from(old_root);
+    let new_root = PathBuf::from(new_root);
+    let report = {
+        let db = profile_manager.db_pool.read().await;
+        let db = db.as_ref().ok_or("Database not available")?;
+        relocate(db, &old_root, &new_root).await?
+    };
+
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        let mut changed = false;
+        for repo in state.session_gc.repositories.iter_mut() {
+            if let Some(rebased) = rebase(repo, &old_root, &new_root) {
+                *repo = rebased;
+                changed = true;
+            }
+        }
+        changed.then(|| state.clone())
+    };
+    if let Some(state) = to_save {
+        state.save().await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rebase() {
+        let (old, new) = (Path::new("/work/repo"), Path::new("/src/renamed"));
+        assert_eq!(rebase("/work/repo/.amp-worktrees/abc", old, new).as_deref(), Some("/src/renamed/.amp-worktrees/abc"));
+        assert_eq!(rebase("/work/repo", old, new).as_deref(), Some("/src/renamed"));
+        assert_eq!(rebase("/work/repo-other/x", old, new), None);
+    }
+
+    #[tokio::test]
+    async fn test_relocate_moved_repository() {
+        let dir = TempDir::new().unwrap();
+        let old_root = dir.path().join("repo");
+        std::fs::create_dir_all(&old_root).unwrap();
+        git(&old_root, &["init", "-q"]).unwrap();
+        std::fs::write(old_root.join("README.md"), "relocate\n").unwrap();
+        git(&old_root, &["add", "README.md"]).unwrap();
+        git(&old_root, &["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "Initial"]).unwrap();
+
+        let pool = test_pool().await;
+        let meta = crate::worktree::create(&old_root, "relocate-0001").unwrap();
+        record_worktree(&pool, &old_root, &meta).await.unwrap();
+        sqlx::query("INSERT INTO trash_items (id, kind, label, metadata, expires_at) VALUES ('t1', 'worktree', 'old', ?, '2999-01-01T00:00:00Z')")
+            .bind(serde_json::json!({ "original_path": old_root.join(".amp-worktrees/gone").to_string_lossy(), "repo_path": old_root.to_string_lossy() }).to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO repo_settings (repo_root, base_branch) VALUES (?, 'main')")
+            .bind(old_root.to_string_lossy())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(detect_moved(&pool).await.unwrap().is_empty());
+
+        let new_root = dir.path().join("renamed");
+        std::fs::rename(&old_root, &new_root).unwrap();
+        let moved = detect_moved(&pool).await.unwrap();
+        assert_eq!(moved, vec![MovedRepository { repo_root: old_root.to_string_lossy().to_string(), worktrees: 1 }]);
+
+        assert!(relocate(&pool, &old_root, &dir.path().join("missing")).await.is_err());
+        let report = relocate(&pool, &old_root, &new_root).await.unwrap();
+        assert_eq!(report.rows_updated, 3);
+        assert_eq!(report.worktrees.len(), 1);
+        assert!(report.worktrees[0].ok, "{:?}", report.worktrees[0].error);
+        assert!(report.worktrees[0].worktree_path.starts_with(new_root.to_string_lossy().as_ref()));
+        assert!(detect_moved(&pool).await.unwrap().is_empty());
+        // The repaired worktree works again
+        git(Path::new(&report.worktrees[0].worktree_path), &["status", "--short"]).unwrap();
+
+        let metadata: String = sqlx::query_scalar("SELECT metadata FROM trash_items WHERE id = 't1'").fetch_one(&pool).await.unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(metadata["repo_path"], new_root.to_string_lossy().as_ref());
+        let settings = crate::repo_settings::get_settings(&pool, &new_root).await.unwrap();
+        assert_eq!(settings.base_branch.as_deref(), Some("main"));
+    }
+}
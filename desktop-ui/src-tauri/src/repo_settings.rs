@@ -0,0 +1,230 @@
+//! Per-repository settings
+//!
+//! Settings are keyed by the repository's root directory. Session worktrees
+//! created in the repository start from `base_branch`, name their branch after
+//! `branch_template` and run `bootstrap_hook` once checked out. Merge-back
+//! targets `base_branch` unless given a branch, runs `verify_hook` in the
+//! worktree first, and refuses branches that change a protected path.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::State;
+
+use crate::context_packs::{compile_glob, glob_matches};
+use crate::profile_auth::ProfileManager;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoSettings {
+    pub repo_root: String,
+    /// Branch new session worktrees start from and merge-back targets
+    pub base_branch: Option<String>,
+    /// Glob patterns of repository-relative paths the agent must not modify; a
+    /// pattern matching a directory covers everything under it
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    /// Shell command run in each new session worktree
+    pub bootstrap_hook: Option<String>,
+    /// Shell command that has to succeed in the worktree before merge-back
+    pub verify_hook: Option<String>,
+    /// Session branch name; `{code}` is the session's short code (its ID without
+    /// one) and `{session_id}` its ID
+    pub branch_template: Option<String>,
+}
+
+/// Key of the repository at `repo_root`: its canonical path when it exists
+pub fn repo_key(repo_root: &Path) -> String {
+    repo_root.canonicalize().unwrap_or_else(|_| repo_root.to_path_buf()).to_string_lossy().to_string()
+}
+
+/// Root of the main checkout of the repository `worktree` belongs to
+pub fn main_repo_root(worktree: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .current_dir(worktree)
+        .args(["rev-parse", "--path-format=absolute", "--git-common-dir"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let common_dir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    common_dir.parent().map(Path::to_path_buf)
+}
+
+/// Branch name for a session from `template`
+pub fn render_branch_name(template: &str, session_id: &str) -> String {
+    let code = crate::session_codes::code_for(session_id).unwrap_or_else(|| session_id.to_string());
+    template.replace("{session_id}", session_id).replace("{code}", &code)
+}
+
+/// Trim the settings, dropping empty values, and reject invalid ones
+fn normalized(mut settings: RepoSettings) -> Result<RepoSettings, String> {
+    let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    settings.base_branch = non_empty(settings.base_branch);
+    settings.bootstrap_hook = non_empty(settings.bootstrap_hook);
+    settings.verify_hook = non_empty(settings.verify_hook);
+    settings.branch_template = non_empty(settings.branch_template);
+    settings.protected_paths = settings.protected_paths.iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+
+    for pattern in &settings.protected_paths {
+        compile_glob(pattern)?;
+    }
+    if let Some(template) = &settings.branch_template {
+        if !template.contains("{code}") && !template.contains("{session_id}") {
+            return Err("Branch template must contain {code} or {session_id}".to_string());
+        }
+        if template.contains("..") || template.chars().any(|c| c.is_whitespace() || "~^:?*[\\".contains(c)) {
+            return Err(format!("Branch template {} would not make a valid branch name", template));
+        }
+    }
+    Ok(settings)
+}
+
+type SettingsRow = (Option<String>, String, Option<String>, Option<String>, Option<String>);
+
+/// Settings of the repository at `repo_root`; all unset when none were saved
+pub async fn get_settings(db: &SqlitePool, repo_root: &Path) -> Result<RepoSettings, String> {
+    let repo_root = repo_key(repo_root);
+    let row = sqlx::query_as::<_, SettingsRow>(
+        "SELECT base_branch, protected_paths, bootstrap_hook, verify_hook, branch_template FROM repo_settings WHERE repo_root = ?",
+    )
+    .bind(&repo_root)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to read repository settings: {}", e))?;
+    let Some((base_branch, protected_paths, bootstrap_hook, verify_hook, branch_template)) = row else {
+        return Ok(RepoSettings { repo_root, ..Default::default() });
+    };
+    Ok(RepoSettings {
+        repo_root,
+        base_branch,
+        protected_paths: serde_json::from_str(&protected_paths).unwrap_or_default(),
+        bootstrap_hook,
+        verify_hook,
+        branch_template,
+    })
+}
+
+pub async fn save_settings(db: &SqlitePool, settings: RepoSettings) -> Result<RepoSettings, String> {
+    let mut settings = normalized(settings)?;
+    settings.repo_root = repo_key(Path::new(&settings.repo_root));
+    sqlx::query(
+        "INSERT INTO repo_settings (repo_root, base_branch, protected_paths, bootstrap_hook, verify_hook, branch_template)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON CONFLICT(repo_root) DO UPDATE SET base_branch = excluded.base_branch, protected_paths = excluded.protected_paths,
+             bootstrap_hook = excluded.bootstrap_hook, verify_hook = excluded.verify_hook,
+             branch_template = excluded.branch_template, updated_at = (datetime('now', 'utc') || 'Z')",
+    )
+    .bind(&settings.repo_root)
+    .bind(&settings.base_branch)
+    .bind(serde_json::to_string(&settings.protected_paths).map_err(|e| e.to_string())?)
+    .bind(&settings.bootstrap_hook)
+    .bind(&settings.verify_hook)
+    .bind(&settings.branch_template)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save repository settings: {}", e))?;
+    Ok(settings)
+}
+
+/// Settings of the repository a session worktree belongs to
+pub async fn settings_for_worktree(db: &SqlitePool, worktree: &Path) -> Result<RepoSettings, String> {
+    match main_repo_root(worktree) {
+        Some(repo_root) => get_settings(db, &repo_root).await,
+        None => Ok(RepoSettings::default()),
+    }
+}
+
+/// Those of `paths` that a protected pattern, or the pattern of a directory above them, matches
+pub fn protected_changes<'a>(patterns: &[String], paths: impl IntoIterator<Item = &'a str>) -> Result<Vec<String>, String> {
+    let globs = patterns.iter().map(|p| compile_glob(p)).collect::<Result<Vec<_>, _>>()?;
+    Ok(paths
+        .into_iter()
+        .filter(|path| {
+            let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+            (1..=parts.len()).any(|depth| globs.iter().any(|glob| glob_matches(glob, &parts[..depth])))
+        })
+        .map(str::to_string)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_repo_settings(repo_root: String, profile_manager: State<'_, ProfileManager>) -> Result<RepoSettings, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    get_settings(db, Path::new(&repo_root)).await
+}
+
+#[tauri::command]
+pub async fn set_repo_settings(settings: RepoSettings, profile_manager: State<'_, ProfileManager>) -> Result<RepoSettings, String> {
+    if !Path::new(&settings.repo_root).is_dir() {
+        return Err(format!("Repository {} not found", settings.repo_root));
+    }
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    save_settings(db, settings).await
+}
+
+/// Forget a repository's settings
+#[tauri::command]
+pub async fn delete_repo_settings(repo_root: String, profile_manager: State<'_, ProfileManager>) -> Result<(), String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    sqlx::query("DELETE FROM repo_settings WHERE repo_root = ?")
+        .bind(repo_key(Path::new(&repo_root)))
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to delete repository settings: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_protected_changes_cover_directories_and_globs() {
+        let patterns = vec!["migrations".to_string(), "**/*.lock".to_string(), ".github/workflows/*.yml".to_string()];
+        let changed = protected_changes(
+            &patterns,
+            ["migrations/001_init.sql", "src/main.rs", "web/yarn.lock", ".github/workflows/ci.yml", ".github/CODEOWNERS", "migrations_old.sql"],
+        )
+        .unwrap();
+        assert_eq!(changed, vec!["migrations/001_init.sql", "web/yarn.lock", ".github/workflows/ci.yml"]);
+        assert!(protected_changes(&[], ["src/main.rs"]).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_settings_round_trip_and_validation() {
+        let pool = test_pool().await;
+        let repo = TempDir::new().unwrap();
+
+        let unset = get_settings(&pool, repo.path()).await.unwrap();
+        assert_eq!(unset, RepoSettings { repo_root: repo_key(repo.path()), ..Default::default() });
+
+        let saved = save_settings(
+            &pool,
+            RepoSettings {
+                repo_root: repo.path().to_string_lossy().to_string(),
+                base_branch: Some(" develop ".to_string()),
+                protected_paths: vec!["migrations".to_string(), " ".to_string()],
+                bootstrap_hook: Some("npm ci".to_string()),
+                verify_hook: Some(String::new()),
+                branch_template: Some("agents/{code}".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(saved.base_branch.as_deref(), Some("develop"));
+        assert_eq!(saved.protected_paths, vec!["migrations"]);
+        assert_eq!(saved.verify_hook, None);
+        assert_eq!(get_settings(&pool, repo.path()).await.unwrap(), saved);
+        assert_eq!(render_branch_name("agents/{code}", "0123456789abcdef"), "agents/0123456789abcdef");
+
+        let invalid = |template: &str| RepoSettings { repo_root: saved.repo_root.clone(), branch_template: Some(template.to_string()), ..Default::default() };
+        assert!(save_settings(&pool, invalid("agents/fixed")).await.is_err());
+        assert!(save_settings(&pool, invalid("agents/{code} x")).await.is_err());
+        assert_eq!(get_settings(&pool, repo.path()).await.unwrap(), saved);
+    }
+}
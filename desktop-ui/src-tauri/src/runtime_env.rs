@@ -132,7 +132,7 @@ impl RuntimeEnvironment {
                 .iter()
                 .map(|p| p.to_string_lossy().to_string())
                 .collect();
-            env.insert("AMP_TOOLBOX_PATHS".to_string(), toolbox_paths.join(":"));
+            env.insert("AMP_TOOLBOX_PATHS".to_string(), crate::platform::join_env_paths(&toolbox_paths));
             env.insert("AMP_ENABLE_TOOLBOXES".to_string(), "1".to_string());
         }
 
@@ -161,10 +161,8 @@ impl RuntimeEnvironment {
         };
 
         let toolbox_config = if std::env::var("AMP_ENABLE_TOOLBOXES").is_ok() {
-            let toolbox_paths = std::env::var("AMP_TOOLBOX_PATHS")
-                .unwrap_or_default()
-                .split(':')
-                .filter(|s| !s.is_empty())
+            let toolbox_paths = split_paths(&std::env::var("AMP_TOOLBOX_PATHS").unwrap_or_default())
+                .into_iter()
                 .map(PathBuf::from)
                 .collect();
 
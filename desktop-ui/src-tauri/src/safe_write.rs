@@ -0,0 +1,259 @@
+//! Atomic file writes with optimistic conflict detection
+//!
+//! Content is written to a temp file in the target's directory and renamed into
+//! place, so readers never see a half-written file. Callers that pass the hash
+//! from `read_file_with_hash` get a `conflict` error instead of overwriting a file
+//! that changed since they read it. The previous version is copied into the
+//! session's artifacts directory before it is replaced.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Backups kept per file name in a session's backup directory
+pub const MAX_BACKUPS_PER_FILE: usize = 20;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum SafeWriteError {
+    #[error("{path} changed since it was read")]
+    Conflict { path: String, expected_hash: String, actual_hash: Option<String> },
+
+    #[error("Failed to write {path}: {reason}")]
+    Io { path: String, reason: String },
+}
+
+impl SafeWriteError {
+    fn io(path: &Path, e: impl std::fmt::Display) -> Self {
+        Self::Io { path: path.to_string_lossy().to_string(), reason: e.to_string() }
+    }
+
+    /// JSON form returned to the frontend: the error fields plus a readable `message`
+    pub fn to_command_error(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("message".to_string(), serde_json::Value::String(self.to_string()));
+        }
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileWithHash {
+    pub content: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WriteOutcome {
+    /// Hash of the new content, usable as the next `expected_hash`
+    pub hash: String,
+    pub bytes: usize,
+    pub backup_path: Option<String>,
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+/// Copy the current file into `backup_dir` and prune old copies of the same file
+fn backup(path: &Path, backup_dir: &Path) -> Result<PathBuf, SafeWriteError> {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "file".to_string());
+    std::fs::create_dir_all(backup_dir).map_err(|e| SafeWriteError::io(backup_dir, e))?;
+
+    // Timestamp prefix keeps lexical order equal to age
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.6fZ");
+    let target = backup_dir.join(format!("{}-{}", stamp, name));
+    std::fs::copy(path, &target).map_err(|e| SafeWriteError::io(&target, e))?;
+
+    let suffix = format!("-{}", name);
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(backup_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().ends_with(&suffix)))
+                .collect()
+        })
+        .unwrap_or_default();
+    if existing.len() > MAX_BACKUPS_PER_FILE {
+        existing.sort();
+        for old in &existing[..existing.len() - MAX_BACKUPS_PER_FILE] {
+            let _ = std::fs::remove_file(old);
+        }
+    }
+    Ok(target)
+}
+
+/// Write `content` to `path` atomically. Fails with `Conflict` when `expected_hash`
+/// is given and does not match the file on disk (a missing file has no hash).
+pub fn write_atomic(
+    path: &Path,
+    content: &[u8],
+    expected_hash: Option<&str>,
+    backup_dir: Option<&Path>,
+) -> Result<WriteOutcome, SafeWriteError> {
+    let current = match std::fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(SafeWriteError::io(path, e)),
+    };
+
+    if let Some(expected) = expected_hash {
+        let actual = current.as_deref().map(content_hash);
+        if actual.as_deref() != Some(expected) {
+            return Err(SafeWriteError::Conflict {
+                path: path.to_string_lossy().to_string(),
+                expected_hash: expected.to_string(),
+                actual_hash: actual,
+            });
+        }
+    }
+
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent).map_err(|e| SafeWriteError::io(parent, e))?;
+
+    let backup_path = match (&current, backup_dir) {
+        (Some(_), Some(dir)) => Some(backup(path, dir)?),
+        _ => None,
+    };
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let temp = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        // Keep the original file's permissions (e.g. executable scripts)
+        if let Ok(meta) = std::fs::metadata(path) {
+            std::fs::set_permissions(&temp, meta.permissions())?;
+        }
+        std::fs::rename(&temp, path)
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(SafeWriteError::io(path, e));
+    }
+
+    Ok(WriteOutcome {
+        hash: content_hash(content),
+        bytes: content.len(),
+        backup_path: backup_path.map(|p| p.to_string_lossy().to_string()),
+    })
+}
+
+/// Backup directory for a session's overwritten files
+pub fn session_backup_dir(app_handle: &AppHandle, session_id: &str) -> Result<PathBuf, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("artifacts").join(session_id).join("backups"))
+}
+
+#[tauri::command]
+pub async fn read_file_with_hash(path: String) -> Result<FileWithHash, String> {
+    let bytes = tokio::fs::read(&path).await.map_err(|e| format!("Failed to read file: {}", e))?;
+    let hash = content_hash(&bytes);
+    let content = String::from_utf8(bytes).map_err(|e| format!("File is not valid UTF-8: {}", e))?;
+    Ok(FileWithHash { content, hash })
+}
+
+/// Atomic write. Pass `expected_hash` to fail on concurrent changes and
+/// `session_id` to back up the previous version under that session's artifacts.
+#[tauri::command]
+pub async fn write_file_safe(
+    path: String,
+    content: String,
+    expected_hash: Option<String>,
+    session_id: Option<String>,
+    app_handle: AppHandle,
+) -> Result<WriteOutcome, String> {
+    let backup_dir = session_id
+        .as_deref()
+        .map(|sid| session_backup_dir(&app_handle, sid))
+        .transpose()?;
+
+    tokio::task::spawn_blocking(move || {
+        write_atomic(Path::new(&path), content.as_bytes(), expected_hash.as_deref(), backup_dir.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Failed to write file: {}", e))?
+    .map_err(|e| e.to_command_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_creates_and_replaces() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested/notes.md");
+
+        let first = write_atomic(&path, b"one", None, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one");
+        assert_eq!(first.hash, content_hash(b"one"));
+        assert!(first.backup_path.is_none());
+
+        let second = write_atomic(&path, b"two", Some(&first.hash), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two");
+        assert_eq!(second.bytes, 3);
+        // No temp files left behind
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_conflict_detection() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "fn main() {}").unwrap();
+        let stale = content_hash(b"fn main() { old }");
+
+        let err = write_atomic(&path, b"new", Some(&stale), None).unwrap_err();
+        assert!(matches!(&err, SafeWriteError::Conflict { actual_hash: Some(_), .. }));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fn main() {}");
+
+        let json: serde_json::Value = serde_json::from_str(&err.to_command_error()).unwrap();
+        assert_eq!(json["code"], "conflict");
+
+        // A file deleted since it was read is also a conflict
+        let missing = dir.path().join("gone.rs");
+        assert!(matches!(
+            write_atomic(&missing, b"x", Some(&stale), None),
+            Err(SafeWriteError::Conflict { actual_hash: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_backups_are_kept_and_pruned() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.json");
+        let backups = dir.path().join("backups");
+        std::fs::write(&path, "v0").unwrap();
+
+        let outcome = write_atomic(&path, b"v1", None, Some(&backups)).unwrap();
+        let backup_path = PathBuf::from(outcome.backup_path.unwrap());
+        assert_eq!(std::fs::read_to_string(backup_path).unwrap(), "v0");
+
+        for i in 2..(MAX_BACKUPS_PER_FILE + 5) {
+            write_atomic(&path, format!("v{}", i).as_bytes(), None, Some(&backups)).unwrap();
+        }
+        assert_eq!(std::fs::read_dir(&backups).unwrap().count(), MAX_BACKUPS_PER_FILE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.sh");
+        std::fs::write(&path, "#!/bin/sh").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        write_atomic(&path, b"#!/bin/sh\necho hi", None, None).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o755);
+    }
+}
@@ -0,0 +1,141 @@
+//! Database schema migrations
+//!
+//! `run_migrations` brings a database up to `SCHEMA_VERSION` at startup. Kept
+//! apart from the profile code so modules built without the app, and their
+//! tests, can set up the same schema.
+
+use sqlx::sqlite::SqlitePool;
+#[cfg(test)]
+use sqlx::{pool::PoolOptions, sqlite::SqliteConnectOptions, ConnectOptions, Sqlite};
+
+/// Migrations in the order `initialize_db` runs them
+pub const MIGRATIONS: &[(&str, &str)] = &[
+    ("001_initial.sql", include_str!("../migrations/001_initial.sql")),
+    ("002_chat_sessions.sql", include_str!("../migrations/002_chat_sessions.sql")),
+    ("003_chat_sessions_agent_mode.sql", include_str!("../migrations/003_chat_sessions_agent_mode.sql")),
+    ("004_add_toolbox_profiles.sql", include_str!("../migrations/004_add_toolbox_profiles.sql")),
+    ("005_add_worktrees_support.sql", include_str!("../migrations/005_add_worktrees_support.sql")),
+    ("006_batch_processing.sql", include_str!("../migrations/006_batch_processing.sql")),
+    ("007_add_threads_architecture.sql", include_str!("../migrations/007_add_threads_architecture.sql")),
+    ("008_script_hooks.sql", include_str!("../migrations/008_script_hooks.sql")),
+    ("009_session_outcomes.sql", include_str!("../migrations/009_session_outcomes.sql")),
+    ("010_thread_feedback.sql", include_str!("../migrations/010_thread_feedback.sql")),
+    ("011_trash.sql", include_str!("../migrations/011_trash.sql")),
+    ("012_session_amp_profiles.sql", include_str!("../migrations/012_session_amp_profiles.sql")),
+    ("013_message_content_offload.sql", include_str!("../migrations/013_message_content_offload.sql")),
+    ("014_thread_archives.sql", include_str!("../migrations/014_thread_archives.sql")),
+    ("015_profile_defaults.sql", include_str!("../migrations/015_profile_defaults.sql")),
+    ("016_session_codes.sql", include_str!("../migrations/016_session_codes.sql")),
+    ("017_message_metrics.sql", include_str!("../migrations/017_message_metrics.sql")),
+    ("018_remote_sync.sql", include_str!("../migrations/018_remote_sync.sql")),
+    ("019_conflicts.sql", include_str!("../migrations/019_conflicts.sql")),
+    ("020_quarantined_events.sql", include_str!("../migrations/020_quarantined_events.sql")),
+    ("021_session_priorities.sql", include_str!("../migrations/021_session_priorities.sql")),
+    ("022_session_worktrees.sql", include_str!("../migrations/022_session_worktrees.sql")),
+    ("023_session_processes.sql", include_str!("../migrations/023_session_processes.sql")),
+    ("024_profile_secrets.sql", include_str!("../migrations/024_profile_secrets.sql")),
+    ("025_toolbox_snapshots.sql", include_str!("../migrations/025_toolbox_snapshots.sql")),
+    ("026_profile_quotas.sql", include_str!("../migrations/026_profile_quotas.sql")),
+    ("027_context_packs.sql", include_str!("../migrations/027_context_packs.sql")),
+    ("028_history_replay.sql", include_str!("../migrations/028_history_replay.sql")),
+    ("029_staging_sync.sql", include_str!("../migrations/029_staging_sync.sql")),
+    ("030_thread_spawn_envs.sql", include_str!("../migrations/030_thread_spawn_envs.sql")),
+    ("031_message_interrupts.sql", include_str!("../migrations/031_message_interrupts.sql")),
+    ("032_session_stacks.sql", include_str!("../migrations/032_session_stacks.sql")),
+    ("033_env_isolation.sql", include_str!("../migrations/033_env_isolation.sql")),
+    ("034_repo_settings.sql", include_str!("../migrations/034_repo_settings.sql")),
+    ("035_backfill_session_worktrees.sql", include_str!("../migrations/035_backfill_session_worktrees.sql")),
+    ("036_cache_versions.sql", include_str!("../migrations/036_cache_versions.sql")),
+    ("037_benchmark_presets.sql", include_str!("../migrations/037_benchmark_presets.sql")),
+    ("038_spawn_cli_versions.sql", include_str!("../migrations/038_spawn_cli_versions.sql")),
+];
+
+/// Stored in `PRAGMA user_version` once every migration has run
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Data migrations, which unlike schema migrations change rows again each time
+/// they run. They are skipped once `PRAGMA user_version` records them as run.
+const ONE_SHOT_MIGRATIONS: &[&str] = &["035_backfill_session_worktrees.sql"];
+
+/// Run every migration on `pool` and record `SCHEMA_VERSION`
+pub async fn run_migrations(pool: &SqlitePool) -> Result<(), String> {
+    let applied: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(pool).await.unwrap_or(0);
+    for (version, (name, migration_sql)) in (1..).zip(MIGRATIONS.iter().copied()) {
+        if version <= applied && ONE_SHOT_MIGRATIONS.contains(&name) {
+            log::debug!("initialize_db: Migration {} already applied, skipping", name);
+            continue;
+        }
+        log::debug!("initialize_db: Running migration {}, SQL length: {} characters", name, migration_sql.len());
+
+        // Execute migration with better error handling
+        match sqlx::query(migration_sql).execute(pool).await {
+            Ok(result) => {
+                log::debug!("initialize_db: Migration {} executed successfully, rows affected: {}", name, result.rows_affected());
+            },
+            Err(e) => {
+                // Check if error is due to tables already existing (not a critical error)
+                let error_str = e.to_string();
+                if error_str.contains("already exists") || error_str.contains("duplicate column name") {
+                    log::debug!("initialize_db: Migration {} - tables already exist, skipping", name);
+                } else {
+                    log::error!("initialize_db: Failed to run migration {}: {}", name, e);
+                    return Err(format!("Failed to run migration {}: {}", name, e));
+                }
+            }
+        }
+    }
+
+    if let Err(e) = sqlx::query(&format!("PRAGMA user_version = {}", SCHEMA_VERSION)).execute(pool).await {
+        log::warn!("initialize_db: Failed to record schema version: {}", e);
+    }
+    Ok(())
+}
+
+/// In-memory database with every migration applied, for tests
+#[cfg(test)]
+pub async fn test_pool() -> SqlitePool {
+    use std::str::FromStr;
+    test_pool_with(PoolOptions::new(), SqliteConnectOptions::from_str(":memory:").unwrap()).await
+}
+
+/// Like `test_pool`, for tests that need a file-backed database or a single
+/// connection. Foreign keys are off, so tests only set up the rows they use.
+#[cfg(test)]
+pub async fn test_pool_with(pool_options: PoolOptions<Sqlite>, options: SqliteConnectOptions) -> SqlitePool {
+    let options = options.create_if_missing(true).foreign_keys(false).disable_statement_logging();
+    let pool = pool_options.connect_with(options).await.unwrap();
+    // 004 adds a column to the legacy `runs` table, which no migration creates
+    sqlx::query("CREATE TABLE runs (id TEXT PRIMARY KEY, toolbox_path TEXT)").execute(&pool).await.unwrap();
+    run_migrations(&pool).await.unwrap();
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backfill_runs_once() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO worktrees (id, session_id, repo_root, base_branch, branch_name, worktree_path, created_at)
+             VALUES ('w1', 's1', '/repo', 'main', 'orchestra/s1', '/repo/.worktrees/s1', '2024-01-01T00:00:00Z')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Rows removed after the first backfill stay removed on later startups
+        run_migrations(&pool).await.unwrap();
+        let count = || sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM session_worktrees").fetch_one(&pool);
+        assert_eq!(count().await.unwrap(), 0);
+        sqlx::query("PRAGMA user_version = 34").execute(&pool).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        assert_eq!(count().await.unwrap(), 1);
+        sqlx::query("DELETE FROM session_worktrees").execute(&pool).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        assert_eq!(count().await.unwrap(), 0);
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+}
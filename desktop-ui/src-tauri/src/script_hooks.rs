@@ -0,0 +1,694 @@
+//! Programmable session hooks backed by an embedded Rhai engine
+//!
+//! Users attach small scripts to a profile and pick the event that triggers them
+//! (`session_completed`, `tool_use`, `budget_exceeded`). Scripts run in a locked-down
+//! engine with operation, depth and size limits and no filesystem or process access.
+//! The only way a script can affect the app is through the host API below, which
+//! records `HookAction`s that are applied after the script returns:
+//! - `send_message(text)` - send a user message to the session's Amp process
+//! - `tag_session(tag)` - attach a tag to the session
+//! - `run_hook(name)` - run another hook of the same profile by name
+//! - `post_webhook(url, body)` - POST `body` as JSON to an http(s) URL
+//!
+//! Webhook bodies are sealed (see `sealed_box`) when a public key is set for
+//! the URL, so relays and whatever stores the payloads only see ciphertext.
+//! The receiver gets `{"sealed": <base64 box>, "scheme": <sealed_box::SCHEME>}`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use tauri::{AppHandle, Emitter, Manager, State};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::profile_auth::ProfileManager;
+use crate::sealed_box::{self, PublicKey};
+use crate::session_commands::AmpSessionMap;
+use crate::trash::{Capture, TrashKind, TrashStore};
+
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_ACTIONS_PER_RUN: usize = 32;
+/// Limits how deep `run_hook` chains may go before they are dropped
+const MAX_HOOK_CHAIN_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    SessionCompleted,
+    ToolUse,
+    BudgetExceeded,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::SessionCompleted => "session_completed",
+            HookEvent::ToolUse => "tool_use",
+            HookEvent::BudgetExceeded => "budget_exceeded",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "session_completed" => Some(HookEvent::SessionCompleted),
+            "tool_use" => Some(HookEvent::ToolUse),
+            "budget_exceeded" => Some(HookEvent::BudgetExceeded),
+            _ => None,
+        }
+    }
+}
+
+/// An action requested by a script through the sandboxed API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookAction {
+    SendMessage { text: String },
+    TagSession { tag: String },
+    RunHook { name: String },
+    PostWebhook { url: String, body: serde_json::Value },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScriptHook {
+    pub id: String,
+    pub profile_id: String,
+    pub name: String,
+    pub event: String,
+    pub source: String,
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveScriptHookRequest {
+    /// Existing hook to update; a new hook is created when omitted
+    pub id: Option<String>,
+    /// Defaults to the active profile
+    pub profile_id: Option<String>,
+    pub name: String,
+    pub event: HookEvent,
+    pub source: String,
+    pub enabled: Option<bool>,
+}
+
+fn build_engine(actions: Arc<Mutex<Vec<HookAction>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(16);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(1_000);
+    engine.set_max_map_size(1_000);
+    engine.disable_symbol("eval");
+    engine.on_print(|text| log::info!("[script_hook] {}", text));
+    engine.on_debug(|text, _, _| log::debug!("[script_hook] {}", text));
+
+    let push = move |action: HookAction| -> Result<(), Box<EvalAltResult>> {
+        let mut actions = actions.lock().unwrap();
+        if actions.len() >= MAX_ACTIONS_PER_RUN {
+            return Err(format!("Hook exceeded the limit of {} actions", MAX_ACTIONS_PER_RUN).into());
+        }
+        actions.push(action);
+        Ok(())
+    };
+
+    let p = push.clone();
+    engine.register_fn("send_message", move |text: &str| {
+        p(HookAction::SendMessage { text: text.to_string() })
+    });
+    let p = push.clone();
+    engine.register_fn("tag_session", move |tag: &str| {
+        p(HookAction::TagSession { tag: tag.to_string() })
+    });
+    let p = push.clone();
+    engine.register_fn("run_hook", move |name: &str| {
+        p(HookAction::RunHook { name: name.to_string() })
+    });
+    engine.register_fn("post_webhook", move |url: &str, body: Dynamic| {
+        let body: serde_json::Value = rhai::serde::from_dynamic(&body)?;
+        push(HookAction::PostWebhook { url: url.to_string(), body })
+    });
+
+    engine
+}
+
+/// Check that a script parses without running it
+pub fn validate_script(source: &str) -> Result<(), String> {
+    let engine = build_engine(Arc::new(Mutex::new(Vec::new())));
+    engine
+        .compile(source)
+        .map(|_| ())
+        .map_err(|e| format!("Script does not compile: {}", e))
+}
+
+/// Run a script for an event and return the actions it requested.
+///
+/// `event` and `payload` are exposed to the script as constants.
+pub fn run_script(source: &str, event: HookEvent, payload: &serde_json::Value) -> Result<Vec<HookAction>, String> {
+    let actions = Arc::new(Mutex::new(Vec::new()));
+    let engine = build_engine(actions.clone());
+
+    let payload = rhai::serde::to_dynamic(payload).map_err(|e| format!("Invalid hook payload: {}", e))?;
+    let mut scope = Scope::new();
+    scope.push_constant("event", event.as_str().to_string());
+    scope.push_constant("payload", payload);
+
+    engine
+        .run_with_scope(&mut scope, source)
+        .map_err(|e| format!("Script failed: {}", e))?;
+
+    let actions = actions.lock().unwrap().clone();
+    Ok(actions)
+}
+
+pub struct ScriptHookStore {
+    db: SqlitePool,
+}
+
+impl ScriptHookStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn list_hooks(&self, profile_id: &str) -> Result<Vec<ScriptHook>, sqlx::Error> {
+        sqlx::query_as::<_, ScriptHook>(
+            "SELECT id, profile_id, name, event, source, enabled, created_at, updated_at
+             FROM script_hooks WHERE profile_id = ? ORDER BY name"
+        )
+        .bind(profile_id)
+        .fetch_all(&self.db)
+        .await
+    }
+
+    pub async fn list_enabled_for_event(&self, profile_id: &str, event: HookEvent) -> Result<Vec<ScriptHook>, sqlx::Error> {
+        sqlx::query_as::<_, ScriptHook>(
+            "SELECT id, profile_id, name, event, source, enabled, created_at, updated_at
+             FROM script_hooks WHERE profile_id = ? AND event = ? AND enabled = 1 ORDER BY name"
+        )
+        .bind(profile_id)
+        .bind(event.as_str())
+        .fetch_all(&self.db)
+        .await
+    }
+
+    pub async fn get_hook_by_name(&self, profile_id: &str, name: &str) -> Result<Option<ScriptHook>, sqlx::Error> {
+        sqlx::query_as::<_, ScriptHook>(
+            "SELECT id, profile_id, name, event, source, enabled, created_at, updated_at
+             FROM script_hooks WHERE profile_id = ? AND name = ?"
+        )
+        .bind(profile_id)
+        .bind(name)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    /// Insert a new hook or update the existing one with the same id
+    pub async fn save_hook(&self, id: Option<&str>, profile_id: &str, name: &str, event: HookEvent, source: &str, enabled: bool) -> Result<ScriptHook, sqlx::Error> {
+        match id {
+            Some(id) => {
+                sqlx::query_as::<_, ScriptHook>(
+                    "UPDATE script_hooks SET name = ?, event = ?, source = ?, enabled = ?
+                     WHERE id = ? AND profile_id = ?
+                     RETURNING id, profile_id, name, event, source, enabled, created_at, updated_at"
+                )
+                .bind(name)
+                .bind(event.as_str())
+                .bind(source)
+                .bind(enabled)
+                .bind(id)
+                .bind(profile_id)
+                .fetch_one(&self.db)
+                .await
+            }
+            None => {
+                sqlx::query_as::<_, ScriptHook>(
+                    "INSERT INTO script_hooks (id, profile_id, name, event, source, enabled) VALUES (?, ?, ?, ?, ?, ?)
+                     RETURNING id, profile_id, name, event, source, enabled, created_at, updated_at"
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(profile_id)
+                .bind(name)
+                .bind(event.as_str())
+                .bind(source)
+                .bind(enabled)
+                .fetch_one(&self.db)
+                .await
+            }
+        }
+    }
+
+    pub async fn delete_hook(&self, profile_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM script_hooks WHERE id = ? AND profile_id = ?")
+            .bind(id)
+            .bind(profile_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn tag_session(&self, session_id: &str, tag: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO session_tags (session_id, tag) VALUES (?, ?)")
+            .bind(session_id)
+            .bind(tag)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_session_tags(&self, session_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT tag FROM session_tags WHERE session_id = ? ORDER BY tag")
+            .bind(session_id)
+            .fetch_all(&self.db)
+            .await
+    }
+}
+
+async fn resolve_profile_id(profile_manager: &ProfileManager, profile_id: Option<String>) -> Result<String, String> {
+    match profile_id {
+        Some(id) => Ok(id),
+        None => profile_manager
+            .active_profile_id
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| "No active profile".to_string()),
+    }
+}
+
+/// Fire hooks for an event on a background task so stream readers are never blocked
+pub fn spawn_hook_event(app_handle: AppHandle, session_id: String, event: HookEvent, payload: serde_json::Value) {
+    tokio::spawn(async move {
+        dispatch_hook_event(&app_handle, &session_id, event, payload).await;
+    });
+}
+
+/// Fire `tool_use` hooks for each tool call contained in an assistant stream event
+pub fn dispatch_tool_use_hooks(app_handle: &AppHandle, session_id: &str, event: &serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+        return;
+    }
+    let Some(parts) = event.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()) else { return };
+
+    for part in parts.iter().filter(|p| p.get("type").and_then(|t| t.as_str()) == Some("tool_use")) {
+        spawn_hook_event(
+            app_handle.clone(),
+            session_id.to_string(),
+            HookEvent::ToolUse,
+            serde_json::json!({ "session_id": session_id, "tool": part }),
+        );
+    }
+}
+
+/// Run every enabled hook registered for `event` of the profile the session is
+/// bound to, or of the active profile for an unbound session
+pub async fn dispatch_hook_event(app_handle: &AppHandle, session_id: &str, event: HookEvent, payload: serde_json::Value) {
+    let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else { return };
+    let bound = crate::session_commands::bound_profile_id(&profile_manager, session_id).await;
+    let Some(profile_id) = bound.or(profile_manager.active_profile_id.read().await.clone()) else { return };
+    let Some(db) = profile_manager.db_pool.read().await.clone() else { return };
+
+    let store = ScriptHookStore::new(db);
+    let hooks = match store.list_enabled_for_event(&profile_id, event).await {
+        Ok(hooks) => hooks,
+        Err(e) => {
+            log::warn!("Failed to load script hooks for {}: {}", event.as_str(), e);
+            return;
+        }
+    };
+
+    for hook in hooks {
+        execute_hook(app_handle, &store, hook, session_id, event, &payload).await;
+    }
+}
+
+async fn execute_hook(
+    app_handle: &AppHandle,
+    store: &ScriptHookStore,
+    hook: ScriptHook,
+    session_id: &str,
+    event: HookEvent,
+    payload: &serde_json::Value,
+) {
+    // Hooks requested through run_hook are queued rather than awaited recursively
+    let mut queue = vec![(hook, 0usize)];
+
+    while let Some((hook, depth)) = queue.pop() {
+        // Scripts are CPU-bound and bounded by MAX_OPERATIONS, keep them off the async workers
+        let source = hook.source.clone();
+        let script_payload = payload.clone();
+        let result = tokio::task::spawn_blocking(move || run_script(&source, event, &script_payload))
+            .await
+            .unwrap_or_else(|e| Err(format!("Script task panicked: {}", e)));
+
+        let (actions, error) = match result {
+            Ok(actions) => (actions, None),
+            Err(e) => {
+                log::warn!("Script hook '{}' failed: {}", hook.name, e);
+                (Vec::new(), Some(e))
+            }
+        };
+
+        let _ = app_handle.emit("script_hook_executed", serde_json::json!({
+            "hook_id": hook.id,
+            "hook_name": hook.name,
+            "event": event.as_str(),
+            "session_id": session_id,
+            "actions": actions,
+            "error": error,
+        }));
+
+        for action in actions {
+            match action {
+                HookAction::SendMessage { text } => {
+                    if let Some(amp_sessions) = app_handle.try_state::<AmpSessionMap>() {
+                        if let Some(session) = amp_sessions.get(session_id) {
+                            let session = session.lock().await;
+                            let message = serde_json::json!({
+                                "type": "user",
+                                "message": {
+                                    "role": "user",
+                                    "content": [{ "type": "text", "text": text }]
+                                }
+                            });
+                            let _ = session.tx.send(message.to_string());
+                        }
+                    }
+                }
+                HookAction::TagSession { tag } => {
+                    if let Err(e) = store.tag_session(session_id, &tag).await {
+                        log::warn!("Script hook '{}' failed to tag session {}: {}", hook.name, session_id, e);
+                    }
+                }
+                HookAction::RunHook { name } => {
+                    if depth + 1 >= MAX_HOOK_CHAIN_DEPTH {
+                        log::warn!("Script hook '{}' exceeded chain depth, not running '{}'", hook.name, name);
+                        continue;
+                    }
+                    match store.get_hook_by_name(&hook.profile_id, &name).await {
+                        Ok(Some(next)) if next.enabled => queue.push((next, depth + 1)),
+                        Ok(_) => log::warn!("Script hook '{}' requested unknown or disabled hook '{}'", hook.name, name),
+                        Err(e) => log::warn!("Failed to look up script hook '{}': {}", name, e),
+                    }
+                }
+                HookAction::PostWebhook { url, body } => {
+                    if !(url.starts_with("https://") || url.starts_with("http://")) {
+                        log::warn!("Script hook '{}' tried to post to non-http URL {}", hook.name, url);
+                        continue;
+                    }
+                    let keys = app_handle
+                        .try_state::<AppState>()
+                        .map(|state| state.lock().unwrap().webhook_keys.clone())
+                        .unwrap_or_default();
+                    let body = match webhook_body(&keys, &url, &body) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            log::warn!("Script hook '{}' did not post to {}: {}", hook.name, url, e);
+                            continue;
+                        }
+                    };
+                    let client = reqwest::Client::new();
+                    if let Err(e) = client.post(&url).json(&body).send().await {
+                        log::warn!("Script hook '{}' webhook to {} failed: {}", hook.name, url, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The body posted to `url`, sealed to the key of the longest matching URL prefix
+fn webhook_body(keys: &HashMap<String, String>, url: &str, body: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let Some(key) = keys
+        .iter()
+        .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, key)| key)
+    else {
+        return Ok(body.clone());
+    };
+    let plaintext = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+    let sealed = sealed_box::seal(&PublicKey::from_base64(key)?, &plaintext)?;
+    Ok(serde_json::json!({ "sealed": BASE64.encode(sealed), "scheme": sealed_box::SCHEME }))
+}
+
+/// List script hooks for a profile (defaults to the active profile)
+#[tauri::command]
+pub async fn list_script_hooks(
+    profile_id: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<ScriptHook>, String> {
+    let profile_id = resolve_profile_id(&profile_manager, profile_id).await?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    ScriptHookStore::new(db.clone())
+        .list_hooks(&profile_id)
+        .await
+        .map_err(|e| format!("Failed to list script hooks: {}", e))
+}
+
+/// Create or update a script hook after checking that it compiles
+#[tauri::command]
+pub async fn save_script_hook(
+    request: SaveScriptHookRequest,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ScriptHook, String> {
+    if request.name.trim().is_empty() {
+        return Err("Hook name cannot be empty".to_string());
+    }
+    validate_script(&request.source)?;
+
+    let profile_id = resolve_profile_id(&profile_manager, request.profile_id.clone()).await?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    ScriptHookStore::new(db.clone())
+        .save_hook(
+            request.id.as_deref(),
+            &profile_id,
+            request.name.trim(),
+            request.event,
+            &request.source,
+            request.enabled.unwrap_or(true),
+        )
+        .await
+        .map_err(|e| format!("Failed to save script hook: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_script_hook(
+    id: String,
+    profile_id: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<bool, String> {
+    let profile_id = resolve_profile_id(&profile_manager, profile_id).await?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    // Hooks of other profiles are left alone
+    let Some(label) = sqlx::query_scalar::<_, String>("SELECT name FROM script_hooks WHERE id = ? AND profile_id = ?")
+        .bind(&id)
+        .bind(&profile_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to get script hook: {}", e))?
+    else {
+        return Ok(false);
+    };
+    TrashStore::new(db.clone())
+        .trash_rows(TrashKind::ScriptHook, &label, &[Capture::new("script_hooks", "id = ?", &id)], serde_json::json!({}))
+        .await
+        .map(|item| item.is_some())
+        .map_err(|e| format!("Failed to delete script hook: {}", e))
+}
+
+/// Dry-run a script against a sample payload without applying its actions
+#[tauri::command]
+pub async fn test_script_hook(
+    source: String,
+    event: HookEvent,
+    payload: Option<serde_json::Value>,
+) -> Result<Vec<HookAction>, String> {
+    let payload = payload.unwrap_or_else(|| serde_json::json!({}));
+    tokio::task::spawn_blocking(move || run_script(&source, event, &payload))
+        .await
+        .map_err(|e| format!("Script task panicked: {}", e))?
+}
+
+/// Fire hooks for an event raised by the frontend (e.g. budget_exceeded)
+#[tauri::command]
+pub async fn trigger_script_hooks(
+    session_id: String,
+    event: HookEvent,
+    payload: Option<serde_json::Value>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let payload = payload.unwrap_or_else(|| serde_json::json!({ "session_id": session_id }));
+    dispatch_hook_event(&app_handle, &session_id, event, payload).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_session_tags(
+    session_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<String>, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    ScriptHookStore::new(db.clone())
+        .list_session_tags(&session_id)
+        .await
+        .map_err(|e| format!("Failed to get session tags: {}", e))
+}
+
+/// Public keys webhook bodies are sealed to, by URL prefix
+#[tauri::command]
+pub async fn list_webhook_keys(app_state: State<'_, AppState>) -> Result<HashMap<String, String>, String> {
+    Ok(app_state.lock().unwrap().webhook_keys.clone())
+}
+
+/// Seal bodies posted to URLs starting with `url_prefix` to `public_key`, a
+/// base64 X25519 key; without one they are posted in the clear again
+#[tauri::command]
+pub async fn set_webhook_key(
+    url_prefix: String,
+    public_key: Option<String>,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !(url_prefix.starts_with("https://") || url_prefix.starts_with("http://")) {
+        return Err("Webhook URL must start with http:// or https://".to_string());
+    }
+    if let Some(public_key) = &public_key {
+        PublicKey::from_base64(public_key)?;
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        match public_key {
+            Some(public_key) => state.webhook_keys.insert(url_prefix, public_key.trim().to_string()),
+            None => state.webhook_keys.remove(&url_prefix),
+        };
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[test]
+    fn test_script_records_actions() {
+        let payload = serde_json::json!({ "session_id": "abc", "tool": { "name": "Bash" } });
+        let actions = run_script(
+            r#"
+                if payload.tool.name == "Bash" { tag_session("used-bash"); }
+                send_message("checked " + payload.session_id);
+                post_webhook("https://example.com/hook", #{ event: event });
+            "#,
+            HookEvent::ToolUse,
+            &payload,
+        ).unwrap();
+
+        assert_eq!(actions, vec![
+            HookAction::TagSession { tag: "used-bash".to_string() },
+            HookAction::SendMessage { text: "checked abc".to_string() },
+            HookAction::PostWebhook {
+                url: "https://example.com/hook".to_string(),
+                body: serde_json::json!({ "event": "tool_use" }),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_script_sandbox_limits() {
+        let payload = serde_json::json!({});
+        assert!(run_script("loop {}", HookEvent::SessionCompleted, &payload).is_err());
+        assert!(run_script("for i in 0..100 { tag_session(`t${i}`); }", HookEvent::SessionCompleted, &payload).is_err());
+        assert!(validate_script(r#"eval("1")"#).is_err());
+        assert!(validate_script("let x = ;").is_err());
+        assert!(validate_script("run_hook(\"notify\");").is_ok());
+    }
+
+    #[test]
+    fn test_hook_event_round_trip() {
+        for event in [HookEvent::SessionCompleted, HookEvent::ToolUse, HookEvent::BudgetExceeded] {
+            assert_eq!(HookEvent::parse(event.as_str()), Some(event));
+        }
+        assert_eq!(HookEvent::parse("unknown"), None);
+    }
+
+    #[tokio::test]
+    async fn test_store_save_and_list() {
+        let store = ScriptHookStore::new(test_pool().await);
+
+        let hook = store
+            .save_hook(None, "bundled", "notify", HookEvent::SessionCompleted, "tag_session(\"done\");", true)
+            .await
+            .unwrap();
+        store
+            .save_hook(None, "bundled", "budget", HookEvent::BudgetExceeded, "send_message(\"stop\");", false)
+            .await
+            .unwrap();
+
+        let enabled = store.list_enabled_for_event("bundled", HookEvent::SessionCompleted).await.unwrap();
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].name, "notify");
+        assert!(store.list_enabled_for_event("bundled", HookEvent::BudgetExceeded).await.unwrap().is_empty());
+        assert!(store.list_hooks("global").await.unwrap().is_empty());
+
+        let updated = store
+            .save_hook(Some(&hook.id), "bundled", "notify", HookEvent::ToolUse, "tag_session(\"tool\");", true)
+            .await
+            .unwrap();
+        assert_eq!(updated.id, hook.id);
+        assert_eq!(updated.event, "tool_use");
+
+        assert!(!store.delete_hook("global", &hook.id).await.unwrap());
+        assert!(store.delete_hook("bundled", &hook.id).await.unwrap());
+        assert_eq!(store.list_hooks("bundled").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_tags_are_unique() {
+        let store = ScriptHookStore::new(test_pool().await);
+
+        store.tag_session("s1", "reviewed").await.unwrap();
+        store.tag_session("s1", "reviewed").await.unwrap();
+        store.tag_session("s1", "bash").await.unwrap();
+
+        assert_eq!(store.list_session_tags("s1").await.unwrap(), vec!["bash", "reviewed"]);
+    }
+
+    #[test]
+    fn test_webhook_bodies_are_sealed_to_the_endpoint_key() {
+        let (team, ops) = (sealed_box::SecretKey::generate().unwrap(), sealed_box::SecretKey::generate().unwrap());
+        let keys = HashMap::from([
+            ("https://hooks.example.com/".to_string(), team.public_key().to_base64()),
+            ("https://hooks.example.com/ops/".to_string(), ops.public_key().to_base64()),
+        ]);
+        let body = serde_json::json!({ "event": "session_completed", "transcript": "secret" });
+        assert_eq!(webhook_body(&keys, "https://other.example.com/", &body).unwrap(), body);
+
+        let posted = webhook_body(&keys, "https://hooks.example.com/ops/alerts", &body).unwrap();
+        assert_eq!(posted["scheme"], sealed_box::SCHEME);
+        let sealed = BASE64.decode(posted["sealed"].as_str().unwrap()).unwrap();
+        assert!(sealed_box::open(&team, &sealed).is_err());
+        let opened: serde_json::Value = serde_json::from_slice(&sealed_box::open(&ops, &sealed).unwrap()).unwrap();
+        assert_eq!(opened, body);
+
+        let broken = HashMap::from([("https://".to_string(), "not a key".to_string())]);
+        assert!(webhook_body(&broken, "https://hooks.example.com/", &body).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Public-key encryption of payloads left on infrastructure we do not trust
+//!
+//! A sealed box is readable only by the holder of the recipient's secret key,
+//! and the sender needs nothing but the recipient's public key. Boxes are
+//! libsodium's `crypto_box_seal` (X25519, XSalsa20-Poly1305, a fresh key pair
+//! per box), made with the `crypto_box` crate, so receivers open them with
+//! `crypto_box_seal_open` or any binding of it, such as PyNaCl's `SealedBox`.
+//! Keys are exchanged as standard base64 of their 32 raw bytes, the encoding
+//! those bindings take.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use crypto_box::aead::OsRng;
+use zeroize::Zeroizing;
+
+const KEY_LEN: usize = 32;
+/// Name of the construction, for envelopes that say how a payload was sealed
+pub const SCHEME: &str = "libsodium-crypto_box_seal";
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct PublicKey(crypto_box::PublicKey);
+
+/// Zeroized on drop
+#[derive(Clone)]
+pub struct SecretKey(crypto_box::SecretKey);
+
+impl PublicKey {
+    pub fn from_base64(encoded: &str) -> Result<Self, String> {
+        Ok(Self(crypto_box::PublicKey::from_bytes(*decode_key(encoded)?)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.0.as_bytes())
+    }
+}
+
+impl std::fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PublicKey({})", self.to_base64())
+    }
+}
+
+impl SecretKey {
+    pub fn generate() -> Result<Self, String> {
+        Ok(Self(crypto_box::SecretKey::generate(&mut OsRng)))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, String> {
+        Ok(Self(crypto_box::SecretKey::from_bytes(*decode_key(encoded)?)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(Zeroizing::new(self.0.to_bytes()))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.0.public_key())
+    }
+}
+
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+fn decode_key(encoded: &str) -> Result<Zeroizing<[u8; KEY_LEN]>, String> {
+    let bytes = Zeroizing::new(BASE64.decode(encoded.trim()).map_err(|e| format!("Invalid key: {}", e))?);
+    let key: [u8; KEY_LEN] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("Invalid key: expected {} bytes, got {}", KEY_LEN, bytes.len()))?;
+    Ok(Zeroizing::new(key))
+}
+
+/// Encrypt `plaintext` so only the holder of `recipient`'s secret key can read it
+pub fn seal(recipient: &PublicKey, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    recipient.0.seal(&mut OsRng, plaintext).map_err(|_| "Failed to encrypt payload".to_string())
+}
+
+/// Decrypt a sealed box addressed to `secret`'s public key
+pub fn open(secret: &SecretKey, sealed: &[u8]) -> Result<Vec<u8>, String> {
+    secret.0.unseal(sealed).map_err(|_| "Sealed box does not open with this key or was altered".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_boxes_open_only_with_the_recipient_key() {
+        let recipient = SecretKey::generate().unwrap();
+        let public = PublicKey::from_base64(&recipient.public_key().to_base64()).unwrap();
+        let sealed = seal(&public, b"session transcript").unwrap();
+        assert!(!sealed.windows(10).any(|w| w == b"transcript"));
+        assert_eq!(open(&recipient, &sealed).unwrap(), b"session transcript");
+        let imported = SecretKey::from_base64(&recipient.to_base64()).unwrap();
+        assert_eq!(open(&imported, &sealed).unwrap(), b"session transcript");
+
+        // Every box has its own ephemeral key
+        assert_ne!(seal(&public, b"session transcript").unwrap(), sealed);
+
+        let other = SecretKey::generate().unwrap();
+        assert!(open(&other, &sealed).is_err());
+        let mut altered = sealed.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert!(open(&recipient, &altered).is_err());
+        assert!(open(&recipient, b"{\"plain\": true}").is_err());
+        assert!(PublicKey::from_base64("c2hvcnQ=").unwrap_err().contains("expected 32 bytes"));
+    }
+}
@@ -0,0 +1,357 @@
+//! Secrets pulled from external managers at spawn time
+//!
+//! A profile can name environment variables (`AMP_API_KEY`, tokens for custom
+//! tools) whose values come from a secret manager rather than the keychain or the
+//! user's shell dotfiles. Only the reference is stored; the value is fetched each
+//! time a session of that profile is spawned, by the `SecretProvider` for the
+//! source's provider:
+//! - `1password` - `op read <reference>`, e.g. `op://Private/Amp/credential`
+//! - `pass` - first line of `pass show <reference>`
+//! - `dotenv` - `<file>` or `<file>#KEY`, KEY defaulting to the variable's name
+
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use tauri::State;
+
+use crate::profile_auth::ProfileManager;
+
+/// Source of secret values
+pub trait SecretProvider: Send + Sync {
+    /// Value that `reference` names; `env_key` is the variable it will be set as
+    fn fetch(&self, reference: &str, env_key: &str) -> Result<String, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecretProviderKind {
+    #[serde(rename = "1password")]
+    OnePassword,
+    #[serde(rename = "pass")]
+    Pass,
+    #[serde(rename = "dotenv")]
+    Dotenv,
+}
+
+impl SecretProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OnePassword => "1password",
+            Self::Pass => "pass",
+            Self::Dotenv => "dotenv",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "1password" => Some(Self::OnePassword),
+            "pass" => Some(Self::Pass),
+            "dotenv" => Some(Self::Dotenv),
+            _ => None,
+        }
+    }
+
+    pub fn provider(&self) -> Box<dyn SecretProvider> {
+        match self {
+            Self::OnePassword => Box::new(OnePasswordProvider { program: "op".to_string() }),
+            Self::Pass => Box::new(PassProvider { program: "pass".to_string() }),
+            Self::Dotenv => Box::new(DotenvProvider),
+        }
+    }
+}
+
+/// Run a secret manager's CLI and return its stdout. Stdin is closed so a
+/// manager that wants to prompt fails instead of waiting.
+fn run_cli(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{} returned a value that is not UTF-8", program))
+}
+
+/// 1Password CLI, which must already be signed in
+pub struct OnePasswordProvider {
+    pub program: String,
+}
+
+impl SecretProvider for OnePasswordProvider {
+    fn fetch(&self, reference: &str, _env_key: &str) -> Result<String, String> {
+        if !reference.starts_with("op://") {
+            return Err(format!("1Password references look like op://vault/item/field, got {}", reference));
+        }
+        run_cli(&self.program, &["read", "--no-newline", reference])
+    }
+}
+
+/// The standard unix password manager
+pub struct PassProvider {
+    pub program: String,
+}
+
+impl SecretProvider for PassProvider {
+    fn fetch(&self, reference: &str, _env_key: &str) -> Result<String, String> {
+        let output = run_cli(&self.program, &["show", reference])?;
+        output
+            .lines()
+            .next()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| format!("pass entry {} is empty", reference))
+    }
+}
+
+/// Env files in dotenv syntax
+pub struct DotenvProvider;
+
+impl SecretProvider for DotenvProvider {
+    fn fetch(&self, reference: &str, env_key: &str) -> Result<String, String> {
+        let (file, key) = match reference.rsplit_once('#') {
+            Some((file, key)) if !key.is_empty() => (file, key),
+            _ => (reference, env_key),
+        };
+        let file = expand_home(file);
+        let content = std::fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        parse_dotenv(&content)
+            .remove(key)
+            .ok_or_else(|| format!("{} does not define {}", file, key))
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// `KEY=value` lines, allowing `export`, comments and single or double quotes
+pub fn parse_dotenv(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = if let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                quoted.replace("\\n", "\n").replace("\\\"", "\"")
+            } else if let Some(quoted) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                quoted.to_string()
+            } else {
+                value.split(" #").next().unwrap_or_default().trim_end().to_string()
+            };
+            Some((key.trim().to_string(), value))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct SecretSource {
+    pub profile_id: String,
+    pub env_key: String,
+    pub provider: String,
+    pub reference: String,
+    pub created_at: String,
+}
+
+fn validate_env_key(env_key: &str) -> Result<(), String> {
+    let valid = env_key.chars().next().is_some_and(|c| c.is_ascii_uppercase() || c == '_')
+        && env_key.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{} is not a valid environment variable name", env_key))
+    }
+}
+
+pub async fn list_sources(db: &SqlitePool, profile_id: &str) -> Result<Vec<SecretSource>, String> {
+    sqlx::query_as::<_, SecretSource>(
+        "SELECT profile_id, env_key, provider, reference, created_at FROM profile_secrets WHERE profile_id = ? ORDER BY env_key",
+    )
+    .bind(profile_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to list profile secrets: {}", e))
+}
+
+/// Fetch every source's value. Errors name the variable but never a value.
+pub fn resolve(sources: &[SecretSource]) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    for source in sources {
+        let kind = SecretProviderKind::parse(&source.provider)
+            .ok_or_else(|| format!("Unknown secret provider {}", source.provider))?;
+        let value = kind
+            .provider()
+            .fetch(&source.reference, &source.env_key)
+            .map_err(|e| format!("Failed to fetch {} from {}: {}", source.env_key, source.provider, e))?;
+        values.insert(source.env_key.clone(), value);
+    }
+    Ok(values)
+}
+
+/// Secrets configured for a profile, fetched off the async runtime
+pub async fn profile_secrets(db: &SqlitePool, profile_id: &str) -> Result<HashMap<String, String>, String> {
+    let sources = list_sources(db, profile_id).await?;
+    if sources.is_empty() {
+        return Ok(HashMap::new());
+    }
+    tokio::task::spawn_blocking(move || resolve(&sources))
+        .await
+        .map_err(|e| format!("Secret lookup failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn list_profile_secrets(
+    profile_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<SecretSource>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    list_sources(db, &profile_id).await
+}
+
+/// Add or replace where a profile's `env_key` comes from
+pub(crate) async fn save_source(
+    conn: &mut SqliteConnection,
+    profile_id: &str,
+    env_key: &str,
+    provider: SecretProviderKind,
+    reference: &str,
+) -> Result<SecretSource, String> {
+    validate_env_key(env_key)?;
+    if reference.trim().is_empty() {
+        return Err("Secret reference cannot be empty".to_string());
+    }
+    sqlx::query_as::<_, SecretSource>(
+        "INSERT INTO profile_secrets (profile_id, env_key, provider, reference) VALUES (?, ?, ?, ?)
+         ON CONFLICT(profile_id, env_key) DO UPDATE SET provider = excluded.provider, reference = excluded.reference
+         RETURNING profile_id, env_key, provider, reference, created_at",
+    )
+    .bind(profile_id)
+    .bind(env_key)
+    .bind(provider.as_str())
+    .bind(reference.trim())
+    .fetch_one(conn)
+    .await
+    .map_err(|e| format!("Failed to save profile secret: {}", e))
+}
+
+/// Point an environment variable of a profile's sessions at a secret manager entry
+#[tauri::command]
+pub async fn save_profile_secret(
+    profile_id: String,
+    env_key: String,
+    provider: SecretProviderKind,
+    reference: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SecretSource, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let mut conn = db.acquire().await.map_err(|e| format!("Failed to save profile secret: {}", e))?;
+    save_source(&mut conn, &profile_id, &env_key, provider, &reference).await
+}
+
+#[tauri::command]
+pub async fn delete_profile_secret(
+    profile_id: String,
+    env_key: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<bool, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    sqlx::query("DELETE FROM profile_secrets WHERE profile_id = ? AND env_key = ?")
+        .bind(&profile_id)
+        .bind(&env_key)
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected() > 0)
+        .map_err(|e| format!("Failed to delete profile secret: {}", e))
+}
+
+/// Fetch a profile's secrets without spawning anything, reporting only which resolved
+#[tauri::command]
+pub async fn test_profile_secrets(
+    profile_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<String>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let mut keys: Vec<String> = profile_secrets(db, &profile_id).await?.into_keys().collect();
+    keys.sort();
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let values = parse_dotenv(
+            "# comment\nexport AMP_API_KEY=\"sk-1\"\nTOOL_TOKEN='a b'\nPLAIN=value # trailing\n\nBROKEN\n",
+        );
+        assert_eq!(values["AMP_API_KEY"], "sk-1");
+        assert_eq!(values["TOOL_TOKEN"], "a b");
+        assert_eq!(values["PLAIN"], "value");
+        assert_eq!(values.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_providers_fetch_values() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join("secrets.env");
+        std::fs::write(&env_file, "AMP_API_KEY=from-file\nOTHER=other\n").unwrap();
+        let file = env_file.to_string_lossy();
+        assert_eq!(DotenvProvider.fetch(&file, "AMP_API_KEY").unwrap(), "from-file");
+        assert_eq!(DotenvProvider.fetch(&format!("{}#OTHER", file), "AMP_API_KEY").unwrap(), "other");
+        assert!(DotenvProvider.fetch(&file, "MISSING").is_err());
+
+        let pass = temp.path().join("pass");
+        std::fs::write(&pass, "#!/bin/sh\n[ \"$2\" = amp/key ] || exit 1\nprintf 'sk-pass\\nlogin: me\\n'\n").unwrap();
+        std::fs::set_permissions(&pass, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let provider = PassProvider { program: pass.to_string_lossy().to_string() };
+        assert_eq!(provider.fetch("amp/key", "AMP_API_KEY").unwrap(), "sk-pass");
+        assert!(provider.fetch("amp/other", "AMP_API_KEY").is_err());
+
+        let op = OnePasswordProvider { program: pass.to_string_lossy().to_string() };
+        assert!(op.fetch("Private/Amp", "AMP_API_KEY").unwrap_err().contains("op://"));
+    }
+
+    #[tokio::test]
+    async fn test_profile_secrets_resolve_from_stored_sources() {
+        let pool = test_pool().await;
+        assert!(profile_secrets(&pool, "global").await.unwrap().is_empty());
+
+        let temp = TempDir::new().unwrap();
+        let env_file = temp.path().join("amp.env");
+        std::fs::write(&env_file, "AMP_API_KEY=sk-global\n").unwrap();
+        sqlx::query("INSERT INTO profile_secrets (profile_id, env_key, provider, reference) VALUES ('global', 'AMP_API_KEY', 'dotenv', ?)")
+            .bind(env_file.to_string_lossy().to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let secrets = profile_secrets(&pool, "global").await.unwrap();
+        assert_eq!(secrets.get("AMP_API_KEY").map(String::as_str), Some("sk-global"));
+        assert!(profile_secrets(&pool, "bundled").await.unwrap().is_empty());
+
+        std::fs::remove_file(&env_file).unwrap();
+        let error = profile_secrets(&pool, "global").await.unwrap_err();
+        assert!(error.contains("AMP_API_KEY") && !error.contains("sk-global"));
+
+        assert!(validate_env_key("AMP_API_KEY").is_ok());
+        assert!(validate_env_key("1KEY").is_err());
+        assert!(validate_env_key("bad-key").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! End-to-end self test for support requests
+//!
+//! `run_self_test` exercises the pieces a session depends on (process spawning
+//! with stream-json pipes, git worktrees, the SQLite driver, the OS keychain and
+//! the configured Amp server) inside a scratch directory, and returns a
+//! pass/fail matrix with a plain-text rendering users can paste into a bug report.
+
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::State;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::commit_message::git;
+use crate::keychain_auth::{KeychainAuth, TokenType};
+use crate::profile_auth::ProfileManager;
+
+const DEFAULT_API_URL: &str = "https://ampcode.com";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub duration_ms: u64,
+    /// What was verified, or why it failed
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub started_at: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub checks: Vec<SelfTestCheck>,
+    /// `checks` as a fixed-width table for bug reports
+    pub summary: String,
+}
+
+impl SelfTestReport {
+    fn new(checks: Vec<SelfTestCheck>, started_at: String) -> Self {
+        let passed = checks.iter().filter(|c| c.status == CheckStatus::Pass).count();
+        let mut report = Self {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            started_at,
+            passed,
+            failed: checks.len() - passed,
+            checks,
+            summary: String::new(),
+        };
+        report.summary = report.render();
+        report
+    }
+
+    fn render(&self) -> String {
+        let mut out = format!(
+            "Amp Orchestra {} self test ({}/{}, {})\n",
+            self.app_version, self.os, self.arch, self.started_at
+        );
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Pass => "PASS",
+                CheckStatus::Fail => "FAIL",
+            };
+            out.push_str(&format!("{:<4} {:<10} {:>6}ms  {}\n", status, check.name, check.duration_ms, check.detail));
+        }
+        out.push_str(&format!("{} passed, {} failed\n", self.passed, self.failed));
+        out
+    }
+}
+
+async fn run_check<F>(name: &str, check: F) -> SelfTestCheck
+where
+    F: Future<Output = Result<String, String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    let (status, detail) = match result {
+        Ok(detail) => (CheckStatus::Pass, detail),
+        Err(detail) => (CheckStatus::Fail, detail),
+    };
+    SelfTestCheck { name: name.to_string(), status, duration_ms: started.elapsed().as_millis() as u64, detail }
+}
+
+/// Spawn a stand-in CLI speaking stream-json over stdin/stdout and exchange one message
+pub async fn check_mock_cli(dir: &Path) -> Result<String, String> {
+    let (script, program, args): (PathBuf, &str, Vec<String>) = if cfg!(windows) {
+        let script = dir.join("mock-amp.cmd");
+        std::fs::write(&script, "@echo off\r\nset /p line=\r\necho {\"type\":\"system\",\"subtype\":\"init\"}\r\n")
+            .map_err(|e| e.to_string())?;
+        let arg = script.to_string_lossy().to_string();
+        (script, "cmd", vec!["/C".to_string(), arg])
+    } else {
+        let script = dir.join("mock-amp.sh");
+        std::fs::write(&script, "read line\necho '{\"type\":\"system\",\"subtype\":\"init\"}'\n").map_err(|e| e.to_string())?;
+        let arg = script.to_string_lossy().to_string();
+        (script, "sh", vec![arg])
+    };
+
+    let mut child = tokio::process::Command::new(program)
+        .args(&args)
+        .current_dir(dir)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn {}: {}", script.display(), e))?;
+    let mut stdin = child.stdin.take().ok_or("Failed to open stdin")?;
+    let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
+
+    let input = serde_json::json!({ "type": "user", "message": { "role": "user", "content": "ping" } });
+    stdin.write_all(format!("{}\n", input).as_bytes()).await.map_err(|e| e.to_string())?;
+    stdin.flush().await.map_err(|e| e.to_string())?;
+
+    let line = BufReader::new(stdout)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Mock CLI exited without output")?;
+    let event: serde_json::Value = serde_json::from_str(&line).map_err(|e| format!("Unparseable output {:?}: {}", line, e))?;
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("Mock CLI exited with {}", status));
+    }
+    Ok(format!("received {} event", event["type"].as_str().unwrap_or("unknown")))
+}
+
+/// Create a repository with one commit, add a session worktree and remove it
+pub async fn check_git_worktree(dir: &Path) -> Result<String, String> {
+    let repo = dir.join("repo");
+    std::fs::create_dir_all(&repo).map_err(|e| e.to_string())?;
+    git(&repo, &["init", "-q"])?;
+    std::fs::write(repo.join("README.md"), "self test\n").map_err(|e| e.to_string())?;
+    git(&repo, &["add", "README.md"])?;
+    git(&repo, &["-c", "user.name=Self Test", "-c", "user.email=self-test@localhost", "commit", "-q", "-m", "Initial commit"])?;
+    let version = git(&repo, &["--version"])?;
+
+    let session_id = Uuid::new_v4().to_string();
+    let meta = crate::worktree::create(&repo, &session_id).map_err(|e| e.to_string())?;
+    if !meta.path.join("README.md").exists() {
+        return Err(format!("Worktree {} is missing checked-out files", meta.path.display()));
+    }
+    crate::worktree::remove(&meta.path, &meta.branch, true).map_err(|e| e.to_string())?;
+    Ok(format!("{}; created and removed {}", version.trim(), meta.branch))
+}
+
+/// Write and read back a row in a fresh database file
+pub async fn check_database(dir: &Path) -> Result<String, String> {
+    let options = SqliteConnectOptions::new().filename(dir.join("self-test.db")).create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await.map_err(|e| e.to_string())?;
+    let result = async {
+        sqlx::query("CREATE TABLE self_test (id TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL)").execute(&pool).await?;
+        let value = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO self_test (id, value) VALUES ('probe', ?)").bind(&value).execute(&pool).await?;
+        let read: String = sqlx::query_scalar("SELECT value FROM self_test WHERE id = 'probe'").fetch_one(&pool).await?;
+        let version: String = sqlx::query_scalar("SELECT sqlite_version()").fetch_one(&pool).await?;
+        Ok::<_, sqlx::Error>((read == value, version))
+    }
+    .await;
+    pool.close().await;
+    match result.map_err(|e| e.to_string())? {
+        (true, version) => Ok(format!("SQLite {}", version)),
+        (false, _) => Err("Read back a different value than was written".to_string()),
+    }
+}
+
+/// Store, read and delete a throwaway token
+pub async fn check_keychain() -> Result<String, String> {
+    let keychain = KeychainAuth::new();
+    let profile_id = format!("self-test-{}", Uuid::new_v4());
+    let token = Uuid::new_v4().to_string();
+    keychain.store_token(&profile_id, TokenType::AccessToken, &token)?;
+    let read = keychain.get_token(&profile_id, &TokenType::AccessToken);
+    let deleted = keychain.delete_token(&profile_id, &TokenType::AccessToken);
+    if read? != token {
+        return Err("Read back a different token than was stored".to_string());
+    }
+    deleted?;
+    Ok("stored, read and deleted a token".to_string())
+}
+
+/// Any HTTP response from the server counts; only transport errors fail
+pub async fn check_network(api_url: &str, tls_insecure: bool) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(tls_insecure)
+        .timeout(CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(api_url).send().await.map_err(|e| format!("{}: {}", api_url, e))?;
+    Ok(format!("{} answered {}", api_url, response.status()))
+}
+
+#[tauri::command]
+pub async fn run_self_test(
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SelfTestReport, String> {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let (api_url, tls_insecure) = match profile_manager.get_active_profile().await {
+        Some(profile_ctx) => {
+            let ctx = profile_ctx.read().await;
+            (ctx.profile.api_url.clone(), ctx.profile.tls_insecure)
+        }
+        None => {
+            let env = app_state.lock().unwrap().compose_env();
+            let api_url = env.get("AMP_URL").cloned().unwrap_or_else(|| DEFAULT_API_URL.to_string());
+            (api_url, env.get("NODE_TLS_REJECT_UNAUTHORIZED").is_some_and(|v| v == "0"))
+        }
+    };
+
+    let dir = std::env::temp_dir().join(format!("amp-orchestra-self-test-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let checks = vec![
+        run_check("cli", check_mock_cli(&dir)).await,
+        run_check("worktree", check_git_worktree(&dir)).await,
+        run_check("database", check_database(&dir)).await,
+        run_check("keychain", check_keychain()).await,
+        run_check("network", check_network(&api_url, tls_insecure)).await,
+    ];
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        log::warn!("Failed to remove self test directory {}: {}", dir.display(), e);
+    }
+    Ok(SelfTestReport::new(checks, started_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_local_checks_pass_and_render() {
+        let dir = TempDir::new().unwrap();
+        let checks = vec![
+            run_check("cli", check_mock_cli(dir.path())).await,
+            run_check("worktree", check_git_worktree(dir.path())).await,
+            run_check("database", check_database(dir.path())).await,
+            run_check("network", async { Err("unreachable".to_string()) }).await,
+        ];
+        let report = SelfTestReport::new(checks, "2024-01-01T00:00:00Z".to_string());
+        for check in &report.checks[..3] {
+            assert_eq!(check.status, CheckStatus::Pass, "{}: {}", check.name, check.detail);
+        }
+        assert_eq!((report.passed, report.failed), (3, 1));
+        assert!(report.summary.contains("FAIL network"));
+        assert!(report.summary.ends_with("3 passed, 1 failed\n"));
+    }
+}
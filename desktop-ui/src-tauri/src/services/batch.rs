@@ -0,0 +1,198 @@
+//! Starting, validating and estimating batches
+
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::batch_engine::{BatchConfig, BatchEngine, BatchHandle, BatchValidationReport, HistoricalMetrics};
+use crate::batch_estimate::{self, BatchSimulation};
+use crate::batch_shards::shard_case_count;
+use crate::profile_quotas::{self, UsageKind};
+
+pub struct BatchService {
+    engine: Arc<BatchEngine>,
+    /// Batch history and quotas; both are skipped without a database
+    db: Option<SqlitePool>,
+}
+
+impl BatchService {
+    pub fn new(engine: Arc<BatchEngine>, db: Option<SqlitePool>) -> Self {
+        Self { engine, db }
+    }
+
+    /// Start a batch whose cases count against `profile_id`'s session quota
+    pub async fn start(&self, config: BatchConfig, profile_id: Option<&str>) -> Result<BatchHandle, String> {
+        let cases = shard_case_count(&config) as i64;
+        if let Some(db) = &self.db {
+            profile_quotas::check(db, profile_id, cases).await.map_err(|e| e.to_command_error())?;
+        }
+
+        let handle = self.engine.start_batch(config).await.map_err(|e| format!("Failed to start batch: {}", e))?;
+        if let (Some(db), Some(profile_id)) = (&self.db, profile_id) {
+            if let Err(e) = profile_quotas::record(db, profile_id, UsageKind::BatchCase, cases).await {
+                log::warn!("{}", e);
+            }
+        }
+        Ok(handle)
+    }
+
+    /// Check a batch definition, estimating it from past runs with the same agent mode
+    pub async fn validate(&self, config: &BatchConfig, dataset_path: Option<&Path>) -> Result<BatchValidationReport, String> {
+        let history = match &self.db {
+            Some(db) => load_historical_metrics(db, config.agent_mode.as_deref()).await?,
+            None => None,
+        };
+        Ok(self.engine.validate_batch(config, dataset_path, history.as_ref()))
+    }
+
+    /// Simulate a batch against past runs to predict its duration and token use
+    pub async fn estimate(&self, config: BatchConfig) -> Result<BatchSimulation, String> {
+        let concurrency = self.engine.effective_concurrency(&config);
+        let history = match &self.db {
+            Some(db) => batch_estimate::load_history(db).await?,
+            None => Vec::new(),
+        };
+
+        // Counting files shells out to git once per repository
+        tokio::task::spawn_blocking(move || {
+            let mut sizes: HashMap<PathBuf, Option<f64>> = HashMap::new();
+            for repo in config.repositories.iter().chain(history.iter().flat_map(|(_, repos)| repos)) {
+                if !sizes.contains_key(repo) {
+                    sizes.insert(repo.clone(), batch_estimate::repo_file_count(repo));
+                }
+            }
+            let samples = batch_estimate::attach_repo_sizes(history, &sizes);
+            let cases = batch_estimate::case_repo_files(&config, &sizes);
+            batch_estimate::simulate(&cases, config.agent_mode.as_deref(), concurrency, config.timeout_sec, &samples)
+        })
+        .await
+        .map_err(|e| format!("Failed to estimate batch: {}", e))
+    }
+}
+
+/// Average duration and token usage of completed batch sessions, preferring
+/// runs with the same agent mode and falling back to all runs
+async fn load_historical_metrics(
+    db: &SqlitePool,
+    agent_mode: Option<&str>,
+) -> Result<Option<HistoricalMetrics>, String> {
+    let query = "SELECT COUNT(*),
+                AVG((julianday(s.completed_at) - julianday(s.started_at)) * 86400.0),
+                AVG(CAST(json_extract(s.metrics_json, '$.tokens_used') AS REAL))
+         FROM batch_sessions s JOIN batch_runs r ON r.id = s.batch_id
+         WHERE s.status = 'completed' AND s.started_at IS NOT NULL AND s.completed_at IS NOT NULL";
+
+    let mut row = None;
+    if let Some(mode) = agent_mode {
+        let by_mode = sqlx::query_as::<_, (i64, Option<f64>, Option<f64>)>(
+            &format!("{} AND json_extract(r.config_json, '$.agent_mode') = ?", query)
+        )
+        .bind(mode)
+        .fetch_one(db)
+        .await
+        .map_err(|e| format!("Failed to load batch history: {}", e))?;
+        if by_mode.0 > 0 {
+            row = Some(by_mode);
+        }
+    }
+
+    let (count, avg_secs, avg_tokens) = match row {
+        Some(row) => row,
+        None => sqlx::query_as::<_, (i64, Option<f64>, Option<f64>)>(query)
+            .fetch_one(db)
+            .await
+            .map_err(|e| format!("Failed to load batch history: {}", e))?,
+    };
+
+    if count == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(HistoricalMetrics {
+        sample_size: count as usize,
+        avg_session_secs: avg_secs,
+        avg_tokens_per_session: avg_tokens,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile_quotas::ProfileQuota;
+    use crate::runtime_env::{AmpConfig, EnvKind, RuntimeEnvironment, ToolboxConfig};
+    use crate::schema::test_pool;
+    use crate::session_manager::EnhancedSessionManager;
+
+    fn engine() -> Arc<BatchEngine> {
+        let runtime_env = RuntimeEnvironment {
+            env_kind: EnvKind::Production,
+            amp_config: AmpConfig { server_url: None, cli_path: None, agent_mode: None },
+            toolbox_config: ToolboxConfig { toolbox_paths: vec![], max_file_count: 1000, max_total_size: 1024 },
+            agent_mode: None,
+            worktree_path: None,
+        };
+        Arc::new(BatchEngine::new(Arc::new(EnhancedSessionManager::new(Default::default(), runtime_env))))
+    }
+
+    fn config(prompts: usize) -> BatchConfig {
+        BatchConfig {
+            name: "Service".to_string(),
+            prompts: (0..prompts).map(|i| format!("Prompt {}", i)).collect(),
+            repositories: vec![PathBuf::from("/repo")],
+            concurrency: 2,
+            timeout_sec: 600,
+            retry_policy: None,
+            agent_mode: Some("geppetto:main".to_string()),
+            toolbox_path: None,
+            shard: None,
+            results_dir: None,
+            dependencies: Vec::new(),
+            priorities: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_refuses_batches_over_quota() {
+        let db = test_pool().await;
+        let quota = ProfileQuota { sessions_per_day: Some(2), tokens_per_week: None };
+        profile_quotas::set_quota(&db, "work", &quota).await.unwrap();
+        let service = BatchService::new(engine(), Some(db));
+
+        let error = service.start(config(3), Some("work")).await.err().unwrap();
+        assert!(error.contains("\"code\":\"sessions_per_day\""), "{}", error);
+        assert!(service.engine.list_active_batches().await.is_empty());
+
+        let error = service.start(config(0), None).await.err().unwrap();
+        assert!(error.contains("No prompts provided"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_validate_estimates_from_runs_with_the_same_agent_mode() {
+        let db = test_pool().await;
+        for (batch, mode, secs) in [("b1", "geppetto:main", 60), ("b2", "default", 600)] {
+            sqlx::query("INSERT INTO batch_runs (id, name, config_json, total_sessions, created_at) VALUES (?, 'run', ?, 1, '2026-01-01')")
+                .bind(batch)
+                .bind(serde_json::json!({ "agent_mode": mode }).to_string())
+                .execute(&db)
+                .await
+                .unwrap();
+            sqlx::query(
+                "INSERT INTO batch_sessions (batch_id, session_id, status, started_at, completed_at, metrics_json)
+                 VALUES (?, 's', 'completed', '2026-01-01 00:00:00', datetime('2026-01-01 00:00:00', ?), '{\"tokens_used\": 1000}')",
+            )
+            .bind(batch)
+            .bind(format!("+{} seconds", secs))
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let report = BatchService::new(engine(), Some(db)).validate(&config(2), None).await.unwrap();
+        assert_eq!(report.estimate.sample_size, 1);
+        assert_eq!(report.estimate.estimated_total_tokens, Some(2000));
+
+        let report = BatchService::new(engine(), None).validate(&config(2), None).await.unwrap();
+        assert_eq!(report.estimate.sample_size, 0);
+    }
+}
@@ -0,0 +1,18 @@
+//! Service layer behind the Tauri commands
+//!
+//! Services are plain async structs holding the stores and clients they work
+//! with, handed in by the caller, so they can be built and tested without a
+//! Tauri app. Commands resolve their managed state, build the service and
+//! delegate to it, keeping only Tauri concerns such as feature flags, events
+//! and handles to themselves.
+
+pub mod batch;
+pub mod read_cache;
+pub mod toolbox;
+
+pub use batch::BatchService;
+pub use read_cache::{CachedQuery, ReadCache};
+pub use toolbox::ToolboxService;
+
+/// Error of calls that need the database when none is open
+pub const NO_DATABASE: &str = "Database not available";
@@ -0,0 +1,239 @@
+//! Cache of the lists the UI polls
+//!
+//! Sessions, profiles and toolbox profiles are re-read every few seconds, each
+//! a page query with a count. A cached result is served while the tables it
+//! reads are unchanged: triggers bump a table's row in `cache_versions` on
+//! every write, whichever code path made it, so a check costs one primary key
+//! lookup instead of the query. Services also `invalidate` after their own
+//! writes, which drops results loaded while a write was under way. Without the
+//! versions table nothing is cached.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Results kept per query; older versions are dropped first, then all of them
+const MAX_ENTRIES_PER_QUERY: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachedQuery {
+    Sessions,
+    Profiles,
+    ToolboxProfiles,
+}
+
+impl CachedQuery {
+    const ALL: [CachedQuery; 3] = [CachedQuery::Sessions, CachedQuery::Profiles, CachedQuery::ToolboxProfiles];
+
+    /// Tables whose writes make the query's results stale
+    fn tables(self) -> &'static [&'static str] {
+        match self {
+            CachedQuery::Sessions => &["chat_sessions"],
+            CachedQuery::Profiles => &["profiles"],
+            CachedQuery::ToolboxProfiles => &["toolbox_profiles", "toolbox_profile_paths"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub query: CachedQuery,
+    pub hits: u64,
+    pub misses: u64,
+    /// Reads that went to the database because there are no table versions
+    pub bypassed: u64,
+    pub invalidations: u64,
+    /// Hits over hits and misses; 0 before any read
+    pub hit_rate: f64,
+    pub entries: usize,
+}
+
+struct Entry {
+    version: i64,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    hits: u64,
+    misses: u64,
+    bypassed: u64,
+    invalidations: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<(CachedQuery, String), Entry>,
+    counters: HashMap<CachedQuery, Counters>,
+    /// Bumped by `invalidate`, so loads that straddle one are not kept
+    generations: HashMap<CachedQuery, u64>,
+}
+
+#[derive(Clone, Default)]
+pub struct ReadCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ReadCache {
+    /// The cached result of `query` for `key`, or what `load` returns, kept
+    /// until one of the query's tables is written
+    pub async fn get_or_load<T, Fut>(
+        &self,
+        db: &SqlitePool,
+        query: CachedQuery,
+        key: &str,
+        load: impl FnOnce() -> Fut,
+    ) -> Result<T, String>
+    where
+        T: Clone + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        // Read before loading, so a write during the load leaves the result stale rather than wrong
+        let Some(version) = table_version(db, query).await else {
+            self.inner.lock().unwrap().counters.entry(query).or_default().bypassed += 1;
+            return load().await;
+        };
+
+        let generation = {
+            let mut inner = self.inner.lock().unwrap();
+            let cached = inner
+                .entries
+                .get(&(query, key.to_string()))
+                .filter(|entry| entry.version == version)
+                .and_then(|entry| entry.value.downcast_ref::<T>())
+                .cloned();
+            let counters = inner.counters.entry(query).or_default();
+            match cached {
+                Some(value) => {
+                    counters.hits += 1;
+                    return Ok(value);
+                }
+                None => counters.misses += 1,
+            }
+            inner.generations.get(&query).copied().unwrap_or(0)
+        };
+
+        let value = load().await?;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.generations.get(&query).copied().unwrap_or(0) == generation {
+            inner.entries.retain(|(q, _), entry| *q != query || entry.version == version);
+            if inner.entries.keys().filter(|(q, _)| *q == query).count() >= MAX_ENTRIES_PER_QUERY {
+                inner.entries.retain(|(q, _), _| *q != query);
+            }
+            inner.entries.insert((query, key.to_string()), Entry { version, value: Arc::new(value.clone()) });
+        }
+        Ok(value)
+    }
+
+    /// Drop the results of `query`
+    pub fn invalidate(&self, query: CachedQuery) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.retain(|(q, _), _| *q != query);
+        *inner.generations.entry(query).or_default() += 1;
+        inner.counters.entry(query).or_default().invalidations += 1;
+    }
+
+    /// Hit rates per query, optionally starting the counts over
+    pub fn stats(&self, reset: bool) -> Vec<CacheStats> {
+        let mut inner = self.inner.lock().unwrap();
+        let stats = CachedQuery::ALL
+            .into_iter()
+            .map(|query| {
+                let counters = inner.counters.get(&query).copied().unwrap_or_default();
+                let reads = counters.hits + counters.misses;
+                CacheStats {
+                    query,
+                    hits: counters.hits,
+                    misses: counters.misses,
+                    bypassed: counters.bypassed,
+                    invalidations: counters.invalidations,
+                    hit_rate: if reads == 0 { 0.0 } else { counters.hits as f64 / reads as f64 },
+                    entries: inner.entries.keys().filter(|(q, _)| *q == query).count(),
+                }
+            })
+            .collect();
+        if reset {
+            inner.counters.clear();
+        }
+        stats
+    }
+}
+
+/// Sum of the versions of the query's tables; it grows with every write to any of them
+async fn table_version(db: &SqlitePool, query: CachedQuery) -> Option<i64> {
+    let tables = query.tables();
+    let sql = format!(
+        "SELECT SUM(version) FROM cache_versions WHERE table_name IN ({})",
+        vec!["?"; tables.len()].join(", ")
+    );
+    let mut statement = sqlx::query_scalar::<_, Option<i64>>(&sql);
+    for table in tables {
+        statement = statement.bind(*table);
+    }
+    statement.fetch_one(db).await.ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn session_count(cache: &ReadCache, db: &SqlitePool, key: &str, loads: &AtomicUsize) -> i64 {
+        cache
+            .get_or_load(db, CachedQuery::Sessions, key, || async {
+                loads.fetch_add(1, Ordering::SeqCst);
+                sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM chat_sessions").fetch_one(db).await.map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_results_are_kept_until_their_table_is_written() {
+        let db = test_pool().await;
+        let cache = ReadCache::default();
+        let loads = AtomicUsize::new(0);
+
+        assert_eq!(session_count(&cache, &db, "page-0", &loads).await, 0);
+        assert_eq!(session_count(&cache, &db, "page-0", &loads).await, 0);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // Writes to other tables leave the result alone
+        sqlx::query("INSERT INTO toolbox_profiles (name) VALUES ('web')").execute(&db).await.unwrap();
+        assert_eq!(session_count(&cache, &db, "page-0", &loads).await, 0);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        sqlx::query("INSERT INTO chat_sessions (id, context) VALUES ('s1', 'production')").execute(&db).await.unwrap();
+        assert_eq!(session_count(&cache, &db, "page-0", &loads).await, 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+        let stats = cache.stats(true);
+        assert_eq!((stats[0].hits, stats[0].misses, stats[0].entries), (2, 2, 1));
+        assert_eq!(stats[0].hit_rate, 0.5);
+        assert_eq!(cache.stats(false)[0].hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_and_missing_versions_table() {
+        let db = test_pool().await;
+        let cache = ReadCache::default();
+        let loads = AtomicUsize::new(0);
+        session_count(&cache, &db, "page-0", &loads).await;
+        cache.invalidate(CachedQuery::Sessions);
+        session_count(&cache, &db, "page-0", &loads).await;
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.stats(false)[0].invalidations, 1);
+
+        sqlx::query("DROP TABLE cache_versions").execute(&db).await.unwrap();
+        session_count(&cache, &db, "page-0", &loads).await;
+        session_count(&cache, &db, "page-0", &loads).await;
+        assert_eq!(loads.load(Ordering::SeqCst), 4);
+        assert_eq!(cache.stats(false)[0].bypassed, 2);
+    }
+}
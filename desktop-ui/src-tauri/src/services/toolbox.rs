@@ -0,0 +1,233 @@
+//! Toolbox profiles and which one is active
+
+use sqlx::SqlitePool;
+use std::future::Future;
+
+use crate::app_state::AppState;
+use crate::list_query::{ListQuery, ListResult};
+use crate::toolbox_profiles::{CreateToolboxProfileRequest, ToolboxProfile, ToolboxProfileStore, UpdateToolboxProfileRequest};
+use crate::trash::{Capture, TrashKind, TrashStore};
+
+use super::{CachedQuery, ReadCache, NO_DATABASE};
+
+/// Environment variables the active toolbox profile sets
+const TOOLBOX_ENV: [&str; 3] = ["AMP_TOOLBOX_PATHS", "AMP_ACTIVE_TOOLBOX_PROFILE", "AMP_ENABLE_TOOLBOXES"];
+
+/// Where the active toolbox profile and the environment it sets are kept
+pub trait ToolboxSettings: Send + Sync {
+    fn active_profile_id(&self) -> Option<i64>;
+    /// Record the active profile, setting each variable given a value and removing the others
+    fn apply(&self, active_profile_id: Option<i64>, env: &[(&str, Option<String>)]);
+    fn persist(&self) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+impl ToolboxSettings for AppState {
+    fn active_profile_id(&self) -> Option<i64> {
+        self.lock().unwrap().active_toolbox_profile_id
+    }
+
+    fn apply(&self, active_profile_id: Option<i64>, env: &[(&str, Option<String>)]) {
+        let mut state = self.lock().unwrap();
+        for (key, value) in env {
+            match value {
+                Some(value) => state.set_env(key.to_string(), value.clone()),
+                None => {
+                    state.amp_env.remove(*key);
+                }
+            }
+        }
+        state.active_toolbox_profile_id = active_profile_id;
+    }
+
+    fn persist(&self) -> impl Future<Output = Result<(), String>> + Send {
+        let to_save = self.lock().unwrap().clone();
+        async move { to_save.save().await }
+    }
+}
+
+pub struct ToolboxService<S> {
+    db: Option<SqlitePool>,
+    settings: S,
+    cache: Option<ReadCache>,
+}
+
+impl<S: ToolboxSettings> ToolboxService<S> {
+    pub fn new(db: Option<SqlitePool>, settings: S) -> Self {
+        Self { db, settings, cache: None }
+    }
+
+    /// Serve `list` from `cache`, dropping its results after this service's writes
+    pub fn with_cache(mut self, cache: ReadCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn invalidate(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(CachedQuery::ToolboxProfiles);
+        }
+    }
+
+    fn store(&self) -> Result<ToolboxProfileStore, String> {
+        self.db.clone().map(ToolboxProfileStore::new).ok_or_else(|| NO_DATABASE.to_string())
+    }
+
+    /// One page of profiles; none without a database
+    pub async fn list(&self, query: &ListQuery) -> Result<ListResult<ToolboxProfile>, String> {
+        let Some(db) = &self.db else {
+            return Ok(ListResult::empty());
+        };
+        let load = || async move { ToolboxProfileStore::new(db.clone()).query_profiles(query).await };
+        match &self.cache {
+            Some(cache) => {
+                let key = serde_json::to_string(query).map_err(|e| e.to_string())?;
+                cache.get_or_load(db, CachedQuery::ToolboxProfiles, &key, load).await
+            }
+            None => load().await,
+        }
+    }
+
+    pub async fn create(&self, request: CreateToolboxProfileRequest) -> Result<ToolboxProfile, String> {
+        let profile = self.store()?.create_profile(request).await.map_err(|e| e.to_string())?;
+        self.invalidate();
+        Ok(profile)
+    }
+
+    pub async fn update(&self, request: UpdateToolboxProfileRequest) -> Result<Option<ToolboxProfile>, String> {
+        let profile = self.store()?.update_profile(request).await.map_err(|e| e.to_string())?;
+        self.invalidate();
+        Ok(profile)
+    }
+
+    /// Move a profile and its paths to the trash. Returns whether it existed.
+    pub async fn delete(&self, id: i64) -> Result<bool, String> {
+        let db = self.db.as_ref().ok_or(NO_DATABASE)?;
+        let key = id.to_string();
+        let captures = [
+            Capture::new("toolbox_profiles", "id = ?", &key),
+            Capture::new("toolbox_profile_paths", "profile_id = ?", &key),
+        ];
+        let label = self.store()?
+            .get_profile(id)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|p| p.name)
+            .unwrap_or(key);
+        let trashed = TrashStore::new(db.clone())
+            .trash_rows(TrashKind::ToolboxProfile, &label, &captures, serde_json::json!({}))
+            .await
+            .map_err(|e| e.to_string())?;
+        self.invalidate();
+        Ok(trashed.is_some())
+    }
+
+    /// Make a profile the active one, pointing the CLI at its paths, or with
+    /// `None` stop using toolbox profiles; the settings are saved either way
+    pub async fn set_active(&self, profile_id: Option<i64>) -> Result<(), String> {
+        match profile_id {
+            Some(id) => {
+                let profile = self.store()?.get_profile(id).await.map_err(|e| e.to_string())?.ok_or("Profile not found")?;
+                let paths = profile.paths.join(if cfg!(windows) { ";" } else { ":" });
+                // Toolboxes are always enabled while a profile is active
+                let values = [Some(paths), Some(profile.name), Some("1".to_string())];
+                let env: Vec<(&str, Option<String>)> = TOOLBOX_ENV.into_iter().zip(values).collect();
+                self.settings.apply(Some(id), &env);
+            }
+            None => {
+                let env: Vec<(&str, Option<String>)> = TOOLBOX_ENV.iter().map(|key| (*key, None)).collect();
+                self.settings.apply(None, &env);
+            }
+        }
+        self.settings.persist().await
+    }
+
+    /// The active profile; none without a database
+    pub async fn active(&self) -> Result<Option<ToolboxProfile>, String> {
+        match (self.settings.active_profile_id(), &self.db) {
+            (Some(id), Some(db)) => ToolboxProfileStore::new(db.clone()).get_profile(id).await.map_err(|e| e.to_string()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Turn single toolbox paths of earlier versions into profiles
+    pub async fn migrate(&self) -> Result<(), String> {
+        self.store()?.migrate_single_paths().await.map_err(|e| e.to_string())?;
+        self.invalidate();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeSettings {
+        active: Mutex<Option<i64>>,
+        env: Mutex<HashMap<String, String>>,
+        saves: Mutex<usize>,
+    }
+
+    impl ToolboxSettings for &FakeSettings {
+        fn active_profile_id(&self) -> Option<i64> {
+            *self.active.lock().unwrap()
+        }
+
+        fn apply(&self, active_profile_id: Option<i64>, env: &[(&str, Option<String>)]) {
+            let mut vars = self.env.lock().unwrap();
+            for (key, value) in env {
+                match value {
+                    Some(value) => vars.insert(key.to_string(), value.clone()),
+                    None => vars.remove(*key),
+                };
+            }
+            *self.active.lock().unwrap() = active_profile_id;
+        }
+
+        fn persist(&self) -> impl Future<Output = Result<(), String>> + Send {
+            *self.saves.lock().unwrap() += 1;
+            std::future::ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_active_profile_updates_and_saves_settings() {
+        let settings = FakeSettings::default();
+        let service = ToolboxService::new(Some(test_pool().await), &settings);
+        let profile = service
+            .create(CreateToolboxProfileRequest { name: "web".to_string(), paths: vec!["/tools/a".to_string(), "/tools/b".to_string()] })
+            .await
+            .unwrap();
+
+        service.set_active(Some(profile.id)).await.unwrap();
+        {
+            let env = settings.env.lock().unwrap();
+            let separator = if cfg!(windows) { ";" } else { ":" };
+            assert_eq!(env["AMP_TOOLBOX_PATHS"], format!("/tools/a{}/tools/b", separator));
+            assert_eq!(env["AMP_ACTIVE_TOOLBOX_PROFILE"], "web");
+            assert_eq!(env["AMP_ENABLE_TOOLBOXES"], "1");
+        }
+        assert_eq!(service.active().await.unwrap().map(|p| p.name).as_deref(), Some("web"));
+
+        service.set_active(None).await.unwrap();
+        assert!(settings.env.lock().unwrap().is_empty());
+        assert!(service.active().await.unwrap().is_none());
+        assert_eq!(*settings.saves.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_database_or_profile_leaves_settings_alone() {
+        let settings = FakeSettings::default();
+        let offline = ToolboxService::new(None, &settings);
+        assert!(offline.list(&ListQuery::default()).await.unwrap().items.is_empty());
+        assert_eq!(offline.set_active(Some(1)).await.unwrap_err(), NO_DATABASE);
+
+        let service = ToolboxService::new(Some(test_pool().await), &settings);
+        assert_eq!(service.set_active(Some(42)).await.unwrap_err(), "Profile not found");
+        assert_eq!(*settings.active.lock().unwrap(), None);
+        assert_eq!(*settings.saves.lock().unwrap(), 0);
+    }
+}
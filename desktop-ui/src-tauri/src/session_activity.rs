@@ -0,0 +1,297 @@
+//! Activity heartbeat and stale session detection
+//!
+//! Stream output and user input touch a session's last-activity time. A
+//! background monitor compares that against `StaleSessionConfig` for every live
+//! process and emits `session_stale` once per idle period. With `auto_detach`
+//! stale thread processes are stopped; threads keep their history in the
+//! database, so `thread_attach` brings a detached thread back. With
+//! `auto_suspend` any other stale process is paused in place instead (see
+//! `process_suspend`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::{AppState, StaleSessionConfig};
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+use crate::session_commands::AmpSessionMap;
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityState {
+    Active,
+    Stale,
+    /// Process paused with `session_suspend`, still in the session map
+    Suspended,
+    /// Process stopped; the thread re-spawns from history on attach
+    Detached,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionActivity {
+    pub session_id: String,
+    /// Unix time in milliseconds
+    pub last_activity: i64,
+    pub idle_secs: u64,
+    pub state: ActivityState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStaleEvent {
+    pub session_id: String,
+    pub idle_secs: u64,
+    pub suspended: bool,
+    pub detached: bool,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+#[derive(Clone, Default)]
+pub struct ActivityTracker {
+    inner: Arc<Mutex<HashMap<String, (i64, ActivityState)>>>,
+}
+
+impl ActivityTracker {
+    /// Record activity. A suspended session stays suspended: output still buffered
+    /// in its pipes can arrive after the process was paused.
+    pub fn touch(&self, session_id: &str, now_ms: i64) {
+        let mut records = self.inner.lock().unwrap();
+        let record = records.entry(session_id.to_string()).or_insert((now_ms, ActivityState::Active));
+        record.0 = now_ms;
+        if record.1 != ActivityState::Suspended {
+            record.1 = ActivityState::Active;
+        }
+    }
+
+    pub fn state(&self, session_id: &str) -> Option<ActivityState> {
+        self.inner.lock().unwrap().get(session_id).map(|(_, state)| *state)
+    }
+
+    /// Set a session's state without counting it as activity
+    pub fn set_state(&self, session_id: &str, state: ActivityState, now_ms: i64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .entry(session_id.to_string())
+            .or_insert((now_ms, state))
+            .1 = state;
+    }
+
+    /// Flag live sessions idle for at least `threshold_ms`. Returns only the ones that
+    /// just became stale, with their idle time. Records for sessions that are no longer
+    /// live are dropped unless they were detached.
+    pub fn sweep(&self, live: &[String], now_ms: i64, threshold_ms: i64) -> Vec<(String, i64)> {
+        let mut records = self.inner.lock().unwrap();
+        records.retain(|id, (_, state)| live.contains(id) || *state == ActivityState::Detached);
+
+        let mut newly_stale = Vec::new();
+        for id in live {
+            // A session seen for the first time starts its idle clock now
+            let (last, state) = records.entry(id.clone()).or_insert((now_ms, ActivityState::Active));
+            let idle = now_ms - *last;
+            if *state == ActivityState::Active && idle >= threshold_ms {
+                *state = ActivityState::Stale;
+                newly_stale.push((id.clone(), idle));
+            }
+        }
+        newly_stale
+    }
+
+    pub fn snapshot(&self, now_ms: i64) -> Vec<SessionActivity> {
+        let mut sessions: Vec<SessionActivity> = self
+            .inner
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (last, state))| SessionActivity {
+                session_id: id.clone(),
+                last_activity: *last,
+                idle_secs: ((now_ms - *last).max(0) / 1000) as u64,
+                state: *state,
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_activity));
+        sessions
+    }
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Record stream output or input for a session
+pub fn touch_session(app_handle: &AppHandle, session_id: &str) {
+    if let Some(tracker) = app_handle.try_state::<ActivityTracker>() {
+        tracker.touch(session_id, now_ms());
+    }
+}
+
+/// Whether a session id belongs to a thread, which can be re-attached from its history
+async fn is_reattachable(app_handle: &AppHandle, session_id: &str) -> bool {
+    let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+        return false;
+    };
+    let db = profile_manager.db_pool.read().await;
+    let Some(db) = db.as_ref() else {
+        return false;
+    };
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM threads WHERE id = ? AND archived_at IS NULL")
+        .bind(session_id)
+        .fetch_one(db)
+        .await
+        .map(|n| n > 0)
+        .unwrap_or(false)
+}
+
+async fn check_stale_sessions(app_handle: &AppHandle) {
+    let (Some(tracker), Some(amp_sessions), Some(app_state)) = (
+        app_handle.try_state::<ActivityTracker>(),
+        app_handle.try_state::<AmpSessionMap>(),
+        app_handle.try_state::<AppState>(),
+    ) else {
+        return;
+    };
+    let config = app_state.lock().unwrap().stale_sessions.clone();
+    let live: Vec<String> = amp_sessions.ids();
+
+    let threshold_ms = (config.idle_threshold_secs as i64).saturating_mul(1000);
+    for (session_id, idle_ms) in tracker.sweep(&live, now_ms(), threshold_ms) {
+        let (mut suspended, mut detached) = (false, false);
+        if config.auto_detach && is_reattachable(app_handle, &session_id).await {
+            // Dropping the session kills its process
+            if amp_sessions.remove(&session_id).is_some() {
+                tracker.set_state(&session_id, ActivityState::Detached, now_ms());
+                detached = true;
+                log::info!("Detached stale thread {} after {}s idle", session_id, idle_ms / 1000);
+            }
+        }
+        if !detached && config.auto_suspend {
+            if let Some(session) = amp_sessions.get(&session_id) {
+                let session = session.lock().await;
+                match crate::process_suspend::suspend_session(&session, &tracker, &session_id) {
+                    Ok(()) => {
+                        suspended = true;
+                        log::info!("Suspended stale session {} after {}s idle", session_id, idle_ms / 1000);
+                    }
+                    Err(e) => log::warn!("Failed to suspend stale session {}: {}", session_id, e),
+                }
+            }
+        }
+        let key = match (suspended, detached) {
+            (_, true) => "notify-session-stale-detached",
+            (true, _) => "notify-session-stale-suspended",
+            _ => "notify-session-stale",
+        };
+        let label = crate::session_codes::code_for(&session_id).unwrap_or_else(|| session_id.clone());
+        let message = tr!(key, session = label, minutes = idle_ms / 60_000);
+        let _ = app_handle.emit("session_stale", SessionStaleEvent {
+            session_id,
+            idle_secs: (idle_ms / 1000) as u64,
+            suspended,
+            detached,
+            message,
+        });
+    }
+}
+
+/// Run the stale session monitor for the lifetime of the app
+pub fn spawn_monitor(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            check_stale_sessions(&app_handle).await;
+            crate::session_priority::notify_waiting(&app_handle);
+        }
+    });
+}
+
+/// Last activity and stale/suspended/detached state for tracked sessions, most recent first
+#[tauri::command]
+pub async fn get_session_activity(tracker: State<'_, ActivityTracker>) -> Result<Vec<SessionActivity>, String> {
+    Ok(tracker.snapshot(now_ms()))
+}
+
+#[tauri::command]
+pub async fn get_stale_session_config(app_state: State<'_, AppState>) -> Result<StaleSessionConfig, String> {
+    Ok(app_state.lock().unwrap().stale_sessions.clone())
+}
+
+#[tauri::command]
+pub async fn set_stale_session_config(
+    config: StaleSessionConfig,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    if config.idle_threshold_secs == 0 {
+        return Err("Idle threshold must be greater than zero".to_string());
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.stale_sessions = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_go_stale_once_per_idle_period() {
+        let tracker = ActivityTracker::default();
+        let live = vec!["a".to_string(), "b".to_string()];
+        tracker.touch("a", 0);
+        tracker.touch("b", 0);
+
+        assert!(tracker.sweep(&live, 500, 1000).is_empty());
+        tracker.touch("b", 900);
+        let stale = tracker.sweep(&live, 1200, 1000);
+        assert_eq!(stale, vec![("a".to_string(), 1200)]);
+        // Already flagged, so not reported again
+        assert!(tracker.sweep(&live, 1500, 1000).is_empty());
+
+        // Activity resets the flag
+        tracker.touch("a", 1600);
+        assert!(tracker.sweep(&live, 2000, 1000).iter().all(|(id, _)| id == "b"));
+        assert_eq!(tracker.sweep(&live, 2700, 1000), vec![("a".to_string(), 1100)]);
+    }
+
+    #[test]
+    fn test_untracked_and_finished_sessions() {
+        let tracker = ActivityTracker::default();
+        // A live session with no activity yet starts its clock on first sweep
+        assert!(tracker.sweep(&["new".to_string()], 10_000, 1000).is_empty());
+        assert_eq!(tracker.sweep(&["new".to_string()], 11_000, 1000).len(), 1);
+
+        tracker.touch("gone", 0);
+        tracker.touch("parked", 0);
+        tracker.set_state("parked", ActivityState::Detached, 0);
+        tracker.sweep(&[], 20_000, 1000);
+
+        let states: Vec<(String, ActivityState)> =
+            tracker.snapshot(20_000).into_iter().map(|s| (s.session_id, s.state)).collect();
+        assert_eq!(states, vec![("parked".to_string(), ActivityState::Detached)]);
+    }
+
+    #[test]
+    fn test_touch_keeps_suspended_sessions_suspended() {
+        let tracker = ActivityTracker::default();
+        let live = vec!["a".to_string()];
+        tracker.set_state("a", ActivityState::Suspended, 0);
+        tracker.touch("a", 5000);
+        assert_eq!(tracker.state("a"), Some(ActivityState::Suspended));
+        // Suspended sessions are not flagged stale again
+        assert!(tracker.sweep(&live, 100_000, 1000).is_empty());
+
+        tracker.set_state("a", ActivityState::Active, 100_000);
+        tracker.touch("a", 100_000);
+        assert_eq!(tracker.state("a"), Some(ActivityState::Active));
+        assert_eq!(tracker.sweep(&live, 102_000, 1000).len(), 1);
+    }
+}
@@ -0,0 +1,590 @@
+//! Session outcome classification and aggregate statistics
+//!
+//! When a chat session or thread finishes, its stream signals (whether an Amp
+//! `result` event arrived, whether it reported an error) are recorded together with
+//! the agent mode and toolbox profile it ran with. An outcome of succeeded, failed
+//! or abandoned is then derived from those signals, tags applied by post-run script
+//! hooks and any explicit user verdict, using rules stored in `ui_state`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use tauri::{AppHandle, Manager, State};
+
+use crate::profile_auth::ProfileManager;
+use crate::script_hooks::{HookEvent, ScriptHookStore};
+use crate::thread_feedback::FeedbackStore;
+
+const CONFIG_KEY: &str = "session_classification_config";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Succeeded,
+    Failed,
+    Abandoned,
+}
+
+impl SessionOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionOutcome::Succeeded => "succeeded",
+            SessionOutcome::Failed => "failed",
+            SessionOutcome::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "succeeded" => Some(SessionOutcome::Succeeded),
+            "failed" => Some(SessionOutcome::Failed),
+            "abandoned" => Some(SessionOutcome::Abandoned),
+            _ => None,
+        }
+    }
+}
+
+/// Rules used to turn signals into an outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassificationConfig {
+    /// Session tags (e.g. set by a post-run hook) that mark a success
+    pub success_tags: Vec<String>,
+    /// Session tags that mark a failure; checked before success tags
+    pub failure_tags: Vec<String>,
+    /// Ratings at or above this value count as success
+    pub min_success_rating: i64,
+    /// Ratings at or below this value count as failure
+    pub max_failure_rating: i64,
+    /// Treat an error reported by the Amp stream as a failure
+    pub error_means_failed: bool,
+}
+
+impl Default for ClassificationConfig {
+    fn default() -> Self {
+        Self {
+            success_tags: vec!["succeeded".to_string(), "pass".to_string()],
+            failure_tags: vec!["failed".to_string(), "fail".to_string()],
+            min_success_rating: 4,
+            max_failure_rating: 2,
+            error_means_failed: true,
+        }
+    }
+}
+
+/// Everything known about a finished session that can influence its outcome
+#[derive(Debug, Clone, Default)]
+pub struct OutcomeSignals {
+    pub explicit: Option<SessionOutcome>,
+    pub user_rating: Option<i64>,
+    pub tags: Vec<String>,
+    pub errored: bool,
+    pub received_result: bool,
+}
+
+/// Classify a session. Signals are checked from most to least deliberate:
+/// explicit verdict, user rating, hook tags, stream error, then whether the
+/// stream finished with a result at all. Returns the outcome and the signal that decided it.
+pub fn classify(signals: &OutcomeSignals, config: &ClassificationConfig) -> (SessionOutcome, &'static str) {
+    if let Some(outcome) = signals.explicit {
+        return (outcome, "explicit");
+    }
+
+    if let Some(rating) = signals.user_rating {
+        if rating >= config.min_success_rating {
+            return (SessionOutcome::Succeeded, "rating");
+        }
+        if rating <= config.max_failure_rating {
+            return (SessionOutcome::Failed, "rating");
+        }
+    }
+
+    let has_tag = |wanted: &[String]| signals.tags.iter().any(|t| wanted.iter().any(|w| w.eq_ignore_ascii_case(t)));
+    if has_tag(&config.failure_tags) {
+        return (SessionOutcome::Failed, "hook_tag");
+    }
+    if has_tag(&config.success_tags) {
+        return (SessionOutcome::Succeeded, "hook_tag");
+    }
+
+    if signals.errored && config.error_means_failed {
+        return (SessionOutcome::Failed, "error_status");
+    }
+
+    if signals.received_result {
+        (SessionOutcome::Succeeded, "completed")
+    } else {
+        (SessionOutcome::Abandoned, "no_result")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionOutcomeRecord {
+    pub session_id: String,
+    pub outcome: String,
+    pub decided_by: String,
+    pub agent_mode: Option<String>,
+    pub toolbox_profile_id: Option<i64>,
+    pub errored: bool,
+    pub received_result: bool,
+    pub explicit_outcome: Option<String>,
+    pub created_at: String,
+    pub classified_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutcomeStats {
+    /// Agent mode or toolbox profile id; `None` groups sessions without one
+    pub key: Option<String>,
+    pub total: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub abandoned: i64,
+    pub success_rate: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsGrouping {
+    AgentMode,
+    ToolboxProfile,
+}
+
+pub struct SessionOutcomeStore {
+    db: SqlitePool,
+}
+
+impl SessionOutcomeStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    pub async fn load_config(&self) -> Result<ClassificationConfig, sqlx::Error> {
+        let value = sqlx::query_scalar::<_, String>("SELECT value FROM ui_state WHERE key = ?")
+            .bind(CONFIG_KEY)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(value
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default())
+    }
+
+    pub async fn save_config(&self, config: &ClassificationConfig) -> Result<(), sqlx::Error> {
+        let value = serde_json::to_string(config).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        sqlx::query("INSERT OR REPLACE INTO ui_state (key, value) VALUES (?, ?)")
+            .bind(CONFIG_KEY)
+            .bind(value)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record the stream signals of a finished session, keeping any explicit verdict
+    pub async fn record_completion(
+        &self,
+        session_id: &str,
+        agent_mode: Option<&str>,
+        toolbox_profile_id: Option<i64>,
+        errored: bool,
+        received_result: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO session_outcomes (session_id, outcome, decided_by, agent_mode, toolbox_profile_id, errored, received_result)
+             VALUES (?, 'abandoned', 'pending', ?, ?, ?, ?)
+             ON CONFLICT(session_id) DO UPDATE SET
+                agent_mode = excluded.agent_mode,
+                toolbox_profile_id = excluded.toolbox_profile_id,
+                errored = excluded.errored,
+                received_result = excluded.received_result"
+        )
+        .bind(session_id)
+        .bind(agent_mode)
+        .bind(toolbox_profile_id)
+        .bind(errored)
+        .bind(received_result)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_explicit_outcome(&self, session_id: &str, outcome: Option<SessionOutcome>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE session_outcomes SET explicit_outcome = ? WHERE session_id = ?")
+            .bind(outcome.map(|o| o.as_str()))
+            .bind(session_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_outcome(&self, session_id: &str) -> Result<Option<SessionOutcomeRecord>, sqlx::Error> {
+        sqlx::query_as::<_, SessionOutcomeRecord>(
+            "SELECT session_id, outcome, decided_by, agent_mode, toolbox_profile_id, errored, received_result,
+                    explicit_outcome, created_at, classified_at
+             FROM session_outcomes WHERE session_id = ?"
+        )
+        .bind(session_id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    /// Recompute and persist the outcome of a recorded session
+    pub async fn reclassify(&self, session_id: &str, config: &ClassificationConfig) -> Result<Option<SessionOutcomeRecord>, sqlx::Error> {
+        let Some(record) = self.get_outcome(session_id).await? else { return Ok(None) };
+
+        let signals = OutcomeSignals {
+            explicit: record.explicit_outcome.as_deref().and_then(SessionOutcome::parse),
+            user_rating: FeedbackStore::new(self.db.clone()).latest_rating(session_id).await?,
+            tags: ScriptHookStore::new(self.db.clone()).list_session_tags(session_id).await?,
+            errored: record.errored,
+            received_result: record.received_result,
+        };
+        let (outcome, decided_by) = classify(&signals, config);
+
+        sqlx::query(
+            "UPDATE session_outcomes SET outcome = ?, decided_by = ?, classified_at = (datetime('now', 'utc') || 'Z')
+             WHERE session_id = ?"
+        )
+        .bind(outcome.as_str())
+        .bind(decided_by)
+        .bind(session_id)
+        .execute(&self.db)
+        .await?;
+
+        self.get_outcome(session_id).await
+    }
+
+    pub async fn stats(&self, grouping: StatsGrouping) -> Result<Vec<OutcomeStats>, sqlx::Error> {
+        let column = match grouping {
+            StatsGrouping::AgentMode => "agent_mode",
+            StatsGrouping::ToolboxProfile => "CAST(toolbox_profile_id AS TEXT)",
+        };
+
+        let rows = sqlx::query_as::<_, (Option<String>, i64, i64, i64, i64)>(&format!(
+            "SELECT {column},
+                    COUNT(*),
+                    SUM(CASE WHEN outcome = 'succeeded' THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN outcome = 'failed' THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN outcome = 'abandoned' THEN 1 ELSE 0 END)
+             FROM session_outcomes WHERE decided_by != 'pending'
+             GROUP BY {column} ORDER BY COUNT(*) DESC"
+        ))
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(key, total, succeeded, failed, abandoned)| OutcomeStats {
+                key,
+                total,
+                succeeded,
+                failed,
+                abandoned,
+                success_rate: if total > 0 { succeeded as f64 / total as f64 } else { 0.0 },
+            })
+            .collect())
+    }
+}
+
+/// Agent mode and toolbox profile a session ran with, looked up from threads
+/// (new architecture) first and chat sessions second
+async fn session_context(db: &SqlitePool, session_id: &str) -> (Option<String>, Option<i64>) {
+    if let Ok(Some(row)) = sqlx::query_as::<_, (Option<String>, Option<i64>)>(
+        "SELECT t.agent_mode, s.profile_id FROM threads t JOIN sessions s ON s.id = t.session_id WHERE t.id = ?"
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+    {
+        return row;
+    }
+
+    sqlx::query_as::<_, (Option<String>, Option<i64>)>(
+        "SELECT agent_mode, toolbox_profile_id FROM chat_sessions WHERE id = ?"
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or((None, None))
+}
+
+/// Track the stream-level signals used for classification
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StreamSignals {
+    pub errored: bool,
+    pub received_result: bool,
+}
+
+impl StreamSignals {
+    pub fn observe(&mut self, event: &serde_json::Value) {
+        if event.get("type").and_then(|t| t.as_str()) != Some("result") {
+            return;
+        }
+        self.received_result = true;
+        let is_error = event.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+        let error_subtype = event
+            .get("subtype")
+            .and_then(|v| v.as_str())
+            .is_some_and(|s| s.starts_with("error"));
+        if is_error || error_subtype {
+            self.errored = true;
+        }
+    }
+}
+
+/// Run post-run hooks for a finished session, then record and classify it
+/// The session behind a completion: a chat session's own ID, a thread's session
+pub async fn completed_session(db: &SqlitePool, id: &str, kind: &str) -> Option<String> {
+    match kind {
+        "thread" => sqlx::query_scalar::<_, String>("SELECT session_id FROM threads WHERE id = ?")
+            .bind(id)
+            .fetch_optional(db)
+            .await
+            .ok()
+            .flatten(),
+        _ => Some(id.to_string()),
+    }
+}
+
+pub fn spawn_completion(app_handle: AppHandle, session_id: String, kind: &'static str, signals: StreamSignals) {
+    tokio::spawn(async move {
+        crate::script_hooks::dispatch_hook_event(
+            &app_handle,
+            &session_id,
+            HookEvent::SessionCompleted,
+            serde_json::json!({
+                "session_id": session_id,
+                "kind": kind,
+                "errored": signals.errored,
+                "received_result": signals.received_result,
+            }),
+        )
+        .await;
+
+        let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else { return };
+        let Some(db) = profile_manager.db_pool.read().await.clone() else { return };
+
+        let (agent_mode, toolbox_profile_id) = session_context(&db, &session_id).await;
+        let store = SessionOutcomeStore::new(db.clone());
+        let result = async {
+            store
+                .record_completion(&session_id, agent_mode.as_deref(), toolbox_profile_id, signals.errored, signals.received_result)
+                .await?;
+            let config = store.load_config().await?;
+            store.reclassify(&session_id, &config).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            log::warn!("Failed to classify session {}: {}", session_id, e);
+        }
+
+        crate::staging_sync::run_on_completion(&app_handle, &db, &session_id, kind).await;
+        crate::stack_detection::run_on_completion(&app_handle, &db, &session_id, kind).await;
+    });
+}
+
+#[tauri::command]
+pub async fn get_session_outcome(
+    session_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Option<SessionOutcomeRecord>, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    SessionOutcomeStore::new(db.clone())
+        .get_outcome(&session_id)
+        .await
+        .map_err(|e| format!("Failed to get session outcome: {}", e))
+}
+
+/// Explicitly mark a session's outcome, or clear the override with `None`
+#[tauri::command]
+pub async fn set_session_outcome(
+    session_id: String,
+    outcome: Option<SessionOutcome>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SessionOutcomeRecord, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = SessionOutcomeStore::new(db.clone());
+
+    if !store.set_explicit_outcome(&session_id, outcome).await.map_err(|e| format!("Failed to set session outcome: {}", e))? {
+        return Err(format!("Session {} has not completed yet", session_id));
+    }
+
+    let config = store.load_config().await.map_err(|e| format!("Failed to load classification config: {}", e))?;
+    store
+        .reclassify(&session_id, &config)
+        .await
+        .map_err(|e| format!("Failed to classify session: {}", e))?
+        .ok_or_else(|| format!("Session {} not found", session_id))
+}
+
+#[tauri::command]
+pub async fn get_session_outcome_stats(
+    group_by: StatsGrouping,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<OutcomeStats>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    SessionOutcomeStore::new(db.clone())
+        .stats(group_by)
+        .await
+        .map_err(|e| format!("Failed to get outcome stats: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_classification_config(
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ClassificationConfig, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    SessionOutcomeStore::new(db.clone())
+        .load_config()
+        .await
+        .map_err(|e| format!("Failed to load classification config: {}", e))
+}
+
+/// Save classification rules and re-run them over every recorded session
+#[tauri::command]
+pub async fn set_classification_config(
+    config: ClassificationConfig,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<usize, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = SessionOutcomeStore::new(db.clone());
+
+    store.save_config(&config).await.map_err(|e| format!("Failed to save classification config: {}", e))?;
+
+    let session_ids = sqlx::query_scalar::<_, String>("SELECT session_id FROM session_outcomes")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to list session outcomes: {}", e))?;
+
+    for session_id in &session_ids {
+        store
+            .reclassify(session_id, &config)
+            .await
+            .map_err(|e| format!("Failed to reclassify session {}: {}", session_id, e))?;
+    }
+
+    Ok(session_ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[test]
+    fn test_classify_signal_precedence() {
+        let config = ClassificationConfig::default();
+
+        let mut signals = OutcomeSignals { received_result: true, ..Default::default() };
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Succeeded, "completed"));
+
+        signals.errored = true;
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Failed, "error_status"));
+
+        signals.tags = vec!["PASS".to_string()];
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Succeeded, "hook_tag"));
+
+        signals.user_rating = Some(1);
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Failed, "rating"));
+
+        signals.explicit = Some(SessionOutcome::Abandoned);
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Abandoned, "explicit"));
+
+        assert_eq!(classify(&OutcomeSignals::default(), &config), (SessionOutcome::Abandoned, "no_result"));
+    }
+
+    #[test]
+    fn test_neutral_rating_falls_through() {
+        let config = ClassificationConfig::default();
+        let signals = OutcomeSignals { user_rating: Some(3), received_result: true, ..Default::default() };
+        assert_eq!(classify(&signals, &config), (SessionOutcome::Succeeded, "completed"));
+    }
+
+    #[test]
+    fn test_stream_signals() {
+        let mut signals = StreamSignals::default();
+        signals.observe(&serde_json::json!({ "type": "assistant" }));
+        assert!(!signals.received_result);
+
+        signals.observe(&serde_json::json!({ "type": "result", "subtype": "error_during_execution" }));
+        assert!(signals.received_result);
+        assert!(signals.errored);
+    }
+
+    #[tokio::test]
+    async fn test_record_reclassify_and_stats() {
+        let pool = test_pool().await;
+        let store = SessionOutcomeStore::new(pool.clone());
+        let config = ClassificationConfig::default();
+
+        store.record_completion("s1", Some("geppetto:main"), Some(1), false, true).await.unwrap();
+        store.record_completion("s2", Some("geppetto:main"), Some(1), true, true).await.unwrap();
+        store.record_completion("s3", None, None, false, false).await.unwrap();
+        for id in ["s1", "s2", "s3"] {
+            store.reclassify(id, &config).await.unwrap();
+        }
+
+        assert_eq!(store.get_outcome("s2").await.unwrap().unwrap().outcome, "failed");
+        assert_eq!(store.get_outcome("s3").await.unwrap().unwrap().outcome, "abandoned");
+
+        // A hook tag overrides the error status
+        ScriptHookStore::new(pool).tag_session("s2", "succeeded").await.unwrap();
+        let record = store.reclassify("s2", &config).await.unwrap().unwrap();
+        assert_eq!(record.outcome, "succeeded");
+        assert_eq!(record.decided_by, "hook_tag");
+
+        let stats = store.stats(StatsGrouping::AgentMode).await.unwrap();
+        let geppetto = stats.iter().find(|s| s.key.as_deref() == Some("geppetto:main")).unwrap();
+        assert_eq!((geppetto.total, geppetto.succeeded), (2, 2));
+        assert_eq!(geppetto.success_rate, 1.0);
+
+        let by_profile = store.stats(StatsGrouping::ToolboxProfile).await.unwrap();
+        assert!(by_profile.iter().any(|s| s.key.as_deref() == Some("1") && s.total == 2));
+    }
+
+    #[tokio::test]
+    async fn test_rating_overrides_stream_signals() {
+        let pool = test_pool().await;
+        let store = SessionOutcomeStore::new(pool.clone());
+        let config = ClassificationConfig::default();
+
+        store.record_completion("t1", Some("bolt"), None, false, true).await.unwrap();
+        FeedbackStore::new(pool).add_feedback("t1", 1, Some("wrong file"), "hash", Some("bolt")).await.unwrap();
+
+        let record = store.reclassify("t1", &config).await.unwrap().unwrap();
+        assert_eq!(record.outcome, "failed");
+        assert_eq!(record.decided_by, "rating");
+    }
+
+    #[tokio::test]
+    async fn test_explicit_outcome_and_config_round_trip() {
+        let store = SessionOutcomeStore::new(test_pool().await);
+
+        assert!(!store.set_explicit_outcome("missing", Some(SessionOutcome::Failed)).await.unwrap());
+
+        store.record_completion("s1", None, None, false, true).await.unwrap();
+        store.set_explicit_outcome("s1", Some(SessionOutcome::Failed)).await.unwrap();
+        let record = store.reclassify("s1", &store.load_config().await.unwrap()).await.unwrap().unwrap();
+        assert_eq!(record.outcome, "failed");
+        assert_eq!(record.decided_by, "explicit");
+
+        let config = ClassificationConfig { min_success_rating: 5, ..Default::default() };
+        store.save_config(&config).await.unwrap();
+        assert_eq!(store.load_config().await.unwrap().min_success_rating, 5);
+    }
+}
@@ -0,0 +1,139 @@
+//! Short human-friendly session codes
+//!
+//! Session IDs are UUIDs, which are unreadable in branch names, logs and the UI.
+//! Every session also gets a short code such as `0kq3fa-7hx2`: the creation time
+//! in base32 followed by random digits, in the Crockford alphabet so codes never
+//! contain easily confused letters. Codes are unique in `sessions.short_code`; a
+//! collision is retried with a fresh random part that grows on repeated clashes.
+//! Known codes are cached in memory, and `resolve` lets any command that takes a
+//! session ID accept a code instead.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use sqlx::SqlitePool;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::app_state::SessionCodeConfig;
+
+/// Crockford base32, lowercase: no i, l, o or u
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+const MAX_ATTEMPTS: usize = 8;
+
+static CODE_BY_ID: Lazy<DashMap<String, String>> = Lazy::new(DashMap::new);
+static ID_BY_CODE: Lazy<DashMap<String, String>> = Lazy::new(DashMap::new);
+
+fn base32(mut value: u128, digits: usize) -> String {
+    let mut out = vec![b'0'; digits];
+    for slot in out.iter_mut().rev() {
+        *slot = ALPHABET[(value & 31) as usize];
+        value >>= 5;
+    }
+    String::from_utf8(out).unwrap()
+}
+
+/// A new code for a session created at `created_at` (unix seconds)
+pub fn generate(config: &SessionCodeConfig, created_at: u64, random_chars: usize) -> String {
+    let random_chars = random_chars.clamp(1, 25);
+    let timestamp = base32(created_at as u128, config.timestamp_chars.clamp(1, 12));
+    format!("{}-{}", timestamp, base32(Uuid::new_v4().as_u128(), random_chars))
+}
+
+/// Canonical form of a typed code: lowercase, with the letters Crockford
+/// base32 leaves out read as the digits they resemble
+pub fn normalize(input: &str) -> String {
+    input
+        .trim()
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'o' => '0',
+            'i' | 'l' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+fn register(session_id: &str, code: &str) {
+    CODE_BY_ID.insert(session_id.to_string(), code.to_string());
+    ID_BY_CODE.insert(code.to_string(), session_id.to_string());
+}
+
+/// Short code of a session, if it has one
+pub fn code_for(session_id: &str) -> Option<String> {
+    CODE_BY_ID.get(session_id).map(|code| code.clone())
+}
+
+/// Session ID for a session ID or short code; unknown input is returned as-is
+pub fn resolve(input: &str) -> String {
+    ID_BY_CODE.get(&normalize(input)).map(|id| id.clone()).unwrap_or_else(|| input.to_string())
+}
+
+/// Give a session a unique short code, retrying on collisions
+pub async fn assign(db: &SqlitePool, session_id: &str, config: &SessionCodeConfig) -> Result<String, String> {
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    for attempt in 0..MAX_ATTEMPTS {
+        // Every second collision adds a random digit
+        let code = generate(config, created_at, config.random_chars + attempt / 2);
+        match sqlx::query("UPDATE sessions SET short_code = ? WHERE id = ?").bind(&code).bind(session_id).execute(db).await {
+            Ok(result) if result.rows_affected() == 0 => return Err(format!("Session {} not found", session_id)),
+            Ok(_) => {
+                register(session_id, &code);
+                return Ok(code);
+            }
+            Err(e) if e.to_string().contains("UNIQUE") => {
+                log::debug!("Session code {} already taken, retrying", code);
+            }
+            Err(e) => return Err(format!("Failed to store session code: {}", e)),
+        }
+    }
+    Err(format!("Could not find a free session code after {} attempts", MAX_ATTEMPTS))
+}
+
+/// Cache existing codes and give sessions created before codes existed one
+pub async fn load(db: &SqlitePool, config: &SessionCodeConfig) -> Result<usize, String> {
+    let rows = sqlx::query_as::<_, (String, Option<String>)>("SELECT id, short_code FROM sessions")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to load session codes: {}", e))?;
+    let mut assigned = 0;
+    for (session_id, code) in rows {
+        match code {
+            Some(code) => register(&session_id, &code),
+            None => {
+                assign(db, &session_id, config).await?;
+                assigned += 1;
+            }
+        }
+    }
+    Ok(assigned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_codes_are_assigned_and_resolved() {
+        let pool = test_pool().await;
+        for id in ["code-s1", "code-s2"] {
+            sqlx::query("INSERT INTO sessions (id, title) VALUES (?, 'Session')").bind(id).execute(&pool).await.unwrap();
+        }
+
+        let config = SessionCodeConfig::default();
+        assert_eq!(load(&pool, &config).await.unwrap(), 2);
+        assert_eq!(load(&pool, &config).await.unwrap(), 0);
+
+        let code = code_for("code-s1").unwrap();
+        assert_eq!(code.len(), config.timestamp_chars + 1 + config.random_chars);
+        assert!(code.chars().all(|c| c == '-' || ALPHABET.contains(&(c as u8))));
+        assert_ne!(code_for("code-s2").unwrap(), code);
+        assert_eq!(resolve(&code.to_uppercase()), "code-s1");
+        assert_eq!(resolve("code-s2"), "code-s2");
+
+        // A taken code is rejected by the unique index
+        let taken = sqlx::query("UPDATE sessions SET short_code = ? WHERE id = 'code-s2'").bind(&code).execute(&pool).await;
+        assert!(taken.unwrap_err().to_string().contains("UNIQUE"));
+        assert!(assign(&pool, "missing", &config).await.is_err());
+    }
+}
@@ -1,8 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 use std::fs;
 use std::env;
 use tauri::{AppHandle, State, Emitter};
@@ -10,7 +8,10 @@ use tokio::process::{Command, Child};
 use tokio::io::{AsyncBufReadExt, BufReader, BufWriter, AsyncWriteExt};
 use serde_json::Value;
 use uuid::Uuid;
-use crate::toolbox_profiles::{ToolboxProfile, ToolboxProfileStore, CreateToolboxProfileRequest, UpdateToolboxProfileRequest};
+use crate::worktree_paths::WorktreePathResolver;
+use crate::services::{CachedQuery, ReadCache, ToolboxService};
+use crate::toolbox_profiles::{ToolboxProfile, CreateToolboxProfileRequest, UpdateToolboxProfileRequest};
+use crate::list_query::{fetch_page, ListQuery, ListResult, ListSpec, SortDirection};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -21,6 +22,8 @@ pub struct SessionConfig {
     pub auto_route: Option<bool>,
     pub alloy_mode: Option<bool>,
     pub multi_provider: Option<bool>,
+    /// Amp account profile to run under; defaults to the active profile
+    pub amp_profile_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -55,7 +58,7 @@ pub struct AmpSession {
     pub worktree_guard: Option<crate::worktree_manager::WorktreeGuard>,
 }
 
-pub type AmpSessionMap = Arc<Mutex<HashMap<String, AmpSession>>>;
+pub type AmpSessionMap = Arc<crate::session_map::SessionMap<AmpSession>>;
 
 // Initialize managers in Tauri state
 pub fn init_session_manager() -> SessionManager {
@@ -67,49 +70,28 @@ pub fn init_process_manager() -> ProcessManager {
 }
 
 pub fn init_amp_sessions() -> AmpSessionMap {
-    Arc::new(Mutex::new(HashMap::new()))
+    Arc::new(crate::session_map::SessionMap::new())
 }
 
-/// Generate the worktree path for a given session ID
-fn path_for(repo_path: &std::path::Path, session_id: &str) -> std::path::PathBuf {
-    let short_sid = &session_id[..session_id.len().min(8)];
-    repo_path.join(".amp-worktrees").join(short_sid)
-}
-
-/// Helper function to get session worktree path
-/// Falls back to current directory if session worktree cannot be determined
-async fn get_session_worktree_path(session_id: Option<&str>) -> PathBuf {
-    if let Some(session_id) = session_id {
-        // Try to find the repository root from current directory
-        if let Ok(current_dir) = std::env::current_dir() {
-            if let Ok(repo_path) = find_repo_root(&current_dir) {
-                let worktree_path = path_for(&repo_path, session_id);
-                if worktree_path.exists() {
-                    return worktree_path;
-                }
-                // If worktree doesn't exist, return repo root
-                return repo_path;
-            }
-        }
-    }
-    // Default fallback to current directory
-    std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-}
-
-/// Find the Git repository root starting from a given path
-fn find_repo_root(start_path: &std::path::Path) -> Result<std::path::PathBuf, String> {
-    let mut current_path = start_path;
-    
-    loop {
-        if current_path.join(".git").exists() {
-            return Ok(current_path.to_path_buf());
-        }
-        
-        match current_path.parent() {
-            Some(parent) => current_path = parent,
-            None => return Err("No Git repository found".to_string()),
-        }
-    }
+/// Amp profile a chat session or thread-architecture session was created under
+pub async fn bound_profile_id(
+    profile_manager: &crate::profile_auth::ProfileManager,
+    session_id: &str,
+) -> Option<String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref()?;
+    sqlx::query_scalar::<_, Option<String>>(
+        "SELECT amp_profile_id FROM chat_sessions WHERE id = ?1
+         UNION ALL SELECT amp_profile_id FROM sessions WHERE id = ?1
+         UNION ALL SELECT s.amp_profile_id FROM threads t JOIN sessions s ON s.id = t.session_id WHERE t.id = ?1"
+    )
+    .bind(session_id)
+    .fetch_all(db)
+    .await
+    .ok()?
+    .into_iter()
+    .flatten()
+    .next()
 }
 
 #[tauri::command]
@@ -121,6 +103,14 @@ pub async fn auth_status(
 ) -> Result<crate::amp_auth::AuthStatus, String> {
     use crate::amp_auth::{ensure_auth, ResolvedConfig};
     
+    // A session bound to a profile always authenticates as that profile
+    if let Some(session_id) = session_id.as_deref() {
+        if let Some(profile_id) = bound_profile_id(&profile_manager, session_id).await {
+            let config = profile_manager.resolved_config_for(&profile_id).await?;
+            return ensure_auth(&app_handle, &config).await;
+        }
+    }
+    
     // Always prefer app state over profiles when connection_mode is explicitly set
     let prefer_app_state = {
         let state = app_state.lock().unwrap();
@@ -150,7 +140,7 @@ pub async fn auth_status(
     
     // Use session worktree path if session_id is provided
     if let Some(session_id) = session_id {
-        let worktree_path = get_session_worktree_path(Some(&session_id)).await;
+        let worktree_path = WorktreePathResolver::of(&app_handle).working_dir_from_current(Some(&session_id)).await;
         config.cwd = worktree_path;
     }
     
@@ -168,29 +158,9 @@ fn build_env_from_state(app_state: &State<'_, crate::app_state::AppState>) -> Ha
     merged_env
 }
 
+/// Amp command line for `env`, shared with the benchmark harness in unified-core
 pub fn choose_amp_command(env: &HashMap<String, String>) -> (String, Vec<String>) {
-
-    if let Some(path) = env.get("AMP_CLI_PATH") {
-        // Local CLI: node path/to/main.js --execute --stream-json --stream-json-input
-        ("node".to_string(), vec![
-            "--enable-source-maps".into(),
-            "--no-warnings".into(), 
-            "--unhandled-rejections=strict".into(),
-            "--max-old-space-size=2048".into(),
-            "--experimental-json-modules".into(),
-            path.clone(),
-            "--execute".into(),
-            "--stream-json".into(),
-            "--stream-json-input".into()
-        ])
-    } else {
-        // Production: amp --execute --stream-json --stream-json-input
-        (env.get("AMP_BIN").cloned().unwrap_or_else(|| "amp".into()), vec![
-            "--execute".into(),
-            "--stream-json".into(),
-            "--stream-json-input".into()
-        ])
-    }
+    unified_core::AmpHarness::command(env)
 }
 
 #[tauri::command]
@@ -203,18 +173,45 @@ pub async fn session_create(
 ) -> Result<String, String> {
     let session_id = Uuid::new_v4().to_string();
 
+    // Reject unsafe working directories before anything is spawned or stored
+    let requested_dir = config
+        .working_directory
+        .as_deref()
+        .map(|dir| crate::working_dir::validate_with_state(dir, &app_state))
+        .transpose()
+        .map_err(|e| e.to_command_error())?;
+
     // Build env and choose command
     let mut merged_env = build_env_from_state(&app_state);
-    // Compose runtime env (toolboxes, etc.) using the new EnvComposer system
-    // This will use ChatSpawnComposer for backward compatibility
-    let compose = crate::runtime_env::compose_runtime_env(&mut merged_env).map_err(|e| e.to_string())?;
 
-    // Ensure AMP_API_KEY is present by reading shell config if missing
-    if !merged_env.contains_key("AMP_API_KEY") {
+    // Bind the session to its profile now so later profile switches don't affect it
+    let prefer_app_state = app_state.lock().unwrap().connection_mode.is_some();
+    let amp_profile_id = profile_manager
+        .resolve_session_profile(config.amp_profile_id.as_deref(), prefer_app_state)
+        .await?;
+    let strict_env = match profile_manager.db_pool.read().await.as_ref() {
+        Some(db) => crate::env_isolation::is_strict(db, &session_id, amp_profile_id.as_deref()).await,
+        None => false,
+    };
+
+    // Ensure AMP_API_KEY is present by reading shell config if missing, unless
+    // strict isolation keeps the shell's variables out
+    if !strict_env && !merged_env.contains_key("AMP_API_KEY") {
         if let Ok(Some(api_key)) = get_shell_env_var("AMP_API_KEY".to_string()).await {
             merged_env.insert("AMP_API_KEY".to_string(), api_key);
         }
     }
+    if let Some(profile_id) = &amp_profile_id {
+        profile_manager.apply_profile_env(profile_id, &mut merged_env).await?;
+    }
+
+    // Compose runtime env (toolboxes, etc.) using the new EnvComposer system, after
+    // the profile so its resolutions land in the profile's own directory
+    let compose = crate::runtime_env::compose_runtime_env(&mut merged_env).map_err(|e| e.to_string())?;
+    if strict_env {
+        merged_env = unified_core::isolated_env(&merged_env);
+    }
+    crate::session_log::record_env(&app_handle, &session_id, &merged_env);
 
     // Diagnostics
     {
@@ -231,12 +228,7 @@ pub async fn session_create(
             merged_env.get("AMP_URL"),
             mode, cli_path, srv_url, session_id
         ));
-        let _ = std::fs::create_dir_all("/Users/sjarmak/amp-orchestra/logs");
-        let _ = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open("/Users/sjarmak/amp-orchestra/logs/ui-connection.log")
-            .and_then(|mut f| std::io::Write::write_all(&mut f, diag.as_bytes()));
+        let _ = crate::profile_dirs::ProfileDirs::for_profile(amp_profile_id.as_deref()).append_log("ui-connection.log", &diag);
     }
 
     let (cmd, args) = choose_amp_command(&merged_env);
@@ -255,35 +247,45 @@ pub async fn session_create(
                 state.amp_env.get("AMP_TOOLBOX_PATHS").cloned()
             )
         };
-        let _ = sqlx::query("INSERT OR IGNORE INTO chat_sessions (id, context, title, agent_mode, toolbox_path) VALUES (?, ?, ?, ?, ?)")
+        let _ = sqlx::query("INSERT OR IGNORE INTO chat_sessions (id, context, title, agent_mode, toolbox_path, amp_profile_id) VALUES (?, ?, ?, ?, ?, ?)")
             .bind(&session_id)
             .bind(&context_label)
             .bind("New chat")
             .bind(&agent_mode)
             .bind(&toolbox_path)
+            .bind(&amp_profile_id)
             .execute(db)
             .await;
     }
 
     // Determine the working directory for the Amp session
-    let working_dir = if let Some(working_directory) = &config.working_directory {
-        // Use the provided working directory from the config
-        PathBuf::from(working_directory)
+    let working_dir = if let Some(working_directory) = requested_dir {
+        // Use the validated working directory from the config
+        working_directory
     } else {
         // Fall back to session worktree path
-        get_session_worktree_path(Some(&session_id)).await
+        WorktreePathResolver::of(&app_handle).working_dir_from_current(Some(&session_id)).await
     };
 
-    let mut child = Command::new(&cmd)
+    let mut command = Command::new(&cmd);
+    command
         .args(&args)
         .env_clear()
         .envs(&merged_env)
-        .current_dir(working_dir)
+        .current_dir(&working_dir)
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    crate::process_limits::apply(&app_handle, &mut command);
+    let mut child = command
         .spawn()
-        .map_err(|e| format!("Failed to spawn amp process: {}", e))?;
+        .map_err(|e| {
+            crate::session_log::record_spawn(&app_handle, &session_id, &cmd, &args, &working_dir, Err(&e));
+            format!("Failed to spawn amp process: {}", e)
+        })?;
+    let pid = child.id();
+    crate::session_log::record_spawn(&app_handle, &session_id, &cmd, &args, &working_dir, Ok(pid));
+    crate::file_locks::register_session_root(&app_handle, &session_id, working_dir);
 
     let stdin = child.stdin.take().ok_or_else(|| "Failed to open stdin".to_string())?;
     let stdout = child.stdout.take().ok_or_else(|| "Failed to open stdout".to_string())?;
@@ -307,13 +309,29 @@ pub async fn session_create(
         use tauri::Manager;
         
         if let Some(wt_manager) = app_handle.try_state::<TauriWorktreeManager>() {
-            match wt_manager.create_session_worktree(&session_id, None).await {
+            match wt_manager.create_session_worktree_with_progress(&app_handle, &session_id, None, None).await {
                 Ok(guard) => {
                     log::info!("Created worktree for session {} at {}", session_id, guard.worktree_path().display());
+                    crate::session_log::record(
+                        &app_handle,
+                        &session_id,
+                        crate::session_log::LogCategory::Git,
+                        crate::session_log::LogLevel::Info,
+                        "Created worktree",
+                        serde_json::json!({ "path": guard.worktree_path() }),
+                    );
                     Some(guard)
                 }
                 Err(e) => {
                     log::error!("Failed to create worktree for session {}: {}", session_id, e);
+                    crate::session_log::record(
+                        &app_handle,
+                        &session_id,
+                        crate::session_log::LogCategory::Git,
+                        crate::session_log::LogLevel::Error,
+                        format!("Failed to create worktree: {}", e),
+                        serde_json::Value::Null,
+                    );
                     None
                 }
             }
@@ -324,90 +342,105 @@ pub async fn session_create(
     };
     
     // Store session
-    {
-        let mut map = amp_sessions.lock().await;
-        map.insert(session_id.clone(), AmpSession { 
-            child, 
-            tx, 
-            toolbox_guard: compose.guard,
-            #[cfg(feature = "worktree-manager")]
-            worktree_guard,
-        });
-    }
+    amp_sessions.insert(session_id.clone(), AmpSession {
+        child,
+        tx,
+        toolbox_guard: compose.guard,
+        #[cfg(feature = "worktree-manager")]
+        worktree_guard,
+    });
 
     // Reader for stdout
     let window = app_handle.clone();
     let sid_stdout = session_id.clone();
+    let program = crate::stream_quarantine::CliInfo::new(&cmd, &args).executable().to_string();
     let db_pool_for_stdout = profile_manager.db_pool.clone();
+    let mut validator = crate::stream_quarantine::StreamValidator::new(
+        "chat",
+        &session_id,
+        crate::stream_quarantine::CliInfo::new(&cmd, &args),
+    );
     tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
+        let mut lines = crate::stream_quarantine::StreamLines::new(BufReader::new(stdout));
+        let mut stream_signals = crate::session_analytics::StreamSignals::default();
+        crate::session_priority::session_started(&sid_stdout);
+        crate::startup_reconciliation::process_started(db_pool_for_stdout.read().await.as_ref(), &sid_stdout, "chat", pid, &program).await;
+        let limit_watch = crate::process_limits::watch(&window, &sid_stdout, "chat", pid);
         while let Ok(Some(line)) = lines.next_line().await {
-            if let Ok(parsed) = serde_json::from_str::<Value>(&line) {
-                // Update session title/last_snippet heuristics
-                if let Some(t) = parsed.get("type").and_then(|v| v.as_str()) {
-                    if t == "assistant" {
-                        // Extract text
-                        let mut text = String::new();
-                        if let Some(content) = parsed.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()) {
-                            for part in content {
-                                if let Some(s) = part.get("text").and_then(|x| x.as_str()) { text.push_str(s); }
+            crate::session_activity::touch_session(&window, &sid_stdout);
+            crate::chaos::maybe_kill(pid);
+            match validator.check(&line) {
+                Ok(parsed) => {
+                    stream_signals.observe(&parsed);
+                    // Update session title/last_snippet heuristics
+                    if let Some(t) = parsed.get("type").and_then(|v| v.as_str()) {
+                        if t == "assistant" {
+                            if let Some(snippet) = crate::stream_text::assistant_snippet(&parsed) {
+                                if let Some(db) = db_pool_for_stdout.read().await.as_ref() {
+                                    let _ = sqlx::query("UPDATE chat_sessions SET last_snippet = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                                        .bind(&snippet)
+                                        .bind(&sid_stdout)
+                                        .execute(db)
+                                        .await;
+                                }
                             }
-                        } else if let Some(s) = parsed.get("text").and_then(|x| x.as_str()) { text.push_str(s); }
-                        if !text.is_empty() {
+                        } else if t == "user" {
                             if let Some(db) = db_pool_for_stdout.read().await.as_ref() {
-                                let snippet = if text.len() > 120 { format!("{}…", &text[..120]) } else { text.clone() };
-                                let _ = sqlx::query("UPDATE chat_sessions SET last_snippet = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-                                    .bind(&snippet)
-                                    .bind(&sid_stdout)
-                                    .execute(db)
-                                    .await;
-                            }
-                        }
-                    } else if t == "user" {
-                        if let Some(db) = db_pool_for_stdout.read().await.as_ref() {
-                            if let Some(prompt) = parsed.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()).and_then(|arr| arr.get(0)).and_then(|p| p.get("text")).and_then(|x| x.as_str()) {
-                                let title = if prompt.len() > 60 { format!("{}…", &prompt[..60]) } else { prompt.to_string() };
-                                let _ = sqlx::query("UPDATE chat_sessions SET title = COALESCE(NULLIF(title,'New chat'), ?), updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-                                    .bind(&title)
-                                    .bind(&sid_stdout)
-                                    .execute(db)
-                                    .await;
+                                if let Some(title) = crate::stream_text::user_title(&parsed) {
+                                    let _ = sqlx::query("UPDATE chat_sessions SET title = COALESCE(NULLIF(title,'New chat'), ?), updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                                        .bind(&title)
+                                        .bind(&sid_stdout)
+                                        .execute(db)
+                                        .await;
+                                }
                             }
                         }
                     }
+                    crate::script_hooks::dispatch_tool_use_hooks(&window, &sid_stdout, &parsed);
+                    crate::file_locks::observe_stream_event(&window, &sid_stdout, &parsed);
+                    crate::watch_mode::observe_stream_event(&window, &sid_stdout, &parsed);
+                    crate::session_priority::observe_stream_event(&window, &sid_stdout, &parsed);
+                    crate::stream_buffer::emit_buffered(&window, "chat_stream", &sid_stdout, serde_json::json!({
+                        "session_id": sid_stdout,
+                        "event": parsed,
+                        "timestamp": chrono::Utc::now().timestamp_millis()
+                    }));
+                }
+                Err(quarantined) => {
+                    // Malformed line from CLI; keep it for debugging and forward as error_output
+                    quarantined.store_logged(db_pool_for_stdout.read().await.as_ref()).await;
+                    crate::session_log::record_quarantined(&window, &quarantined);
+                    crate::stream_buffer::emit_buffered(&window, "chat_stream", &sid_stdout, serde_json::json!({
+                        "session_id": sid_stdout,
+                        "event": quarantined.error_output(),
+                        "timestamp": chrono::Utc::now().timestamp_millis()
+                    }));
                 }
-                let _ = window.emit("chat_stream", serde_json::json!({
-                    "session_id": sid_stdout,
-                    "event": parsed,
-                    "timestamp": chrono::Utc::now().timestamp_millis()
-                }));
-            } else {
-                // Non-JSON line from CLI; forward as error_output
-                let _ = window.emit("chat_stream", serde_json::json!({
-                    "session_id": sid_stdout,
-                    "event": { "type": "error_output", "data": { "content": line } },
-                    "timestamp": chrono::Utc::now().timestamp_millis()
-                }));
             }
         }
-        let _ = window.emit("chat_stream", serde_json::json!({
+        crate::stream_buffer::emit_buffered(&window, "chat_stream", &sid_stdout, serde_json::json!({
             "session_id": sid_stdout,
             "event": { "type": "result", "data": { "ended": true } },
             "timestamp": chrono::Utc::now().timestamp_millis()
         }));
+        limit_watch.abort();
+        crate::stream_buffer::mark_stream_ended(&window, &sid_stdout);
+        crate::session_priority::session_ended(&sid_stdout);
+        crate::startup_reconciliation::process_ended(db_pool_for_stdout.read().await.as_ref(), &sid_stdout, pid).await;
+        crate::file_locks::release_session_locks(&window, &sid_stdout);
+        crate::session_analytics::spawn_completion(window.clone(), sid_stdout.clone(), "chat", stream_signals);
     });
 
     // Reader for stderr
     let window_err = app_handle.clone();
     let sid_stderr = session_id.clone();
     tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
+        let mut lines = crate::stream_quarantine::StreamLines::new(BufReader::new(stderr));
         while let Ok(Some(line)) = lines.next_line().await {
-            let _ = window_err.emit("chat_stream", serde_json::json!({
+            crate::session_activity::touch_session(&window_err, &sid_stderr);
+            crate::stream_buffer::emit_buffered(&window_err, "chat_stream", &sid_stderr, serde_json::json!({
                 "session_id": sid_stderr,
-                "event": { "type": "error_output", "data": { "content": line } },
+                "event": { "type": "error_output", "data": { "content": line.text } },
                 "timestamp": chrono::Utc::now().timestamp_millis()
             }));
         }
@@ -421,17 +454,20 @@ pub async fn chat_send(
     options: SendMessageOptions,
     amp_sessions: State<'_, AmpSessionMap>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    activity: State<'_, crate::session_activity::ActivityTracker>,
 ) -> Result<(), String> {
-    let map = amp_sessions.lock().await;
-    let session = map.get(&options.session_id).ok_or_else(|| format!("Session {} not found", options.session_id))?;
-
-    let payload = serde_json::json!({
-        "type": "user",
-        "message": {
-            "role": "user",
-            "content": [{ "type": "text", "text": options.prompt }]
-        }
-    });
+    let session = amp_sessions.get(&options.session_id).ok_or_else(|| format!("Session {} not found", options.session_id))?;
+    // Only this session is locked, and not while the title is written
+    let tx = {
+        let session = session.lock().await;
+        // A suspended process would queue the message without answering it
+        crate::process_suspend::resume_session(&session, &activity, &options.session_id)?;
+        session.tx.clone()
+    };
+    activity.touch(&options.session_id, chrono::Utc::now().timestamp_millis());
+    crate::session_priority::input_received(&options.session_id);
+
+    let payload = unified_core::AmpHarness::user_message(&options.prompt);
 
     // Update title on first prompt if needed
     if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
@@ -444,7 +480,7 @@ pub async fn chat_send(
     }
 
     // Send via writer task
-    session.tx.send(payload.to_string()).map_err(|e| e.to_string())?;
+    tx.send(payload.to_string()).map_err(|e| e.to_string())?;
 
     Ok(())
 }
@@ -454,6 +490,7 @@ pub async fn config_get(
     key: Option<String>,
     session_id: Option<String>,
     app_state: State<'_, crate::app_state::AppState>,
+    worktree_paths: State<'_, WorktreePathResolver>,
 ) -> Result<Value, String> {
     // unchanged
 
@@ -483,7 +520,7 @@ pub async fn config_get(
     };
 
     // Get session worktree path for command execution
-    let working_dir = get_session_worktree_path(session_id.as_deref()).await;
+    let working_dir = worktree_paths.working_dir_from_current(session_id.as_deref()).await;
 
     let output = Command::new("node")
         .arg("-e")
@@ -512,6 +549,7 @@ pub async fn config_set(
     value: Value,
     session_id: Option<String>,
     app_state: State<'_, crate::app_state::AppState>,
+    worktree_paths: State<'_, WorktreePathResolver>,
 ) -> Result<(), String> {
     let script = format!(r#"
         const {{ setConfigValue }} = require('../../node_modules/.pnpm/node_modules/@ampsm/amp-backend-core/dist/config.js');
@@ -528,7 +566,7 @@ pub async fn config_set(
     };
 
     // Get session worktree path for command execution
-    let working_dir = get_session_worktree_path(session_id.as_deref()).await;
+    let working_dir = worktree_paths.working_dir_from_current(session_id.as_deref()).await;
 
     let output = Command::new("node")
         .arg("-e")
@@ -573,7 +611,7 @@ pub async fn set_environment(
         
         // Set custom CLI path or clear it for production mode
         if normalized_mode == "local-cli" {
-            let path = cli_path.unwrap_or_else(|| "/Users/sjarmak/amp/cli/dist/main.js".to_string());
+            let path = cli_path.unwrap_or_else(crate::app_state::default_dev_cli_path);
             state.custom_cli_path = Some(path.clone());
             state.set_env("AMP_CLI_PATH".to_string(), path);
             // Clear AMP_BIN when using local CLI
@@ -767,33 +805,59 @@ mod tests {
     }
 }
 
+const SESSION_LIST: ListSpec = ListSpec {
+    fields: &[
+        ("id", "id"),
+        ("context", "context"),
+        ("title", "title"),
+        ("agent_mode", "agent_mode"),
+        ("toolbox_path", "toolbox_path"),
+        ("created_at", "created_at"),
+        ("updated_at", "updated_at"),
+    ],
+    default_sort: &[("updated_at", SortDirection::Desc), ("id", SortDirection::Asc)],
+};
+
+type SessionRow = (String, String, Option<String>, Option<String>, Option<String>, Option<String>, String, String);
+
 // List chat sessions
 #[tauri::command]
 pub async fn sessions_list(
+    query: Option<ListQuery>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
-) -> Result<Vec<serde_json::Value>, String> {
-     if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-     use sqlx::Row;
-     let rows = sqlx::query("SELECT id, context, title, last_snippet, agent_mode, toolbox_path, created_at, updated_at FROM chat_sessions ORDER BY updated_at DESC")
-     .fetch_all(db)
-     .await
-         .map_err(|e| e.to_string())?;
-     let out = rows.into_iter().map(|r| serde_json::json!({
-     "id": r.try_get::<String, _>("id").unwrap_or_default(),
-     "context": r.try_get::<String, _>("context").unwrap_or_default(),
-     "title": r.try_get::<String, _>("title").ok(),
-     "last_snippet": r.try_get::<String, _>("last_snippet").ok(),
-     "agent_mode": r.try_get::<String, _>("agent_mode").ok(),
-     "toolbox_path": r.try_get::<String, _>("toolbox_path").ok(),
-     "created_at": r.try_get::<String, _>("created_at").unwrap_or_default(),
-         "updated_at": r.try_get::<String, _>("updated_at").unwrap_or_default(),
-     })).collect();
-         Ok(out)
-     } else {
-         Ok(vec![])
-    }
- }
- 
+    read_cache: State<'_, ReadCache>,
+) -> Result<ListResult<serde_json::Value>, String> {
+    let Some(db) = profile_manager.db_pool.read().await.clone() else {
+        return Ok(ListResult::empty());
+    };
+    let query = query.unwrap_or_default();
+    let key = serde_json::to_string(&query).map_err(|e| e.to_string())?;
+    let rows: ListResult<SessionRow> = read_cache
+        .get_or_load(&db, CachedQuery::Sessions, &key, || {
+            fetch_page(
+                &db,
+                "id, context, title, last_snippet, agent_mode, toolbox_path, created_at, updated_at",
+                "chat_sessions",
+                (&[], &[]),
+                &query,
+                &SESSION_LIST,
+            )
+        })
+        .await?;
+    Ok(rows.map(|(id, context, title, last_snippet, agent_mode, toolbox_path, created_at, updated_at)| {
+        serde_json::json!({
+            "id": id,
+            "context": context,
+            "title": title,
+            "last_snippet": last_snippet,
+            "agent_mode": agent_mode,
+            "toolbox_path": toolbox_path,
+            "created_at": created_at,
+            "updated_at": updated_at,
+        })
+    }))
+}
+
  #[tauri::command]
  pub async fn spawn_amp_process(
     command: String,
@@ -858,7 +922,7 @@ pub async fn sessions_list(
     );
     
     // Get session worktree path for command execution
-    let working_dir = get_session_worktree_path(Some(&session_id)).await;
+    let working_dir = WorktreePathResolver::of(&app_handle).working_dir_from_current(Some(&session_id)).await;
     println!("[spawn_amp_process] Using working directory: {}", working_dir.display());
     
     cmd.args(&args)
@@ -917,7 +981,7 @@ pub async fn sessions_list(
                 }
                 Ok(_) => {
                     // Emit output to frontend
-                    let _ = app_handle_stdout.emit("process_output", serde_json::json!({
+                    crate::event_subscriptions::emit_session_event(&app_handle_stdout, "process_output", &session_id_stdout, serde_json::json!({
                         "sessionId": session_id_stdout,
                         "processId": process_id_stdout,
                         "data": line,
@@ -952,7 +1016,7 @@ pub async fn sessions_list(
             match reader.read_line(&mut line).await {
                 Ok(0) => break,
                 Ok(_) => {
-                    let _ = app_handle_stderr.emit("process_output", serde_json::json!({
+                    crate::event_subscriptions::emit_session_event(&app_handle_stderr, "process_output", &session_id_stderr, serde_json::json!({
                         "sessionId": session_id_stderr,
                         "processId": process_id_stderr,
                         "data": line,
@@ -1039,7 +1103,7 @@ pub async fn spawn_process_raw(
             match reader.read_line(&mut line).await {
                 Ok(0) => break,
                 Ok(_) => {
-                    let _ = app_handle_stdout.emit("process_output", serde_json::json!({
+                    crate::event_subscriptions::emit_session_event(&app_handle_stdout, "process_output", &session_id_stdout, serde_json::json!({
                         "sessionId": session_id_stdout,
                         "processId": process_id_stdout,
                         "data": line,
@@ -1067,7 +1131,7 @@ pub async fn spawn_process_raw(
             match reader.read_line(&mut line).await {
                 Ok(0) => break,
                 Ok(_) => {
-                    let _ = app_handle_stderr.emit("process_output", serde_json::json!({
+                    crate::event_subscriptions::emit_session_event(&app_handle_stderr, "process_output", &session_id_stderr, serde_json::json!({
                         "sessionId": session_id_stderr,
                         "processId": process_id_stderr,
                         "data": line,
@@ -1152,35 +1216,17 @@ pub async fn get_shell_env_var(var_name: String) -> Result<Option<String>, Strin
     println!("[get_shell_env_var] {} not found in env(), trying shell config files...", var_name);
     
     // Try to read from common shell config files
-    let home = env::var("HOME").map_err(|_| "Could not get HOME directory")?;
-    let shell_files = vec![
-        format!("{}/.zprofile", home),  // macOS zsh users commonly put exports here
-        format!("{}/.zshrc", home),
-        format!("{}/.bashrc", home),
-        format!("{}/.bash_profile", home),
-        format!("{}/.profile", home),
-    ];
-    
-    for file_path in shell_files {
-        println!("[get_shell_env_var] Scanning {}", file_path);
+    let home = crate::platform::home_dir().ok_or("Could not get home directory")?;
+    for file_path in crate::platform::shell_config_files(&home) {
+        println!("[get_shell_env_var] Scanning {}", file_path.display());
         if let Ok(contents) = fs::read_to_string(&file_path) {
-            // Look for export VAR_NAME=value patterns
             for line in contents.lines() {
-                let line = line.trim();
-                if line.starts_with(&format!("export {}=", var_name)) {
-                    if let Some(eq_pos) = line.find('=') {
-                        let value_part = &line[eq_pos + 1..];
-                        // Remove quotes and handle different quote styles
-                        let cleaned_value = value_part
-                            .trim_matches('"')
-                            .trim_matches('\'');
-                        
-                        if !cleaned_value.contains("your-actual") && 
-                           !cleaned_value.contains("REDACTED") && 
-                           !cleaned_value.is_empty() {
-                            println!("[get_shell_env_var] Found {} in {}", var_name, file_path);
-                            return Ok(Some(cleaned_value.to_string()));
-                        }
+                if let Some(cleaned_value) = crate::platform::shell_export_value(line, &var_name) {
+                    if !cleaned_value.contains("your-actual") && 
+                       !cleaned_value.contains("REDACTED") && 
+                       !cleaned_value.is_empty() {
+                        println!("[get_shell_env_var] Found {} in {}", var_name, file_path.display());
+                        return Ok(Some(cleaned_value.to_string()));
                     }
                 }
             }
@@ -1192,55 +1238,67 @@ pub async fn get_shell_env_var(var_name: String) -> Result<Option<String>, Strin
 
 // Toolbox Profile Management Commands
 
+async fn toolbox_service(
+    app_state: &State<'_, crate::app_state::AppState>,
+    profile_manager: &State<'_, crate::profile_auth::ProfileManager>,
+) -> ToolboxService<crate::app_state::AppState> {
+    ToolboxService::new(profile_manager.db_pool.read().await.clone(), app_state.inner().clone())
+}
+
 #[tauri::command]
 pub async fn list_toolbox_profiles(
+    query: Option<ListQuery>,
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
-) -> Result<Vec<ToolboxProfile>, String> {
-    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        let store = ToolboxProfileStore::new(db.clone());
-        store.list_profiles().await.map_err(|e| e.to_string())
-    } else {
-        Ok(vec![])
-    }
+    read_cache: State<'_, ReadCache>,
+) -> Result<ListResult<ToolboxProfile>, String> {
+    toolbox_service(&app_state, &profile_manager)
+        .await
+        .with_cache(read_cache.inner().clone())
+        .list(&query.unwrap_or_default())
+        .await
 }
 
 #[tauri::command]
 pub async fn create_toolbox_profile(
     request: CreateToolboxProfileRequest,
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    read_cache: State<'_, ReadCache>,
 ) -> Result<ToolboxProfile, String> {
-    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        let store = ToolboxProfileStore::new(db.clone());
-        store.create_profile(request).await.map_err(|e| e.to_string())
-    } else {
-        Err("Database not available".to_string())
-    }
+    toolbox_service(&app_state, &profile_manager)
+        .await
+        .with_cache(read_cache.inner().clone())
+        .create(request)
+        .await
 }
 
 #[tauri::command]
 pub async fn update_toolbox_profile(
     request: UpdateToolboxProfileRequest,
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    read_cache: State<'_, ReadCache>,
 ) -> Result<Option<ToolboxProfile>, String> {
-    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        let store = ToolboxProfileStore::new(db.clone());
-        store.update_profile(request).await.map_err(|e| e.to_string())
-    } else {
-        Err("Database not available".to_string())
-    }
+    toolbox_service(&app_state, &profile_manager)
+        .await
+        .with_cache(read_cache.inner().clone())
+        .update(request)
+        .await
 }
 
 #[tauri::command]
 pub async fn delete_toolbox_profile(
     id: i64,
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    read_cache: State<'_, ReadCache>,
 ) -> Result<bool, String> {
-    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        let store = ToolboxProfileStore::new(db.clone());
-        store.delete_profile(id).await.map_err(|e| e.to_string())
-    } else {
-        Err("Database not available".to_string())
-    }
+    toolbox_service(&app_state, &profile_manager)
+        .await
+        .with_cache(read_cache.inner().clone())
+        .delete(id)
+        .await
 }
 
 #[tauri::command]
@@ -1249,39 +1307,7 @@ pub async fn set_active_toolbox_profile(
     app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<(), String> {
-    if let Some(id) = profileId {
-        // Get the profile and set its paths
-        if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-            let store = ToolboxProfileStore::new(db.clone());
-            if let Some(profile) = store.get_profile(id).await.map_err(|e| e.to_string())? {
-                let paths_str = profile.paths.join(if cfg!(windows) { ";" } else { ":" });
-                {
-                    let mut state = app_state.lock().unwrap();
-                    state.set_env("AMP_TOOLBOX_PATHS".to_string(), paths_str.clone());
-                    state.set_env("AMP_ACTIVE_TOOLBOX_PROFILE".to_string(), profile.name.clone());
-                    // Always enable toolboxes when a profile is active
-                    state.set_env("AMP_ENABLE_TOOLBOXES".to_string(), "1".to_string());
-                    // Store active profile ID for persistence
-                    state.active_toolbox_profile_id = Some(id);
-                }
-            } else {
-                return Err("Profile not found".to_string());
-            }
-        } else {
-            return Err("Database not available".to_string());
-        }
-    } else {
-        // Clear active toolbox profile
-        let mut state = app_state.lock().unwrap();
-        state.amp_env.remove("AMP_TOOLBOX_PATHS");
-        state.amp_env.remove("AMP_ACTIVE_TOOLBOX_PROFILE");
-        state.amp_env.remove("AMP_ENABLE_TOOLBOXES");
-        state.active_toolbox_profile_id = None;
-    }
-    
-    let to_save = { let state = app_state.lock().unwrap(); state.clone() };
-    to_save.save().await?;
-    Ok(())
+    toolbox_service(&app_state, &profile_manager).await.set_active(profileId).await
 }
 
 #[tauri::command]
@@ -1289,31 +1315,18 @@ pub async fn get_active_toolbox_profile(
     app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<Option<ToolboxProfile>, String> {
-    let profile_id = {
-        let state = app_state.lock().unwrap();
-        state.active_toolbox_profile_id
-    };
-    
-    if let Some(id) = profile_id {
-        if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-            let store = ToolboxProfileStore::new(db.clone());
-            store.get_profile(id).await.map_err(|e| e.to_string())
-        } else {
-            Ok(None)
-        }
-    } else {
-        Ok(None)
-    }
+    toolbox_service(&app_state, &profile_manager).await.active().await
 }
 
 #[tauri::command]
 pub async fn migrate_toolbox_profiles(
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    read_cache: State<'_, ReadCache>,
 ) -> Result<(), String> {
-    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
-        let store = ToolboxProfileStore::new(db.clone());
-        store.migrate_single_paths().await.map_err(|e| e.to_string())
-    } else {
-        Err("Database not available".to_string())
-    }
+    toolbox_service(&app_state, &profile_manager)
+        .await
+        .with_cache(read_cache.inner().clone())
+        .migrate()
+        .await
 }
@@ -0,0 +1,100 @@
+//! Health report for a session's worktree
+//!
+//! `diagnose_session` gathers what is needed to explain odd agent behaviour in
+//! a worktree: its branch and pending changes, the path scope it is limited to,
+//! and the state of Git LFS content and submodules.
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::commit_message::{git, session_worktree};
+use crate::git_lfs::{lfs_status, LfsStatus, NON_LFS_PATHSPECS};
+use crate::path_scope::read_scope;
+use crate::submodules::{submodule_status, SubmoduleStatus};
+
+/// Line counts of uncommitted changes, LFS files excluded
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub insertions: u64,
+    pub deletions: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDiagnostics {
+    pub session_id: String,
+    pub worktree_path: String,
+    pub worktree_exists: bool,
+    pub branch: Option<String>,
+    pub path_scope: Option<Vec<String>>,
+    pub diff: Option<DiffStats>,
+    pub lfs: Option<LfsStatus>,
+    pub submodules: Option<Vec<SubmoduleStatus>>,
+    /// Checks that could not be run, with the reason
+    pub errors: Vec<String>,
+}
+
+/// Parse `git diff --numstat`; binary files (`-` counts) add to the file count only
+pub fn parse_numstat(numstat: &str) -> DiffStats {
+    numstat.lines().filter(|l| !l.trim().is_empty()).fold(DiffStats::default(), |mut stats, line| {
+        let mut fields = line.split('\t');
+        stats.files_changed += 1;
+        stats.insertions += fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        stats.deletions += fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        stats
+    })
+}
+
+#[tauri::command]
+pub async fn diagnose_session(session_id: String, app_handle: AppHandle) -> Result<SessionDiagnostics, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let worktree = session_worktree(&app_handle, &session_id).await?;
+    let mut report = SessionDiagnostics {
+        session_id,
+        worktree_path: worktree.to_string_lossy().to_string(),
+        worktree_exists: worktree.is_dir(),
+        branch: None,
+        path_scope: None,
+        diff: None,
+        lfs: None,
+        submodules: None,
+        errors: Vec::new(),
+    };
+    if !report.worktree_exists {
+        return Ok(report);
+    }
+
+    match git(&worktree, &["rev-parse", "--abbrev-ref", "HEAD"]) {
+        Ok(branch) => report.branch = Some(branch.trim().to_string()),
+        Err(e) => report.errors.push(e),
+    }
+    report.path_scope = read_scope(&worktree).map(|scope| scope.paths().to_vec());
+
+    let mut numstat_args = vec!["diff", "--numstat", "HEAD", "--"];
+    numstat_args.extend(NON_LFS_PATHSPECS);
+    match git(&worktree, &numstat_args) {
+        Ok(numstat) => report.diff = Some(parse_numstat(&numstat)),
+        Err(e) => report.errors.push(e),
+    }
+    match lfs_status(&worktree) {
+        Ok(lfs) => report.lfs = Some(lfs),
+        Err(e) => report.errors.push(e),
+    }
+    match submodule_status(&worktree) {
+        Ok(submodules) => report.submodules = Some(submodules),
+        Err(e) => report.errors.push(e),
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numstat() {
+        let stats = parse_numstat("3\t1\tsrc/main.rs\n-\t-\tassets/logo.png\n10\t0\tREADME.md\n");
+        assert_eq!(stats, DiffStats { files_changed: 3, insertions: 13, deletions: 1 });
+        assert_eq!(parse_numstat(""), DiffStats::default());
+    }
+}
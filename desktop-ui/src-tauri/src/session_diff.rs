@@ -0,0 +1,277 @@
+//! Comparing what two sessions did to the same repository
+//!
+//! `diff_sessions` snapshots each session's worktree as it stands, committed and
+//! uncommitted changes alike, and compares both against the commit the two
+//! branches share. Files are sorted into those only one session changed and
+//! those both changed; for the latter, hunks touching the same or adjacent base
+//! lines are paired up, as `git merge` would report them as conflicts. The real
+//! indexes are left alone: snapshots go through a temporary index file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::AppHandle;
+
+use crate::commit_message::{git, session_worktree};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    /// `A`, `M` or `D`, as in `git diff --name-status`
+    pub status: String,
+    /// Unset for binary files
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hunk {
+    /// First base line the hunk replaces; for a pure insertion, the line it follows
+    pub base_start: u32,
+    pub base_lines: u32,
+    /// The hunk in unified diff form, header included
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictingHunk {
+    pub a: Hunk,
+    pub b: Hunk,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedFile {
+    pub path: String,
+    pub a: FileChange,
+    pub b: FileChange,
+    /// Both sessions ended with the same content
+    pub identical: bool,
+    /// Binary files are not split into hunks; differing ones always conflict
+    pub binary: bool,
+    pub conflicts: Vec<ConflictingHunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorktreeDiff {
+    /// Commit both sides are compared against
+    pub base: String,
+    pub only_a: Vec<FileChange>,
+    pub only_b: Vec<FileChange>,
+    pub both: Vec<SharedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionDiff {
+    pub session_a: String,
+    pub session_b: String,
+    #[serde(flatten)]
+    pub diff: WorktreeDiff,
+}
+
+/// Run git with a private index so the worktree's own index is untouched
+fn git_with_index(dir: &Path, index: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .env("GIT_INDEX_FILE", index)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git {}: {}", args.join(" "), e))?;
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Tree of the worktree's current files, untracked ones included
+fn snapshot_tree(worktree: &Path) -> Result<String, String> {
+    let index = std::env::temp_dir().join(format!("session-diff-{}.index", uuid::Uuid::new_v4()));
+    let result = git_with_index(worktree, &index, &["read-tree", "HEAD"])
+        .and_then(|_| git_with_index(worktree, &index, &["add", "-A"]))
+        .and_then(|_| git_with_index(worktree, &index, &["write-tree"]));
+    let _ = std::fs::remove_file(&index);
+    result
+}
+
+fn common_dir(worktree: &Path) -> Result<PathBuf, String> {
+    let dir = git(worktree, &["rev-parse", "--path-format=absolute", "--git-common-dir"])?;
+    let dir = PathBuf::from(dir.trim());
+    Ok(dir.canonicalize().unwrap_or(dir))
+}
+
+fn changes(worktree: &Path, base: &str, tree: &str) -> Result<BTreeMap<String, FileChange>, String> {
+    let mut changes = BTreeMap::new();
+    for line in git(worktree, &["diff", "--no-renames", "--name-status", base, tree])?.lines() {
+        if let Some((status, path)) = line.split_once('\t') {
+            let change = FileChange { path: path.to_string(), status: status.to_string(), additions: None, deletions: None };
+            changes.insert(path.to_string(), change);
+        }
+    }
+    for line in git(worktree, &["diff", "--no-renames", "--numstat", base, tree])?.lines() {
+        let mut fields = line.splitn(3, '\t');
+        if let (Some(added), Some(deleted), Some(path)) = (fields.next(), fields.next(), fields.next()) {
+            if let Some(change) = changes.get_mut(path) {
+                change.additions = added.parse().ok();
+                change.deletions = deleted.parse().ok();
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Hunks of a zero-context diff
+pub fn parse_hunks(diff: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@ -") {
+            let range = header.split_whitespace().next().unwrap_or_default();
+            let (start, lines) = match range.split_once(',') {
+                Some((start, lines)) => (start.parse().unwrap_or(0), lines.parse().unwrap_or(0)),
+                None => (range.parse().unwrap_or(0), 1),
+            };
+            hunks.push(Hunk { base_start: start, base_lines: lines, text: format!("{}\n", line) });
+        } else if let Some(hunk) = hunks.last_mut() {
+            hunk.text.push_str(line);
+            hunk.text.push('\n');
+        }
+    }
+    hunks
+}
+
+/// Hunks of the two sides that touch the same or adjacent base lines
+pub fn conflicting_hunks(a: &[Hunk], b: &[Hunk]) -> Vec<ConflictingHunk> {
+    let span = |h: &Hunk| (h.base_start, h.base_start + h.base_lines);
+    let mut conflicts = Vec::new();
+    for hunk_a in a {
+        let (a_start, a_end) = span(hunk_a);
+        for hunk_b in b {
+            let (b_start, b_end) = span(hunk_b);
+            if a_start <= b_end && b_start <= a_end {
+                conflicts.push(ConflictingHunk { a: hunk_a.clone(), b: hunk_b.clone() });
+            }
+        }
+    }
+    conflicts
+}
+
+fn file_hunks(worktree: &Path, base: &str, tree: &str, path: &str) -> Result<Vec<Hunk>, String> {
+    let diff = git(worktree, &["diff", "--no-renames", "--no-color", "-U0", base, tree, "--", path])?;
+    Ok(parse_hunks(&diff))
+}
+
+/// Compare two worktrees of the same repository
+pub fn diff_worktrees(a: &Path, b: &Path) -> Result<WorktreeDiff, String> {
+    if common_dir(a)? != common_dir(b)? {
+        return Err("The sessions' worktrees belong to different repositories".to_string());
+    }
+    let head_a = git(a, &["rev-parse", "HEAD"])?.trim().to_string();
+    let head_b = git(b, &["rev-parse", "HEAD"])?.trim().to_string();
+    let base = git(a, &["merge-base", &head_a, &head_b])
+        .map_err(|_| "The sessions' branches have no common base".to_string())?
+        .trim()
+        .to_string();
+    let (tree_a, tree_b) = (snapshot_tree(a)?, snapshot_tree(b)?);
+
+    let mut changes_b = changes(a, &base, &tree_b)?;
+    let mut only_a = Vec::new();
+    let mut both = Vec::new();
+    for (path, change_a) in changes(a, &base, &tree_a)? {
+        let Some(change_b) = changes_b.remove(&path) else {
+            only_a.push(change_a);
+            continue;
+        };
+        let identical = git(a, &["diff", "--quiet", &tree_a, &tree_b, "--", &path]).is_ok();
+        let binary = change_a.additions.is_none() || change_b.additions.is_none();
+        let conflicts = if identical || binary {
+            Vec::new()
+        } else {
+            conflicting_hunks(&file_hunks(a, &base, &tree_a, &path)?, &file_hunks(a, &base, &tree_b, &path)?)
+        };
+        both.push(SharedFile { path, a: change_a, b: change_b, identical, binary, conflicts });
+    }
+    Ok(WorktreeDiff { base, only_a, only_b: changes_b.into_values().collect(), both })
+}
+
+/// Compare the final worktree states of two sessions on the same repository
+#[tauri::command]
+pub async fn diff_sessions(a: String, b: String, app_handle: AppHandle) -> Result<SessionDiff, String> {
+    let (session_a, session_b) = (crate::session_codes::resolve(&a), crate::session_codes::resolve(&b));
+    if session_a == session_b {
+        return Err("Pick two different sessions to compare".to_string());
+    }
+    let worktree_a = session_worktree(&app_handle, &session_a).await?;
+    let worktree_b = session_worktree(&app_handle, &session_b).await?;
+    let diff = tokio::task::spawn_blocking(move || diff_worktrees(&worktree_a, &worktree_b))
+        .await
+        .map_err(|e| format!("Failed to compare sessions: {}", e))??;
+    Ok(SessionDiff { session_a, session_b, diff })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(base_start: u32, base_lines: u32) -> Hunk {
+        Hunk { base_start, base_lines, text: String::new() }
+    }
+
+    #[test]
+    fn test_parse_and_pair_hunks() {
+        let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -2 +2 @@ fn main\n-old\n+new\n@@ -10,0 +11,2 @@\n+x\n+y\n";
+        let hunks = parse_hunks(diff);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].base_start, hunks[0].base_lines), (2, 1));
+        assert_eq!(hunks[0].text, "@@ -2 +2 @@ fn main\n-old\n+new\n");
+        assert_eq!((hunks[1].base_start, hunks[1].base_lines), (10, 0));
+
+        let conflicts = conflicting_hunks(&hunks, &[hunk(3, 1), hunk(20, 2)]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].a.base_start, 2);
+        assert!(conflicting_hunks(&[hunk(2, 1)], &[hunk(5, 1)]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_worktrees() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        for args in [vec!["init"], vec!["config", "user.name", "Test User"], vec!["config", "user.email", "test@example.com"]] {
+            git(&repo, &args).unwrap();
+        }
+        let lines: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(repo.join("shared.txt"), &lines).unwrap();
+        std::fs::write(repo.join("same.txt"), "base\n").unwrap();
+        git(&repo, &["add", "-A"]).unwrap();
+        git(&repo, &["commit", "-m", "init"]).unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        git(&repo, &["worktree", "add", "-b", "session-a", a.to_str().unwrap()]).unwrap();
+        git(&repo, &["worktree", "add", "-b", "session-b", b.to_str().unwrap()]).unwrap();
+
+        // A commits its change, B leaves everything uncommitted
+        std::fs::write(a.join("shared.txt"), lines.replace("line 2\n", "line two\n").replace("line 15\n", "line fifteen\n")).unwrap();
+        std::fs::write(a.join("only_a.txt"), "a\n").unwrap();
+        std::fs::write(a.join("same.txt"), "agreed\n").unwrap();
+        git(&a, &["add", "-A"]).unwrap();
+        git(&a, &["commit", "-m", "a"]).unwrap();
+        std::fs::write(b.join("shared.txt"), lines.replace("line 3\n", "line three\n")).unwrap();
+        std::fs::write(b.join("same.txt"), "agreed\n").unwrap();
+        std::fs::write(b.join("only_b.txt"), "b\n").unwrap();
+
+        let WorktreeDiff { base, only_a, only_b, both } = diff_worktrees(&a, &b).unwrap();
+        assert_eq!(base, git(&repo, &["rev-parse", "HEAD"]).unwrap().trim());
+        assert_eq!(only_a.iter().map(|c| c.path.as_str()).collect::<Vec<_>>(), vec!["only_a.txt"]);
+        assert_eq!((only_b[0].path.as_str(), only_b[0].status.as_str(), only_b[0].additions), ("only_b.txt", "A", Some(1)));
+        assert_eq!(both.len(), 2);
+        assert!(both[0].path == "same.txt" && both[0].identical && both[0].conflicts.is_empty());
+        let shared = &both[1];
+        assert!(!shared.identical);
+        assert_eq!(shared.conflicts.len(), 1);
+        assert!(shared.conflicts[0].a.text.contains("+line two") && shared.conflicts[0].b.text.contains("+line three"));
+        // The real index was not touched
+        assert!(git(&b, &["diff", "--cached", "--name-only"]).unwrap().is_empty());
+
+        let other = tempfile::TempDir::new().unwrap();
+        git(other.path(), &["init"]).unwrap();
+        assert!(diff_worktrees(&a, other.path()).is_err());
+    }
+}
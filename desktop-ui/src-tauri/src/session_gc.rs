@@ -0,0 +1,406 @@
+//! Garbage collection of worktrees and branches left by finished sessions
+//!
+//! Deleting or archiving a session leaves its `orchestra/<code>` branch and its
+//! `.amp-worktrees` checkout behind. The collector walks those branches in the
+//! configured repositories, looks up the session each belongs to and removes the
+//! ones `SessionGcConfig` allows: live sessions are never touched, deleted and
+//! archived ones are kept for `keep_days`, and with `keep_unmerged` a branch
+//! holding commits found nowhere else is kept. A worktree with uncommitted
+//! changes is only removed when forced. Every run returns what it removed and
+//! what it kept, with the reason for each.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::app_state::{AppState, SessionGcConfig};
+use crate::commit_message::git;
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+
+const BRANCH_PREFIX: &str = "orchestra/";
+/// How often the scheduler re-reads its config while waiting for the next run
+const SCHEDULE_POLL: Duration = Duration::from_secs(60);
+
+/// A session branch and its worktree, if it still has one
+#[derive(Debug, Clone)]
+pub struct GcCandidate {
+    pub repo: PathBuf,
+    pub branch: String,
+    pub worktree: Option<PathBuf>,
+}
+
+impl GcCandidate {
+    /// Session ID or short code the branch was named after
+    fn session_key(&self) -> &str {
+        self.branch.trim_start_matches(BRANCH_PREFIX)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionState {
+    Live,
+    /// All threads archived; the time is the latest `archived_at`
+    Archived(DateTime<Utc>),
+    /// Not in `sessions`; the time is when it was trashed, if still known
+    Deleted(Option<DateTime<Utc>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Live sessions are not reported
+    Ignore,
+    Keep(String),
+    Remove(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcEntry {
+    pub repo: String,
+    pub branch: String,
+    pub worktree_path: Option<String>,
+    pub session_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub removed: Vec<GcEntry>,
+    pub kept: Vec<GcEntry>,
+    /// Repositories that could not be scanned or entries that failed to remove
+    pub errors: Vec<String>,
+}
+
+/// Payload of the `session_gc` event sent after a scheduled run removed something
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionGcEvent {
+    #[serde(flatten)]
+    pub report: GcReport,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc()))
+        .ok()
+}
+
+/// Session branches in `repo`, joined with the worktrees that have them checked out
+pub fn list_candidates(repo: &Path) -> Result<Vec<GcCandidate>, String> {
+    let refs = git(repo, &["for-each-ref", "--format=%(refname:short)", &format!("refs/heads/{}", BRANCH_PREFIX)])?;
+    let mut candidates: BTreeMap<String, Option<PathBuf>> =
+        refs.lines().filter(|l| !l.is_empty()).map(|b| (b.to_string(), None)).collect();
+
+    let mut worktree = None;
+    for line in git(repo, &["worktree", "list", "--porcelain"])?.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            worktree = Some(PathBuf::from(path));
+        } else if let Some(branch) = line.strip_prefix("branch refs/heads/") {
+            if let Some(slot) = candidates.get_mut(branch) {
+                *slot = worktree.clone();
+            }
+        }
+    }
+    Ok(candidates
+        .into_iter()
+        .map(|(branch, worktree)| GcCandidate { repo: repo.to_path_buf(), branch, worktree })
+        .collect())
+}
+
+/// State of the session a branch was named after, by ID or short code
+pub async fn session_state(db: &SqlitePool, key: &str) -> Result<SessionState, String> {
+    let session_id: Option<String> = sqlx::query_scalar("SELECT id FROM sessions WHERE id = ? OR short_code = ?")
+        .bind(key)
+        .bind(key)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to look up session {}: {}", key, e))?;
+
+    let Some(session_id) = session_id else {
+        let deleted_at: Option<String> = sqlx::query_scalar(
+            "SELECT MAX(deleted_at) FROM trash_items WHERE kind = 'session' AND (label = ? OR payload LIKE ?)",
+        )
+        .bind(key)
+        .bind(format!("%\"short_code\":\"{}\"%", key))
+        .fetch_one(db)
+        .await
+        .map_err(|e| format!("Failed to look up trashed session {}: {}", key, e))?;
+        return Ok(SessionState::Deleted(deleted_at.as_deref().and_then(parse_time)));
+    };
+
+    let (threads, open, archived_at): (i64, i64, Option<String>) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(SUM(archived_at IS NULL), 0), MAX(archived_at) FROM threads WHERE session_id = ?",
+    )
+    .bind(&session_id)
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to read threads of session {}: {}", session_id, e))?;
+    match archived_at.as_deref().and_then(parse_time) {
+        Some(archived_at) if threads > 0 && open == 0 => Ok(SessionState::Archived(archived_at)),
+        _ => Ok(SessionState::Live),
+    }
+}
+
+/// Commits on `branch` that no other branch or remote-tracking branch contains
+pub fn unmerged_commits(repo: &Path, branch: &str) -> Result<usize, String> {
+    // Patterns excluded from --branches are matched without the refs/heads/ prefix
+    let exclude = format!("--exclude={}", branch);
+    let count = git(repo, &["rev-list", "--count", &format!("refs/heads/{}", branch), "--not", &exclude, "--branches", "--remotes"])?;
+    count.trim().parse().map_err(|e| format!("Unexpected rev-list output {:?}: {}", count, e))
+}
+
+/// Whether a branch may be collected, checked cheapest first
+pub fn decide(
+    state: &SessionState,
+    config: &SessionGcConfig,
+    now: DateTime<Utc>,
+    unmerged: impl FnOnce() -> Result<usize, String>,
+    dirty: impl FnOnce() -> Result<bool, String>,
+    force: bool,
+) -> Decision {
+    let (label, since) = match state {
+        SessionState::Live => return Decision::Ignore,
+        SessionState::Archived(at) => ("archived", Some(*at)),
+        SessionState::Deleted(at) => ("deleted", *at),
+    };
+    if let Some(since) = since {
+        let until = since + ChronoDuration::days(config.keep_days as i64);
+        if until > now {
+            return Decision::Keep(format!("session {} {}; kept until {}", label, since.to_rfc3339(), until.to_rfc3339()));
+        }
+    }
+    if config.keep_unmerged {
+        match unmerged() {
+            Ok(0) => {}
+            Ok(n) => return Decision::Keep(format!("{} unmerged commits", n)),
+            Err(e) => return Decision::Keep(format!("could not check for unmerged commits: {}", e)),
+        }
+    }
+    if !force {
+        match dirty() {
+            Ok(false) => {}
+            Ok(true) => return Decision::Keep("worktree has uncommitted changes".to_string()),
+            Err(e) => return Decision::Keep(format!("could not check worktree status: {}", e)),
+        }
+    }
+    Decision::Remove(format!("session {}", label))
+}
+
+fn remove_candidate(candidate: &GcCandidate) -> Result<(), String> {
+    match &candidate.worktree {
+        // Cleanliness was checked in `decide` or skipped by `force`
+        Some(path) => crate::worktree::remove(path, &candidate.branch, true).map_err(|e| e.to_string()),
+        None => git(&candidate.repo, &["branch", "-D", &candidate.branch]).map(|_| ()),
+    }
+}
+
+/// Collect in `repos`. With `dry_run` the report lists what would be removed.
+pub async fn collect(
+    db: &SqlitePool,
+    repos: &[PathBuf],
+    config: &SessionGcConfig,
+    force: bool,
+    dry_run: bool,
+) -> GcReport {
+    let mut report = GcReport { dry_run, ..Default::default() };
+    let now = Utc::now();
+    for repo in repos {
+        let candidates = match list_candidates(repo) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                report.errors.push(format!("{}: {}", repo.display(), e));
+                continue;
+            }
+        };
+        for candidate in candidates {
+            let state = match session_state(db, candidate.session_key()).await {
+                Ok(state) => state,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            let decision = decide(
+                &state,
+                config,
+                now,
+                || unmerged_commits(&candidate.repo, &candidate.branch),
+                || match &candidate.worktree {
+                    Some(path) => match crate::worktree::check_worktree_clean(path) {
+                        Ok(()) => Ok(false),
+                        Err(crate::worktree::WorktreeError::DirtyRepository) => Ok(true),
+                        Err(e) => Err(e.to_string()),
+                    },
+                    None => Ok(false),
+                },
+                force,
+            );
+            let entry = |reason: String| GcEntry {
+                repo: candidate.repo.to_string_lossy().to_string(),
+                branch: candidate.branch.clone(),
+                worktree_path: candidate.worktree.as_ref().map(|p| p.to_string_lossy().to_string()),
+                session_id: crate::session_codes::resolve(candidate.session_key()),
+                reason,
+            };
+            match decision {
+                Decision::Ignore => {}
+                Decision::Keep(reason) => report.kept.push(entry(reason)),
+                Decision::Remove(reason) if dry_run => report.removed.push(entry(reason)),
+                Decision::Remove(reason) => match remove_candidate(&candidate) {
+                    Ok(()) => report.removed.push(entry(reason)),
+                    Err(e) => report.errors.push(format!("{}: {}", candidate.branch, e)),
+                },
+            }
+        }
+    }
+    report
+}
+
+/// The working directory's repository followed by the configured ones
+fn repositories(config: &SessionGcConfig) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    if let Ok(cwd) = std::env::current_dir() {
+        if let Ok(top) = git(&cwd, &["rev-parse", "--show-toplevel"]) {
+            repos.push(PathBuf::from(top.trim()));
+        }
+    }
+    repos.extend(config.repositories.iter().map(PathBuf::from));
+    let mut seen = HashSet::new();
+    repos.retain(|repo| seen.insert(repo.canonicalize().unwrap_or_else(|_| repo.clone())));
+    repos
+}
+
+/// Run the collector on `SessionGcConfig`'s schedule for the lifetime of the app
+pub fn spawn_collector(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run = std::time::Instant::now();
+        loop {
+            tokio::time::sleep(SCHEDULE_POLL).await;
+            let Some(config) = app_handle.try_state::<AppState>().map(|state| state.lock().unwrap().session_gc.clone()) else {
+                continue;
+            };
+            if !config.scheduled || last_run.elapsed() < Duration::from_secs(config.interval_secs) {
+                continue;
+            }
+            let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+                continue;
+            };
+            let db = profile_manager.db_pool.read().await;
+            let Some(db) = db.as_ref() else {
+                continue;
+            };
+            last_run = std::time::Instant::now();
+            let report = collect(db, &repositories(&config), &config, false, false).await;
+            for error in &report.errors {
+                log::warn!("Session GC: {}", error);
+            }
+            if !report.removed.is_empty() {
+                log::info!("Session GC removed {} branches", report.removed.len());
+                let message = tr!("notify-session-gc", count = report.removed.len());
+                let _ = app_handle.emit("session_gc", SessionGcEvent { report, message });
+            }
+        }
+    });
+}
+
+/// Collect now. `force` also removes worktrees with uncommitted changes.
+#[tauri::command]
+pub async fn run_session_gc(
+    force: Option<bool>,
+    dry_run: Option<bool>,
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<GcReport, String> {
+    let config = app_state.lock().unwrap().session_gc.clone();
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    Ok(collect(db, &repositories(&config), &config, force.unwrap_or(false), dry_run.unwrap_or(false)).await)
+}
+
+#[tauri::command]
+pub async fn get_session_gc_config(app_state: State<'_, AppState>) -> Result<SessionGcConfig, String> {
+    Ok(app_state.lock().unwrap().session_gc.clone())
+}
+
+#[tauri::command]
+pub async fn set_session_gc_config(config: SessionGcConfig, app_state: State<'_, AppState>) -> Result<(), String> {
+    if config.interval_secs == 0 {
+        return Err("GC interval must be greater than zero".to_string());
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.session_gc = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_collect_applies_policy() {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let commit = ["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-q", "-m"];
+        git(&repo, &["init", "-q"]).unwrap();
+        std::fs::write(repo.join("README.md"), "gc\n").unwrap();
+        git(&repo, &["add", "README.md"]).unwrap();
+        git(&repo, &[&commit[..], &["Initial commit"]].concat()).unwrap();
+
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('gc-live-0001', 'Live')").execute(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO trash_items (id, kind, label, deleted_at, expires_at)
+             VALUES ('t1', 'session', 'gc-recent-0001', ?, '2999-01-01T00:00:00Z')",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut worktrees = std::collections::HashMap::new();
+        for id in ["gc-live-0001", "gc-recent-0001", "gc-clean-0001", "gc-dirty-0001", "gc-ahead-0001"] {
+            worktrees.insert(id, crate::worktree::create(&repo, id).unwrap().path);
+        }
+        std::fs::write(worktrees["gc-dirty-0001"].join("notes.txt"), "wip").unwrap();
+        std::fs::write(worktrees["gc-ahead-0001"].join("feature.txt"), "done").unwrap();
+        git(&worktrees["gc-ahead-0001"], &["add", "feature.txt"]).unwrap();
+        git(&worktrees["gc-ahead-0001"], &[&commit[..], &["Feature"]].concat()).unwrap();
+
+        let config = SessionGcConfig { keep_days: 1, ..Default::default() };
+        let repos = vec![repo.clone()];
+        let reasons = |entries: &[GcEntry]| -> Vec<(String, String)> {
+            entries.iter().map(|e| (e.session_id.clone(), e.reason.clone())).collect()
+        };
+
+        let dry = collect(&pool, &repos, &config, false, true).await;
+        assert!(dry.errors.is_empty(), "{:?}", dry.errors);
+        assert_eq!(reasons(&dry.removed), vec![("gc-clean-0001".to_string(), "session deleted".to_string())]);
+        let kept = reasons(&dry.kept);
+        assert_eq!(kept.len(), 3);
+        assert!(kept.iter().any(|(id, reason)| id == "gc-ahead-0001" && reason == "1 unmerged commits"));
+        assert!(kept.iter().any(|(id, reason)| id == "gc-dirty-0001" && reason.contains("uncommitted")));
+        assert!(kept.iter().any(|(id, reason)| id == "gc-recent-0001" && reason.contains("kept until")));
+        assert!(worktrees["gc-clean-0001"].exists());
+
+        let report = collect(&pool, &repos, &config, true, false).await;
+        let removed: Vec<String> = report.removed.iter().map(|e| e.session_id.clone()).collect();
+        assert_eq!(removed, vec!["gc-clean-0001".to_string(), "gc-dirty-0001".to_string()]);
+        assert!(!worktrees["gc-dirty-0001"].exists());
+        assert!(worktrees["gc-live-0001"].exists() && worktrees["gc-ahead-0001"].exists());
+        let branches = git(&repo, &["branch", "--list", "orchestra/*"]).unwrap();
+        assert!(!branches.contains("gc-clean-0001") && branches.contains("gc-recent-0001"));
+    }
+}
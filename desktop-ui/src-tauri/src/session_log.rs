@@ -0,0 +1,341 @@
+//! Structured log of what happened around a session
+//!
+//! Spawn diagnostics, environment composition, Git operations and stream errors
+//! are recorded per session (chat session or thread) in a bounded buffer, next to
+//! the ordinary log lines. `tail_session_log` returns the latest entries and, with
+//! `follow`, streams new ones to the calling webview as `session_log` events until
+//! `stop_session_log_tail` is called or the webview closes.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, EventTarget, Manager, State, Webview};
+
+use crate::memory_manager::{CacheUnit, ReclaimableCache};
+use crate::stream_quarantine::QuarantinedLine;
+
+/// Entries kept per session; older ones are dropped first
+pub const MAX_LOG_ENTRIES: usize = 1000;
+/// Entries `tail_session_log` returns when no count is given
+pub const DEFAULT_TAIL_LINES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogCategory {
+    Spawn,
+    Env,
+    Git,
+    Stream,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionLogEntry {
+    /// Increases by one per entry of the session
+    pub seq: u64,
+    pub session_id: String,
+    pub timestamp: String,
+    pub category: LogCategory,
+    pub level: LogLevel,
+    pub message: String,
+    /// Details such as the command line of a spawn
+    pub fields: Value,
+}
+
+#[derive(Debug)]
+struct SessionLogBuffer {
+    next_seq: u64,
+    /// Serialized size and entry
+    entries: VecDeque<(usize, SessionLogEntry)>,
+    bytes: usize,
+    last_used: Instant,
+}
+
+impl Default for SessionLogBuffer {
+    fn default() -> Self {
+        Self { next_seq: 0, entries: VecDeque::new(), bytes: 0, last_used: Instant::now() }
+    }
+}
+
+#[derive(Default)]
+struct LogState {
+    buffers: HashMap<String, SessionLogBuffer>,
+    /// Follow id -> (session id, webview label)
+    followers: HashMap<String, (String, String)>,
+}
+
+#[derive(Clone, Default)]
+pub struct SessionLogs {
+    inner: Arc<Mutex<LogState>>,
+}
+
+impl SessionLogs {
+    /// Add an entry, returning it with the labels of the webviews following the session
+    pub fn append(
+        &self,
+        session_id: &str,
+        category: LogCategory,
+        level: LogLevel,
+        message: String,
+        fields: Value,
+    ) -> (SessionLogEntry, Vec<String>) {
+        let mut state = self.inner.lock().unwrap();
+        let buffer = state.buffers.entry(session_id.to_string()).or_default();
+        let entry = SessionLogEntry {
+            seq: buffer.next_seq,
+            session_id: session_id.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            category,
+            level,
+            message,
+            fields,
+        };
+        buffer.next_seq += 1;
+        if buffer.entries.len() >= MAX_LOG_ENTRIES {
+            if let Some((size, _)) = buffer.entries.pop_front() {
+                buffer.bytes -= size;
+            }
+        }
+        let size = serde_json::to_string(&entry).map_or(0, |s| s.len());
+        buffer.bytes += size;
+        buffer.last_used = Instant::now();
+        buffer.entries.push_back((size, entry.clone()));
+
+        let webviews = state
+            .followers
+            .values()
+            .filter(|(followed, _)| followed == session_id)
+            .map(|(_, webview)| webview.clone())
+            .collect();
+        (entry, webviews)
+    }
+
+    /// The last `lines` entries of a session, oldest first
+    pub fn tail(&self, session_id: &str, lines: usize) -> Vec<SessionLogEntry> {
+        let mut state = self.inner.lock().unwrap();
+        let Some(buffer) = state.buffers.get_mut(session_id) else {
+            return Vec::new();
+        };
+        buffer.last_used = Instant::now();
+        let skip = buffer.entries.len().saturating_sub(lines);
+        buffer.entries.iter().skip(skip).map(|(_, entry)| entry.clone()).collect()
+    }
+
+    pub fn follow(&self, session_id: &str, webview: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.inner
+            .lock()
+            .unwrap()
+            .followers
+            .insert(id.clone(), (session_id.to_string(), webview.to_string()));
+        id
+    }
+
+    pub fn unfollow(&self, follow_id: &str) -> bool {
+        self.inner.lock().unwrap().followers.remove(follow_id).is_some()
+    }
+
+    /// Stop streaming to a closed webview
+    pub fn remove_webview(&self, webview: &str) {
+        self.inner.lock().unwrap().followers.retain(|_, (_, label)| label != webview);
+    }
+}
+
+/// Whole session logs, least recently used first. Evicting one keeps its
+/// sequence counter so followers can tell entries were dropped.
+impl ReclaimableCache for SessionLogs {
+    fn subsystem(&self) -> &'static str {
+        "session_logs"
+    }
+
+    fn units(&self) -> Vec<CacheUnit> {
+        self.inner
+            .lock()
+            .unwrap()
+            .buffers
+            .iter()
+            .filter(|(_, buffer)| !buffer.entries.is_empty())
+            .map(|(session_id, buffer)| CacheUnit {
+                key: session_id.clone(),
+                bytes: buffer.bytes,
+                entries: buffer.entries.len(),
+                last_used: buffer.last_used,
+                replayable: false,
+            })
+            .collect()
+    }
+
+    fn evict(&self, key: &str) -> usize {
+        let mut state = self.inner.lock().unwrap();
+        let Some(buffer) = state.buffers.get_mut(key) else {
+            return 0;
+        };
+        buffer.entries.clear();
+        std::mem::take(&mut buffer.bytes)
+    }
+}
+
+/// Record an entry for a session and send it to the webviews following it
+pub fn record(
+    app_handle: &AppHandle,
+    session_id: &str,
+    category: LogCategory,
+    level: LogLevel,
+    message: impl Into<String>,
+    fields: Value,
+) {
+    let Some(logs) = app_handle.try_state::<SessionLogs>() else {
+        return;
+    };
+    let (entry, webviews) = logs.append(session_id, category, level, message.into(), fields);
+    if webviews.is_empty() {
+        return;
+    }
+    let _ = app_handle.emit_filter("session_log", entry, |target| match target {
+        EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label }
+        | EventTarget::Window { label }
+        | EventTarget::AnyLabel { label } => webviews.contains(label),
+        _ => false,
+    });
+}
+
+/// Record the parts of a spawn environment worth seeing when debugging, leaving
+/// out values that may hold credentials
+pub fn record_env(app_handle: &AppHandle, session_id: &str, env: &HashMap<String, String>) {
+    let mut keys: Vec<&str> = env.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    let shown = ["AMP_URL", "AMP_BIN", "AMP_CLI_PATH", "AMP_TOOLBOX_PATHS", "AMP_EXPERIMENTAL_AGENT_MODE", "AMP_MODEL"];
+    let values: serde_json::Map<String, Value> = shown
+        .iter()
+        .filter_map(|key| env.get(*key).map(|value| (key.to_string(), Value::from(value.as_str()))))
+        .collect();
+    record(
+        app_handle,
+        session_id,
+        LogCategory::Env,
+        LogLevel::Info,
+        format!("Composed environment with {} variables", keys.len()),
+        serde_json::json!({ "values": values, "keys": keys }),
+    );
+}
+
+/// Record the outcome of starting a session's CLI process
+pub fn record_spawn(
+    app_handle: &AppHandle,
+    session_id: &str,
+    program: &str,
+    args: &[String],
+    working_dir: &Path,
+    outcome: Result<Option<u32>, &std::io::Error>,
+) {
+    let mut fields = serde_json::json!({ "program": program, "args": args, "working_dir": working_dir });
+    let (level, message) = match outcome {
+        Ok(pid) => {
+            fields["pid"] = serde_json::json!(pid);
+            (LogLevel::Info, format!("Started {}", program))
+        }
+        Err(e) => (LogLevel::Error, format!("Failed to start {}: {}", program, e)),
+    };
+    record(app_handle, session_id, LogCategory::Spawn, level, message, fields);
+}
+
+/// Record a stream line that failed validation and was quarantined
+pub fn record_quarantined(app_handle: &AppHandle, line: &QuarantinedLine) {
+    record(
+        app_handle,
+        &line.session_id,
+        LogCategory::Stream,
+        LogLevel::Warn,
+        format!("Malformed {} stream line: {}", line.stream, line.violation.reason),
+        serde_json::json!({
+            "event_type": line.violation.event_type,
+            "byte_offset": line.byte_offset,
+            "line_bytes": line.line_bytes,
+        }),
+    );
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionLogTail {
+    pub session_id: String,
+    /// Latest entries, oldest first
+    pub entries: Vec<SessionLogEntry>,
+    /// Pass to `stop_session_log_tail`; set when following
+    pub follow_id: Option<String>,
+}
+
+/// Latest log entries of a session. With `follow`, later entries arrive as
+/// `session_log` events; any whose `seq` was already returned can be skipped.
+#[tauri::command]
+pub async fn tail_session_log(
+    session_id: String,
+    follow: bool,
+    lines: Option<usize>,
+    webview: Webview,
+    logs: State<'_, SessionLogs>,
+) -> Result<SessionLogTail, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    // Follow first so nothing recorded in between is missed
+    let follow_id = follow.then(|| logs.follow(&session_id, webview.label()));
+    let entries = logs.tail(&session_id, lines.unwrap_or(DEFAULT_TAIL_LINES));
+    Ok(SessionLogTail { session_id, entries, follow_id })
+}
+
+#[tauri::command]
+pub async fn stop_session_log_tail(follow_id: String, logs: State<'_, SessionLogs>) -> Result<bool, String> {
+    Ok(logs.unfollow(&follow_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append(logs: &SessionLogs, session_id: &str, message: &str) -> (SessionLogEntry, Vec<String>) {
+        logs.append(session_id, LogCategory::Spawn, LogLevel::Info, message.to_string(), Value::Null)
+    }
+
+    #[test]
+    fn test_tail_is_bounded_and_per_session() {
+        let logs = SessionLogs::default();
+        for i in 0..MAX_LOG_ENTRIES + 5 {
+            append(&logs, "s1", &format!("entry {}", i));
+        }
+        append(&logs, "s2", "other");
+
+        let tail = logs.tail("s1", 3);
+        assert_eq!(tail.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![1002, 1003, 1004]);
+        assert_eq!(logs.tail("s1", usize::MAX).len(), MAX_LOG_ENTRIES);
+        assert_eq!(logs.tail("s2", 10)[0].seq, 0);
+        assert!(logs.tail("missing", 10).is_empty());
+
+        assert!(logs.evict("s1") > 0);
+        assert!(logs.tail("s1", 10).is_empty());
+        assert_eq!(append(&logs, "s1", "after").0.seq, 1005);
+    }
+
+    #[test]
+    fn test_followers_get_their_session_only() {
+        let logs = SessionLogs::default();
+        let follow_id = logs.follow("s1", "main");
+        logs.follow("s2", "other");
+        assert_eq!(append(&logs, "s1", "spawned").1, vec!["main".to_string()]);
+
+        assert!(logs.unfollow(&follow_id));
+        assert!(!logs.unfollow(&follow_id));
+        assert!(append(&logs, "s1", "spawned").1.is_empty());
+
+        logs.remove_webview("other");
+        assert!(append(&logs, "s2", "spawned").1.is_empty());
+    }
+}
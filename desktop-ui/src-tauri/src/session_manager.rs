@@ -133,7 +133,7 @@ impl EnhancedSessionManager {
         if self.config.enable_worktrees {
             if let Some(worktree_manager) = &self.worktree_manager {
                 let worktree_guard = worktree_manager
-                    .create_session_worktree(&session.id, Some(&session.base_branch))
+                    .create_session_worktree(&session.id, Some(&session.base_branch), Some(&session.prompt))
                     .await
                     .map_err(|e| anyhow!("Failed to create worktree: {}", e))?;
                 
@@ -262,14 +262,27 @@ impl EnhancedSessionManager {
         Ok(session.status)
     }
 
+    /// Get a stored session by id
+    pub async fn get_session(&self, session_id: &SessionId) -> Result<Session> {
+        self.store.get_session(session_id).await
+            .map_err(|e| anyhow!("Failed to get session: {}", e))?
+            .ok_or_else(|| anyhow!("Session not found: {}", session_id))
+    }
+
     /// Get current session metrics
     pub async fn get_metrics(&self) -> SessionMetrics {
         self.metrics.read().await.clone()
     }
 
+    /// Environment a session's agent process starts with: the session's variables, over
+    /// this process's own unless the session is strictly isolated
+    pub fn process_env(&self, session: &Session) -> HashMap<String, String> {
+        session.runtime_config.process_env()
+    }
+
     /// Compose the runtime environment for a session
     async fn compose_environment(&self, session: &Session) -> Result<ComposeResult> {
-        let mut env = std::env::vars().collect::<HashMap<String, String>>();
+        let mut env = self.process_env(session);
         
         // Create a runtime environment configured for this session
         let mut runtime_env = self.runtime_env.clone();
@@ -306,9 +319,8 @@ impl EnhancedSessionManager {
             cmd.current_dir(&session.worktree_path);
         }
 
-        // Apply environment variables - get from current environment with modifications
-        let env = std::env::vars().collect::<HashMap<String, String>>();
-        cmd.envs(&env);
+        cmd.env_clear().envs(self.process_env(session));
+        unified_core::process_limits::apply_rlimits(&mut cmd, &session.runtime_config.process_limits);
 
         let child = cmd.spawn()
             .map_err(|e| anyhow!("Failed to spawn Amp CLI process: {}", e))?;
@@ -0,0 +1,102 @@
+//! Running sessions by id, locked one session at a time
+//!
+//! The map is sharded, so finding one session never waits on another, and each
+//! session sits behind its own async lock, so a slow operation on one (a send
+//! that records the prompt, a restart with a new environment) only holds up
+//! that session. Shard guards are never held across an await: handles are
+//! cloned out of the map and locked afterwards.
+//!
+//! Kept free of other crate modules so `benches/chat_send.rs` can include it.
+
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// One session, shared between the map and whoever is working with it
+pub type SessionHandle<T> = Arc<Mutex<T>>;
+
+pub struct SessionMap<T> {
+    sessions: DashMap<String, SessionHandle<T>>,
+}
+
+impl<T> Default for SessionMap<T> {
+    fn default() -> Self {
+        Self { sessions: DashMap::new() }
+    }
+}
+
+impl<T> SessionMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a session, returning the one it replaces. A removed or replaced
+    /// session is dropped once nobody holds its handle.
+    pub fn insert(&self, id: String, session: T) -> Option<SessionHandle<T>> {
+        self.sessions.insert(id, Arc::new(Mutex::new(session)))
+    }
+
+    pub fn get(&self, id: &str) -> Option<SessionHandle<T>> {
+        self.sessions.get(id).map(|entry| entry.value().clone())
+    }
+
+    pub fn remove(&self, id: &str) -> Option<SessionHandle<T>> {
+        self.sessions.remove(id).map(|(_, session)| session)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.sessions.contains_key(id)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.sessions.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_busy_session_does_not_block_others() {
+        let map = SessionMap::new();
+        map.insert("a".to_string(), 0);
+        map.insert("b".to_string(), 0);
+
+        let a = map.get("a").unwrap();
+        let _held = a.lock().await;
+        let b = map.get("b").unwrap();
+        let mut b = tokio::time::timeout(Duration::from_millis(100), b.lock()).await.expect("b is free");
+        *b += 1;
+        assert!(tokio::time::timeout(Duration::from_millis(20), a.lock()).await.is_err(), "a stays locked");
+
+        let mut ids = map.ids();
+        ids.sort();
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_removed_session_lives_while_held() {
+        let map = SessionMap::new();
+        map.insert("a".to_string(), vec![1]);
+        let held = map.get("a").unwrap();
+
+        let replaced = map.insert("a".to_string(), vec![2]).unwrap();
+        assert!(Arc::ptr_eq(&held, &replaced));
+        assert_eq!(*map.get("a").unwrap().lock().await, [2]);
+
+        map.remove("a").unwrap();
+        assert!(!map.contains("a") && map.is_empty());
+        held.lock().await.push(3);
+        assert_eq!(*held.lock().await, [1, 3]);
+    }
+}
@@ -0,0 +1,542 @@
+//! Moving threads between sessions and merging sessions
+//!
+//! Both operations run in a single transaction that is rolled back in dry-run
+//! mode, so a dry run reports exactly what a real run would change. When two
+//! sessions are merged, a thread whose messages are a prefix of another thread's
+//! (the same conversation started twice) is folded into the longer one instead
+//! of being kept as a duplicate.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tauri::State;
+
+use crate::conflicts::{Conflict, ConflictKind, ConflictSource};
+use crate::profile_auth::ProfileManager;
+use crate::session_commands::AmpSessionMap;
+
+/// Tables keyed by a thread id that follow a thread when it is folded into another
+const THREAD_KEYED_TABLES: &[(&str, &str)] = &[
+    ("session_tags", "session_id"),
+    ("session_outcomes", "session_id"),
+    ("thread_feedback", "thread_id"),
+];
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum SessionMergeError {
+    #[error("Session {session_id} not found")]
+    SessionNotFound { session_id: String },
+
+    #[error("Thread {thread_id} not found")]
+    ThreadNotFound { thread_id: String },
+
+    #[error("Source and target are the same session")]
+    SameSession { session_id: String },
+
+    #[error("Thread {thread_id} is running; stop it before moving it")]
+    ThreadRunning { thread_id: String },
+
+    #[error("Merge would leave {count} dangling references")]
+    IntegrityViolation { count: usize, tables: Vec<String> },
+
+    #[error("Database error: {reason}")]
+    Database { reason: String },
+}
+
+impl From<sqlx::Error> for SessionMergeError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database { reason: e.to_string() }
+    }
+}
+
+impl SessionMergeError {
+    /// JSON form returned to the frontend: the error fields plus a readable `message`
+    pub fn to_command_error(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("message".to_string(), serde_json::Value::String(self.to_string()));
+        }
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeduplicatedThread {
+    pub removed_thread_id: String,
+    pub kept_thread_id: String,
+    pub messages_removed: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub dry_run: bool,
+    pub target_session_id: String,
+    pub threads_moved: Vec<String>,
+    pub threads_deduplicated: Vec<DeduplicatedThread>,
+    pub messages_moved: i64,
+    pub source_session_deleted: bool,
+    /// Non-blocking differences, e.g. sessions bound to different profiles
+    pub warnings: Vec<String>,
+    /// Choices made for the user, recorded for review unless this is a dry run
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct SessionRow {
+    id: String,
+    profile_id: Option<i64>,
+    amp_profile_id: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ThreadRow {
+    id: String,
+    session_id: String,
+    context: String,
+    agent_mode: Option<String>,
+}
+
+pub struct SessionMergeStore {
+    db: SqlitePool,
+}
+
+impl SessionMergeStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Thread ids belonging to a session, for checking which ones are running
+    pub async fn thread_ids(&self, session_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT id FROM threads WHERE session_id = ?")
+            .bind(session_id)
+            .fetch_all(&self.db)
+            .await
+    }
+
+    /// Move one thread into another session, keeping its messages and history
+    pub async fn move_thread(
+        &self,
+        thread_id: &str,
+        target_session_id: &str,
+        dry_run: bool,
+    ) -> Result<MergeReport, SessionMergeError> {
+        let mut tx = self.db.begin().await?;
+
+        let thread = get_thread(&mut tx, thread_id)
+            .await?
+            .ok_or_else(|| SessionMergeError::ThreadNotFound { thread_id: thread_id.to_string() })?;
+        if thread.session_id == target_session_id {
+            return Err(SessionMergeError::SameSession { session_id: target_session_id.to_string() });
+        }
+        let source = require_session(&mut tx, &thread.session_id).await?;
+        let target = require_session(&mut tx, target_session_id).await?;
+
+        let mut report = MergeReport {
+            dry_run,
+            target_session_id: target.id.clone(),
+            warnings: binding_warnings(&source, &target),
+            ..Default::default()
+        };
+        report.messages_moved = reassign_thread(&mut tx, &thread.id, &target.id).await?;
+        report.threads_moved.push(thread.id);
+
+        finish(tx, &[&source.id, &target.id], None, &[], dry_run).await?;
+        Ok(report)
+    }
+
+    /// Move every thread of `source` into `target`, fold duplicate conversations
+    /// together, and delete the emptied source session
+    pub async fn merge_sessions(
+        &self,
+        source_session_id: &str,
+        target_session_id: &str,
+        dry_run: bool,
+    ) -> Result<MergeReport, SessionMergeError> {
+        if source_session_id == target_session_id {
+            return Err(SessionMergeError::SameSession { session_id: source_session_id.to_string() });
+        }
+        let mut tx = self.db.begin().await?;
+        let source = require_session(&mut tx, source_session_id).await?;
+        let target = require_session(&mut tx, target_session_id).await?;
+
+        let mut report = MergeReport {
+            dry_run,
+            target_session_id: target.id.clone(),
+            warnings: binding_warnings(&source, &target),
+            ..Default::default()
+        };
+
+        if let Some(conflict) = binding_conflict(&source, &target) {
+            crate::conflicts::record(&mut tx, &conflict).await?;
+            report.conflicts.push(conflict);
+        }
+
+        let mut existing = Vec::new();
+        for thread in session_threads(&mut tx, &target.id).await? {
+            let messages = message_keys(&mut tx, &thread.id).await?;
+            existing.push((thread, messages));
+        }
+
+        // Oldest first, so the merged session reads in the order the threads were started
+        for thread in session_threads(&mut tx, &source.id).await? {
+            let messages = message_keys(&mut tx, &thread.id).await?;
+            let duplicate = existing.iter().position(|(other, other_messages)| {
+                other.context == thread.context
+                    && other.agent_mode == thread.agent_mode
+                    && !messages.is_empty()
+                    && !other_messages.is_empty()
+                    && (other_messages.starts_with(&messages) || messages.starts_with(other_messages))
+            });
+
+            match duplicate {
+                Some(index) => {
+                    let (other, other_messages) = &existing[index];
+                    // Keep whichever copy has the full conversation
+                    let (kept, removed, removed_count) = if messages.len() > other_messages.len() {
+                        (thread.id.clone(), other.id.clone(), other_messages.len())
+                    } else {
+                        (other.id.clone(), thread.id.clone(), messages.len())
+                    };
+                    if kept == thread.id {
+                        report.messages_moved += reassign_thread(&mut tx, &thread.id, &target.id).await?;
+                        report.threads_moved.push(thread.id.clone());
+                        existing[index] = (thread.clone(), messages);
+                    }
+                    fold_thread(&mut tx, &removed, &kept).await?;
+                    // The removed copy may itself have been moved in from the source earlier
+                    report.threads_moved.retain(|id| *id != removed);
+                    report.threads_deduplicated.push(DeduplicatedThread {
+                        removed_thread_id: removed,
+                        kept_thread_id: kept,
+                        messages_removed: removed_count as i64,
+                    });
+                }
+                None => {
+                    report.messages_moved += reassign_thread(&mut tx, &thread.id, &target.id).await?;
+                    report.threads_moved.push(thread.id.clone());
+                    existing.push((thread, messages));
+                }
+            }
+        }
+
+        sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(&source.id)
+            .execute(&mut *tx)
+            .await?;
+        report.source_session_deleted = true;
+
+        let removed: Vec<&str> = report.threads_deduplicated.iter().map(|d| d.removed_thread_id.as_str()).collect();
+        finish(tx, &[&target.id], Some(&source.id), &removed, dry_run).await?;
+        Ok(report)
+    }
+}
+
+async fn require_session(tx: &mut Transaction<'_, Sqlite>, session_id: &str) -> Result<SessionRow, SessionMergeError> {
+    sqlx::query_as::<_, SessionRow>("SELECT id, profile_id, amp_profile_id FROM sessions WHERE id = ?")
+        .bind(session_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| SessionMergeError::SessionNotFound { session_id: session_id.to_string() })
+}
+
+async fn get_thread(tx: &mut Transaction<'_, Sqlite>, thread_id: &str) -> Result<Option<ThreadRow>, sqlx::Error> {
+    sqlx::query_as::<_, ThreadRow>("SELECT id, session_id, context, agent_mode FROM threads WHERE id = ?")
+        .bind(thread_id)
+        .fetch_optional(&mut **tx)
+        .await
+}
+
+async fn session_threads(tx: &mut Transaction<'_, Sqlite>, session_id: &str) -> Result<Vec<ThreadRow>, sqlx::Error> {
+    sqlx::query_as::<_, ThreadRow>(
+        "SELECT id, session_id, context, agent_mode FROM threads
+         WHERE session_id = ? ORDER BY created_at ASC, id ASC"
+    )
+    .bind(session_id)
+    .fetch_all(&mut **tx)
+    .await
+}
+
+/// A thread's conversation as (role, content) pairs in chronological order
+async fn message_keys(tx: &mut Transaction<'_, Sqlite>, thread_id: &str) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, Option<String>)>(
+        "SELECT role, content, content_ref FROM messages WHERE thread_id = ? ORDER BY created_at ASC, rowid ASC"
+    )
+    .bind(thread_id)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(role, content, content_ref)| (role, crate::message_content::resolve_content(content, content_ref)))
+        .collect())
+}
+
+fn binding_warnings(source: &SessionRow, target: &SessionRow) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if source.amp_profile_id != target.amp_profile_id {
+        warnings.push(format!(
+            "Sessions are bound to different Amp profiles ({:?} and {:?}); moved threads will run as {:?}",
+            source.amp_profile_id, target.amp_profile_id, target.amp_profile_id
+        ));
+    }
+    if source.profile_id != target.profile_id {
+        warnings.push(format!(
+            "Sessions use different toolbox profiles ({:?} and {:?})",
+            source.profile_id, target.profile_id
+        ));
+    }
+    warnings
+}
+
+/// The merged session keeps the target's profiles; the source's stay available as an option
+fn binding_conflict(source: &SessionRow, target: &SessionRow) -> Option<Conflict> {
+    if source.profile_id == target.profile_id && source.amp_profile_id == target.amp_profile_id {
+        return None;
+    }
+    let binding = |row: &SessionRow| serde_json::json!({ "profile_id": row.profile_id, "amp_profile_id": row.amp_profile_id });
+    let options = vec![Conflict::option("target", binding(target)), Conflict::option("source", binding(source))];
+    Some(Conflict::new(ConflictSource::Merge, ConflictKind::Binding, &target.id, None, options, "target"))
+}
+
+/// Point a thread at another session. Returns the number of messages it carries.
+async fn reassign_thread(tx: &mut Transaction<'_, Sqlite>, thread_id: &str, session_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query("UPDATE threads SET session_id = ?, updated_at = (datetime('now', 'utc') || 'Z') WHERE id = ?")
+        .bind(session_id)
+        .bind(thread_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE thread_id = ?")
+        .bind(thread_id)
+        .fetch_one(&mut **tx)
+        .await
+}
+
+/// Delete a duplicate thread, carrying its tags, outcome and ratings over to the kept one
+async fn fold_thread(tx: &mut Transaction<'_, Sqlite>, removed: &str, kept: &str) -> Result<(), sqlx::Error> {
+    for (table, column) in THREAD_KEYED_TABLES {
+        // OR IGNORE keeps the kept thread's row where both have one
+        let sql = format!("UPDATE OR IGNORE {table} SET {column} = ? WHERE {column} = ?");
+        sqlx::query(&sql).bind(kept).bind(removed).execute(&mut **tx).await?;
+        let sql = format!("DELETE FROM {table} WHERE {column} = ?");
+        sqlx::query(&sql).bind(removed).execute(&mut **tx).await?;
+    }
+    sqlx::query("DELETE FROM messages WHERE thread_id = ?")
+        .bind(removed)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM threads WHERE id = ?")
+        .bind(removed)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Verify nothing still points at the deleted session or threads, touch the
+/// affected sessions, then commit or roll back
+async fn finish(
+    mut tx: Transaction<'_, Sqlite>,
+    sessions: &[&str],
+    removed_session: Option<&str>,
+    removed_threads: &[&str],
+    dry_run: bool,
+) -> Result<(), SessionMergeError> {
+    // Checked explicitly because foreign key enforcement may be off for this connection
+    let mut dangling = Vec::new();
+    if let Some(session_id) = removed_session {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM threads WHERE session_id = ?")
+            .bind(session_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        dangling.push(("threads", count));
+    }
+    for thread_id in removed_threads {
+        let tables = std::iter::once(&("messages", "thread_id")).chain(THREAD_KEYED_TABLES);
+        for (table, column) in tables {
+            let sql = format!("SELECT COUNT(*) FROM {table} WHERE {column} = ?");
+            let count: i64 = sqlx::query_scalar(&sql).bind(thread_id).fetch_one(&mut *tx).await?;
+            dangling.push((table, count));
+        }
+    }
+    dangling.retain(|(_, count)| *count > 0);
+    if !dangling.is_empty() {
+        let mut tables: Vec<String> = dangling.iter().map(|(t, _)| t.to_string()).collect();
+        tables.sort();
+        tables.dedup();
+        let count = dangling.iter().map(|(_, c)| *c as usize).sum();
+        return Err(SessionMergeError::IntegrityViolation { count, tables });
+    }
+
+    for session_id in sessions {
+        sqlx::query("UPDATE sessions SET updated_at = (datetime('now', 'utc') || 'Z') WHERE id = ?")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+fn ensure_not_running(amp_sessions: &AmpSessionMap, thread_ids: &[String]) -> Result<(), String> {
+    match thread_ids.iter().find(|id| amp_sessions.contains(id)) {
+        Some(id) => Err(SessionMergeError::ThreadRunning { thread_id: id.clone() }.to_command_error()),
+        None => Ok(()),
+    }
+}
+
+/// Move a thread to another session. With `dry_run` nothing is changed.
+#[tauri::command]
+pub async fn thread_move(
+    thread_id: String,
+    target_session_id: String,
+    dry_run: Option<bool>,
+    amp_sessions: State<'_, AmpSessionMap>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<MergeReport, String> {
+    let target_session_id = crate::session_codes::resolve(&target_session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    ensure_not_running(&amp_sessions, std::slice::from_ref(&thread_id))?;
+    SessionMergeStore::new(db.clone())
+        .move_thread(&thread_id, &target_session_id, dry_run.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_command_error())
+}
+
+/// Merge `source_session_id` into `target_session_id`. With `dry_run` nothing is changed.
+#[tauri::command]
+pub async fn session_merge(
+    source_session_id: String,
+    target_session_id: String,
+    dry_run: Option<bool>,
+    amp_sessions: State<'_, AmpSessionMap>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<MergeReport, String> {
+    let source_session_id = crate::session_codes::resolve(&source_session_id);
+    let target_session_id = crate::session_codes::resolve(&target_session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = SessionMergeStore::new(db.clone());
+
+    let thread_ids = store
+        .thread_ids(&source_session_id)
+        .await
+        .map_err(|e| format!("Failed to list session threads: {}", e))?;
+    ensure_not_running(&amp_sessions, &thread_ids)?;
+
+    store
+        .merge_sessions(&source_session_id, &target_session_id, dry_run.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_command_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    async fn add_thread(pool: &SqlitePool, session_id: &str, thread_id: &str, created_at: &str, messages: &[&str]) {
+        sqlx::query("INSERT OR IGNORE INTO sessions (id, title) VALUES (?, ?)")
+            .bind(session_id)
+            .bind(session_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, created_at) VALUES (?, ?, 'production', ?)")
+            .bind(thread_id)
+            .bind(session_id)
+            .bind(created_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        for (i, content) in messages.iter().enumerate() {
+            sqlx::query("INSERT INTO messages (id, thread_id, role, content, created_at) VALUES (?, ?, 'user', ?, ?)")
+                .bind(format!("{}-{}", thread_id, i))
+                .bind(thread_id)
+                .bind(content)
+                .bind(format!("{}-{:03}", created_at, i))
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn threads_of(pool: &SqlitePool, session_id: &str) -> Vec<String> {
+        sqlx::query_scalar("SELECT id FROM threads WHERE session_id = ? ORDER BY created_at")
+            .bind(session_id)
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_move_thread() {
+        let pool = test_pool().await;
+        add_thread(&pool, "a", "t1", "2024-01-01", &["hello", "world"]).await;
+        add_thread(&pool, "b", "t2", "2024-01-02", &["other"]).await;
+        let store = SessionMergeStore::new(pool.clone());
+
+        let report = store.move_thread("t1", "b", true).await.unwrap();
+        assert_eq!(report.messages_moved, 2);
+        assert_eq!(threads_of(&pool, "a").await, vec!["t1"], "dry run must not change anything");
+
+        store.move_thread("t1", "b", false).await.unwrap();
+        assert_eq!(threads_of(&pool, "b").await, vec!["t1", "t2"]);
+
+        assert!(matches!(store.move_thread("t1", "b", false).await, Err(SessionMergeError::SameSession { .. })));
+        assert!(matches!(store.move_thread("t1", "zzz", false).await, Err(SessionMergeError::SessionNotFound { .. })));
+        assert!(matches!(store.move_thread("nope", "b", false).await, Err(SessionMergeError::ThreadNotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_merge_deduplicates_repeated_conversations() {
+        let pool = test_pool().await;
+        // t1 and t3 are the same conversation, t3 got further
+        add_thread(&pool, "target", "t1", "2024-01-01", &["fix the build", "done"]).await;
+        add_thread(&pool, "source", "t2", "2024-01-02", &["add docs"]).await;
+        add_thread(&pool, "source", "t3", "2024-01-03", &["fix the build", "done", "now run tests"]).await;
+        sqlx::query("INSERT INTO session_tags (session_id, tag) VALUES ('t1', 'ci')").execute(&pool).await.unwrap();
+        sqlx::query("UPDATE sessions SET amp_profile_id = 'dev' WHERE id = 'source'").execute(&pool).await.unwrap();
+        let store = SessionMergeStore::new(pool.clone());
+
+        let preview = store.merge_sessions("source", "target", true).await.unwrap();
+        assert_eq!(threads_of(&pool, "source").await.len(), 2);
+
+        let report = store.merge_sessions("source", "target", false).await.unwrap();
+        assert_eq!(report.threads_moved, preview.threads_moved);
+        assert_eq!(report.threads_moved, vec!["t2", "t3"]);
+        assert_eq!(report.threads_deduplicated.len(), 1);
+        assert_eq!(report.threads_deduplicated[0].removed_thread_id, "t1");
+        assert_eq!(report.threads_deduplicated[0].kept_thread_id, "t3");
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].options[1].value["amp_profile_id"], "dev");
+        let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conflicts").fetch_one(&pool).await.unwrap();
+        assert_eq!(recorded, 1, "the dry run must not record its conflict");
+
+        assert_eq!(threads_of(&pool, "target").await, vec!["t2", "t3"]);
+        let sessions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sessions").fetch_one(&pool).await.unwrap();
+        assert_eq!(sessions, 1);
+        let orphans: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE thread_id = 't1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(orphans, 0);
+        let tag: String = sqlx::query_scalar("SELECT session_id FROM session_tags WHERE tag = 'ci'").fetch_one(&pool).await.unwrap();
+        assert_eq!(tag, "t3");
+    }
+
+    #[tokio::test]
+    async fn test_merge_rejects_missing_and_same_session() {
+        let pool = test_pool().await;
+        add_thread(&pool, "a", "t1", "2024-01-01", &["hi"]).await;
+        let store = SessionMergeStore::new(pool.clone());
+
+        assert!(matches!(store.merge_sessions("a", "a", false).await, Err(SessionMergeError::SameSession { .. })));
+        let err = store.merge_sessions("a", "missing", false).await.unwrap_err();
+        let json: serde_json::Value = serde_json::from_str(&err.to_command_error()).unwrap();
+        assert_eq!(json["code"], "session_not_found");
+        assert_eq!(threads_of(&pool, "a").await, vec!["t1"]);
+    }
+}
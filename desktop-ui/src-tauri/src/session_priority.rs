@@ -0,0 +1,361 @@
+//! Session priority labels
+//!
+//! Sessions and threads are `normal` unless labelled with `set_session_priority`.
+//! Labels are stored in `session_priorities` and cached in memory. Priority decides:
+//! - process slots: batch cases share the batch engine's concurrency limit with
+//!   live high-priority sessions, so a queued case waits while a high-priority
+//!   session holds a slot. Cases already running are left alone.
+//! - notifications: a session that finishes a turn is awaiting input. High
+//!   priority sessions emit `session_awaiting_input` at once, others after waiting
+//!   `notify_delay`, checked on the activity heartbeat.
+//! - the attention queue: `get_attention_queue` lists sessions awaiting input,
+//!   highest priority first, then longest waiting.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Notify;
+
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl SessionPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(Self::Low),
+            "normal" => Some(Self::Normal),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    /// How long a session awaits input before it is announced
+    pub fn notify_delay(&self) -> Duration {
+        match self {
+            Self::High => Duration::ZERO,
+            Self::Normal => Duration::from_secs(60),
+            Self::Low => Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+static PRIORITIES: Lazy<DashMap<String, SessionPriority>> = Lazy::new(DashMap::new);
+
+pub fn priority_of(session_id: &str) -> SessionPriority {
+    PRIORITIES.get(session_id).map(|p| *p).unwrap_or_default()
+}
+
+/// Cache stored priorities
+pub async fn load(db: &SqlitePool) -> Result<usize, String> {
+    let rows = sqlx::query_as::<_, (String, String)>("SELECT session_id, priority FROM session_priorities")
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to load session priorities: {}", e))?;
+    let count = rows.len();
+    for (session_id, priority) in rows {
+        PRIORITIES.insert(session_id, SessionPriority::parse(&priority).unwrap_or_default());
+    }
+    Ok(count)
+}
+
+pub async fn store_priority(db: &SqlitePool, session_id: &str, priority: SessionPriority) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO session_priorities (session_id, priority) VALUES (?, ?)
+         ON CONFLICT(session_id) DO UPDATE SET priority = excluded.priority, updated_at = (datetime('now', 'utc') || 'Z')",
+    )
+    .bind(session_id)
+    .bind(priority.as_str())
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to store session priority: {}", e))?;
+    PRIORITIES.insert(session_id.to_string(), priority);
+    PROCESS_SLOTS.changed.notify_waiters();
+    Ok(())
+}
+
+#[derive(Default)]
+struct SlotState {
+    batch: usize,
+    live: HashSet<String>,
+}
+
+/// Process slots shared by batch cases and live high-priority sessions
+#[derive(Default)]
+pub struct ProcessSlots {
+    state: Mutex<SlotState>,
+    changed: Notify,
+}
+
+pub static PROCESS_SLOTS: Lazy<ProcessSlots> = Lazy::new(ProcessSlots::default);
+
+/// A batch case's slot, given back when dropped
+pub struct BatchSlot<'a> {
+    slots: &'a ProcessSlots,
+}
+
+impl Drop for BatchSlot<'_> {
+    fn drop(&mut self) {
+        self.slots.state.lock().unwrap().batch -= 1;
+        self.slots.changed.notify_waiters();
+    }
+}
+
+impl ProcessSlots {
+    fn high_priority_live(state: &SlotState) -> usize {
+        state.live.iter().filter(|id| priority_of(id) == SessionPriority::High).count()
+    }
+
+    /// Wait for a slot under `capacity` that no high-priority session needs
+    pub async fn acquire_batch(&self, capacity: usize) -> BatchSlot<'_> {
+        loop {
+            // Registered before the check so a release in between is not missed
+            let changed = self.changed.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.batch + Self::high_priority_live(&state) < capacity.max(1) {
+                    state.batch += 1;
+                    return BatchSlot { slots: self };
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// Slots held by batch cases and by high-priority sessions
+    pub fn in_use(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.batch, Self::high_priority_live(&state))
+    }
+
+    pub fn session_started(&self, session_id: &str) {
+        self.state.lock().unwrap().live.insert(session_id.to_string());
+        self.changed.notify_waiters();
+    }
+
+    pub fn session_ended(&self, session_id: &str) {
+        self.state.lock().unwrap().live.remove(session_id);
+        self.changed.notify_waiters();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Awaiting {
+    since_ms: i64,
+    notified: bool,
+}
+
+static AWAITING: Lazy<DashMap<String, Awaiting>> = Lazy::new(DashMap::new);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttentionItem {
+    pub session_id: String,
+    pub short_code: Option<String>,
+    pub priority: SessionPriority,
+    /// Unix time in milliseconds the session started awaiting input
+    pub awaiting_since: i64,
+    pub waiting_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionAwaitingInputEvent {
+    pub session_id: String,
+    pub priority: SessionPriority,
+    pub waiting_secs: u64,
+    /// Notification text in the user's language
+    pub message: String,
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Sessions awaiting input, highest priority first, then longest waiting
+pub fn attention_queue(now_ms: i64) -> Vec<AttentionItem> {
+    let mut items: Vec<AttentionItem> = AWAITING
+        .iter()
+        .map(|entry| AttentionItem {
+            session_id: entry.key().clone(),
+            short_code: crate::session_codes::code_for(entry.key()),
+            priority: priority_of(entry.key()),
+            awaiting_since: entry.since_ms,
+            waiting_secs: (now_ms - entry.since_ms).max(0) as u64 / 1000,
+        })
+        .collect();
+    items.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.awaiting_since.cmp(&b.awaiting_since)));
+    items
+}
+
+/// Sessions whose notification delay has passed, each returned once per wait
+fn take_due(now_ms: i64) -> Vec<AttentionItem> {
+    let mut due: Vec<AttentionItem> = attention_queue(now_ms)
+        .into_iter()
+        .filter(|item| item.priority.notify_delay().as_millis() as i64 <= now_ms - item.awaiting_since)
+        .collect();
+    due.retain(|item| match AWAITING.get_mut(&item.session_id) {
+        Some(mut entry) if !entry.notified => {
+            entry.notified = true;
+            true
+        }
+        _ => false,
+    });
+    due
+}
+
+fn emit_due(app_handle: &AppHandle) {
+    for item in take_due(now_ms()) {
+        let label = item.short_code.clone().unwrap_or_else(|| item.session_id.clone());
+        let message = tr!("notify-session-awaiting-input", session = label);
+        let _ = app_handle.emit("session_awaiting_input", SessionAwaitingInputEvent {
+            session_id: item.session_id,
+            priority: item.priority,
+            waiting_secs: item.waiting_secs,
+            message,
+        });
+    }
+}
+
+/// Mark a session awaiting input when its agent finishes a turn
+pub fn observe_stream_event(app_handle: &AppHandle, session_id: &str, event: &serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) == Some("result") {
+        awaiting_input(app_handle, session_id);
+    }
+}
+
+/// The session's turn ended without a result, e.g. because it was interrupted
+pub fn awaiting_input(app_handle: &AppHandle, session_id: &str) {
+    AWAITING.entry(session_id.to_string()).or_insert(Awaiting { since_ms: now_ms(), notified: false });
+    emit_due(app_handle);
+}
+
+/// The user answered, or the session's process ended
+pub fn input_received(session_id: &str) {
+    AWAITING.remove(session_id);
+}
+
+pub fn session_started(session_id: &str) {
+    PROCESS_SLOTS.session_started(session_id);
+}
+
+pub fn session_ended(session_id: &str) {
+    input_received(session_id);
+    PROCESS_SLOTS.session_ended(session_id);
+}
+
+/// Announce sessions whose notification delay passed since the last heartbeat
+pub fn notify_waiting(app_handle: &AppHandle) {
+    emit_due(app_handle);
+}
+
+#[tauri::command]
+pub async fn set_session_priority(
+    session_id: String,
+    priority: SessionPriority,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<(), String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    store_priority(db, &session_id, priority).await
+}
+
+#[tauri::command]
+pub async fn get_session_priority(session_id: String) -> Result<SessionPriority, String> {
+    Ok(priority_of(&crate::session_codes::resolve(&session_id)))
+}
+
+#[tauri::command]
+pub async fn get_attention_queue() -> Result<Vec<AttentionItem>, String> {
+    Ok(attention_queue(now_ms()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[tokio::test]
+    async fn test_priorities_round_trip() {
+        let pool = test_pool().await;
+
+        store_priority(&pool, "prio-s1", SessionPriority::High).await.unwrap();
+        store_priority(&pool, "prio-s1", SessionPriority::Low).await.unwrap();
+        store_priority(&pool, "prio-s2", SessionPriority::High).await.unwrap();
+        PRIORITIES.remove("prio-s1");
+        assert_eq!(priority_of("prio-s1"), SessionPriority::Normal);
+
+        assert!(load(&pool).await.unwrap() >= 2);
+        assert_eq!(priority_of("prio-s1"), SessionPriority::Low);
+        assert_eq!(priority_of("prio-s2"), SessionPriority::High);
+        assert!(sqlx::query("INSERT INTO session_priorities (session_id, priority) VALUES ('prio-s3', 'urgent')").execute(&pool).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_sessions_take_batch_slots() {
+        let slots = ProcessSlots::default();
+        PRIORITIES.insert("slot-high".to_string(), SessionPriority::High);
+
+        let first = slots.acquire_batch(2).await;
+        slots.session_started("slot-normal");
+        slots.session_started("slot-high");
+        assert_eq!(slots.in_use(), (1, 1));
+        // Both slots are taken, so the next case waits
+        assert!(tokio::time::timeout(Duration::from_millis(50), slots.acquire_batch(2)).await.is_err());
+
+        slots.session_ended("slot-high");
+        let second = tokio::time::timeout(Duration::from_millis(50), slots.acquire_batch(2)).await.unwrap();
+        assert_eq!(slots.in_use(), (2, 0));
+        drop((first, second));
+        assert_eq!(slots.in_use(), (0, 0));
+    }
+
+    #[test]
+    fn test_attention_queue_order_and_notifications() {
+        PRIORITIES.insert("queue-high".to_string(), SessionPriority::High);
+        PRIORITIES.insert("queue-low".to_string(), SessionPriority::Low);
+        for (id, since_ms) in [("queue-normal-new", 50_000), ("queue-low", 0), ("queue-normal-old", 10_000), ("queue-high", 60_000)] {
+            AWAITING.insert(id.to_string(), Awaiting { since_ms, notified: false });
+        }
+
+        let order: Vec<String> = attention_queue(70_000)
+            .into_iter()
+            .map(|item| item.session_id)
+            .filter(|id| id.starts_with("queue-"))
+            .collect();
+        assert_eq!(order, vec!["queue-high", "queue-normal-old", "queue-normal-new", "queue-low"]);
+
+        let due = |now| -> Vec<String> {
+            take_due(now).into_iter().map(|item| item.session_id).filter(|id| id.starts_with("queue-")).collect()
+        };
+        // High priority is due at once; normal after a minute, low after five
+        assert_eq!(due(60_000), vec!["queue-high"]);
+        assert_eq!(due(75_000), vec!["queue-normal-old"]);
+        assert_eq!(due(75_000), Vec::<String>::new());
+        assert_eq!(due(300_000), vec!["queue-normal-new", "queue-low"]);
+
+        session_ended("queue-high");
+        input_received("queue-low");
+        assert!(attention_queue(300_000).iter().all(|item| item.session_id != "queue-high" && item.session_id != "queue-low"));
+    }
+}
@@ -0,0 +1,213 @@
+//! Read-only snapshots of past sessions
+//!
+//! `thread_attach` rebuilds a thread's environment and worktree path so its
+//! process can be resumed, which is wrong for a session that is only being looked
+//! at. `get_session_snapshot` answers from the database alone: the session and
+//! all of its threads, archived ones included, each with its full conversation,
+//! the file edits its agent made, its turn metrics and its outcome. It never
+//! starts processes, composes environments or reads the repository.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use tauri::State;
+
+use crate::file_locks::{PATH_KEYS, WRITE_TOOLS};
+use crate::message_metrics::ThreadMetrics;
+use crate::profile_auth::ProfileManager;
+use crate::session_analytics::{SessionOutcomeRecord, SessionOutcomeStore};
+use crate::thread_session_commands::{thread_history, SessionInfo, ThreadInfo};
+
+/// A file edit as the agent's tool call recorded it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEdit {
+    pub message_id: String,
+    pub tool_use_id: String,
+    pub tool: String,
+    pub path: String,
+    /// The tool's input, holding the replaced and new text or the full file content
+    pub input: Value,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSnapshot {
+    pub thread: ThreadInfo,
+    /// Messages in `get_thread_history` form, oldest first
+    pub messages: Vec<Value>,
+    pub file_edits: Vec<FileEdit>,
+    pub metrics: ThreadMetrics,
+    pub outcome: Option<SessionOutcomeRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub session: SessionInfo,
+    pub tags: Vec<String>,
+    pub threads: Vec<ThreadSnapshot>,
+}
+
+/// Write tool calls in a stored message
+pub fn file_edits(message: &Value) -> Vec<FileEdit> {
+    let event = &message["content"];
+    if event.get("type").and_then(|t| t.as_str()) != Some("assistant") {
+        return Vec::new();
+    }
+    let Some(content) = event.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+    let field = |key: &str| message.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+    content
+        .iter()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        .filter_map(|part| {
+            let tool = part.get("name").and_then(|n| n.as_str()).filter(|n| WRITE_TOOLS.contains(n))?;
+            let input = part.get("input")?;
+            let path = PATH_KEYS.iter().find_map(|k| input.get(*k).and_then(|p| p.as_str()))?;
+            Some(FileEdit {
+                message_id: field("id"),
+                tool_use_id: part.get("id").and_then(|i| i.as_str()).unwrap_or_default().to_string(),
+                tool: tool.to_string(),
+                path: path.to_string(),
+                input: input.clone(),
+                created_at: field("created_at"),
+            })
+        })
+        .collect()
+}
+
+pub async fn session_snapshot(db: &SqlitePool, session_id: &str) -> Result<SessionSnapshot, String> {
+    let session = sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>, String, String)>(
+        "SELECT id, title, profile_id, amp_profile_id, created_at, updated_at FROM sessions WHERE id = ?",
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to read session: {}", e))?
+    .map(|(id, title, profile_id, amp_profile_id, created_at, updated_at)| SessionInfo {
+        short_code: crate::session_codes::code_for(&id),
+        id,
+        title,
+        profile_id,
+        amp_profile_id,
+        created_at,
+        updated_at,
+    })
+    .ok_or_else(|| format!("Session {} not found", session_id))?;
+
+    let tags: Vec<String> = sqlx::query_scalar("SELECT tag FROM session_tags WHERE session_id = ? ORDER BY tag")
+        .bind(session_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to read session tags: {}", e))?;
+
+    let threads = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>)>(
+        "SELECT id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at
+         FROM threads WHERE session_id = ? ORDER BY created_at ASC",
+    )
+    .bind(session_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to list threads: {}", e))?;
+
+    let outcomes = SessionOutcomeStore::new(db.clone());
+    let mut snapshots = Vec::with_capacity(threads.len());
+    for (id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at) in threads {
+        let messages = thread_history(db, &id, i64::MAX, 0).await?;
+        let file_edits = messages.iter().flat_map(file_edits).collect();
+        let metrics = crate::message_metrics::thread_metrics(db, &id).await?;
+        let outcome = outcomes
+            .get_outcome(&id)
+            .await
+            .map_err(|e| format!("Failed to read outcome: {}", e))?;
+        snapshots.push(ThreadSnapshot {
+            thread: ThreadInfo { id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at },
+            messages,
+            file_edits,
+            metrics,
+            outcome,
+        });
+    }
+
+    Ok(SessionSnapshot { session, tags, threads: snapshots })
+}
+
+/// Everything needed to view a past session, read from the database only
+#[tauri::command]
+pub async fn get_session_snapshot(
+    session_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SessionSnapshot, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    session_snapshot(db, &session_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_content::insert_message;
+    use crate::schema::test_pool;
+
+    fn edit_event(tool_use_id: &str) -> String {
+        serde_json::json!({
+            "type": "assistant",
+            "message": { "content": [
+                { "type": "text", "text": "Fixing it" },
+                { "type": "tool_use", "id": tool_use_id, "name": "edit_file",
+                  "input": { "path": "src/lib.rs", "old_str": "a", "new_str": "b" } },
+                { "type": "tool_use", "id": "read-1", "name": "read_file", "input": { "path": "src/lib.rs" } }
+            ] }
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reads_archived_and_live_threads() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Old work')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO session_tags (session_id, tag) VALUES ('s1', 'pass')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, created_at, archived_at) VALUES ('t1', 's1', 'development', '2024-01-01T00:00:00Z', '2024-01-02T00:00:00Z')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, created_at) VALUES ('t2', 's1', 'production', '2024-01-03T00:00:00Z')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        insert_message(&pool, "m1", "t1", "user", r#"{"type":"user"}"#).await.unwrap();
+        insert_message(&pool, "m2", "t1", "assistant", &edit_event("edit-1")).await.unwrap();
+        insert_message(&pool, "m3", "t2", "assistant", "plain text").await.unwrap();
+        crate::archive_compression::compress_thread(&pool, "t1").await.unwrap();
+        sqlx::query(
+            "INSERT INTO session_outcomes (session_id, outcome, decided_by) VALUES ('t1', 'succeeded', 'tag')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let snapshot = session_snapshot(&pool, "s1").await.unwrap();
+        assert_eq!(snapshot.session.title.as_deref(), Some("Old work"));
+        assert_eq!(snapshot.tags, vec!["pass"]);
+        assert_eq!(snapshot.threads.len(), 2);
+
+        let archived = &snapshot.threads[0];
+        assert_eq!(archived.thread.id, "t1");
+        assert!(archived.thread.archived_at.is_some());
+        assert_eq!(archived.messages.len(), 2);
+        assert_eq!(archived.file_edits.len(), 1);
+        assert_eq!(archived.file_edits[0].message_id, "m2");
+        assert_eq!(archived.file_edits[0].path, "src/lib.rs");
+        assert_eq!(archived.file_edits[0].input["new_str"], "b");
+        assert_eq!(archived.outcome.as_ref().map(|o| o.outcome.as_str()), Some("succeeded"));
+
+        let live = &snapshot.threads[1];
+        assert_eq!(live.messages[0]["content"], "plain text");
+        assert!(live.file_edits.is_empty());
+        assert!(live.outcome.is_none());
+
+        assert!(session_snapshot(&pool, "missing").await.is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Environment audit of thread process spawns
+//!
+//! Every attempt to spawn a thread's CLI, whether starting, re-attaching or
+//! refreshing its environment, records the composed environment and the
+//! version of the CLI it runs. Secret values
+//! are stored as short fingerprints, enough to tell that a token changed without
+//! keeping the token. `diff_thread_spawn_envs` compares two recorded spawns of a
+//! thread; only the latest `MAX_SPAWNS_PER_THREAD` are kept.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use tauri::State;
+
+use crate::batch_case_logs::is_secret;
+use crate::profile_auth::ProfileManager;
+
+pub const MAX_SPAWNS_PER_THREAD: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnReason {
+    Start,
+    Attach,
+    RefreshEnv,
+}
+
+impl SpawnReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            SpawnReason::Start => "start",
+            SpawnReason::Attach => "attach",
+            SpawnReason::RefreshEnv => "refresh_env",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "attach" => SpawnReason::Attach,
+            "refresh_env" => SpawnReason::RefreshEnv,
+            _ => SpawnReason::Start,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadSpawnEnv {
+    pub id: i64,
+    pub thread_id: String,
+    pub reason: SpawnReason,
+    /// Redacted, sorted by name
+    pub env: BTreeMap<String, String>,
+    /// `None` when the CLI did not report one
+    pub cli_version: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvChange {
+    pub name: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// How the environment of spawn `b` differs from that of spawn `a`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvDiff {
+    pub added: BTreeMap<String, String>,
+    pub removed: BTreeMap<String, String>,
+    pub changed: Vec<EnvChange>,
+}
+
+/// The environment with secret values replaced by a fingerprint of their content
+pub fn redacted_env(env: &HashMap<String, String>) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(key, value)| {
+            let value = if is_secret(key) {
+                format!("[REDACTED {}]", &blake3::hash(value.as_bytes()).to_hex()[..8])
+            } else {
+                value.clone()
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+pub fn diff_envs(a: &BTreeMap<String, String>, b: &BTreeMap<String, String>) -> EnvDiff {
+    let mut diff = EnvDiff::default();
+    for (name, before) in a {
+        match b.get(name) {
+            None => {
+                diff.removed.insert(name.clone(), before.clone());
+            }
+            Some(after) if after != before => {
+                diff.changed.push(EnvChange { name: name.clone(), before: before.clone(), after: after.clone() });
+            }
+            Some(_) => {}
+        }
+    }
+    diff.added = b.iter().filter(|(name, _)| !a.contains_key(*name)).map(|(k, v)| (k.clone(), v.clone())).collect();
+    diff
+}
+
+/// Record a spawn attempt and drop the thread's oldest records past the limit
+pub async fn record_spawn_env(
+    db: &SqlitePool,
+    thread_id: &str,
+    reason: SpawnReason,
+    env: &HashMap<String, String>,
+    cli_version: Option<&str>,
+) -> Result<i64, String> {
+    let env = serde_json::to_string(&redacted_env(env)).map_err(|e| e.to_string())?;
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO thread_spawn_envs (thread_id, reason, env, cli_version) VALUES (?, ?, ?, ?) RETURNING id",
+    )
+    .bind(thread_id)
+    .bind(reason.as_str())
+    .bind(env)
+    .bind(cli_version)
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to record spawn environment: {}", e))?;
+    sqlx::query(
+        "DELETE FROM thread_spawn_envs WHERE thread_id = ?1 AND id NOT IN
+         (SELECT id FROM thread_spawn_envs WHERE thread_id = ?1 ORDER BY id DESC LIMIT ?2)",
+    )
+    .bind(thread_id)
+    .bind(MAX_SPAWNS_PER_THREAD)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to prune spawn environments: {}", e))?;
+    Ok(id)
+}
+
+/// Record a spawn attempt with the version of the CLI `env` selects, logging
+/// rather than failing the spawn when that fails
+pub async fn record(db: &SqlitePool, thread_id: &str, reason: SpawnReason, env: &HashMap<String, String>) {
+    let (program, args) = crate::session_commands::choose_amp_command(env);
+    let cli_version = crate::stream_quarantine::CliInfo::new(&program, &args).version().await;
+    if let Err(e) = record_spawn_env(db, thread_id, reason, env, cli_version.as_deref()).await {
+        log::warn!("Thread {}: {}", thread_id, e);
+    }
+}
+
+type SpawnRow = (i64, String, String, String, Option<String>, String);
+
+fn from_row((id, thread_id, reason, env, cli_version, created_at): SpawnRow) -> ThreadSpawnEnv {
+    let env = serde_json::from_str(&env).unwrap_or_default();
+    ThreadSpawnEnv { id, thread_id, reason: SpawnReason::parse(&reason), env, cli_version, created_at }
+}
+
+/// Recorded spawns of a thread, oldest first
+pub async fn spawn_envs(db: &SqlitePool, thread_id: &str) -> Result<Vec<ThreadSpawnEnv>, String> {
+    let rows = sqlx::query_as::<_, SpawnRow>(
+        "SELECT id, thread_id, reason, env, cli_version, created_at FROM thread_spawn_envs WHERE thread_id = ? ORDER BY id",
+    )
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load spawn environments: {}", e))?;
+    Ok(rows.into_iter().map(from_row).collect())
+}
+
+async fn spawn_env(db: &SqlitePool, thread_id: &str, spawn_id: i64) -> Result<ThreadSpawnEnv, String> {
+    sqlx::query_as::<_, SpawnRow>(
+        "SELECT id, thread_id, reason, env, cli_version, created_at FROM thread_spawn_envs WHERE id = ? AND thread_id = ?",
+    )
+    .bind(spawn_id)
+    .bind(thread_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to load spawn environment: {}", e))?
+    .map(from_row)
+    .ok_or_else(|| format!("Spawn {} of thread {} not found", spawn_id, thread_id))
+}
+
+pub async fn diff_spawns(db: &SqlitePool, thread_id: &str, spawn_a: i64, spawn_b: i64) -> Result<EnvDiff, String> {
+    let (a, b) = (spawn_env(db, thread_id, spawn_a).await?, spawn_env(db, thread_id, spawn_b).await?);
+    Ok(diff_envs(&a.env, &b.env))
+}
+
+#[tauri::command]
+pub async fn list_thread_spawn_envs(thread_id: String, profile_manager: State<'_, ProfileManager>) -> Result<Vec<ThreadSpawnEnv>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    spawn_envs(db, &thread_id).await
+}
+
+/// Variables added, removed and changed from spawn `spawn_a` to spawn `spawn_b`
+#[tauri::command]
+pub async fn diff_thread_spawn_envs(
+    thread_id: String,
+    spawn_a: i64,
+    spawn_b: i64,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<EnvDiff, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    diff_spawns(db, &thread_id, spawn_a, spawn_b).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_diff_redacts_secrets_but_shows_their_changes() {
+        let a = redacted_env(&env(&[("PATH", "/usr/bin"), ("AMP_API_KEY", "first"), ("AMP_MODEL", "fast"), ("HOME", "/home/a")]));
+        let b = redacted_env(&env(&[("PATH", "/usr/bin"), ("AMP_API_KEY", "second"), ("AMP_MODEL", "smart"), ("AMP_URL", "http://x")]));
+        assert!(a["AMP_API_KEY"].starts_with("[REDACTED ") && !a["AMP_API_KEY"].contains("first"));
+
+        let diff = diff_envs(&a, &b);
+        assert_eq!(diff.added, BTreeMap::from([("AMP_URL".to_string(), "http://x".to_string())]));
+        assert_eq!(diff.removed, BTreeMap::from([("HOME".to_string(), "/home/a".to_string())]));
+        assert_eq!(diff.changed.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["AMP_API_KEY", "AMP_MODEL"]);
+        assert_eq!(diff.changed[1], EnvChange { name: "AMP_MODEL".to_string(), before: "fast".to_string(), after: "smart".to_string() });
+        assert_eq!(diff_envs(&a, &a), EnvDiff::default());
+    }
+
+    #[tokio::test]
+    async fn test_spawns_are_recorded_and_pruned() {
+        let pool = test_pool().await;
+
+        let first = record_spawn_env(&pool, "t1", SpawnReason::Start, &env(&[("AMP_MODEL", "fast")]), Some("0.0.1")).await.unwrap();
+        let second = record_spawn_env(&pool, "t1", SpawnReason::Attach, &env(&[("AMP_MODEL", "smart")]), None).await.unwrap();
+        let diff = diff_spawns(&pool, "t1", first, second).await.unwrap();
+        assert_eq!(diff.changed.len(), 1);
+        assert!(diff_spawns(&pool, "t2", first, second).await.is_err());
+
+        for _ in 0..MAX_SPAWNS_PER_THREAD {
+            record_spawn_env(&pool, "t1", SpawnReason::RefreshEnv, &env(&[]), None).await.unwrap();
+        }
+        record_spawn_env(&pool, "t2", SpawnReason::Start, &env(&[]), Some("0.0.2")).await.unwrap();
+        let spawns = spawn_envs(&pool, "t1").await.unwrap();
+        assert_eq!(spawns.len() as i64, MAX_SPAWNS_PER_THREAD);
+        assert!(spawns.iter().all(|spawn| spawn.reason == SpawnReason::RefreshEnv));
+        assert_eq!(spawn_envs(&pool, "t2").await.unwrap()[0].cli_version.as_deref(), Some("0.0.2"));
+    }
+}
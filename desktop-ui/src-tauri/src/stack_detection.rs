@@ -0,0 +1,562 @@
+//! Language and framework detection for session defaults
+//!
+//! When a session is created, the manifest files within two levels of the
+//! repository root (Cargo.toml, package.json, pyproject.toml, requirements.txt,
+//! setup.py, go.mod) tell which languages and frameworks it uses; languages are
+//! ranked by how many source files each has. The detection is stored on the
+//! session and supplies its defaults: a toolbox profile named after a detected
+//! framework or language when none was chosen, the saved context packs whose
+//! names mention one, and the commands (`cargo test`, `npm test`, ...) that
+//! verify the session's work each time it completes.
+
+use dashmap::DashSet;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tokio::process::Command;
+
+use crate::context_packs::candidate_files;
+use crate::profile_auth::ProfileManager;
+
+/// Directories below the root searched for manifests
+const MANIFEST_DEPTH: usize = 2;
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Output kept of each verification command, from the end
+const VERIFY_OUTPUT_BYTES: usize = 8 * 1024;
+/// `npm init`'s placeholder test script
+const NPM_PLACEHOLDER_TEST: &str = "echo \"Error: no test specified\" && exit 1";
+
+static CARGO_FRAMEWORKS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^\s*(tauri|axum|actix-web|rocket|warp|bevy|leptos|dioxus)\s*=").unwrap());
+static PYTHON_FRAMEWORKS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b(django|flask|fastapi|pytest)\b").unwrap());
+static GO_FRAMEWORKS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"github\.com/(?:gin-gonic/(gin)|labstack/(echo)|gofiber/(fiber))").unwrap());
+/// package.json dependencies and the framework each stands for
+const NODE_FRAMEWORKS: [(&str, &str); 10] = [
+    ("next", "next"),
+    ("react", "react"),
+    ("vue", "vue"),
+    ("svelte", "svelte"),
+    ("@angular/core", "angular"),
+    ("express", "express"),
+    ("@nestjs/core", "nestjs"),
+    ("vite", "vite"),
+    ("@tauri-apps/api", "tauri"),
+    ("electron", "electron"),
+];
+
+/// Sessions with verification in progress
+static VERIFYING: Lazy<DashSet<String>> = Lazy::new(DashSet::new);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyCommand {
+    /// Directory relative to the worktree root, empty for the root
+    pub dir: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StackDetection {
+    /// Primary language first
+    pub languages: Vec<String>,
+    pub frameworks: Vec<String>,
+    pub manifests: Vec<String>,
+    pub verify_commands: Vec<VerifyCommand>,
+    /// Files worth putting in a context pack for the repository
+    pub context_sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationResult {
+    pub dir: String,
+    pub command: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// The end of stdout followed by the end of stderr
+    pub output: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStack {
+    pub session_id: String,
+    pub detection: StackDetection,
+    /// Toolbox profile the detection chose for the session, if it chose one
+    pub toolbox_profile_id: Option<i64>,
+    /// Saved context packs matching the detection
+    pub suggested_packs: Vec<String>,
+    /// Run on completion; starts as the detected commands
+    pub verify_commands: Vec<VerifyCommand>,
+    pub last_verification: Option<Vec<VerificationResult>>,
+    pub verified_at: Option<String>,
+    pub detected_at: String,
+}
+
+fn push_unique(list: &mut Vec<String>, value: &str) {
+    if !list.iter().any(|v| v == value) {
+        list.push(value.to_string());
+    }
+}
+
+/// Directory of a relative path, empty at the root
+fn dir_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+fn is_manifest(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    path.matches('/').count() <= MANIFEST_DEPTH
+        && matches!(name, "Cargo.toml" | "package.json" | "pyproject.toml" | "requirements.txt" | "setup.py" | "go.mod")
+}
+
+fn language_of(path: &str) -> Option<&'static str> {
+    match path.rsplit_once('.')?.1 {
+        "rs" => Some("rust"),
+        "ts" | "tsx" | "mts" | "cts" => Some("typescript"),
+        "js" | "jsx" | "mjs" | "cjs" => Some("javascript"),
+        "py" => Some("python"),
+        "go" => Some("go"),
+        _ => None,
+    }
+}
+
+/// Command a directory's package manager runs its tests with, judged by its lockfile
+fn node_test_command(files: &[String], dir: &str) -> String {
+    let has = |name: &str| files.iter().any(|f| dir_of(f) == dir && f.ends_with(name));
+    let manager = if has("pnpm-lock.yaml") {
+        "pnpm"
+    } else if has("yarn.lock") {
+        "yarn"
+    } else if has("bun.lockb") || has("bun.lock") {
+        "bun"
+    } else {
+        "npm"
+    };
+    format!("{} test", manager)
+}
+
+/// Detect the languages, frameworks and verification commands of the repository at `root`
+pub fn detect(root: &Path) -> StackDetection {
+    let files = candidate_files(root);
+    let read = |path: &str| std::fs::read_to_string(root.join(path)).unwrap_or_default();
+    let manifests: Vec<String> = files.iter().filter(|path| is_manifest(path)).cloned().collect();
+    let mut detection = StackDetection::default();
+    let mut manifest_languages = Vec::new();
+    // Python directories, and whether any of their manifests mentions pytest
+    let mut python_dirs: BTreeMap<String, bool> = BTreeMap::new();
+
+    for manifest in &manifests {
+        let dir = dir_of(manifest);
+        let text = read(manifest);
+        match manifest.rsplit('/').next().unwrap_or_default() {
+            "Cargo.toml" => {
+                push_unique(&mut manifest_languages, "rust");
+                for captures in CARGO_FRAMEWORKS.captures_iter(&text) {
+                    push_unique(&mut detection.frameworks, &captures[1]);
+                }
+                // Members of a workspace are tested from the workspace root
+                let in_workspace = manifests.iter().any(|other| {
+                    let other_dir = dir_of(other);
+                    other.ends_with("Cargo.toml")
+                        && other_dir != dir
+                        && (other_dir.is_empty() || dir.starts_with(&format!("{}/", other_dir)))
+                });
+                if !in_workspace {
+                    detection.verify_commands.push(VerifyCommand { dir: dir.to_string(), command: "cargo test".to_string() });
+                }
+            }
+            "package.json" => {
+                push_unique(&mut manifest_languages, "javascript");
+                let package: serde_json::Value = serde_json::from_str(&text).unwrap_or_default();
+                let has_dependency = |name: &str| {
+                    ["dependencies", "devDependencies"].iter().any(|key| package.get(key).and_then(|deps| deps.get(name)).is_some())
+                };
+                for (dependency, framework) in NODE_FRAMEWORKS {
+                    if has_dependency(dependency) {
+                        push_unique(&mut detection.frameworks, framework);
+                    }
+                }
+                let test_script = package.get("scripts").and_then(|scripts| scripts.get("test")).and_then(|test| test.as_str());
+                if test_script.is_some_and(|script| script.trim() != NPM_PLACEHOLDER_TEST) {
+                    detection.verify_commands.push(VerifyCommand { dir: dir.to_string(), command: node_test_command(&files, dir) });
+                }
+            }
+            "go.mod" => {
+                push_unique(&mut manifest_languages, "go");
+                for captures in GO_FRAMEWORKS.captures_iter(&text) {
+                    if let Some(name) = captures.iter().skip(1).flatten().next() {
+                        push_unique(&mut detection.frameworks, name.as_str());
+                    }
+                }
+                detection.verify_commands.push(VerifyCommand { dir: dir.to_string(), command: "go test ./...".to_string() });
+            }
+            _ => {
+                push_unique(&mut manifest_languages, "python");
+                let mut pytest = false;
+                for captures in PYTHON_FRAMEWORKS.captures_iter(&text) {
+                    let name = captures[1].to_lowercase();
+                    if name == "pytest" {
+                        pytest = true;
+                    } else {
+                        push_unique(&mut detection.frameworks, &name);
+                    }
+                }
+                *python_dirs.entry(dir.to_string()).or_default() |= pytest;
+            }
+        }
+    }
+    for (dir, pytest) in python_dirs {
+        let command = if pytest { "pytest" } else { "python -m unittest" };
+        detection.verify_commands.push(VerifyCommand { dir, command: command.to_string() });
+    }
+    detection.verify_commands.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for language in files.iter().filter_map(|path| language_of(path)) {
+        *counts.entry(language).or_default() += 1;
+    }
+    for language in manifest_languages {
+        // package.json stands for whichever of JavaScript and TypeScript the sources are
+        if language == "javascript" {
+            let (ts, js) = (counts.get("typescript").copied().unwrap_or(0), counts.get("javascript").copied().unwrap_or(0));
+            if ts > 0 {
+                push_unique(&mut detection.languages, "typescript");
+            }
+            if js > 0 || ts == 0 {
+                push_unique(&mut detection.languages, "javascript");
+            }
+        } else {
+            push_unique(&mut detection.languages, &language);
+        }
+    }
+    detection.languages.sort_by_key(|language| std::cmp::Reverse(counts.get(language.as_str()).copied().unwrap_or(0)));
+
+    detection.manifests = manifests;
+    detection.context_sources = files
+        .iter()
+        .filter(|path| path.eq_ignore_ascii_case("README.md") || (is_manifest(path) && !path.contains('/')))
+        .cloned()
+        .collect();
+    detection
+}
+
+/// Detection for the repository the app was started in, if it is one
+pub async fn detect_repo() -> Option<StackDetection> {
+    let cwd = std::env::current_dir().ok()?;
+    let root = crate::worktree::find_repo_root(&cwd).ok()?;
+    tokio::task::spawn_blocking(move || detect(&root)).await.ok()
+}
+
+/// Whether a name such as "rust-tools" or "React app" mentions a detected tag
+fn mentions(name: &str, tag: &str) -> bool {
+    let name = name.to_lowercase();
+    name == tag || name.split(|c: char| !c.is_alphanumeric()).any(|word| word == tag)
+}
+
+/// The first profile named after a detected framework, else after a language, most prominent first
+pub fn pick_toolbox_profile(profiles: &[(i64, String)], detection: &StackDetection) -> Option<i64> {
+    detection
+        .frameworks
+        .iter()
+        .chain(&detection.languages)
+        .find_map(|tag| profiles.iter().find(|(_, name)| mentions(name, tag)).map(|(id, _)| *id))
+}
+
+/// IDs of the packs whose names mention a detected language or framework
+pub fn suggest_packs(packs: &[(String, String)], detection: &StackDetection) -> Vec<String> {
+    packs
+        .iter()
+        .filter(|(_, name)| detection.frameworks.iter().chain(&detection.languages).any(|tag| mentions(name, tag)))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Toolbox profile to default a new session to, from its repository's detection
+pub async fn default_toolbox_profile(db: &SqlitePool, detection: &StackDetection) -> Option<i64> {
+    let profiles = sqlx::query_as::<_, (i64, String)>("SELECT id, name FROM toolbox_profiles ORDER BY id")
+        .fetch_all(db)
+        .await
+        .ok()?;
+    pick_toolbox_profile(&profiles, detection)
+}
+
+type StackRow = (String, String, Option<i64>, String, String, Option<String>, Option<String>, String);
+
+fn stack_from_row(row: StackRow) -> SessionStack {
+    let (session_id, detection, toolbox_profile_id, suggested_packs, verify_commands, last_verification, verified_at, detected_at) = row;
+    SessionStack {
+        session_id,
+        detection: serde_json::from_str(&detection).unwrap_or_default(),
+        toolbox_profile_id,
+        suggested_packs: serde_json::from_str(&suggested_packs).unwrap_or_default(),
+        verify_commands: serde_json::from_str(&verify_commands).unwrap_or_default(),
+        last_verification: last_verification.and_then(|json| serde_json::from_str(&json).ok()),
+        verified_at,
+        detected_at,
+    }
+}
+
+/// Store a new session's detection, suggesting the saved packs that match it
+pub async fn save_stack(
+    db: &SqlitePool,
+    session_id: &str,
+    detection: &StackDetection,
+    toolbox_profile_id: Option<i64>,
+) -> Result<SessionStack, String> {
+    let packs: Vec<(String, String)> = crate::context_packs::list_packs(db)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|pack| (pack.id, pack.name))
+        .collect();
+    let suggested = suggest_packs(&packs, detection);
+    let (detection_json, suggested, commands) = (
+        serde_json::to_string(detection).map_err(|e| e.to_string())?,
+        serde_json::to_string(&suggested).map_err(|e| e.to_string())?,
+        serde_json::to_string(&detection.verify_commands).map_err(|e| e.to_string())?,
+    );
+    sqlx::query(
+        "INSERT OR REPLACE INTO session_stacks (session_id, detection, toolbox_profile_id, suggested_packs, verify_commands)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(session_id)
+    .bind(detection_json)
+    .bind(toolbox_profile_id)
+    .bind(suggested)
+    .bind(commands)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save session stack: {}", e))?;
+    get_stack(db, session_id).await?.ok_or_else(|| format!("Session stack of {} not found", session_id))
+}
+
+pub async fn get_stack(db: &SqlitePool, session_id: &str) -> Result<Option<SessionStack>, String> {
+    let row = sqlx::query_as::<_, StackRow>(
+        "SELECT session_id, detection, toolbox_profile_id, suggested_packs, verify_commands, last_verification, verified_at, detected_at
+         FROM session_stacks WHERE session_id = ?",
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to load session stack: {}", e))?;
+    Ok(row.map(stack_from_row))
+}
+
+fn validate_commands(commands: &[VerifyCommand]) -> Result<(), String> {
+    for command in commands {
+        if command.command.trim().is_empty() {
+            return Err("Verification commands cannot be empty".to_string());
+        }
+        let dir = Path::new(&command.dir);
+        if dir.is_absolute() || dir.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(format!("Directory '{}' must be inside the worktree", command.dir));
+        }
+    }
+    Ok(())
+}
+
+pub async fn set_verify_commands(db: &SqlitePool, session_id: &str, commands: &[VerifyCommand]) -> Result<SessionStack, String> {
+    validate_commands(commands)?;
+    let commands = serde_json::to_string(commands).map_err(|e| e.to_string())?;
+    // Sessions created before detection existed get an empty detection
+    sqlx::query(
+        "INSERT INTO session_stacks (session_id, detection, verify_commands) VALUES (?, '{}', ?)
+         ON CONFLICT(session_id) DO UPDATE SET verify_commands = excluded.verify_commands",
+    )
+    .bind(session_id)
+    .bind(commands)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save verification commands: {}", e))?;
+    get_stack(db, session_id).await?.ok_or_else(|| format!("Session stack of {} not found", session_id))
+}
+
+pub async fn record_verification(db: &SqlitePool, session_id: &str, results: &[VerificationResult]) -> Result<(), String> {
+    sqlx::query("UPDATE session_stacks SET last_verification = ?, verified_at = (datetime('now', 'utc') || 'Z') WHERE session_id = ?")
+        .bind(serde_json::to_string(results).map_err(|e| e.to_string())?)
+        .bind(session_id)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to record verification: {}", e))?;
+    Ok(())
+}
+
+/// The end of `bytes` as text, at most `VERIFY_OUTPUT_BYTES` long
+fn output_tail(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    if text.len() <= VERIFY_OUTPUT_BYTES {
+        return text.to_string();
+    }
+    let mut start = text.len() - VERIFY_OUTPUT_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("…{}", &text[start..])
+}
+
+pub async fn run_verification(root: &Path, command: &VerifyCommand) -> VerificationResult {
+    let started = Instant::now();
+    let mut process = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    process
+        .arg(if cfg!(windows) { "/C" } else { "-c" })
+        .arg(&command.command)
+        .current_dir(root.join(&command.dir))
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+    let (success, exit_code, output) = match tokio::time::timeout(VERIFY_TIMEOUT, process.output()).await {
+        Ok(Ok(output)) => {
+            let text = format!("{}{}", output_tail(&output.stdout), output_tail(&output.stderr));
+            (output.status.success(), output.status.code(), text)
+        }
+        Ok(Err(e)) => (false, None, format!("Failed to run {}: {}", command.command, e)),
+        Err(_) => (false, None, format!("Timed out after {} seconds", VERIFY_TIMEOUT.as_secs())),
+    };
+    VerificationResult {
+        dir: command.dir.clone(),
+        command: command.command.clone(),
+        success,
+        exit_code,
+        output,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+async fn worktree_for(app_handle: &AppHandle, db: &SqlitePool, id: &str, session_id: &str) -> Option<PathBuf> {
+    match crate::commit_message::session_worktree(app_handle, id).await {
+        Ok(path) => Some(path),
+        Err(_) => crate::repo_relocation::recorded_worktree(db, session_id).await,
+    }
+}
+
+/// Run the verification commands of the session behind a completed session or thread
+pub async fn run_on_completion(app_handle: &AppHandle, db: &SqlitePool, id: &str, kind: &str) {
+    let Some(session_id) = crate::session_analytics::completed_session(db, id, kind).await else { return };
+    let stack = match get_stack(db, &session_id).await {
+        Ok(Some(stack)) if !stack.verify_commands.is_empty() => stack,
+        Ok(_) => return,
+        Err(e) => return log::warn!("{}", e),
+    };
+    let Some(root) = worktree_for(app_handle, db, id, &session_id).await else {
+        return log::warn!("Verification of {} skipped: no worktree", session_id);
+    };
+    if !VERIFYING.insert(session_id.clone()) {
+        return;
+    }
+    let mut results = Vec::new();
+    for command in &stack.verify_commands {
+        results.push(run_verification(&root, command).await);
+    }
+    VERIFYING.remove(&session_id);
+    if let Err(e) = record_verification(db, &session_id, &results).await {
+        log::warn!("{}", e);
+    }
+    let _ = app_handle.emit("session_verification", serde_json::json!({ "session_id": session_id, "results": results }));
+}
+
+/// Detected languages, frameworks and defaults of a session, if it was detected
+#[tauri::command]
+pub async fn get_session_stack(session_id: String, profile_manager: State<'_, ProfileManager>) -> Result<Option<SessionStack>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    get_stack(db, &crate::session_codes::resolve(&session_id)).await
+}
+
+/// Replace the commands run when a session completes; an empty list turns verification off
+#[tauri::command]
+pub async fn set_session_verify_commands(
+    session_id: String,
+    commands: Vec<VerifyCommand>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SessionStack, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    set_verify_commands(db, &crate::session_codes::resolve(&session_id), &commands).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn command(dir: &str, command: &str) -> VerifyCommand {
+        VerifyCommand { dir: dir.to_string(), command: command.to_string() }
+    }
+
+    #[test]
+    fn test_detects_languages_frameworks_and_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("repo");
+        write(&root, "README.md", "# Repo");
+        write(&root, "Cargo.toml", "[workspace]\nmembers = [\"core\", \"app\"]\n");
+        write(&root, "core/Cargo.toml", "[dependencies]\naxum = \"0.7\"\n");
+        write(&root, "app/Cargo.toml", "[dependencies]\ntauri = { version = \"2\" }\n");
+        for i in 0..3 {
+            write(&root, &format!("core/src/m{}.rs", i), "");
+        }
+        write(&root, "web/package.json", r#"{"scripts":{"test":"vitest"},"dependencies":{"react":"18"},"devDependencies":{"vite":"5"}}"#);
+        write(&root, "web/pnpm-lock.yaml", "");
+        write(&root, "web/src/app.tsx", "");
+        write(&root, "docs/package.json", &format!(r#"{{"scripts":{{"test":"{}"}}}}"#, NPM_PLACEHOLDER_TEST.replace('"', "\\\"")));
+        write(&root, "tools/pyproject.toml", "[project]\ndependencies = [\"FastAPI>=0.100\"]\n[project.optional-dependencies]\ntest = [\"pytest\"]\n");
+        write(&root, "tools/requirements.txt", "requests\n");
+        write(&root, "a/b/c/package.json", r#"{"scripts":{"test":"jest"}}"#);
+
+        let detection = detect(&root);
+        assert_eq!(detection.languages, vec!["rust", "typescript", "python"]);
+        assert_eq!(detection.frameworks, vec!["tauri", "axum", "fastapi", "react", "vite"]);
+        assert_eq!(detection.verify_commands, vec![command("", "cargo test"), command("tools", "pytest"), command("web", "pnpm test")]);
+        assert!(!detection.manifests.contains(&"a/b/c/package.json".to_string()));
+        assert_eq!(detection.context_sources, vec!["Cargo.toml", "README.md"]);
+
+        let profiles = vec![(1, "Python tools".to_string()), (2, "rust".to_string()), (3, "React app".to_string())];
+        assert_eq!(pick_toolbox_profile(&profiles, &detection), Some(3));
+        assert_eq!(pick_toolbox_profile(&profiles[..2], &detection), Some(2));
+        assert_eq!(pick_toolbox_profile(&[(4, "Reactor".to_string())], &detection), None);
+        let packs = vec![("p1".to_string(), "Axum handlers".to_string()), ("p2".to_string(), "Release notes".to_string())];
+        assert_eq!(suggest_packs(&packs, &detection), vec!["p1"]);
+        assert_eq!(detect(&dir.path().join("missing")), StackDetection::default());
+    }
+
+    #[tokio::test]
+    async fn test_stack_round_trip_and_verification() {
+        let pool = test_pool().await;
+
+        let detection = StackDetection {
+            languages: vec!["rust".to_string()],
+            verify_commands: vec![command("", "cargo test")],
+            ..Default::default()
+        };
+        let stack = save_stack(&pool, "s1", &detection, Some(7)).await.unwrap();
+        assert_eq!((stack.detection, stack.toolbox_profile_id), (detection.clone(), Some(7)));
+        assert_eq!(stack.verify_commands, detection.verify_commands);
+        assert_eq!(get_stack(&pool, "s2").await.unwrap(), None);
+
+        assert!(set_verify_commands(&pool, "s1", &[command("../elsewhere", "make")]).await.is_err());
+        let stack = set_verify_commands(&pool, "s2", &[command("", "make check")]).await.unwrap();
+        assert_eq!((stack.detection, stack.verify_commands.len()), (StackDetection::default(), 1));
+
+        #[cfg(unix)]
+        {
+            let dir = tempfile::tempdir().unwrap();
+            let passed = run_verification(dir.path(), &command("", "echo ok")).await;
+            assert!(passed.success && passed.output == "ok\n");
+            let failed = run_verification(dir.path(), &command("", "echo broken >&2; exit 3")).await;
+            assert_eq!((failed.success, failed.exit_code, failed.output.as_str()), (false, Some(3), "broken\n"));
+            record_verification(&pool, "s1", &[passed.clone(), failed]).await.unwrap();
+            let stack = get_stack(&pool, "s1").await.unwrap().unwrap();
+            assert_eq!(stack.last_verification.unwrap()[0], passed);
+            assert!(stack.verified_at.is_some());
+        }
+    }
+}
@@ -0,0 +1,692 @@
+//! Sync rules between session worktrees and staging directories
+//!
+//! A rule mirrors the worktree files matching its source globs into a directory
+//! outside the repository, such as a folder another tool watches, and copies
+//! edits made there back into the worktree. Each run compares both sides with
+//! the content hashes recorded by the run before: a file changed on one side is
+//! copied to the other, a file deleted from the worktree is deleted from the
+//! destination, and a file changed on both sides is a conflict that is left
+//! alone until the two copies agree again. Rules run when their session's
+//! branch gains a checkpoint commit, when the session completes, or on demand,
+//! and each keeps the status and outcome of its last run.
+
+use chrono::Utc;
+use dashmap::DashSet;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commit_message::{git, session_worktree};
+use crate::context_packs::{candidate_files, compile_glob, glob_matches};
+use crate::profile_auth::ProfileManager;
+use crate::safe_write::content_hash;
+
+pub const MAX_SOURCES: usize = 20;
+/// How often worktree heads are checked for new checkpoints
+const CHECKPOINT_POLL: Duration = Duration::from_secs(15);
+/// Event carrying a rule whenever its status changes
+const STATUS_EVENT: &str = "sync_rule_status";
+
+/// Rules with a run in progress
+static RUNNING: Lazy<DashSet<String>> = Lazy::new(DashSet::new);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    /// Never run
+    Idle,
+    Running,
+    Synced,
+    /// The last run finished but left conflicting files alone
+    Conflicts,
+    Failed,
+}
+
+impl SyncStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyncStatus::Idle => "idle",
+            SyncStatus::Running => "running",
+            SyncStatus::Synced => "synced",
+            SyncStatus::Conflicts => "conflicts",
+            SyncStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "running" => SyncStatus::Running,
+            "synced" => SyncStatus::Synced,
+            "conflicts" => SyncStatus::Conflicts,
+            "failed" => SyncStatus::Failed,
+            _ => SyncStatus::Idle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTrigger {
+    Checkpoint,
+    Completion,
+    Manual,
+}
+
+impl SyncTrigger {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyncTrigger::Checkpoint => "checkpoint",
+            SyncTrigger::Completion => "completion",
+            SyncTrigger::Manual => "manual",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "checkpoint" => Some(SyncTrigger::Checkpoint),
+            "completion" => Some(SyncTrigger::Completion),
+            "manual" => Some(SyncTrigger::Manual),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub path: String,
+    pub reason: String,
+}
+
+/// What one run changed, as paths relative to both roots
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Copied from the worktree to the destination
+    pub pushed: Vec<String>,
+    /// Copied from the destination into the worktree
+    pub pulled: Vec<String>,
+    /// Deleted from the destination after being deleted from the worktree
+    pub removed: Vec<String>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRuleRequest {
+    pub session_id: String,
+    /// Globs relative to the worktree root, such as `docs/**/*.md`
+    pub sources: Vec<String>,
+    /// Absolute path of the staging directory
+    pub destination: String,
+    #[serde(default = "default_true")]
+    pub on_checkpoint: bool,
+    #[serde(default = "default_true")]
+    pub on_completion: bool,
+}
+
+impl SyncRuleRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sources.is_empty() || self.sources.len() > MAX_SOURCES {
+            return Err(format!("A sync rule needs between 1 and {} sources", MAX_SOURCES));
+        }
+        for source in &self.sources {
+            let relative = Path::new(source);
+            if source.trim().is_empty()
+                || relative.is_absolute()
+                || relative.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(format!("Source '{}' must be a pattern inside the worktree", source));
+            }
+            compile_glob(source)?;
+        }
+        if !Path::new(&self.destination).is_absolute() {
+            return Err("The destination must be an absolute path".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRule {
+    pub id: String,
+    pub session_id: String,
+    pub sources: Vec<String>,
+    pub destination: String,
+    pub on_checkpoint: bool,
+    pub on_completion: bool,
+    pub enabled: bool,
+    pub status: SyncStatus,
+    /// Worktree commit as of the last run
+    pub last_head: Option<String>,
+    pub last_trigger: Option<SyncTrigger>,
+    pub last_run_at: Option<String>,
+    pub last_result: Option<SyncReport>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+/// Refuse destinations that overlap the worktree, where syncing would copy files onto themselves
+fn check_destination(worktree: &Path, destination: &Path) -> Result<(), String> {
+    let worktree = worktree.canonicalize().unwrap_or_else(|_| worktree.to_path_buf());
+    let destination = destination.canonicalize().unwrap_or_else(|_| destination.to_path_buf());
+    if destination.starts_with(&worktree) || worktree.starts_with(&destination) {
+        return Err("The destination must be outside the session's worktree".to_string());
+    }
+    Ok(())
+}
+
+/// Hash of a regular file, `None` when there is none at `path`
+fn file_hash(path: &Path) -> Result<Option<String>, String> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() => std::fs::read(path)
+            .map(|content| Some(content_hash(&content)))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::copy(from, to).map_err(|e| format!("Failed to copy {} to {}: {}", from.display(), to.display(), e))?;
+    Ok(())
+}
+
+fn destination_files(destination: &Path) -> Vec<String> {
+    walkdir::WalkDir::new(destination)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| Some(entry.path().strip_prefix(destination).ok()?.to_string_lossy().replace('\\', "/")))
+        .collect()
+}
+
+/// Sync the files matching `sources` between the two roots, given the hashes
+/// recorded by the last run. Returns what changed and the hashes to record.
+pub fn sync_files(
+    worktree: &Path,
+    destination: &Path,
+    sources: &[String],
+    baseline: &HashMap<String, String>,
+) -> Result<(SyncReport, HashMap<String, String>), String> {
+    let globs = sources.iter().map(|source| compile_glob(source)).collect::<Result<Vec<_>, _>>()?;
+    let matches = |path: &String| {
+        let parts: Vec<&str> = path.split('/').collect();
+        globs.iter().any(|glob| glob_matches(glob, &parts))
+    };
+    std::fs::create_dir_all(destination).map_err(|e| format!("Failed to create {}: {}", destination.display(), e))?;
+
+    let mut paths: BTreeSet<String> = candidate_files(worktree).into_iter().filter(matches).collect();
+    paths.extend(destination_files(destination).into_iter().filter(matches));
+    paths.extend(baseline.keys().cloned());
+
+    let (mut report, mut synced) = (SyncReport::default(), HashMap::new());
+    for path in paths {
+        let (source, target) = (worktree.join(&path), destination.join(&path));
+        let (ours, theirs) = (file_hash(&source)?, file_hash(&target)?);
+        let base = baseline.get(&path);
+        if ours == theirs {
+            if let Some(hash) = ours {
+                synced.insert(path, hash);
+            }
+            continue;
+        }
+        match (ours.as_ref() != base, theirs.as_ref() != base) {
+            (true, false) => match ours {
+                Some(hash) => {
+                    copy_file(&source, &target)?;
+                    synced.insert(path.clone(), hash);
+                    report.pushed.push(path);
+                }
+                None => {
+                    std::fs::remove_file(&target).map_err(|e| format!("Failed to remove {}: {}", target.display(), e))?;
+                    report.removed.push(path);
+                }
+            },
+            (false, true) => match theirs {
+                Some(hash) => {
+                    copy_file(&target, &source)?;
+                    synced.insert(path.clone(), hash);
+                    report.pulled.push(path);
+                }
+                // Deleted from the destination only: the worktree keeps its copy
+                None => {
+                    synced.extend(base.map(|hash| (path, hash.clone())));
+                }
+            },
+            _ => {
+                let reason = match (&ours, &theirs) {
+                    (None, _) => "Deleted from the worktree but changed in the destination",
+                    (_, None) => "Deleted from the destination but changed in the worktree",
+                    _ => "Changed in both the worktree and the destination",
+                };
+                synced.extend(base.map(|hash| (path.clone(), hash.clone())));
+                report.conflicts.push(SyncConflict { path, reason: reason.to_string() });
+            }
+        }
+    }
+    Ok((report, synced))
+}
+
+type RuleRow = (
+    String,
+    String,
+    String,
+    String,
+    bool,
+    bool,
+    bool,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+const RULE_COLUMNS: &str = "id, session_id, sources, destination, on_checkpoint, on_completion, enabled, status, \
+    last_head, last_trigger, last_run_at, last_result, last_error, created_at";
+
+fn rule_from_row(row: RuleRow) -> SyncRule {
+    let (id, session_id, sources, destination, on_checkpoint, on_completion, enabled, status, last_head, last_trigger, last_run_at, last_result, last_error, created_at) =
+        row;
+    SyncRule {
+        id,
+        session_id,
+        sources: serde_json::from_str(&sources).unwrap_or_default(),
+        destination,
+        on_checkpoint,
+        on_completion,
+        enabled,
+        status: SyncStatus::parse(&status),
+        last_head,
+        last_trigger: last_trigger.as_deref().and_then(SyncTrigger::parse),
+        last_run_at,
+        last_result: last_result.and_then(|json| serde_json::from_str(&json).ok()),
+        last_error,
+        created_at,
+    }
+}
+
+pub async fn create_rule(db: &SqlitePool, request: &SyncRuleRequest, head: Option<&str>) -> Result<SyncRule, String> {
+    request.validate()?;
+    let id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO sync_rules (id, session_id, sources, destination, on_checkpoint, on_completion, last_head) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(&request.session_id)
+    .bind(serde_json::to_string(&request.sources).map_err(|e| e.to_string())?)
+    .bind(&request.destination)
+    .bind(request.on_checkpoint)
+    .bind(request.on_completion)
+    .bind(head)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to save sync rule: {}", e))?;
+    get_rule(db, &id).await
+}
+
+pub async fn get_rule(db: &SqlitePool, rule_id: &str) -> Result<SyncRule, String> {
+    sqlx::query_as::<_, RuleRow>(&format!("SELECT {} FROM sync_rules WHERE id = ?", RULE_COLUMNS))
+        .bind(rule_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to load sync rule: {}", e))?
+        .map(rule_from_row)
+        .ok_or_else(|| format!("Sync rule '{}' not found", rule_id))
+}
+
+/// Rules of one session, or of every session, oldest first
+pub async fn list_rules(db: &SqlitePool, session_id: Option<&str>) -> Result<Vec<SyncRule>, String> {
+    let rows = sqlx::query_as::<_, RuleRow>(&format!(
+        "SELECT {} FROM sync_rules WHERE ?1 IS NULL OR session_id = ?1 ORDER BY created_at, id",
+        RULE_COLUMNS
+    ))
+    .bind(session_id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to load sync rules: {}", e))?;
+    Ok(rows.into_iter().map(rule_from_row).collect())
+}
+
+pub async fn delete_rule(db: &SqlitePool, rule_id: &str) -> Result<(), String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM sync_rule_files WHERE rule_id = ?").bind(rule_id).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    let deleted = sqlx::query("DELETE FROM sync_rules WHERE id = ?").bind(rule_id).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    if deleted.rows_affected() == 0 {
+        return Err(format!("Sync rule '{}' not found", rule_id));
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+pub async fn set_enabled(db: &SqlitePool, rule_id: &str, enabled: bool) -> Result<SyncRule, String> {
+    sqlx::query("UPDATE sync_rules SET enabled = ? WHERE id = ?").bind(enabled).bind(rule_id).execute(db).await.map_err(|e| e.to_string())?;
+    get_rule(db, rule_id).await
+}
+
+async fn set_status(db: &SqlitePool, rule_id: &str, status: SyncStatus) -> Result<(), String> {
+    sqlx::query("UPDATE sync_rules SET status = ? WHERE id = ?").bind(status.as_str()).bind(rule_id).execute(db).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn set_head(db: &SqlitePool, rule_id: &str, head: &str) -> Result<(), String> {
+    sqlx::query("UPDATE sync_rules SET last_head = ? WHERE id = ?").bind(head).bind(rule_id).execute(db).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn baseline(db: &SqlitePool, rule_id: &str) -> Result<HashMap<String, String>, String> {
+    let rows = sqlx::query_as::<_, (String, String)>("SELECT path, hash FROM sync_rule_files WHERE rule_id = ?")
+        .bind(rule_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to load sync state: {}", e))?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Record how a run ended, replacing the baseline when it finished
+pub async fn record_run(
+    db: &SqlitePool,
+    rule_id: &str,
+    trigger: SyncTrigger,
+    head: Option<&str>,
+    outcome: &Result<(SyncReport, HashMap<String, String>), String>,
+) -> Result<(), String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    let (status, result, error) = match outcome {
+        Ok((report, synced)) => {
+            sqlx::query("DELETE FROM sync_rule_files WHERE rule_id = ?").bind(rule_id).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+            for (path, hash) in synced {
+                sqlx::query("INSERT INTO sync_rule_files (rule_id, path, hash) VALUES (?, ?, ?)")
+                    .bind(rule_id)
+                    .bind(path)
+                    .bind(hash)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            let status = if report.conflicts.is_empty() { SyncStatus::Synced } else { SyncStatus::Conflicts };
+            (status, Some(serde_json::to_string(report).map_err(|e| e.to_string())?), None)
+        }
+        Err(e) => (SyncStatus::Failed, None, Some(e.clone())),
+    };
+    sqlx::query(
+        "UPDATE sync_rules SET status = ?, last_head = COALESCE(?, last_head), last_trigger = ?, last_run_at = ?, last_result = ?, last_error = ? WHERE id = ?",
+    )
+    .bind(status.as_str())
+    .bind(head)
+    .bind(trigger.as_str())
+    .bind(Utc::now().to_rfc3339())
+    .bind(result)
+    .bind(error)
+    .bind(rule_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+async fn worktree_for(app_handle: &AppHandle, db: &SqlitePool, session_id: &str) -> Result<PathBuf, String> {
+    match session_worktree(app_handle, session_id).await {
+        Ok(path) => Ok(path),
+        Err(e) => crate::repo_relocation::recorded_worktree(db, session_id).await.ok_or(e),
+    }
+}
+
+fn head_of(worktree: &Path) -> Option<String> {
+    git(worktree, &["rev-parse", "HEAD"]).ok().map(|head| head.trim().to_string())
+}
+
+/// Run a rule now; a failed sync is reported through the rule's status
+pub async fn run_rule(app_handle: &AppHandle, db: &SqlitePool, rule_id: &str, trigger: SyncTrigger) -> Result<SyncRule, String> {
+    if !RUNNING.insert(rule_id.to_string()) {
+        return Err(format!("Sync rule '{}' is already running", rule_id));
+    }
+    let result = async {
+        let rule = get_rule(db, rule_id).await?;
+        set_status(db, rule_id, SyncStatus::Running).await?;
+        let _ = app_handle.emit(STATUS_EVENT, &SyncRule { status: SyncStatus::Running, ..rule.clone() });
+
+        let (head, outcome) = match worktree_for(app_handle, db, &rule.session_id).await {
+            Ok(worktree) => {
+                let synced = baseline(db, rule_id).await?;
+                let head = head_of(&worktree);
+                let (sources, destination) = (rule.sources.clone(), PathBuf::from(&rule.destination));
+                let outcome = tokio::task::spawn_blocking(move || {
+                    check_destination(&worktree, &destination)?;
+                    sync_files(&worktree, &destination, &sources, &synced)
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Sync task failed: {}", e)));
+                (head, outcome)
+            }
+            Err(e) => (None, Err(e)),
+        };
+        if let Err(e) = &outcome {
+            log::warn!("Sync rule {} failed: {}", rule_id, e);
+        }
+        record_run(db, rule_id, trigger, head.as_deref(), &outcome).await?;
+        let rule = get_rule(db, rule_id).await?;
+        let _ = app_handle.emit(STATUS_EVENT, &rule);
+        Ok(rule)
+    }
+    .await;
+    RUNNING.remove(rule_id);
+    result
+}
+
+/// Run the completion rules of the session behind a completed session or thread
+pub async fn run_on_completion(app_handle: &AppHandle, db: &SqlitePool, id: &str, kind: &str) {
+    let Some(session_id) = crate::session_analytics::completed_session(db, id, kind).await else { return };
+    let rules = match list_rules(db, Some(&session_id)).await {
+        Ok(rules) => rules,
+        Err(e) => return log::warn!("Sync rules of {} skipped: {}", session_id, e),
+    };
+    for rule in rules.into_iter().filter(|rule| rule.enabled && rule.on_completion) {
+        if let Err(e) = run_rule(app_handle, db, &rule.id, SyncTrigger::Completion).await {
+            log::warn!("Sync rule {} skipped: {}", rule.id, e);
+        }
+    }
+}
+
+/// Run checkpoint rules whenever their worktree's HEAD moves
+pub fn spawn_watcher(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(CHECKPOINT_POLL).await;
+            let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+                continue;
+            };
+            let Some(db) = profile_manager.db_pool.read().await.clone() else {
+                continue;
+            };
+            let rules = match list_rules(&db, None).await {
+                Ok(rules) => rules,
+                Err(e) => {
+                    log::warn!("Sync rules skipped: {}", e);
+                    continue;
+                }
+            };
+            for rule in rules.into_iter().filter(|rule| rule.enabled && rule.on_checkpoint) {
+                let Some(head) = worktree_for(&app_handle, &db, &rule.session_id).await.ok().and_then(|w| head_of(&w)) else {
+                    continue;
+                };
+                let result = match rule.last_head.as_deref() {
+                    Some(last) if last == head => continue,
+                    Some(_) => run_rule(&app_handle, &db, &rule.id, SyncTrigger::Checkpoint).await.map(|_| ()),
+                    None => set_head(&db, &rule.id, &head).await,
+                };
+                if let Err(e) = result {
+                    log::warn!("Sync rule {} skipped: {}", rule.id, e);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn create_sync_rule(
+    request: SyncRuleRequest,
+    app_handle: AppHandle,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<SyncRule, String> {
+    let request = SyncRuleRequest { session_id: crate::session_codes::resolve(&request.session_id), ..request };
+    request.validate()?;
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let worktree = worktree_for(&app_handle, db, &request.session_id).await?;
+    check_destination(&worktree, Path::new(&request.destination))?;
+    create_rule(db, &request, head_of(&worktree).as_deref()).await
+}
+
+#[tauri::command]
+pub async fn list_sync_rules(session_id: String, profile_manager: State<'_, ProfileManager>) -> Result<Vec<SyncRule>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    list_rules(db, Some(&crate::session_codes::resolve(&session_id))).await
+}
+
+#[tauri::command]
+pub async fn delete_sync_rule(rule_id: String, profile_manager: State<'_, ProfileManager>) -> Result<(), String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    delete_rule(db, &rule_id).await
+}
+
+#[tauri::command]
+pub async fn set_sync_rule_enabled(rule_id: String, enabled: bool, profile_manager: State<'_, ProfileManager>) -> Result<SyncRule, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    set_enabled(db, &rule_id, enabled).await
+}
+
+#[tauri::command]
+pub async fn run_sync_rule(rule_id: String, app_handle: AppHandle, profile_manager: State<'_, ProfileManager>) -> Result<SyncRule, String> {
+    let db = profile_manager.db_pool.read().await.clone().ok_or("Database not available")?;
+    run_rule(&app_handle, &db, &rule_id, SyncTrigger::Manual).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn read(root: &Path, path: &str) -> Option<String> {
+        std::fs::read_to_string(root.join(path)).ok()
+    }
+
+    #[test]
+    fn test_sync_copies_both_ways_and_reports_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let (worktree, destination) = (dir.path().join("worktree"), dir.path().join("staging"));
+        write(&worktree, "docs/guide.md", "v1");
+        write(&worktree, "docs/api.md", "api");
+        write(&worktree, "docs/old.md", "old");
+        write(&worktree, "src/main.rs", "fn main() {}");
+        let sources = vec!["docs/**/*.md".to_string()];
+
+        let (report, synced) = sync_files(&worktree, &destination, &sources, &HashMap::new()).unwrap();
+        assert_eq!(report.pushed, vec!["docs/api.md", "docs/guide.md", "docs/old.md"]);
+        assert_eq!(read(&destination, "docs/guide.md").as_deref(), Some("v1"));
+        assert_eq!(read(&destination, "src/main.rs"), None);
+
+        // One change per side, a deletion, a new staged file and a conflicting edit
+        write(&worktree, "docs/guide.md", "v2");
+        write(&destination, "docs/api.md", "api, edited");
+        std::fs::remove_file(worktree.join("docs/old.md")).unwrap();
+        write(&destination, "docs/new/notes.md", "notes");
+        write(&destination, "docs/ignored.txt", "txt");
+        let (report, synced) = sync_files(&worktree, &destination, &sources, &synced).unwrap();
+        assert_eq!(report.pushed, vec!["docs/guide.md"]);
+        assert_eq!(report.pulled, vec!["docs/api.md", "docs/new/notes.md"]);
+        assert_eq!(report.removed, vec!["docs/old.md"]);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(read(&worktree, "docs/api.md").as_deref(), Some("api, edited"));
+        assert_eq!(read(&worktree, "docs/new/notes.md").as_deref(), Some("notes"));
+        assert_eq!(read(&worktree, "docs/ignored.txt"), None);
+        assert_eq!(read(&destination, "docs/old.md"), None);
+
+        write(&worktree, "docs/guide.md", "v3 from the agent");
+        write(&destination, "docs/guide.md", "v3 from the user");
+        let (report, synced) = sync_files(&worktree, &destination, &sources, &synced).unwrap();
+        assert_eq!(report.conflicts, vec![SyncConflict {
+            path: "docs/guide.md".to_string(),
+            reason: "Changed in both the worktree and the destination".to_string()
+        }]);
+        assert_eq!(read(&destination, "docs/guide.md").as_deref(), Some("v3 from the user"));
+
+        // Once both sides agree the conflict clears
+        write(&destination, "docs/guide.md", "v3 from the agent");
+        let (report, _) = sync_files(&worktree, &destination, &sources, &synced).unwrap();
+        assert_eq!(report, SyncReport::default());
+
+        assert!(check_destination(&worktree, &worktree.join("out")).is_err());
+        assert!(check_destination(&worktree, &destination).is_ok());
+        let request = SyncRuleRequest {
+            session_id: "s1".to_string(),
+            sources: vec!["../secrets/*".to_string()],
+            destination: destination.to_string_lossy().to_string(),
+            on_checkpoint: true,
+            on_completion: true,
+        };
+        assert!(request.validate().is_err());
+        assert!(SyncRuleRequest { sources, destination: "relative/dir".to_string(), ..request }.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rules_round_trip() {
+        let pool = test_pool().await;
+
+        let request = SyncRuleRequest {
+            session_id: "s1".to_string(),
+            sources: vec!["docs/*.md".to_string()],
+            destination: "/tmp/staging".to_string(),
+            on_checkpoint: false,
+            on_completion: true,
+        };
+        let rule = create_rule(&pool, &request, Some("abc")).await.unwrap();
+        assert_eq!((rule.status, rule.last_head.as_deref(), rule.on_checkpoint), (SyncStatus::Idle, Some("abc"), false));
+        assert_eq!(list_rules(&pool, Some("s1")).await.unwrap(), vec![rule.clone()]);
+        assert!(list_rules(&pool, Some("s2")).await.unwrap().is_empty());
+
+        let report = SyncReport {
+            conflicts: vec![SyncConflict { path: "docs/a.md".to_string(), reason: "both".to_string() }],
+            ..SyncReport::default()
+        };
+        let synced = HashMap::from([("docs/b.md".to_string(), "hash".to_string())]);
+        record_run(&pool, &rule.id, SyncTrigger::Checkpoint, Some("def"), &Ok((report.clone(), synced.clone()))).await.unwrap();
+        let updated = get_rule(&pool, &rule.id).await.unwrap();
+        assert_eq!(updated.status, SyncStatus::Conflicts);
+        assert_eq!((updated.last_head.as_deref(), updated.last_trigger), (Some("def"), Some(SyncTrigger::Checkpoint)));
+        assert_eq!(updated.last_result, Some(report));
+        assert_eq!(baseline(&pool, &rule.id).await.unwrap(), synced);
+
+        // A failed run keeps the baseline of the last finished one
+        record_run(&pool, &rule.id, SyncTrigger::Manual, None, &Err("disk full".to_string())).await.unwrap();
+        let failed = get_rule(&pool, &rule.id).await.unwrap();
+        assert_eq!((failed.status, failed.last_error.as_deref(), failed.last_head.as_deref()), (SyncStatus::Failed, Some("disk full"), Some("def")));
+        assert_eq!(baseline(&pool, &rule.id).await.unwrap(), synced);
+
+        assert!(!set_enabled(&pool, &rule.id, false).await.unwrap().enabled);
+        delete_rule(&pool, &rule.id).await.unwrap();
+        assert!(get_rule(&pool, &rule.id).await.is_err());
+        assert!(baseline(&pool, &rule.id).await.unwrap().is_empty());
+    }
+}
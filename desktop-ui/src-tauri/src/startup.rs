@@ -0,0 +1,427 @@
+//! Startup orchestration
+//!
+//! Subsystems are declared with the subsystems they depend on, and each starts
+//! as soon as those have finished, so independent ones initialize concurrently.
+//! `StartupPlan::run` returns once everything the app needs before it can take
+//! commands is done; deferred subsystems only start then and finish in the
+//! background. A subsystem whose dependency failed, is unknown or depends on it
+//! in turn is skipped rather than started.
+//!
+//! Every subsystem is timed. When the last one finishes the launch is compared
+//! with the median of recent launches, kept next to the config file, and
+//! subsystems that got markedly slower are flagged. The latest report is kept
+//! for `get_startup_report`.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::task::JoinSet;
+
+use crate::app_state::AppConfig;
+
+/// Launches kept as the regression baseline
+const HISTORY_RUNS: usize = 20;
+/// Launches needed before a baseline is trusted
+const MIN_BASELINE_RUNS: usize = 3;
+/// A subsystem regressed when it took this many times its median...
+const REGRESSION_FACTOR: f64 = 1.5;
+/// ...and at least this much longer, so fast subsystems don't flag on jitter
+const REGRESSION_MIN_MS: u64 = 50;
+/// Name the time until ready is recorded under in the history
+const READY: &str = "ready";
+
+static LAST_REPORT: Lazy<Mutex<Option<StartupReport>>> = Lazy::new(|| Mutex::new(None));
+
+type InitFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+struct Subsystem {
+    name: &'static str,
+    deps: &'static [&'static str],
+    deferred: bool,
+    init: Box<dyn FnOnce() -> InitFuture + Send>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubsystemStatus {
+    Pending,
+    Running,
+    Ready,
+    Failed,
+    Skipped,
+}
+
+impl SubsystemStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Ready | Self::Failed | Self::Skipped)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubsystemTiming {
+    pub name: String,
+    pub depends_on: Vec<String>,
+    pub deferred: bool,
+    pub status: SubsystemStatus,
+    /// Since startup began
+    pub started_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// A subsystem, or `ready` for the whole, that took markedly longer than usual
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartupRegression {
+    pub name: String,
+    pub duration_ms: u64,
+    /// Median of recent launches
+    pub baseline_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartupReport {
+    pub started_at: String,
+    /// Until every subsystem that is not deferred had finished
+    pub ready_ms: Option<u64>,
+    /// Until every subsystem had finished
+    pub total_ms: Option<u64>,
+    pub subsystems: Vec<SubsystemTiming>,
+    /// Filled in once startup has finished
+    pub regressions: Vec<StartupRegression>,
+}
+
+#[derive(Default)]
+pub struct StartupPlan {
+    subsystems: Vec<Subsystem>,
+}
+
+impl StartupPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Initialize `name` once `deps` are ready; `run` waits for it
+    pub fn add<F, Fut>(&mut self, name: &'static str, deps: &'static [&'static str], init: F) -> &mut Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.push(name, deps, false, init)
+    }
+
+    /// Initialize `name` in the background once `deps` are ready and startup is
+    /// done; nothing waits for it
+    pub fn defer<F, Fut>(&mut self, name: &'static str, deps: &'static [&'static str], init: F) -> &mut Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.push(name, deps, true, init)
+    }
+
+    fn push<F, Fut>(&mut self, name: &'static str, deps: &'static [&'static str], deferred: bool, init: F) -> &mut Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.subsystems.push(Subsystem { name, deps, deferred, init: Box::new(move || Box::pin(init())) });
+        self
+    }
+
+    /// Run the plan, calling `on_update` whenever a subsystem starts or finishes.
+    /// Returns the report as of the moment every subsystem that is not deferred
+    /// has finished; deferred ones carry on in the background.
+    pub async fn run(self, on_update: impl Fn(&StartupReport) + Send + 'static) -> StartupReport {
+        let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(drive(self.subsystems, on_update, ready_tx));
+        ready_rx.await.expect("startup driver stopped before startup was ready")
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// Why a pending subsystem can never start, if it can't
+fn blocked_by(report: &StartupReport, index: &HashMap<&str, usize>, i: usize) -> Option<String> {
+    report.subsystems[i].depends_on.iter().find_map(|dep| match index.get(dep.as_str()) {
+        None => Some(format!("unknown dependency {}", dep)),
+        Some(&j) if report.subsystems[j].status == SubsystemStatus::Failed => Some(format!("{} failed", dep)),
+        Some(&j) if report.subsystems[j].status == SubsystemStatus::Skipped => Some(format!("{} was skipped", dep)),
+        // Deferred subsystems only start once everything else is done
+        Some(&j) if report.subsystems[j].deferred && !report.subsystems[i].deferred => Some(format!("{} is deferred", dep)),
+        _ => None,
+    })
+}
+
+async fn drive(
+    subsystems: Vec<Subsystem>,
+    on_update: impl Fn(&StartupReport),
+    ready_tx: tokio::sync::oneshot::Sender<StartupReport>,
+) {
+    let start = Instant::now();
+    let index: HashMap<&str, usize> = subsystems.iter().enumerate().map(|(i, s)| (s.name, i)).collect();
+    let mut report = StartupReport {
+        started_at: chrono::Utc::now().to_rfc3339(),
+        ready_ms: None,
+        total_ms: None,
+        subsystems: subsystems
+            .iter()
+            .map(|s| SubsystemTiming {
+                name: s.name.to_string(),
+                depends_on: s.deps.iter().map(|d| d.to_string()).collect(),
+                deferred: s.deferred,
+                status: SubsystemStatus::Pending,
+                started_ms: None,
+                duration_ms: None,
+                error: None,
+            })
+            .collect(),
+        regressions: Vec::new(),
+    };
+    let mut inits: Vec<Option<Box<dyn FnOnce() -> InitFuture + Send>>> = subsystems.into_iter().map(|s| Some(s.init)).collect();
+    let mut ready_tx = Some(ready_tx);
+    let mut running = JoinSet::new();
+    let mut tasks = HashMap::new();
+
+    loop {
+        // Skipping one subsystem can block others, so repeat until nothing changes
+        while let Some((i, reason)) = (0..report.subsystems.len())
+            .filter(|&i| report.subsystems[i].status == SubsystemStatus::Pending)
+            .find_map(|i| blocked_by(&report, &index, i).map(|reason| (i, reason)))
+        {
+            log::warn!("startup: skipping {}: {}", report.subsystems[i].name, reason);
+            report.subsystems[i].status = SubsystemStatus::Skipped;
+            report.subsystems[i].error = Some(reason);
+        }
+
+        let ready = report.subsystems.iter().all(|s| s.deferred || s.status.is_finished());
+        if ready && report.ready_ms.is_none() {
+            report.ready_ms = Some(elapsed_ms(start));
+            log::info!("startup: ready in {}ms", elapsed_ms(start));
+            if let Some(tx) = ready_tx.take() {
+                let _ = tx.send(report.clone());
+            }
+        }
+
+        for (i, init) in inits.iter_mut().enumerate() {
+            let subsystem = &report.subsystems[i];
+            if subsystem.status != SubsystemStatus::Pending || (subsystem.deferred && !ready) {
+                continue;
+            }
+            if !subsystem.depends_on.iter().all(|dep| report.subsystems[index[dep.as_str()]].status == SubsystemStatus::Ready) {
+                continue;
+            }
+            let Some(init) = init.take() else { continue };
+            report.subsystems[i].status = SubsystemStatus::Running;
+            report.subsystems[i].started_ms = Some(elapsed_ms(start));
+            let handle = running.spawn(async move {
+                let began = Instant::now();
+                (init().await, elapsed_ms(began))
+            });
+            tasks.insert(handle.id(), i);
+        }
+        on_update(&report);
+
+        let (i, result, duration_ms) = match running.join_next_with_id().await {
+            Some(Ok((id, (result, duration_ms)))) => (tasks[&id], result, Some(duration_ms)),
+            Some(Err(e)) => (tasks[&e.id()], Err(format!("panicked: {}", e)), None),
+            None if report.subsystems.iter().any(|s| s.status == SubsystemStatus::Pending) => {
+                // Nothing is running, so whatever is pending and allowed to start waits on a cycle
+                let stuck = |s: &SubsystemTiming| s.status == SubsystemStatus::Pending && (ready || !s.deferred);
+                for subsystem in report.subsystems.iter_mut().filter(|s| stuck(s)) {
+                    log::warn!("startup: skipping {}: dependency cycle", subsystem.name);
+                    subsystem.status = SubsystemStatus::Skipped;
+                    subsystem.error = Some("dependency cycle".to_string());
+                }
+                continue;
+            }
+            None => break,
+        };
+        let subsystem = &mut report.subsystems[i];
+        subsystem.duration_ms = duration_ms;
+        match result {
+            Ok(()) => {
+                log::info!("startup: {} ready in {}ms", subsystem.name, duration_ms.unwrap_or_default());
+                subsystem.status = SubsystemStatus::Ready;
+            }
+            Err(e) => {
+                log::error!("startup: {} failed: {}", subsystem.name, e);
+                subsystem.status = SubsystemStatus::Failed;
+                subsystem.error = Some(e);
+            }
+        }
+    }
+
+    report.total_ms = Some(elapsed_ms(start));
+    on_update(&report);
+}
+
+/// Durations of one launch by subsystem, with the time until ready as `ready`
+type Launch = HashMap<String, u64>;
+
+fn launch_durations(report: &StartupReport) -> Launch {
+    let mut launch: Launch = report
+        .subsystems
+        .iter()
+        .filter(|s| s.status == SubsystemStatus::Ready)
+        .filter_map(|s| Some((s.name.clone(), s.duration_ms?)))
+        .collect();
+    if let Some(ready_ms) = report.ready_ms {
+        launch.insert(READY.to_string(), ready_ms);
+    }
+    launch
+}
+
+/// What in `report` took markedly longer than its median over `history`
+pub fn regressions(report: &StartupReport, history: &[Launch]) -> Vec<StartupRegression> {
+    let mut regressions: Vec<StartupRegression> = launch_durations(report)
+        .into_iter()
+        .filter_map(|(name, duration_ms)| {
+            let mut samples: Vec<u64> = history.iter().filter_map(|launch| launch.get(&name).copied()).collect();
+            if samples.len() < MIN_BASELINE_RUNS {
+                return None;
+            }
+            samples.sort_unstable();
+            let baseline_ms = samples[samples.len() / 2];
+            let regressed = duration_ms as f64 > baseline_ms as f64 * REGRESSION_FACTOR && duration_ms >= baseline_ms + REGRESSION_MIN_MS;
+            regressed.then_some(StartupRegression { name, duration_ms, baseline_ms })
+        })
+        .collect();
+    regressions.sort_by(|a, b| a.name.cmp(&b.name));
+    regressions
+}
+
+fn history_path() -> PathBuf {
+    AppConfig::config_path().with_file_name("startup_history.json")
+}
+
+/// Keep `report` for `get_startup_report`. Once startup has finished it is also
+/// checked for regressions against recent launches and added to them.
+pub fn publish(report: &StartupReport) {
+    let mut report = report.clone();
+    if report.total_ms.is_some() {
+        let path = history_path();
+        let mut history: Vec<Launch> = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        report.regressions = regressions(&report, &history);
+        for regression in &report.regressions {
+            log::warn!("startup: {} took {}ms, usually {}ms", regression.name, regression.duration_ms, regression.baseline_ms);
+        }
+        history.push(launch_durations(&report));
+        let excess = history.len().saturating_sub(HISTORY_RUNS);
+        history.drain(..excess);
+        let saved = serde_json::to_vec(&history)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            log::warn!("startup: failed to save startup history to {}: {}", path.display(), e);
+        }
+    }
+    *LAST_REPORT.lock().unwrap() = Some(report);
+}
+
+/// Per-subsystem timings of this launch, None before startup began
+#[tauri::command]
+pub async fn get_startup_report() -> Result<Option<StartupReport>, String> {
+    Ok(LAST_REPORT.lock().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_plan_runs_by_dependency_and_defers() {
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut plan = StartupPlan::new();
+        plan.add("config", &[], || async {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            Ok(())
+        })
+        .add("database", &[], || async {
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            Ok(())
+        })
+        .add("profiles", &["config", "database"], || async { Ok(()) })
+        .add("worktrees", &[], || async { Err("no git".to_string()) })
+        .add("sessions", &["worktrees"], || async { Ok(()) })
+        .add("orphan", &["missing"], || async { Ok(()) })
+        .add("chicken", &["egg"], || async { Ok(()) })
+        .add("egg", &["chicken"], || async { Ok(()) })
+        .defer("migration", &["database"], || async {
+            let _ = release_rx.await;
+            Ok(())
+        });
+
+        let ready = plan
+            .run(move |report| {
+                if report.total_ms.is_some() {
+                    let _ = done_tx.send(report.clone());
+                }
+            })
+            .await;
+        let timing = |report: &StartupReport, name: &str| report.subsystems.iter().find(|s| s.name == name).unwrap().clone();
+
+        let (config, database, profiles) = (timing(&ready, "config"), timing(&ready, "database"), timing(&ready, "profiles"));
+        assert!(database.started_ms.unwrap() < config.started_ms.unwrap() + config.duration_ms.unwrap(), "independent subsystems run together");
+        assert!(profiles.started_ms.unwrap() >= config.started_ms.unwrap() + config.duration_ms.unwrap());
+        assert_eq!(profiles.status, SubsystemStatus::Ready);
+        assert_eq!(timing(&ready, "worktrees").status, SubsystemStatus::Failed);
+        assert_eq!(timing(&ready, "sessions").error.as_deref(), Some("worktrees failed"));
+        assert_eq!(timing(&ready, "orphan").error.as_deref(), Some("unknown dependency missing"));
+        assert_eq!(timing(&ready, "egg").error.as_deref(), Some("dependency cycle"));
+        assert!(ready.ready_ms.is_some() && ready.total_ms.is_none());
+        assert_eq!(timing(&ready, "migration").status, SubsystemStatus::Pending, "deferred subsystems start after ready");
+
+        release_tx.send(()).unwrap();
+        let done = done_rx.recv().await.unwrap();
+        assert_eq!(timing(&done, "migration").status, SubsystemStatus::Ready);
+        assert!(done.total_ms.unwrap() >= done.ready_ms.unwrap());
+    }
+
+    #[test]
+    fn test_regressions_against_median() {
+        let report = StartupReport {
+            started_at: String::new(),
+            ready_ms: Some(900),
+            total_ms: Some(1000),
+            subsystems: [("database", 400, SubsystemStatus::Ready), ("config", 30, SubsystemStatus::Ready), ("profiles", 900, SubsystemStatus::Failed)]
+                .into_iter()
+                .map(|(name, duration, status)| SubsystemTiming {
+                    name: name.to_string(),
+                    depends_on: Vec::new(),
+                    deferred: false,
+                    status,
+                    started_ms: Some(0),
+                    duration_ms: Some(duration),
+                    error: None,
+                })
+                .collect(),
+            regressions: Vec::new(),
+        };
+        let launch = |database: u64, config: u64, ready: u64| -> Launch {
+            [("database", database), ("config", config), ("profiles", 10), (READY, ready)].into_iter().map(|(n, d)| (n.to_string(), d)).collect()
+        };
+
+        // Too few launches for a baseline
+        assert!(regressions(&report, &[launch(100, 5, 300), launch(100, 5, 300)]).is_empty());
+
+        let history = [launch(100, 5, 800), launch(120, 10, 850), launch(5000, 10, 700)];
+        let found = regressions(&report, &history);
+        // config is 3x slower but only by 20ms; failed profiles have no duration to compare
+        assert_eq!(
+            found,
+            vec![StartupRegression { name: "database".to_string(), duration_ms: 400, baseline_ms: 120 }]
+        );
+    }
+}
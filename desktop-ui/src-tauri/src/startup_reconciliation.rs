@@ -0,0 +1,413 @@
+//! Startup reconciliation of the database with processes and the filesystem
+//!
+//! Every chat session and thread process is recorded in `session_processes` with
+//! its PID while its output is being read. A crash or forced quit leaves rows
+//! marked running, and since chat processes are not killed with the app, their
+//! agent may still be running unattended. On launch the scan:
+//! - reaps processes from recorded PIDs that are still alive, after checking that
+//!   the PID still runs the recorded program
+//! - corrects sessions marked running that have no live process: threads become
+//!   idle (`thread_attach` resumes them), chat sessions become errors
+//! - fails batch sessions and runs left running
+//! - flags recorded worktrees whose directory is gone while their repository is
+//!   still there (moved repositories are `repo_relocation`'s concern)
+//!
+//! The result is emitted as `startup_reconciliation` and kept for
+//! `get_startup_reconciliation`, in case the UI subscribes after it was sent.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use unified_core::domain::SessionStatus;
+
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+use crate::session_commands::AmpSessionMap;
+
+const INTERRUPTED: &str = "Interrupted when the app last exited";
+
+/// Statuses set by the last scan or a failed process, until the session starts a process again
+static CORRECTED: Lazy<DashMap<String, SessionStatus>> = Lazy::new(DashMap::new);
+static LAST_SUMMARY: Lazy<Mutex<Option<ReconciliationSummary>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorrectedSession {
+    pub session_id: String,
+    pub kind: String,
+    pub status: SessionStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingWorktree {
+    pub session_id: String,
+    pub worktree_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReapedProcess {
+    pub session_id: String,
+    pub pid: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconciliationSummary {
+    pub corrected: Vec<CorrectedSession>,
+    pub interrupted_batch_sessions: u64,
+    pub missing_worktrees: Vec<MissingWorktree>,
+    pub reaped: Vec<ReapedProcess>,
+    /// Failures that did not stop the scan
+    pub errors: Vec<String>,
+    /// Summary text in the user's language
+    pub message: String,
+}
+
+impl ReconciliationSummary {
+    pub fn is_clean(&self) -> bool {
+        self.corrected.is_empty() && self.interrupted_batch_sessions == 0 && self.missing_worktrees.is_empty() && self.reaped.is_empty()
+    }
+}
+
+/// Record a session's new process; `kind` is "chat" or "thread"
+pub async fn process_started(db: Option<&SqlitePool>, session_id: &str, kind: &str, pid: Option<u32>, program: &str) {
+    CORRECTED.remove(session_id);
+    let Some(db) = db else {
+        return;
+    };
+    crate::chaos::delay_db_write().await;
+    let result = sqlx::query(
+        "INSERT INTO session_processes (session_id, kind, pid, program, status) VALUES (?, ?, ?, ?, 'running')
+         ON CONFLICT(session_id) DO UPDATE SET kind = excluded.kind, pid = excluded.pid, program = excluded.program,
+             status = 'running', error = NULL, started_at = (datetime('now', 'utc') || 'Z'), updated_at = (datetime('now', 'utc') || 'Z')",
+    )
+    .bind(session_id)
+    .bind(kind)
+    .bind(pid.map(i64::from))
+    .bind(program)
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        log::warn!("Failed to record process of session {}: {}", session_id, e);
+    }
+}
+
+/// Mark a session idle once its process's output ends. A restarted session has
+/// already recorded its new process, so only the row for `pid` is touched.
+pub async fn process_ended(db: Option<&SqlitePool>, session_id: &str, pid: Option<u32>) {
+    let Some(db) = db else {
+        return;
+    };
+    crate::chaos::delay_db_write().await;
+    let result = sqlx::query(
+        "UPDATE session_processes SET status = 'idle', pid = NULL, updated_at = (datetime('now', 'utc') || 'Z')
+         WHERE session_id = ? AND pid IS ?",
+    )
+    .bind(session_id)
+    .bind(pid.map(i64::from))
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        log::warn!("Failed to record end of session {}: {}", session_id, e);
+    }
+}
+
+/// Mark a session failed once its process `pid` was stopped, so `session_status`
+/// reports the error until the session starts a process again
+pub async fn process_failed(db: Option<&SqlitePool>, session_id: &str, pid: Option<u32>, error: &str) {
+    CORRECTED.insert(session_id.to_string(), SessionStatus::Error(error.to_string()));
+    let Some(db) = db else {
+        return;
+    };
+    crate::chaos::delay_db_write().await;
+    let result = sqlx::query(
+        "UPDATE session_processes SET status = 'error', error = ?, pid = NULL, updated_at = (datetime('now', 'utc') || 'Z')
+         WHERE session_id = ? AND pid IS ?",
+    )
+    .bind(error)
+    .bind(session_id)
+    .bind(pid.map(i64::from))
+    .execute(db)
+    .await;
+    if let Err(e) = result {
+        log::warn!("Failed to record failure of session {}: {}", session_id, e);
+    }
+}
+
+/// Status set by the last startup scan, or by a failed process, for a session without a live process
+pub fn corrected_status(session_id: &str) -> Option<SessionStatus> {
+    CORRECTED.get(session_id).map(|s| s.clone())
+}
+
+/// Command line of a running process
+#[cfg(unix)]
+fn process_command(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps").args(["-p", &pid.to_string(), "-o", "args="]).output().ok()?;
+    let command = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !command.is_empty()).then_some(command)
+}
+
+#[cfg(not(unix))]
+fn process_command(_pid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn kill_process(pid: u32) -> std::io::Result<()> {
+    // SAFETY: kill only reads its arguments
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn kill_process(_pid: u32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Reaping processes is not supported on this platform"))
+}
+
+/// Whether `command` runs `program`; the PID may since have been reused by something else
+fn runs_program(command: &str, program: &str) -> bool {
+    let name = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or(program);
+    command
+        .split_whitespace()
+        .any(|arg| Path::new(arg).file_name().and_then(|n| n.to_str()) == Some(name))
+}
+
+/// Reconcile the database with what is actually running and on disk.
+/// `live` holds sessions with a process started by this run of the app.
+pub async fn reconcile(db: &SqlitePool, live: &HashSet<String>) -> ReconciliationSummary {
+    let mut summary = ReconciliationSummary::default();
+
+    match sqlx::query_as::<_, (String, String, Option<i64>, String)>(
+        "SELECT session_id, kind, pid, program FROM session_processes WHERE status = 'running' ORDER BY started_at",
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(rows) => {
+            for (session_id, kind, pid, program) in rows.into_iter().filter(|(id, ..)| !live.contains(id)) {
+                if let Some(pid) = pid.and_then(|p| u32::try_from(p).ok()) {
+                    if process_command(pid).is_some_and(|command| runs_program(&command, &program)) {
+                        match kill_process(pid) {
+                            Ok(()) => summary.reaped.push(ReapedProcess { session_id: session_id.clone(), pid }),
+                            Err(e) => summary.errors.push(format!("Failed to reap process {} of session {}: {}", pid, session_id, e)),
+                        }
+                    }
+                }
+                let (status, column, error) = match kind.as_str() {
+                    "thread" => (SessionStatus::Idle, "idle", None),
+                    _ => (SessionStatus::Error(INTERRUPTED.to_string()), "error", Some(INTERRUPTED)),
+                };
+                let result = sqlx::query(
+                    "UPDATE session_processes SET status = ?, error = ?, pid = NULL, updated_at = (datetime('now', 'utc') || 'Z')
+                     WHERE session_id = ? AND status = 'running'",
+                )
+                .bind(column)
+                .bind(error)
+                .bind(&session_id)
+                .execute(db)
+                .await;
+                match result {
+                    Ok(_) => {
+                        CORRECTED.insert(session_id.clone(), status.clone());
+                        summary.corrected.push(CorrectedSession { session_id, kind, status });
+                    }
+                    Err(e) => summary.errors.push(format!("Failed to correct session {}: {}", session_id, e)),
+                }
+            }
+        }
+        Err(e) => summary.errors.push(format!("Failed to read session processes: {}", e)),
+    }
+
+    // Batch runs do not survive a restart
+    let batch = async {
+        let mut tx = db.begin().await?;
+        let sessions = sqlx::query(
+            "UPDATE batch_sessions SET status = 'failed', error_message = ?, completed_at = (datetime('now', 'utc') || 'Z')
+             WHERE status = 'running'",
+        )
+        .bind(INTERRUPTED)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE batch_runs SET status = 'failed', completed_at = (datetime('now', 'utc') || 'Z') WHERE status = 'running'")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<u64, sqlx::Error>(sessions.rows_affected())
+    };
+    match batch.await {
+        Ok(count) => summary.interrupted_batch_sessions = count,
+        Err(e) => summary.errors.push(format!("Failed to correct batch runs: {}", e)),
+    }
+
+    match sqlx::query_as::<_, (String, String, String)>(
+        "SELECT session_id, repo_root, worktree_path FROM session_worktrees ORDER BY session_id",
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(rows) => {
+            summary.missing_worktrees = rows
+                .into_iter()
+                .filter(|(_, repo_root, worktree_path)| Path::new(repo_root).exists() && !Path::new(worktree_path).exists())
+                .map(|(session_id, _, worktree_path)| MissingWorktree { session_id, worktree_path })
+                .collect();
+        }
+        Err(e) => summary.errors.push(format!("Failed to read session worktrees: {}", e)),
+    }
+
+    summary.message = tr!(
+        "notify-startup-reconciliation",
+        corrected = summary.corrected.len(),
+        reaped = summary.reaped.len(),
+        missing = summary.missing_worktrees.len()
+    );
+    summary
+}
+
+/// Run the scan once the database is up and tell the UI what it found
+pub fn spawn_startup_scan(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+            return;
+        };
+        let live: HashSet<String> = match app_handle.try_state::<AmpSessionMap>() {
+            Some(sessions) => sessions.ids().into_iter().collect(),
+            None => HashSet::new(),
+        };
+        let db = profile_manager.db_pool.read().await;
+        let Some(db) = db.as_ref() else {
+            return;
+        };
+        let summary = reconcile(db, &live).await;
+        for error in &summary.errors {
+            log::warn!("Startup reconciliation: {}", error);
+        }
+        if !summary.is_clean() {
+            log::info!("Startup reconciliation: {}", summary.message);
+        }
+        *LAST_SUMMARY.lock().unwrap() = Some(summary.clone());
+        let _ = app_handle.emit("startup_reconciliation", summary);
+    });
+}
+
+/// Result of this launch's reconciliation, or `None` while it is still running
+#[tauri::command]
+pub async fn get_startup_reconciliation() -> Result<Option<ReconciliationSummary>, String> {
+    Ok(LAST_SUMMARY.lock().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_runs_program() {
+        assert!(runs_program("node /usr/local/bin/amp --execute --stream-json", "amp"));
+        assert!(runs_program("/opt/amp/bin/amp --stream-json", "/opt/amp/bin/amp"));
+        assert!(!runs_program("/usr/bin/vim notes.txt", "amp"));
+        assert!(!runs_program("/usr/bin/ampere", "amp"));
+    }
+
+    #[tokio::test]
+    async fn test_process_records_follow_restarts() {
+        let pool = test_pool().await;
+        process_started(Some(&pool), "thread-restart", "thread", Some(10), "amp").await;
+        process_started(Some(&pool), "thread-restart", "thread", Some(11), "amp").await;
+        // The replaced process's output ends after the new one started
+        process_ended(Some(&pool), "thread-restart", Some(10)).await;
+        let status: String = sqlx::query_scalar("SELECT status FROM session_processes WHERE session_id = 'thread-restart'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "running");
+        process_ended(Some(&pool), "thread-restart", Some(11)).await;
+        let status: String = sqlx::query_scalar("SELECT status FROM session_processes WHERE session_id = 'thread-restart'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "idle");
+
+        // A process stopped for passing a limit stays failed once its output ends
+        process_started(Some(&pool), "thread-limited", "thread", Some(12), "amp").await;
+        process_failed(Some(&pool), "thread-limited", Some(12), "Timed out after 60s").await;
+        process_ended(Some(&pool), "thread-limited", Some(12)).await;
+        let status: String = sqlx::query_scalar("SELECT status FROM session_processes WHERE session_id = 'thread-limited'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "error");
+        assert_eq!(corrected_status("thread-limited"), Some(SessionStatus::Error("Timed out after 60s".to_string())));
+        process_started(Some(&pool), "thread-limited", "thread", Some(13), "amp").await;
+        assert_eq!(corrected_status("thread-limited"), None);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_corrects_stale_state() {
+        let pool = test_pool().await;
+        let dir = TempDir::new().unwrap();
+
+        #[cfg(unix)]
+        let mut orphan = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        #[cfg(unix)]
+        process_started(Some(&pool), "chat-orphan", "chat", Some(orphan.id()), "sleep").await;
+        process_started(Some(&pool), "thread-dead", "thread", Some(u32::MAX - 1), "amp").await;
+        process_started(Some(&pool), "thread-live", "thread", Some(1), "amp").await;
+        process_started(Some(&pool), "thread-done", "thread", Some(2), "amp").await;
+        process_ended(Some(&pool), "thread-done", Some(2)).await;
+
+        sqlx::query("INSERT INTO batch_runs (id, name, config_json, status, total_sessions, created_at) VALUES ('b1', 'b', '{}', 'running', 1, '')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO batch_sessions (batch_id, session_id, status) VALUES ('b1', 's1', 'running'), ('b1', 's2', 'completed')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let repo = dir.path().to_string_lossy().to_string();
+        let gone = dir.path().join(".amp-worktrees/gone").to_string_lossy().to_string();
+        sqlx::query(
+            "INSERT INTO session_worktrees (session_id, repo_root, worktree_path, branch_name)
+             VALUES ('wt-gone', ?, ?, 'orchestra/gone'), ('wt-moved', '/no/such/repo', '/no/such/repo/.amp-worktrees/x', 'orchestra/x')",
+        )
+        .bind(&repo)
+        .bind(&gone)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let live = HashSet::from(["thread-live".to_string()]);
+        let summary = reconcile(&pool, &live).await;
+        assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+
+        let corrected: Vec<&str> = summary.corrected.iter().map(|c| c.session_id.as_str()).collect();
+        assert!(corrected.contains(&"thread-dead"));
+        assert!(!corrected.contains(&"thread-live"));
+        assert!(!corrected.contains(&"thread-done"));
+        assert_eq!(corrected_status("thread-dead"), Some(SessionStatus::Idle));
+        assert_eq!(summary.interrupted_batch_sessions, 1);
+        assert_eq!(summary.missing_worktrees, vec![MissingWorktree { session_id: "wt-gone".to_string(), worktree_path: gone }]);
+
+        #[cfg(unix)]
+        {
+            assert_eq!(summary.reaped, vec![ReapedProcess { session_id: "chat-orphan".to_string(), pid: orphan.id() }]);
+            assert!(!orphan.wait().unwrap().success());
+            assert!(matches!(corrected_status("chat-orphan"), Some(SessionStatus::Error(_))));
+        }
+
+        // A second launch finds nothing left to fix
+        let again = reconcile(&pool, &live).await;
+        assert!(again.corrected.is_empty());
+        assert_eq!(again.interrupted_batch_sessions, 0);
+
+        process_started(Some(&pool), "thread-dead", "thread", Some(3), "amp").await;
+        assert_eq!(corrected_status("thread-dead"), None);
+    }
+}
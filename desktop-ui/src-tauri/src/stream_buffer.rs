@@ -0,0 +1,231 @@
+//! Replay buffer for session stream events
+//!
+//! Every `chat_stream` / `thread_stream` event gets a per-session `seq` and is
+//! kept in a bounded ring buffer. After a webview reload the frontend listens
+//! again and calls `resume_stream(session_id, last_seq)` to fetch whatever it
+//! missed, dropping any live event whose `seq` it has already seen.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::memory_manager::{CacheUnit, ReclaimableCache};
+
+/// Events kept per session; older ones are dropped first
+pub const MAX_BUFFERED_EVENTS: usize = 5000;
+/// How long a finished session's events stay available for replay
+pub const RETAIN_AFTER_END: Duration = Duration::from_secs(600);
+
+#[derive(Debug)]
+struct SessionBuffer {
+    next_seq: u64,
+    /// Sequence number, serialized size and event
+    events: VecDeque<(u64, usize, Value)>,
+    /// Serialized size of `events`, for the memory manager
+    bytes: usize,
+    last_used: Instant,
+    ended_at: Option<Instant>,
+}
+
+impl Default for SessionBuffer {
+    fn default() -> Self {
+        Self { next_seq: 0, events: VecDeque::new(), bytes: 0, last_used: Instant::now(), ended_at: None }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResumedStream {
+    pub session_id: String,
+    /// Events after `last_seq`, oldest first, exactly as they were emitted
+    pub events: Vec<Value>,
+    /// Sequence number the next live event will carry
+    pub next_seq: u64,
+    /// True when events after `last_seq` were already dropped from the buffer
+    pub truncated: bool,
+    pub ended: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct StreamBuffers {
+    inner: Arc<Mutex<HashMap<String, SessionBuffer>>>,
+}
+
+impl StreamBuffers {
+    /// Assign the next sequence number, add it to the payload as `seq`, and buffer it
+    pub fn record(&self, session_id: &str, mut payload: Value) -> Value {
+        let mut buffers = self.inner.lock().unwrap();
+        buffers.retain(|_, b| b.ended_at.is_none_or(|t| t.elapsed() < RETAIN_AFTER_END));
+
+        let buffer = buffers.entry(session_id.to_string()).or_default();
+        let seq = buffer.next_seq;
+        buffer.next_seq += 1;
+        if let Some(obj) = payload.as_object_mut() {
+            obj.insert("seq".to_string(), Value::from(seq));
+        }
+        if buffer.events.len() >= MAX_BUFFERED_EVENTS {
+            if let Some((_, size, _)) = buffer.events.pop_front() {
+                buffer.bytes -= size;
+            }
+        }
+        let size = payload.to_string().len();
+        buffer.bytes += size;
+        buffer.last_used = Instant::now();
+        buffer.events.push_back((seq, size, payload.clone()));
+        payload
+    }
+
+    /// Start the retention countdown; the stream may still be resumed until it expires
+    pub fn mark_ended(&self, session_id: &str) {
+        if let Some(buffer) = self.inner.lock().unwrap().get_mut(session_id) {
+            buffer.ended_at = Some(Instant::now());
+        }
+    }
+
+    /// Events with `seq > last_seq`, or everything buffered when `last_seq` is `None`
+    pub fn since(&self, session_id: &str, last_seq: Option<u64>) -> Option<ResumedStream> {
+        let mut buffers = self.inner.lock().unwrap();
+        let buffer = buffers.get_mut(session_id)?;
+        buffer.last_used = Instant::now();
+
+        let first_wanted = last_seq.map_or(0, |s| s + 1);
+        let oldest = buffer.events.front().map_or(buffer.next_seq, |(seq, _, _)| *seq);
+        Some(ResumedStream {
+            session_id: session_id.to_string(),
+            events: buffer
+                .events
+                .iter()
+                .filter(|(seq, _, _)| *seq >= first_wanted)
+                .map(|(_, _, event)| event.clone())
+                .collect(),
+            next_seq: buffer.next_seq,
+            truncated: first_wanted < oldest,
+            ended: buffer.ended_at.is_some(),
+        })
+    }
+}
+
+/// Whole session buffers, least recently used first. Evicting one keeps its
+/// sequence counter, so a later `resume_stream` reports the gap as truncated.
+impl ReclaimableCache for StreamBuffers {
+    fn subsystem(&self) -> &'static str {
+        "stream_buffers"
+    }
+
+    fn units(&self) -> Vec<CacheUnit> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, buffer)| !buffer.events.is_empty())
+            .map(|(session_id, buffer)| CacheUnit {
+                key: session_id.clone(),
+                bytes: buffer.bytes,
+                entries: buffer.events.len(),
+                last_used: buffer.last_used,
+                replayable: true,
+            })
+            .collect()
+    }
+
+    fn evict(&self, key: &str) -> usize {
+        let mut buffers = self.inner.lock().unwrap();
+        let Some(buffer) = buffers.get_mut(key) else {
+            return 0;
+        };
+        buffer.events.clear();
+        std::mem::take(&mut buffer.bytes)
+    }
+}
+
+/// Buffer a stream event for replay and emit it with its `seq` to subscribed webviews
+pub fn emit_buffered(app_handle: &AppHandle, event: &str, session_id: &str, payload: Value) {
+    let payload = match app_handle.try_state::<StreamBuffers>() {
+        Some(buffers) => buffers.record(session_id, payload),
+        None => payload,
+    };
+    crate::event_subscriptions::emit_session_event(app_handle, event, session_id, payload);
+}
+
+pub fn mark_stream_ended(app_handle: &AppHandle, session_id: &str) {
+    if let Some(buffers) = app_handle.try_state::<StreamBuffers>() {
+        buffers.mark_ended(session_id);
+    }
+}
+
+/// Replay events a listener missed. `session_id` is a chat session or thread id.
+#[tauri::command]
+pub async fn resume_stream(
+    session_id: String,
+    last_seq: Option<u64>,
+    buffers: State<'_, StreamBuffers>,
+) -> Result<ResumedStream, String> {
+    buffers
+        .since(&session_id, last_seq)
+        .ok_or_else(|| format!("No buffered stream for session {}", session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_replays_events_after_last_seq() {
+        let buffers = StreamBuffers::default();
+        for i in 0..5 {
+            let emitted = buffers.record("s1", json!({ "session_id": "s1", "event": { "n": i } }));
+            assert_eq!(emitted["seq"], i);
+        }
+        buffers.record("s2", json!({ "session_id": "s2" }));
+
+        let resumed = buffers.since("s1", Some(2)).unwrap();
+        let seqs: Vec<u64> = resumed.events.iter().map(|e| e["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, vec![3, 4]);
+        assert_eq!(resumed.next_seq, 5);
+        assert!(!resumed.truncated);
+        assert!(!resumed.ended);
+
+        assert_eq!(buffers.since("s1", None).unwrap().events.len(), 5);
+        assert!(buffers.since("s1", Some(4)).unwrap().events.is_empty());
+        assert!(buffers.since("missing", None).is_none());
+    }
+
+    #[test]
+    fn test_reports_truncation_when_events_were_dropped() {
+        let buffers = StreamBuffers::default();
+        for _ in 0..(MAX_BUFFERED_EVENTS + 10) {
+            buffers.record("s1", json!({}));
+        }
+        buffers.mark_ended("s1");
+
+        let resumed = buffers.since("s1", Some(3)).unwrap();
+        assert!(resumed.truncated);
+        assert!(resumed.ended);
+        assert_eq!(resumed.events.len(), MAX_BUFFERED_EVENTS);
+        assert_eq!(resumed.events[0]["seq"], 10);
+
+        assert!(!buffers.since("s1", Some(9)).unwrap().truncated);
+    }
+
+    #[test]
+    fn test_evicted_buffer_resumes_as_truncated() {
+        let buffers = StreamBuffers::default();
+        for _ in 0..3 {
+            buffers.record("s1", json!({ "text": "hello" }));
+        }
+        let units = buffers.units();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].entries, 3);
+        assert!(units[0].bytes > 0);
+
+        assert_eq!(buffers.evict("s1"), units[0].bytes);
+        assert!(buffers.units().is_empty());
+        let resumed = buffers.since("s1", Some(0)).unwrap();
+        assert!(resumed.truncated);
+        assert!(resumed.events.is_empty());
+        assert_eq!(buffers.record("s1", json!({}))["seq"], 3);
+    }
+}
@@ -0,0 +1,280 @@
+//! Seeded fuzzing of the stream-json pipeline: line reading, event validation,
+//! title and snippet extraction, and message persistence, fed arbitrary bytes and
+//! adversarial JSON. Set `STREAM_FUZZ_CASES` for a longer run and `STREAM_FUZZ_SEED`
+//! to replay the seed printed by a failing one.
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use std::str::FromStr;
+    use tokio::io::BufReader;
+
+    use crate::message_content::{insert_message_with_metrics, load, prepare, resolve_content};
+    use crate::schema::test_pool;
+    use crate::stream_quarantine::{CliInfo, StreamLine, StreamLines, StreamValidator};
+    use crate::stream_text::{assistant_snippet, user_title, SNIPPET_BYTES, TITLE_BYTES};
+
+    /// Line limit for the reader under test, small enough that cases cross it often
+    const LINE_LIMIT: usize = 4096;
+    const DEFAULT_SEED: u64 = 0x5eed_2455;
+
+    const EVENTS: &[&str] = &[
+        r#"{"type":"system","subtype":"init","session_id":"s1","tools":["read_file"]}"#,
+        r#"{"type":"user","message":{"content":[{"type":"text","text":"Explain this repository"}]}}"#,
+        r#"{"type":"assistant","message":{"content":[{"type":"text","text":"It is a desktop app."},{"type":"tool_use","id":"t1","name":"read_file","input":{"path":"README.md"}}],"usage":{"input_tokens":10,"output_tokens":4}}}"#,
+        r##"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","content":"# Title"}]}}"##,
+        r#"{"type":"assistant","text":"legacy text event"}"#,
+        r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":1200,"total_cost_usd":0.01}"#,
+    ];
+
+    /// xorshift64*, so a run is reproducible from its seed alone
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n.max(1) as u64) as usize
+        }
+
+        fn chance(&mut self, percent: usize) -> bool {
+            self.below(100) < percent
+        }
+
+        fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+            &items[self.below(items.len())]
+        }
+    }
+
+    fn env_or<T: FromStr>(key: &str, default: T) -> T {
+        std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn fuzz_rng() -> (u64, Rng) {
+        let seed = env_or("STREAM_FUZZ_SEED", DEFAULT_SEED);
+        (seed, Rng(seed.max(1)))
+    }
+
+    fn random_bytes(rng: &mut Rng, len: usize) -> Vec<u8> {
+        const PUNCTUATION: &[u8] = b"{}[]\":,\\ \r\n0e-.";
+        (0..len)
+            .map(|_| match rng.below(10) {
+                0..=3 => *rng.pick(PUNCTUATION),
+                4..=5 => 0x80 + rng.below(0x80) as u8,
+                _ => b' ' + rng.below(95) as u8,
+            })
+            .collect()
+    }
+
+    /// Text that puts multi-byte characters on either side of the truncation points
+    fn boundary_text(rng: &mut Rng) -> String {
+        let limit = *rng.pick(&[SNIPPET_BYTES, TITLE_BYTES]);
+        let chars: &[&str] = &["é", "€", "🦀", "\u{200d}", "\u{0}"];
+        let mut text = "a".repeat(limit.saturating_sub(rng.below(4)));
+        for _ in 0..rng.below(8) + 1 {
+            text.push_str(chars[rng.below(chars.len())]);
+        }
+        text
+    }
+
+    fn adversarial(rng: &mut Rng) -> String {
+        let depth = *rng.pick(&[64, 127, 129, 5_000]);
+        match rng.below(9) {
+            0 => format!("{}{}", "[".repeat(depth), "]".repeat(depth)),
+            1 => format!("{}1{}", r#"{"type":"assistant","message":{"content":"#.repeat(depth), "}}".repeat(depth)),
+            2 => format!(r#"{{"type":"result","subtype":"success","duration_ms":{}e99999}}"#, "9".repeat(400)),
+            3 => r#"{"type":"assistant","text":"\ud800 lone surrogate"}"#.to_string(),
+            4 => r#"{"type":"user","message":{"content":[{"type":"text","text":"\u12"}]}}"#.to_string(),
+            5 => json!({ "type": "assistant", "message": { "content": [{ "type": "text", "text": boundary_text(rng) }] } }).to_string(),
+            6 => json!({ "type": "user", "message": { "content": [{ "type": "text", "text": boundary_text(rng) }] } }).to_string(),
+            7 => r#"{"type":"assistant","message":{"content":[{"type":"text","text":5},{"type":"tool_use"}]}}"#.to_string(),
+            _ => json!({ "type": "assistant", "message": { "content": [{ "type": "text", "text": "x".repeat(LINE_LIMIT * 2) }] } }).to_string(),
+        }
+    }
+
+    fn mutate(rng: &mut Rng, line: &str) -> Vec<u8> {
+        let mut bytes = line.as_bytes().to_vec();
+        for _ in 0..rng.below(6) + 1 {
+            let at = rng.below(bytes.len() + 1);
+            match rng.below(4) {
+                0 if at < bytes.len() => bytes[at] ^= 1 << rng.below(8),
+                1 if at < bytes.len() => {
+                    bytes.remove(at);
+                }
+                2 => bytes.truncate(at),
+                _ => {
+                    let len = rng.below(8) + 1;
+                    bytes.splice(at..at, random_bytes(rng, len));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// A run of process output: valid events mixed with broken and hostile lines
+    fn stream(rng: &mut Rng) -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..rng.below(12) {
+            let line = match rng.below(5) {
+                0 => rng.pick(EVENTS).as_bytes().to_vec(),
+                1 => adversarial(rng).into_bytes(),
+                2 => {
+                    let event = *rng.pick(EVENTS);
+                    mutate(rng, event)
+                }
+                3 => {
+                    let len = *rng.pick(&[0, 1, 64, LINE_LIMIT - 1, LINE_LIMIT, LINE_LIMIT + 1, LINE_LIMIT * 3]);
+                    random_bytes(rng, len)
+                }
+                _ => vec![b'\r'],
+            };
+            data.extend(line);
+            data.extend_from_slice(if rng.chance(20) { b"\r\n" } else { b"\n" });
+        }
+        if rng.chance(30) {
+            data.extend(random_bytes(rng, 40));
+        }
+        data
+    }
+
+    fn check_extraction(event: &Value) {
+        let ellipsis = "…".len();
+        if let Some(snippet) = assistant_snippet(event) {
+            assert!(snippet.len() <= SNIPPET_BYTES + ellipsis, "snippet of {} bytes", snippet.len());
+        }
+        if let Some(title) = user_title(event) {
+            assert!(title.len() <= TITLE_BYTES + ellipsis, "title of {} bytes", title.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn fuzz_stream_reader_and_parser() {
+        let (seed, mut rng) = fuzz_rng();
+        for case in 0..env_or("STREAM_FUZZ_CASES", 300usize) {
+            let data = stream(&mut rng);
+            let context = format!("seed {} case {}", seed, case);
+            // Small buffers split lines and characters across reads
+            let reader = BufReader::with_capacity(rng.below(64) + 1, data.as_slice());
+            let mut lines = StreamLines::with_limit(reader, LINE_LIMIT);
+            let mut validator = StreamValidator::new("thread", "fuzz", CliInfo::new("amp", &[]));
+
+            let mut expected = data.split(|b| *b == b'\n').collect::<Vec<_>>();
+            if data.last() == Some(&b'\n') || data.is_empty() {
+                expected.pop();
+            }
+            let mut offset = 0;
+            for raw in expected {
+                let line = lines.next_line().await.unwrap().unwrap_or_else(|| panic!("{}: stream ended early", context));
+                assert_eq!(line.bytes, raw.len(), "{}", context);
+                assert_eq!(line.truncated, raw.len() > LINE_LIMIT, "{}", context);
+                // Lossy decoding can at most triple what was kept
+                assert!(line.text.len() <= LINE_LIMIT * 3, "{}: {} byte line kept", context, line.text.len());
+                if let Ok(text) = std::str::from_utf8(raw) {
+                    if !line.truncated {
+                        assert_eq!(line.text, text.strip_suffix('\r').unwrap_or(text), "{}", context);
+                    }
+                }
+
+                match validator.check(&line) {
+                    Ok(event) => check_extraction(&event),
+                    Err(quarantined) => {
+                        assert_eq!(quarantined.byte_offset, offset, "{}", context);
+                        assert_eq!(quarantined.line_bytes, raw.len(), "{}", context);
+                        assert!(quarantined.error_output()["data"]["content"].is_string(), "{}", context);
+                    }
+                }
+                offset += raw.len() as u64 + 1;
+            }
+            assert_eq!(lines.next_line().await.unwrap(), None, "{}", context);
+        }
+    }
+
+    fn random_string(rng: &mut Rng) -> String {
+        match rng.below(4) {
+            0 => boundary_text(rng),
+            1 => {
+                let len = rng.below(200);
+                String::from_utf8_lossy(&random_bytes(rng, len)).into_owned()
+            }
+            2 => "\"\\\u{0}\u{1f}'; DROP TABLE messages; --".to_string(),
+            _ => "ü".repeat(rng.below(40_000)),
+        }
+    }
+
+    fn random_value(rng: &mut Rng, depth: usize) -> Value {
+        match rng.below(if depth == 0 { 4 } else { 6 }) {
+            0 => Value::Null,
+            1 => json!(rng.next() as i64),
+            2 => json!(f64::from_bits(rng.next())),
+            3 => json!(random_string(rng)),
+            4 => Value::Array((0..rng.below(4)).map(|_| random_value(rng, depth - 1)).collect()),
+            _ => Value::Object((0..rng.below(4)).map(|_| (random_string(rng), random_value(rng, depth - 1))).collect()),
+        }
+    }
+
+    fn random_message(rng: &mut Rng) -> Value {
+        let role = *rng.pick(&["user", "assistant"]);
+        let mut parts = vec![json!({ "type": "text", "text": random_string(rng) })];
+        if rng.chance(50) {
+            parts.push(json!({ "type": "tool_use", "id": "t", "name": random_string(rng), "input": random_value(rng, 3) }));
+        }
+        let mut message = json!({ "content": parts });
+        if rng.chance(50) {
+            message["usage"] = json!({ "input_tokens": random_value(rng, 0), "output_tokens": rng.next() });
+        }
+        if rng.chance(20) {
+            message["extra"] = random_value(rng, 4);
+        }
+        json!({ "type": role, "message": message })
+    }
+
+    #[tokio::test]
+    async fn fuzz_message_persistence() {
+        let (seed, mut rng) = fuzz_rng();
+        let pool = test_pool().await;
+        let blobs = tempfile::tempdir().unwrap();
+        let thread = "fuzz-persist";
+        let mut validator = StreamValidator::new("thread", thread, CliInfo::new("amp", &[]));
+
+        for case in 0..env_or("STREAM_FUZZ_CASES", 300usize) / 3 {
+            let context = format!("seed {} case {}", seed, case);
+            let raw = random_message(&mut rng).to_string();
+            let line = match rng.chance(30) {
+                true => String::from_utf8_lossy(&mutate(&mut rng, &raw)).into_owned(),
+                false => raw,
+            };
+
+            match validator.check(&StreamLine::from(line.as_str())) {
+                Ok(event) => {
+                    check_extraction(&event);
+                    let role = event["type"].as_str().unwrap().to_string();
+                    let content = serde_json::to_string(&event).unwrap();
+                    let metrics = crate::message_metrics::observe(thread, &event);
+                    let id = format!("m{}", case);
+                    insert_message_with_metrics(&pool, &id, thread, &role, &content, &metrics).await.unwrap();
+
+                    let (stored, content_ref) = sqlx::query_as::<_, (String, Option<String>)>(
+                        "SELECT content, content_ref FROM messages WHERE id = ?",
+                    )
+                    .bind(&id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+                    assert_eq!(resolve_content(stored, content_ref), content, "{}", context);
+
+                    let offloaded = prepare(blobs.path(), &content).unwrap();
+                    let loaded = load(blobs.path(), offloaded.content, offloaded.content_ref.as_deref()).unwrap();
+                    assert_eq!(loaded, content, "{}", context);
+                }
+                Err(quarantined) => {
+                    quarantined.store(&pool).await.unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Validating the CLI's stream JSON and quarantining lines that fail
+//!
+//! Every stdout line of an Amp process is checked before anything acts on it.
+//! Lines that are not JSON objects with a string `type`, and events of a known
+//! type that lack the fields the app reads, never reach hooks, storage or the
+//! stream: they are kept in `quarantined_events` with the session, where the
+//! line starts in the process's stdout, and the CLI version, and the frontend
+//! sees them only as `error_output`. Unknown event types pass unchecked so a
+//! newer CLI is not quarantined wholesale.
+//!
+//! Lines are read with `StreamLines`, which decodes invalid UTF-8 lossily and
+//! holds at most `MAX_LINE_BYTES` of a line, so a misbehaving process cannot
+//! stop its reader or grow it without bound.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tauri::State;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::profile_auth::ProfileManager;
+
+/// Longest prefix of a line read into memory; longer lines are quarantined
+pub const MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+/// Longest prefix of a line that is stored
+const MAX_STORED_BYTES: usize = 64 * 1024;
+/// Quarantined lines kept; older ones are dropped as new ones arrive
+const MAX_KEPT: i64 = 1000;
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `--version` output per CLI, looked up the first time one of its lines is quarantined
+static CLI_VERSIONS: Lazy<DashMap<String, Option<String>>> = Lazy::new(DashMap::new);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The event's `type`, when it got that far
+    pub event_type: Option<String>,
+    pub reason: String,
+}
+
+fn violation(event_type: Option<&str>, reason: impl Into<String>) -> Violation {
+    Violation { event_type: event_type.map(str::to_string), reason: reason.into() }
+}
+
+fn require<'a>(event: &'a Value, field: &str, is_valid: fn(&Value) -> bool, expected: &str) -> Result<&'a Value, String> {
+    match event.get(field) {
+        Some(value) if is_valid(value) => Ok(value),
+        Some(_) => Err(format!("`{}` is not {}", field, expected)),
+        None => Err(format!("missing `{}`", field)),
+    }
+}
+
+fn optional(event: &Value, field: &str, is_valid: fn(&Value) -> bool, expected: &str) -> Result<(), String> {
+    match event.get(field) {
+        Some(value) if !value.is_null() && !is_valid(value) => Err(format!("`{}` is not {}", field, expected)),
+        _ => Ok(()),
+    }
+}
+
+/// Content parts of user and assistant messages
+fn check_part(part: &Value) -> Result<(), String> {
+    let part_type = require(part, "type", Value::is_string, "a string")?.as_str().unwrap_or_default();
+    match part_type {
+        "text" => require(part, "text", Value::is_string, "a string").map(|_| ()),
+        "tool_use" => {
+            require(part, "id", Value::is_string, "a string")?;
+            require(part, "name", Value::is_string, "a string")?;
+            require(part, "input", Value::is_object, "an object").map(|_| ())
+        }
+        "tool_result" => require(part, "tool_use_id", Value::is_string, "a string").map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+fn check_message(event: &Value) -> Result<(), String> {
+    // Older CLIs put assistant text at the top level
+    if event.get("message").is_none() && event.get("text").is_some_and(Value::is_string) {
+        return Ok(());
+    }
+    let message = require(event, "message", Value::is_object, "an object")?;
+    let content = require(message, "content", |v| v.is_array() || v.is_string(), "an array or string")
+        .map_err(|e| format!("message {}", e))?;
+    for (i, part) in content.as_array().into_iter().flatten().enumerate() {
+        check_part(part).map_err(|e| format!("message part {}: {}", i, e))?;
+    }
+    optional(message, "usage", Value::is_object, "an object").map_err(|e| format!("message {}", e))
+}
+
+fn check_result(event: &Value) -> Result<(), String> {
+    require(event, "subtype", Value::is_string, "a string")?;
+    optional(event, "is_error", Value::is_boolean, "a boolean")?;
+    optional(event, "usage", Value::is_object, "an object")?;
+    for field in ["duration_ms", "total_cost_usd", "cost_usd"] {
+        optional(event, field, Value::is_number, "a number")?;
+    }
+    Ok(())
+}
+
+/// Parse one stream line and check it against the shape of its event type
+pub fn validate(line: &str) -> Result<Value, Violation> {
+    let event: Value = serde_json::from_str(line).map_err(|e| violation(None, format!("invalid JSON: {}", e)))?;
+    if !event.is_object() {
+        return Err(violation(None, "event is not a JSON object"));
+    }
+    let event_type = match event.get("type").and_then(Value::as_str) {
+        Some(event_type) => event_type.to_string(),
+        None => return Err(violation(None, "event has no string `type`")),
+    };
+    let checked = match event_type.as_str() {
+        "system" => require(&event, "subtype", Value::is_string, "a string").map(|_| ()),
+        "user" | "assistant" => check_message(&event),
+        "result" => check_result(&event),
+        _ => Ok(()),
+    };
+    checked.map_err(|reason| violation(Some(&event_type), reason))?;
+    Ok(event)
+}
+
+/// One line of output, without its line ending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamLine {
+    pub text: String,
+    /// Length before decoding and truncation
+    pub bytes: usize,
+    /// Whether the line was longer than the reader's limit
+    pub truncated: bool,
+}
+
+impl From<&str> for StreamLine {
+    fn from(text: &str) -> Self {
+        Self { text: text.to_string(), bytes: text.len(), truncated: false }
+    }
+}
+
+/// Newline-delimited reader for process output. Unlike `AsyncBufReadExt::lines`
+/// it keeps going past invalid UTF-8 and drops whatever of a line exceeds its limit.
+pub struct StreamLines<R> {
+    reader: R,
+    max_bytes: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> StreamLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limit(reader, MAX_LINE_BYTES)
+    }
+
+    pub fn with_limit(reader: R, max_bytes: usize) -> Self {
+        Self { reader, max_bytes }
+    }
+
+    pub async fn next_line(&mut self) -> std::io::Result<Option<StreamLine>> {
+        loop {
+            let line = self.read_line().await?;
+            if line.is_none() || !crate::chaos::drop_line() {
+                return Ok(line);
+            }
+        }
+    }
+
+    async fn read_line(&mut self) -> std::io::Result<Option<StreamLine>> {
+        let mut kept = Vec::new();
+        let (mut bytes, mut read_any) = (0, false);
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                if !read_any {
+                    return Ok(None);
+                }
+                break;
+            }
+            read_any = true;
+            let newline = available.iter().position(|b| *b == b'\n');
+            let line_part = &available[..newline.unwrap_or(available.len())];
+            let room = self.max_bytes.saturating_sub(kept.len());
+            kept.extend_from_slice(&line_part[..line_part.len().min(room)]);
+            bytes += line_part.len();
+            let consumed = line_part.len() + usize::from(newline.is_some());
+            self.reader.consume(consumed);
+            if newline.is_some() {
+                break;
+            }
+        }
+        let truncated = bytes > kept.len();
+        if !truncated && kept.last() == Some(&b'\r') {
+            kept.pop();
+        }
+        Ok(Some(StreamLine { text: String::from_utf8_lossy(&kept).into_owned(), bytes, truncated }))
+    }
+}
+
+/// How an Amp process was started, enough to ask it for its version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliInfo {
+    program: String,
+    /// Script run by `program`, for a local CLI run through node
+    script: Option<String>,
+}
+
+impl CliInfo {
+    /// From the command and arguments given by `choose_amp_command`
+    pub fn new(program: &str, args: &[String]) -> Self {
+        let script = args.iter().find(|arg| !arg.starts_with('-')).cloned();
+        Self { program: program.to_string(), script }
+    }
+
+    /// The file the process runs: the script for a CLI run through node, else the program
+    pub fn executable(&self) -> &str {
+        self.script.as_deref().unwrap_or(&self.program)
+    }
+
+    pub async fn version(&self) -> Option<String> {
+        let key = format!("{} {}", self.program, self.script.as_deref().unwrap_or_default());
+        if let Some(version) = CLI_VERSIONS.get(&key) {
+            return version.clone();
+        }
+        let mut command = tokio::process::Command::new(&self.program);
+        command.args(self.script.iter()).arg("--version").kill_on_drop(true);
+        let version = match tokio::time::timeout(VERSION_TIMEOUT, command.output()).await {
+            Ok(Ok(output)) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|v| !v.is_empty())
+            }
+            _ => None,
+        };
+        CLI_VERSIONS.insert(key, version.clone());
+        version
+    }
+}
+
+/// Checks the stdout lines of one process in order, tracking where each starts
+pub struct StreamValidator {
+    stream: &'static str,
+    session_id: String,
+    cli: CliInfo,
+    offset: u64,
+}
+
+/// A line that failed validation, with everything needed to store it
+#[derive(Debug, Clone)]
+pub struct QuarantinedLine {
+    pub stream: &'static str,
+    pub session_id: String,
+    pub byte_offset: u64,
+    pub violation: Violation,
+    pub line: String,
+    /// Length of the line as received, which `line` may fall short of
+    pub line_bytes: usize,
+    cli: CliInfo,
+}
+
+impl StreamValidator {
+    /// `stream` is `chat` or `thread`, matching the event the lines are emitted on
+    pub fn new(stream: &'static str, session_id: &str, cli: CliInfo) -> Self {
+        Self { stream, session_id: session_id.to_string(), cli, offset: 0 }
+    }
+
+    /// Validate the next line
+    pub fn check(&mut self, line: &StreamLine) -> Result<Value, Box<QuarantinedLine>> {
+        let byte_offset = self.offset;
+        self.offset += line.bytes as u64 + 1;
+        let result = match line.truncated {
+            true => Err(violation(None, format!("line of {} bytes is longer than the {} byte limit", line.bytes, MAX_LINE_BYTES))),
+            false => validate(&line.text),
+        };
+        result.map_err(|violation| {
+            Box::new(QuarantinedLine {
+                stream: self.stream,
+                session_id: self.session_id.clone(),
+                byte_offset,
+                violation,
+                line: line.text.clone(),
+                line_bytes: line.bytes,
+                cli: self.cli.clone(),
+            })
+        })
+    }
+}
+
+impl QuarantinedLine {
+    /// The `error_output` event that stands in for the line in the stream
+    pub fn error_output(&self) -> Value {
+        json!({ "type": "error_output", "data": { "content": self.line, "reason": self.violation.reason } })
+    }
+
+    /// Store the line, dropping the oldest beyond the retention limit
+    pub async fn store(&self, db: &SqlitePool) -> Result<i64, String> {
+        let mut end = self.line.len().min(MAX_STORED_BYTES);
+        while !self.line.is_char_boundary(end) {
+            end -= 1;
+        }
+        let cli_version = self.cli.version().await;
+        let store_error = |e: sqlx::Error| format!("Failed to quarantine stream line: {}", e);
+        let id = sqlx::query(
+            "INSERT INTO quarantined_events (session_id, stream, byte_offset, cli_version, event_type, reason, line, line_bytes)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&self.session_id)
+        .bind(self.stream)
+        .bind(self.byte_offset as i64)
+        .bind(cli_version)
+        .bind(&self.violation.event_type)
+        .bind(&self.violation.reason)
+        .bind(&self.line[..end])
+        .bind(self.line_bytes as i64)
+        .execute(db)
+        .await
+        .map_err(store_error)?
+        .last_insert_rowid();
+        sqlx::query("DELETE FROM quarantined_events WHERE id <= ?")
+            .bind(id - MAX_KEPT)
+            .execute(db)
+            .await
+            .map_err(store_error)?;
+        Ok(id)
+    }
+
+    /// Store the line if a database is open, logging failures; for stream readers
+    pub async fn store_logged(&self, db: Option<&SqlitePool>) {
+        log::warn!(
+            "Quarantined {} stream line at byte {} of {}: {}",
+            self.stream, self.byte_offset, self.session_id, self.violation.reason
+        );
+        if let Some(db) = db {
+            if let Err(e) = self.store(db).await {
+                log::warn!("{}", e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct QuarantinedEvent {
+    pub id: i64,
+    pub session_id: String,
+    pub stream: String,
+    pub byte_offset: i64,
+    pub cli_version: Option<String>,
+    pub event_type: Option<String>,
+    pub reason: String,
+    /// At most the first 64 KiB of the line
+    pub line: String,
+    pub line_bytes: i64,
+    pub created_at: String,
+}
+
+pub async fn quarantined_events(db: &SqlitePool, session_id: Option<&str>, limit: i64) -> Result<Vec<QuarantinedEvent>, sqlx::Error> {
+    sqlx::query_as::<_, QuarantinedEvent>(
+        "SELECT * FROM quarantined_events WHERE ? IS NULL OR session_id = ? ORDER BY id DESC LIMIT ?",
+    )
+    .bind(session_id)
+    .bind(session_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
+
+/// Quarantined stream lines, newest first, optionally for one session or thread
+#[tauri::command]
+pub async fn get_quarantined_events(
+    session_id: Option<String>,
+    limit: Option<i64>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<QuarantinedEvent>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let session_id = session_id.map(|id| crate::session_codes::resolve(&id));
+    quarantined_events(db, session_id.as_deref(), limit.unwrap_or(100).clamp(1, MAX_KEPT))
+        .await
+        .map_err(|e| format!("Failed to read quarantined events: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[test]
+    fn test_validate_known_event_types() {
+        let valid = [
+            r#"{"type":"system","subtype":"init","session_id":"s"}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"hi"},{"type":"tool_use","id":"t","name":"read_file","input":{}}]}}"#,
+            r#"{"type":"user","message":{"content":"plain prompt"}}"#,
+            r#"{"type":"assistant","text":"legacy"}"#,
+            r#"{"type":"result","subtype":"success","is_error":false,"duration_ms":12}"#,
+            r#"{"type":"something_new","whatever":1}"#,
+        ];
+        for line in valid {
+            assert!(validate(line).is_ok(), "{}: {:?}", line, validate(line));
+        }
+
+        let reason = |line: &str| validate(line).unwrap_err();
+        assert!(reason("Loading config...").reason.starts_with("invalid JSON"));
+        assert_eq!(reason("[1, 2]").reason, "event is not a JSON object");
+        assert_eq!(reason(r#"{"type": 3}"#).reason, "event has no string `type`");
+        assert_eq!(reason(r#"{"type":"system"}"#), violation(Some("system"), "missing `subtype`"));
+        assert_eq!(
+            reason(r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"t","input":{}}]}}"#).reason,
+            "message part 0: missing `name`"
+        );
+        assert_eq!(reason(r#"{"type":"result","subtype":"success","is_error":"no"}"#).reason, "`is_error` is not a boolean");
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_keeps_context() {
+        let pool = test_pool().await;
+
+        let cli = CliInfo::new("/nonexistent/amp", &["--execute".to_string(), "--stream-json".to_string()]);
+        let mut validator = StreamValidator::new("thread", "t1", cli);
+        let output = format!("{{\"type\":\"system\",\"subtype\":\"init\"}}\r\n{{\"type\":\"assistant\"\n{}", "é".repeat(MAX_STORED_BYTES));
+        let mut lines = StreamLines::new(output.as_bytes());
+        assert!(validator.check(&lines.next_line().await.unwrap().unwrap()).is_ok());
+        let quarantined = validator.check(&lines.next_line().await.unwrap().unwrap()).unwrap_err();
+        assert_eq!(quarantined.byte_offset, 36);
+        assert_eq!(quarantined.error_output()["type"], "error_output");
+        quarantined.store(&pool).await.unwrap();
+        validator.check(&lines.next_line().await.unwrap().unwrap()).unwrap_err().store(&pool).await.unwrap();
+        assert_eq!(lines.next_line().await.unwrap(), None);
+
+        let events = quarantined_events(&pool, Some("t1"), 10).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].line.len() <= MAX_STORED_BYTES);
+        assert_eq!(events[0].line_bytes, 2 * MAX_STORED_BYTES as i64);
+        let first = &events[1];
+        assert_eq!((first.byte_offset, first.stream.as_str(), first.cli_version.as_deref()), (36, "thread", None));
+        assert!(first.reason.starts_with("invalid JSON"), "{}", first.reason);
+        assert!(quarantined_events(&pool, Some("other"), 10).await.unwrap().is_empty());
+    }
+}
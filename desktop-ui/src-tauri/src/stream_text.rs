@@ -0,0 +1,51 @@
+//! Chat session titles and snippets taken from stream events
+
+use serde_json::Value;
+
+/// Longest snippet of the last assistant message, in bytes before the ellipsis
+pub const SNIPPET_BYTES: usize = 120;
+/// Longest title taken from the first prompt, in bytes before the ellipsis
+pub const TITLE_BYTES: usize = 60;
+
+/// `text` cut to at most `max` bytes at a character boundary, marked with an ellipsis when cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+/// Snippet of an assistant event's text, if it has any
+pub fn assistant_snippet(event: &Value) -> Option<String> {
+    let mut text = String::new();
+    if let Some(content) = event.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_array()) {
+        for part in content {
+            if let Some(s) = part.get("text").and_then(|x| x.as_str()) {
+                text.push_str(s);
+                // Nothing past the snippet is kept, however long the message
+                if text.len() > SNIPPET_BYTES {
+                    break;
+                }
+            }
+        }
+    } else if let Some(s) = event.get("text").and_then(|x| x.as_str()) {
+        text.push_str(s);
+    }
+    (!text.is_empty()).then(|| truncate(&text, SNIPPET_BYTES))
+}
+
+/// Title from the first text part of a user event
+pub fn user_title(event: &Value) -> Option<String> {
+    let prompt = event
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|p| p.get("text"))
+        .and_then(|x| x.as_str())?;
+    Some(truncate(prompt, TITLE_BYTES))
+}
@@ -0,0 +1,117 @@
+//! Git submodules in session worktrees
+//!
+//! `git worktree add` leaves submodules uninitialized, so a new worktree gets a
+//! recursive `submodule update --init` unless that is turned off in the app
+//! config. Submodule state is reported alongside the worktree's changes, and
+//! merge-back refuses a branch that moves a submodule pointer unless the caller
+//! says the move is intended.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::commit_message::git;
+
+/// Mode of a gitlink (submodule pointer) entry in a tree or index
+const GITLINK_MODE: &str = "160000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmoduleState {
+    /// Checked out at the commit recorded in the superproject
+    Current,
+    Uninitialized,
+    /// Checked out at a different commit than the recorded one
+    Moved,
+    /// Merge conflict on the pointer
+    Conflicted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmoduleStatus {
+    pub path: String,
+    /// Commit checked out in the submodule, or the recorded one when uninitialized
+    pub commit: String,
+    pub state: SubmoduleState,
+}
+
+/// Parse `git submodule status --recursive`
+pub fn parse_submodule_status(output: &str) -> Vec<SubmoduleStatus> {
+    output
+        .lines()
+        .filter(|line| line.len() > 1)
+        .filter_map(|line| {
+            let state = match line.as_bytes()[0] {
+                b' ' => SubmoduleState::Current,
+                b'-' => SubmoduleState::Uninitialized,
+                b'+' => SubmoduleState::Moved,
+                b'U' => SubmoduleState::Conflicted,
+                _ => return None,
+            };
+            let mut fields = line[1..].split_whitespace();
+            let commit = fields.next()?.to_string();
+            let path = fields.next()?.to_string();
+            Some(SubmoduleStatus { path, commit, state })
+        })
+        .collect()
+}
+
+pub fn submodule_status(worktree: &Path) -> Result<Vec<SubmoduleStatus>, String> {
+    if !worktree.join(".gitmodules").exists() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_submodule_status(&git(worktree, &["submodule", "status", "--recursive"])?))
+}
+
+/// Check out every submodule, recursively, at its recorded commit
+pub fn init_submodules(worktree: &Path) -> Result<Vec<SubmoduleStatus>, String> {
+    if !worktree.join(".gitmodules").exists() {
+        return Ok(Vec::new());
+    }
+    git(worktree, &["submodule", "update", "--init", "--recursive"])?;
+    submodule_status(worktree)
+}
+
+/// Submodule paths whose pointer differs between `base` and `head`, from `git diff --raw`
+pub fn changed_pointers(worktree: &Path, base: &str, head: &str) -> Result<Vec<String>, String> {
+    let raw = git(worktree, &["diff", "--raw", "--no-abbrev", base, head])?;
+    Ok(parse_gitlink_changes(&raw))
+}
+
+fn parse_gitlink_changes(raw: &str) -> Vec<String> {
+    raw.lines()
+        .filter_map(|line| {
+            let (meta, path) = line.strip_prefix(':')?.split_once('\t')?;
+            let mut modes = meta.split_whitespace();
+            let (old_mode, new_mode) = (modes.next()?, modes.next()?);
+            (old_mode == GITLINK_MODE || new_mode == GITLINK_MODE).then(|| path.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_submodule_status_and_pointer_changes() {
+        let status = parse_submodule_status(
+            " 1111111111111111111111111111111111111111 vendor/lib (v1.2.0)\n\
+             -2222222222222222222222222222222222222222 vendor/tools\n\
+             +3333333333333333333333333333333333333333 vendor/lib/nested (heads/main)\n",
+        );
+        let states: Vec<(&str, SubmoduleState)> = status.iter().map(|s| (s.path.as_str(), s.state)).collect();
+        assert_eq!(
+            states,
+            vec![
+                ("vendor/lib", SubmoduleState::Current),
+                ("vendor/tools", SubmoduleState::Uninitialized),
+                ("vendor/lib/nested", SubmoduleState::Moved),
+            ]
+        );
+
+        let raw = ":160000 160000 aaaa bbbb M\tvendor/lib\n\
+                   :100644 100644 cccc dddd M\tsrc/main.rs\n\
+                   :000000 160000 0000 eeee A\tvendor/new\n";
+        assert_eq!(parse_gitlink_changes(raw), vec!["vendor/lib".to_string(), "vendor/new".to_string()]);
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     io::{Read, Write},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
 };
@@ -16,8 +17,11 @@ struct SessionHandles {
     writer: Box<dyn Write + Send>,
     #[allow(dead_code)]
     reader_thread: thread::JoinHandle<()>,
-    #[allow(dead_code)]
     child: Box<dyn portable_pty::Child + Send>,
+    /// Directory the shell started in or was last sent a `cd` to
+    cwd: Option<PathBuf>,
+    /// Follow the active session's worktree, see `terminal_active_session_changed`
+    follow_active_session: bool,
 }
 
 static SESSIONS: once_cell::sync::Lazy<Arc<Mutex<HashMap<String, SessionHandles>>>> =
@@ -29,10 +33,6 @@ struct PtyData {
     chunk: String,
 }
 
-fn login_shell() -> String {
-    std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
-}
-
 fn open_pty(cols: u16, rows: u16) -> anyhow::Result<PtyPair> {
     let pty_system = native_pty_system();
     let pair = pty_system.openpty(PtySize {
@@ -82,7 +82,7 @@ async fn build_tui_env_from_state(
 
 fn resolve_simple_shell() -> String {
     // Just return the user's default shell - no amp command launching
-    login_shell()
+    crate::platform::default_shell()
 }
 
 #[tauri::command]
@@ -125,7 +125,7 @@ pub async fn cmd_start_tui(
     let shell_cmd = resolve_simple_shell();
     let mut cmd = CommandBuilder::new(&shell_cmd);
 
-    if let Some(dir) = cwd {
+    if let Some(dir) = &cwd {
         cmd.cwd(dir);
     }
     
@@ -177,6 +177,8 @@ pub async fn cmd_start_tui(
             writer,
             reader_thread,
             child,
+            cwd: cwd.map(PathBuf::from),
+            follow_active_session: false,
         },
     );
 
@@ -234,3 +236,151 @@ pub fn cmd_kill(session_id: String) -> Result<(), String> {
         }
     }
 }
+
+/// What `terminal_sync_cwd` did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CwdSync {
+    /// A `cd` was sent to the shell
+    Changed { path: String },
+    /// The shell is already there
+    Unchanged { path: String },
+    /// A command is running in the terminal, so nothing was sent
+    Busy,
+}
+
+/// Whether the shell is at its prompt: no command it started holds the
+/// terminal's foreground process group
+#[cfg(unix)]
+fn shell_is_idle(session: &SessionHandles) -> bool {
+    match (session.master.process_group_leader(), session.child.process_id()) {
+        (Some(foreground), Some(shell)) => foreground as i64 == shell as i64,
+        _ => false,
+    }
+}
+
+/// Without process groups a running command cannot be told apart from the prompt
+#[cfg(not(unix))]
+fn shell_is_idle(_session: &SessionHandles) -> bool {
+    false
+}
+
+/// The shell's working directory, as the OS reports it where it can
+fn shell_cwd(session: &SessionHandles) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    if let Some(cwd) = session.child.process_id().and_then(|pid| std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()) {
+        return Some(cwd);
+    }
+    session.cwd.clone()
+}
+
+/// A `cd` into `path` for POSIX shells; the leading space keeps it out of the
+/// history of shells that ignore space-prefixed lines
+fn cd_line(path: &Path) -> String {
+    format!(" cd -- '{}'\r", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+fn sync_cwd(terminal_id: &str, target: &Path) -> Result<CwdSync, String> {
+    let target = target.canonicalize().unwrap_or_else(|_| target.to_path_buf());
+    let path = target.to_string_lossy().to_string();
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.get_mut(terminal_id).ok_or("no such session")?;
+    if shell_cwd(session).and_then(|cwd| cwd.canonicalize().ok()).as_deref() == Some(target.as_path()) {
+        return Ok(CwdSync::Unchanged { path });
+    }
+    if !shell_is_idle(session) {
+        return Ok(CwdSync::Busy);
+    }
+    session.writer.write_all(cd_line(&target).as_bytes()).map_err(|e| e.to_string())?;
+    session.writer.flush().ok();
+    session.cwd = Some(target);
+    Ok(CwdSync::Changed { path })
+}
+
+/// `cd` a terminal into a session's worktree, unless a command is running in it
+#[tauri::command]
+pub async fn terminal_sync_cwd(app: AppHandle, terminal_id: String, session_id: String) -> Result<CwdSync, String> {
+    let worktree = crate::commit_message::session_worktree(&app, &session_id).await?;
+    sync_cwd(&terminal_id, &worktree)
+}
+
+/// Have a terminal follow the active session's worktree
+#[tauri::command]
+pub fn terminal_set_follow(terminal_id: String, follow: bool) -> Result<(), String> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.get_mut(&terminal_id).ok_or("no such session")?;
+    session.follow_active_session = follow;
+    Ok(())
+}
+
+/// Called by the UI when the active session changes: syncs every following
+/// terminal to its worktree and returns what happened in each
+#[tauri::command]
+pub async fn terminal_active_session_changed(app: AppHandle, session_id: String) -> Result<HashMap<String, CwdSync>, String> {
+    let following: Vec<String> = SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, session)| session.follow_active_session)
+        .map(|(id, _)| id.clone())
+        .collect();
+    if following.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let worktree = crate::commit_message::session_worktree(&app, &session_id).await?;
+    following
+        .into_iter()
+        .map(|terminal_id| sync_cwd(&terminal_id, &worktree).map(|outcome| (terminal_id, outcome)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cd_line_quotes_the_path() {
+        assert_eq!(cd_line(Path::new("/repo/.worktrees/it's")), " cd -- '/repo/.worktrees/it'\\''s'\r");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sync_cwd_leaves_running_commands_alone() {
+        fn wait_until(mut condition: impl FnMut() -> bool) {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+            while !condition() {
+                assert!(std::time::Instant::now() < deadline, "timed out");
+                thread::sleep(std::time::Duration::from_millis(20));
+            }
+        }
+        let idle = |id: &str| SESSIONS.lock().unwrap().get(id).is_some_and(shell_is_idle);
+
+        let worktree = tempfile::TempDir::new().unwrap();
+        let pair = open_pty(80, 24).unwrap();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.cwd(std::env::temp_dir());
+        let child = pair.slave.spawn_command(cmd).unwrap();
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let reader_thread = thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while matches!(reader.read(&mut buf), Ok(n) if n > 0) {}
+        });
+        let writer = pair.master.take_writer().unwrap();
+        let id = "test-sync-cwd";
+        SESSIONS.lock().unwrap().insert(
+            id.to_string(),
+            SessionHandles { master: pair.master, writer, reader_thread, child, cwd: None, follow_active_session: true },
+        );
+
+        wait_until(|| idle(id));
+        cmd_write_stdin(id.to_string(), "sleep 30\r".to_string()).unwrap();
+        wait_until(|| !idle(id));
+        assert_eq!(sync_cwd(id, worktree.path()).unwrap(), CwdSync::Busy);
+
+        cmd_write_stdin(id.to_string(), "\x03".to_string()).unwrap();
+        wait_until(|| idle(id));
+        assert!(matches!(sync_cwd(id, worktree.path()).unwrap(), CwdSync::Changed { .. }));
+        wait_until(|| matches!(sync_cwd(id, worktree.path()), Ok(CwdSync::Unchanged { .. })));
+        cmd_kill(id.to_string()).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+//! User ratings and comments per thread
+//!
+//! Each rating records a hash of the environment the thread ran with so feedback
+//! can be grouped by configuration. The latest rating also feeds session outcome
+//! classification in `session_analytics`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use tauri::State;
+
+use crate::profile_auth::ProfileManager;
+use crate::session_analytics::SessionOutcomeStore;
+
+pub const MIN_RATING: i64 = 1;
+pub const MAX_RATING: i64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ThreadFeedback {
+    pub id: i64,
+    pub thread_id: String,
+    pub rating: i64,
+    pub comment: Option<String>,
+    pub env_hash: String,
+    pub agent_mode: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackSummary {
+    pub env_hash: String,
+    pub agent_mode: Option<String>,
+    pub count: i64,
+    pub avg_rating: f64,
+}
+
+/// Stable hash of the settings that shape a thread's environment
+pub fn environment_hash(context: &str, agent_mode: Option<&str>, toolbox: Option<&str>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for part in [context, agent_mode.unwrap_or(""), toolbox.unwrap_or("")] {
+        hasher.update(part.as_bytes());
+        // Separator keeps ("ab", "c") and ("a", "bc") distinct
+        hasher.update(&[0]);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+pub struct FeedbackStore {
+    db: SqlitePool,
+}
+
+impl FeedbackStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Context, agent mode and toolbox of a thread, falling back to legacy chat sessions
+    pub async fn environment_for(&self, thread_id: &str) -> Result<Option<(String, Option<String>, Option<String>)>, sqlx::Error> {
+        let thread = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT context, agent_mode, toolbox_snapshot FROM threads WHERE id = ?"
+        )
+        .bind(thread_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        if thread.is_some() {
+            return Ok(thread);
+        }
+
+        sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT context, agent_mode, toolbox_path FROM chat_sessions WHERE id = ?"
+        )
+        .bind(thread_id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    pub async fn add_feedback(
+        &self,
+        thread_id: &str,
+        rating: i64,
+        comment: Option<&str>,
+        env_hash: &str,
+        agent_mode: Option<&str>,
+    ) -> Result<ThreadFeedback, sqlx::Error> {
+        sqlx::query_as::<_, ThreadFeedback>(
+            "INSERT INTO thread_feedback (thread_id, rating, comment, env_hash, agent_mode) VALUES (?, ?, ?, ?, ?)
+             RETURNING id, thread_id, rating, comment, env_hash, agent_mode, created_at"
+        )
+        .bind(thread_id)
+        .bind(rating)
+        .bind(comment)
+        .bind(env_hash)
+        .bind(agent_mode)
+        .fetch_one(&self.db)
+        .await
+    }
+
+    pub async fn list_feedback(&self, thread_id: &str) -> Result<Vec<ThreadFeedback>, sqlx::Error> {
+        sqlx::query_as::<_, ThreadFeedback>(
+            "SELECT id, thread_id, rating, comment, env_hash, agent_mode, created_at
+             FROM thread_feedback WHERE thread_id = ? ORDER BY id DESC"
+        )
+        .bind(thread_id)
+        .fetch_all(&self.db)
+        .await
+    }
+
+    pub async fn latest_feedback(&self, thread_id: &str) -> Result<Option<ThreadFeedback>, sqlx::Error> {
+        Ok(self.list_feedback(thread_id).await?.into_iter().next())
+    }
+
+    pub async fn latest_rating(&self, thread_id: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT rating FROM thread_feedback WHERE thread_id = ? ORDER BY id DESC LIMIT 1"
+        )
+        .bind(thread_id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    /// Average rating per environment hash, counting only each thread's latest rating
+    pub async fn summary(&self) -> Result<Vec<FeedbackSummary>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, Option<String>, i64, f64)>(
+            "SELECT env_hash, agent_mode, COUNT(*), AVG(rating)
+             FROM thread_feedback
+             WHERE id IN (SELECT MAX(id) FROM thread_feedback GROUP BY thread_id)
+             GROUP BY env_hash, agent_mode ORDER BY COUNT(*) DESC"
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(env_hash, agent_mode, count, avg_rating)| FeedbackSummary { env_hash, agent_mode, count, avg_rating })
+            .collect())
+    }
+}
+
+/// Rate a thread from 1 to 5 with an optional comment
+#[tauri::command]
+pub async fn thread_rate(
+    thread_id: String,
+    rating: i64,
+    comment: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ThreadFeedback, String> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(format!("Rating must be between {} and {}", MIN_RATING, MAX_RATING));
+    }
+    let comment = comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = FeedbackStore::new(db.clone());
+
+    let (context, agent_mode, toolbox) = store
+        .environment_for(&thread_id)
+        .await
+        .map_err(|e| format!("Failed to get thread: {}", e))?
+        .ok_or_else(|| format!("Thread {} not found", thread_id))?;
+    let env_hash = environment_hash(&context, agent_mode.as_deref(), toolbox.as_deref());
+
+    let feedback = store
+        .add_feedback(&thread_id, rating, comment.as_deref(), &env_hash, agent_mode.as_deref())
+        .await
+        .map_err(|e| format!("Failed to save feedback: {}", e))?;
+
+    // Keep the stored outcome in line with the new rating
+    let outcomes = SessionOutcomeStore::new(db.clone());
+    if let Ok(config) = outcomes.load_config().await {
+        if let Err(e) = outcomes.reclassify(&thread_id, &config).await {
+            log::warn!("Failed to reclassify thread {} after rating: {}", thread_id, e);
+        }
+    }
+
+    Ok(feedback)
+}
+
+#[tauri::command]
+pub async fn get_thread_feedback(
+    thread_id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<ThreadFeedback>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    FeedbackStore::new(db.clone())
+        .list_feedback(&thread_id)
+        .await
+        .map_err(|e| format!("Failed to get thread feedback: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_feedback_summary(
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<FeedbackSummary>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    FeedbackStore::new(db.clone())
+        .summary()
+        .await
+        .map_err(|e| format!("Failed to get feedback summary: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    #[test]
+    fn test_environment_hash() {
+        let a = environment_hash("production", Some("geppetto:main"), Some("/tools"));
+        assert_eq!(a, environment_hash("production", Some("geppetto:main"), Some("/tools")));
+        assert_ne!(a, environment_hash("development", Some("geppetto:main"), Some("/tools")));
+        assert_ne!(environment_hash("ab", Some("c"), None), environment_hash("a", Some("bc"), None));
+    }
+
+    #[tokio::test]
+    async fn test_environment_lookup_prefers_threads() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Session')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, agent_mode, toolbox_snapshot) VALUES ('t1', 's1', 'development', 'bolt', '{}')")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO chat_sessions (id, context, agent_mode) VALUES ('c1', 'production', 'default')")
+            .execute(&pool).await.unwrap();
+        let store = FeedbackStore::new(pool);
+
+        let thread = store.environment_for("t1").await.unwrap().unwrap();
+        assert_eq!(thread, ("development".to_string(), Some("bolt".to_string()), Some("{}".to_string())));
+        let chat = store.environment_for("c1").await.unwrap().unwrap();
+        assert_eq!(chat.0, "production");
+        assert!(store.environment_for("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_feedback_latest_and_summary() {
+        let store = FeedbackStore::new(test_pool().await);
+
+        store.add_feedback("t1", 2, Some("meh"), "hash-a", Some("bolt")).await.unwrap();
+        store.add_feedback("t1", 5, None, "hash-a", Some("bolt")).await.unwrap();
+        store.add_feedback("t2", 3, None, "hash-a", Some("bolt")).await.unwrap();
+        store.add_feedback("t3", 1, Some("broke the build"), "hash-b", None).await.unwrap();
+
+        assert_eq!(store.latest_rating("t1").await.unwrap(), Some(5));
+        assert_eq!(store.list_feedback("t1").await.unwrap().len(), 2);
+        assert_eq!(store.latest_rating("none").await.unwrap(), None);
+
+        let summary = store.summary().await.unwrap();
+        let a = summary.iter().find(|s| s.env_hash == "hash-a").unwrap();
+        assert_eq!(a.count, 2);
+        assert_eq!(a.avg_rating, 4.0);
+        assert!(store.add_feedback("t1", 9, None, "hash-a", None).await.is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Amp server chosen for a single thread
+//!
+//! A thread can talk to a different Amp server from the rest of the app, so one
+//! thread can run against a local dev server while another stays on production.
+//! The override is stored in the thread's toolbox snapshot and applied again
+//! whenever the thread's process is restarted. It takes precedence over the
+//! session's Amp profile and the app's connection mode.
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerOverride {
+    pub amp_url: String,
+    /// Accept self-signed certificates; only allowed for a server on this machine
+    #[serde(default)]
+    pub tls_insecure: bool,
+}
+
+fn is_loopback(url: &Url) -> bool {
+    match url.host_str() {
+        Some(host) => {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            host == "localhost"
+                || host.ends_with(".localhost")
+                || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+        }
+        None => false,
+    }
+}
+
+impl ServerOverride {
+    /// Check the URL and TLS settings, returning the override with a normalized URL
+    pub fn validate(self) -> Result<Self, String> {
+        let url = Url::parse(self.amp_url.trim()).map_err(|e| format!("Invalid Amp URL '{}': {}", self.amp_url, e))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err(format!("Amp URL '{}' must be an http or https address", self.amp_url));
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err("Amp URL must not contain credentials".to_string());
+        }
+        let local = is_loopback(&url);
+        if url.scheme() == "http" && !local {
+            return Err(format!("Amp URL '{}' must use https unless the server is on this machine", self.amp_url));
+        }
+        if self.tls_insecure && (url.scheme() != "https" || !local) {
+            return Err("Certificate checks can only be disabled for an https server on this machine".to_string());
+        }
+        Ok(Self { amp_url: url.as_str().trim_end_matches('/').to_string(), tls_insecure: self.tls_insecure })
+    }
+
+    /// Context label of a thread talking to this server
+    pub fn context(&self) -> &'static str {
+        match Url::parse(&self.amp_url) {
+            Ok(url) if is_loopback(&url) => "development",
+            _ => "production",
+        }
+    }
+
+    pub fn apply(&self, env: &mut HashMap<String, String>) {
+        env.insert("AMP_URL".to_string(), self.amp_url.clone());
+        if self.tls_insecure {
+            env.insert("NODE_TLS_REJECT_UNAUTHORIZED".to_string(), "0".to_string());
+        } else {
+            env.remove("NODE_TLS_REJECT_UNAUTHORIZED");
+        }
+    }
+
+    /// The override recorded in a thread's toolbox snapshot, if any
+    pub fn from_snapshot(snapshot: Option<&str>) -> Option<Self> {
+        let snapshot: serde_json::Value = serde_json::from_str(snapshot?).ok()?;
+        serde_json::from_value(snapshot.get("server")?.clone()).ok()
+    }
+}
+
+/// Record `server` in a toolbox snapshot
+pub fn with_server(snapshot: String, server: Option<&ServerOverride>) -> String {
+    let Some(server) = server else {
+        return snapshot;
+    };
+    match serde_json::from_str::<serde_json::Value>(&snapshot) {
+        Ok(serde_json::Value::Object(mut fields)) => {
+            fields.insert("server".to_string(), serde_json::json!(server));
+            serde_json::Value::Object(fields).to_string()
+        }
+        _ => snapshot,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(url: &str, tls_insecure: bool) -> Result<ServerOverride, String> {
+        ServerOverride { amp_url: url.to_string(), tls_insecure }.validate()
+    }
+
+    #[test]
+    fn test_validate() {
+        let local = server(" https://localhost:7002/ ", true).unwrap();
+        assert_eq!(local.amp_url, "https://localhost:7002");
+        assert_eq!(local.context(), "development");
+        assert_eq!(server("http://127.0.0.1:7002", false).unwrap().context(), "development");
+        assert_eq!(server("https://ampcode.com", false).unwrap().context(), "production");
+
+        assert!(server("ampcode.com", false).is_err());
+        assert!(server("ftp://localhost", false).is_err());
+        assert!(server("http://ampcode.com", false).is_err());
+        assert!(server("https://ampcode.com", true).is_err());
+        assert!(server("http://localhost:7002", true).is_err());
+        assert!(server("https://user:pw@localhost", false).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_apply() {
+        let snapshot = r#"{"profile_id":null,"paths":[]}"#.to_string();
+        assert_eq!(with_server(snapshot.clone(), None), snapshot);
+        assert_eq!(ServerOverride::from_snapshot(Some(&snapshot)), None);
+
+        let local = server("https://localhost:7002", true).unwrap();
+        let stored = with_server(snapshot, Some(&local));
+        assert_eq!(ServerOverride::from_snapshot(Some(&stored)), Some(local.clone()));
+
+        let mut env = HashMap::from([("AMP_URL".to_string(), "https://ampcode.com".to_string())]);
+        local.apply(&mut env);
+        assert_eq!(env["AMP_URL"], "https://localhost:7002");
+        assert_eq!(env["NODE_TLS_REJECT_UNAUTHORIZED"], "0");
+        server("https://ampcode.com", false).unwrap().apply(&mut env);
+        assert!(!env.contains_key("NODE_TLS_REJECT_UNAUTHORIZED"));
+    }
+}
@@ -1,68 +1,38 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri::{AppHandle, State, Emitter};
+use tauri::{AppHandle, State};
 use tokio::process::Command;
-use tokio::io::{AsyncBufReadExt, BufReader, BufWriter, AsyncWriteExt};
+use tokio::io::{BufReader, BufWriter, AsyncWriteExt};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 use sqlx::SqlitePool;
 
 use crate::session_commands::{AmpSessionMap, AmpSession, choose_amp_command};
 use crate::toolbox_profiles::ToolboxProfileStore;
+use crate::list_query::{fetch_page, ListQuery, ListResult, ListSpec, SortDirection};
+use crate::thread_server::{self, ServerOverride};
+use crate::spawn_env_audit::SpawnReason;
+use crate::worktree_paths::WorktreePathResolver;
 
 
-/// Generate the worktree path for a given session ID
-fn path_for(repo_path: &std::path::Path, session_id: &str) -> std::path::PathBuf {
-    let short_sid = &session_id[..session_id.len().min(8)];
-    repo_path.join(".amp-worktrees").join(short_sid)
-}
-
-/// Helper function to get session worktree path
-/// Falls back to current directory if session worktree cannot be determined
-async fn get_session_worktree_path(session_id: Option<&str>) -> std::path::PathBuf {
-    if let Some(session_id) = session_id {
-        // Try to find the repository root from current directory
-        if let Ok(current_dir) = std::env::current_dir() {
-            if let Ok(repo_path) = find_repo_root(&current_dir) {
-                let worktree_path = path_for(&repo_path, session_id);
-                if worktree_path.exists() {
-                    return worktree_path;
-                }
-                // If worktree doesn't exist, return repo root
-                return repo_path;
-            }
-        }
-    }
-    // Default fallback to current directory
-    std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-}
-
-/// Find the Git repository root starting from a given path
-fn find_repo_root(start_path: &std::path::Path) -> Result<std::path::PathBuf, String> {
-    let mut current_path = start_path;
-    
-    loop {
-        if current_path.join(".git").exists() {
-            return Ok(current_path.to_path_buf());
-        }
-        
-        match current_path.parent() {
-            Some(parent) => current_path = parent,
-            None => return Err("No Git repository found".to_string()),
-        }
-    }
-}
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SessionCreateRequest {
     pub profile_id: Option<i64>,
+    /// Amp account profile the session's threads run under; defaults to the active profile
+    pub amp_profile_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ThreadStartRequest {
     pub session_id: String,
     pub context: String,  // "production" or "development"
+    /// Defaults to the session's Amp profile default agent mode
     pub agent_mode: Option<String>,
+    /// Overrides the Amp profile's default model for this thread
+    pub model: Option<String>,
+    /// Amp server for this thread only; also decides its context
+    #[serde(default)]
+    pub server: Option<ServerOverride>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -78,8 +48,11 @@ pub struct ThreadRefreshEnvRequest {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SessionInfo {
     pub id: String,
+    /// Short code shown instead of the ID; commands accept either
+    pub short_code: Option<String>,
     pub title: Option<String>,
     pub profile_id: Option<i64>,
+    pub amp_profile_id: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -100,7 +73,7 @@ pub struct ThreadInfo {
 #[tauri::command]
 pub async fn new_session_create(
     request: SessionCreateRequest,
-    _app_state: State<'_, crate::app_state::AppState>,
+    app_state: State<'_, crate::app_state::AppState>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<SessionInfo, String> {
     let session_id = Uuid::new_v4().to_string();
@@ -109,8 +82,30 @@ pub async fn new_session_create(
     let db = profile_manager.db_pool.read().await;
     let db = db.as_ref().ok_or("Database not available")?;
 
+    let prefer_app_state = app_state.lock().unwrap().connection_mode.is_some();
+    let amp_profile_id = profile_manager
+        .resolve_session_profile(request.amp_profile_id.as_deref(), prefer_app_state)
+        .await?;
+
+    // Without an explicit toolbox profile, use the Amp profile's default, then
+    // one named after the repository's languages and frameworks
+    let toolbox_profile_id = match (request.profile_id, &amp_profile_id) {
+        (Some(id), _) => Some(id),
+        (None, Some(amp_profile_id)) => profile_manager
+            .profile_row(amp_profile_id)
+            .await
+            .and_then(|profile| profile.default_toolbox_profile_id),
+        (None, None) => None,
+    };
+    let stack = crate::stack_detection::detect_repo().await;
+    let detected_toolbox_profile_id = match (toolbox_profile_id, &stack) {
+        (None, Some(stack)) => crate::stack_detection::default_toolbox_profile(db, stack).await,
+        _ => None,
+    };
+    let toolbox_profile_id = toolbox_profile_id.or(detected_toolbox_profile_id);
+
     // Validate profile exists if provided
-    if let Some(profile_id) = request.profile_id {
+    if let Some(profile_id) = toolbox_profile_id {
         let store = ToolboxProfileStore::new(db.clone());
         let profile = store.get_profile(profile_id).await
             .map_err(|e| format!("Failed to get profile: {}", e))?;
@@ -120,45 +115,72 @@ pub async fn new_session_create(
         }
     }
 
+    crate::profile_quotas::check(db, amp_profile_id.as_deref(), 1)
+        .await
+        .map_err(|e| e.to_command_error())?;
+
     // Insert session into database
-    let result = sqlx::query_as::<_, (String, Option<String>, Option<i64>, String, String)>(
-        "INSERT INTO sessions (id, title, profile_id) VALUES (?, ?, ?) 
-         RETURNING id, title, profile_id, created_at, updated_at"
+    let result = sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>, String, String)>(
+        "INSERT INTO sessions (id, title, profile_id, amp_profile_id) VALUES (?, ?, ?, ?) 
+         RETURNING id, title, profile_id, amp_profile_id, created_at, updated_at"
     )
     .bind(&session_id)
     .bind("New Session")
-    .bind(request.profile_id)
+    .bind(toolbox_profile_id)
+    .bind(&amp_profile_id)
     .fetch_one(db)
     .await
     .map_err(|e| format!("Failed to create session: {}", e))?;
 
+    if let Some(amp_profile_id) = &amp_profile_id {
+        if let Err(e) = crate::profile_quotas::record(db, amp_profile_id, crate::profile_quotas::UsageKind::Session, 1).await {
+            log::warn!("{}", e);
+        }
+    }
+
+    if let Some(stack) = &stack {
+        if let Err(e) = crate::stack_detection::save_stack(db, &session_id, stack, detected_toolbox_profile_id).await {
+            log::warn!("{}", e);
+        }
+    }
+
+    let code_config = app_state.lock().unwrap().session_codes.clone();
+    let short_code = crate::session_codes::assign(db, &session_id, &code_config).await?;
+
     Ok(SessionInfo {
         id: result.0,
+        short_code: Some(short_code),
         title: result.1,
         profile_id: result.2,
-        created_at: result.3,
-        updated_at: result.4,
+        amp_profile_id: result.3,
+        created_at: result.4,
+        updated_at: result.5,
     })
 }
 
 /// Starts a new thread within a session with proper environment isolation
 #[tauri::command]
 pub async fn thread_start(
-    request: ThreadStartRequest,
+    mut request: ThreadStartRequest,
     app_handle: AppHandle,
     app_state: State<'_, crate::app_state::AppState>,
     amp_sessions: State<'_, AmpSessionMap>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<ThreadInfo, String> {
     let thread_id = Uuid::new_v4().to_string();
+    request.session_id = crate::session_codes::resolve(&request.session_id);
+    let server = request.server.take().map(ServerOverride::validate).transpose()?;
+    if let Some(server) = &server {
+        request.context = server.context().to_string();
+    }
     
     // Get database connection
     let db = profile_manager.db_pool.read().await;
     let db = db.as_ref().ok_or("Database not available")?;
 
     // Verify session exists and get profile info
-    let session = sqlx::query_as::<_, (String, Option<String>, Option<i64>)>(
-        "SELECT id, title, profile_id FROM sessions WHERE id = ?"
+    let session = sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>)>(
+        "SELECT id, title, profile_id, amp_profile_id FROM sessions WHERE id = ?"
     )
     .bind(&request.session_id)
     .fetch_optional(db)
@@ -166,27 +188,49 @@ pub async fn thread_start(
     .map_err(|e| format!("Failed to get session: {}", e))?
     .ok_or_else(|| format!("Session {} not found", request.session_id))?;
 
+    // Explicit agent mode and model win over the Amp profile's defaults
+    let profile_defaults = match session.3.as_deref() {
+        Some(amp_profile_id) => profile_manager.profile_row(amp_profile_id).await,
+        None => None,
+    };
+    let agent_mode = request
+        .agent_mode
+        .clone()
+        .or_else(|| profile_defaults.and_then(|profile| profile.default_agent_mode));
+
     // Build environment with toolbox isolation
-    let mut merged_env = build_thread_env(&app_state, session.2, &request.context, &request.agent_mode).await?;
+    let mut merged_env = build_thread_env(&app_state, session.2, &request.context, &agent_mode).await?;
+    apply_session_profile(&profile_manager, session.3.as_deref(), &mut merged_env).await?;
+    if let Some(server) = &server {
+        server.apply(&mut merged_env);
+    }
+    if let Some(model) = &request.model {
+        merged_env.insert("AMP_MODEL".to_string(), model.clone());
+    }
     
     // Create toolbox snapshot for thread isolation
-    let toolbox_snapshot = create_toolbox_snapshot(session.2, &profile_manager).await?;
+    let toolbox_snapshot = create_toolbox_snapshot(session.2, &thread_id, &profile_manager).await?;
+    let toolbox_snapshot = thread_server::with_server(toolbox_snapshot, server.as_ref());
     
     // Compose runtime environment (includes toolbox resolver)
     let compose = crate::runtime_env::compose_runtime_env(&mut merged_env)
         .map_err(|e| format!("Failed to compose runtime env: {}", e))?;
+    crate::env_isolation::isolate(db, &request.session_id, session.3.as_deref(), &mut merged_env).await;
+    crate::session_log::record_env(&app_handle, &thread_id, &merged_env);
+    crate::spawn_env_audit::record(db, &thread_id, SpawnReason::Start, &merged_env).await;
 
     // Insert thread into database
     let result = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>)>(
-        "INSERT INTO threads (id, session_id, context, agent_mode, toolbox_snapshot) 
-         VALUES (?, ?, ?, ?, ?) 
+        "INSERT INTO threads (id, session_id, context, agent_mode, toolbox_snapshot, model) 
+         VALUES (?, ?, ?, ?, ?, ?) 
          RETURNING id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at"
     )
     .bind(&thread_id)
     .bind(&request.session_id)
     .bind(&request.context)
-    .bind(&request.agent_mode)
+    .bind(&agent_mode)
     .bind(&toolbox_snapshot)
+    .bind(&request.model)
     .fetch_one(db)
     .await
     .map_err(|e| format!("Failed to create thread: {}", e))?;
@@ -194,19 +238,29 @@ pub async fn thread_start(
     // Start Amp process with isolated environment
     let (cmd, args) = choose_amp_command(&merged_env);
     
-    // Get session worktree path for command execution
-    let working_dir = get_session_worktree_path(Some(&request.session_id)).await;
+    // Get session worktree path for command execution; a recorded worktree, e.g. one
+    // created for an editor's workspace, wins over the launch repository's
+    let working_dir = WorktreePathResolver::for_pool(db.clone()).working_dir_from_current(Some(&request.session_id)).await;
     
-    let mut child = Command::new(&cmd)
+    let mut command = Command::new(&cmd);
+    command
         .args(&args)
-        .current_dir(working_dir)
+        .current_dir(&working_dir)
         .env_clear()
         .envs(&merged_env)
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    crate::process_limits::apply(&app_handle, &mut command);
+    let mut child = command
         .spawn()
-        .map_err(|e| format!("Failed to spawn amp process: {}", e))?;
+        .map_err(|e| {
+            crate::session_log::record_spawn(&app_handle, &thread_id, &cmd, &args, &working_dir, Err(&e));
+            format!("Failed to spawn amp process: {}", e)
+        })?;
+    let pid = child.id();
+    crate::session_log::record_spawn(&app_handle, &thread_id, &cmd, &args, &working_dir, Ok(pid));
+    crate::file_locks::register_session_root(&app_handle, &thread_id, working_dir);
 
     let stdin = child.stdin.take().ok_or_else(|| "Failed to open stdin".to_string())?;
     let stdout = child.stdout.take().ok_or_else(|| "Failed to open stdout".to_string())?;
@@ -231,14 +285,31 @@ pub async fn thread_start(
         use crate::worktree_manager::TauriWorktreeManager;
         use tauri::Manager;
         
-        if let Some(wt_manager) = app_handle.try_state::<TauriWorktreeManager>() {
-            match wt_manager.create_session_worktree(&request.session_id, None).await {
+        let enabled = crate::feature_flags::is_enabled(&app_state.lock().unwrap(), crate::feature_flags::WORKTREE_MANAGER);
+        if let Some(wt_manager) = app_handle.try_state::<TauriWorktreeManager>().filter(|_| enabled) {
+            match wt_manager.create_session_worktree_with_progress(&app_handle, &request.session_id, None, None).await {
                 Ok(guard) => {
                     log::info!("Created worktree for thread {} at {}", thread_id, guard.worktree_path().display());
+                    crate::session_log::record(
+                        &app_handle,
+                        &thread_id,
+                        crate::session_log::LogCategory::Git,
+                        crate::session_log::LogLevel::Info,
+                        "Created worktree",
+                        serde_json::json!({ "path": guard.worktree_path() }),
+                    );
                     Some(guard)
                 }
                 Err(e) => {
                     log::error!("Failed to create worktree for thread {}: {}", thread_id, e);
+                    crate::session_log::record(
+                        &app_handle,
+                        &thread_id,
+                        crate::session_log::LogCategory::Git,
+                        crate::session_log::LogLevel::Error,
+                        format!("Failed to create worktree: {}", e),
+                        serde_json::Value::Null,
+                    );
                     None
                 }
             }
@@ -248,19 +319,16 @@ pub async fn thread_start(
     };
 
     // Store session in AmpSessionMap
-    {
-        let mut map = amp_sessions.lock().await;
-        map.insert(thread_id.clone(), AmpSession {
-            child,
-            tx,
-            toolbox_guard: compose.guard,
-            #[cfg(feature = "worktree-manager")]
-            worktree_guard,
-        });
-    }
+    amp_sessions.insert(thread_id.clone(), AmpSession {
+        child,
+        tx,
+        toolbox_guard: compose.guard,
+        #[cfg(feature = "worktree-manager")]
+        worktree_guard,
+    });
 
     // Start output handling tasks
-    spawn_output_handlers(app_handle.clone(), thread_id.clone(), stdout, stderr, db.clone()).await;
+    spawn_output_handlers(app_handle.clone(), thread_id.clone(), stdout, stderr, pid, db.clone(), crate::stream_quarantine::CliInfo::new(&cmd, &args)).await;
 
     Ok(ThreadInfo {
         id: result.0,
@@ -279,7 +347,7 @@ pub async fn thread_start(
 pub async fn thread_attach(
     request: ThreadAttachRequest,
     app_handle: AppHandle,
-    _app_state: State<'_, crate::app_state::AppState>,
+    app_state: State<'_, crate::app_state::AppState>,
     amp_sessions: State<'_, AmpSessionMap>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<ThreadInfo, String> {
@@ -299,8 +367,7 @@ pub async fn thread_attach(
 
     // Check if thread is already active
     {
-        let map = amp_sessions.lock().await;
-        if map.contains_key(&request.thread_id) {
+        if amp_sessions.contains(&request.thread_id) {
             return Ok(ThreadInfo {
                 id: thread.0,
                 session_id: thread.1,
@@ -315,8 +382,8 @@ pub async fn thread_attach(
     }
 
     // Get session info for profile
-    let session = sqlx::query_as::<_, (Option<i64>,)>(
-        "SELECT profile_id FROM sessions WHERE id = ?"
+    let session = sqlx::query_as::<_, (Option<i64>, Option<String>)>(
+        "SELECT profile_id, amp_profile_id FROM sessions WHERE id = ?"
     )
     .bind(&thread.1)
     .fetch_optional(db)
@@ -326,23 +393,34 @@ pub async fn thread_attach(
 
     // Restore environment from thread snapshot
     let mut merged_env = restore_thread_env(&thread.4, session.0, &thread.2, &thread.3)?;
+    apply_session_profile(&profile_manager, session.1.as_deref(), &mut merged_env).await?;
+    if let Some(server) = ServerOverride::from_snapshot(thread.4.as_deref()) {
+        server.apply(&mut merged_env);
+    }
+    apply_thread_model(db, &request.thread_id, &mut merged_env).await?;
     
     // Re-compose runtime environment
     let compose = crate::runtime_env::compose_runtime_env(&mut merged_env)
         .map_err(|e| format!("Failed to compose runtime env: {}", e))?;
+    crate::env_isolation::isolate(db, &thread.1, session.1.as_deref(), &mut merged_env).await;
+    crate::spawn_env_audit::record(db, &request.thread_id, SpawnReason::Attach, &merged_env).await;
 
     // Restart Amp process
     let (cmd, args) = choose_amp_command(&merged_env);
     
-    let mut child = Command::new(&cmd)
+    let mut command = Command::new(&cmd);
+    command
         .args(&args)
         .env_clear()
         .envs(&merged_env)
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    crate::process_limits::apply(&app_handle, &mut command);
+    let mut child = command
         .spawn()
         .map_err(|e| format!("Failed to spawn amp process: {}", e))?;
+    let pid = child.id();
 
     let stdin = child.stdin.take().ok_or_else(|| "Failed to open stdin".to_string())?;
     let stdout = child.stdout.take().ok_or_else(|| "Failed to open stdout".to_string())?;
@@ -362,22 +440,19 @@ pub async fn thread_attach(
     });
 
     // Store session in AmpSessionMap
-    {
-        let mut map = amp_sessions.lock().await;
-        map.insert(request.thread_id.clone(), AmpSession {
-            child,
-            tx,
-            toolbox_guard: compose.guard,
-            #[cfg(feature = "worktree-manager")]
-            worktree_guard: None, // Could restore worktree if needed
-        });
-    }
+    amp_sessions.insert(request.thread_id.clone(), AmpSession {
+        child,
+        tx: tx.clone(),
+        toolbox_guard: compose.guard,
+        #[cfg(feature = "worktree-manager")]
+        worktree_guard: None, // Could restore worktree if needed
+    });
 
     // Start output handling tasks
-    spawn_output_handlers(app_handle.clone(), request.thread_id.clone(), stdout, stderr, db.clone()).await;
+    spawn_output_handlers(app_handle.clone(), request.thread_id.clone(), stdout, stderr, pid, db.clone(), crate::stream_quarantine::CliInfo::new(&cmd, &args)).await;
 
     // Send thread history to re-establish context
-    send_thread_history(&request.thread_id, &amp_sessions, db).await?;
+    crate::history_replay::replay(db, &request.thread_id, session.1.as_deref(), app_state, &tx).await?;
 
     Ok(ThreadInfo {
         id: thread.0,
@@ -396,7 +471,7 @@ pub async fn thread_attach(
 pub async fn thread_refresh_env(
     request: ThreadRefreshEnvRequest,
     app_handle: AppHandle,
-    _app_state: State<'_, crate::app_state::AppState>,
+    app_state: State<'_, crate::app_state::AppState>,
     amp_sessions: State<'_, AmpSessionMap>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<ThreadInfo, String> {
@@ -404,9 +479,9 @@ pub async fn thread_refresh_env(
     let db = db.as_ref().ok_or("Database not available")?;
 
     // Get thread and session info
-    let thread_session = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>, Option<i64>)>(
+    let thread_session = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>, Option<i64>, Option<String>)>(
         "SELECT t.id, t.session_id, t.context, t.agent_mode, t.toolbox_snapshot, 
-                t.created_at, t.updated_at, t.archived_at, s.profile_id
+                t.created_at, t.updated_at, t.archived_at, s.profile_id, s.amp_profile_id
          FROM threads t
          JOIN sessions s ON t.session_id = s.id
          WHERE t.id = ? AND t.archived_at IS NULL"
@@ -418,7 +493,9 @@ pub async fn thread_refresh_env(
     .ok_or_else(|| format!("Thread {} not found", request.thread_id))?;
 
     // Create new toolbox snapshot
-    let new_snapshot = create_toolbox_snapshot(thread_session.8, &profile_manager).await?;
+    let server = ServerOverride::from_snapshot(thread_session.4.as_deref());
+    let new_snapshot = create_toolbox_snapshot(thread_session.8, &request.thread_id, &profile_manager).await?;
+    let new_snapshot = thread_server::with_server(new_snapshot, server.as_ref());
     
     // Update thread with new snapshot
     sqlx::query("UPDATE threads SET toolbox_snapshot = ?, updated_at = (datetime('now', 'utc') || 'Z') WHERE id = ?")
@@ -428,32 +505,43 @@ pub async fn thread_refresh_env(
         .await
         .map_err(|e| format!("Failed to update thread: {}", e))?;
 
-    // If thread is active, restart it with new environment
+    // If thread is active, restart it with new environment. Its lock is held
+    // throughout, so nothing is sent to it mid-restart; if the restart fails
+    // the old process keeps running.
     {
-        let mut map = amp_sessions.lock().await;
-        if let Some(session) = map.remove(&request.thread_id) {
-            // Kill existing process
-            drop(session);
-            
+        if let Some(handle) = amp_sessions.get(&request.thread_id) {
+            let mut session = handle.lock().await;
+
             // Build new environment
             let mut merged_env = restore_thread_env(&Some(new_snapshot), thread_session.8, &thread_session.2, &thread_session.3)?;
+            apply_session_profile(&profile_manager, thread_session.9.as_deref(), &mut merged_env).await?;
+            if let Some(server) = &server {
+                server.apply(&mut merged_env);
+            }
+            apply_thread_model(db, &request.thread_id, &mut merged_env).await?;
             
             // Re-compose runtime environment
             let compose = crate::runtime_env::compose_runtime_env(&mut merged_env)
                 .map_err(|e| format!("Failed to compose runtime env: {}", e))?;
+            crate::env_isolation::isolate(db, &thread_session.1, thread_session.9.as_deref(), &mut merged_env).await;
+            crate::spawn_env_audit::record(db, &request.thread_id, SpawnReason::RefreshEnv, &merged_env).await;
 
             // Start new process
             let (cmd, args) = choose_amp_command(&merged_env);
             
-            let mut child = Command::new(&cmd)
+            let mut command = Command::new(&cmd);
+            command
                 .args(&args)
                 .env_clear()
                 .envs(&merged_env)
                 .stdin(std::process::Stdio::piped())
                 .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped());
+            crate::process_limits::apply(&app_handle, &mut command);
+            let mut child = command
                 .spawn()
                 .map_err(|e| format!("Failed to spawn amp process: {}", e))?;
+            let pid = child.id();
 
             let stdin = child.stdin.take().ok_or_else(|| "Failed to open stdin".to_string())?;
             let stdout = child.stdout.take().ok_or_else(|| "Failed to open stdout".to_string())?;
@@ -472,20 +560,21 @@ pub async fn thread_refresh_env(
                 }
             });
 
-            // Store new session
-            map.insert(request.thread_id.clone(), AmpSession {
+            // Replace the session; dropping the old one ends its process
+            *session = AmpSession {
                 child,
-                tx,
+                tx: tx.clone(),
                 toolbox_guard: compose.guard,
                 #[cfg(feature = "worktree-manager")]
                 worktree_guard: None, // Preserve existing worktree
-            });
+            };
+            drop(session);
 
             // Start output handling
-            spawn_output_handlers(app_handle.clone(), request.thread_id.clone(), stdout, stderr, db.clone()).await;
+            spawn_output_handlers(app_handle.clone(), request.thread_id.clone(), stdout, stderr, pid, db.clone(), crate::stream_quarantine::CliInfo::new(&cmd, &args)).await;
             
             // Send thread history to re-establish context
-            send_thread_history(&request.thread_id, &amp_sessions, db).await?;
+            crate::history_replay::replay(db, &request.thread_id, thread_session.9.as_deref(), app_state, &tx).await?;
         }
     }
 
@@ -513,6 +602,32 @@ pub async fn thread_refresh_env(
 
 // Helper functions
 
+/// Apply the Amp profile a session is bound to, if any
+async fn apply_session_profile(
+    profile_manager: &crate::profile_auth::ProfileManager,
+    amp_profile_id: Option<&str>,
+    env: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    match amp_profile_id {
+        Some(profile_id) => profile_manager.apply_profile_env(profile_id, env).await,
+        None => Ok(()),
+    }
+}
+
+/// Apply the model a thread was started with, if it overrode the profile default
+async fn apply_thread_model(db: &SqlitePool, thread_id: &str, env: &mut HashMap<String, String>) -> Result<(), String> {
+    let model: Option<String> = sqlx::query_scalar("SELECT model FROM threads WHERE id = ?")
+        .bind(thread_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to get thread model: {}", e))?
+        .flatten();
+    if let Some(model) = model {
+        env.insert("AMP_MODEL".to_string(), model);
+    }
+    Ok(())
+}
+
 async fn build_thread_env(
     app_state: &State<'_, crate::app_state::AppState>,
     _profile_id: Option<i64>,
@@ -547,6 +662,7 @@ async fn build_thread_env(
 
 async fn create_toolbox_snapshot(
     profile_id: Option<i64>,
+    thread_id: &str,
     profile_manager: &State<'_, crate::profile_auth::ProfileManager>,
 ) -> Result<String, String> {
     if let Some(id) = profile_id {
@@ -554,6 +670,10 @@ async fn create_toolbox_snapshot(
         if let Some(db) = db.as_ref() {
             let store = ToolboxProfileStore::new(db.clone());
             if let Some(profile) = store.get_profile(id).await.map_err(|e| e.to_string())? {
+                // Record what the profile's directories hold right now, for diffing runs later
+                if let Err(e) = crate::toolbox_snapshots::record_snapshot(db, Some(id), Some(thread_id), profile.paths.clone()).await {
+                    log::warn!("Failed to record toolbox snapshot for thread {}: {}", thread_id, e);
+                }
                 let snapshot = serde_json::json!({
                     "profile_id": id,
                     "name": profile.name,
@@ -626,106 +746,98 @@ async fn spawn_output_handlers(
     thread_id: String,
     stdout: tokio::process::ChildStdout,
     stderr: tokio::process::ChildStderr,
+    pid: Option<u32>,
     db: SqlitePool,
+    cli: crate::stream_quarantine::CliInfo,
 ) {
     // Spawn stdout handler
     let app_handle_stdout = app_handle.clone();
     let thread_id_stdout = thread_id.clone();
     let db_stdout = db.clone();
+    let program = cli.executable().to_string();
+    let mut validator = crate::stream_quarantine::StreamValidator::new("thread", &thread_id, cli);
     tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
+        let mut lines = crate::stream_quarantine::StreamLines::new(BufReader::new(stdout));
+        let mut stream_signals = crate::session_analytics::StreamSignals::default();
+        crate::session_priority::session_started(&thread_id_stdout);
+        crate::startup_reconciliation::process_started(Some(&db_stdout), &thread_id_stdout, "thread", pid, &program).await;
+        let limit_watch = crate::process_limits::watch(&app_handle_stdout, &thread_id_stdout, "thread", pid);
         while let Ok(Some(line)) = lines.next_line().await {
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&line) {
-                // Store message in database if it's a user or assistant message
-                if let Some(msg_type) = parsed.get("type").and_then(|v| v.as_str()) {
-                    match msg_type {
-                        "user" | "assistant" => {
-                            let message_id = Uuid::new_v4().to_string();
-                            let content = serde_json::to_string(&parsed).unwrap_or_else(|_| line.clone());
+            crate::session_activity::touch_session(&app_handle_stdout, &thread_id_stdout);
+            crate::chaos::maybe_kill(pid);
+            match validator.check(&line) {
+                Ok(parsed) => {
+                    stream_signals.observe(&parsed);
+                    // Store message in database if it's a user or assistant message
+                    if let Some(msg_type) = parsed.get("type").and_then(|v| v.as_str()) {
+                        match msg_type {
+                            "user" | "assistant" => {
+                                let message_id = Uuid::new_v4().to_string();
+                                let content = serde_json::to_string(&parsed).unwrap_or_else(|_| line.text.clone());
+                                let metrics = crate::message_metrics::observe(&thread_id_stdout, &parsed);
                             
-                            let _ = sqlx::query(
-                                "INSERT INTO messages (id, thread_id, role, content) VALUES (?, ?, ?, ?)"
-                            )
-                            .bind(&message_id)
-                            .bind(&thread_id_stdout)
-                            .bind(msg_type)
-                            .bind(&content)
-                            .execute(&db_stdout)
-                            .await;
+                                let _ = crate::message_content::insert_message_with_metrics(&db_stdout, &message_id, &thread_id_stdout, msg_type, &content, &metrics).await;
+                            }
+                            "result" => {
+                                if let Err(e) = crate::message_metrics::finish_turn(&db_stdout, &thread_id_stdout, &parsed).await {
+                                    log::warn!("{}", e);
+                                }
+                            }
+                            _ => {}
                         }
-                        _ => {}
                     }
-                }
                 
-                let _ = app_handle_stdout.emit("thread_stream", serde_json::json!({
-                    "thread_id": thread_id_stdout,
-                    "event": parsed,
-                    "timestamp": chrono::Utc::now().timestamp_millis()
-                }));
-            } else {
-                let _ = app_handle_stdout.emit("thread_stream", serde_json::json!({
-                    "thread_id": thread_id_stdout,
-                    "event": { "type": "error_output", "data": { "content": line } },
-                    "timestamp": chrono::Utc::now().timestamp_millis()
-                }));
+                    crate::script_hooks::dispatch_tool_use_hooks(&app_handle_stdout, &thread_id_stdout, &parsed);
+                    crate::file_locks::observe_stream_event(&app_handle_stdout, &thread_id_stdout, &parsed);
+                    crate::watch_mode::observe_stream_event(&app_handle_stdout, &thread_id_stdout, &parsed);
+                    crate::session_priority::observe_stream_event(&app_handle_stdout, &thread_id_stdout, &parsed);
+                    crate::stream_buffer::emit_buffered(&app_handle_stdout, "thread_stream", &thread_id_stdout, serde_json::json!({
+                        "thread_id": thread_id_stdout,
+                        "event": parsed,
+                        "timestamp": chrono::Utc::now().timestamp_millis()
+                    }));
+                }
+                Err(quarantined) => {
+                    quarantined.store_logged(Some(&db_stdout)).await;
+                    crate::session_log::record_quarantined(&app_handle_stdout, &quarantined);
+                    crate::stream_buffer::emit_buffered(&app_handle_stdout, "thread_stream", &thread_id_stdout, serde_json::json!({
+                        "thread_id": thread_id_stdout,
+                        "event": quarantined.error_output(),
+                        "timestamp": chrono::Utc::now().timestamp_millis()
+                    }));
+                }
             }
         }
-        let _ = app_handle_stdout.emit("thread_stream", serde_json::json!({
+        crate::stream_buffer::emit_buffered(&app_handle_stdout, "thread_stream", &thread_id_stdout, serde_json::json!({
             "thread_id": thread_id_stdout,
             "event": { "type": "result", "data": { "ended": true } },
             "timestamp": chrono::Utc::now().timestamp_millis()
         }));
+        limit_watch.abort();
+        crate::stream_buffer::mark_stream_ended(&app_handle_stdout, &thread_id_stdout);
+        crate::session_priority::session_ended(&thread_id_stdout);
+        crate::startup_reconciliation::process_ended(Some(&db_stdout), &thread_id_stdout, pid).await;
+        crate::file_locks::release_session_locks(&app_handle_stdout, &thread_id_stdout);
+        crate::message_metrics::end_thread(&thread_id_stdout);
+        crate::session_analytics::spawn_completion(app_handle_stdout.clone(), thread_id_stdout.clone(), "thread", stream_signals);
     });
 
     // Spawn stderr handler
     let app_handle_stderr = app_handle.clone();
     let thread_id_stderr = thread_id.clone();
     tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
+        let mut lines = crate::stream_quarantine::StreamLines::new(BufReader::new(stderr));
         while let Ok(Some(line)) = lines.next_line().await {
-            let _ = app_handle_stderr.emit("thread_stream", serde_json::json!({
+            crate::session_activity::touch_session(&app_handle_stderr, &thread_id_stderr);
+            crate::stream_buffer::emit_buffered(&app_handle_stderr, "thread_stream", &thread_id_stderr, serde_json::json!({
                 "thread_id": thread_id_stderr,
-                "event": { "type": "error_output", "data": { "content": line } },
+                "event": { "type": "error_output", "data": { "content": line.text } },
                 "timestamp": chrono::Utc::now().timestamp_millis()
             }));
         }
     });
 }
 
-async fn send_thread_history(
-    thread_id: &str,
-    amp_sessions: &State<'_, AmpSessionMap>,
-    db: &SqlitePool,
-) -> Result<(), String> {
-    // Get thread history from database
-    let messages = sqlx::query_as::<_, (String, String, String)>(
-        "SELECT role, content, created_at FROM messages 
-         WHERE thread_id = ? ORDER BY created_at ASC"
-    )
-    .bind(thread_id)
-    .fetch_all(db)
-    .await
-    .map_err(|e| format!("Failed to get thread history: {}", e))?;
-
-    if messages.is_empty() {
-        return Ok(());
-    }
-
-    // Send history to Amp process
-    let map = amp_sessions.lock().await;
-    if let Some(session) = map.get(thread_id) {
-        for (_role, content, _created_at) in messages {
-            if let Ok(parsed_content) = serde_json::from_str::<serde_json::Value>(&content) {
-                let _ = session.tx.send(parsed_content.to_string());
-            }
-        }
-    }
-
-    Ok(())
-}
-
 // Additional helper commands for managing sessions and threads
 
 /// List all sessions with optional profile filter
@@ -738,16 +850,16 @@ pub async fn list_sessions(
     let db = db.as_ref().ok_or("Database not available")?;
 
     let sessions = if let Some(pid) = profile_id {
-        sqlx::query_as::<_, (String, Option<String>, Option<i64>, String, String)>(
-            "SELECT id, title, profile_id, created_at, updated_at FROM sessions WHERE profile_id = ? ORDER BY updated_at DESC"
+        sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>, String, String)>(
+            "SELECT id, title, profile_id, amp_profile_id, created_at, updated_at FROM sessions WHERE profile_id = ? ORDER BY updated_at DESC"
         )
         .bind(pid)
         .fetch_all(db)
         .await
         .map_err(|e| format!("Failed to list sessions: {}", e))?
     } else {
-        sqlx::query_as::<_, (String, Option<String>, Option<i64>, String, String)>(
-            "SELECT id, title, profile_id, created_at, updated_at FROM sessions ORDER BY updated_at DESC"
+        sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>, String, String)>(
+            "SELECT id, title, profile_id, amp_profile_id, created_at, updated_at FROM sessions ORDER BY updated_at DESC"
         )
         .fetch_all(db)
         .await
@@ -756,10 +868,12 @@ pub async fn list_sessions(
 
     let session_infos: Vec<SessionInfo> = sessions
         .into_iter()
-        .map(|(id, title, profile_id, created_at, updated_at)| SessionInfo {
+        .map(|(id, title, profile_id, amp_profile_id, created_at, updated_at)| SessionInfo {
+            short_code: crate::session_codes::code_for(&id),
             id,
             title,
             profile_id,
+            amp_profile_id,
             created_at,
             updated_at,
         })
@@ -768,51 +882,55 @@ pub async fn list_sessions(
     Ok(session_infos)
 }
 
+const THREAD_LIST: ListSpec = ListSpec {
+    fields: &[
+        ("id", "id"),
+        ("context", "context"),
+        ("agent_mode", "agent_mode"),
+        ("created_at", "created_at"),
+        ("updated_at", "updated_at"),
+        ("archived_at", "archived_at"),
+    ],
+    default_sort: &[("created_at", SortDirection::Asc), ("id", SortDirection::Asc)],
+};
+
 /// List all threads in a session
 #[tauri::command]
 pub async fn list_threads(
     session_id: String,
     include_archived: Option<bool>,
+    query: Option<ListQuery>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
-) -> Result<Vec<ThreadInfo>, String> {
+) -> Result<ListResult<ThreadInfo>, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
     let db = profile_manager.db_pool.read().await;
     let db = db.as_ref().ok_or("Database not available")?;
 
-    let threads = if include_archived.unwrap_or(false) {
-        sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>)>(
-            "SELECT id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at 
-             FROM threads WHERE session_id = ? ORDER BY created_at ASC"
-        )
-        .bind(&session_id)
-        .fetch_all(db)
-        .await
-        .map_err(|e| format!("Failed to list threads: {}", e))?
+    let conditions: &[&str] = if include_archived.unwrap_or(false) {
+        &["session_id = ?"]
     } else {
-        sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, String, Option<String>)>(
-            "SELECT id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at 
-             FROM threads WHERE session_id = ? AND archived_at IS NULL ORDER BY created_at ASC"
-        )
-        .bind(&session_id)
-        .fetch_all(db)
-        .await
-        .map_err(|e| format!("Failed to list threads: {}", e))?
+        &["session_id = ?", "archived_at IS NULL"]
     };
-
-    let thread_infos: Vec<ThreadInfo> = threads
-        .into_iter()
-        .map(|(id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at)| ThreadInfo {
-            id,
-            session_id,
-            context,
-            agent_mode,
-            toolbox_snapshot,
-            created_at,
-            updated_at,
-            archived_at,
-        })
-        .collect();
-
-    Ok(thread_infos)
+    let threads = fetch_page::<(String, String, String, Option<String>, Option<String>, String, String, Option<String>)>(
+        db,
+        "id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at",
+        "threads",
+        (conditions, &[session_id]),
+        &query.unwrap_or_default(),
+        &THREAD_LIST,
+    )
+    .await?;
+
+    Ok(threads.map(|(id, session_id, context, agent_mode, toolbox_snapshot, created_at, updated_at, archived_at)| ThreadInfo {
+        id,
+        session_id,
+        context,
+        agent_mode,
+        toolbox_snapshot,
+        created_at,
+        updated_at,
+        archived_at,
+    }))
 }
 
 /// Send a message to a thread
@@ -822,39 +940,87 @@ pub async fn thread_send_message(
     message: String,
     amp_sessions: State<'_, AmpSessionMap>,
     profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    activity: State<'_, crate::session_activity::ActivityTracker>,
 ) -> Result<(), String> {
-    let map = amp_sessions.lock().await;
-    let session = map.get(&thread_id).ok_or_else(|| format!("Thread {} not found or not active", thread_id))?;
-
-    let payload = serde_json::json!({
-        "type": "user",
-        "message": {
-            "role": "user",
-            "content": [{ "type": "text", "text": message }]
-        }
-    });
+    send_user_message(&thread_id, &message, &amp_sessions, &profile_manager, &activity).await
+}
+
+/// Store a user message and send it to a thread's process
+pub(crate) async fn send_user_message(
+    thread_id: &str,
+    message: &str,
+    amp_sessions: &AmpSessionMap,
+    profile_manager: &crate::profile_auth::ProfileManager,
+    activity: &crate::session_activity::ActivityTracker,
+) -> Result<(), String> {
+    let session = amp_sessions.get(thread_id).ok_or_else(|| format!("Thread {} not found or not active", thread_id))?;
+    // Only this thread is locked, and not while the message is stored
+    let tx = {
+        let session = session.lock().await;
+        crate::process_suspend::resume_session(&session, activity, thread_id)?;
+        session.tx.clone()
+    };
+    activity.touch(thread_id, chrono::Utc::now().timestamp_millis());
+    crate::session_priority::input_received(thread_id);
+
+    let payload = unified_core::AmpHarness::user_message(message);
 
     // Store message in database
     let db = profile_manager.db_pool.read().await;
     if let Some(db) = db.as_ref() {
         let message_id = Uuid::new_v4().to_string();
-        let _ = sqlx::query(
-            "INSERT INTO messages (id, thread_id, role, content) VALUES (?, ?, ?, ?)"
-        )
-        .bind(&message_id)
-        .bind(&thread_id)
-        .bind("user")
-        .bind(&payload.to_string())
-        .execute(db)
-        .await;
+        let metrics = crate::message_metrics::start_turn(thread_id, &message_id);
+        let _ = crate::message_content::insert_message_with_metrics(db, &message_id, thread_id, "user", &payload.to_string(), &metrics).await;
     }
 
     // Send via writer task
-    session.tx.send(payload.to_string()).map_err(|e| e.to_string())?;
+    tx.send(payload.to_string()).map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadInterruptResult {
+    pub thread_id: String,
+    pub method: crate::process_suspend::InterruptMethod,
+    /// The partial message marked interrupted
+    pub message_id: Option<String>,
+    pub status: unified_core::domain::SessionStatus,
+}
+
+/// Stop the response a thread is generating without ending its process
+#[tauri::command]
+pub async fn thread_interrupt(
+    thread_id: String,
+    app_handle: AppHandle,
+    amp_sessions: State<'_, AmpSessionMap>,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+    activity: State<'_, crate::session_activity::ActivityTracker>,
+) -> Result<ThreadInterruptResult, String> {
+    let method = {
+        let session = amp_sessions.get(&thread_id).ok_or_else(|| format!("Thread {} not found or not active", thread_id))?;
+        let session = session.lock().await;
+        if crate::message_metrics::open_turn(&thread_id).is_none() {
+            return Err(format!("Thread {} is not generating a response", thread_id));
+        }
+        // A stopped process would only see the interrupt once continued
+        crate::process_suspend::resume_session(&session, &activity, &thread_id)?;
+        crate::process_suspend::interrupt_session(&session, &thread_id)?
+    };
+
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let message_id = crate::message_metrics::interrupt_turn(db, &thread_id).await?;
+    crate::session_priority::awaiting_input(&app_handle, &thread_id);
+    crate::stream_buffer::emit_buffered(&app_handle, "thread_stream", &thread_id, serde_json::json!({
+        "thread_id": thread_id,
+        "event": { "type": "interrupted", "data": { "message_id": message_id } },
+        "timestamp": chrono::Utc::now().timestamp_millis()
+    }));
+
+    Ok(ThreadInterruptResult { thread_id, method, message_id, status: unified_core::domain::SessionStatus::AwaitingInput })
+}
+
 /// Archive a thread (soft delete)
 #[tauri::command]
 pub async fn thread_archive(
@@ -873,16 +1039,32 @@ pub async fn thread_archive(
         .map_err(|e| format!("Failed to archive thread: {}", e))?;
 
     // Stop the process if it's running
-    {
-        let mut map = amp_sessions.lock().await;
-        if let Some(session) = map.remove(&thread_id) {
-            drop(session); // This will kill the process
-        }
+    if let Some(session) = amp_sessions.remove(&thread_id) {
+        drop(session); // This will kill the process
     }
 
     Ok(())
 }
 
+/// Unarchive a thread, restoring its messages if they were compressed
+#[tauri::command]
+pub async fn thread_unarchive(
+    thread_id: String,
+    profile_manager: State<'_, crate::profile_auth::ProfileManager>,
+) -> Result<(), String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    crate::archive_compression::restore_thread(db, &thread_id).await?;
+    sqlx::query("UPDATE threads SET archived_at = NULL WHERE id = ?")
+        .bind(&thread_id)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to unarchive thread: {}", e))?;
+
+    Ok(())
+}
+
 /// Get thread message history
 #[tauri::command]
 pub async fn get_thread_history(
@@ -893,29 +1075,45 @@ pub async fn get_thread_history(
 ) -> Result<Vec<serde_json::Value>, String> {
     let db = profile_manager.db_pool.read().await;
     let db = db.as_ref().ok_or("Database not available")?;
+    thread_history(db, &thread_id, limit.unwrap_or(100), offset.unwrap_or(0)).await
+}
 
-    let limit = limit.unwrap_or(100);
-    let offset = offset.unwrap_or(0);
+/// A page of a thread's messages, oldest first, with stream events parsed back to JSON
+pub async fn thread_history(db: &SqlitePool, thread_id: &str, limit: i64, offset: i64) -> Result<Vec<serde_json::Value>, String> {
+    // A compressed archive holds the oldest messages; page through it before the table
+    let archived = crate::archive_compression::archived_messages(db, thread_id).await?.unwrap_or_default();
+    let archived_len = archived.len() as i64;
+    let mut messages: Vec<(String, String, String, Option<String>, String, Option<String>)> = archived
+        .into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .map(|m| (m.id, m.role, m.content, None, m.created_at, None))
+        .collect();
 
-    let messages = sqlx::query_as::<_, (String, String, String, String)>(
-        "SELECT id, role, content, created_at FROM messages 
-         WHERE thread_id = ? ORDER BY created_at ASC LIMIT ? OFFSET ?"
-    )
-    .bind(&thread_id)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(db)
-    .await
-    .map_err(|e| format!("Failed to get thread history: {}", e))?;
+    let table_limit = limit - messages.len() as i64;
+    if table_limit > 0 {
+        messages.extend(sqlx::query_as::<_, (String, String, String, Option<String>, String, Option<String>)>(
+            "SELECT id, role, content, content_ref, created_at, interrupted_at FROM messages 
+             WHERE thread_id = ? ORDER BY created_at ASC LIMIT ? OFFSET ?"
+        )
+        .bind(thread_id)
+        .bind(table_limit)
+        .bind((offset - archived_len).max(0))
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to get thread history: {}", e))?);
+    }
 
     let history: Vec<serde_json::Value> = messages
         .into_iter()
-        .map(|(id, role, content, created_at)| {
+        .map(|(id, role, content, content_ref, created_at, interrupted_at)| {
+            let content = crate::message_content::resolve_content(content, content_ref);
             serde_json::json!({
                 "id": id,
                 "role": role,
                 "content": serde_json::from_str::<serde_json::Value>(&content).unwrap_or_else(|_| serde_json::Value::String(content)),
-                "created_at": created_at
+                "created_at": created_at,
+                "interrupted_at": interrupted_at
             })
         })
         .collect();
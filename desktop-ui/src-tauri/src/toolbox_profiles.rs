@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{Row, SqlitePool, FromRow};
 
+use crate::list_query::{fetch_page, ListQuery, ListResult, ListSpec, SortDirection};
+
+const PROFILE_LIST: ListSpec = ListSpec {
+    fields: &[("id", "id"), ("name", "name"), ("created_at", "created_at")],
+    default_sort: &[("created_at", SortDirection::Desc), ("id", SortDirection::Desc)],
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct ToolboxProfile {
     pub id: i64,
@@ -34,20 +41,14 @@ impl ToolboxProfileStore {
         Self { db }
     }
 
-    pub async fn list_profiles(&self) -> Result<Vec<ToolboxProfile>, sqlx::Error> {
-        let profiles = sqlx::query_as::<_, ToolboxProfile>(
-            "SELECT id, name, created_at FROM toolbox_profiles ORDER BY created_at DESC"
-        )
-        .fetch_all(&self.db)
-        .await?;
-
-        let mut result = Vec::new();
-        for mut profile in profiles {
-            profile.paths = self.get_profile_paths(profile.id).await?;
-            result.push(profile);
+    /// One page of profiles, each with its paths
+    pub async fn query_profiles(&self, query: &ListQuery) -> Result<ListResult<ToolboxProfile>, String> {
+        let mut page: ListResult<ToolboxProfile> =
+            fetch_page(&self.db, "id, name, created_at", "toolbox_profiles", (&[], &[]), query, &PROFILE_LIST).await?;
+        for profile in &mut page.items {
+            profile.paths = self.get_profile_paths(profile.id).await.map_err(|e| e.to_string())?;
         }
-
-        Ok(result)
+        Ok(page)
     }
 
     pub async fn get_profile(&self, id: i64) -> Result<Option<ToolboxProfile>, sqlx::Error> {
@@ -227,35 +228,11 @@ impl ToolboxProfileStore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sqlx::{SqlitePool, sqlite::SqliteConnectOptions, ConnectOptions};
-    use std::str::FromStr;
-
-    async fn setup_test_db() -> SqlitePool {
-        let options = SqliteConnectOptions::from_str(":memory:")
-            .unwrap()
-            .create_if_missing(true)
-            .disable_statement_logging();
-        
-        let pool = SqlitePool::connect_with(options).await.unwrap();
-        
-        // Run migrations
-        let migrations = vec![
-            include_str!("../migrations/001_initial.sql"),
-            include_str!("../migrations/002_chat_sessions.sql"),
-            include_str!("../migrations/003_chat_sessions_agent_mode.sql"),
-            include_str!("../migrations/004_add_toolbox_profiles.sql"),
-        ];
-        
-        for migration_sql in migrations {
-            sqlx::query(migration_sql).execute(&pool).await.unwrap();
-        }
-        
-        pool
-    }
+    use crate::schema::test_pool;
 
     #[tokio::test]
     async fn test_create_profile() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         let request = CreateToolboxProfileRequest {
@@ -272,7 +249,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_list_profiles() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         // Create two profiles
@@ -286,7 +263,7 @@ mod tests {
             paths: vec!["/path2".to_string(), "/path3".to_string()],
         }).await.unwrap();
         
-        let profiles = store.list_profiles().await.unwrap();
+        let profiles = store.query_profiles(&ListQuery::default()).await.unwrap().items;
         
         assert_eq!(profiles.len(), 2);
         assert!(profiles.iter().any(|p| p.name == "Profile 1" && p.paths == vec!["/path1"]));
@@ -295,7 +272,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_profile() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         let created = store.create_profile(CreateToolboxProfileRequest {
@@ -312,7 +289,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_profile() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         let created = store.create_profile(CreateToolboxProfileRequest {
@@ -332,7 +309,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_delete_profile() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         let created = store.create_profile(CreateToolboxProfileRequest {
@@ -349,7 +326,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_profile_by_name() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         store.create_profile(CreateToolboxProfileRequest {
@@ -366,7 +343,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_path_ordering() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool);
         
         let created = store.create_profile(CreateToolboxProfileRequest {
@@ -389,7 +366,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_migrate_single_paths() {
-        let pool = setup_test_db().await;
+        let pool = test_pool().await;
         let store = ToolboxProfileStore::new(pool.clone());
         
         // Insert some test data into chat_sessions with toolbox_path
@@ -402,7 +379,7 @@ mod tests {
         store.migrate_single_paths().await.unwrap();
         
         // Check that profiles were created
-        let profiles = store.list_profiles().await.unwrap();
+        let profiles = store.query_profiles(&ListQuery::default()).await.unwrap().items;
         assert_eq!(profiles.len(), 2);
         
         // Check that one of the profiles has the right path
@@ -6,6 +6,8 @@ use std::fs;
 use std::io;
 use std::os::unix;
 use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
 use walkdir::WalkDir;
 use log::{warn, debug};
 
@@ -32,9 +34,38 @@ pub struct ToolboxGuard {
     keep: bool,
 }
 
+/// A resolved directory and how many guards still use it. Threads of one session
+/// resolve the same toolboxes to the same directory, so it is only removed when
+/// the last of them lets go, and never if any of them asked to keep it.
+#[derive(Debug)]
+struct Resolution {
+    users: usize,
+    keep: bool,
+    manifest: ToolboxManifest,
+}
+
+static RESOLUTIONS: Lazy<Mutex<HashMap<PathBuf, Resolution>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn resolutions() -> MutexGuard<'static, HashMap<PathBuf, Resolution>> {
+    RESOLUTIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Guards currently holding the resolution at `root`
+pub fn resolution_users(root: &Path) -> usize {
+    resolutions().get(root).map_or(0, |r| r.users)
+}
+
 impl Drop for ToolboxGuard {
     fn drop(&mut self) {
-        if !self.keep {
+        // Removal happens under the lock so a concurrent resolve of the same
+        // toolboxes either reuses the directory or rebuilds it after it is gone
+        let mut resolutions = resolutions();
+        if let Some(resolution) = resolutions.get_mut(&self.root).filter(|r| r.users > 1) {
+            resolution.users -= 1;
+            return;
+        }
+        let keep = resolutions.remove(&self.root).is_some_and(|r| r.keep);
+        if !keep && !self.keep {
             let _ = fs::remove_dir_all(&self.root);
         }
     }
@@ -173,6 +204,15 @@ fn link_or_copy_file(src: &Path, dst: &Path, copy_mode: bool) -> io::Result<()>
 }
 
 pub fn resolve_toolboxes(roots: &[PathBuf], keep_artifacts: bool) -> Result<ResolvedToolbox> {
+    let base = crate::platform::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".amp-orchestra")
+        .join("runtime_toolboxes");
+    resolve_toolboxes_in(&base, roots, keep_artifacts)
+}
+
+/// Resolve into `base` rather than the shared runtime directory
+pub fn resolve_toolboxes_in(base: &Path, roots: &[PathBuf], keep_artifacts: bool) -> Result<ResolvedToolbox> {
     if roots.is_empty() {
         return Err(anyhow!("no toolbox roots provided"));
     }
@@ -182,17 +222,28 @@ pub fn resolve_toolboxes(roots: &[PathBuf], keep_artifacts: bool) -> Result<Reso
         .map(|p| fs::canonicalize(p).map_err(|e| anyhow!("canonicalize {:?}: {}", p, e)))
         .collect::<Result<_>>()?;
 
-    let base = dirs::home_dir()
-        .or_else(|| std::env::var("HOME").ok().map(PathBuf::from))
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".amp-orchestra")
-        .join("runtime_toolboxes");
-    ensure_dir(&base)?;
+    ensure_dir(base)?;
 
     let digest = hash_set(&canon);
     let root_dir = base.join(&digest);
     let bin_dir = root_dir.join("bin");
 
+    // Held until the resolution is recorded so concurrent resolves of the same
+    // toolboxes never build into one directory at once
+    let mut resolutions = resolutions();
+    if let Some(resolution) = resolutions.get_mut(&root_dir) {
+        if bin_dir.exists() {
+            resolution.users += 1;
+            resolution.keep |= keep_artifacts;
+            return Ok(ResolvedToolbox {
+                root: root_dir.clone(),
+                bin: bin_dir,
+                manifest: resolution.manifest.clone(),
+                guard: Some(ToolboxGuard { root: root_dir, keep: keep_artifacts }),
+            });
+        }
+    }
+
     if root_dir.exists() {
         // Clean existing to ensure deterministic merge
         let _ = fs::remove_dir_all(&root_dir);
@@ -247,6 +298,12 @@ pub fn resolve_toolboxes(roots: &[PathBuf], keep_artifacts: bool) -> Result<Reso
         bin_entries: bin_entries.clone(),
     };
 
+    // Guards of a directory that was removed from under them still count as users
+    let resolution = resolutions.entry(root_dir.clone()).or_insert(Resolution { users: 0, keep: false, manifest: manifest.clone() });
+    resolution.users += 1;
+    resolution.keep |= keep_artifacts;
+    resolution.manifest = manifest.clone();
+
     Ok(ResolvedToolbox { root: root_dir, bin: bin_dir, manifest, guard: Some(guard) })
 }
 
@@ -498,4 +498,79 @@ mod tests {
             assert_eq!(max_bytes, expected_mb * 1024 * 1024);
         }
     }
+
+    mod shared_guards {
+        use super::create_toolbox_with_tools;
+        use crate::toolbox_resolver::{resolution_users, resolve_toolboxes_in};
+        use std::sync::{Arc, Barrier};
+
+        #[test]
+        fn last_guard_removes_shared_resolution() {
+            let tmp = tempfile::tempdir().unwrap();
+            let base = tmp.path().join("runtime");
+            let toolbox = create_toolbox_with_tools(tmp.path(), "shared", &[("tool", "content")]);
+
+            let mut first = resolve_toolboxes_in(&base, &[toolbox.clone()], false).unwrap();
+            let mut second = resolve_toolboxes_in(&base, &[toolbox], false).unwrap();
+            assert_eq!(first.root, second.root);
+            assert_eq!(resolution_users(&first.root), 2);
+
+            drop(first.take_guard());
+            assert!(second.bin.join("tool").exists(), "dropping one guard must not delete a shared resolution");
+            assert_eq!(resolution_users(&second.root), 1);
+
+            drop(second.take_guard());
+            assert!(!second.root.exists());
+            assert_eq!(resolution_users(&second.root), 0);
+        }
+
+        #[test]
+        fn kept_resolution_survives_other_guards() {
+            let tmp = tempfile::tempdir().unwrap();
+            let base = tmp.path().join("runtime");
+            let toolbox = create_toolbox_with_tools(tmp.path(), "kept", &[("tool", "content")]);
+
+            let mut kept = resolve_toolboxes_in(&base, &[toolbox.clone()], true).unwrap();
+            let mut temporary = resolve_toolboxes_in(&base, &[toolbox], false).unwrap();
+            drop(kept.take_guard());
+            drop(temporary.take_guard());
+            assert!(temporary.bin.join("tool").exists());
+        }
+
+        #[test]
+        fn concurrent_create_and_drop() {
+            const THREADS: usize = 8;
+            let tmp = tempfile::tempdir().unwrap();
+            let base = tmp.path().join("runtime");
+            let toolbox = create_toolbox_with_tools(tmp.path(), "concurrent", &[("tool", "content")]);
+
+            // One guard is held throughout, so the directory must survive every other drop
+            let mut holder = resolve_toolboxes_in(&base, &[toolbox.clone()], false).unwrap();
+            let barrier = Arc::new(Barrier::new(THREADS));
+            let handles: Vec<_> = (0..THREADS)
+                .map(|i| {
+                    let (base, toolbox, barrier) = (base.clone(), toolbox.clone(), barrier.clone());
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        for _ in 0..20 {
+                            let mut resolved = resolve_toolboxes_in(&base, &[toolbox.clone()], false).unwrap();
+                            assert!(resolved.bin.join("tool").exists());
+                            if i % 2 == 0 {
+                                std::thread::yield_now();
+                            }
+                            drop(resolved.take_guard());
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            assert!(holder.bin.join("tool").exists());
+            assert_eq!(resolution_users(&holder.root), 1);
+            drop(holder.take_guard());
+            assert!(!holder.root.exists());
+        }
+    }
 }
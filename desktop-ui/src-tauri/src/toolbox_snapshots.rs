@@ -0,0 +1,277 @@
+//! Content digests of toolboxes over time
+//!
+//! A toolbox profile names directories, not their contents, so the same profile
+//! can hand an agent different tools from one run to the next. Each time a thread
+//! is started with a toolbox profile the tools it resolves to are hashed and
+//! recorded, and `diff_toolbox_snapshots` reports which tools were added, removed
+//! or changed between two of those records.
+//!
+//! Tools are the files under each root's `bin`, merged last-wins by name like
+//! `toolbox_resolver` does, so a digest describes what the agent actually ran.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::State;
+use walkdir::WalkDir;
+
+use crate::profile_auth::ProfileManager;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolEntry {
+    /// Path relative to the toolbox's `bin`
+    pub name: String,
+    /// Toolbox root the tool came from
+    pub source: String,
+    pub hash: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolboxDigest {
+    pub digest: String,
+    pub tools: Vec<ToolEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolboxSnapshot {
+    pub id: i64,
+    pub toolbox_profile_id: Option<i64>,
+    pub thread_id: Option<String>,
+    pub digest: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolChange {
+    pub before: ToolEntry,
+    pub after: ToolEntry,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolboxDiff {
+    pub added: Vec<ToolEntry>,
+    pub removed: Vec<ToolEntry>,
+    pub changed: Vec<ToolChange>,
+}
+
+/// Hash the tools `paths` resolve to. Missing roots contribute nothing.
+pub fn digest_paths(paths: &[String]) -> ToolboxDigest {
+    let mut tools: BTreeMap<String, ToolEntry> = BTreeMap::new();
+    for root in paths {
+        let bin = Path::new(root).join("bin");
+        for entry in WalkDir::new(&bin).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(content) = fs::read(entry.path()) else {
+                continue;
+            };
+            let name = entry.path().strip_prefix(&bin).unwrap_or(entry.path()).to_string_lossy().to_string();
+            tools.insert(
+                name.clone(),
+                ToolEntry {
+                    name,
+                    source: root.clone(),
+                    hash: blake3::hash(&content).to_hex().to_string(),
+                    size: content.len() as u64,
+                },
+            );
+        }
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    for tool in tools.values() {
+        hasher.update(tool.name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(tool.hash.as_bytes());
+    }
+    ToolboxDigest { digest: hasher.finalize().to_hex().to_string(), tools: tools.into_values().collect() }
+}
+
+/// Tools added, removed or changed going from `a` to `b`. A tool that moved to
+/// another root with the same content is not a change.
+pub fn diff(a: &[ToolEntry], b: &[ToolEntry]) -> ToolboxDiff {
+    let before: BTreeMap<&str, &ToolEntry> = a.iter().map(|t| (t.name.as_str(), t)).collect();
+    let after: BTreeMap<&str, &ToolEntry> = b.iter().map(|t| (t.name.as_str(), t)).collect();
+    let mut result = ToolboxDiff::default();
+    for (name, tool) in &after {
+        match before.get(name) {
+            None => result.added.push((*tool).clone()),
+            Some(old) if old.hash != tool.hash => {
+                result.changed.push(ToolChange { before: (*old).clone(), after: (*tool).clone() })
+            }
+            Some(_) => {}
+        }
+    }
+    result.removed = before
+        .iter()
+        .filter(|(name, _)| !after.contains_key(*name))
+        .map(|(_, tool)| (*tool).clone())
+        .collect();
+    result
+}
+
+/// Hash a toolbox profile's contents and record that `thread_id` used them
+pub async fn record_snapshot(
+    db: &SqlitePool,
+    toolbox_profile_id: Option<i64>,
+    thread_id: Option<&str>,
+    paths: Vec<String>,
+) -> Result<ToolboxSnapshot, String> {
+    let digest = tokio::task::spawn_blocking(move || digest_paths(&paths))
+        .await
+        .map_err(|e| format!("Failed to hash toolbox: {}", e))?;
+    let tools = serde_json::to_string(&digest.tools).map_err(|e| e.to_string())?;
+
+    sqlx::query("INSERT OR IGNORE INTO toolbox_digests (digest, tools) VALUES (?, ?)")
+        .bind(&digest.digest)
+        .bind(&tools)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to store toolbox digest: {}", e))?;
+    sqlx::query_as::<_, (i64, Option<i64>, Option<String>, String, String)>(
+        "INSERT INTO toolbox_snapshots (toolbox_profile_id, thread_id, digest) VALUES (?, ?, ?)
+         RETURNING id, toolbox_profile_id, thread_id, digest, created_at",
+    )
+    .bind(toolbox_profile_id)
+    .bind(thread_id)
+    .bind(&digest.digest)
+    .fetch_one(db)
+    .await
+    .map(|(id, toolbox_profile_id, thread_id, digest, created_at)| ToolboxSnapshot {
+        id,
+        toolbox_profile_id,
+        thread_id,
+        digest,
+        created_at,
+    })
+    .map_err(|e| format!("Failed to record toolbox snapshot: {}", e))
+}
+
+async fn snapshot_tools(db: &SqlitePool, snapshot_id: i64) -> Result<Vec<ToolEntry>, String> {
+    let tools: String = sqlx::query_scalar(
+        "SELECT d.tools FROM toolbox_snapshots s JOIN toolbox_digests d ON d.digest = s.digest WHERE s.id = ?",
+    )
+    .bind(snapshot_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| format!("Failed to read toolbox snapshot: {}", e))?
+    .ok_or_else(|| format!("Toolbox snapshot {} not found", snapshot_id))?;
+    serde_json::from_str(&tools).map_err(|e| format!("Corrupt toolbox snapshot {}: {}", snapshot_id, e))
+}
+
+pub async fn diff_snapshots(db: &SqlitePool, a: i64, b: i64) -> Result<ToolboxDiff, String> {
+    Ok(diff(&snapshot_tools(db, a).await?, &snapshot_tools(db, b).await?))
+}
+
+/// Snapshots newest first, optionally only those of one toolbox profile or thread
+#[tauri::command]
+pub async fn list_toolbox_snapshots(
+    toolbox_profile_id: Option<i64>,
+    thread_id: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Vec<ToolboxSnapshot>, String> {
+    let thread_id = thread_id.map(|id| crate::session_codes::resolve(&id));
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    sqlx::query_as::<_, (i64, Option<i64>, Option<String>, String, String)>(
+        "SELECT id, toolbox_profile_id, thread_id, digest, created_at FROM toolbox_snapshots
+         WHERE (?1 IS NULL OR toolbox_profile_id = ?1) AND (?2 IS NULL OR thread_id = ?2)
+         ORDER BY id DESC",
+    )
+    .bind(toolbox_profile_id)
+    .bind(thread_id)
+    .fetch_all(db)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .map(|(id, toolbox_profile_id, thread_id, digest, created_at)| ToolboxSnapshot {
+                id,
+                toolbox_profile_id,
+                thread_id,
+                digest,
+                created_at,
+            })
+            .collect()
+    })
+    .map_err(|e| format!("Failed to list toolbox snapshots: {}", e))
+}
+
+/// Tools added, removed or changed between snapshot `a` and the later snapshot `b`
+#[tauri::command]
+pub async fn diff_toolbox_snapshots(
+    a: i64,
+    b: i64,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<ToolboxDiff, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    diff_snapshots(db, a, b).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    fn write_tool(root: &Path, name: &str, content: &str) {
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(root.join("bin").join(name), content).unwrap();
+    }
+
+    #[test]
+    fn test_digest_merges_last_wins_and_diff() {
+        let temp = TempDir::new().unwrap();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        write_tool(&a, "lint", "v1");
+        write_tool(&a, "fmt", "fmt");
+        write_tool(&b, "lint", "override");
+        let paths = vec![a.to_string_lossy().to_string(), b.to_string_lossy().to_string()];
+
+        let first = digest_paths(&paths);
+        assert_eq!(first.tools.len(), 2);
+        let lint = first.tools.iter().find(|t| t.name == "lint").unwrap();
+        assert_eq!(lint.source, paths[1]);
+        assert_eq!(digest_paths(&paths), first);
+
+        write_tool(&b, "lint", "override v2");
+        fs::remove_file(a.join("bin/fmt")).unwrap();
+        write_tool(&a, "test", "test");
+        let second = digest_paths(&paths);
+        assert_ne!(second.digest, first.digest);
+
+        let changes = diff(&first.tools, &second.tools);
+        assert_eq!(changes.added.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), vec!["test"]);
+        assert_eq!(changes.removed.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), vec!["fmt"]);
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].after.name, "lint");
+        assert_eq!(diff(&second.tools, &second.tools), ToolboxDiff::default());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_share_digests_and_diff() {
+        let pool = test_pool().await;
+
+        let temp = TempDir::new().unwrap();
+        write_tool(temp.path(), "lint", "v1");
+        let paths = vec![temp.path().to_string_lossy().to_string()];
+
+        let first = record_snapshot(&pool, Some(1), Some("t1"), paths.clone()).await.unwrap();
+        let again = record_snapshot(&pool, Some(1), Some("t2"), paths.clone()).await.unwrap();
+        assert_eq!(first.digest, again.digest);
+        let digests: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM toolbox_digests").fetch_one(&pool).await.unwrap();
+        assert_eq!(digests, 1);
+
+        write_tool(temp.path(), "lint", "v2");
+        let later = record_snapshot(&pool, Some(1), Some("t3"), paths).await.unwrap();
+        let changes = diff_snapshots(&pool, first.id, later.id).await.unwrap();
+        assert_eq!(changes.changed.len(), 1);
+        assert!(diff_snapshots(&pool, first.id, again.id).await.unwrap().changed.is_empty());
+        assert!(diff_snapshots(&pool, first.id, 999).await.is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Several commands applied as one
+//!
+//! Some UI flows only make sense if every step succeeds, such as creating a
+//! profile, storing its token and activating it. `transact` runs a list of steps
+//! from a fixed set of commands inside one database transaction: if any step
+//! fails the transaction is rolled back and none of them take effect.
+//!
+//! A step is `{ "command": ..., "args": {...} }` with the arguments named as in
+//! Rust. A string argument of the form `$<step>.<field>` is replaced with that
+//! field of an earlier step's result, so later steps can use the id of a profile
+//! created earlier in the batch (`"$0.id"`).
+//!
+//! Changes outside the database, namely keychain tokens, the in-memory profile
+//! list and the active profile, are made only once the transaction commits.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqliteConnection;
+use tauri::State;
+use tokio::sync::RwLock;
+
+use crate::keychain_auth::{KeychainAuth, TokenType};
+use crate::profile_auth::{self, CreateProfileRequest, ProfileCtx, ProfileManager, ProfileRow};
+use crate::secret_providers::{self, SecretProviderKind};
+
+pub const MAX_STEPS: usize = 32;
+
+/// Commands that can run as a step
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", content = "args", rename_all = "snake_case")]
+pub enum TransactCommand {
+    ProfileCreate { profile: CreateProfileRequest },
+    StoreProfileToken { profile_id: String, token: String },
+    SaveProfileSecret { profile_id: String, env_key: String, provider: SecretProviderKind, reference: String },
+    ProfileActivate { id: String },
+}
+
+impl TransactCommand {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ProfileCreate { .. } => "profile_create",
+            Self::StoreProfileToken { .. } => "store_profile_token",
+            Self::SaveProfileSecret { .. } => "save_profile_secret",
+            Self::ProfileActivate { .. } => "profile_activate",
+        }
+    }
+}
+
+/// Work left for after commit
+#[derive(Debug, Clone)]
+enum Effect {
+    AddProfile(Box<ProfileRow>),
+    StoreToken { profile_id: String, token: String },
+    Activate(String),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransactResult {
+    /// One per step, in order
+    pub results: Vec<Value>,
+    /// Post-commit changes that could not be made, such as a keychain write
+    pub warnings: Vec<String>,
+}
+
+/// Replace `$<step>.<field>` strings with fields of earlier results
+fn resolve_refs(value: &mut Value, results: &[Value]) -> Result<(), String> {
+    match value {
+        Value::String(s) => {
+            let Some((step, path)) = s.strip_prefix('$').and_then(|r| r.split_once('.')) else {
+                return Ok(());
+            };
+            let Ok(step) = step.parse::<usize>() else {
+                return Ok(());
+            };
+            let result = results
+                .get(step)
+                .ok_or_else(|| format!("'{}' refers to step {} which has not run yet", s, step))?;
+            let pointer = format!("/{}", path.replace('.', "/"));
+            let field = result.pointer(&pointer).ok_or_else(|| format!("Step {} has no field '{}'", step, path))?;
+            *value = field.clone();
+        }
+        Value::Array(items) => {
+            for item in items {
+                resolve_refs(item, results)?;
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                resolve_refs(field, results)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn profile_in_tx(conn: &mut SqliteConnection, id: &str) -> Result<ProfileRow, String> {
+    sqlx::query_as::<_, ProfileRow>("SELECT * FROM profiles WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| format!("Failed to load profile: {}", e))?
+        .ok_or_else(|| format!("Profile '{}' not found", id))
+}
+
+async fn run_step(conn: &mut SqliteConnection, command: TransactCommand, effects: &mut Vec<Effect>) -> Result<Value, String> {
+    let result = match command {
+        TransactCommand::ProfileCreate { profile } => {
+            let row = profile_auth::insert_profile(conn, &profile).await?;
+            let amp_profile = row.to_amp_profile(false);
+            let profile_id = row.id.clone();
+            effects.push(Effect::AddProfile(Box::new(row)));
+            if let Some(token) = profile.token {
+                effects.push(Effect::StoreToken { profile_id, token });
+            }
+            serde_json::to_value(amp_profile)
+        }
+        TransactCommand::StoreProfileToken { profile_id, token } => {
+            if token.trim().is_empty() {
+                return Err("Token cannot be empty".to_string());
+            }
+            profile_in_tx(conn, &profile_id).await?;
+            effects.push(Effect::StoreToken { profile_id: profile_id.clone(), token });
+            Ok(serde_json::json!({ "profile_id": profile_id }))
+        }
+        TransactCommand::SaveProfileSecret { profile_id, env_key, provider, reference } => {
+            profile_in_tx(conn, &profile_id).await?;
+            let source = secret_providers::save_source(conn, &profile_id, &env_key, provider, &reference).await?;
+            serde_json::to_value(source)
+        }
+        TransactCommand::ProfileActivate { id } => {
+            let row = profile_in_tx(conn, &id).await?;
+            profile_auth::record_activation(conn, &id).await?;
+            effects.push(Effect::Activate(id));
+            serde_json::to_value(row.to_amp_profile(true))
+        }
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Run every step against `conn`, stopping at the first failure. The caller
+/// owns the transaction.
+async fn run_steps(conn: &mut SqliteConnection, steps: Vec<Value>) -> Result<(Vec<Value>, Vec<Effect>), String> {
+    if steps.len() > MAX_STEPS {
+        return Err(format!("A transaction can have at most {} steps", MAX_STEPS));
+    }
+    let mut results = Vec::with_capacity(steps.len());
+    let mut effects = Vec::new();
+    for (index, mut step) in steps.into_iter().enumerate() {
+        resolve_refs(&mut step, &results).map_err(|e| format!("Step {}: {}", index, e))?;
+        let command: TransactCommand =
+            serde_json::from_value(step).map_err(|e| format!("Step {}: unsupported command: {}", index, e))?;
+        let name = command.name();
+        let result = run_step(conn, command, &mut effects)
+            .await
+            .map_err(|e| format!("Step {} ({}) failed: {}", index, name, e))?;
+        results.push(result);
+    }
+    Ok((results, effects))
+}
+
+async fn apply_effects(profile_manager: &ProfileManager, effects: Vec<Effect>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for effect in effects {
+        match effect {
+            Effect::AddProfile(row) => {
+                let id = row.id.clone();
+                profile_manager.profiles.insert(id, Arc::new(RwLock::new(ProfileCtx::new(*row))));
+            }
+            Effect::StoreToken { profile_id, token } => {
+                if let Err(e) = KeychainAuth::new().store_token(&profile_id, TokenType::AccessToken, &token) {
+                    log::warn!("transact: Failed to store token for profile {}: {}", profile_id, e);
+                    warnings.push(format!("Failed to store token for profile {}: {}", profile_id, e));
+                }
+            }
+            Effect::Activate(id) => {
+                if let Err(e) = profile_manager.apply_activation(&id).await {
+                    warnings.push(format!("Failed to activate profile {}: {}", id, e));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Run `commands` all-or-nothing and return each one's result
+#[tauri::command]
+pub async fn transact(
+    commands: Vec<Value>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<TransactResult, String> {
+    let (results, effects) = {
+        let db = profile_manager.db_pool.read().await;
+        let db = db.as_ref().ok_or("Database not available")?;
+        let mut tx = db.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        // Dropping `tx` on an error rolls every step back
+        let outcome = run_steps(&mut tx, commands).await?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        outcome
+    };
+    let warnings = apply_effects(&profile_manager, effects).await;
+    Ok(TransactResult { results, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool_with;
+    use serde_json::json;
+    use sqlx::{pool::PoolOptions, sqlite::SqliteConnectOptions, SqlitePool};
+    use std::str::FromStr;
+
+    // One connection, so a dropped transaction is rolled back before the next query
+    async fn setup_db() -> SqlitePool {
+        let options = SqliteConnectOptions::from_str(":memory:").unwrap();
+        test_pool_with(PoolOptions::new().max_connections(1), options).await
+    }
+
+    fn create_profile(name: &str) -> Value {
+        json!({ "command": "profile_create", "args": { "profile": { "name": name, "connection_type": "production" } } })
+    }
+
+    #[test]
+    fn test_resolve_refs() {
+        let results = vec![json!({ "id": "p1", "nested": { "key": 3 } })];
+        let mut args = json!({ "profile_id": "$0.id", "n": ["$0.nested.key"], "literal": "$HOME", "price": "$5" });
+        resolve_refs(&mut args, &results).unwrap();
+        assert_eq!(args, json!({ "profile_id": "p1", "n": [3], "literal": "$HOME", "price": "$5" }));
+
+        assert!(resolve_refs(&mut json!("$1.id"), &results).is_err());
+        assert!(resolve_refs(&mut json!("$0.missing"), &results).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_steps_share_one_transaction() {
+        let pool = setup_db().await;
+        let steps = vec![
+            create_profile("work"),
+            json!({ "command": "store_profile_token", "args": { "profile_id": "$0.id", "token": "secret" } }),
+            json!({ "command": "save_profile_secret", "args": {
+                "profile_id": "$0.id", "env_key": "GH_TOKEN", "provider": "pass", "reference": "gh/token" } }),
+            json!({ "command": "profile_activate", "args": { "id": "$0.id" } }),
+        ];
+        let mut tx = pool.begin().await.unwrap();
+        let (results, effects) = run_steps(&mut tx, steps).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let id = results[0]["id"].as_str().unwrap().to_string();
+        assert_eq!(results[3]["is_active"], json!(true));
+        assert_eq!(effects.len(), 3);
+        assert!(matches!(&effects[1], Effect::StoreToken { profile_id, token } if *profile_id == id && token == "secret"));
+        assert!(matches!(&effects[2], Effect::Activate(activated) if *activated == id));
+        let active: String = sqlx::query_scalar("SELECT value FROM ui_state WHERE key = 'active_profile_id'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(active, id);
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_rolls_back_earlier_ones() {
+        let pool = setup_db().await;
+        let steps = vec![
+            create_profile("work"),
+            json!({ "command": "save_profile_secret", "args": {
+                "profile_id": "$0.id", "env_key": "bad-key", "provider": "pass", "reference": "x" } }),
+        ];
+        let mut tx = pool.begin().await.unwrap();
+        let err = run_steps(&mut tx, steps).await.unwrap_err();
+        drop(tx);
+        assert!(err.starts_with("Step 1 (save_profile_secret) failed"), "{}", err);
+        let profiles: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM profiles").fetch_one(&pool).await.unwrap();
+        assert_eq!(profiles, 3, "only the seeded profiles remain");
+
+        let mut tx = pool.begin().await.unwrap();
+        let err = run_steps(&mut tx, vec![json!({ "command": "profile_delete", "args": { "id": "x" } })]).await.unwrap_err();
+        assert!(err.contains("unsupported command"), "{}", err);
+    }
+}
@@ -0,0 +1,677 @@
+//! Recycle bin for sessions, profiles, hooks and worktrees
+//!
+//! Destructive commands capture the affected rows as JSON before deleting them,
+//! and worktree removal moves the directory under `<app data>/trash/<id>` instead
+//! of deleting it. Items can be restored until they expire after
+//! `TRASH_TTL_DAYS`; expired items are purged whenever the trash is listed or
+//! written to.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::sqlite::{SqliteArguments, SqliteRow};
+use sqlx::{Column, FromRow, Row, SqlitePool, TypeInfo, ValueRef};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tauri::{AppHandle, Manager, State};
+
+use crate::profile_auth::{ProfileCtx, ProfileManager, ProfileRow};
+use crate::session_commands::AmpSessionMap;
+
+pub(crate) type SqliteQuery<'q> = sqlx::query::Query<'q, sqlx::Sqlite, SqliteArguments<'q>>;
+
+pub const TRASH_TTL_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Session,
+    Profile,
+    ToolboxProfile,
+    ScriptHook,
+    Worktree,
+}
+
+impl TrashKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashKind::Session => "session",
+            TrashKind::Profile => "profile",
+            TrashKind::ToolboxProfile => "toolbox_profile",
+            TrashKind::ScriptHook => "script_hook",
+            TrashKind::Worktree => "worktree",
+        }
+    }
+}
+
+/// Rows to capture: `SELECT * FROM {table} WHERE {filter}` with `key` bound once per `?`
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub table: &'static str,
+    pub filter: &'static str,
+    pub key: String,
+}
+
+impl Capture {
+    pub fn new(table: &'static str, filter: &'static str, key: impl Into<String>) -> Self {
+        Self { table, filter, key: key.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableRows {
+    pub table: String,
+    pub rows: Vec<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrashItem {
+    pub id: String,
+    pub kind: String,
+    pub label: String,
+    #[serde(skip_serializing)]
+    pub payload: String,
+    pub metadata: String,
+    pub trash_path: Option<String>,
+    pub deleted_at: String,
+    pub expires_at: String,
+}
+
+impl TrashItem {
+    fn metadata_value(&self) -> Value {
+        serde_json::from_str(&self.metadata).unwrap_or_default()
+    }
+}
+
+pub(crate) fn row_to_json(row: &SqliteRow) -> Result<Map<String, Value>, sqlx::Error> {
+    let mut map = Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        let raw = row.try_get_raw(i)?;
+        // Dispatch on the stored value's type, since SQLite columns are loosely typed
+        let value = if raw.is_null() {
+            Value::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" | "BOOLEAN" => Value::from(row.try_get_unchecked::<i64, _>(i)?),
+                "REAL" => Value::from(row.try_get_unchecked::<f64, _>(i)?),
+                "BLOB" => Value::from(row.try_get_unchecked::<Vec<u8>, _>(i)?),
+                _ => Value::String(row.try_get_unchecked::<String, _>(i)?),
+            }
+        };
+        map.insert(column.name().to_string(), value);
+    }
+    Ok(map)
+}
+
+/// Bind a value produced by `row_to_json`
+pub(crate) fn bind_json<'q>(query: SqliteQuery<'q>, value: &Value) -> SqliteQuery<'q> {
+    match value {
+        Value::Null => query.bind(None::<String>),
+        Value::Bool(b) => query.bind(*b),
+        Value::Number(n) if n.is_i64() => query.bind(n.as_i64()),
+        Value::Number(n) => query.bind(n.as_f64()),
+        Value::String(s) => query.bind(s.clone()),
+        Value::Array(bytes) => query.bind(bytes.iter().filter_map(|b| b.as_u64().map(|b| b as u8)).collect::<Vec<u8>>()),
+        Value::Object(_) => query.bind(value.to_string()),
+    }
+}
+
+pub struct TrashStore {
+    db: SqlitePool,
+}
+
+impl TrashStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Capture and delete rows in one transaction. Captures are listed parent first;
+    /// deletion runs in reverse so children go before the rows they reference.
+    /// Returns `None` when the first capture matches nothing.
+    pub async fn trash_rows(
+        &self,
+        kind: TrashKind,
+        label: &str,
+        captures: &[Capture],
+        metadata: Value,
+    ) -> Result<Option<TrashItem>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+
+        let mut tables = Vec::with_capacity(captures.len());
+        for capture in captures {
+            let sql = format!("SELECT * FROM {} WHERE {}", capture.table, capture.filter);
+            let mut query = sqlx::query(&sql);
+            for _ in 0..capture.filter.matches('?').count() {
+                query = query.bind(&capture.key);
+            }
+            let rows = query.fetch_all(&mut *tx).await?;
+            let rows = rows.iter().map(row_to_json).collect::<Result<Vec<_>, _>>()?;
+            tables.push(TableRows { table: capture.table.to_string(), rows });
+        }
+        if tables.first().is_none_or(|t| t.rows.is_empty()) {
+            return Ok(None);
+        }
+
+        for capture in captures.iter().rev() {
+            let sql = format!("DELETE FROM {} WHERE {}", capture.table, capture.filter);
+            let mut query = sqlx::query(&sql);
+            for _ in 0..capture.filter.matches('?').count() {
+                query = query.bind(&capture.key);
+            }
+            query.execute(&mut *tx).await?;
+        }
+
+        let payload = serde_json::to_string(&tables).unwrap_or_else(|_| "[]".to_string());
+        let item = Self::insert_item(&mut tx, kind, label, &payload, &metadata, None).await?;
+        tx.commit().await?;
+        Ok(Some(item))
+    }
+
+    /// Record a trashed item that has no rows, such as a moved worktree directory
+    pub async fn add_item(
+        &self,
+        kind: TrashKind,
+        label: &str,
+        metadata: Value,
+        trash_path: Option<&Path>,
+    ) -> Result<TrashItem, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let item = Self::insert_item(&mut tx, kind, label, "[]", &metadata, trash_path).await?;
+        tx.commit().await?;
+        Ok(item)
+    }
+
+    async fn insert_item(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        kind: TrashKind,
+        label: &str,
+        payload: &str,
+        metadata: &Value,
+        trash_path: Option<&Path>,
+    ) -> Result<TrashItem, sqlx::Error> {
+        sqlx::query_as::<_, TrashItem>(
+            "INSERT INTO trash_items (id, kind, label, payload, metadata, trash_path, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, datetime('now', 'utc', ?) || 'Z')
+             RETURNING id, kind, label, payload, metadata, trash_path, deleted_at, expires_at"
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(kind.as_str())
+        .bind(label)
+        .bind(payload)
+        .bind(metadata.to_string())
+        .bind(trash_path.map(|p| p.to_string_lossy().to_string()))
+        .bind(format!("+{} days", TRASH_TTL_DAYS))
+        .fetch_one(&mut **tx)
+        .await
+    }
+
+    pub async fn list_items(&self) -> Result<Vec<TrashItem>, sqlx::Error> {
+        sqlx::query_as::<_, TrashItem>(
+            "SELECT id, kind, label, payload, metadata, trash_path, deleted_at, expires_at
+             FROM trash_items ORDER BY deleted_at DESC"
+        )
+        .fetch_all(&self.db)
+        .await
+    }
+
+    pub async fn get_item(&self, id: &str) -> Result<Option<TrashItem>, sqlx::Error> {
+        sqlx::query_as::<_, TrashItem>(
+            "SELECT id, kind, label, payload, metadata, trash_path, deleted_at, expires_at
+             FROM trash_items WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    pub async fn expired_items(&self) -> Result<Vec<TrashItem>, sqlx::Error> {
+        sqlx::query_as::<_, TrashItem>(
+            "SELECT id, kind, label, payload, metadata, trash_path, deleted_at, expires_at
+             FROM trash_items WHERE expires_at <= (datetime('now', 'utc') || 'Z')"
+        )
+        .fetch_all(&self.db)
+        .await
+    }
+
+    pub async fn remove_item(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM trash_items WHERE id = ?")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Re-insert an item's rows and drop it from the trash. Fails without changes
+    /// if any row conflicts with one created since the deletion.
+    pub async fn restore_rows(&self, item: &TrashItem) -> Result<(), sqlx::Error> {
+        let tables: Vec<TableRows> = serde_json::from_str(&item.payload)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let mut tx = self.db.begin().await?;
+
+        for table in &tables {
+            for row in &table.rows {
+                let columns: Vec<String> = row.keys().map(|c| format!("\"{}\"", c.replace('"', "\"\""))).collect();
+                let placeholders = vec!["?"; columns.len()].join(", ");
+                let sql = format!("INSERT INTO {} ({}) VALUES ({})", table.table, columns.join(", "), placeholders);
+                let mut query = sqlx::query(&sql);
+                for value in row.values() {
+                    query = bind_json(query, value);
+                }
+                query.execute(&mut *tx).await?;
+            }
+        }
+
+        sqlx::query("DELETE FROM trash_items WHERE id = ?")
+            .bind(&item.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+}
+
+/// Rows making up a chat session (legacy) or a session with its threads.
+///
+/// Only resolved conflicts are captured: open ones stay listed until the user
+/// settles them, and settling a sync delete is itself what trashes the session.
+/// Sync state (`sync_sessions`, `sync_rows`) is never captured, as it is what
+/// pushes the deletion to other devices and, after a restore, the session back.
+pub fn session_captures(session_id: &str) -> [Vec<Capture>; 2] {
+    let chat = vec![
+        Capture::new("chat_sessions", "id = ?", session_id),
+        Capture::new("session_outcomes", "session_id = ?", session_id),
+        Capture::new("session_tags", "session_id = ?", session_id),
+        Capture::new("thread_feedback", "thread_id = ?", session_id),
+        Capture::new("quarantined_events", "session_id = ?", session_id),
+        Capture::new("session_priorities", "session_id = ?", session_id),
+        Capture::new("session_worktrees", "session_id = ?", session_id),
+        Capture::new("session_processes", "session_id = ?", session_id),
+    ];
+    let threads = vec![
+        Capture::new("sessions", "id = ?", session_id),
+        Capture::new("threads", "session_id = ?", session_id),
+        Capture::new("messages", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("thread_archives", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("session_outcomes", "session_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("session_tags", "session_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("thread_feedback", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("toolbox_snapshots", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("context_injections", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("thread_replays", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("thread_spawn_envs", "thread_id IN (SELECT id FROM threads WHERE session_id = ?)", session_id),
+        Capture::new("conflicts", "session_id = ? AND resolved_at IS NOT NULL", session_id),
+        Capture::new(
+            "quarantined_events",
+            "session_id = ? OR session_id IN (SELECT id FROM threads WHERE session_id = ?)",
+            session_id,
+        ),
+        Capture::new(
+            "session_priorities",
+            "session_id = ? OR session_id IN (SELECT id FROM threads WHERE session_id = ?)",
+            session_id,
+        ),
+        Capture::new("session_worktrees", "session_id = ?", session_id),
+        Capture::new(
+            "session_processes",
+            "session_id = ? OR session_id IN (SELECT id FROM threads WHERE session_id = ?)",
+            session_id,
+        ),
+        Capture::new("sync_rules", "session_id = ?", session_id),
+        Capture::new("sync_rule_files", "rule_id IN (SELECT id FROM sync_rules WHERE session_id = ?)", session_id),
+        Capture::new("session_stacks", "session_id = ?", session_id),
+        Capture::new("env_isolation_settings", "scope = 'session' AND scope_id = ?", session_id),
+    ];
+    [chat, threads]
+}
+
+/// Move a directory, copying across filesystems when a rename is not possible
+fn move_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::other)?;
+        let target = to.join(entry.path().strip_prefix(from).map_err(std::io::Error::other)?);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    std::fs::remove_dir_all(from)
+}
+
+fn git(repo: &Path, args: &[&str]) {
+    match Command::new("git").current_dir(repo).args(args).output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => log::warn!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr)),
+        Err(e) => log::warn!("Failed to run git {}: {}", args.join(" "), e),
+    }
+}
+
+pub fn trash_root(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(data_dir.join("trash"))
+}
+
+/// Move a worktree into the trash. Git's registration and the branch are kept so
+/// a restore can reattach it; both are cleaned up when the item is purged.
+pub async fn trash_worktree(
+    app_handle: &AppHandle,
+    db: &SqlitePool,
+    worktree_path: &Path,
+    branch_name: &str,
+) -> Result<TrashItem, String> {
+    if !worktree_path.exists() {
+        return Err(format!("Worktree not found: {}", worktree_path.display()));
+    }
+    let repo_root = crate::worktree::find_repo_root(worktree_path).map_err(|e| e.to_string())?;
+    let item_dir = trash_root(app_handle)?.join(uuid::Uuid::new_v4().to_string());
+    let destination = item_dir.join("worktree");
+
+    let source = worktree_path.to_path_buf();
+    let target = destination.clone();
+    tokio::task::spawn_blocking(move || move_dir(&source, &target))
+        .await
+        .map_err(|e| format!("Failed to move worktree: {}", e))?
+        .map_err(|e| format!("Failed to move worktree to trash: {}", e))?;
+
+    let metadata = serde_json::json!({
+        "original_path": worktree_path.to_string_lossy(),
+        "repo_path": repo_root.to_string_lossy(),
+        "branch": branch_name,
+    });
+    TrashStore::new(db.clone())
+        .add_item(TrashKind::Worktree, &worktree_path.to_string_lossy(), metadata, Some(&destination))
+        .await
+        .map_err(|e| format!("Failed to record trashed worktree: {}", e))
+}
+
+/// Permanently delete an item and anything it left on disk
+async fn purge_item(db: &SqlitePool, item: &TrashItem) -> Result<(), String> {
+    if let Some(trash_path) = &item.trash_path {
+        let path = PathBuf::from(trash_path);
+        // Remove the per-item directory, not just the worktree inside it
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or(path);
+        if dir.exists() {
+            let result = tokio::task::spawn_blocking(move || std::fs::remove_dir_all(&dir)).await;
+            if let Ok(Err(e)) = result {
+                log::warn!("Failed to remove trashed files for {}: {}", item.id, e);
+            }
+        }
+    }
+    if item.kind == TrashKind::Worktree.as_str() {
+        let metadata = item.metadata_value();
+        if let Some(repo) = metadata.get("repo_path").and_then(|v| v.as_str()) {
+            git(Path::new(repo), &["worktree", "prune"]);
+            if let Some(branch) = metadata.get("branch").and_then(|v| v.as_str()) {
+                git(Path::new(repo), &["branch", "-D", branch]);
+            }
+        }
+    }
+    TrashStore::new(db.clone())
+        .remove_item(&item.id)
+        .await
+        .map_err(|e| format!("Failed to purge trash item: {}", e))?;
+    Ok(())
+}
+
+/// Purge items past their expiry. Returns how many were removed.
+pub async fn purge_expired(db: &SqlitePool) -> Result<usize, String> {
+    let expired = TrashStore::new(db.clone())
+        .expired_items()
+        .await
+        .map_err(|e| format!("Failed to list expired trash: {}", e))?;
+    for item in &expired {
+        purge_item(db, item).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Trashed sessions, profiles, hooks and worktrees, newest first
+#[tauri::command]
+pub async fn list_trash(profile_manager: State<'_, ProfileManager>) -> Result<Vec<TrashItem>, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    purge_expired(db).await?;
+    TrashStore::new(db.clone())
+        .list_items()
+        .await
+        .map_err(|e| format!("Failed to list trash: {}", e))
+}
+
+#[tauri::command]
+pub async fn restore_from_trash(
+    id: String,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<TrashItem, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = TrashStore::new(db.clone());
+
+    let item = store
+        .get_item(&id)
+        .await
+        .map_err(|e| format!("Failed to get trash item: {}", e))?
+        .ok_or_else(|| format!("Trash item {} not found", id))?;
+
+    if item.kind == TrashKind::Worktree.as_str() {
+        let metadata = item.metadata_value();
+        let original = metadata
+            .get("original_path")
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .ok_or("Trashed worktree has no original path")?;
+        let trashed = item.trash_path.as_deref().map(PathBuf::from).ok_or("Trashed worktree has no files")?;
+        if original.exists() {
+            return Err(format!("Cannot restore worktree: {} already exists", original.display()));
+        }
+
+        let (from, to) = (trashed.clone(), original.clone());
+        tokio::task::spawn_blocking(move || move_dir(&from, &to))
+            .await
+            .map_err(|e| format!("Failed to restore worktree: {}", e))?
+            .map_err(|e| format!("Failed to restore worktree: {}", e))?;
+        if let Some(repo) = metadata.get("repo_path").and_then(|v| v.as_str()) {
+            git(Path::new(repo), &["worktree", "repair", &original.to_string_lossy()]);
+        }
+        if let Some(item_dir) = trashed.parent() {
+            let _ = std::fs::remove_dir_all(item_dir);
+        }
+        store.remove_item(&id).await.map_err(|e| format!("Failed to update trash: {}", e))?;
+    } else {
+        store
+            .restore_rows(&item)
+            .await
+            .map_err(|e| format!("Failed to restore {}: {}", item.label, e))?;
+    }
+
+    // Keychain tokens were cleared on delete, so a restored profile must sign in again
+    if item.kind == TrashKind::Profile.as_str() {
+        let profile_id = item.metadata_value().get("profile_id").and_then(|v| v.as_str()).map(str::to_string);
+        let row = sqlx::query_as::<_, ProfileRow>("SELECT * FROM profiles WHERE id = ?")
+            .bind(profile_id)
+            .fetch_optional(db)
+            .await
+            .map_err(|e| format!("Failed to load restored profile: {}", e))?;
+        if let Some(row) = row {
+            let ctx = Arc::new(RwLock::new(ProfileCtx::new(row.clone())));
+            profile_manager.profiles.insert(row.id, ctx);
+        }
+    }
+    Ok(item)
+}
+
+/// Permanently delete everything in the trash. Returns the number of items removed.
+#[tauri::command]
+pub async fn empty_trash(profile_manager: State<'_, ProfileManager>) -> Result<usize, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+
+    let items = TrashStore::new(db.clone())
+        .list_items()
+        .await
+        .map_err(|e| format!("Failed to list trash: {}", e))?;
+    for item in &items {
+        purge_item(db, item).await?;
+    }
+    Ok(items.len())
+}
+
+/// Move a chat session or a session with all its threads to the trash
+#[tauri::command]
+pub async fn session_delete(
+    session_id: String,
+    amp_sessions: State<'_, AmpSessionMap>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<TrashItem, String> {
+    let session_id = crate::session_codes::resolve(&session_id);
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let store = TrashStore::new(db.clone());
+
+    let thread_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM threads WHERE session_id = ?")
+        .bind(&session_id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to get session threads: {}", e))?;
+
+    let mut trashed = None;
+    for captures in session_captures(&session_id) {
+        trashed = store
+            .trash_rows(TrashKind::Session, &session_id, &captures, serde_json::json!({}))
+            .await
+            .map_err(|e| format!("Failed to delete session: {}", e))?;
+        if trashed.is_some() {
+            break;
+        }
+    }
+    let item = trashed.ok_or_else(|| format!("Session {} not found", session_id))?;
+
+    // Stop anything still running for the session
+    for id in std::iter::once(&session_id).chain(thread_ids.iter()) {
+        amp_sessions.remove(id);
+    }
+
+    let _ = purge_expired(db).await;
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    async fn count(pool: &SqlitePool, sql: &str) -> i64 {
+        sqlx::query_scalar(sql).fetch_one(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_trash_and_restore_session_with_threads() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO sessions (id, title) VALUES ('s1', 'Refactor')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO threads (id, session_id, context, agent_mode) VALUES ('t1', 's1', 'production', NULL)")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO messages (id, thread_id, role, content) VALUES ('m1', 't1', 'user', 'hi')")
+            .execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO thread_feedback (thread_id, rating, env_hash) VALUES ('t1', 4, 'h')")
+            .execute(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO session_worktrees (session_id, repo_root, worktree_path, branch_name) VALUES ('s1', '/repo', '/repo/.amp-worktrees/s1', 'b');
+             INSERT INTO session_processes (session_id, kind, program, status) VALUES ('t1', 'thread', 'amp', 'idle');
+             INSERT INTO sync_rules (id, session_id, sources, destination) VALUES ('r1', 's1', '[]', '/tmp/staging');
+             INSERT INTO sync_rule_files (rule_id, path, hash) VALUES ('r1', 'docs/a.md', 'h');
+             INSERT INTO thread_spawn_envs (thread_id, reason, env) VALUES ('t1', 'start', '{}');
+             INSERT INTO conflicts (id, source, kind, session_id, summary, options, applied, resolved_at) VALUES
+                 ('c-open', 'sync', 'delete', 's1', '', '[]', 'keep', NULL),
+                 ('c-done', 'merge', 'edit', 's1', '', '[]', 'local', '2026-01-01T00:00:00Z');
+             INSERT INTO sync_sessions (session_id) VALUES ('s1');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let attached = "SELECT (SELECT COUNT(*) FROM session_worktrees) + (SELECT COUNT(*) FROM session_processes)
+            + (SELECT COUNT(*) FROM sync_rule_files) + (SELECT COUNT(*) FROM thread_spawn_envs) + (SELECT COUNT(*) FROM conflicts)";
+        let store = TrashStore::new(pool.clone());
+
+        let [chat, threads] = session_captures("s1");
+        assert!(store.trash_rows(TrashKind::Session, "s1", &chat, Value::Null).await.unwrap().is_none());
+        let item = store.trash_rows(TrashKind::Session, "s1", &threads, Value::Null).await.unwrap().unwrap();
+
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM sessions").await, 0);
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM messages").await, 0);
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM thread_feedback").await, 0);
+        // Open conflicts and sync state stay behind
+        assert_eq!(count(&pool, attached).await, 1);
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM sync_sessions").await, 1);
+        assert!(item.expires_at > item.deleted_at);
+        assert_eq!(store.list_items().await.unwrap().len(), 1);
+
+        store.restore_rows(&item).await.unwrap();
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM threads WHERE session_id = 's1'").await, 1);
+        let content: String = sqlx::query_scalar("SELECT content FROM messages WHERE id = 'm1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(content, "hi");
+        let rating: i64 = sqlx::query_scalar("SELECT rating FROM thread_feedback").fetch_one(&pool).await.unwrap();
+        assert_eq!(rating, 4);
+        assert_eq!(count(&pool, attached).await, 6);
+        assert!(store.list_items().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_conflict_leaves_trash_intact() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO script_hooks (id, profile_id, name, event, source, enabled) VALUES ('h1', 'bundled', 'tagger', 'tool_use', 'tag_session(\"x\");', 1)")
+            .execute(&pool).await.unwrap();
+        let store = TrashStore::new(pool.clone());
+
+        let item = store
+            .trash_rows(TrashKind::ScriptHook, "tagger", &[Capture::new("script_hooks", "id = ?", "h1")], Value::Null)
+            .await
+            .unwrap()
+            .unwrap();
+        // A new hook with the same name now occupies the unique slot
+        sqlx::query("INSERT INTO script_hooks (id, profile_id, name, event, source) VALUES ('h2', 'bundled', 'tagger', 'tool_use', '')")
+            .execute(&pool).await.unwrap();
+
+        assert!(store.restore_rows(&item).await.is_err());
+        assert!(store.get_item(&item.id).await.unwrap().is_some());
+        assert_eq!(count(&pool, "SELECT COUNT(*) FROM script_hooks").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_items() {
+        let pool = test_pool().await;
+        let store = TrashStore::new(pool.clone());
+        let item = store.add_item(TrashKind::Worktree, "/tmp/wt", serde_json::json!({}), None).await.unwrap();
+        assert!(store.expired_items().await.unwrap().is_empty());
+
+        sqlx::query("UPDATE trash_items SET expires_at = '2000-01-01 00:00:00Z' WHERE id = ?")
+            .bind(&item.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(store.expired_items().await.unwrap().len(), 1);
+        assert_eq!(purge_expired(&pool).await.unwrap(), 1);
+        assert!(store.list_items().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_move_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let from = dir.path().join("wt");
+        std::fs::create_dir_all(from.join("src")).unwrap();
+        std::fs::write(from.join("src/lib.rs"), "pub fn f() {}").unwrap();
+
+        let to = dir.path().join("trash/item/worktree");
+        move_dir(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read_to_string(to.join("src/lib.rs")).unwrap(), "pub fn f() {}");
+    }
+}
@@ -0,0 +1,431 @@
+//! Persistent UI state
+//!
+//! Layout and selection state the frontend wants back after a restart, stored in
+//! the `ui_state` table as `ui:<namespace>:<key>` so it cannot collide with the
+//! app's own entries there. Each namespace has a schema its values must match
+//! and a cap on how many keys it holds, and no value may exceed
+//! `MAX_VALUE_BYTES` of JSON. Window geometry is saved when a window closes and
+//! applied again at startup.
+//!
+//! `ui_state_subscribe` sends `ui_state_changed` to the calling webview when
+//! another webview changes one of the namespaces it watches.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, EventTarget, LogicalPosition, LogicalSize, Manager, Runtime, State, Webview, Window};
+
+use crate::profile_auth::ProfileManager;
+
+pub const MAX_VALUE_BYTES: usize = 16 * 1024;
+pub const MAX_KEY_BYTES: usize = 128;
+const KEY_PREFIX: &str = "ui:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Schema {
+    /// `WindowState`
+    Window,
+    /// Split sizes and collapsed flags: a number or a boolean
+    Layout,
+    /// `TabsState`
+    Tabs,
+    /// The id of whatever was last selected
+    Selection,
+}
+
+struct Namespace {
+    name: &'static str,
+    schema: Schema,
+    max_keys: i64,
+}
+
+const NAMESPACES: &[Namespace] = &[
+    Namespace { name: "window", schema: Schema::Window, max_keys: 16 },
+    Namespace { name: "layout", schema: Schema::Layout, max_keys: 128 },
+    Namespace { name: "tabs", schema: Schema::Tabs, max_keys: 16 },
+    Namespace { name: "selection", schema: Schema::Selection, max_keys: 64 },
+];
+
+/// Window geometry in logical pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowState {
+    pub width: f64,
+    pub height: f64,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TabsState {
+    /// Session or thread ids, in tab order
+    pub open: Vec<String>,
+    pub active: Option<String>,
+}
+
+impl Schema {
+    fn validate(self, value: &Value) -> Result<(), String> {
+        match self {
+            Schema::Window => {
+                let window = WindowState::deserialize(value).map_err(|e| e.to_string())?;
+                if !(window.width > 0.0 && window.height > 0.0) {
+                    return Err("width and height must be positive".to_string());
+                }
+                Ok(())
+            }
+            Schema::Layout if value.is_number() || value.is_boolean() => Ok(()),
+            Schema::Layout => Err("expected a number or a boolean".to_string()),
+            Schema::Tabs => TabsState::deserialize(value).map(|_| ()).map_err(|e| e.to_string()),
+            Schema::Selection if value.is_string() => Ok(()),
+            Schema::Selection => Err("expected a string".to_string()),
+        }
+    }
+}
+
+fn namespace(name: &str) -> Result<&'static Namespace, String> {
+    NAMESPACES
+        .iter()
+        .find(|ns| ns.name == name)
+        .ok_or_else(|| format!("Unknown UI state namespace '{}'", name))
+}
+
+fn storage_key(namespace: &str, key: &str) -> Result<String, String> {
+    if key.is_empty() || key.len() > MAX_KEY_BYTES || key.chars().any(char::is_control) {
+        return Err(format!("UI state keys must be 1 to {} bytes without control characters", MAX_KEY_BYTES));
+    }
+    Ok(format!("{}{}:{}", KEY_PREFIX, namespace, key))
+}
+
+pub async fn get(db: &SqlitePool, namespace_name: &str, key: &str) -> Result<Option<Value>, String> {
+    namespace(namespace_name)?;
+    let stored: Option<String> = sqlx::query_scalar("SELECT value FROM ui_state WHERE key = ?")
+        .bind(storage_key(namespace_name, key)?)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| format!("Failed to read UI state: {}", e))?;
+    // Values are validated on the way in, so anything unreadable is left over from an older schema
+    Ok(stored.and_then(|s| serde_json::from_str(&s).ok()))
+}
+
+/// Every key of a namespace
+pub async fn get_all(db: &SqlitePool, namespace_name: &str) -> Result<Map<String, Value>, String> {
+    namespace(namespace_name)?;
+    let prefix = format!("{}{}:", KEY_PREFIX, namespace_name);
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT key, value FROM ui_state WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key",
+    )
+    .bind(&prefix)
+    .fetch_all(db)
+    .await
+    .map_err(|e| format!("Failed to read UI state: {}", e))?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(key, value)| Some((key[prefix.len()..].to_string(), serde_json::from_str(&value).ok()?)))
+        .collect())
+}
+
+/// Store a value, or remove the key when `value` is `None`
+pub async fn set(db: &SqlitePool, namespace_name: &str, key: &str, value: Option<&Value>) -> Result<(), String> {
+    let ns = namespace(namespace_name)?;
+    let storage_key = storage_key(namespace_name, key)?;
+    let Some(value) = value else {
+        sqlx::query("DELETE FROM ui_state WHERE key = ?")
+            .bind(&storage_key)
+            .execute(db)
+            .await
+            .map_err(|e| format!("Failed to save UI state: {}", e))?;
+        return Ok(());
+    };
+    ns.schema
+        .validate(value)
+        .map_err(|e| format!("Invalid value for {}:{}: {}", namespace_name, key, e))?;
+    let json = value.to_string();
+    if json.len() > MAX_VALUE_BYTES {
+        return Err(format!("UI state values are limited to {} bytes, got {}", MAX_VALUE_BYTES, json.len()));
+    }
+
+    let prefix = format!("{}{}:", KEY_PREFIX, namespace_name);
+    let (keys, exists): (i64, bool) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(MAX(key = ?2), 0) FROM ui_state WHERE substr(key, 1, length(?1)) = ?1",
+    )
+    .bind(&prefix)
+    .bind(&storage_key)
+    .fetch_one(db)
+    .await
+    .map_err(|e| format!("Failed to read UI state: {}", e))?;
+    if !exists && keys >= ns.max_keys {
+        return Err(format!("UI state namespace '{}' is limited to {} keys", namespace_name, ns.max_keys));
+    }
+
+    sqlx::query("INSERT OR REPLACE INTO ui_state (key, value) VALUES (?, ?)")
+        .bind(&storage_key)
+        .bind(json)
+        .execute(db)
+        .await
+        .map_err(|e| format!("Failed to save UI state: {}", e))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UiStateChange {
+    pub namespace: String,
+    pub key: String,
+    /// `None` when the key was removed
+    pub value: Option<Value>,
+}
+
+/// Subscription id -> namespaces
+type WebviewSubscriptions = HashMap<String, Vec<String>>;
+
+#[derive(Clone, Default)]
+pub struct UiStateSubscriptions {
+    /// Webview label -> its subscriptions
+    inner: Arc<Mutex<HashMap<String, WebviewSubscriptions>>>,
+}
+
+impl UiStateSubscriptions {
+    pub fn subscribe(&self, webview: &str, namespaces: Vec<String>) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.inner.lock().unwrap().entry(webview.to_string()).or_default().insert(id.clone(), namespaces);
+        id
+    }
+
+    /// Remove one subscription, or all of a webview's when `id` is `None`
+    pub fn unsubscribe(&self, webview: &str, id: Option<&str>) -> usize {
+        let mut subs = self.inner.lock().unwrap();
+        let Some(webview_subs) = subs.get_mut(webview) else {
+            return 0;
+        };
+        match id {
+            Some(id) => webview_subs.remove(id).map_or(0, |_| 1),
+            None => subs.remove(webview).map_or(0, |removed| removed.len()),
+        }
+    }
+
+    pub fn remove_webview(&self, webview: &str) {
+        self.inner.lock().unwrap().remove(webview);
+    }
+
+    /// Webviews other than `except` watching `namespace`
+    pub fn watchers(&self, namespace: &str, except: &str) -> Vec<String> {
+        let subs = self.inner.lock().unwrap();
+        let mut labels: Vec<String> = subs
+            .iter()
+            .filter(|(label, webview_subs)| {
+                label.as_str() != except && webview_subs.values().any(|namespaces| namespaces.iter().any(|n| n == namespace))
+            })
+            .map(|(label, _)| label.clone())
+            .collect();
+        labels.sort();
+        labels
+    }
+}
+
+/// Size and position in logical pixels, and whether the window is maximized
+fn geometry<R: Runtime>(window: &Window<R>) -> tauri::Result<(LogicalSize<f64>, LogicalPosition<f64>, bool)> {
+    let scale = window.scale_factor()?;
+    let size = window.inner_size()?.to_logical(scale);
+    let position = window.outer_position()?.to_logical(scale);
+    Ok((size, position, window.is_maximized()?))
+}
+
+/// Remember a window's geometry as it closes. A maximized window keeps the
+/// size it had before, so it restores to something sensible when unmaximized.
+pub fn save_window<R: Runtime>(window: &Window<R>) {
+    let Some(profile_manager) = window.try_state::<ProfileManager>() else {
+        return;
+    };
+    let (size, position, maximized) = match geometry(window) {
+        Ok(geometry) => geometry,
+        Err(e) => {
+            log::warn!("Failed to read geometry of window {}: {}", window.label(), e);
+            return;
+        }
+    };
+    let label = window.label().to_string();
+    tauri::async_runtime::block_on(async {
+        let db = profile_manager.db_pool.read().await;
+        let Some(db) = db.as_ref() else {
+            return;
+        };
+        let state = match get(db, "window", &label).await.ok().flatten() {
+            Some(previous) if maximized => serde_json::from_value::<WindowState>(previous)
+                .map(|previous| WindowState { maximized: true, ..previous })
+                .ok(),
+            _ if maximized => None,
+            _ => Some(WindowState { width: size.width, height: size.height, x: Some(position.x), y: Some(position.y), maximized }),
+        };
+        let Some(state) = state else {
+            return;
+        };
+        if let Err(e) = set(db, "window", &label, Some(&serde_json::json!(state))).await {
+            log::warn!("Failed to save geometry of window {}: {}", label, e);
+        }
+    });
+}
+
+/// Put windows back where they were when the app last closed. A position
+/// outside every connected monitor is dropped.
+pub async fn restore_windows<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Some(profile_manager) = app_handle.try_state::<ProfileManager>() else {
+        return;
+    };
+    let db = profile_manager.db_pool.read().await;
+    let Some(db) = db.as_ref() else {
+        return;
+    };
+    for (label, window) in app_handle.webview_windows() {
+        let Some(state) = get(db, "window", &label).await.ok().flatten() else {
+            continue;
+        };
+        let Ok(state) = serde_json::from_value::<WindowState>(state) else {
+            continue;
+        };
+        let _ = window.set_size(LogicalSize::new(state.width, state.height));
+        if let (Some(x), Some(y)) = (state.x, state.y) {
+            let on_screen = window.available_monitors().unwrap_or_default().iter().any(|monitor| {
+                let origin: LogicalPosition<f64> = monitor.position().to_logical(monitor.scale_factor());
+                let size: LogicalSize<f64> = monitor.size().to_logical(monitor.scale_factor());
+                x >= origin.x && y >= origin.y && x < origin.x + size.width && y < origin.y + size.height
+            });
+            if on_screen {
+                let _ = window.set_position(LogicalPosition::new(x, y));
+            }
+        }
+        if state.maximized {
+            let _ = window.maximize();
+        }
+    }
+}
+
+/// One value, or the whole namespace as an object when `key` is omitted
+#[tauri::command]
+pub async fn ui_state_get(
+    namespace: String,
+    key: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<Value, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    match key {
+        Some(key) => Ok(get(db, &namespace, &key).await?.unwrap_or(Value::Null)),
+        None => Ok(Value::Object(get_all(db, &namespace).await?)),
+    }
+}
+
+/// Store a value; `null` removes the key. Webviews watching the namespace get
+/// `ui_state_changed`.
+#[tauri::command]
+pub async fn ui_state_set(
+    namespace: String,
+    key: String,
+    value: Option<Value>,
+    webview: Webview,
+    app_handle: AppHandle,
+    subs: State<'_, UiStateSubscriptions>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<(), String> {
+    {
+        let db = profile_manager.db_pool.read().await;
+        let db = db.as_ref().ok_or("Database not available")?;
+        set(db, &namespace, &key, value.as_ref()).await?;
+    }
+    let watchers = subs.watchers(&namespace, webview.label());
+    if !watchers.is_empty() {
+        let change = UiStateChange { namespace, key, value };
+        let _ = app_handle.emit_filter("ui_state_changed", change, |target| match target {
+            EventTarget::Webview { label }
+            | EventTarget::WebviewWindow { label }
+            | EventTarget::Window { label }
+            | EventTarget::AnyLabel { label } => watchers.contains(label),
+            _ => false,
+        });
+    }
+    Ok(())
+}
+
+/// Receive `ui_state_changed` in the calling webview for changes other webviews
+/// make to `namespaces`. Returns the subscription id.
+#[tauri::command]
+pub async fn ui_state_subscribe(
+    namespaces: Vec<String>,
+    webview: Webview,
+    subs: State<'_, UiStateSubscriptions>,
+) -> Result<String, String> {
+    for name in &namespaces {
+        namespace(name)?;
+    }
+    Ok(subs.subscribe(webview.label(), namespaces))
+}
+
+/// Drop a subscription, or all of the calling webview's when
+/// `subscription_id` is omitted. Returns how many were removed.
+#[tauri::command]
+pub async fn ui_state_unsubscribe(
+    subscription_id: Option<String>,
+    webview: Webview,
+    subs: State<'_, UiStateSubscriptions>,
+) -> Result<usize, String> {
+    Ok(subs.unsubscribe(webview.label(), subscription_id.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_values_are_validated_and_capped() {
+        let pool = test_pool().await;
+        let window = json!({ "width": 1200.0, "height": 800.0, "x": 40.0, "y": 20.0 });
+        set(&pool, "window", "main", Some(&window)).await.unwrap();
+        set(&pool, "layout", "split-main", Some(&json!(35.5))).await.unwrap();
+        set(&pool, "tabs", "main", Some(&json!({ "open": ["s1", "s2"], "active": "s2" }))).await.unwrap();
+
+        assert_eq!(get(&pool, "window", "main").await.unwrap(), Some(window));
+        assert_eq!(get(&pool, "selection", "profile").await.unwrap(), None);
+        assert_eq!(get_all(&pool, "layout").await.unwrap(), Map::from_iter([("split-main".to_string(), json!(35.5))]));
+        // The app's own entries in the table are not UI state
+        assert!(get_all(&pool, "selection").await.unwrap().is_empty());
+
+        assert!(set(&pool, "window", "main", Some(&json!({ "width": 0, "height": 800 }))).await.is_err());
+        assert!(set(&pool, "tabs", "main", Some(&json!({ "open": "s1", "active": null }))).await.is_err());
+        assert!(set(&pool, "tabs", "main", Some(&json!({ "open": [], "active": null, "pinned": [] }))).await.is_err());
+        assert!(set(&pool, "layout", "split-main", Some(&json!("wide"))).await.is_err());
+        assert!(set(&pool, "themes", "main", Some(&json!("dark"))).await.is_err());
+        assert!(set(&pool, "layout", "", Some(&json!(1))).await.is_err());
+        let long = json!({ "open": vec!["s".repeat(100); 200], "active": null });
+        assert!(set(&pool, "tabs", "main", Some(&long)).await.unwrap_err().contains("limited to"));
+
+        for i in 0..15 {
+            set(&pool, "window", &format!("w{}", i), Some(&json!({ "width": 1, "height": 1 }))).await.unwrap();
+        }
+        assert!(set(&pool, "window", "one-too-many", Some(&json!({ "width": 1, "height": 1 }))).await.is_err());
+        // Replacing an existing key is still allowed at the cap
+        set(&pool, "window", "main", Some(&json!({ "width": 640, "height": 480, "maximized": true }))).await.unwrap();
+        set(&pool, "window", "main", None).await.unwrap();
+        assert_eq!(get(&pool, "window", "main").await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_watchers_exclude_the_writer() {
+        let subs = UiStateSubscriptions::default();
+        let id = subs.subscribe("main", vec!["layout".to_string(), "tabs".to_string()]);
+        subs.subscribe("inspector", vec!["tabs".to_string()]);
+
+        assert_eq!(subs.watchers("tabs", "main"), vec!["inspector".to_string()]);
+        assert_eq!(subs.watchers("layout", "inspector"), vec!["main".to_string()]);
+        assert!(subs.watchers("window", "other").is_empty());
+
+        assert_eq!(subs.unsubscribe("main", Some(&id)), 1);
+        assert!(subs.watchers("layout", "inspector").is_empty());
+        subs.remove_webview("inspector");
+        assert!(subs.watchers("tabs", "main").is_empty());
+    }
+}
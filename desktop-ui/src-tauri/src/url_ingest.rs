@@ -0,0 +1,635 @@
+//! URL ingestion for context packs
+//!
+//! Downloads web pages and documents named in a pack and turns them into text
+//! an agent can read: HTML becomes Markdown, Markdown and plain text are kept
+//! as they are. Downloads stop at `MAX_DOWNLOAD_BYTES` and `FETCH_TIMEOUT`,
+//! `robots.txt` is honoured for our agent token, and results are cached for
+//! `CACHE_TTL` so repeated sends do not hit the site again. GitHub file and
+//! repository links are fetched as raw files, which reads better than the page.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// Bodies are cut off past this size
+pub const MAX_DOWNLOAD_BYTES: usize = 2 * 1024 * 1024;
+const MAX_ROBOTS_BYTES: usize = 512 * 1024;
+const MAX_REDIRECTS: usize = 5;
+pub const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+const MAX_CACHE_ENTRIES: usize = 64;
+/// Token matched against `User-agent` lines in robots.txt
+const ROBOTS_AGENT: &str = "amp-orchestra";
+const USER_AGENT: &str = concat!("amp-orchestra/", env!("CARGO_PKG_VERSION"), " (context packs)");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchedDocument {
+    /// Where the content came from, after rewrites and redirects
+    pub url: String,
+    pub title: Option<String>,
+    /// Markdown or plain text
+    pub text: String,
+    pub content_type: String,
+    /// Bytes downloaded
+    pub bytes: u64,
+    /// Whether the download stopped at `MAX_DOWNLOAD_BYTES`
+    pub truncated: bool,
+    pub fetched_at: String,
+}
+
+impl FetchedDocument {
+    /// Fence language for the text
+    pub fn language(&self) -> &'static str {
+        if self.content_type.contains("html") || self.content_type.contains("markdown") {
+            "md"
+        } else {
+            ""
+        }
+    }
+}
+
+static DOCUMENTS: Lazy<DashMap<String, (Instant, FetchedDocument)>> = Lazy::new(DashMap::new);
+static ROBOTS: Lazy<DashMap<String, (Instant, RobotsRules)>> = Lazy::new(DashMap::new);
+
+fn cached<T: Clone>(cache: &DashMap<String, (Instant, T)>, key: &str) -> Option<T> {
+    let entry = cache.get(key)?;
+    (entry.0.elapsed() < CACHE_TTL).then(|| entry.1.clone())
+}
+
+fn remember<T>(cache: &DashMap<String, (Instant, T)>, key: String, value: T) {
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    if cache.len() >= MAX_CACHE_ENTRIES {
+        let oldest = cache.iter().min_by_key(|entry| entry.value().0).map(|entry| entry.key().clone());
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(key, (Instant::now(), value));
+}
+
+/// Allow and Disallow rules from robots.txt that apply to us
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RobotsRules {
+    /// `(allow, pattern)`
+    rules: Vec<(bool, String)>,
+}
+
+impl RobotsRules {
+    /// The longest matching rule decides; Allow wins a tie, and no match allows
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| robots_match(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+fn robots_match(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<String> = pattern.split('*').map(regex::escape).collect();
+    let re = format!("^{}{}", parts.join(".*"), if anchored { "$" } else { "" });
+    Regex::new(&re).is_ok_and(|re| re.is_match(path))
+}
+
+/// Rules of the group naming `agent`, else those of the `*` group
+pub fn parse_robots(text: &str, agent: &str) -> RobotsRules {
+    let agent = agent.to_ascii_lowercase();
+    let (mut named, mut wildcard, mut has_named_group) = (Vec::new(), Vec::new(), false);
+    let (mut agents, mut in_rules) = (Vec::<String>::new(), false);
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((field, value)) = line.split_once(':') else { continue };
+        let (field, value) = (field.trim().to_ascii_lowercase(), value.trim());
+        match field.as_str() {
+            "user-agent" => {
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_ascii_lowercase());
+                has_named_group |= value.eq_ignore_ascii_case(&agent);
+            }
+            "allow" | "disallow" => {
+                in_rules = true;
+                // An empty Disallow allows everything
+                if value.is_empty() {
+                    continue;
+                }
+                let rule = (field == "allow", value.to_string());
+                if agents.contains(&agent) {
+                    named.push(rule.clone());
+                }
+                if agents.iter().any(|a| a == "*") {
+                    wildcard.push(rule);
+                }
+            }
+            _ => {}
+        }
+    }
+    RobotsRules { rules: if has_named_group { named } else { wildcard } }
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Whether robots.txt lets us fetch `url`. A missing or unreachable robots.txt
+/// disallows nothing.
+async fn robots_allow(client: &reqwest::Client, url: &Url) -> bool {
+    let origin = url.origin().ascii_serialization();
+    let rules = match cached(&ROBOTS, &origin) {
+        Some(rules) => rules,
+        None => {
+            let rules = match client.get(format!("{}/robots.txt", origin)).send().await {
+                Ok(response) if response.status().is_success() => {
+                    let (body, _) = read_body(response, MAX_ROBOTS_BYTES).await.unwrap_or_default();
+                    parse_robots(&String::from_utf8_lossy(&body), ROBOTS_AGENT)
+                }
+                _ => RobotsRules::default(),
+            };
+            remember(&ROBOTS, origin, rules.clone());
+            rules
+        }
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    rules.allows(&path)
+}
+
+/// Addresses to try for `url`, best first: raw files for GitHub links
+fn candidates(url: &Url) -> Vec<Url> {
+    let mut urls = Vec::new();
+    if url.host_str() == Some("github.com") {
+        let segments: Vec<&str> = url.path_segments().map(|s| s.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+        let raw = match segments.as_slice() {
+            [owner, repo, "blob", rest @ ..] if !rest.is_empty() => Some(format!("{}/{}/{}", owner, repo, rest.join("/"))),
+            [owner, repo] => Some(format!("{}/{}/HEAD/README.md", owner, repo)),
+            _ => None,
+        };
+        if let Some(raw) = raw.and_then(|path| Url::parse(&format!("https://raw.githubusercontent.com/{}", path)).ok()) {
+            urls.push(raw);
+        }
+    }
+    urls.push(url.clone());
+    urls
+}
+
+async fn read_body(mut response: reqwest::Response, limit: usize) -> Result<(Vec<u8>, bool), String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Download failed: {}", e))? {
+        let room = limit - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+async fn download(client: &reqwest::Client, url: &Url) -> Result<FetchedDocument, String> {
+    if !robots_allow(client, url).await {
+        return Err(format!("robots.txt of {} does not allow fetching {}", url.origin().ascii_serialization(), url.path()));
+    }
+    let response = client
+        .get(url.clone())
+        .header(ACCEPT, "text/html, text/markdown, text/plain;q=0.9, */*;q=0.1")
+        .send()
+        .await
+        .map_err(|e| if e.is_timeout() { format!("{} timed out", url) } else { format!("Failed to fetch {}: {}", url, e) })?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let textual = content_type.is_empty()
+        || content_type.starts_with("text/")
+        || ["json", "xml", "markdown", "yaml", "toml"].iter().any(|t| content_type.contains(t));
+    if !textual {
+        return Err(format!("{} is {}, not a text document", url, content_type));
+    }
+    let (body, truncated) = read_body(response, MAX_DOWNLOAD_BYTES).await?;
+    let raw = String::from_utf8_lossy(&body);
+    let is_html = content_type.contains("html") || (content_type.is_empty() && raw.trim_start().starts_with('<'));
+    let (title, text) = if is_html { html_to_markdown(&raw, Some(&final_url)) } else { (None, raw.into_owned()) };
+    Ok(FetchedDocument {
+        url: final_url.to_string(),
+        title,
+        text,
+        content_type: if is_html { "text/html".to_string() } else { content_type },
+        bytes: body.len() as u64,
+        truncated,
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// Fetch `url` as text, from the cache when it was fetched recently
+pub async fn fetch(url: &str) -> Result<FetchedDocument, String> {
+    if let Some(document) = cached(&DOCUMENTS, url) {
+        return Ok(document);
+    }
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{} is not an http(s) URL", url));
+    }
+    let client = client()?;
+    let mut error = String::new();
+    for candidate in candidates(&parsed) {
+        match download(&client, &candidate).await {
+            Ok(document) => {
+                remember(&DOCUMENTS, url.to_string(), document.clone());
+                return Ok(document);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "svg", "nav", "footer", "template", "iframe", "form", "button", "head"];
+const BLOCK_TAGS: &[&str] = &["p", "div", "section", "article", "main", "header", "aside", "blockquote", "table", "figure", "details", "dl"];
+const LINE_TAGS: &[&str] = &["tr", "dt", "dd", "summary", "caption"];
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 10).map(|end| &rest[1..=end]);
+        let decoded = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            "copy" => Some('©'),
+            _ => match entity.strip_prefix('#') {
+                Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32),
+                Some(dec) => dec.parse().ok().and_then(char::from_u32),
+                None => None,
+            },
+        });
+        match (entity, decoded) {
+            (Some(entity), Some(c)) => {
+                out.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Content between the first `<tag` and the last `</tag>`, searched ASCII-case-insensitively
+fn element<'a>(html: &'a str, lower: &str, tag: &str) -> Option<&'a str> {
+    let start = lower.find(&format!("<{}", tag))?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = lower.rfind(&format!("</{}>", tag)).filter(|end| *end >= start)?;
+    Some(&html[start..end])
+}
+
+struct MarkdownWriter<'a> {
+    out: String,
+    base: Option<&'a Url>,
+    /// The skipped element being passed over, and how deeply it is nested
+    skipping: Option<(String, usize)>,
+    pre: bool,
+    list_depth: usize,
+    /// Where each open link's text starts, and its target
+    links: Vec<(usize, Option<String>)>,
+}
+
+impl MarkdownWriter<'_> {
+    fn newline(&mut self) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn paragraph(&mut self) {
+        self.newline();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        if self.skipping.is_some() || raw.is_empty() {
+            return;
+        }
+        let text = decode_entities(raw);
+        if self.pre {
+            self.out.push_str(&text);
+            return;
+        }
+        let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let at_break = self.out.is_empty() || self.out.ends_with([' ', '\n']);
+        if text.starts_with(char::is_whitespace) && !at_break {
+            self.out.push(' ');
+        }
+        self.out.push_str(&words);
+        if !words.is_empty() && text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    fn link_target(&self, tag: &str) -> Option<String> {
+        static HREF: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap());
+        let captures = HREF.captures(tag)?;
+        let href = decode_entities(captures.get(1).or(captures.get(2)).or(captures.get(3))?.as_str().trim());
+        if href.starts_with('#') {
+            return None;
+        }
+        let url = match self.base {
+            Some(base) => base.join(&href).ok()?,
+            None => Url::parse(&href).ok()?,
+        };
+        matches!(url.scheme(), "http" | "https" | "mailto").then(|| url.to_string())
+    }
+
+    fn tag(&mut self, tag: &str) {
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if let Some((skipped, depth)) = &mut self.skipping {
+            if *skipped == name {
+                if !closing {
+                    *depth += 1;
+                } else if *depth == 0 {
+                    self.skipping = None;
+                } else {
+                    *depth -= 1;
+                }
+            }
+            return;
+        }
+        if name.is_empty() {
+            return;
+        }
+        if SKIPPED_TAGS.contains(&name.as_str()) {
+            if !closing && !tag.ends_with('/') {
+                self.skipping = Some((name, 0));
+            }
+            return;
+        }
+        match name.as_str() {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph();
+                if !closing {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.out.push_str(&format!("{} ", "#".repeat(level)));
+                }
+            }
+            "br" => self.newline(),
+            "hr" => {
+                self.paragraph();
+                self.out.push_str("---");
+                self.paragraph();
+            }
+            "ul" | "ol" => {
+                self.list_depth = if closing { self.list_depth.saturating_sub(1) } else { self.list_depth + 1 };
+                if self.list_depth == 0 {
+                    self.paragraph();
+                } else {
+                    self.newline();
+                }
+            }
+            "li" if !closing => {
+                self.newline();
+                self.out.push_str(&"  ".repeat(self.list_depth.saturating_sub(1)));
+                self.out.push_str("- ");
+            }
+            "pre" if !closing => {
+                self.paragraph();
+                self.out.push_str("```\n");
+                self.pre = true;
+            }
+            "pre" if self.pre => {
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```");
+                self.pre = false;
+                self.paragraph();
+            }
+            "code" if !self.pre => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" => self.out.push('*'),
+            "td" | "th" if closing => self.out.push_str(" | "),
+            "a" if !closing => {
+                let target = self.link_target(tag);
+                self.links.push((self.out.len(), target));
+            }
+            "a" => {
+                if let Some((start, Some(target))) = self.links.pop() {
+                    if start <= self.out.len() && !self.out[start..].trim().is_empty() {
+                        self.out.insert(start, '[');
+                        self.out.push_str(&format!("]({})", target));
+                    }
+                }
+            }
+            _ if BLOCK_TAGS.contains(&name.as_str()) => self.paragraph(),
+            _ if LINE_TAGS.contains(&name.as_str()) => self.newline(),
+            _ => {}
+        }
+    }
+}
+
+/// The page's title and its main content as Markdown. Relative links are
+/// resolved against `base`.
+pub fn html_to_markdown(html: &str, base: Option<&Url>) -> (Option<String>, String) {
+    let lower = html.to_ascii_lowercase();
+    let title = element(html, &lower, "title")
+        .map(|title| decode_entities(title).split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|title| !title.is_empty());
+    let content = ["main", "article", "body"]
+        .iter()
+        .find_map(|tag| element(html, &lower, tag))
+        .unwrap_or(html);
+
+    let mut writer = MarkdownWriter { out: String::new(), base, skipping: None, pre: false, list_depth: 0, links: Vec::new() };
+    let mut rest = content;
+    while let Some(lt) = rest.find('<') {
+        writer.text(&rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else { break };
+        writer.tag(&rest[1..gt]);
+        rest = &rest[gt + 1..];
+    }
+    writer.text(rest);
+
+    let mut markdown = String::new();
+    let mut blank = false;
+    for line in writer.out.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank = !markdown.is_empty();
+            continue;
+        }
+        if blank {
+            markdown.push('\n');
+            blank = false;
+        }
+        markdown.push_str(line);
+        markdown.push('\n');
+    }
+    (title, markdown)
+}
+
+/// Fetch a URL the way a context pack would, to preview it
+#[tauri::command]
+pub async fn fetch_context_url(url: String) -> Result<FetchedDocument, String> {
+    fetch(&url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_html_and_robots_parsing() {
+        let html = r#"<!DOCTYPE html><html><head><title>Guide &amp; Notes</title><style>p{}</style></head>
+            <body><nav><a href="/">Home</a></nav>
+            <main><h1>Install</h1><p>Run the <code>setup</code> script,
+            then read <a href="docs/next.html">the next   step</a>.</p>
+            <!-- hidden --><script>alert("x")</script>
+            <ul><li>One</li><li>Two &lt;2&gt;</li></ul>
+            <pre>fn main() {
+    println!("hi");
+}</pre></main><footer>Copyright</footer></body></html>"#;
+        let base = Url::parse("https://example.com/guide/index.html").unwrap();
+        let (title, markdown) = html_to_markdown(html, Some(&base));
+        assert_eq!(title.as_deref(), Some("Guide & Notes"));
+        assert_eq!(
+            markdown,
+            "# Install\n\nRun the `setup` script, then read [the next step](https://example.com/guide/docs/next.html).\n\n- One\n- Two <2>\n\n```\nfn main() {\n    println!(\"hi\");\n}\n```\n"
+        );
+
+        let robots = "User-agent: *\nDisallow: /private\nAllow: /private/ok$\n\nUser-agent: Googlebot\nDisallow: /\n";
+        let rules = parse_robots(robots, ROBOTS_AGENT);
+        assert!(rules.allows("/docs"));
+        assert!(!rules.allows("/private/notes"));
+        assert!(rules.allows("/private/ok"));
+        assert!(!rules.allows("/private/ok/more"));
+        let named = parse_robots("User-agent: *\nDisallow:\n\nUser-agent: amp-orchestra\nDisallow: /*.pdf$\n", ROBOTS_AGENT);
+        assert!(named.allows("/a.pdf.html") && !named.allows("/docs/a.pdf"));
+
+        let repo = Url::parse("https://github.com/owner/repo").unwrap();
+        assert_eq!(candidates(&repo)[0].as_str(), "https://raw.githubusercontent.com/owner/repo/HEAD/README.md");
+        let file = Url::parse("https://github.com/owner/repo/blob/main/docs/a.md").unwrap();
+        assert_eq!(candidates(&file)[0].as_str(), "https://raw.githubusercontent.com/owner/repo/main/docs/a.md");
+    }
+
+    /// A one-request-per-connection HTTP server counting hits per path
+    async fn serve(routes: HashMap<&'static str, (&'static str, String)>) -> (String, Arc<Mutex<HashMap<String, usize>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                *counter.lock().unwrap().entry(path.clone()).or_insert(0) += 1;
+                let response = match routes.get(path.as_str()) {
+                    Some((content_type, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        content_type,
+                        body.len(),
+                        body
+                    ),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (base, hits)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_honours_robots_limits_and_cache() {
+        let routes = HashMap::from([
+            ("/robots.txt", ("text/plain", "User-agent: *\nDisallow: /private\n".to_string())),
+            ("/docs", ("text/html; charset=utf-8", "<html><body><h2>Docs</h2><p>Hello</p></body></html>".to_string())),
+            ("/notes.md", ("text/markdown", "# Notes\n".to_string())),
+            ("/private/page", ("text/html", "<p>secret</p>".to_string())),
+            ("/big.txt", ("text/plain", "x".repeat(MAX_DOWNLOAD_BYTES + 10))),
+            ("/image.png", ("image/png", "png".to_string())),
+        ]);
+        let (base, hits) = serve(routes).await;
+
+        let docs = fetch(&format!("{}/docs", base)).await.unwrap();
+        assert_eq!(docs.text, "## Docs\n\nHello\n");
+        assert_eq!(docs.language(), "md");
+        assert_eq!(fetch(&format!("{}/docs", base)).await.unwrap(), docs);
+        assert_eq!(fetch(&format!("{}/notes.md", base)).await.unwrap().text, "# Notes\n");
+
+        let big = fetch(&format!("{}/big.txt", base)).await.unwrap();
+        assert!(big.truncated);
+        assert_eq!(big.text.len(), MAX_DOWNLOAD_BYTES);
+
+        let denied = fetch(&format!("{}/private/page", base)).await.unwrap_err();
+        assert!(denied.contains("robots.txt"), "{}", denied);
+        assert!(fetch(&format!("{}/image.png", base)).await.unwrap_err().contains("not a text document"));
+        assert!(fetch(&format!("{}/missing", base)).await.unwrap_err().contains("404"));
+        assert!(fetch("ftp://example.com/file").await.is_err());
+
+        let hits = hits.lock().unwrap();
+        assert_eq!(hits.get("/docs"), Some(&1));
+        assert_eq!(hits.get("/robots.txt"), Some(&1));
+        assert_eq!(hits.get("/private/page"), None);
+    }
+}
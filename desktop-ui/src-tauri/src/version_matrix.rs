@@ -0,0 +1,257 @@
+//! Which CLI versions, agent modes and toolboxes threads ran with, and when
+//!
+//! Every recorded spawn of a thread's CLI is one use of a combination of CLI
+//! version, the thread's agent mode and the digest of its toolbox. Uses are
+//! grouped by that combination, with the period it was in use and the ratings
+//! its threads received, so poor results can be traced to an old CLI or a
+//! changed toolbox. Threads started before spawns were recorded count once, at
+//! their creation, with an unknown CLI version.
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tauri::State;
+
+use crate::exporters::{export_report_to_string, ExportFormat, ReportTable};
+use crate::i18n::tr;
+use crate::profile_auth::ProfileManager;
+
+/// One spawn of a thread, or a thread without recorded spawns
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct VersionUse {
+    pub thread_id: String,
+    pub session_id: String,
+    pub spawn_id: Option<i64>,
+    pub cli_version: Option<String>,
+    pub agent_mode: Option<String>,
+    pub toolbox_digest: Option<String>,
+    pub used_at: String,
+    /// The thread's latest rating
+    pub rating: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionMatrixRow {
+    pub cli_version: Option<String>,
+    pub agent_mode: Option<String>,
+    pub toolbox_digest: Option<String>,
+    pub sessions: usize,
+    pub threads: usize,
+    pub spawns: usize,
+    pub first_used_at: String,
+    pub last_used_at: String,
+    pub rated_threads: usize,
+    pub average_rating: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionMatrix {
+    pub generated_at: String,
+    /// Most recently used combination first
+    pub rows: Vec<VersionMatrixRow>,
+}
+
+const USES_QUERY: &str = "
+    SELECT t.id AS thread_id, t.session_id, e.id AS spawn_id, e.cli_version, t.agent_mode,
+           (SELECT s.digest FROM toolbox_snapshots s WHERE s.thread_id = t.id ORDER BY s.id DESC LIMIT 1) AS toolbox_digest,
+           COALESCE(e.created_at, t.created_at) AS used_at,
+           (SELECT f.rating FROM thread_feedback f WHERE f.thread_id = t.id ORDER BY f.id DESC LIMIT 1) AS rating
+    FROM threads t
+    LEFT JOIN thread_spawn_envs e ON e.thread_id = t.id
+    WHERE COALESCE(e.created_at, t.created_at) >= ? AND COALESCE(e.created_at, t.created_at) < ?";
+
+/// Uses between `since` (inclusive) and `until`, both RFC 3339 or unbounded
+pub async fn load_uses(db: &SqlitePool, since: Option<&str>, until: Option<&str>) -> Result<Vec<VersionUse>, String> {
+    sqlx::query_as::<_, VersionUse>(USES_QUERY)
+        .bind(since.unwrap_or(""))
+        .bind(until.unwrap_or("\u{10FFFF}"))
+        .fetch_all(db)
+        .await
+        .map_err(|e| format!("Failed to load version history: {}", e))
+}
+
+type Combination = (Option<String>, Option<String>, Option<String>);
+
+#[derive(Default)]
+struct Group {
+    sessions: HashSet<String>,
+    ratings: HashMap<String, Option<i64>>,
+    spawns: usize,
+    first_used_at: Option<String>,
+    last_used_at: Option<String>,
+}
+
+pub fn build_matrix(uses: Vec<VersionUse>) -> Vec<VersionMatrixRow> {
+    let mut groups: BTreeMap<Combination, Group> = BTreeMap::new();
+    for use_ in uses {
+        let group = groups.entry((use_.cli_version, use_.agent_mode, use_.toolbox_digest)).or_default();
+        group.sessions.insert(use_.session_id);
+        group.ratings.insert(use_.thread_id, use_.rating);
+        group.spawns += usize::from(use_.spawn_id.is_some());
+        if group.first_used_at.as_ref().is_none_or(|first| use_.used_at < *first) {
+            group.first_used_at = Some(use_.used_at.clone());
+        }
+        if group.last_used_at.as_ref().is_none_or(|last| use_.used_at > *last) {
+            group.last_used_at = Some(use_.used_at);
+        }
+    }
+
+    let mut rows: Vec<VersionMatrixRow> = groups
+        .into_iter()
+        .map(|((cli_version, agent_mode, toolbox_digest), group)| {
+            let ratings: Vec<i64> = group.ratings.values().flatten().copied().collect();
+            VersionMatrixRow {
+                cli_version,
+                agent_mode,
+                toolbox_digest,
+                sessions: group.sessions.len(),
+                threads: group.ratings.len(),
+                spawns: group.spawns,
+                first_used_at: group.first_used_at.unwrap_or_default(),
+                last_used_at: group.last_used_at.unwrap_or_default(),
+                rated_threads: ratings.len(),
+                average_rating: (!ratings.is_empty()).then(|| ratings.iter().sum::<i64>() as f64 / ratings.len() as f64),
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+    rows
+}
+
+const COLUMNS: [&str; 10] = [
+    "cli_version",
+    "agent_mode",
+    "toolbox_digest",
+    "sessions",
+    "threads",
+    "spawns",
+    "first_used_at",
+    "last_used_at",
+    "rated_threads",
+    "average_rating",
+];
+
+pub fn report_table(matrix: &VersionMatrix) -> ReportTable {
+    let rows = matrix
+        .rows
+        .iter()
+        .map(|row| {
+            vec![
+                row.cli_version.clone(),
+                row.agent_mode.clone(),
+                row.toolbox_digest.clone(),
+                Some(row.sessions.to_string()),
+                Some(row.threads.to_string()),
+                Some(row.spawns.to_string()),
+                Some(row.first_used_at.clone()),
+                Some(row.last_used_at.clone()),
+                Some(row.rated_threads.to_string()),
+                row.average_rating.map(|r| format!("{:.2}", r)),
+            ]
+        })
+        .collect();
+    ReportTable { title: tr!("version-matrix-title"), generated_at: matrix.generated_at.clone(), columns: COLUMNS.to_vec(), rows }
+}
+
+async fn load_matrix(profile_manager: &ProfileManager, since: Option<&str>, until: Option<&str>) -> Result<VersionMatrix, String> {
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    let uses = load_uses(db, since, until).await?;
+    Ok(VersionMatrix { generated_at: chrono::Utc::now().to_rfc3339(), rows: build_matrix(uses) })
+}
+
+#[tauri::command]
+pub async fn get_version_matrix(
+    since: Option<String>,
+    until: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<VersionMatrix, String> {
+    load_matrix(&profile_manager, since.as_deref(), until.as_deref()).await
+}
+
+/// The matrix as html, csv, jsonl or markdown
+#[tauri::command]
+pub async fn export_version_matrix(
+    format: String,
+    since: Option<String>,
+    until: Option<String>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<String, String> {
+    let format = crate::exporters::export_commands::parse_format(&format)?;
+    let matrix = load_matrix(&profile_manager, since.as_deref(), until.as_deref()).await?;
+    export_report_to_string(&report_table(&matrix), format).map_err(|e| format!("Export error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+
+    fn use_(thread: &str, spawn: Option<i64>, version: Option<&str>, used_at: &str, rating: Option<i64>) -> VersionUse {
+        VersionUse {
+            thread_id: thread.to_string(),
+            session_id: format!("s-{}", thread),
+            spawn_id: spawn,
+            cli_version: version.map(String::from),
+            agent_mode: Some("smart".to_string()),
+            toolbox_digest: None,
+            used_at: used_at.to_string(),
+            rating,
+        }
+    }
+
+    #[test]
+    fn test_uses_group_by_combination() {
+        let rows = build_matrix(vec![
+            use_("t1", Some(1), Some("0.1.0"), "2026-01-01T00:00:00Z", Some(2)),
+            use_("t1", Some(2), Some("0.1.0"), "2026-01-03T00:00:00Z", Some(2)),
+            use_("t2", Some(3), Some("0.1.0"), "2026-01-02T00:00:00Z", Some(3)),
+            use_("t3", Some(4), Some("0.2.0"), "2026-02-01T00:00:00Z", Some(5)),
+            use_("t4", None, None, "2025-12-01T00:00:00Z", None),
+        ]);
+        let versions: Vec<Option<&str>> = rows.iter().map(|r| r.cli_version.as_deref()).collect();
+        assert_eq!(versions, vec![Some("0.2.0"), Some("0.1.0"), None]);
+
+        let old = &rows[1];
+        assert_eq!((old.sessions, old.threads, old.spawns, old.rated_threads), (2, 2, 3, 2));
+        assert_eq!((old.first_used_at.as_str(), old.last_used_at.as_str()), ("2026-01-01T00:00:00Z", "2026-01-03T00:00:00Z"));
+        assert_eq!(old.average_rating, Some(2.5));
+        assert_eq!((rows[2].spawns, rows[2].average_rating), (0, None));
+
+        let table = report_table(&VersionMatrix { generated_at: "now".to_string(), rows });
+        assert_eq!(table.rows[1][9].as_deref(), Some("2.50"));
+        assert_eq!(table.rows[2][0], None);
+    }
+
+    #[tokio::test]
+    async fn test_uses_load_from_spawns_and_threads() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO threads (id, session_id, context, agent_mode, created_at) VALUES
+                ('t1', 's1', 'production', 'smart', '2026-01-01T00:00:00Z'),
+                ('t2', 's1', 'production', 'rush', '2026-01-05T00:00:00Z');
+             INSERT INTO toolbox_digests (digest, tools) VALUES ('d1', '[]');
+             INSERT INTO toolbox_snapshots (thread_id, digest) VALUES ('t1', 'd1');
+             INSERT INTO thread_spawn_envs (thread_id, reason, env, cli_version, created_at) VALUES
+                ('t1', 'start', '{}', '0.1.0', '2026-01-01T00:00:01Z'),
+                ('t1', 'attach', '{}', '0.2.0', '2026-01-10T00:00:00Z');
+             INSERT INTO thread_feedback (thread_id, rating, env_hash) VALUES ('t1', 1, 'h'), ('t1', 4, 'h');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let uses = load_uses(&pool, None, None).await.unwrap();
+        assert_eq!(uses.len(), 3);
+        let rows = build_matrix(uses);
+        let keys: Vec<(Option<&str>, Option<&str>, Option<&str>)> = rows
+            .iter()
+            .map(|r| (r.cli_version.as_deref(), r.agent_mode.as_deref(), r.toolbox_digest.as_deref()))
+            .collect();
+        assert_eq!(keys, vec![(Some("0.2.0"), Some("smart"), Some("d1")), (None, Some("rush"), None), (Some("0.1.0"), Some("smart"), Some("d1"))]);
+        assert_eq!(rows[0].average_rating, Some(4.0));
+
+        let january = load_uses(&pool, Some("2026-01-02"), Some("2026-01-09")).await.unwrap();
+        assert_eq!(january.iter().map(|u| u.thread_id.as_str()).collect::<Vec<_>>(), vec!["t2"]);
+    }
+}
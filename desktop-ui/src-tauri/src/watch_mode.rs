@@ -0,0 +1,534 @@
+//! Watch mode: file changes that send a prompt to a thread
+//!
+//! A watch rule names a thread, globs relative to the thread's working
+//! directory and a prompt template. Matching changes are collected until the
+//! files have been quiet for the rule's debounce, then the prompt is sent with
+//! `{files}` replaced by the changed paths (one per line) and `{count}` by how
+//! many there are. Rules live in memory for as long as the app runs.
+//!
+//! Changes the agent makes must not trigger it again, so a change is ignored
+//! while the thread is generating a response, shortly after its turn ends, and
+//! when the path was written by one of the agent's own tool calls. As a last
+//! resort a rule that fires `max_triggers_per_hour` times within an hour is
+//! disabled and a `watch_mode_paused` event is emitted.
+
+use chrono::{DateTime, Duration, Utc};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::context_packs::{compile_glob, glob_matches, GlobSegment};
+
+pub const DEFAULT_DEBOUNCE_MS: u64 = 2000;
+pub const DEFAULT_MAX_TRIGGERS_PER_HOUR: u32 = 6;
+/// Changes this soon after the thread's turn ends are still the agent's
+const SETTLE_MS: i64 = 3000;
+/// How long a path the agent wrote is ignored
+const AGENT_WRITE_GRACE_SECS: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchRule {
+    pub id: String,
+    pub thread_id: String,
+    /// The thread's working directory; patterns are relative to it
+    pub root: String,
+    pub patterns: Vec<String>,
+    pub prompt: String,
+    pub debounce_ms: u64,
+    pub max_triggers_per_hour: u32,
+    pub enabled: bool,
+    pub created_at: String,
+    pub last_triggered_at: Option<String>,
+    pub trigger_count: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchRuleRequest {
+    pub thread_id: String,
+    pub patterns: Vec<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
+    #[serde(default)]
+    pub max_triggers_per_hour: Option<u32>,
+}
+
+/// A prompt ready to send
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WatchTrigger {
+    pub rule_id: String,
+    pub thread_id: String,
+    pub files: Vec<String>,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Poll {
+    /// Nothing pending, or the rule is gone or disabled
+    Idle,
+    /// Check again after this long
+    Wait(std::time::Duration),
+    Fire(WatchTrigger),
+    /// The rule hit its hourly limit and was disabled
+    Paused(WatchRule),
+}
+
+/// The prompt for a set of changed files
+pub fn render_prompt(template: &str, files: &[String]) -> String {
+    template.replace("{files}", &files.join("\n")).replace("{count}", &files.len().to_string())
+}
+
+struct RuleState {
+    rule: WatchRule,
+    root: PathBuf,
+    globs: Vec<Vec<GlobSegment>>,
+    pending: BTreeSet<String>,
+    last_change: Option<DateTime<Utc>>,
+    /// When the rule fired within the last hour
+    recent: VecDeque<DateTime<Utc>>,
+}
+
+impl RuleState {
+    /// Path of `path` relative to the rule's root when a pattern matches it
+    fn matching(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<&str> = relative
+            .components()
+            .map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        if parts.first() == Some(&".git") {
+            return None;
+        }
+        self.globs.iter().any(|glob| glob_matches(glob, &parts)).then(|| parts.join("/"))
+    }
+}
+
+/// Rule bookkeeping, kept free of Tauri so it can be exercised directly
+#[derive(Default)]
+pub struct WatchTable {
+    rules: HashMap<String, RuleState>,
+    agent_writes: HashMap<PathBuf, DateTime<Utc>>,
+    turn_ended: HashMap<String, DateTime<Utc>>,
+}
+
+impl WatchTable {
+    pub fn add(&mut self, request: WatchRuleRequest, root: &Path, now: DateTime<Utc>) -> Result<WatchRule, String> {
+        if request.patterns.is_empty() {
+            return Err("A watch rule needs at least one pattern".to_string());
+        }
+        if request.prompt.trim().is_empty() {
+            return Err("A watch rule needs a prompt".to_string());
+        }
+        let globs = request.patterns.iter().map(|p| compile_glob(p)).collect::<Result<Vec<_>, _>>()?;
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let rule = WatchRule {
+            id: uuid::Uuid::new_v4().to_string(),
+            thread_id: request.thread_id,
+            root: root.to_string_lossy().to_string(),
+            patterns: request.patterns,
+            prompt: request.prompt,
+            debounce_ms: request.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS),
+            max_triggers_per_hour: request.max_triggers_per_hour.unwrap_or(DEFAULT_MAX_TRIGGERS_PER_HOUR).max(1),
+            enabled: true,
+            created_at: now.to_rfc3339(),
+            last_triggered_at: None,
+            trigger_count: 0,
+        };
+        self.rules.insert(rule.id.clone(), RuleState {
+            rule: rule.clone(),
+            root,
+            globs,
+            pending: BTreeSet::new(),
+            last_change: None,
+            recent: VecDeque::new(),
+        });
+        Ok(rule)
+    }
+
+    pub fn remove(&mut self, rule_id: &str) -> Option<WatchRule> {
+        self.rules.remove(rule_id).map(|state| state.rule)
+    }
+
+    /// Enabling starts afresh: earlier changes and triggers are forgotten
+    pub fn set_enabled(&mut self, rule_id: &str, enabled: bool) -> Result<WatchRule, String> {
+        let state = self.rules.get_mut(rule_id).ok_or_else(|| format!("Watch rule {} not found", rule_id))?;
+        state.rule.enabled = enabled;
+        state.pending.clear();
+        state.last_change = None;
+        if enabled {
+            state.recent.clear();
+        }
+        Ok(state.rule.clone())
+    }
+
+    pub fn rules(&self, thread_id: Option<&str>) -> Vec<WatchRule> {
+        let mut rules: Vec<WatchRule> = self
+            .rules
+            .values()
+            .filter(|state| thread_id.is_none_or(|t| state.rule.thread_id == t))
+            .map(|state| state.rule.clone())
+            .collect();
+        rules.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        rules
+    }
+
+    /// Directories enabled rules need watched
+    pub fn watched_roots(&self) -> HashSet<PathBuf> {
+        self.rules.values().filter(|s| s.rule.enabled).map(|s| s.root.clone()).collect()
+    }
+
+    /// Remember a file written by the agent of `thread_id`; relative paths resolve against its rules' roots
+    pub fn agent_wrote(&mut self, thread_id: &str, raw_path: &str, now: DateTime<Utc>) {
+        let path = Path::new(raw_path);
+        let roots: HashSet<&PathBuf> = self.rules.values().filter(|s| s.rule.thread_id == thread_id).map(|s| &s.root).collect();
+        let paths: Vec<PathBuf> = if path.is_absolute() {
+            vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())]
+        } else {
+            roots.into_iter().map(|root| root.join(path)).collect()
+        };
+        for path in paths {
+            self.agent_writes.insert(path, now);
+        }
+    }
+
+    pub fn turn_ended(&mut self, thread_id: &str, now: DateTime<Utc>) {
+        self.turn_ended.insert(thread_id.to_string(), now);
+    }
+
+    /// Record a change. Returns the rules it made pending, which need polling.
+    pub fn on_change(&mut self, path: &Path, now: DateTime<Utc>, busy: &dyn Fn(&str) -> bool) -> Vec<String> {
+        self.agent_writes.retain(|_, at| (now - *at).num_seconds() <= AGENT_WRITE_GRACE_SECS);
+        if self.agent_writes.contains_key(path) {
+            return Vec::new();
+        }
+        let mut newly_pending = Vec::new();
+        for (id, state) in self.rules.iter_mut().filter(|(_, s)| s.rule.enabled) {
+            let Some(relative) = state.matching(path) else {
+                continue;
+            };
+            let thread_id = state.rule.thread_id.as_str();
+            let settling = self.turn_ended.get(thread_id).is_some_and(|at| (now - *at).num_milliseconds() < SETTLE_MS);
+            if settling || busy(thread_id) {
+                continue;
+            }
+            if state.pending.is_empty() {
+                newly_pending.push(id.clone());
+            }
+            state.pending.insert(relative);
+            state.last_change = Some(now);
+        }
+        newly_pending
+    }
+
+    /// Fire a rule once its files have been quiet for the debounce and its thread is idle
+    pub fn poll(&mut self, rule_id: &str, now: DateTime<Utc>, busy: &dyn Fn(&str) -> bool) -> Poll {
+        let Some(state) = self.rules.get_mut(rule_id) else {
+            return Poll::Idle;
+        };
+        let Some(last_change) = state.last_change.filter(|_| state.rule.enabled && !state.pending.is_empty()) else {
+            return Poll::Idle;
+        };
+        let debounce = Duration::milliseconds(state.rule.debounce_ms as i64);
+        let quiet = now - last_change;
+        if quiet < debounce {
+            return Poll::Wait((debounce - quiet).to_std().unwrap_or_default());
+        }
+        if busy(&state.rule.thread_id) {
+            return Poll::Wait(debounce.to_std().unwrap_or_default());
+        }
+
+        state.recent.retain(|at| now - *at < Duration::hours(1));
+        if state.recent.len() >= state.rule.max_triggers_per_hour as usize {
+            state.rule.enabled = false;
+            state.pending.clear();
+            state.last_change = None;
+            return Poll::Paused(state.rule.clone());
+        }
+
+        let files: Vec<String> = std::mem::take(&mut state.pending).into_iter().collect();
+        state.last_change = None;
+        state.recent.push_back(now);
+        state.rule.last_triggered_at = Some(now.to_rfc3339());
+        state.rule.trigger_count += 1;
+        Poll::Fire(WatchTrigger {
+            rule_id: rule_id.to_string(),
+            thread_id: state.rule.thread_id.clone(),
+            message: render_prompt(&state.rule.prompt, &files),
+            files,
+        })
+    }
+}
+
+fn thread_busy(thread_id: &str) -> bool {
+    crate::message_metrics::open_turn(thread_id).is_some()
+}
+
+#[derive(Default)]
+struct WatchState {
+    watcher: Option<RecommendedWatcher>,
+    roots: HashSet<PathBuf>,
+}
+
+/// Managed state shared by the stream readers, the watcher callback and the commands
+#[derive(Clone, Default)]
+pub struct WatchModeService {
+    table: Arc<Mutex<WatchTable>>,
+    watch: Arc<Mutex<WatchState>>,
+}
+
+impl WatchModeService {
+    /// Watch the roots of enabled rules and no others
+    fn sync_watches(&self, app: &AppHandle) {
+        let wanted = self.table.lock().unwrap().watched_roots();
+        let mut watch = self.watch.lock().unwrap();
+        if watch.watcher.is_none() && !wanted.is_empty() {
+            match self.create_watcher(app.clone()) {
+                Ok(watcher) => watch.watcher = Some(watcher),
+                Err(e) => {
+                    log::error!("Failed to start watch mode watcher: {}", e);
+                    return;
+                }
+            }
+        }
+        let WatchState { watcher, roots } = &mut *watch;
+        let Some(watcher) = watcher.as_mut() else {
+            return;
+        };
+        for root in roots.difference(&wanted) {
+            let _ = watcher.unwatch(root);
+        }
+        roots.retain(|root| wanted.contains(root));
+        for root in wanted {
+            if roots.contains(&root) {
+                continue;
+            }
+            match watcher.watch(&root, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    roots.insert(root);
+                }
+                Err(e) => log::warn!("Failed to watch {}: {}", root.display(), e),
+            }
+        }
+    }
+
+    fn create_watcher(&self, app: AppHandle) -> notify::Result<RecommendedWatcher> {
+        let service = self.clone();
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("Watch mode watcher error: {}", e);
+                    return;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                return;
+            }
+            let now = Utc::now();
+            let pending: Vec<String> = {
+                let mut table = service.table.lock().unwrap();
+                event.paths.iter().flat_map(|p| table.on_change(p, now, &thread_busy)).collect()
+            };
+            for rule_id in pending {
+                tauri::async_runtime::spawn(service.clone().run_pending(app.clone(), rule_id));
+            }
+        })
+    }
+
+    /// Wait out the rule's debounce and send its prompt
+    async fn run_pending(self, app: AppHandle, rule_id: String) {
+        loop {
+            let poll = self.table.lock().unwrap().poll(&rule_id, Utc::now(), &thread_busy);
+            match poll {
+                Poll::Idle => return,
+                Poll::Wait(wait) => tokio::time::sleep(wait).await,
+                Poll::Paused(rule) => {
+                    log::warn!("Watch rule {} on thread {} fired too often and was disabled", rule.id, rule.thread_id);
+                    self.sync_watches(&app);
+                    let _ = app.emit("watch_mode_paused", &rule);
+                    return;
+                }
+                Poll::Fire(trigger) => {
+                    let sent = crate::thread_session_commands::send_user_message(
+                        &trigger.thread_id,
+                        &trigger.message,
+                        &app.state::<crate::session_commands::AmpSessionMap>(),
+                        &app.state::<crate::profile_auth::ProfileManager>(),
+                        &app.state::<crate::session_activity::ActivityTracker>(),
+                    )
+                    .await;
+                    let error = sent.err();
+                    if let Some(e) = &error {
+                        log::warn!("Watch rule {} could not prompt thread {}: {}", trigger.rule_id, trigger.thread_id, e);
+                    }
+                    let _ = app.emit("watch_mode_triggered", serde_json::json!({
+                        "rule_id": trigger.rule_id,
+                        "thread_id": trigger.thread_id,
+                        "files": trigger.files,
+                        "error": error,
+                    }));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Note the agent's own writes and turn ends so they do not trigger its rules
+pub fn observe_stream_event(app: &AppHandle, thread_id: &str, event: &Value) {
+    let Some(service) = app.try_state::<WatchModeService>() else {
+        return;
+    };
+    let intents = crate::file_locks::write_intents(event);
+    let ended = event.get("type").and_then(|t| t.as_str()) == Some("result");
+    if intents.is_empty() && !ended {
+        return;
+    }
+    let now = Utc::now();
+    let mut table = service.table.lock().unwrap();
+    for intent in intents {
+        table.agent_wrote(thread_id, &intent.path, now);
+    }
+    if ended {
+        table.turn_ended(thread_id, now);
+    }
+}
+
+#[tauri::command]
+pub async fn add_watch_rule(
+    request: WatchRuleRequest,
+    app_handle: AppHandle,
+    watch_mode: State<'_, WatchModeService>,
+    file_locks: State<'_, crate::file_locks::FileLockService>,
+) -> Result<WatchRule, String> {
+    let root = file_locks
+        .session_root(&request.thread_id)
+        .ok_or_else(|| format!("Thread {} not found or not active", request.thread_id))?;
+    let rule = watch_mode.table.lock().unwrap().add(request, &root, Utc::now())?;
+    watch_mode.sync_watches(&app_handle);
+    Ok(rule)
+}
+
+#[tauri::command]
+pub async fn set_watch_rule_enabled(
+    rule_id: String,
+    enabled: bool,
+    app_handle: AppHandle,
+    watch_mode: State<'_, WatchModeService>,
+) -> Result<WatchRule, String> {
+    let rule = watch_mode.table.lock().unwrap().set_enabled(&rule_id, enabled)?;
+    watch_mode.sync_watches(&app_handle);
+    Ok(rule)
+}
+
+#[tauri::command]
+pub async fn remove_watch_rule(
+    rule_id: String,
+    app_handle: AppHandle,
+    watch_mode: State<'_, WatchModeService>,
+) -> Result<(), String> {
+    watch_mode
+        .table
+        .lock()
+        .unwrap()
+        .remove(&rule_id)
+        .ok_or_else(|| format!("Watch rule {} not found", rule_id))?;
+    watch_mode.sync_watches(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_watch_rules(
+    thread_id: Option<String>,
+    watch_mode: State<'_, WatchModeService>,
+) -> Result<Vec<WatchRule>, String> {
+    Ok(watch_mode.table.lock().unwrap().rules(thread_id.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn request(patterns: &[&str], prompt: &str) -> WatchRuleRequest {
+        WatchRuleRequest {
+            thread_id: "t1".to_string(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            prompt: prompt.to_string(),
+            debounce_ms: Some(1000),
+            max_triggers_per_hour: Some(2),
+        }
+    }
+
+    fn idle(_: &str) -> bool {
+        false
+    }
+
+    #[test]
+    fn test_changes_are_debounced_into_one_prompt() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let mut table = WatchTable::default();
+        let start = Utc::now();
+        let rule = table.add(request(&["reports/*.xml", "**/*.log"], "Tests failed in {count} files:\n{files}"), &root, start).unwrap();
+        assert!(table.add(request(&[], "x"), &root, start).is_err());
+
+        assert_eq!(table.on_change(&root.join("reports/junit.xml"), start, &idle), vec![rule.id.clone()]);
+        let later = start + Duration::milliseconds(500);
+        // Already pending, so nothing new to poll
+        assert!(table.on_change(&root.join("build/out.log"), later, &idle).is_empty());
+        assert!(table.on_change(&root.join("src/main.rs"), later, &idle).is_empty());
+        assert!(table.on_change(&root.join(".git/index.log"), later, &idle).is_empty());
+
+        assert_eq!(table.poll(&rule.id, later + Duration::milliseconds(200), &idle), Poll::Wait(std::time::Duration::from_millis(800)));
+        let Poll::Fire(trigger) = table.poll(&rule.id, later + Duration::seconds(1), &idle) else {
+            panic!("rule did not fire");
+        };
+        assert_eq!(trigger.files, vec!["build/out.log", "reports/junit.xml"]);
+        assert_eq!(trigger.message, "Tests failed in 2 files:\nbuild/out.log\nreports/junit.xml");
+        assert_eq!(table.poll(&rule.id, later + Duration::seconds(2), &idle), Poll::Idle);
+        assert_eq!(table.rules(Some("t1"))[0].trigger_count, 1);
+    }
+
+    #[test]
+    fn test_agent_changes_do_not_retrigger() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let mut table = WatchTable::default();
+        let mut now = Utc::now();
+        let rule = table.add(request(&["**/*.rs"], "Fix {files}"), &root, now).unwrap();
+        let busy = |thread: &str| thread == "t1";
+
+        // While the agent works, and right after, its changes are ignored
+        assert!(table.on_change(&root.join("src/lib.rs"), now, &busy).is_empty());
+        table.turn_ended("t1", now);
+        assert!(table.on_change(&root.join("src/lib.rs"), now + Duration::seconds(1), &idle).is_empty());
+        now += Duration::seconds(10);
+        table.agent_wrote("t1", "src/lib.rs", now);
+        assert!(table.on_change(&root.join("src/lib.rs"), now, &idle).is_empty());
+
+        // A user change waits for the thread to be idle
+        assert_eq!(table.on_change(&root.join("src/main.rs"), now, &idle), vec![rule.id.clone()]);
+        now += Duration::seconds(2);
+        assert!(matches!(table.poll(&rule.id, now, &busy), Poll::Wait(_)));
+        for _ in 0..2 {
+            assert!(matches!(table.poll(&rule.id, now, &idle), Poll::Fire(_)));
+            table.on_change(&root.join("src/main.rs"), now, &idle);
+            now += Duration::seconds(2);
+        }
+        // The hourly limit disables the rule
+        let Poll::Paused(paused) = table.poll(&rule.id, now, &idle) else {
+            panic!("rule was not paused");
+        };
+        assert!(!paused.enabled);
+        assert!(table.watched_roots().is_empty());
+        assert!(table.set_enabled(&rule.id, true).unwrap().enabled);
+    }
+}
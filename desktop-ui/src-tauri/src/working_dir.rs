@@ -0,0 +1,248 @@
+//! Validation for session working directories supplied by the frontend
+//!
+//! A working directory must exist, be a directory, resolve (after `..` and
+//! symlinks) to somewhere under one of the allowed roots, and not be a system
+//! location. Rejections carry a machine-readable `code` so the UI can explain why.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::app_state::AppState;
+
+/// System locations a session may never run in, even when under an allowed root
+#[cfg(not(windows))]
+const SENSITIVE_PATHS: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys", "/usr",
+    "/var/lib", "/var/log", "/var/run", "/System", "/Library", "/private/etc", "/private/var/db",
+];
+#[cfg(windows)]
+const SENSITIVE_PATHS: &[&str] = &["C:\\Windows", "C:\\Program Files", "C:\\Program Files (x86)", "C:\\ProgramData"];
+
+/// Credential directories under the user's home
+const SENSITIVE_HOME_DIRS: &[&str] = &[".ssh", ".gnupg", ".aws", ".kube", ".docker", ".config/gcloud"];
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum WorkingDirError {
+    #[error("Working directory is empty or malformed: {path:?}")]
+    Invalid { path: String },
+
+    #[error("Working directory does not exist: {path}")]
+    NotFound { path: String },
+
+    #[error("Working directory is not a directory: {path}")]
+    NotADirectory { path: String },
+
+    #[error("Working directory {path} is a protected system location")]
+    SensitivePath { path: String },
+
+    #[error("Working directory {path} is outside the allowed roots")]
+    OutsideAllowedRoots { path: String, allowed_roots: Vec<String> },
+}
+
+impl WorkingDirError {
+    /// JSON form returned to the frontend: the error fields plus a readable `message`
+    pub fn to_command_error(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("message".to_string(), serde_json::Value::String(self.to_string()));
+        }
+        value.to_string()
+    }
+}
+
+/// Roots used when none are configured: the home directory and the directory the app was launched from
+pub fn default_allowed_roots() -> Vec<PathBuf> {
+    dirs::home_dir().into_iter().chain(std::env::current_dir().ok()).collect()
+}
+
+/// Configured roots, or the defaults when the list is empty
+pub fn allowed_roots(configured: &[String]) -> Vec<PathBuf> {
+    if configured.is_empty() {
+        default_allowed_roots()
+    } else {
+        configured.iter().map(PathBuf::from).collect()
+    }
+}
+
+fn is_sensitive(path: &Path) -> bool {
+    if path.parent().is_none() {
+        // Filesystem root
+        return true;
+    }
+    let system = SENSITIVE_PATHS.iter().map(PathBuf::from);
+    let home = dirs::home_dir()
+        .into_iter()
+        .flat_map(|home| SENSITIVE_HOME_DIRS.iter().map(move |d| home.join(d)));
+    system
+        .chain(home)
+        .map(|p| p.canonicalize().unwrap_or(p))
+        .any(|p| path.starts_with(p))
+}
+
+/// Check a requested working directory and return its canonical path
+pub fn resolve_working_directory(raw: &str, allowed_roots: &[PathBuf]) -> Result<PathBuf, WorkingDirError> {
+    if raw.trim().is_empty() || raw.contains('\0') {
+        return Err(WorkingDirError::Invalid { path: raw.to_string() });
+    }
+
+    // Canonicalizing resolves `..` and symlinks, so the checks below see the real target
+    let canonical = Path::new(raw)
+        .canonicalize()
+        .map_err(|_| WorkingDirError::NotFound { path: raw.to_string() })?;
+    let display = canonical.to_string_lossy().to_string();
+
+    if !canonical.is_dir() {
+        return Err(WorkingDirError::NotADirectory { path: display });
+    }
+    if is_sensitive(&canonical) {
+        return Err(WorkingDirError::SensitivePath { path: display });
+    }
+
+    let inside_root = allowed_roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| canonical.starts_with(&root));
+    if !inside_root {
+        return Err(WorkingDirError::OutsideAllowedRoots {
+            path: display,
+            allowed_roots: allowed_roots.iter().map(|r| r.to_string_lossy().to_string()).collect(),
+        });
+    }
+
+    Ok(canonical)
+}
+
+/// Validate a working directory against the roots configured in app state
+pub fn validate_with_state(raw: &str, app_state: &AppState) -> Result<PathBuf, WorkingDirError> {
+    let configured = app_state.lock().unwrap().allowed_roots.clone();
+    resolve_working_directory(raw, &allowed_roots(&configured))
+}
+
+#[tauri::command]
+pub async fn validate_working_directory(
+    path: String,
+    app_state: State<'_, AppState>,
+) -> Result<String, String> {
+    validate_with_state(&path, &app_state)
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| e.to_command_error())
+}
+
+/// Effective allowed roots (the defaults when none are configured)
+#[tauri::command]
+pub async fn get_allowed_roots(app_state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let configured = app_state.lock().unwrap().allowed_roots.clone();
+    Ok(allowed_roots(&configured).iter().map(|r| r.to_string_lossy().to_string()).collect())
+}
+
+/// Replace the allowed roots. An empty list restores the defaults.
+#[tauri::command]
+pub async fn set_allowed_roots(
+    roots: Vec<String>,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut canonical_roots = Vec::with_capacity(roots.len());
+    for root in &roots {
+        let canonical = Path::new(root)
+            .canonicalize()
+            .map_err(|_| WorkingDirError::NotFound { path: root.clone() }.to_command_error())?;
+        if !canonical.is_dir() {
+            return Err(WorkingDirError::NotADirectory { path: root.clone() }.to_command_error());
+        }
+        if is_sensitive(&canonical) {
+            return Err(WorkingDirError::SensitivePath { path: root.clone() }.to_command_error());
+        }
+        canonical_roots.push(canonical.to_string_lossy().to_string());
+    }
+
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.allowed_roots = canonical_roots;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn project() -> (TempDir, PathBuf) {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        (dir, project)
+    }
+
+    #[test]
+    fn test_accepts_directory_inside_root() {
+        let (_dir, project) = project();
+        let roots = vec![project.clone()];
+
+        let resolved = resolve_working_directory(project.join("src").to_str().unwrap(), &roots).unwrap();
+        assert_eq!(resolved, project.join("src").canonicalize().unwrap());
+        // `..` that stays inside the root is fine
+        let inner = format!("{}/src/..", project.display());
+        assert!(resolve_working_directory(&inner, &roots).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_traversal() {
+        let (_dir, project) = project();
+        let roots = vec![project.clone()];
+
+        let escape = format!("{}/src/../..", project.display());
+        assert!(matches!(
+            resolve_working_directory(&escape, &roots),
+            Err(WorkingDirError::OutsideAllowedRoots { .. })
+        ));
+
+        let depth = project.components().count() + 2;
+        let to_etc = format!("{}/src/{}etc", project.display(), "../".repeat(depth));
+        if Path::new("/etc").is_dir() {
+            assert!(matches!(
+                resolve_working_directory(&to_etc, &[PathBuf::from("/")]),
+                Err(WorkingDirError::SensitivePath { .. })
+            ));
+        }
+        assert!(matches!(
+            resolve_working_directory("/", &[PathBuf::from("/")]),
+            Err(WorkingDirError::SensitivePath { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_symlink_escape() {
+        let (dir, project) = project();
+        let outside = dir.path().join("outside");
+        std::fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, project.join("link")).unwrap();
+
+        let result = resolve_working_directory(project.join("link").to_str().unwrap(), &[project]);
+        assert!(matches!(result, Err(WorkingDirError::OutsideAllowedRoots { .. })));
+    }
+
+    #[test]
+    fn test_rejects_missing_and_files() {
+        let (_dir, project) = project();
+        let file = project.join("README.md");
+        std::fs::write(&file, "hi").unwrap();
+        let roots = vec![project.clone()];
+
+        assert!(matches!(resolve_working_directory("", &roots), Err(WorkingDirError::Invalid { .. })));
+        assert!(matches!(
+            resolve_working_directory(project.join("missing").to_str().unwrap(), &roots),
+            Err(WorkingDirError::NotFound { .. })
+        ));
+        let err = resolve_working_directory(file.to_str().unwrap(), &roots).unwrap_err();
+        assert!(matches!(err, WorkingDirError::NotADirectory { .. }));
+
+        let json: serde_json::Value = serde_json::from_str(&err.to_command_error()).unwrap();
+        assert_eq!(json["code"], "not_a_directory");
+        assert!(json["message"].as_str().unwrap().contains("not a directory"));
+    }
+}
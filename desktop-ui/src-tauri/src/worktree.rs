@@ -1,14 +1,20 @@
 //! Low-level Git worktree management backend for session-scoped worktrees
 //! 
 //! This module provides direct Git worktree operations according to the Oracle's plan:
-//! - Uses `.amp-worktrees/<short-sid>` for directory naming
-//! - Uses `orchestra/<sid>` for branch naming
+//! - Uses `.amp-worktrees/<code>` for directory naming, where `<code>` is the
+//!   session's short code (first 8 characters of the ID for sessions without one)
+//! - Uses `orchestra/<code>` for branch naming (`orchestra/<sid>` without a code), or the
+//!   repository's branch template (see `repo_settings`)
 //! - Includes safety checks for uncommitted changes
 //! - Returns WorktreeMeta struct with path and branch info
 
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use serde::{Deserialize, Serialize};
+use unified_core::GitIdentityConfig;
+use crate::git_lfs::hydrate as hydrate_lfs;
+use crate::path_scope::{apply_sparse_checkout, PathScope};
+use crate::submodules::init_submodules;
 
 /// Metadata returned when creating a worktree
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +45,9 @@ pub enum WorktreeError {
     
     #[error("Invalid session ID: {session_id}")]
     InvalidSessionId { session_id: String },
+
+    #[error("Bootstrap hook failed: {command} - {output}")]
+    HookFailed { command: String, output: String },
     
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -53,7 +62,7 @@ pub type WorktreeResult<T> = std::result::Result<T, WorktreeError>;
 /// 
 /// # Arguments
 /// * `repo_path` - Path to the Git repository root
-/// * `session_id` - Session identifier (must be at least 8 characters)
+/// * `session_id` - Session identifier or short code (must be at least 8 characters)
 /// 
 /// # Returns
 /// WorktreeMeta with the created worktree information
@@ -62,7 +71,38 @@ pub type WorktreeResult<T> = std::result::Result<T, WorktreeError>;
 /// - Validates the repository has no uncommitted changes
 /// - Checks that the target worktree directory doesn't already exist
 /// - Validates session ID format
+///
+/// Commits in the worktree use the default session identity; see `create_with_options`.
 pub fn create(repo_path: &Path, session_id: &str) -> WorktreeResult<WorktreeMeta> {
+    let options = CreateOptions { identity: Some(GitIdentityConfig::default()), init_submodules: true, ..Default::default() };
+    create_with_options(repo_path, session_id, &options)
+}
+
+/// Optional setup applied to a new worktree
+#[derive(Debug, Clone, Default)]
+pub struct CreateOptions {
+    /// Author identity for commits in the worktree; `None` keeps the user's own
+    pub identity: Option<GitIdentityConfig>,
+    /// Check out only these directories (sparse checkout)
+    pub path_scope: Option<PathScope>,
+    /// Run `git submodule update --init --recursive` in the new worktree
+    pub init_submodules: bool,
+    /// Start the worktree from this branch instead of the repository's HEAD
+    pub base_branch: Option<String>,
+    /// Session branch name template, see `RepoSettings::branch_template`
+    pub branch_template: Option<String>,
+    /// Shell command run in the new worktree; the worktree is removed when it fails
+    pub bootstrap_hook: Option<String>,
+}
+
+/// Create a git worktree for a session with the given identity and path scope
+pub fn create_with_options(
+    repo_path: &Path,
+    session_id: &str,
+    options: &CreateOptions,
+) -> WorktreeResult<WorktreeMeta> {
+    let session_id = &crate::session_codes::resolve(session_id);
+
     // Debug logging
     log::info!("Creating worktree for session '{}' in repo: {}", session_id, repo_path.display());
     
@@ -79,9 +119,12 @@ pub fn create(repo_path: &Path, session_id: &str) -> WorktreeResult<WorktreeMeta
     // check_repository_clean(repo_path)?;
     
     // Generate paths and branch name according to Oracle's plan
-    let short_sid = &session_id[..8];
-    let worktree_dir = repo_path.join(".amp-worktrees").join(short_sid);
-    let branch_name = format!("orchestra/{}", session_id);
+    let worktree_dir = path_for(repo_path, session_id);
+    let branch_name = match (&options.branch_template, crate::session_codes::code_for(session_id)) {
+        (Some(template), _) => crate::repo_settings::render_branch_name(template, session_id),
+        (None, Some(code)) => format!("orchestra/{}", code),
+        (None, None) => format!("orchestra/{}", session_id),
+    };
     
     // Check if worktree already exists
     if worktree_dir.exists() {
@@ -93,16 +136,17 @@ pub fn create(repo_path: &Path, session_id: &str) -> WorktreeResult<WorktreeMeta
     // Ensure .amp-worktrees directory exists
     std::fs::create_dir_all(repo_path.join(".amp-worktrees"))?;
     
-    // Create worktree using `git worktree add --detach`
-    let output = Command::new("git")
-        .current_dir(repo_path)
-        .args([
-            "worktree",
-            "add",
-            "--detach",
-            worktree_dir.to_str().unwrap(),
-        ])
-        .output()?;
+    // Create worktree using `git worktree add --detach`; a scoped worktree is
+    // checked out after its sparse-checkout patterns are set
+    let mut add_args = vec!["worktree", "add", "--detach"];
+    if options.path_scope.is_some() {
+        add_args.push("--no-checkout");
+    }
+    add_args.push(worktree_dir.to_str().unwrap());
+    if let Some(base_branch) = &options.base_branch {
+        add_args.push(base_branch.as_str());
+    }
+    let output = crate::chaos::git_output(Command::new("git").current_dir(repo_path).args(&add_args))?;
     
     if !output.status.success() {
         return Err(WorktreeError::GitCommandFailed {
@@ -111,20 +155,59 @@ pub fn create(repo_path: &Path, session_id: &str) -> WorktreeResult<WorktreeMeta
         });
     }
     
+    if let Some(scope) = &options.path_scope {
+        if let Err(stderr) = apply_sparse_checkout(&worktree_dir, scope) {
+            discard_worktree(repo_path, &worktree_dir, None);
+            return Err(WorktreeError::GitCommandFailed { command: "git sparse-checkout set".to_string(), stderr });
+        }
+    }
+    
     // Create and switch to the session branch in the worktree
-    let output = Command::new("git")
-        .current_dir(&worktree_dir)
-        .args(["switch", "-c", &branch_name])
-        .output()?;
+    let output = match crate::chaos::git_output(Command::new("git").current_dir(&worktree_dir).args(["switch", "-c", &branch_name])) {
+        Ok(output) => output,
+        Err(e) => {
+            discard_worktree(repo_path, &worktree_dir, None);
+            return Err(e.into());
+        }
+    };
     
     if !output.status.success() {
         // Clean up worktree if branch creation fails
-        let _ = remove_worktree_directory(&worktree_dir);
+        discard_worktree(repo_path, &worktree_dir, None);
         return Err(WorktreeError::GitCommandFailed {
             command: format!("git switch -c {}", branch_name),
             stderr: String::from_utf8(output.stderr)?,
         });
     }
+
+    if let Some(identity) = &options.identity {
+        if let Err(e) = apply_git_identity(&worktree_dir, session_id, identity) {
+            discard_worktree(repo_path, &worktree_dir, Some(&branch_name));
+            return Err(e);
+        }
+    }
+
+    if options.init_submodules {
+        if let Err(e) = init_submodules(&worktree_dir) {
+            log::warn!("Submodule init failed in {}: {}", worktree_dir.display(), e);
+        }
+    }
+
+    // Missing LFS content is not fatal, but the agent must not see pointer files as the real thing
+    match hydrate_lfs(&worktree_dir) {
+        Ok(status) if !status.pointer_files.is_empty() => {
+            log::warn!("{} LFS files in {} are still pointer files", status.pointer_files.len(), worktree_dir.display());
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("LFS checkout failed in {}: {}", worktree_dir.display(), e),
+    }
+
+    if let Some(hook) = &options.bootstrap_hook {
+        if let Err(e) = run_bootstrap_hook(&worktree_dir, hook) {
+            discard_worktree(repo_path, &worktree_dir, Some(&branch_name));
+            return Err(e);
+        }
+    }
     
     Ok(WorktreeMeta {
         path: worktree_dir,
@@ -134,6 +217,39 @@ pub fn create(repo_path: &Path, session_id: &str) -> WorktreeResult<WorktreeMeta
     })
 }
 
+/// Run a repository's bootstrap command in a new worktree
+fn run_bootstrap_hook(worktree_dir: &Path, hook: &str) -> WorktreeResult<()> {
+    let mut command = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    let output = command
+        .arg(if cfg!(windows) { "/C" } else { "-c" })
+        .arg(hook)
+        .current_dir(worktree_dir)
+        .stdin(std::process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(WorktreeError::HookFailed {
+            command: hook.to_string(),
+            output: format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)).trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Write the session's author identity into the worktree's own git config
+fn apply_git_identity(worktree_dir: &Path, session_id: &str, identity: &GitIdentityConfig) -> WorktreeResult<()> {
+    for args in identity.git_config_commands(session_id) {
+        let output = crate::chaos::git_output(Command::new("git").current_dir(worktree_dir).args(&args))?;
+
+        if !output.status.success() {
+            return Err(WorktreeError::GitCommandFailed {
+                command: format!("git {}", args.join(" ")),
+                stderr: String::from_utf8(output.stderr)?,
+            });
+        }
+    }
+    Ok(())
+}
+
 /// Remove a git worktree and its associated branch
 /// 
 /// # Arguments
@@ -207,13 +323,19 @@ pub fn remove(worktree_path: &Path, branch_name: &str, force: bool) -> WorktreeR
 /// 
 /// # Arguments
 /// * `repo_path` - Path to the Git repository root
-/// * `session_id` - Session identifier
+/// * `session_id` - Session identifier or short code
 /// 
 /// # Returns
-/// The path where the worktree would be located
+/// The path where the worktree would be located. Worktrees created before the
+/// session had a short code keep their directory under the ID prefix.
 pub fn path_for(repo_path: &Path, session_id: &str) -> PathBuf {
-    let short_sid = &session_id[..session_id.len().min(8)];
-    repo_path.join(".amp-worktrees").join(short_sid)
+    let session_id = crate::session_codes::resolve(session_id);
+    let root = repo_path.join(".amp-worktrees");
+    let legacy = root.join(&session_id[..session_id.len().min(8)]);
+    match crate::session_codes::code_for(&session_id) {
+        Some(code) if !legacy.exists() => root.join(code),
+        _ => legacy,
+    }
 }
 
 /// Check if the repository has uncommitted changes
@@ -247,7 +369,7 @@ fn check_repository_clean(repo_path: &Path) -> WorktreeResult<()> {
 }
 
 /// Check if a specific worktree has uncommitted changes
-fn check_worktree_clean(worktree_path: &Path) -> WorktreeResult<()> {
+pub(crate) fn check_worktree_clean(worktree_path: &Path) -> WorktreeResult<()> {
     let output = Command::new("git")
         .current_dir(worktree_path)
         .args(["status", "--porcelain"])
@@ -269,7 +391,7 @@ fn check_worktree_clean(worktree_path: &Path) -> WorktreeResult<()> {
 }
 
 /// Find the root of the git repository
-fn find_repo_root(start_path: &Path) -> WorktreeResult<PathBuf> {
+pub(crate) fn find_repo_root(start_path: &Path) -> WorktreeResult<PathBuf> {
     let mut current = start_path;
     
     while let Some(parent) = current.parent() {
@@ -293,6 +415,18 @@ fn remove_worktree_directory(path: &Path) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Undo a partly created worktree so creating it again can succeed: Git keeps
+/// a worktree registered until pruned, and a branch once switched to
+fn discard_worktree(repo_path: &Path, worktree_dir: &Path, branch_name: Option<&str>) {
+    if let Err(e) = remove_worktree_directory(worktree_dir) {
+        log::warn!("Failed to remove {}: {}", worktree_dir.display(), e);
+    }
+    let _ = Command::new("git").current_dir(repo_path).args(["worktree", "prune"]).output();
+    if let Some(branch_name) = branch_name {
+        let _ = Command::new("git").current_dir(repo_path).args(["branch", "-D", branch_name]).output();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -360,7 +494,76 @@ mod tests {
         let worktree_readme = meta.path.join("README.md");
         assert!(worktree_readme.exists());
     }
+
+    #[test]
+    fn test_create_worktree_sets_session_identity() {
+        let (_temp_dir, repo_path) = create_test_repo();
+        let identity = GitIdentityConfig {
+            email_template: "bot+{session_id}@example.com".to_string(),
+            ..GitIdentityConfig::default()
+        };
+        let options = CreateOptions { identity: Some(identity), ..Default::default() };
+        let meta = create_with_options(&repo_path, "identity-12345678", &options).unwrap();
+
+        let git_config = |dir: &Path, key: &str| {
+            let output = Command::new("git").current_dir(dir).args(["config", key]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        assert_eq!(git_config(&meta.path, "user.name"), "Amp Session identity-12345678");
+        assert_eq!(git_config(&meta.path, "user.email"), "bot+identity-12345678@example.com");
+        assert_eq!(git_config(&repo_path, "user.name"), "Test User");
+    }
+
+    #[test]
+    fn test_create_worktree_with_path_scope() {
+        let (_temp_dir, repo_path) = create_test_repo();
+        for dir in ["services/api", "services/web", "libs/shared"] {
+            std::fs::create_dir_all(repo_path.join(dir)).unwrap();
+            std::fs::write(repo_path.join(dir).join("index.ts"), "export {}\n").unwrap();
+        }
+        Command::new("git").current_dir(&repo_path).args(["add", "."]).output().unwrap();
+        Command::new("git").current_dir(&repo_path).args(["commit", "-m", "Add packages"]).output().unwrap();
+
+        let scope = PathScope::new(&["services/api".to_string(), "libs/shared".to_string()]).unwrap();
+        let options = CreateOptions { path_scope: Some(scope.clone()), ..Default::default() };
+        let meta = create_with_options(&repo_path, "scoped-12345678", &options).unwrap();
+
+        assert!(meta.path.join("services/api/index.ts").exists());
+        assert!(meta.path.join("libs/shared/index.ts").exists());
+        assert!(meta.path.join("README.md").exists());
+        assert!(!meta.path.join("services/web").exists());
+        assert!(repo_path.join("services/web/index.ts").exists());
+        assert_eq!(crate::path_scope::read_scope(&meta.path), Some(scope));
+        assert_eq!(crate::path_scope::read_scope(&repo_path), None);
+    }
     
+    #[test]
+    fn test_create_worktree_with_repo_settings() {
+        let (_temp_dir, repo_path) = create_test_repo();
+        let git = |args: &[&str]| Command::new("git").current_dir(&repo_path).args(args).output().unwrap();
+        git(&["branch", "develop"]);
+        std::fs::write(repo_path.join("later.txt"), "not on develop\n").unwrap();
+        git(&["add", "later.txt"]);
+        git(&["commit", "-m", "After develop"]);
+
+        let options = CreateOptions {
+            base_branch: Some("develop".to_string()),
+            branch_template: Some("agents/{session_id}".to_string()),
+            bootstrap_hook: Some("echo ready > BOOTSTRAPPED".to_string()),
+            ..Default::default()
+        };
+        let meta = create_with_options(&repo_path, "settings-12345678", &options).unwrap();
+        assert_eq!(meta.branch, "agents/settings-12345678");
+        assert!(meta.path.join("BOOTSTRAPPED").exists());
+        assert!(!meta.path.join("later.txt").exists());
+
+        let failing = CreateOptions { bootstrap_hook: Some("exit 3".to_string()), ..Default::default() };
+        let result = create_with_options(&repo_path, "failing-12345678", &failing);
+        assert!(matches!(result, Err(WorktreeError::HookFailed { .. })));
+        assert!(!path_for(&repo_path, "failing-12345678").exists());
+        assert!(git(&["rev-parse", "--verify", "--quiet", "orchestra/failing-12345678"]).stdout.is_empty());
+    }
+
     #[test]
     fn test_create_worktree_invalid_session_id() {
         let (_temp_dir, repo_path) = create_test_repo();
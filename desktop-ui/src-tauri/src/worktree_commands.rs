@@ -2,14 +2,23 @@
 //! 
 //! These commands provide the frontend interface to the low-level worktree operations.
 
-use std::path::PathBuf;
-use crate::worktree::{self, WorktreeMeta};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use unified_core::GitIdentityConfig;
+use crate::app_state::AppState;
+use crate::list_query::{ListQuery, ListResult, ListSpec};
+use crate::profile_auth::ProfileManager;
+use crate::trash;
+use crate::path_scope::PathScope;
+use crate::worktree::{self, CreateOptions, WorktreeMeta};
+use crate::worktree_paths::WorktreePathResolver;
 
 /// Tauri command to create a git worktree for a session
 /// 
 /// # Arguments
 /// * `repo_path` - Path to the Git repository root
 /// * `session_id` - Session identifier (must be at least 8 characters)
+/// * `path_scope` - Optional repository-relative directories; only these are checked out
 /// 
 /// # Returns
 /// WorktreeMeta with the created worktree information or error
@@ -17,18 +26,91 @@ use crate::worktree::{self, WorktreeMeta};
 pub async fn create_git_worktree(
     repo_path: String,
     session_id: String,
+    path_scope: Option<Vec<String>>,
+    app_state: State<'_, AppState>,
+    profile_manager: State<'_, ProfileManager>,
+) -> Result<WorktreeMeta, String> {
+    let (identity, init_submodules) = {
+        let state = app_state.lock().unwrap();
+        (state.git_identity.clone(), state.init_submodules)
+    };
+    let settings = match profile_manager.db_pool.read().await.as_ref() {
+        Some(db) => crate::repo_settings::get_settings(db, Path::new(&repo_path)).await?,
+        None => Default::default(),
+    };
+    let options = CreateOptions {
+        identity,
+        path_scope: path_scope.as_deref().map(PathScope::new).transpose()?,
+        init_submodules,
+        base_branch: settings.base_branch,
+        branch_template: settings.branch_template,
+        bootstrap_hook: settings.bootstrap_hook,
+    };
+    let meta = create_worktree_with_options(repo_path.clone(), session_id, &options)?;
+    // Recorded so the worktree can be found again if the repository moves
+    if let Some(db) = profile_manager.db_pool.read().await.as_ref() {
+        if let Err(e) = crate::repo_relocation::record_worktree(db, &PathBuf::from(repo_path), &meta).await {
+            log::warn!("{}", e);
+        }
+    }
+    Ok(meta)
+}
+
+fn create_worktree_with_options(
+    repo_path: String,
+    session_id: String,
+    options: &CreateOptions,
 ) -> Result<WorktreeMeta, String> {
     let repo_path = PathBuf::from(repo_path);
     
-    worktree::create(&repo_path, &session_id)
+    worktree::create_with_options(&repo_path, &session_id, options)
         .map_err(|e| {
             log::error!("Failed to create worktree for session {}: {}", session_id, e);
             e.to_string()
         })
 }
 
+/// Get the git identity written into new session worktrees (`None` = user's own)
+#[tauri::command]
+pub async fn get_git_identity_config(app_state: State<'_, AppState>) -> Result<Option<GitIdentityConfig>, String> {
+    Ok(app_state.lock().unwrap().git_identity.clone())
+}
+
+/// Set the git identity for new session worktrees; existing worktrees keep theirs
+#[tauri::command]
+pub async fn set_git_identity_config(
+    config: Option<GitIdentityConfig>,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(identity) = &config {
+        if identity.name_template.trim().is_empty() || !identity.email_template.contains('@') {
+            return Err("Git identity needs a name and an email address".to_string());
+        }
+    }
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.git_identity = config;
+        state.clone()
+    };
+    to_save.save().await
+}
+
+/// Turn recursive submodule initialization of new session worktrees on or off
+#[tauri::command]
+pub async fn set_init_submodules(enabled: bool, app_state: State<'_, AppState>) -> Result<(), String> {
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.init_submodules = enabled;
+        state.clone()
+    };
+    to_save.save().await
+}
+
 /// Tauri command to remove a git worktree and its associated branch
 /// 
+/// The worktree directory is moved to the trash rather than deleted; the branch
+/// is deleted when the trash item is purged.
+/// 
 /// # Arguments
 /// * `worktree_path` - Path to the worktree to remove
 /// * `branch_name` - Name of the branch to delete
@@ -41,14 +123,25 @@ pub async fn remove_git_worktree(
     worktree_path: String,
     branch_name: String,
     force: bool,
+    app_handle: AppHandle,
+    profile_manager: State<'_, ProfileManager>,
 ) -> Result<(), String> {
     let worktree_path = PathBuf::from(worktree_path);
     
-    worktree::remove(&worktree_path, &branch_name, force)
+    if worktree_path.exists() && !force {
+        worktree::check_worktree_clean(&worktree_path).map_err(|e| e.to_string())?;
+    }
+    
+    let db = profile_manager.db_pool.read().await;
+    let db = db.as_ref().ok_or("Database not available")?;
+    
+    trash::trash_worktree(&app_handle, db, &worktree_path, &branch_name)
+        .await
         .map_err(|e| {
             log::error!("Failed to remove worktree {}: {}", worktree_path.display(), e);
-            e.to_string()
-        })
+            e
+        })?;
+    crate::repo_relocation::forget_worktree(db, &worktree_path).await
 }
 
 /// Tauri command to get the path where a worktree would be created for a session
@@ -58,14 +151,18 @@ pub async fn remove_git_worktree(
 /// * `session_id` - Session identifier
 /// 
 /// # Returns
-/// The path where the worktree would be located
+/// The path of the session's worktree if it has one, else where it would be located
 #[tauri::command]
 pub async fn get_worktree_path(
     repo_path: String,
     session_id: String,
+    worktree_paths: State<'_, WorktreePathResolver>,
 ) -> Result<String, String> {
     let repo_path = PathBuf::from(repo_path);
-    let worktree_path = worktree::path_for(&repo_path, &session_id);
+    let worktree_path = match worktree_paths.worktree(&session_id, Some(&repo_path)).await {
+        Some(path) => path,
+        None => worktree::path_for(&repo_path, &session_id),
+    };
     
     Ok(worktree_path.to_string_lossy().to_string())
 }
@@ -100,15 +197,26 @@ pub async fn check_repository_clean(repo_path: String) -> Result<bool, String> {
     Ok(status_output.trim().is_empty())
 }
 
+/// Fields `list_git_worktrees` can filter and sort on. Worktrees are not in the
+/// database, so the columns are unused and the query is applied in memory.
+const WORKTREE_LIST: ListSpec = ListSpec {
+    fields: &[("path", ""), ("branch", ""), ("commit", ""), ("is_bare", ""), ("is_detached", "")],
+    default_sort: &[],
+};
+
 /// Tauri command to list all existing worktrees in the repository
 /// 
 /// # Arguments
 /// * `repo_path` - Path to the Git repository root
+/// * `query` - Optional paging, sorting and filtering on `WORKTREE_LIST` fields
 /// 
 /// # Returns
-/// List of worktree paths and their associated branches
+/// Page of worktree paths and their associated branches
 #[tauri::command]
-pub async fn list_git_worktrees(repo_path: String) -> Result<Vec<GitWorktreeInfo>, String> {
+pub async fn list_git_worktrees(
+    repo_path: String,
+    query: Option<ListQuery>,
+) -> Result<ListResult<GitWorktreeInfo>, String> {
     use std::process::Command;
     
     let repo_path = PathBuf::from(repo_path);
@@ -171,7 +279,14 @@ pub async fn list_git_worktrees(repo_path: String) -> Result<Vec<GitWorktreeInfo
         worktrees.push(wt);
     }
     
-    Ok(worktrees)
+    query.unwrap_or_default().apply(worktrees, &WORKTREE_LIST, |wt, field| match field {
+        "path" => Some(wt.path.clone()),
+        "branch" => wt.branch.clone(),
+        "commit" => wt.commit.clone(),
+        "is_bare" => Some(wt.is_bare.to_string()),
+        "is_detached" => Some(wt.is_detached.to_string()),
+        _ => None,
+    })
 }
 
 /// Information about a Git worktree returned by list command
@@ -187,6 +302,7 @@ pub struct GitWorktreeInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::list_query::{FilterOp, ListFilter};
     use std::process::Command;
     use tempfile::TempDir;
     
@@ -233,15 +349,20 @@ mod tests {
         (temp_dir, repo_path)
     }
     
+    fn default_options() -> CreateOptions {
+        CreateOptions { identity: Some(GitIdentityConfig::default()), init_submodules: true, ..Default::default() }
+    }
+    
     #[tokio::test]
     async fn test_create_git_worktree_command() {
         let (_temp_dir, repo_path) = create_test_repo();
         let session_id = "test-session-12345678";
         
-        let result = create_git_worktree(
+        let result = create_worktree_with_options(
             repo_path.to_string_lossy().to_string(),
-            session_id.to_string()
-        ).await;
+            session_id.to_string(),
+            &default_options()
+        );
         
         assert!(result.is_ok());
         let meta = result.unwrap();
@@ -284,25 +405,35 @@ mod tests {
         let (_temp_dir, repo_path) = create_test_repo();
         
         // Initially should have just the main worktree
-        let result = list_git_worktrees(repo_path.to_string_lossy().to_string()).await;
+        let result = list_git_worktrees(repo_path.to_string_lossy().to_string(), None).await;
         assert!(result.is_ok());
         
-        let worktrees = result.unwrap();
+        let worktrees = result.unwrap().items;
         assert!(!worktrees.is_empty());
         
         // Create a session worktree
         let session_id = "test-session-12345678";
-        let _meta = create_git_worktree(
+        let _meta = create_worktree_with_options(
             repo_path.to_string_lossy().to_string(),
-            session_id.to_string()
-        ).await.unwrap();
+            session_id.to_string(),
+            &default_options()
+        ).unwrap();
         
         // Now should have 2 worktrees
-        let result = list_git_worktrees(repo_path.to_string_lossy().to_string()).await;
+        let result = list_git_worktrees(repo_path.to_string_lossy().to_string(), None).await;
         assert!(result.is_ok());
         
-        let worktrees = result.unwrap();
+        let worktrees = result.unwrap().items;
         assert_eq!(worktrees.len(), 2);
+
+        // Filters and paging apply to the parsed list
+        let query = ListQuery {
+            page_size: Some(1),
+            filters: vec![ListFilter { field: "path".to_string(), op: FilterOp::Contains, value: Some(".amp-worktrees".to_string()) }],
+            ..Default::default()
+        };
+        let page = list_git_worktrees(repo_path.to_string_lossy().to_string(), Some(query)).await.unwrap();
+        assert_eq!((page.total, page.items.len(), page.next_cursor), (1, 1, None));
     }
     
     #[tokio::test]
@@ -311,10 +442,11 @@ mod tests {
         let session_id = "test-session-12345678";
         
         // Create worktree
-        let meta = create_git_worktree(
+        let meta = create_worktree_with_options(
             repo_path.to_string_lossy().to_string(),
-            session_id.to_string()
-        ).await.unwrap();
+            session_id.to_string(),
+            &default_options()
+        ).unwrap();
         
         assert!(PathBuf::from(&meta.path).exists());
         
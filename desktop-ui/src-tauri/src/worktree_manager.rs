@@ -1,13 +1,18 @@
 //! Tauri integration for the WorktreeManager from unified-core
 //! Provides WorktreeGuard and integration with session lifecycle.
 
+use dashmap::DashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::RwLock;
-use unified_core::{WorktreeManager, WorktreeManagerConfig, WorktreeError, WorktreeInfo, WorktreeMetrics};
+use tokio_util::sync::CancellationToken;
+use unified_core::{ignore_progress, CheckoutEstimate, CheckoutOptions, GitIdentityConfig, ProgressFn, WorktreeManager, WorktreeManagerConfig, WorktreeError, WorktreeInfo, WorktreeMetrics, REPO_CONTEXT_TEMPLATE_DIR};
 use unified_core::persistence::InMemoryStore;
 use unified_core::SessionId;
 
+use crate::worktree_paths::WorktreePathResolver;
+
 /// Guard that ensures worktree cleanup when dropped
 pub struct WorktreeGuard {
     session_id: SessionId,
@@ -60,6 +65,8 @@ pub struct WorktreeConfig {
     pub repo_root: PathBuf,
     pub base_branch: String,
     pub branch_name_template: Option<String>,
+    /// Sparse-checkout and partial clone settings for `repo_root`
+    pub checkout: CheckoutOptions,
 }
 
 impl Default for WorktreeConfig {
@@ -68,6 +75,7 @@ impl Default for WorktreeConfig {
             repo_root: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             base_branch: "main".to_string(),
             branch_name_template: None,
+            checkout: CheckoutOptions::default(),
         }
     }
 }
@@ -77,6 +85,8 @@ impl Default for WorktreeConfig {
 pub struct TauriWorktreeManager {
     manager: Arc<RwLock<WorktreeManager>>,
     config: WorktreeConfig,
+    /// Worktrees being created, by session
+    creating: Arc<DashMap<SessionId, CancellationToken>>,
 }
 
 impl TauriWorktreeManager {
@@ -85,9 +95,11 @@ impl TauriWorktreeManager {
         let wt_config = WorktreeManagerConfig {
             repo_root: config.repo_root.clone(),
             worktrees_base_dir: config.repo_root.join(".worktrees"),
-            agent_context_template_dir: None,
+            agent_context_template_dir: Some(PathBuf::from(REPO_CONTEXT_TEMPLATE_DIR)),
             auto_cleanup_orphans: true,
             max_concurrent_operations: 10,
+            git_identity: Some(GitIdentityConfig::default()),
+            checkout: config.checkout.clone(),
         };
         
         let store = Arc::new(InMemoryStore::default());
@@ -98,21 +110,60 @@ impl TauriWorktreeManager {
         Ok(Self {
             manager,
             config,
+            creating: Arc::new(DashMap::new()),
         })
     }
 
     /// Create a session with worktree isolation
+    ///
+    /// The repository's context template, if any, is rendered into the
+    /// worktree's AGENT_CONTEXT with `prompt`.
     pub async fn create_session_worktree(
         &self,
         session_id: &SessionId,
         base_branch: Option<&str>,
+        prompt: Option<&str>,
+    ) -> Result<WorktreeGuard, WorktreeError> {
+        self.create(session_id, base_branch, prompt, ignore_progress()).await
+    }
+
+    /// `create_session_worktree` emitting a `worktree_progress` event per stage
+    pub async fn create_session_worktree_with_progress(
+        &self,
+        app: &AppHandle,
+        session_id: &SessionId,
+        base_branch: Option<&str>,
+        prompt: Option<&str>,
+    ) -> Result<WorktreeGuard, WorktreeError> {
+        let resolver = WorktreePathResolver::of(app);
+        let (app, id) = (app.clone(), session_id.clone());
+        let progress: ProgressFn = Arc::new(move |stage| {
+            let _ = app.emit("worktree_progress", serde_json::json!({ "sessionId": id, "progress": stage }));
+        });
+        let guard = self.create(session_id, base_branch, prompt, progress).await?;
+        if let Err(e) = resolver.record(session_id, &self.config.repo_root, guard.worktree_path()).await {
+            log::warn!("{}", e);
+        }
+        Ok(guard)
+    }
+
+    async fn create(
+        &self,
+        session_id: &SessionId,
+        base_branch: Option<&str>,
+        prompt: Option<&str>,
+        progress: ProgressFn,
     ) -> Result<WorktreeGuard, WorktreeError> {
         let base_branch = base_branch.unwrap_or(&self.config.base_branch);
+        let cancel = CancellationToken::new();
+        self.creating.insert(session_id.clone(), cancel.clone());
 
         let manager = self.manager.read().await;
         let worktree_info = manager
-            .create_session_worktree(session_id, base_branch)
-            .await?;
+            .create_session_worktree_with_progress(session_id, base_branch, prompt, &progress, &cancel)
+            .await;
+        self.creating.remove(session_id);
+        let worktree_info = worktree_info?;
 
         log::info!(
             "Created worktree for session {} at {}",
@@ -127,6 +178,24 @@ impl TauriWorktreeManager {
         ))
     }
 
+    /// Stop creating a session's worktree; false when none is being created
+    pub fn cancel_creation(&self, session_id: &str) -> bool {
+        match self.creating.get(session_id) {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pre-flight size of a worktree from `base_branch`, or the configured base branch
+    pub async fn estimate(&self, base_branch: Option<&str>) -> Result<CheckoutEstimate, WorktreeError> {
+        let base_branch = base_branch.unwrap_or(&self.config.base_branch);
+        let manager = self.manager.read().await;
+        manager.estimate_session_worktree(base_branch).await
+    }
+
     /// List all active worktrees
     pub async fn list_worktrees(&self) -> Result<Vec<WorktreeInfo>, WorktreeError> {
         let manager = self.manager.read().await;
@@ -152,3 +221,19 @@ pub async fn init_worktree_manager() -> Result<TauriWorktreeManager, WorktreeErr
     let config = WorktreeConfig::default();
     TauriWorktreeManager::new(config).await
 }
+
+fn worktree_manager(app_handle: &AppHandle) -> Result<tauri::State<'_, TauriWorktreeManager>, String> {
+    app_handle.try_state::<TauriWorktreeManager>().ok_or_else(|| "Worktree manager not available".to_string())
+}
+
+/// Files and bytes a new session worktree would check out
+#[tauri::command]
+pub async fn estimate_session_worktree(base_branch: Option<String>, app_handle: AppHandle) -> Result<CheckoutEstimate, String> {
+    worktree_manager(&app_handle)?.estimate(base_branch.as_deref()).await.map_err(|e| e.to_string())
+}
+
+/// Cancel the worktree being created for a session, removing what was checked out
+#[tauri::command]
+pub async fn cancel_session_worktree(session_id: String, app_handle: AppHandle) -> Result<bool, String> {
+    Ok(worktree_manager(&app_handle)?.cancel_creation(&session_id))
+}
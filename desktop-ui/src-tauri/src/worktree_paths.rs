@@ -0,0 +1,233 @@
+//! Where a session's worktree is
+//!
+//! Worktrees used to be found by directory convention alone: the command
+//! modules looked for `.amp-worktrees/<code>` (or the ID's first 8 characters)
+//! under the repository containing the app's current directory, while
+//! unified-core's manager creates `.worktrees/<id>`, so the same session could
+//! resolve to different directories depending on the command. Every lookup now
+//! goes through `WorktreePathResolver`, which answers from `session_worktrees`,
+//! the record made when the worktree was created and rewritten when the
+//! repository moves. The conventions are only a fallback for worktrees created
+//! before they were recorded; a worktree found that way is recorded, so the
+//! database answers from then on.
+//!
+//! This module is also built into the lib target, which manages no resolver;
+//! there `of` returns one without a database that only knows the conventions.
+
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::RwLock;
+
+/// Directories sessions' worktrees are created under, relative to the repository root
+const WORKTREE_DIRS: [&str; 2] = [".amp-worktrees", ".worktrees"];
+
+#[derive(Clone, Default)]
+pub struct WorktreePathResolver {
+    /// The profile manager's pool, open once the database is
+    db: Arc<RwLock<Option<SqlitePool>>>,
+}
+
+impl WorktreePathResolver {
+    pub fn new(db: Arc<RwLock<Option<SqlitePool>>>) -> Self {
+        Self { db }
+    }
+
+    /// A resolver on an open pool, for callers already holding the profile manager's lock
+    pub fn for_pool(db: SqlitePool) -> Self {
+        Self::new(Arc::new(RwLock::new(Some(db))))
+    }
+
+    /// The app's resolver, or one knowing only the conventions when it has none
+    pub fn of(app: &AppHandle) -> Self {
+        app.try_state::<Self>().map(|resolver| resolver.inner().clone()).unwrap_or_default()
+    }
+
+    /// The existing worktree of a session, given by ID or short code. Conventions
+    /// are looked up under `repo_root`, by default the repository containing the
+    /// current directory.
+    pub async fn worktree(&self, session: &str, repo_root: Option<&Path>) -> Option<PathBuf> {
+        let db = self.db.read().await.clone();
+        let (session_id, code) = match &db {
+            Some(db) => session_identity(db, session).await,
+            None => (session.to_string(), None),
+        };
+        if let Some(path) = match &db {
+            Some(db) => recorded(db, &session_id).await,
+            None => None,
+        } {
+            return Some(path);
+        }
+
+        let repo_root = match repo_root {
+            Some(root) => root.to_path_buf(),
+            None => find_repo_root(&std::env::current_dir().ok()?)?,
+        };
+        let path = convention_candidates(&repo_root, &session_id, code.as_deref()).into_iter().find(|p| p.is_dir())?;
+        if let Some(db) = &db {
+            match record(db, &session_id, &repo_root, &path, None).await {
+                Ok(()) => log::info!("Recorded worktree {} of session {}", path.display(), session_id),
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        Some(path)
+    }
+
+    /// Directory a session's processes run in: its worktree, else the root of
+    /// the repository containing `start`, else `start` itself
+    pub async fn working_dir(&self, session: Option<&str>, start: &Path) -> PathBuf {
+        let repo_root = find_repo_root(start);
+        if let Some(session) = session {
+            if let Some(path) = self.worktree(session, repo_root.as_deref()).await {
+                return path;
+            }
+        }
+        repo_root.unwrap_or_else(|| start.to_path_buf())
+    }
+
+    /// `working_dir` starting from the app's current directory
+    pub async fn working_dir_from_current(&self, session: Option<&str>) -> PathBuf {
+        let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        self.working_dir(session, &current_dir).await
+    }
+
+    /// Record a worktree made outside `worktree::create_with_options`
+    pub async fn record(&self, session_id: &str, repo_root: &Path, worktree: &Path) -> Result<(), String> {
+        match self.db.read().await.as_ref() {
+            Some(db) => record(db, session_id, repo_root, worktree, None).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Session ID and short code of a session given by either
+async fn session_identity(db: &SqlitePool, session: &str) -> (String, Option<String>) {
+    let row = sqlx::query_as::<_, (String, Option<String>)>("SELECT id, short_code FROM sessions WHERE id = ? OR short_code = ?")
+        .bind(session)
+        .bind(session.trim().to_lowercase())
+        .fetch_optional(db)
+        .await;
+    match row {
+        Ok(Some(identity)) => identity,
+        _ => (session.to_string(), None),
+    }
+}
+
+/// The recorded worktree of a session, if it still exists
+pub async fn recorded(db: &SqlitePool, session_id: &str) -> Option<PathBuf> {
+    let path: String = sqlx::query_scalar("SELECT worktree_path FROM session_worktrees WHERE session_id = ?")
+        .bind(session_id)
+        .fetch_optional(db)
+        .await
+        .ok()??;
+    Some(PathBuf::from(path)).filter(|p| p.is_dir())
+}
+
+/// Record where a session's worktree is; without `branch` the worktree's checked-out branch is stored
+pub async fn record(db: &SqlitePool, session_id: &str, repo_root: &Path, worktree: &Path, branch: Option<&str>) -> Result<(), String> {
+    let branch = match branch {
+        Some(branch) => branch.to_string(),
+        None => current_branch(worktree).unwrap_or_default(),
+    };
+    sqlx::query(
+        "INSERT OR REPLACE INTO session_worktrees (session_id, repo_root, worktree_path, branch_name) VALUES (?, ?, ?, ?)",
+    )
+    .bind(session_id)
+    .bind(repo_root.to_string_lossy())
+    .bind(worktree.to_string_lossy())
+    .bind(branch)
+    .execute(db)
+    .await
+    .map_err(|e| format!("Failed to record worktree: {}", e))?;
+    Ok(())
+}
+
+/// Where a session's worktree would be by each convention, oldest first
+fn convention_candidates(repo_root: &Path, session_id: &str, code: Option<&str>) -> Vec<PathBuf> {
+    let [amp_worktrees, worktrees] = WORKTREE_DIRS.map(|dir| repo_root.join(dir));
+    let mut candidates = vec![amp_worktrees.join(session_id.chars().take(8).collect::<String>())];
+    candidates.extend(code.map(|code| amp_worktrees.join(code)));
+    candidates.push(worktrees.join(session_id));
+    candidates
+}
+
+fn current_branch(worktree: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(worktree)
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The Git repository containing `start`
+fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| dir.join(".git").exists()).map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::test_pool;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_conventions_truncate_by_character() {
+        let candidates = convention_candidates(Path::new("/repo"), "aéééééééé", None);
+        assert_eq!(candidates[0], Path::new("/repo").join(WORKTREE_DIRS[0]).join("aééééééé"));
+    }
+
+    #[tokio::test]
+    async fn test_record_wins_over_conventions() {
+        let pool = test_pool().await;
+        let repo = TempDir::new().unwrap();
+        let session_id = "0123456789abcdef";
+        sqlx::query("INSERT INTO sessions (id, short_code) VALUES (?, '0kq3fa-7hx2')")
+            .bind(session_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let resolver = WorktreePathResolver::new(Arc::new(RwLock::new(Some(pool.clone()))));
+        assert_eq!(resolver.worktree(session_id, Some(repo.path())).await, None);
+
+        // Found by convention, by ID or code, and recorded
+        let by_manager = repo.path().join(".worktrees").join(session_id);
+        std::fs::create_dir_all(&by_manager).unwrap();
+        assert_eq!(resolver.worktree("0KQ3FA-7HX2", Some(repo.path())).await, Some(by_manager.clone()));
+        assert_eq!(recorded(&pool, session_id).await, Some(by_manager.clone()));
+
+        // The record wins over a directory the older convention would pick
+        std::fs::create_dir_all(repo.path().join(".amp-worktrees/01234567")).unwrap();
+        assert_eq!(resolver.worktree(session_id, Some(repo.path())).await, Some(by_manager.clone()));
+        assert_eq!(resolver.working_dir(Some(session_id), repo.path()).await, by_manager);
+    }
+
+    #[tokio::test]
+    async fn test_working_dir_falls_back_to_repository_and_backfills_legacy_rows() {
+        let repo = TempDir::new().unwrap();
+        std::fs::create_dir(repo.path().join(".git")).unwrap();
+        let nested = repo.path().join("src/app");
+        std::fs::create_dir_all(&nested).unwrap();
+        let offline = WorktreePathResolver::default();
+        assert_eq!(offline.working_dir(Some("unknown"), &nested).await, repo.path());
+        assert_eq!(offline.working_dir(None, &nested).await, repo.path());
+
+        let pool = test_pool().await;
+        let legacy = repo.path().join(".amp-worktrees/abcdef12");
+        std::fs::create_dir_all(&legacy).unwrap();
+        sqlx::query(
+            "INSERT INTO worktrees (id, session_id, repo_root, base_branch, branch_name, worktree_path, created_at)
+             VALUES ('w1', 'abcdef12-0000', ?, 'main', 'orchestra/abcdef12', ?, '2026-01-01')",
+        )
+        .bind(repo.path().to_string_lossy())
+        .bind(legacy.to_string_lossy())
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(include_str!("../migrations/035_backfill_session_worktrees.sql")).execute(&pool).await.unwrap();
+        assert_eq!(recorded(&pool, "abcdef12-0000").await, Some(legacy));
+    }
+}
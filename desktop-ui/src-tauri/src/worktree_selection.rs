@@ -0,0 +1,266 @@
+//! Choosing which agent changes in a session worktree land
+//!
+//! The index of the session worktree holds the approved files: `stage_files`
+//! adds paths to it and `unstage_files` takes them out again, so commit message
+//! drafts, checkpoints and merge-back only see what was staged. `discard_files`
+//! reverts paths to `HEAD`, optionally writing their changes to a patch file
+//! first so a rejected change can still be applied by hand later.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::commit_message::{git, session_worktree};
+use crate::path_scope::read_scope;
+use crate::submodules::submodule_status;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedFile {
+    pub path: String,
+    /// Two-letter `git status --porcelain` code, index then worktree
+    pub status: String,
+    pub staged: bool,
+    pub untracked: bool,
+    /// The path is a submodule whose pointer or content changed
+    pub submodule: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscardResult {
+    pub discarded: Vec<String>,
+    pub patch_path: Option<String>,
+}
+
+/// Parse `git status --porcelain` output
+pub fn parse_status(porcelain: &str) -> Vec<ChangedFile> {
+    porcelain
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let code = &line[..2];
+            let path = line[3..].rsplit(" -> ").next().unwrap_or(&line[3..]).to_string();
+            ChangedFile {
+                path,
+                status: code.to_string(),
+                staged: !matches!(code.as_bytes()[0], b' ' | b'?'),
+                untracked: code == "??",
+                submodule: false,
+            }
+        })
+        .collect()
+}
+
+pub fn changed_files(worktree: &Path) -> Result<Vec<ChangedFile>, String> {
+    let mut changes = parse_status(&git(worktree, &["status", "--porcelain", "--untracked-files=all"])?);
+    let submodules = submodule_status(worktree)?;
+    for change in &mut changes {
+        change.submodule = submodules.iter().any(|s| s.path == change.path);
+    }
+    Ok(changes)
+}
+
+/// Select the changed files named by `paths`. Paths must be relative to the
+/// worktree and currently changed.
+pub fn select<'a>(changes: &'a [ChangedFile], paths: &[String]) -> Result<Vec<&'a ChangedFile>, String> {
+    if paths.is_empty() {
+        return Err("No paths given".to_string());
+    }
+    paths
+        .iter()
+        .map(|raw| {
+            let path = Path::new(raw);
+            if path.is_absolute() || path.components().any(|c| matches!(c, Component::ParentDir)) {
+                return Err(format!("Path must be relative to the worktree: {}", raw));
+            }
+            changes
+                .iter()
+                .find(|c| Path::new(&c.path) == path)
+                .ok_or_else(|| format!("{} has no changes in this worktree", raw))
+        })
+        .collect()
+}
+
+/// Stage `paths`. In a scoped worktree, files outside the scope are refused.
+pub fn stage(worktree: &Path, paths: &[String]) -> Result<Vec<ChangedFile>, String> {
+    let changes = changed_files(worktree)?;
+    let selected = select(&changes, paths)?;
+    if let Some(scope) = read_scope(worktree) {
+        let outside = scope.outside(selected.iter().map(|c| c.path.as_str()));
+        if !outside.is_empty() {
+            return Err(format!("Outside the session's path scope {:?}: {}", scope.paths(), outside.join(", ")));
+        }
+    }
+    let mut args = vec!["add", "-A", "--"];
+    args.extend(selected.iter().map(|c| c.path.as_str()));
+    git(worktree, &args)?;
+    changed_files(worktree)
+}
+
+pub fn unstage(worktree: &Path, paths: &[String]) -> Result<Vec<ChangedFile>, String> {
+    let changes = changed_files(worktree)?;
+    let selected = select(&changes, paths)?;
+    let mut args = vec!["restore", "--staged", "--"];
+    args.extend(selected.iter().filter(|c| c.staged).map(|c| c.path.as_str()));
+    if args.len() > 3 {
+        git(worktree, &args)?;
+    }
+    changed_files(worktree)
+}
+
+/// Revert `paths` to `HEAD`. When `patch_path` is set, their changes are written
+/// there first as a patch that `git apply` accepts.
+pub fn discard(worktree: &Path, paths: &[String], patch_path: Option<&Path>) -> Result<DiscardResult, String> {
+    let changes = changed_files(worktree)?;
+    let selected = select(&changes, paths)?;
+    let (untracked, tracked): (Vec<&ChangedFile>, Vec<&ChangedFile>) = selected.into_iter().partition(|c| c.untracked);
+
+    if let Some(patch_path) = patch_path {
+        // Intent-to-add makes new files show up in the diff against HEAD
+        if !untracked.is_empty() {
+            let mut args = vec!["add", "-N", "--"];
+            args.extend(untracked.iter().map(|c| c.path.as_str()));
+            git(worktree, &args)?;
+        }
+        let mut args = vec!["diff", "--binary", "HEAD", "--"];
+        args.extend(tracked.iter().chain(untracked.iter()).map(|c| c.path.as_str()));
+        let patch = git(worktree, &args)?;
+        if let Some(parent) = patch_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(patch_path, patch).map_err(|e| format!("Failed to write {}: {}", patch_path.display(), e))?;
+        if !untracked.is_empty() {
+            let mut args = vec!["rm", "--cached", "--quiet", "--"];
+            args.extend(untracked.iter().map(|c| c.path.as_str()));
+            git(worktree, &args)?;
+        }
+    }
+
+    // Files added to the index but never committed have nothing to restore to
+    let (added, restorable): (Vec<&ChangedFile>, Vec<&ChangedFile>) = tracked.iter().partition(|c| c.status.starts_with('A'));
+    if !restorable.is_empty() {
+        let mut args = vec!["restore", "--source=HEAD", "--staged", "--worktree", "--"];
+        args.extend(restorable.iter().map(|c| c.path.as_str()));
+        git(worktree, &args)?;
+    }
+    if !added.is_empty() {
+        let mut args = vec!["rm", "--cached", "--quiet", "--"];
+        args.extend(added.iter().map(|c| c.path.as_str()));
+        git(worktree, &args)?;
+    }
+    for file in untracked.iter().chain(added.iter()) {
+        let path = worktree.join(&file.path);
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+
+    Ok(DiscardResult {
+        discarded: tracked.iter().chain(untracked.iter()).map(|c| c.path.clone()).collect(),
+        patch_path: patch_path.map(|p| p.to_string_lossy().to_string()),
+    })
+}
+
+fn patch_file(app_handle: &AppHandle, session_id: &str) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))?
+        .join("patches");
+    Ok(dir.join(format!("{}-{}.patch", session_id, chrono::Utc::now().format("%Y%m%dT%H%M%S"))))
+}
+
+/// Changed files in a session worktree and whether each is staged
+#[tauri::command]
+pub async fn list_worktree_changes(session_id: String, app_handle: AppHandle) -> Result<Vec<ChangedFile>, String> {
+    changed_files(&session_worktree(&app_handle, &session_id).await?)
+}
+
+/// Approve files for merge-back. Returns the worktree's changes afterwards.
+#[tauri::command]
+pub async fn stage_files(session_id: String, paths: Vec<String>, app_handle: AppHandle) -> Result<Vec<ChangedFile>, String> {
+    stage(&session_worktree(&app_handle, &session_id).await?, &paths)
+}
+
+#[tauri::command]
+pub async fn unstage_files(session_id: String, paths: Vec<String>, app_handle: AppHandle) -> Result<Vec<ChangedFile>, String> {
+    unstage(&session_worktree(&app_handle, &session_id).await?, &paths)
+}
+
+/// Throw away changes to `paths`, saving them as a patch in the app data
+/// directory unless `save_patch` is false
+#[tauri::command]
+pub async fn discard_files(
+    session_id: String,
+    paths: Vec<String>,
+    save_patch: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<DiscardResult, String> {
+    let worktree = session_worktree(&app_handle, &session_id).await?;
+    let patch_path = match save_patch.unwrap_or(true) {
+        true => Some(patch_file(&app_handle, &session_id)?),
+        false => None,
+    };
+    discard(&worktree, &paths, patch_path.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repo() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for args in [
+            vec!["init"],
+            vec!["config", "user.name", "Test User"],
+            vec!["config", "user.email", "test@example.com"],
+        ] {
+            git(dir.path(), &args).unwrap();
+        }
+        std::fs::write(dir.path().join("keep.txt"), "base\n").unwrap();
+        std::fs::write(dir.path().join("drop.txt"), "base\n").unwrap();
+        git(dir.path(), &["add", "."]).unwrap();
+        git(dir.path(), &["commit", "-m", "init"]).unwrap();
+        std::fs::write(dir.path().join("keep.txt"), "agent\n").unwrap();
+        std::fs::write(dir.path().join("drop.txt"), "agent\n").unwrap();
+        std::fs::write(dir.path().join("new.txt"), "agent\n").unwrap();
+        dir
+    }
+
+    fn paths(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_stage_and_unstage_selected_files() {
+        let dir = repo();
+        let changes = stage(dir.path(), &paths(&["keep.txt", "new.txt"])).unwrap();
+        let staged: Vec<&str> = changes.iter().filter(|c| c.staged).map(|c| c.path.as_str()).collect();
+        assert_eq!(staged, vec!["keep.txt", "new.txt"]);
+        assert_eq!(git(dir.path(), &["diff", "--cached", "--name-only"]).unwrap().trim(), "keep.txt\nnew.txt");
+
+        let changes = unstage(dir.path(), &paths(&["new.txt"])).unwrap();
+        assert!(changes.iter().any(|c| c.path == "new.txt" && c.untracked));
+
+        assert!(stage(dir.path(), &paths(&["../outside.txt"])).is_err());
+        assert!(stage(dir.path(), &paths(&["unchanged.txt"])).is_err());
+    }
+
+    #[test]
+    fn test_discard_saves_patch() {
+        let dir = repo();
+        let artifacts = TempDir::new().unwrap();
+        let patch = artifacts.path().join("patches").join("rejected.patch");
+        let result = discard(dir.path(), &paths(&["drop.txt", "new.txt"]), Some(&patch)).unwrap();
+        assert_eq!(result.discarded, paths(&["drop.txt", "new.txt"]));
+
+        assert_eq!(std::fs::read_to_string(dir.path().join("drop.txt")).unwrap(), "base\n");
+        assert!(!dir.path().join("new.txt").exists());
+        assert_eq!(std::fs::read_to_string(dir.path().join("keep.txt")).unwrap(), "agent\n");
+
+        // The patch brings the discarded changes back
+        git(dir.path(), &["apply", patch.to_str().unwrap()]).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("drop.txt")).unwrap(), "agent\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("new.txt")).unwrap(), "agent\n");
+    }
+}
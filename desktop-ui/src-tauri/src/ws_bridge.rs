@@ -0,0 +1,508 @@
+//! Localhost WebSocket bridge for external tools
+//!
+//! When `BridgeConfig` enables it, a WebSocket server listens on
+//! `127.0.0.1:<port>` so editor plugins and launchers can follow running
+//! sessions without going through the webview. Clients authenticate with the
+//! bridge token, kept in the keychain, sent as `Authorization: Bearer <token>`
+//! or as a `token` query parameter.
+//!
+//! Frames are JSON text. A request is `{"id", "method", "params"}` and is
+//! answered with `{"id", "result"}` or `{"id", "error"}`. Only `list_sessions`,
+//! `list_threads`, `send_message` and `subscribe` are accepted. Session events
+//! (`chat_stream`, `thread_stream`, `process_output` and the session lifecycle
+//! events) arrive as `{"event", "session_id", "payload"}`; `subscribe` narrows
+//! them with the same filter webviews use. A client too slow to keep up gets a
+//! `bridge_lagged` event with the number of events it missed.
+//!
+//! Frames carrying `"jsonrpc": "2.0"` go to the editor endpoint instead (see
+//! `editor_rpc`).
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Listener, Manager, State};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+use crate::app_state::{AppState, BridgeConfig};
+use crate::event_subscriptions::EventFilter;
+use crate::keychain_auth::{KeychainAuth, TokenType};
+
+/// Keychain entry holding the bridge token
+const KEYCHAIN_ID: &str = "ws-bridge";
+/// Events buffered per client before it starts missing them
+const EVENT_BUFFER: usize = 1024;
+/// Methods a bridge client may call
+const METHODS: [&str; 4] = ["list_sessions", "list_threads", "send_message", "subscribe"];
+/// App-wide events mirrored to clients besides those sent through `emit_session_event`
+const MIRRORED_EVENTS: [&str; 7] = [
+    "session-created",
+    "session-started",
+    "session-stopped",
+    "session-status-update",
+    "session_awaiting_input",
+    "session_stale",
+    "process_status",
+];
+
+#[derive(Debug)]
+struct BridgeEvent {
+    event: String,
+    session_id: String,
+    /// Serialized once, sent as is to every client that wants it
+    frame: String,
+}
+
+struct Server {
+    port: u16,
+    shutdown: CancellationToken,
+    clients: Arc<AtomicUsize>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+pub struct WsBridge {
+    events: broadcast::Sender<Arc<BridgeEvent>>,
+    server: Mutex<Option<Server>>,
+}
+
+impl Default for WsBridge {
+    fn default() -> Self {
+        Self { events: broadcast::channel(EVENT_BUFFER).0, server: Mutex::new(None) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeStatus {
+    #[serde(flatten)]
+    pub config: BridgeConfig,
+    pub running: bool,
+    /// `ws://127.0.0.1:<port>` while running
+    pub address: Option<String>,
+    pub clients: usize,
+}
+
+impl WsBridge {
+    /// Pass an event on to connected clients; nothing is serialized without any
+    pub fn publish<S: Serialize>(&self, event: &str, session_id: &str, payload: &S) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+        let Ok(payload) = serde_json::to_value(payload) else {
+            return;
+        };
+        let frame = serde_json::json!({ "event": event, "session_id": session_id, "payload": payload }).to_string();
+        let _ = self.events.send(Arc::new(BridgeEvent {
+            event: event.to_string(),
+            session_id: session_id.to_string(),
+            frame,
+        }));
+    }
+
+    fn status(&self, config: BridgeConfig) -> BridgeStatus {
+        let server = self.server.lock().unwrap();
+        BridgeStatus {
+            config,
+            running: server.is_some(),
+            address: server.as_ref().map(|s| format!("ws://{}:{}", Ipv4Addr::LOCALHOST, s.port)),
+            clients: server.as_ref().map_or(0, |s| s.clients.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Close the listener and every client connection
+    async fn stop(&self) {
+        let server = self.server.lock().unwrap().take();
+        if let Some(server) = server {
+            server.shutdown.cancel();
+            // The port is free again once the accept loop has returned
+            let _ = server.task.await;
+        }
+    }
+
+    async fn start(&self, app_handle: &AppHandle, port: u16) -> Result<(), String> {
+        self.stop().await;
+        let token = bridge_token()?;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .await
+            .map_err(|e| format!("Failed to listen on {}:{}: {}", Ipv4Addr::LOCALHOST, port, e))?;
+        let shutdown = CancellationToken::new();
+        let clients = Arc::new(AtomicUsize::new(0));
+        let task = tauri::async_runtime::spawn(serve(
+            app_handle.clone(),
+            listener,
+            token,
+            shutdown.clone(),
+            clients.clone(),
+        ));
+        *self.server.lock().unwrap() = Some(Server { port, shutdown, clients, task });
+        log::info!("WebSocket bridge listening on {}:{}", Ipv4Addr::LOCALHOST, port);
+        Ok(())
+    }
+
+    /// Start, restart or stop the server to match `config`
+    async fn apply(&self, app_handle: &AppHandle, config: &BridgeConfig) -> Result<(), String> {
+        if config.enabled {
+            self.start(app_handle, config.port).await
+        } else {
+            self.stop().await;
+            Ok(())
+        }
+    }
+}
+
+fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+/// The stored token, created on first use
+fn bridge_token() -> Result<String, String> {
+    let keychain = KeychainAuth::new();
+    if let Ok(token) = keychain.get_token(KEYCHAIN_ID, &TokenType::ApiKey) {
+        return Ok(token);
+    }
+    let token = new_token();
+    keychain.store_token(KEYCHAIN_ID, TokenType::ApiKey, &token)?;
+    Ok(token)
+}
+
+/// Compare without stopping at the first differing byte
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Whether a handshake request carries the bridge token
+fn authorized(request: &Request, token: &str) -> bool {
+    let bearer = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    bearer.or(query).is_some_and(|given| tokens_match(given.trim(), token))
+}
+
+#[derive(Debug, Deserialize)]
+struct BridgeRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ListSessionsParams {
+    profile_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ListThreadsParams {
+    session_id: String,
+    include_archived: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct SendMessageParams {
+    thread_id: String,
+    message: String,
+}
+
+fn params<T: serde::de::DeserializeOwned>(method: &str, params: Value) -> Result<T, String> {
+    // A missing `params` is an empty object, so all-optional methods can leave it out
+    let params = if params.is_null() { Value::Object(Default::default()) } else { params };
+    serde_json::from_value(params).map_err(|e| format!("Invalid params for {}: {}", method, e))
+}
+
+/// Parse a client frame, rejecting methods outside the bridge's subset
+fn parse_request(text: &str) -> Result<BridgeRequest, (Value, String)> {
+    let request: BridgeRequest =
+        serde_json::from_str(text).map_err(|e| (Value::Null, format!("Invalid request: {}", e)))?;
+    if !METHODS.contains(&request.method.as_str()) {
+        return Err((request.id, format!("Method '{}' is not available over the bridge", request.method)));
+    }
+    Ok(request)
+}
+
+fn response_frame(id: Value, result: Result<Value, String>) -> String {
+    match result {
+        Ok(result) => serde_json::json!({ "id": id, "result": result }),
+        Err(error) => serde_json::json!({ "id": id, "error": error }),
+    }
+    .to_string()
+}
+
+async fn dispatch(app_handle: &AppHandle, request: BridgeRequest, filter: &mut EventFilter) -> Result<Value, String> {
+    let method = request.method.as_str();
+    let value = match method {
+        "list_sessions" => {
+            let p: ListSessionsParams = params(method, request.params)?;
+            serde_json::to_value(crate::thread_session_commands::list_sessions(p.profile_id, app_handle.state()).await?)
+        }
+        "list_threads" => {
+            let p: ListThreadsParams = params(method, request.params)?;
+            let threads = crate::thread_session_commands::list_threads(
+                p.session_id,
+                p.include_archived,
+                None,
+                app_handle.state(),
+            )
+            .await?;
+            serde_json::to_value(threads)
+        }
+        "send_message" => {
+            let p: SendMessageParams = params(method, request.params)?;
+            crate::thread_session_commands::thread_send_message(
+                p.thread_id,
+                p.message,
+                app_handle.state(),
+                app_handle.state(),
+                app_handle.state(),
+            )
+            .await?;
+            Ok(Value::Bool(true))
+        }
+        "subscribe" => {
+            *filter = params(method, request.params)?;
+            Ok(Value::Bool(true))
+        }
+        _ => return Err(format!("Method '{}' is not available over the bridge", method)),
+    };
+    value.map_err(|e| e.to_string())
+}
+
+/// Counts a connected client for as long as it is held
+struct ClientGuard(Arc<AtomicUsize>);
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+async fn handle_client(app_handle: AppHandle, stream: TcpStream, token: String, shutdown: CancellationToken, clients: Arc<AtomicUsize>) {
+    // The handshake callback's error type is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    let check = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if authorized(request, &token) {
+            Ok(response)
+        } else {
+            let mut denied = ErrorResponse::new(Some("Invalid or missing bridge token".to_string()));
+            *denied.status_mut() = StatusCode::UNAUTHORIZED;
+            Err(denied)
+        }
+    };
+    let socket = match tokio_tungstenite::accept_hdr_async(stream, check).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::debug!("WebSocket bridge handshake failed: {}", e);
+            return;
+        }
+    };
+    clients.fetch_add(1, Ordering::Relaxed);
+    let _guard = ClientGuard(clients);
+    let Some(bridge) = app_handle.try_state::<WsBridge>() else {
+        return;
+    };
+    let mut events = bridge.events.subscribe();
+    let (mut sink, mut incoming) = socket.split();
+    let mut filter = EventFilter::default();
+    let mut editor = crate::editor_rpc::EditorConnection::default();
+    let mut diff_poll = tokio::time::interval(crate::editor_rpc::DIFF_POLL);
+    diff_poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let frame = tokio::select! {
+            _ = shutdown.cancelled() => {
+                let _ = sink.send(Message::Close(None)).await;
+                break;
+            }
+            message = incoming.next() => match message {
+                Some(Ok(Message::Text(text))) if crate::editor_rpc::is_rpc(&text) => {
+                    match crate::editor_rpc::handle(&app_handle, &text, &mut editor).await {
+                        Some(frame) => frame,
+                        None => continue,
+                    }
+                }
+                Some(Ok(Message::Text(text))) => match parse_request(&text) {
+                    Ok(request) => {
+                        let id = request.id.clone();
+                        response_frame(id, dispatch(&app_handle, request, &mut filter).await)
+                    }
+                    Err((id, error)) => response_frame(id, Err(error)),
+                },
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    log::debug!("WebSocket bridge client error: {}", e);
+                    break;
+                }
+            },
+            _ = diff_poll.tick(), if editor.watching() => match editor.poll_diff().await {
+                Some(frame) => frame,
+                None => continue,
+            },
+            event = events.recv() => match event {
+                Ok(event) if filter.matches(&event.event, &event.session_id) => event.frame.clone(),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    serde_json::json!({ "event": "bridge_lagged", "payload": { "missed": missed } }).to_string()
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if sink.send(Message::Text(frame)).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn serve(app_handle: AppHandle, listener: TcpListener, token: String, shutdown: CancellationToken, clients: Arc<AtomicUsize>) {
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    log::warn!("WebSocket bridge failed to accept a connection: {}", e);
+                    continue;
+                }
+            },
+        };
+        tauri::async_runtime::spawn(handle_client(
+            app_handle.clone(),
+            stream,
+            token.clone(),
+            shutdown.clone(),
+            clients.clone(),
+        ));
+    }
+}
+
+/// Mirror the session lifecycle events to the bridge and start it when enabled
+pub fn spawn_bridge(app_handle: AppHandle) {
+    for name in MIRRORED_EVENTS {
+        let handle = app_handle.clone();
+        app_handle.listen_any(name, move |event| {
+            let Some(bridge) = handle.try_state::<WsBridge>() else {
+                return;
+            };
+            let payload: Value = serde_json::from_str(event.payload()).unwrap_or(Value::Null);
+            let session_id = payload
+                .get("session_id")
+                .or_else(|| payload.get("sessionId"))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            bridge.publish(name, &session_id, &payload);
+        });
+    }
+    tauri::async_runtime::spawn(async move {
+        let (Some(app_state), Some(bridge)) = (app_handle.try_state::<AppState>(), app_handle.try_state::<WsBridge>()) else {
+            return;
+        };
+        let config = app_state.lock().unwrap().bridge.clone();
+        if let Err(e) = bridge.apply(&app_handle, &config).await {
+            log::warn!("WebSocket bridge not started: {}", e);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_bridge_status(
+    app_state: State<'_, AppState>,
+    bridge: State<'_, WsBridge>,
+) -> Result<BridgeStatus, String> {
+    let config = app_state.lock().unwrap().bridge.clone();
+    Ok(bridge.status(config))
+}
+
+/// Save the bridge settings and start, restart or stop the server to match
+#[tauri::command]
+pub async fn set_bridge_config(
+    config: BridgeConfig,
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    bridge: State<'_, WsBridge>,
+) -> Result<BridgeStatus, String> {
+    if config.port < 1024 {
+        return Err("Bridge port must be 1024 or higher".to_string());
+    }
+    bridge.apply(&app_handle, &config).await?;
+    let to_save = {
+        let mut state = app_state.lock().unwrap();
+        state.bridge = config.clone();
+        state.clone()
+    };
+    to_save.save().await?;
+    Ok(bridge.status(config))
+}
+
+/// The token external tools authenticate with
+#[tauri::command]
+pub async fn get_bridge_token() -> Result<String, String> {
+    bridge_token()
+}
+
+/// Replace the token; connected clients are dropped and must reconnect with the new one
+#[tauri::command]
+pub async fn rotate_bridge_token(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    bridge: State<'_, WsBridge>,
+) -> Result<String, String> {
+    let token = new_token();
+    KeychainAuth::new().store_token(KEYCHAIN_ID, TokenType::ApiKey, &token)?;
+    let config = app_state.lock().unwrap().bridge.clone();
+    bridge.apply(&app_handle, &config).await?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(uri: &str, authorization: Option<&str>) -> Request {
+        let mut request = Request::builder().uri(uri);
+        if let Some(value) = authorization {
+            request = request.header("Authorization", value);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_authorized_by_header_or_query() {
+        let token = "a1b2c3";
+        assert!(authorized(&handshake("/", Some("Bearer a1b2c3")), token));
+        assert!(authorized(&handshake("/?client=vscode&token=a1b2c3", None), token));
+        assert!(!authorized(&handshake("/", Some("Bearer a1b2c4")), token));
+        assert!(!authorized(&handshake("/?token=a1b2c", None), token));
+        assert!(!authorized(&handshake("/", Some("Basic a1b2c3")), token));
+        assert!(!authorized(&handshake("/", None), token));
+    }
+
+    #[test]
+    fn test_requests_limited_to_safe_methods() {
+        let request = parse_request(r#"{"id": 1, "method": "send_message", "params": {"thread_id": "t1", "message": "hi"}}"#).unwrap();
+        assert_eq!((request.id.clone(), request.method.as_str()), (Value::from(1), "send_message"));
+        let p: SendMessageParams = params("send_message", request.params).unwrap();
+        assert_eq!((p.thread_id.as_str(), p.message.as_str()), ("t1", "hi"));
+        assert!(params::<ListSessionsParams>("list_sessions", Value::Null).unwrap().profile_id.is_none());
+
+        let (id, error) = parse_request(r#"{"id": "x", "method": "session_delete"}"#).unwrap_err();
+        assert_eq!(id, Value::from("x"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&response_frame(id, Err(error))).unwrap(),
+            serde_json::json!({ "id": "x", "error": "Method 'session_delete' is not available over the bridge" })
+        );
+        assert_eq!(parse_request("not json").unwrap_err().0, Value::Null);
+    }
+}
@@ -11,6 +11,19 @@ import {
 } from 'lucide-react'
 import { useFileLinks } from '../hooks/useFileLinks'
 
+interface DirectoryEntry {
+  name: string
+  path: string
+  isDir: boolean
+}
+
+interface DirectoryPage {
+  entries: DirectoryEntry[]
+  total: number
+  offset: number
+  hasMore: boolean
+}
+
 interface FileExplorerProps {
   currentPath: string
   onFileSelect: (path: string, content: string) => void
@@ -18,29 +31,32 @@ interface FileExplorerProps {
 }
 
 export const FileExplorer = ({ currentPath, onFileSelect, onToggleSidebar }: FileExplorerProps) => {
-  const [files, setFiles] = useState<string[]>([])
+  const [files, setFiles] = useState<DirectoryEntry[]>([])
+  const [hasMore, setHasMore] = useState(false)
   const { handleFileLink } = useFileLinks()
 
   useEffect(() => {
     loadDirectory(currentPath)
   }, [currentPath])
 
-  const loadDirectory = async (path: string) => {
+  const loadDirectory = async (path: string, offset = 0) => {
     try {
-      const fileList = await invoke<string[]>('list_directory', { path })
-      setFiles(fileList.sort())
+      const page = await invoke<DirectoryPage>('list_directory', {
+        path,
+        options: { offset, includeHidden: true }
+      })
+      setFiles(prev => (offset === 0 ? page.entries : [...prev, ...page.entries]))
+      setHasMore(page.hasMore)
     } catch (error) {
       console.error('Failed to load directory:', error)
     }
   }
 
-  const handleFileClick = async (fileName: string) => {
-    const filePath = `${currentPath}/${fileName}`
+  const handleFileClick = async (file: DirectoryEntry) => {
+    const filePath = file.path
     
-    // Check if it's a directory first
-    if (!fileName.includes('.')) {
-      // It's likely a directory, don't try to open it
-      console.log('Clicked on directory:', fileName)
+    if (file.isDir) {
+      console.log('Clicked on directory:', file.name)
       return
     }
     
@@ -83,28 +99,36 @@ export const FileExplorer = ({ currentPath, onFileSelect, onToggleSidebar }: Fil
       <div className="space-y-1">
         {files.map((file) => (
           <div
-            key={file}
+            key={file.path}
             onClick={() => handleFileClick(file)}
             className="flex items-center px-2 py-1 text-sm hover:bg-accent cursor-pointer rounded transition-colors"
           >
             <div className="mr-2 w-4 h-4 text-muted-foreground">
               {getFileIcon(file)}
             </div>
-            <span className="truncate overflow-hidden whitespace-nowrap" title={file}>{file}</span>
+            <span className="truncate overflow-hidden whitespace-nowrap" title={file.name}>{file.name}</span>
           </div>
         ))}
+        {hasMore && (
+          <button
+            onClick={() => loadDirectory(currentPath, files.length)}
+            className="w-full px-2 py-1 text-left text-sm text-muted-foreground hover:bg-accent rounded transition-colors"
+          >
+            Show more
+          </button>
+        )}
       </div>
     </div>
   )
 }
 
-const getFileIcon = (filename: string) => {
+const getFileIcon = ({ name: filename, isDir }: DirectoryEntry) => {
+  if (isDir) return <FolderClosed className="w-4 h-4" />
   if (filename.endsWith('.ts') || filename.endsWith('.tsx')) return <FileText className="w-4 h-4" />
   if (filename.endsWith('.js') || filename.endsWith('.jsx')) return <FileText className="w-4 h-4" />
   if (filename.endsWith('.rs')) return <Settings className="w-4 h-4" />
   if (filename.endsWith('.py')) return <FileIcon className="w-4 h-4" />
   if (filename.endsWith('.json')) return <FileJson className="w-4 h-4" />
   if (filename.endsWith('.md')) return <BookOpen className="w-4 h-4" />
-  if (filename.includes('.')) return <FileIcon className="w-4 h-4" />
-  return <FolderClosed className="w-4 h-4" />
+  return <FileIcon className="w-4 h-4" />
 }
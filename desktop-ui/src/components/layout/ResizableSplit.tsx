@@ -1,8 +1,9 @@
-import React, { useCallback } from 'react';
-import { PanelGroup, Panel, PanelResizeHandle } from 'react-resizable-panels';
+import React, { useCallback, useEffect, useRef } from 'react';
+import { PanelGroup, Panel, PanelResizeHandle, ImperativePanelHandle } from 'react-resizable-panels';
+import { useUiState } from '../../hooks/useUiState';
 
 interface ResizableSplitProps {
-  /** Unique storage key for localStorage persistence */
+  /** Unique key the split position is persisted under */
   storageKey: string;
   /** Default size for the first panel (percentage) */
   defaultSize?: number;
@@ -21,10 +22,12 @@ interface ResizableSplitProps {
 }
 
 const STORAGE_KEY_PREFIX = 'amp-split-';
+/** Dragging reports every step; only save once it settles */
+const SAVE_DELAY_MS = 300;
 
 /**
  * ResizableSplit is a wrapper around react-resizable-panels that provides:
- * - Automatic localStorage persistence of split positions
+ * - Persistence of split positions through the backend's UI state (`layout` namespace)
  * - Proper minimum/maximum size handling
  * - Clean API for managing resizable panels
  */
@@ -38,41 +41,30 @@ export const ResizableSplit: React.FC<ResizableSplitProps> = ({
   direction = 'horizontal',
   className = '',
 }) => {
-  const fullStorageKey = `${STORAGE_KEY_PREFIX}${storageKey}`;
-  
-  // Load initial size from localStorage or use default
-  const getInitialSize = useCallback(() => {
-    try {
-      const saved = localStorage.getItem(fullStorageKey);
-      if (saved !== null) {
-        const parsedSize = parseFloat(saved);
-        // Validate the saved size is within bounds
-        if (parsedSize >= minSize && parsedSize <= maxSize) {
-          return parsedSize;
-        }
-      }
-    } catch (error) {
-      console.warn(`Failed to load split size from localStorage for key ${fullStorageKey}:`, error);
+  const [savedSize, saveSize, loaded] = useUiState<number>('layout', `${STORAGE_KEY_PREFIX}${storageKey}`, defaultSize);
+  const panelRef = useRef<ImperativePanelHandle>(null);
+  const saveTimer = useRef<ReturnType<typeof setTimeout> | undefined>(undefined);
+
+  // Apply the saved size once it arrives, if it is still within bounds
+  useEffect(() => {
+    if (loaded && savedSize >= minSize && savedSize <= maxSize) {
+      panelRef.current?.resize(savedSize);
     }
-    return defaultSize;
-  }, [fullStorageKey, defaultSize, minSize, maxSize]);
+  }, [loaded, savedSize, minSize, maxSize]);
 
-  const [firstPanelSize, setFirstPanelSize] = React.useState<number>(getInitialSize);
+  useEffect(() => () => clearTimeout(saveTimer.current), []);
 
   const handleResize = useCallback((sizes: number[]) => {
     const newSize = sizes[0] || defaultSize;
-    setFirstPanelSize(newSize);
-    
-    // Persist to localStorage
-    try {
-      localStorage.setItem(fullStorageKey, String(newSize));
-    } catch (error) {
-      console.warn(`Failed to save split size to localStorage for key ${fullStorageKey}:`, error);
+
+    if (loaded && newSize !== savedSize) {
+      clearTimeout(saveTimer.current);
+      saveTimer.current = setTimeout(() => saveSize(newSize), SAVE_DELAY_MS);
     }
 
     // Call external handler
     onSizeChange?.(newSize);
-  }, [fullStorageKey, onSizeChange, defaultSize]);
+  }, [loaded, savedSize, saveSize, onSizeChange, defaultSize]);
 
   return (
     <PanelGroup 
@@ -81,7 +73,8 @@ export const ResizableSplit: React.FC<ResizableSplitProps> = ({
       className={className}
     >
       <Panel 
-        defaultSize={firstPanelSize}
+        ref={panelRef}
+        defaultSize={defaultSize}
         minSize={minSize}
         maxSize={maxSize}
       >
@@ -1,6 +1,7 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
+import type { ListResult } from '../types/list';
 
 export interface AuthStatus {
   success: boolean;
@@ -259,7 +260,8 @@ export const useAmpService = () => {
   // Toolbox profile management
   const listToolboxProfiles = useCallback(async (): Promise<ToolboxProfile[]> => {
     try {
-      return await invoke<ToolboxProfile[]>('list_toolbox_profiles');
+      const profiles = await invoke<ListResult<ToolboxProfile>>('list_toolbox_profiles');
+      return profiles.items;
     } catch (error) {
       console.error('Failed to list toolbox profiles:', error);
       throw error;
@@ -1,5 +1,6 @@
 import { useState, useCallback, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import type { ListResult } from '../types/list';
 
 export interface ChatSession {
   id: string;
@@ -23,8 +24,8 @@ export const useSessionManager = () => {
     setError(null);
     
     try {
-      const sessionList = await invoke<ChatSession[]>('sessions_list');
-      setSessions(sessionList);
+      const sessionList = await invoke<ListResult<ChatSession>>('sessions_list');
+      setSessions(sessionList.items);
     } catch (err) {
       const errorMessage = err instanceof Error ? err.message : 'Failed to load sessions';
       setError(errorMessage);
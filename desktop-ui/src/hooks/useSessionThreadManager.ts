@@ -1,5 +1,6 @@
 import { useState, useCallback, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import type { ListResult } from '../types/list';
 
 export interface SessionInfo {
   id: string;
@@ -28,6 +29,13 @@ export interface ThreadStartRequest {
   session_id: string;
   context: 'production' | 'development';
   agent_mode?: string | null;
+  /** Amp server for this thread only; a server on this machine makes it a development thread */
+  server?: ThreadServerOverride | null;
+}
+
+export interface ThreadServerOverride {
+  amp_url: string;
+  tls_insecure?: boolean;
 }
 
 export interface ThreadAttachRequest {
@@ -64,11 +72,11 @@ export const useSessionThreadManager = () => {
   // Load threads for a specific session
   const loadThreadsForSession = useCallback(async (sessionId: string) => {
     try {
-      const threadList = await invoke<ThreadInfo[]>('list_threads', { 
+      const threadList = await invoke<ListResult<ThreadInfo>>('list_threads', { 
         sessionId, 
         includeArchived: false 
       });
-      setThreads(prev => [...prev.filter(t => t.session_id !== sessionId), ...threadList]);
+      setThreads(prev => [...prev.filter(t => t.session_id !== sessionId), ...threadList.items]);
     } catch (err) {
       console.error(`Failed to load threads for session ${sessionId}:`, err);
     }
@@ -0,0 +1,75 @@
+/**
+ * useUiState Hook
+ *
+ * A value kept by the backend's UI state service (`ui_state_get` / `ui_state_set`)
+ * instead of localStorage, so it survives restarts and stays in step with other
+ * windows through `ui_state_changed`.
+ */
+
+import { useCallback, useEffect, useRef, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { getCurrentWebview } from '@tauri-apps/api/webview'
+
+/** Namespaces the backend accepts, each with its own value schema */
+export type UiStateNamespace = 'window' | 'layout' | 'tabs' | 'selection'
+
+interface UiStateChange<T> {
+  namespace: UiStateNamespace
+  key: string
+  value: T | null
+}
+
+export function useUiState<T>(namespace: UiStateNamespace, key: string, defaultValue: T) {
+  const [value, setValue] = useState<T>(defaultValue)
+  const [loaded, setLoaded] = useState(false)
+  const defaultRef = useRef(defaultValue)
+  defaultRef.current = defaultValue
+
+  useEffect(() => {
+    let cancelled = false
+    let unlisten: (() => void) | undefined
+    let subscriptionId: string | undefined
+
+    invoke<T | null>('ui_state_get', { namespace, key })
+      .then((stored) => {
+        if (!cancelled && stored !== null) setValue(stored)
+      })
+      .catch((error) => console.warn(`[useUiState] Failed to load ${namespace}:${key}:`, error))
+      .finally(() => {
+        if (!cancelled) setLoaded(true)
+      })
+
+    getCurrentWebview()
+      .listen<UiStateChange<T>>('ui_state_changed', ({ payload }) => {
+        if (payload.namespace === namespace && payload.key === key) {
+          setValue(payload.value ?? defaultRef.current)
+        }
+      })
+      .then((fn) => (cancelled ? fn() : (unlisten = fn)))
+
+    invoke<string>('ui_state_subscribe', { namespaces: [namespace] })
+      .then((id) => {
+        if (cancelled) invoke('ui_state_unsubscribe', { subscriptionId: id })
+        else subscriptionId = id
+      })
+      .catch((error) => console.warn(`[useUiState] Failed to subscribe to ${namespace}:`, error))
+
+    return () => {
+      cancelled = true
+      unlisten?.()
+      if (subscriptionId) invoke('ui_state_unsubscribe', { subscriptionId }).catch(() => {})
+    }
+  }, [namespace, key])
+
+  const update = useCallback(
+    (next: T) => {
+      setValue(next)
+      invoke('ui_state_set', { namespace, key, value: next }).catch((error) =>
+        console.warn(`[useUiState] Failed to save ${namespace}:${key}:`, error)
+      )
+    },
+    [namespace, key]
+  )
+
+  return [value, update, loaded] as const
+}
@@ -0,0 +1,47 @@
+/**
+ * Paging, filtering and sorting for list commands
+ *
+ * Mirrors `list_query.rs`. Commands that take a `query` return a `ListResult`;
+ * without `page_size` the whole list comes back in one page.
+ */
+
+export type FilterOp =
+  | 'eq'
+  | 'ne'
+  | 'contains'
+  | 'gt'
+  | 'gte'
+  | 'lt'
+  | 'lte'
+  | 'is_null'
+  | 'not_null'
+
+export interface ListFilter {
+  field: string
+  op: FilterOp
+  value?: string | null
+}
+
+export interface ListSort {
+  field: string
+  direction?: 'asc' | 'desc'
+}
+
+export interface ListQuery {
+  /** Zero-based page, ignored when `cursor` is set */
+  page?: number
+  page_size?: number | null
+  /** `next_cursor` from the previous page */
+  cursor?: string | null
+  sort?: ListSort[]
+  filters?: ListFilter[]
+}
+
+export interface ListResult<T> {
+  items: T[]
+  /** Matching items across all pages */
+  total: number
+  page: number
+  page_size: number | null
+  next_cursor: string | null
+}
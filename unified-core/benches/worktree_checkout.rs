@@ -0,0 +1,117 @@
+//! Worktree creation time and disk usage: full vs sparse checkout, full vs partial clone
+//!
+//! Run with `cargo bench -p unified-core --bench worktree_checkout`. A synthetic
+//! monorepo of `PACKAGES` packages is generated in a temp directory; the sparse
+//! cases check out a single package.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use unified_core::*;
+
+const PACKAGES: usize = 40;
+const FILES_PER_PACKAGE: usize = 50;
+const FILE_BYTES: usize = 8 * 1024;
+const ROUNDS: usize = 3;
+
+fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git").current_dir(dir).args(args).output().expect("failed to run git");
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+}
+
+fn create_monorepo(root: &Path) {
+    git(root, &["init", "--quiet", "--initial-branch=main"]);
+    git(root, &["config", "user.name", "Bench"]);
+    git(root, &["config", "user.email", "bench@example.com"]);
+    // Serve filtered fetches for the partial clone case
+    git(root, &["config", "uploadpack.allowFilter", "true"]);
+    std::fs::write(root.join(".gitignore"), ".worktrees/\n").unwrap();
+    for package in 0..PACKAGES {
+        let dir = root.join("packages").join(format!("pkg-{:02}", package));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in 0..FILES_PER_PACKAGE {
+            let line = format!("// pkg {} file {}\n", package, file);
+            std::fs::write(dir.join(format!("mod_{:02}.ts", file)), line.repeat(FILE_BYTES / line.len())).unwrap();
+        }
+    }
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "Generate monorepo"]);
+}
+
+fn disk_usage(path: &Path) -> u64 {
+    walk(path).iter().map(|p| p.metadata().map(|m| m.len()).unwrap_or(0)).sum()
+}
+
+fn walk(path: &Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(path),
+                Ok(t) if t.is_file() => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+fn report(label: &str, times: &[Duration], bytes: u64) {
+    let mean_ms = times.iter().map(Duration::as_secs_f64).sum::<f64>() * 1000.0 / times.len() as f64;
+    println!("{:<28} {:>10.1} ms {:>12.1} KiB", label, mean_ms, bytes as f64 / 1024.0);
+}
+
+async fn bench_worktrees(repo: &Path, label: &str, checkout: CheckoutOptions) {
+    let backend = CliBackend::new(repo.to_path_buf()).unwrap().with_checkout(checkout);
+    backend.initialize().await.unwrap();
+    let mut times = Vec::new();
+    let mut bytes = 0;
+    for round in 0..ROUNDS {
+        let session_id = format!("bench-{}-{}", label.replace(' ', "-"), round);
+        let start = Instant::now();
+        let info = backend.create_worktree(&session_id, "main", &session_id).await.unwrap();
+        times.push(start.elapsed());
+        bytes = disk_usage(&info.worktree_path);
+        backend.cleanup_worktree(&session_id).await.unwrap();
+    }
+    report(label, &times, bytes);
+}
+
+async fn bench_clone(source: &Path, label: &str, checkout: CheckoutOptions) {
+    let url = format!("file://{}", source.display());
+    let mut times = Vec::new();
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        let dest = tempfile::TempDir::new().unwrap();
+        let clone_dir = dest.path().join("clone");
+        let start = Instant::now();
+        clone_repository(&url, &clone_dir, &checkout).await.unwrap();
+        times.push(start.elapsed());
+        bytes = disk_usage(&clone_dir);
+    }
+    report(label, &times, bytes);
+}
+
+#[tokio::main]
+async fn main() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let repo = temp_dir.path().join("monorepo");
+    std::fs::create_dir_all(&repo).unwrap();
+    create_monorepo(&repo);
+
+    let sparse = CheckoutOptions { sparse_paths: vec!["packages/pkg-00".to_string()], blob_filter: None };
+    let partial = CheckoutOptions { sparse_paths: sparse.sparse_paths.clone(), blob_filter: Some("blob:none".to_string()) };
+
+    println!(
+        "{} packages x {} files x {} KiB, mean of {} rounds\n",
+        PACKAGES, FILES_PER_PACKAGE, FILE_BYTES / 1024, ROUNDS
+    );
+    println!("{:<28} {:>13} {:>16}", "case", "time", "disk");
+    bench_worktrees(&repo, "worktree full", CheckoutOptions::default()).await;
+    bench_worktrees(&repo, "worktree sparse", sparse.clone()).await;
+    bench_clone(&repo, "clone full", CheckoutOptions::default()).await;
+    bench_clone(&repo, "clone sparse", sparse).await;
+    bench_clone(&repo, "clone partial + sparse", partial).await;
+}
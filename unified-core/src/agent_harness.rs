@@ -0,0 +1,562 @@
+//! Agents a benchmark can run, Amp or any other CLI agent, behind one interface
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use crate::domain::{MetricsCollector, ProcessLimits};
+use crate::error::{HarnessError, HarnessResult};
+use crate::process_limits::{self, LimitExceeded};
+
+/// Placeholder in `CommandHarness` arguments that is replaced by the prompt
+pub const PROMPT_PLACEHOLDER: &str = "{prompt}";
+
+/// What a harness needs to start an agent on one case
+#[derive(Debug, Clone)]
+pub struct HarnessSpec {
+    pub prompt: String,
+    pub working_dir: PathBuf,
+    /// The agent's whole environment; nothing is inherited from this process
+    pub env: HashMap<String, String>,
+    /// Open-file limit set on the agent; see `AgentProcess::drain_within` for the others
+    pub limits: Option<ProcessLimits>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// Stdout line that parsed as a JSON object, such as an Amp stream-json event
+    Json { event: Value },
+    /// Any other stdout line
+    Output { text: String },
+    Stderr { text: String },
+}
+
+/// What an agent left behind once it exited
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarnessOutcome {
+    pub harness: String,
+    pub exit_code: Option<i32>,
+    /// Exited cleanly without reporting an error result
+    pub success: bool,
+    pub error_message: Option<String>,
+    /// Changes against `HEAD`, untracked files included; `None` outside a git repository
+    pub diff: Option<String>,
+    pub metrics: MetricsCollector,
+    pub transcript: Vec<AgentEvent>,
+}
+
+enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// A running agent, read and written through its harness
+pub struct AgentProcess {
+    harness: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    output: mpsc::UnboundedReceiver<OutputLine>,
+    working_dir: PathBuf,
+    json_stdout: bool,
+    error_message: Option<String>,
+    pub metrics: MetricsCollector,
+    pub transcript: Vec<AgentEvent>,
+}
+
+impl AgentProcess {
+    /// Start `program` and forward its stdout and stderr line by line
+    pub fn spawn(harness: &str, program: &str, args: &[String], spec: &HarnessSpec, json_stdout: bool) -> HarnessResult<Self> {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env_clear()
+            .envs(&spec.env)
+            .current_dir(&spec.working_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(limits) = &spec.limits {
+            process_limits::apply_rlimits(&mut command, limits);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| HarnessError::SpawnFailed { program: program.to_string(), reason: e.to_string() })?;
+
+        let (tx, output) = mpsc::unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(forward_lines(BufReader::new(stdout), tx.clone(), OutputLine::Stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(forward_lines(BufReader::new(stderr), tx, OutputLine::Stderr));
+        }
+
+        let metrics = MetricsCollector { start_time: Some(Utc::now()), ..MetricsCollector::default() };
+        Ok(Self {
+            harness: harness.to_string(),
+            stdin: child.stdin.take(),
+            child,
+            output,
+            working_dir: spec.working_dir.clone(),
+            json_stdout,
+            error_message: None,
+            metrics,
+            transcript: Vec::new(),
+        })
+    }
+
+    /// Write one line to the agent's stdin
+    pub async fn write_line(&mut self, line: &str) -> HarnessResult<()> {
+        let stdin = self.stdin.as_mut().ok_or(HarnessError::InputClosed)?;
+        let written = async {
+            stdin.write_all(line.as_bytes()).await?;
+            stdin.write_all(b"\n").await?;
+            stdin.flush().await
+        };
+        match written.await {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Err(HarnessError::InputClosed),
+            result => Ok(result?),
+        }
+    }
+
+    /// Signal end of input, which agents reading prompts from stdin wait for before exiting
+    pub fn close_input(&mut self) {
+        self.stdin = None;
+    }
+
+    /// Stop the agent, for instance once a case runs out of time
+    pub async fn kill(&mut self) -> HarnessResult<()> {
+        self.child.kill().await?;
+        Ok(())
+    }
+
+    /// Read events until the agent's output ends. An agent that passes one of
+    /// `limits` first is killed, and the limit returned.
+    pub async fn drain_within(&mut self, limits: &ProcessLimits) -> Option<LimitExceeded> {
+        let pid = self.child.id();
+        let exceeded = tokio::select! {
+            exceeded = process_limits::watch(pid, limits) => Some(exceeded),
+            _ = async { while self.next_event().await.is_some() {} } => None,
+        };
+        if exceeded.is_some() {
+            // finish() still reports a kill that failed through the exit status
+            let _ = self.kill().await;
+        }
+        exceeded
+    }
+
+    /// Next line of output as an event, or `None` once both streams have ended
+    pub async fn next_event(&mut self) -> Option<AgentEvent> {
+        let event = match self.output.recv().await? {
+            OutputLine::Stderr(text) => AgentEvent::Stderr { text },
+            OutputLine::Stdout(text) => match self.json_stdout {
+                true => match serde_json::from_str::<Value>(&text) {
+                    Ok(event) if event.is_object() => AgentEvent::Json { event },
+                    _ => AgentEvent::Output { text },
+                },
+                false => AgentEvent::Output { text },
+            },
+        };
+        if let AgentEvent::Json { event } = &event {
+            self.observe(event);
+        }
+        self.transcript.push(event.clone());
+        Some(event)
+    }
+
+    /// Fold a stream-json event into the metrics
+    fn observe(&mut self, event: &Value) {
+        match event.get("type").and_then(Value::as_str) {
+            Some("assistant") => {
+                self.metrics.iterations += 1;
+                let message = event.get("message");
+                if let Some(usage) = message.and_then(|m| m.get("usage")) {
+                    for key in ["input_tokens", "output_tokens"] {
+                        self.metrics.tokens_used += usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+                    }
+                }
+                let parts = message.and_then(|m| m.get("content")).and_then(Value::as_array);
+                for part in parts.into_iter().flatten() {
+                    if part.get("type").and_then(Value::as_str) == Some("tool_use") {
+                        if let Some(name) = part.get("name").and_then(Value::as_str) {
+                            *self.metrics.tools_used.entry(name.to_string()).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+            Some("result") => {
+                if let Some(cost) = event.get("total_cost_usd").and_then(Value::as_f64) {
+                    self.metrics.cost += cost;
+                }
+                let is_error = event.get("is_error").and_then(Value::as_bool).unwrap_or(false);
+                let subtype = event.get("subtype").and_then(Value::as_str).unwrap_or("success");
+                if is_error || subtype.starts_with("error") {
+                    let reason = event.get("error").and_then(Value::as_str).unwrap_or(subtype);
+                    self.error_message = Some(reason.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Read the rest of the output, wait for exit, and take the diff of the working directory
+    pub async fn finish(mut self) -> HarnessResult<HarnessOutcome> {
+        self.close_input();
+        while self.next_event().await.is_some() {}
+        let status = self.child.wait().await?;
+        self.metrics.end_time = Some(Utc::now());
+
+        let error_message = self.error_message.or_else(|| {
+            (!status.success()).then(|| match status.code() {
+                Some(code) => format!("{} exited with status {}", self.harness, code),
+                None => format!("{} was terminated by a signal", self.harness),
+            })
+        });
+        Ok(HarnessOutcome {
+            success: error_message.is_none(),
+            exit_code: status.code(),
+            error_message,
+            diff: working_tree_diff(&self.working_dir).await,
+            harness: self.harness,
+            metrics: self.metrics,
+            transcript: self.transcript,
+        })
+    }
+}
+
+async fn forward_lines<R: AsyncBufRead + Unpin>(mut reader: R, tx: mpsc::UnboundedSender<OutputLine>, wrap: fn(String) -> OutputLine) {
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\n', '\r']);
+                if tx.send(wrap(line.to_string())).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn git(dir: &Path, index: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .env("GIT_INDEX_FILE", index)
+        .args(args)
+        .output()
+        .await
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Diff of `dir` against `HEAD`, staged through a scratch index so the real one is left alone
+async fn working_tree_diff(dir: &Path) -> Option<String> {
+    let index = std::env::temp_dir().join(format!("amp-harness-{}.index", uuid::Uuid::new_v4()));
+    let diff = async {
+        git(dir, &index, &["read-tree", "HEAD"]).await?;
+        git(dir, &index, &["add", "-A"]).await?;
+        git(dir, &index, &["diff", "--cached", "--binary", "HEAD"]).await
+    }
+    .await;
+    let _ = std::fs::remove_file(&index);
+    diff
+}
+
+/// An agent a benchmark can spawn, prompt, and read events from
+#[async_trait]
+pub trait AgentHarness: Send + Sync {
+    /// Name recorded as the agent in benchmark results
+    fn name(&self) -> &str;
+
+    /// Start the agent in `spec.working_dir` on `spec.prompt`
+    async fn spawn(&self, spec: &HarnessSpec) -> HarnessResult<AgentProcess>;
+
+    /// Give a running agent a follow-up prompt
+    async fn send_prompt(&self, process: &mut AgentProcess, prompt: &str) -> HarnessResult<()> {
+        process.write_line(prompt).await
+    }
+
+    /// Next event from the agent, or `None` once its output has ended
+    async fn next_event(&self, process: &mut AgentProcess) -> HarnessResult<Option<AgentEvent>> {
+        Ok(process.next_event().await)
+    }
+
+    /// Wait for the agent to exit and gather its diff and metrics
+    async fn collect(&self, process: AgentProcess) -> HarnessResult<HarnessOutcome> {
+        process.finish().await
+    }
+}
+
+/// Run one case start to finish: spawn, end input, and collect
+pub async fn run_case(harness: &dyn AgentHarness, spec: &HarnessSpec) -> HarnessResult<HarnessOutcome> {
+    run_case_cancellable(harness, spec, &CancellationToken::new()).await
+}
+
+/// `run_case` that stops when `cancel` fires. The agent is killed and
+/// `HarnessError::Cancelled` returned; no diff is taken.
+pub async fn run_case_cancellable(
+    harness: &dyn AgentHarness,
+    spec: &HarnessSpec,
+    cancel: &CancellationToken,
+) -> HarnessResult<HarnessOutcome> {
+    let mut process = tokio::select! {
+        process = harness.spawn(spec) => process?,
+        _ = cancel.cancelled() => return Err(HarnessError::Cancelled),
+    };
+    process.close_input();
+    // Dropping the process on cancellation kills the agent
+    tokio::select! {
+        outcome = harness.collect(process) => outcome,
+        _ = cancel.cancelled() => Err(HarnessError::Cancelled),
+    }
+}
+
+/// Amp in `--execute --stream-json` mode, prompted over stdin
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AmpHarness;
+
+impl AmpHarness {
+    /// Command and arguments for `env`: a local CLI build under node when
+    /// `AMP_CLI_PATH` is set, otherwise `AMP_BIN` or `amp`
+    pub fn command(env: &HashMap<String, String>) -> (String, Vec<String>) {
+        if let Some(path) = env.get("AMP_CLI_PATH") {
+            // Local CLI: node path/to/main.js --execute --stream-json --stream-json-input
+            ("node".to_string(), vec![
+                "--enable-source-maps".into(),
+                "--no-warnings".into(),
+                "--unhandled-rejections=strict".into(),
+                "--max-old-space-size=2048".into(),
+                "--experimental-json-modules".into(),
+                path.clone(),
+                "--execute".into(),
+                "--stream-json".into(),
+                "--stream-json-input".into()
+            ])
+        } else {
+            // Production: amp --execute --stream-json --stream-json-input
+            (env.get("AMP_BIN").cloned().unwrap_or_else(|| "amp".into()), vec![
+                "--execute".into(),
+                "--stream-json".into(),
+                "--stream-json-input".into()
+            ])
+        }
+    }
+
+    /// Stream-json input line carrying a user prompt
+    pub fn user_message(prompt: &str) -> Value {
+        serde_json::json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": [{ "type": "text", "text": prompt }]
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl AgentHarness for AmpHarness {
+    fn name(&self) -> &str {
+        "amp"
+    }
+
+    async fn spawn(&self, spec: &HarnessSpec) -> HarnessResult<AgentProcess> {
+        let (program, args) = Self::command(&spec.env);
+        let mut process = AgentProcess::spawn(self.name(), &program, &args, spec, true)?;
+        self.send_prompt(&mut process, &spec.prompt).await?;
+        Ok(process)
+    }
+
+    async fn send_prompt(&self, process: &mut AgentProcess, prompt: &str) -> HarnessResult<()> {
+        process.write_line(&Self::user_message(prompt).to_string()).await
+    }
+}
+
+/// Any CLI agent, such as `aider --yes --message {prompt}`. The prompt replaces
+/// `{prompt}` in the arguments, or is written to stdin when no argument has it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandHarness {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+    /// Parse stdout lines as JSON events, for agents with a stream-json style output
+    #[serde(default)]
+    pub json_events: bool,
+}
+
+impl CommandHarness {
+    pub fn new(name: impl Into<String>, program: impl Into<String>, args: Vec<String>) -> Self {
+        Self { name: name.into(), program: program.into(), args, json_events: false }
+    }
+
+    pub fn with_json_events(mut self, json_events: bool) -> Self {
+        self.json_events = json_events;
+        self
+    }
+
+    fn takes_prompt_argument(&self) -> bool {
+        self.args.iter().any(|arg| arg.contains(PROMPT_PLACEHOLDER))
+    }
+}
+
+#[async_trait]
+impl AgentHarness for CommandHarness {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn spawn(&self, spec: &HarnessSpec) -> HarnessResult<AgentProcess> {
+        let args: Vec<String> = self.args.iter().map(|arg| arg.replace(PROMPT_PLACEHOLDER, &spec.prompt)).collect();
+        let mut process = AgentProcess::spawn(&self.name, &self.program, &args, spec, self.json_events)?;
+        if !self.takes_prompt_argument() {
+            // An agent that exits without reading its input is judged by its exit status
+            match process.write_line(&spec.prompt).await {
+                Err(HarnessError::InputClosed) => {}
+                result => result?,
+            }
+        }
+        Ok(process)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command as StdCommand;
+
+    fn spec(dir: &Path, prompt: &str) -> HarnessSpec {
+        let env = std::env::vars().filter(|(k, _)| k == "PATH" || k == "HOME").collect();
+        HarnessSpec { prompt: prompt.to_string(), working_dir: dir.to_path_buf(), env, limits: None }
+    }
+
+    fn init_repo(dir: &Path) {
+        for args in [
+            vec!["init", "-q"],
+            vec!["config", "user.email", "test@example.com"],
+            vec!["config", "user.name", "Test"],
+        ] {
+            StdCommand::new("git").current_dir(dir).args(&args).status().unwrap();
+        }
+        std::fs::write(dir.join("README.md"), "hello\n").unwrap();
+        StdCommand::new("git").current_dir(dir).args(["add", "."]).status().unwrap();
+        StdCommand::new("git").current_dir(dir).args(["commit", "-qm", "init"]).status().unwrap();
+    }
+
+    #[test]
+    fn test_amp_command_selection() {
+        let mut env = HashMap::new();
+        assert_eq!(AmpHarness::command(&env).0, "amp");
+        env.insert("AMP_BIN".to_string(), "/opt/amp".to_string());
+        assert_eq!(AmpHarness::command(&env), ("/opt/amp".to_string(), vec!["--execute".to_string(), "--stream-json".to_string(), "--stream-json-input".to_string()]));
+        env.insert("AMP_CLI_PATH".to_string(), "/src/cli/main.js".to_string());
+        let (program, args) = AmpHarness::command(&env);
+        assert_eq!(program, "node");
+        assert!(args.contains(&"/src/cli/main.js".to_string()));
+        assert_eq!(AmpHarness::user_message("hi")["message"]["content"][0]["text"], "hi");
+    }
+
+    #[tokio::test]
+    async fn test_command_harness_prompt_argument_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path());
+        let harness = CommandHarness::new(
+            "shell",
+            "sh",
+            vec!["-c".into(), "printf '%s\\n' \"$1\" > notes.txt; echo done; echo warn >&2".into(), "sh".into(), PROMPT_PLACEHOLDER.into()],
+        );
+
+        let outcome = run_case(&harness, &spec(dir.path(), "fix the bug")).await.unwrap();
+        assert!(outcome.success);
+        assert_eq!(outcome.exit_code, Some(0));
+        assert_eq!(outcome.harness, "shell");
+        assert!(outcome.transcript.contains(&AgentEvent::Output { text: "done".into() }));
+        assert!(outcome.transcript.contains(&AgentEvent::Stderr { text: "warn".into() }));
+        let diff = outcome.diff.unwrap();
+        assert!(diff.contains("+fix the bug"), "{}", diff);
+        // The scratch index leaves the repository's own index untouched
+        let status = StdCommand::new("git").current_dir(dir.path()).args(["status", "--porcelain"]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&status.stdout), "?? notes.txt\n");
+    }
+
+    #[tokio::test]
+    async fn test_command_harness_json_events_and_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = r#"read prompt
+echo '{"type":"assistant","message":{"content":[{"type":"tool_use","name":"edit_file"}],"usage":{"input_tokens":7,"output_tokens":3}}}'
+echo "not json: $prompt"
+echo '{"type":"result","subtype":"error_during_execution","is_error":true,"total_cost_usd":0.5}'"#;
+        let harness = CommandHarness::new("fake-amp", "sh", vec!["-c".into(), script.into()]).with_json_events(true);
+
+        let mut process = harness.spawn(&spec(dir.path(), "go")).await.unwrap();
+        process.close_input();
+        let first = harness.next_event(&mut process).await.unwrap().unwrap();
+        assert!(matches!(first, AgentEvent::Json { .. }));
+        let outcome = harness.collect(process).await.unwrap();
+
+        assert!(!outcome.success);
+        assert_eq!(outcome.error_message.as_deref(), Some("error_during_execution"));
+        assert_eq!(outcome.diff, None);
+        assert_eq!((outcome.metrics.iterations, outcome.metrics.tokens_used, outcome.metrics.cost), (1, 10, 0.5));
+        assert_eq!(outcome.metrics.tools_used.get("edit_file"), Some(&1));
+        assert!(outcome.transcript.contains(&AgentEvent::Output { text: "not json: go".into() }));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_failure_and_exit_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = CommandHarness::new("missing", "/nonexistent/agent", Vec::new());
+        assert!(matches!(missing.spawn(&spec(dir.path(), "x")).await, Err(HarnessError::SpawnFailed { .. })));
+
+        let failing = CommandHarness::new("failing", "sh", vec!["-c".into(), "exit 3".into()]);
+        let outcome = run_case(&failing, &spec(dir.path(), "x")).await.unwrap();
+        assert_eq!((outcome.success, outcome.exit_code), (false, Some(3)));
+        assert_eq!(outcome.error_message.as_deref(), Some("failing exited with status 3"));
+    }
+
+    #[tokio::test]
+    async fn test_run_case_cancellable() {
+        let dir = tempfile::tempdir().unwrap();
+        let slow = CommandHarness::new("slow", "sh", vec!["-c".into(), "sleep 30".into()]);
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let result = run_case_cancellable(&slow, &spec(dir.path(), "x"), &cancel).await;
+        assert!(matches!(result, Err(HarnessError::Cancelled)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(matches!(run_case_cancellable(&slow, &spec(dir.path(), "x"), &cancel).await, Err(HarnessError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_drain_within_kills_on_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let slow = CommandHarness::new("slow", "sh", vec!["-c".into(), "echo started; sleep 30".into()]);
+        let limits = ProcessLimits { max_execution_time: std::time::Duration::from_millis(200), ..ProcessLimits::default() };
+
+        let started = std::time::Instant::now();
+        let mut process = slow.spawn(&spec(dir.path(), "x")).await.unwrap();
+        let exceeded = process.drain_within(&limits).await;
+        assert_eq!(exceeded, Some(LimitExceeded::ExecutionTime { limit_secs: 0 }));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(!process.finish().await.unwrap().success);
+
+        let quick = CommandHarness::new("quick", "sh", vec!["-c".into(), "echo done".into()]);
+        let mut process = quick.spawn(&spec(dir.path(), "x")).await.unwrap();
+        assert_eq!(process.drain_within(&limits).await, None);
+    }
+}
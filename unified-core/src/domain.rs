@@ -43,6 +43,8 @@ pub enum SessionStatus {
     Initializing,
     Idle,
     Running,
+    /// Process is paused (SIGSTOP or equivalent) and keeps its state until resumed
+    Suspended,
     AwaitingInput,
     Evaluating,
     Error(String),
@@ -86,13 +88,20 @@ pub struct RuntimeConfig {
     pub environment_variables: HashMap<String, String>,
     pub process_limits: ProcessLimits,
     pub toolbox_config: Option<ToolboxConfig>,
+    /// Spawn with only `environment_variables` and `ISOLATED_ENV_ALLOWLIST`
+    #[serde(default)]
+    pub strict_env_isolation: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessLimits {
+    /// Resident memory, enforced on Linux only; zero for no limit
     pub max_memory_mb: u64,
+    /// Not enforced
     pub max_cpu_percent: f32,
+    /// Zero for no limit
     pub max_execution_time: Duration,
+    /// Zero for no limit
     pub max_open_files: usize,
 }
 
@@ -368,6 +377,7 @@ impl Default for RuntimeConfig {
             environment_variables: HashMap::new(),
             process_limits: ProcessLimits::default(),
             toolbox_config: None,
+            strict_env_isolation: false,
         }
     }
 }
@@ -0,0 +1,190 @@
+use crate::domain::RuntimeConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Expected shape of an environment variable's value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvVarKind {
+    Text,
+    Url,
+    /// `1`/`0`, `true`/`false`, `yes`/`no`, `on`/`off`
+    Bool,
+    UnsignedInteger,
+    /// Platform path list, `:` separated (`;` on Windows)
+    PathList,
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnvVarSpec {
+    pub name: &'static str,
+    pub kind: EnvVarKind,
+    pub description: &'static str,
+}
+
+/// Variables read by the Amp CLI and the orchestrator
+pub const KNOWN_ENV_VARS: &[EnvVarSpec] = &[
+    EnvVarSpec { name: "AMP_ACTIVE_TOOLBOX_PROFILE", kind: EnvVarKind::Text, description: "Name of the toolbox profile in use" },
+    EnvVarSpec { name: "AMP_API_KEY", kind: EnvVarKind::Text, description: "API key used instead of interactive login" },
+    EnvVarSpec { name: "AMP_AUTH_CMD", kind: EnvVarKind::Text, description: "Command that prints an access token" },
+    EnvVarSpec { name: "AMP_BIN", kind: EnvVarKind::Text, description: "Amp executable name or path" },
+    EnvVarSpec { name: "AMP_CLI_PATH", kind: EnvVarKind::Path, description: "Path to a local Amp CLI build" },
+    EnvVarSpec { name: "AMP_DB_NAMESPACE", kind: EnvVarKind::Text, description: "Namespace for the CLI's local database" },
+    EnvVarSpec { name: "AMP_DEBUG", kind: EnvVarKind::Bool, description: "Verbose CLI logging" },
+    EnvVarSpec { name: "AMP_EMAIL", kind: EnvVarKind::Text, description: "Login email for automated runs" },
+    EnvVarSpec { name: "AMP_ENABLE_TOOLBOXES", kind: EnvVarKind::Bool, description: "Whether toolbox paths are resolved" },
+    EnvVarSpec { name: "AMP_ENVIRONMENT", kind: EnvVarKind::Text, description: "development or production" },
+    EnvVarSpec { name: "AMP_EXPERIMENTAL_AGENT_MODE", kind: EnvVarKind::Text, description: "Agent mode such as geppetto:main" },
+    EnvVarSpec { name: "AMP_MODEL", kind: EnvVarKind::Text, description: "Model override for the agent" },
+    EnvVarSpec { name: "AMP_PASSWORD", kind: EnvVarKind::Text, description: "Login password for automated runs" },
+    EnvVarSpec { name: "AMP_PROFILE_ID", kind: EnvVarKind::Text, description: "Amp profile a session runs under, set by the orchestrator" },
+    EnvVarSpec { name: "AMP_REFRESH_TOKEN", kind: EnvVarKind::Text, description: "Refresh token for automated runs" },
+    EnvVarSpec { name: "AMP_SERVER_URL", kind: EnvVarKind::Url, description: "Amp server the CLI talks to" },
+    EnvVarSpec { name: "AMP_TOKEN", kind: EnvVarKind::Text, description: "Access token" },
+    EnvVarSpec { name: "AMP_TOOLBOX", kind: EnvVarKind::Path, description: "Resolved toolbox root, set by the orchestrator" },
+    EnvVarSpec { name: "AMP_TOOLBOX_MAX_BYTES", kind: EnvVarKind::UnsignedInteger, description: "Toolbox size limit in bytes" },
+    EnvVarSpec { name: "AMP_TOOLBOX_MAX_FILES", kind: EnvVarKind::UnsignedInteger, description: "Toolbox file count limit" },
+    EnvVarSpec { name: "AMP_TOOLBOX_MAX_MB", kind: EnvVarKind::UnsignedInteger, description: "Toolbox size limit in megabytes" },
+    EnvVarSpec { name: "AMP_TOOLBOX_PATHS", kind: EnvVarKind::PathList, description: "Toolbox directories to merge" },
+    EnvVarSpec { name: "AMP_URL", kind: EnvVarKind::Url, description: "Amp server URL" },
+    EnvVarSpec { name: "NODE_TLS_REJECT_UNAUTHORIZED", kind: EnvVarKind::Bool, description: "Set to 0 to accept self-signed local servers" },
+];
+
+/// Prefix of variables checked against the schema; others are passed through unchecked
+pub const ENV_SCHEMA_PREFIX: &str = "AMP_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvProblemKind {
+    /// `AMP_*` name the schema does not know, most likely a typo
+    UnknownVariable,
+    InvalidValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvProblem {
+    pub variable: String,
+    pub kind: EnvProblemKind,
+    pub message: String,
+    /// Closest known variable for an unknown name
+    pub suggestion: Option<String>,
+}
+
+pub fn env_var_spec(name: &str) -> Option<&'static EnvVarSpec> {
+    KNOWN_ENV_VARS.iter().find(|spec| spec.name == name)
+}
+
+impl EnvVarKind {
+    fn check(&self, value: &str) -> Result<(), String> {
+        match self {
+            EnvVarKind::Text => Ok(()),
+            EnvVarKind::Url => {
+                if value.starts_with("http://") || value.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err(format!("'{}' is not an http(s) URL", value))
+                }
+            }
+            EnvVarKind::Bool => match value.to_ascii_lowercase().as_str() {
+                "1" | "0" | "true" | "false" | "yes" | "no" | "on" | "off" => Ok(()),
+                _ => Err(format!("'{}' is not a boolean (use 1/0 or true/false)", value)),
+            },
+            EnvVarKind::UnsignedInteger => value
+                .parse::<u64>()
+                .map(|_| ())
+                .map_err(|_| format!("'{}' is not a non-negative integer", value)),
+            EnvVarKind::PathList | EnvVarKind::Path => {
+                if value.trim().is_empty() {
+                    Err("path is empty".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+fn closest_known(name: &str) -> Option<String> {
+    KNOWN_ENV_VARS
+        .iter()
+        .map(|spec| (edit_distance(name, spec.name), spec.name))
+        .filter(|(distance, _)| *distance <= 3)
+        .min()
+        .map(|(_, known)| known.to_string())
+}
+
+/// Check an environment against `KNOWN_ENV_VARS`. Problems are sorted by variable name.
+pub fn lint_env(env: &HashMap<String, String>) -> Vec<EnvProblem> {
+    let mut problems = Vec::new();
+    for (name, value) in env {
+        match env_var_spec(name) {
+            Some(spec) => {
+                if let Err(reason) = spec.kind.check(value) {
+                    problems.push(EnvProblem {
+                        variable: name.clone(),
+                        kind: EnvProblemKind::InvalidValue,
+                        message: format!("{}: {}", name, reason),
+                        suggestion: None,
+                    });
+                }
+            }
+            None if name.starts_with(ENV_SCHEMA_PREFIX) => {
+                let suggestion = closest_known(name);
+                let message = match &suggestion {
+                    Some(known) => format!("{} is not a known variable; did you mean {}?", name, known),
+                    None => format!("{} is not a known variable and will be ignored", name),
+                };
+                problems.push(EnvProblem { variable: name.clone(), kind: EnvProblemKind::UnknownVariable, message, suggestion });
+            }
+            None => {}
+        }
+    }
+    problems.sort_by(|a, b| a.variable.cmp(&b.variable));
+    problems
+}
+
+/// Variables a strictly isolated process still takes from the orchestrator's own environment
+pub const ISOLATED_ENV_ALLOWLIST: &[&str] = &["PATH", "HOME", "LANG"];
+
+/// `composed` over the allowlisted variables of this process, and nothing else
+pub fn isolated_env(composed: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut env: HashMap<String, String> = ISOLATED_ENV_ALLOWLIST
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (name.to_string(), value)))
+        .collect();
+    env.extend(composed.iter().map(|(key, value)| (key.clone(), value.clone())));
+    env
+}
+
+impl RuntimeConfig {
+    /// Problems in `environment_variables`, such as misspelled `AMP_*` names
+    pub fn lint_environment(&self) -> Vec<EnvProblem> {
+        lint_env(&self.environment_variables)
+    }
+
+    /// Complete environment of a session's process, to be spawned with a cleared environment.
+    /// Without strict isolation this process's own environment is inherited underneath.
+    pub fn process_env(&self) -> HashMap<String, String> {
+        if self.strict_env_isolation {
+            return isolated_env(&self.environment_variables);
+        }
+        let mut env: HashMap<String, String> = std::env::vars().collect();
+        env.extend(self.environment_variables.clone());
+        env
+    }
+}
@@ -17,6 +17,9 @@ pub enum UnifiedError {
     
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+
+    #[error("Harness error: {0}")]
+    Harness(#[from] HarnessError),
 }
 
 #[derive(Error, Debug)]
@@ -59,6 +62,9 @@ pub enum GitError {
     
     #[error("Working directory not clean: {reason}")]
     DirtyWorkingDirectory { reason: String },
+
+    #[error("Git operation cancelled: {operation}")]
+    Cancelled { operation: String },
     
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -91,7 +97,23 @@ pub enum PersistenceError {
     NotImplemented(String),
 }
 
+#[derive(Error, Debug)]
+pub enum HarnessError {
+    #[error("Failed to start {program}: {reason}")]
+    SpawnFailed { program: String, reason: String },
+
+    #[error("Agent input is closed")]
+    InputClosed,
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
 pub type Result<T> = std::result::Result<T, UnifiedError>;
 pub type SessionResult<T> = std::result::Result<T, SessionError>;
 pub type GitResult<T> = std::result::Result<T, GitError>;
 pub type PersistenceResult<T> = std::result::Result<T, PersistenceError>;
+pub type HarnessResult<T> = std::result::Result<T, HarnessError>;
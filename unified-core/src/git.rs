@@ -1,7 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 use crate::domain::{SessionId, WorktreeInfo};
 use crate::error::{GitError, GitResult};
 
@@ -14,6 +17,153 @@ pub enum GitContext {
     Session(PathBuf),
 }
 
+/// How much of a repository a new worktree materializes
+///
+/// Both options target very large repositories. `sparse_paths` limits the files
+/// written into each worktree to a set of directories (cone mode: files at the
+/// repository root are always included). `blob_filter` marks `origin` as a
+/// partial-clone promisor remote, so fetches skip file contents and blobs are
+/// downloaded only when a checkout needs them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckoutOptions {
+    /// Repository-relative directories to check out; empty for a full checkout
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    /// Partial clone filter spec such as `blob:none` or `blob:limit=1m`
+    #[serde(default)]
+    pub blob_filter: Option<String>,
+}
+
+impl CheckoutOptions {
+    pub fn is_sparse(&self) -> bool {
+        !self.sparse_paths.is_empty()
+    }
+
+    /// Git config entries that turn `remote` into a promisor remote for `blob_filter`
+    pub fn partial_clone_config(&self, remote: &str) -> Vec<(String, String)> {
+        match &self.blob_filter {
+            Some(filter) => vec![
+                (format!("remote.{}.promisor", remote), "true".to_string()),
+                (format!("remote.{}.partialclonefilter", remote), filter.clone()),
+            ],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Clone `url` into `dest` as a partial and/or sparse clone according to `options`
+///
+/// Uses the git CLI; libgit2 has no support for object filters.
+pub async fn clone_repository(url: &str, dest: &Path, options: &CheckoutOptions) -> GitResult<()> {
+    let dest_str = dest.to_string_lossy().to_string();
+    let filter_arg = options.blob_filter.as_ref().map(|f| format!("--filter={}", f));
+    let mut args = vec!["clone", "--quiet"];
+    if let Some(filter_arg) = &filter_arg {
+        args.push(filter_arg);
+    }
+    if options.is_sparse() {
+        args.push("--sparse");
+    }
+    args.extend([url, dest_str.as_str()]);
+    run_git(Path::new("."), &args).await?;
+
+    if options.is_sparse() {
+        let mut sparse_args = vec!["sparse-checkout", "set", "--cone", "--"];
+        sparse_args.extend(options.sparse_paths.iter().map(String::as_str));
+        run_git(dest, &sparse_args).await?;
+    }
+    Ok(())
+}
+
+async fn run_git(dir: &Path, args: &[&str]) -> GitResult<String> {
+    let output = tokio::process::Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| GitError::OperationFailed {
+            operation: format!("git {}", args.join(" ")),
+            reason: format!("Failed to execute git command: {}", e),
+        })?;
+    if !output.status.success() {
+        return Err(GitError::OperationFailed {
+            operation: format!("git {}", args.join(" ")),
+            reason: format!("Git command failed: {}", String::from_utf8_lossy(&output.stderr)),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Files and bytes a checkout would write
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckoutEstimate {
+    pub files: u64,
+    /// Blobs a partial clone has not downloaded are not counted
+    pub bytes: u64,
+}
+
+/// Size of a checkout of `rev` following `options`' sparse paths
+///
+/// With a blob filter only files are counted, since asking for blob sizes would
+/// download the blobs.
+pub async fn estimate_checkout(repo_root: &Path, rev: &str, options: &CheckoutOptions) -> GitResult<CheckoutEstimate> {
+    let args: &[&str] = if options.blob_filter.is_some() { &["ls-tree", "-r", rev] } else { &["ls-tree", "-r", "-l", rev] };
+    let listing = run_git(repo_root, args).await?;
+    Ok(parse_tree_listing(&listing, options))
+}
+
+/// Sum `git ls-tree -r [-l]` output, keeping only files inside the sparse cone
+pub fn parse_tree_listing(listing: &str, options: &CheckoutOptions) -> CheckoutEstimate {
+    let mut estimate = CheckoutEstimate::default();
+    for line in listing.lines() {
+        let Some((meta, path)) = line.split_once('\t') else { continue };
+        let fields: Vec<&str> = meta.split_whitespace().collect();
+        if fields.get(1) != Some(&"blob") || !in_sparse_cone(path, &options.sparse_paths) {
+            continue;
+        }
+        estimate.files += 1;
+        estimate.bytes += fields.get(3).and_then(|size| size.parse::<u64>().ok()).unwrap_or(0);
+    }
+    estimate
+}
+
+/// Cone mode: files at the repository root and everything under a sparse directory
+fn in_sparse_cone(path: &str, sparse_paths: &[String]) -> bool {
+    sparse_paths.is_empty()
+        || !path.contains('/')
+        || sparse_paths.iter().map(|dir| dir.trim_matches('/')).any(|dir| path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/')))
+}
+
+/// Stage reached while creating a worktree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum WorktreeProgress {
+    /// Pre-flight size of the checkout
+    Estimated { files: u64, bytes: u64 },
+    BranchCreated { branch: String },
+    /// Git only reports files written once a checkout has run for a couple of seconds
+    CheckingOut { percent: u8, files_done: u64, files_total: u64 },
+    /// Checkout hooks have run and the worktree's identity and AGENT_CONTEXT are in place
+    HooksRun,
+    Ready,
+}
+
+/// Receives the stages of a worktree creation as they are reached
+pub type ProgressFn = Arc<dyn Fn(WorktreeProgress) + Send + Sync>;
+
+/// A `ProgressFn` that drops every stage
+pub fn ignore_progress() -> ProgressFn {
+    Arc::new(|_| {})
+}
+
+/// `percent`, files written and total from a git `Updating files:  45% (450/1000)` line
+pub fn parse_checkout_progress(line: &str) -> Option<(u8, u64, u64)> {
+    let rest = line.trim().strip_prefix("Updating files:")?.trim_start();
+    let (percent, rest) = rest.split_once('%')?;
+    let (done, total) = rest.trim().strip_prefix('(')?.split(')').next()?.split_once('/')?;
+    Some((percent.trim().parse().ok()?, done.parse().ok()?, total.parse().ok()?))
+}
+
 /// Trait defining the five async Git backend operations
 #[async_trait]
 pub trait GitBackend: Send + Sync {
@@ -53,6 +203,26 @@ pub trait GitBackend: Send + Sync {
 
     /// Check git command status with specified context
     async fn git_command_succeeds_in_context(&self, args: &[&str], context: GitContext) -> bool;
+
+    /// `create_worktree` reporting its stages to `progress`. When `cancel` fires the
+    /// partial worktree and branch are removed and `GitError::Cancelled` returned;
+    /// backends that cannot interrupt a checkout finish it before removing it.
+    async fn create_worktree_with_progress(
+        &self,
+        session_id: &SessionId,
+        base_branch: &str,
+        branch_name: &str,
+        progress: &ProgressFn,
+        cancel: &CancellationToken,
+    ) -> GitResult<WorktreeInfo> {
+        let info = self.create_worktree(session_id, base_branch, branch_name).await?;
+        if cancel.is_cancelled() {
+            let _ = self.cleanup_worktree(session_id).await;
+            return Err(GitError::Cancelled { operation: format!("create worktree for {}", session_id) });
+        }
+        progress(WorktreeProgress::BranchCreated { branch: branch_name.to_string() });
+        Ok(info)
+    }
 }
 
 /// LibGit2Backend - Production backend using git2-rs with async compatibility
@@ -60,6 +230,7 @@ pub trait GitBackend: Send + Sync {
 pub struct LibGit2Backend {
     repo_root: PathBuf,
     worktrees_dir: PathBuf,
+    checkout: CheckoutOptions,
     /// File lock mutex to prevent concurrent Git operations
     _lock: Arc<Mutex<()>>,
 }
@@ -78,10 +249,17 @@ impl LibGit2Backend {
         Ok(Self {
             repo_root,
             worktrees_dir,
+            checkout: CheckoutOptions::default(),
             _lock: Arc::new(Mutex::new(())),
         })
     }
 
+    /// Use sparse and partial checkouts for new worktrees
+    pub fn with_checkout(mut self, checkout: CheckoutOptions) -> Self {
+        self.checkout = checkout;
+        self
+    }
+
     /// Generate a unique branch name for the session
     fn generate_branch_name(session_id: &SessionId) -> String {
         format!("amp-session-{}", &session_id[..8])
@@ -184,7 +362,30 @@ impl GitBackend for LibGit2Backend {
                 reason: e.to_string(),
             })?;
 
-        // 7. Create AGENT_CONTEXT directory
+        // 7. Write the sparse paths of the branch into the directory
+        if self.checkout.is_sparse() {
+            let target_dir = worktree_path.clone();
+            let branch_name_clone = branch_name.to_string();
+            let sparse_paths = self.checkout.sparse_paths.clone();
+            self.with_repo(move |repo| {
+                let branch = repo.find_branch(&branch_name_clone, git2::BranchType::Local)?;
+                let tree = branch.get().peel_to_tree()?;
+                let mut checkout = git2::build::CheckoutBuilder::new();
+                checkout.target_dir(&target_dir).update_index(false).force();
+                for path in &sparse_paths {
+                    checkout.path(path.trim_matches('/'));
+                }
+                // Cone mode always includes the files at the repository root
+                for entry in tree.iter().filter(|e| e.kind() == Some(git2::ObjectType::Blob)) {
+                    if let Some(name) = entry.name() {
+                        checkout.path(name);
+                    }
+                }
+                repo.checkout_tree(tree.as_object(), Some(&mut checkout))
+            }).await?;
+        }
+
+        // 8. Create AGENT_CONTEXT directory
         let agent_context_dir = worktree_path.join("AGENT_CONTEXT");
         tokio::fs::create_dir_all(&agent_context_dir)
             .await
@@ -193,7 +394,7 @@ impl GitBackend for LibGit2Backend {
                 reason: e.to_string(),
             })?;
 
-        // 8. Return worktree info
+        // 9. Return worktree info
         Ok(WorktreeInfo {
             session_id: session_id.clone(),
             worktree_path,
@@ -368,6 +569,21 @@ impl GitBackend for LibGit2Backend {
             }
         }
 
+        // Mark origin as a promisor remote so later fetches honour the blob filter
+        let entries = self.checkout.partial_clone_config("origin");
+        if !entries.is_empty() {
+            self.with_repo(move |repo| {
+                if repo.find_remote("origin").is_err() {
+                    return Ok(());
+                }
+                let mut config = repo.config()?;
+                for (key, value) in &entries {
+                    config.set_str(key, value)?;
+                }
+                Ok(())
+            }).await?;
+        }
+
         Ok(())
     }
 
@@ -390,6 +606,7 @@ impl GitBackend for LibGit2Backend {
 pub struct CliBackend {
     repo_root: PathBuf,
     worktrees_dir: PathBuf,
+    checkout: CheckoutOptions,
     /// File lock mutex to prevent concurrent Git operations
     _lock: Arc<Mutex<()>>,
 }
@@ -408,10 +625,17 @@ impl CliBackend {
         Ok(Self {
             repo_root,
             worktrees_dir,
+            checkout: CheckoutOptions::default(),
             _lock: Arc::new(Mutex::new(())),
         })
     }
 
+    /// Use sparse and partial checkouts for new worktrees
+    pub fn with_checkout(mut self, checkout: CheckoutOptions) -> Self {
+        self.checkout = checkout;
+        self
+    }
+
     /// Get the worktree path for a session
     fn get_worktree_path(&self, session_id: &SessionId) -> PathBuf {
         self.worktrees_dir.join(session_id)
@@ -427,33 +651,18 @@ impl CliBackend {
         self.git_command_succeeds_in_context(args, GitContext::Repository).await
     }
 
-    /// Extract session ID from worktree path if it's in our .worktrees directory
-    fn extract_session_id(&self, path: &str) -> Option<String> {
-        let path_buf = PathBuf::from(path);
-        
-        // Try to canonicalize both paths to handle symlinks
-        let canonical_path = path_buf.canonicalize().unwrap_or(path_buf.clone());
-        let canonical_worktrees_dir = self.worktrees_dir.canonicalize().unwrap_or(self.worktrees_dir.clone());
-        
-        if let Ok(relative) = canonical_path.strip_prefix(&canonical_worktrees_dir) {
-            if let Some(session_id) = relative.components().next() {
-                return Some(session_id.as_os_str().to_string_lossy().to_string());
-            }
-        }
-        None
+    /// Apply the sparse patterns to a worktree added with `--no-checkout` and check it out
+    async fn sparse_checkout(&self, worktree_path: &Path) -> GitResult<()> {
+        let context = || GitContext::Session(worktree_path.to_path_buf());
+        let mut args = vec!["sparse-checkout", "set", "--cone", "--"];
+        args.extend(self.checkout.sparse_paths.iter().map(String::as_str));
+        self.run_git_command_in_context(&args, context()).await?;
+        self.run_git_command_in_context(&["reset", "--quiet", "--hard", "HEAD"], context()).await?;
+        Ok(())
     }
-}
-
-#[async_trait]
-impl GitBackend for CliBackend {
-    async fn create_worktree(
-        &self,
-        session_id: &SessionId,
-        base_branch: &str,
-        branch_name: &str,
-    ) -> GitResult<WorktreeInfo> {
-        let _guard = self._lock.lock().await;
 
+    /// Fail unless a worktree for `branch_name` can be added at `worktree_path`
+    async fn check_new_worktree(&self, base_branch: &str, branch_name: &str, worktree_path: &Path) -> GitResult<()> {
         // 1. Validate base branch exists
         if !self.is_branch_existing(base_branch).await? {
             return Err(GitError::BranchNotFound {
@@ -475,25 +684,17 @@ impl GitBackend for CliBackend {
             });
         }
 
-        let worktree_path = self.get_worktree_path(session_id);
-
         // 4. Check if worktree path already exists
         if worktree_path.exists() {
             return Err(GitError::WorktreeExists {
-                path: worktree_path,
+                path: worktree_path.to_path_buf(),
             });
         }
+        Ok(())
+    }
 
-        // 5. Create worktree using git commands
-        let worktree_path_str = worktree_path.to_string_lossy();
-        
-        // Create worktree: git worktree add -b <branch> <path> <base_branch>
-        self.run_git_command(&[
-            "worktree", "add", "-b", branch_name, 
-            &worktree_path_str, base_branch
-        ]).await?;
-
-        // 6. Create AGENT_CONTEXT directory
+    /// Create AGENT_CONTEXT in a checked out worktree and describe it
+    async fn finish_worktree(&self, session_id: &SessionId, worktree_path: PathBuf, base_branch: &str, branch_name: &str) -> GitResult<WorktreeInfo> {
         let agent_context_dir = worktree_path.join("AGENT_CONTEXT");
         tokio::fs::create_dir_all(&agent_context_dir)
             .await
@@ -502,7 +703,6 @@ impl GitBackend for CliBackend {
                 reason: e.to_string(),
             })?;
 
-        // 7. Return worktree info
         Ok(WorktreeInfo {
             session_id: session_id.clone(),
             worktree_path,
@@ -514,6 +714,169 @@ impl GitBackend for CliBackend {
         })
     }
 
+    /// Write the files of a worktree added with `--no-checkout`, running its checkout hooks
+    async fn checkout_with_progress(&self, worktree_path: &Path, progress: &ProgressFn, cancel: &CancellationToken) -> GitResult<()> {
+        if self.checkout.is_sparse() {
+            let mut args = vec!["sparse-checkout", "set", "--cone", "--"];
+            args.extend(self.checkout.sparse_paths.iter().map(String::as_str));
+            self.run_git_command_in_context(&args, GitContext::Session(worktree_path.to_path_buf())).await?;
+        }
+
+        let operation = "git checkout --progress --force".to_string();
+        let mut child = tokio::process::Command::new("git")
+            .current_dir(worktree_path)
+            .args(["checkout", "--progress", "--force"])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| GitError::OperationFailed { operation: operation.clone(), reason: e.to_string() })?;
+        let mut stderr = child.stderr.take().ok_or_else(|| GitError::OperationFailed {
+            operation: operation.clone(),
+            reason: "stderr not captured".to_string(),
+        })?;
+
+        // Progress lines end in `\r`; anything else is kept for the error message
+        let watch = async {
+            let mut messages = String::new();
+            let mut pending = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let read = stderr.read(&mut buf).await?;
+                if read == 0 {
+                    break;
+                }
+                pending.extend_from_slice(&buf[..read]);
+                while let Some(end) = pending.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line[..line.len() - 1]).trim().to_string();
+                    match parse_checkout_progress(&line) {
+                        Some((percent, files_done, files_total)) => {
+                            progress(WorktreeProgress::CheckingOut { percent, files_done, files_total });
+                        }
+                        None if !line.is_empty() => {
+                            messages.push_str(&line);
+                            messages.push('\n');
+                        }
+                        None => {}
+                    }
+                }
+            }
+            Ok::<_, std::io::Error>((child.wait().await?, messages))
+        };
+        let outcome = tokio::select! {
+            outcome = watch => Some(outcome),
+            _ = cancel.cancelled() => None,
+        };
+        let Some(outcome) = outcome else {
+            let _ = child.kill().await;
+            return Err(GitError::Cancelled { operation });
+        };
+        let (status, messages) = outcome.map_err(|e| GitError::OperationFailed { operation: operation.clone(), reason: e.to_string() })?;
+        if !status.success() {
+            return Err(GitError::OperationFailed { operation, reason: format!("Git command failed: {}", messages.trim()) });
+        }
+        Ok(())
+    }
+
+    /// Remove whatever a failed or cancelled worktree creation left behind
+    async fn remove_partial_worktree(&self, worktree_path: &Path, branch_name: &str) {
+        let worktree_path_str = worktree_path.to_string_lossy();
+        let _ = self.run_git_command(&["worktree", "remove", "--force", &worktree_path_str]).await;
+        if worktree_path.exists() {
+            let _ = tokio::fs::remove_dir_all(worktree_path).await;
+        }
+        let _ = self.run_git_command(&["worktree", "prune"]).await;
+        let _ = self.run_git_command(&["branch", "-D", branch_name]).await;
+    }
+
+    /// Extract session ID from worktree path if it's in our .worktrees directory
+    fn extract_session_id(&self, path: &str) -> Option<String> {
+        let path_buf = PathBuf::from(path);
+        
+        // Try to canonicalize both paths to handle symlinks
+        let canonical_path = path_buf.canonicalize().unwrap_or(path_buf.clone());
+        let canonical_worktrees_dir = self.worktrees_dir.canonicalize().unwrap_or(self.worktrees_dir.clone());
+        
+        if let Ok(relative) = canonical_path.strip_prefix(&canonical_worktrees_dir) {
+            if let Some(session_id) = relative.components().next() {
+                return Some(session_id.as_os_str().to_string_lossy().to_string());
+            }
+        }
+        None
+    }
+}
+
+#[async_trait]
+impl GitBackend for CliBackend {
+    async fn create_worktree(
+        &self,
+        session_id: &SessionId,
+        base_branch: &str,
+        branch_name: &str,
+    ) -> GitResult<WorktreeInfo> {
+        let _guard = self._lock.lock().await;
+
+        let worktree_path = self.get_worktree_path(session_id);
+        self.check_new_worktree(base_branch, branch_name, &worktree_path).await?;
+
+        // 5. Create worktree using git commands
+        let worktree_path_str = worktree_path.to_string_lossy();
+        
+        // Create worktree: git worktree add -b <branch> <path> <base_branch>
+        if self.checkout.is_sparse() {
+            // Set the sparse patterns before anything is written to disk
+            self.run_git_command(&[
+                "worktree", "add", "--no-checkout", "-b", branch_name,
+                &worktree_path_str, base_branch
+            ]).await?;
+            if let Err(e) = self.sparse_checkout(&worktree_path).await {
+                self.remove_partial_worktree(&worktree_path, branch_name).await;
+                return Err(e);
+            }
+        } else {
+            self.run_git_command(&[
+                "worktree", "add", "-b", branch_name, 
+                &worktree_path_str, base_branch
+            ]).await?;
+        }
+
+        self.finish_worktree(session_id, worktree_path, base_branch, branch_name).await
+    }
+
+    async fn create_worktree_with_progress(
+        &self,
+        session_id: &SessionId,
+        base_branch: &str,
+        branch_name: &str,
+        progress: &ProgressFn,
+        cancel: &CancellationToken,
+    ) -> GitResult<WorktreeInfo> {
+        let _guard = self._lock.lock().await;
+
+        let worktree_path = self.get_worktree_path(session_id);
+        self.check_new_worktree(base_branch, branch_name, &worktree_path).await?;
+
+        // Add the worktree without files so that the checkout can be watched and stopped
+        let worktree_path_str = worktree_path.to_string_lossy();
+        self.run_git_command(&[
+            "worktree", "add", "--no-checkout", "-b", branch_name,
+            &worktree_path_str, base_branch
+        ]).await?;
+        progress(WorktreeProgress::BranchCreated { branch: branch_name.to_string() });
+
+        let checkout = match cancel.is_cancelled() {
+            true => Err(GitError::Cancelled { operation: format!("create worktree for {}", session_id) }),
+            false => self.checkout_with_progress(&worktree_path, progress, cancel).await,
+        };
+        if let Err(e) = checkout {
+            self.remove_partial_worktree(&worktree_path, branch_name).await;
+            return Err(e);
+        }
+
+        self.finish_worktree(session_id, worktree_path, base_branch, branch_name).await
+    }
+
     async fn list_worktrees(&self) -> GitResult<Vec<WorktreeInfo>> {
         // Use git worktree list --porcelain to get worktree information
         let output = self.run_git_command(&["worktree", "list", "--porcelain"])
@@ -677,6 +1040,14 @@ impl GitBackend for CliBackend {
             }
         }
 
+        // Mark origin as a promisor remote so later fetches honour the blob filter
+        let entries = self.checkout.partial_clone_config("origin");
+        if !entries.is_empty() && self.git_command_succeeds(&["remote", "get-url", "origin"]).await {
+            for (key, value) in &entries {
+                self.run_git_command(&["config", key, value]).await?;
+            }
+        }
+
         Ok(())
     }
 
@@ -730,13 +1101,21 @@ impl GitBackend for CliBackend {
 
 /// Factory function to create the appropriate Git backend
 pub fn create_git_backend(repo_root: PathBuf) -> GitResult<Box<dyn GitBackend>> {
+    create_git_backend_with_checkout(repo_root, CheckoutOptions::default())
+}
+
+/// Create a Git backend whose worktrees follow `checkout`
+///
+/// libgit2 cannot download blobs missing from a partial clone, so a blob filter
+/// always selects the CLI backend.
+pub fn create_git_backend_with_checkout(repo_root: PathBuf, checkout: CheckoutOptions) -> GitResult<Box<dyn GitBackend>> {
     // Try LibGit2Backend first (if feature is enabled)
     #[cfg(feature = "libgit2")]
-    {
+    if checkout.blob_filter.is_none() {
         match LibGit2Backend::new(repo_root.clone()) {
             Ok(backend) => {
                 log::info!("Using LibGit2Backend for Git operations");
-                return Ok(Box::new(backend));
+                return Ok(Box::new(backend.with_checkout(checkout)));
             }
             Err(e) => {
                 log::warn!("LibGit2Backend failed to initialize: {:?}, falling back to CLI", e);
@@ -745,7 +1124,7 @@ pub fn create_git_backend(repo_root: PathBuf) -> GitResult<Box<dyn GitBackend>>
     }
     
     // Fallback to CliBackend
-    let backend = CliBackend::new(repo_root)?;
+    let backend = CliBackend::new(repo_root)?.with_checkout(checkout);
     log::info!("Using CliBackend for Git operations");
     Ok(Box::new(backend))
 }
@@ -880,6 +1259,133 @@ mod tests {
         assert!(worktrees.is_empty());
     }
 
+    /// Add `services/{api,web}` and `libs/shared` to a test repository
+    fn add_packages(repo_path: &std::path::Path) {
+        for dir in ["services/api", "services/web", "libs/shared"] {
+            std::fs::create_dir_all(repo_path.join(dir)).unwrap();
+            std::fs::write(repo_path.join(dir).join("index.ts"), "export {}\n").unwrap();
+        }
+        Command::new("git").current_dir(repo_path).args(["add", "."]).status().unwrap();
+        Command::new("git").current_dir(repo_path).args(["commit", "-m", "Add packages"]).status().unwrap();
+    }
+
+    fn sparse_options() -> CheckoutOptions {
+        CheckoutOptions {
+            sparse_paths: vec!["services/api".to_string(), "libs/shared".to_string()],
+            blob_filter: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cli_backend_sparse_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = create_test_repo(&temp_dir).await.unwrap();
+        add_packages(&repo_path);
+
+        let backend = CliBackend::new(repo_path.clone()).unwrap().with_checkout(sparse_options());
+        backend.initialize().await.unwrap();
+        let info = backend.create_worktree(&"sparse-session-1".to_string(), "main", "sparse-branch").await.unwrap();
+
+        assert!(info.worktree_path.join("services/api/index.ts").exists());
+        assert!(info.worktree_path.join("libs/shared/index.ts").exists());
+        assert!(info.worktree_path.join("README.md").exists());
+        assert!(!info.worktree_path.join("services/web").exists());
+        assert!(repo_path.join("services/web/index.ts").exists());
+
+        let branch = backend
+            .run_git_command_in_context(&["rev-parse", "--abbrev-ref", "HEAD"], GitContext::Session(info.worktree_path.clone()))
+            .await
+            .unwrap();
+        assert_eq!(branch, "sparse-branch");
+        backend.cleanup_worktree(&info.session_id).await.unwrap();
+    }
+
+    #[cfg(feature = "libgit2")]
+    #[tokio::test]
+    async fn test_libgit2_backend_sparse_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = create_test_repo(&temp_dir).await.unwrap();
+        add_packages(&repo_path);
+
+        let backend = LibGit2Backend::new(repo_path.clone()).unwrap().with_checkout(sparse_options());
+        backend.initialize().await.unwrap();
+        let info = backend.create_worktree(&"sparse-session-2".to_string(), "main", "sparse-branch").await.unwrap();
+
+        assert!(info.worktree_path.join("services/api/index.ts").exists());
+        assert!(info.worktree_path.join("README.md").exists());
+        assert!(!info.worktree_path.join("services/web").exists());
+        // The main checkout's index is untouched
+        let status = Command::new("git").current_dir(&repo_path).args(["status", "--porcelain", "--untracked-files=no"]).output().unwrap();
+        assert!(status.stdout.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkout_estimate_and_progress_parsing() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = create_test_repo(&temp_dir).await.unwrap();
+        add_packages(&repo_path);
+
+        let full = estimate_checkout(&repo_path, "main", &CheckoutOptions::default()).await.unwrap();
+        assert_eq!(full, CheckoutEstimate { files: 4, bytes: 18 + 3 * 10 });
+        let sparse = estimate_checkout(&repo_path, "main", &sparse_options()).await.unwrap();
+        assert_eq!(sparse, CheckoutEstimate { files: 3, bytes: 18 + 2 * 10 });
+        let filtered = CheckoutOptions { blob_filter: Some("blob:none".to_string()), ..Default::default() };
+        assert_eq!(estimate_checkout(&repo_path, "main", &filtered).await.unwrap(), CheckoutEstimate { files: 4, bytes: 0 });
+
+        assert_eq!(parse_checkout_progress("Updating files:  45% (450/1000)"), Some((45, 450, 1000)));
+        assert_eq!(parse_checkout_progress("Updating files: 100% (1000/1000), done."), Some((100, 1000, 1000)));
+        assert_eq!(parse_checkout_progress("Your branch is up to date."), None);
+    }
+
+    #[tokio::test]
+    async fn test_cli_backend_worktree_progress_and_cancellation() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = create_test_repo(&temp_dir).await.unwrap();
+        let backend = CliBackend::new(repo_path).unwrap();
+        backend.initialize().await.unwrap();
+
+        let stages = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = stages.clone();
+        let progress: ProgressFn = Arc::new(move |stage| recorded.lock().unwrap().push(stage));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let session_id = "cancelled-session-1".to_string();
+        let result = backend.create_worktree_with_progress(&session_id, "main", "cancelled-branch", &progress, &cancel).await;
+        assert!(matches!(result, Err(GitError::Cancelled { .. })));
+        assert!(!backend.get_worktree_path(&session_id).exists());
+        assert!(!backend.is_branch_existing("cancelled-branch").await.unwrap());
+        assert!(backend.list_worktrees().await.unwrap().is_empty());
+
+        let info = backend
+            .create_worktree_with_progress(&"progress-session-1".to_string(), "main", "progress-branch", &progress, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert!(info.worktree_path.join("README.md").exists());
+        assert!(info.worktree_path.join("AGENT_CONTEXT").exists());
+        let branch_created = |branch: &str| WorktreeProgress::BranchCreated { branch: branch.to_string() };
+        assert_eq!(*stages.lock().unwrap(), vec![branch_created("cancelled-branch"), branch_created("progress-branch")]);
+        backend.cleanup_worktree(&info.session_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partial_clone_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = create_test_repo(&temp_dir).await.unwrap();
+        Command::new("git").current_dir(&repo_path).args(["remote", "add", "origin", "https://example.com/repo.git"]).status().unwrap();
+
+        let checkout = CheckoutOptions { sparse_paths: Vec::new(), blob_filter: Some("blob:none".to_string()) };
+        let backend = create_git_backend_with_checkout(repo_path.clone(), checkout).unwrap();
+        backend.initialize().await.unwrap();
+
+        let config = |key: &str| {
+            let output = Command::new("git").current_dir(&repo_path).args(["config", key]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        assert_eq!(config("remote.origin.promisor"), "true");
+        assert_eq!(config("remote.origin.partialclonefilter"), "blob:none");
+    }
+
     #[cfg(feature = "libgit2")]
     #[tokio::test]
     async fn test_libgit2_backend_initialization() {
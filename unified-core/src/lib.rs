@@ -1,12 +1,18 @@
+pub mod agent_harness;
 pub mod domain;
+pub mod env_schema;
 pub mod git;
 pub mod persistence;
+pub mod process_limits;
 pub mod error;
 pub mod worktree_manager;
 
+pub use agent_harness::*;
 pub use domain::*;
+pub use env_schema::*;
 pub use git::*;
 pub use persistence::*;
+pub use process_limits::LimitExceeded;
 pub use error::*;
 pub use worktree_manager::*;
 
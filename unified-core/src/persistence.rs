@@ -365,6 +365,17 @@ pub mod sqlx_impl {
             .await
             .map_err(|e| PersistenceError::Database(e.to_string()))?;
 
+            // Backs the list-by-status and list-by-type queries
+            for index in [
+                "CREATE INDEX IF NOT EXISTS idx_batches_status ON batches(status)",
+                "CREATE INDEX IF NOT EXISTS idx_benchmarks_type ON benchmarks(benchmark_type)",
+            ] {
+                sqlx::query(index)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| PersistenceError::Database(e.to_string()))?;
+            }
+
             Ok(())
         }
     }
@@ -385,6 +396,7 @@ pub mod sqlx_impl {
                 crate::domain::SessionStatus::Initializing => "initializing",
                 crate::domain::SessionStatus::Idle => "idle",
                 crate::domain::SessionStatus::Running => "running",
+                crate::domain::SessionStatus::Suspended => "suspended",
                 crate::domain::SessionStatus::AwaitingInput => "awaiting_input",
                 crate::domain::SessionStatus::Evaluating => "evaluating",
                 crate::domain::SessionStatus::Error(e) => &format!("error:{}", e),
@@ -474,6 +486,7 @@ pub mod sqlx_impl {
                 crate::domain::SessionStatus::Initializing => "initializing",
                 crate::domain::SessionStatus::Idle => "idle",
                 crate::domain::SessionStatus::Running => "running",
+                crate::domain::SessionStatus::Suspended => "suspended",
                 crate::domain::SessionStatus::AwaitingInput => "awaiting_input",
                 crate::domain::SessionStatus::Evaluating => "evaluating",
                 crate::domain::SessionStatus::Error(e) => &format!("error:{}", e),
@@ -576,6 +589,7 @@ pub mod sqlx_impl {
                 crate::domain::SessionStatus::Initializing => "initializing",
                 crate::domain::SessionStatus::Idle => "idle",
                 crate::domain::SessionStatus::Running => "running",
+                crate::domain::SessionStatus::Suspended => "suspended",
                 crate::domain::SessionStatus::AwaitingInput => "awaiting_input",
                 crate::domain::SessionStatus::Evaluating => "evaluating",
                 crate::domain::SessionStatus::Error(e) => &format!("error:{}", e),
@@ -635,6 +649,7 @@ pub mod sqlx_impl {
                     "initializing" => crate::domain::SessionStatus::Initializing,
                     "idle" => crate::domain::SessionStatus::Idle,
                     "running" => crate::domain::SessionStatus::Running,
+                    "suspended" => crate::domain::SessionStatus::Suspended,
                     "awaiting_input" => crate::domain::SessionStatus::AwaitingInput,
                     "evaluating" => crate::domain::SessionStatus::Evaluating,
                     "completed" => crate::domain::SessionStatus::Completed,
@@ -708,60 +723,306 @@ pub mod sqlx_impl {
         }
     }
 
-    // For now, provide minimal implementations for BatchStore and BenchmarkStore
-    // These can be fully implemented later as needed
+    /// Map a failed insert to `ConstraintViolation` when the ID is taken, as `InMemoryStore` does
+    fn insert_error(e: sqlx::Error, kind: &str, id: &str) -> PersistenceError {
+        match e.as_database_error() {
+            Some(db) if db.is_unique_violation() => PersistenceError::ConstraintViolation {
+                constraint: format!("{} with id {} already exists", kind, id),
+            },
+            _ => PersistenceError::Database(e.to_string()),
+        }
+    }
+
+    fn to_json<T: serde::Serialize>(value: &T) -> PersistenceResult<String> {
+        serde_json::to_string(value).map_err(|e| PersistenceError::SerializationError(e.to_string()))
+    }
+
+    fn from_json<T: serde::de::DeserializeOwned>(row: &sqlx::sqlite::SqliteRow, column: &str) -> PersistenceResult<T> {
+        use sqlx::Row;
+        let json: String = row.get(column);
+        serde_json::from_str(&json).map_err(|e| PersistenceError::DeserializationError(e.to_string()))
+    }
+
+    fn parse_timestamp(timestamp: &str) -> PersistenceResult<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::parse_from_rfc3339(timestamp)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .map_err(|e| PersistenceError::DeserializationError(e.to_string()))
+    }
+
+    fn batch_status_str(status: &crate::domain::BatchStatus) -> &'static str {
+        match status {
+            crate::domain::BatchStatus::Pending => "pending",
+            crate::domain::BatchStatus::Running => "running",
+            crate::domain::BatchStatus::Completed => "completed",
+            crate::domain::BatchStatus::Failed => "failed",
+            crate::domain::BatchStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn benchmark_type_str(benchmark_type: &crate::domain::BenchmarkType) -> &'static str {
+        match benchmark_type {
+            crate::domain::BenchmarkType::SweBench => "swe_bench",
+            crate::domain::BenchmarkType::Custom => "custom",
+            crate::domain::BenchmarkType::Performance => "performance",
+        }
+    }
+
+    const BATCH_COLUMNS: &str = "id, name, description, config, status, sessions, created_at, started_at, completed_at, metrics";
+    const BENCHMARK_COLUMNS: &str = "id, name, description, benchmark_type, dataset_info, evaluation_config, results, created_at";
+
     #[async_trait]
     impl BatchStore for SqliteStore {
-        async fn create_batch(&self, _batch: &crate::domain::Batch) -> PersistenceResult<()> {
-            // TODO: Implement batch persistence
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn create_batch(&self, batch: &Batch) -> PersistenceResult<()> {
+            sqlx::query(r#"
+                INSERT INTO batches (
+                    id, name, description, config, status, sessions,
+                    created_at, started_at, completed_at, metrics
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#)
+            .bind(&batch.id)
+            .bind(&batch.name)
+            .bind(&batch.description)
+            .bind(to_json(&batch.config)?)
+            .bind(batch_status_str(&batch.status))
+            .bind(to_json(&batch.sessions)?)
+            .bind(batch.created_at.to_rfc3339())
+            .bind(batch.started_at.as_ref().map(|at| at.to_rfc3339()))
+            .bind(batch.completed_at.as_ref().map(|at| at.to_rfc3339()))
+            .bind(to_json(&batch.metrics)?)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| insert_error(e, "Batch", &batch.id))?;
+
+            Ok(())
         }
 
-        async fn get_batch(&self, _batch_id: &BatchId) -> PersistenceResult<Option<crate::domain::Batch>> {
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn get_batch(&self, batch_id: &BatchId) -> PersistenceResult<Option<Batch>> {
+            let row = sqlx::query(&format!("SELECT {} FROM batches WHERE id = ?", BATCH_COLUMNS))
+                .bind(batch_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            row.map(|row| self.row_to_batch(row)).transpose()
         }
 
-        async fn update_batch(&self, _batch: &crate::domain::Batch) -> PersistenceResult<()> {
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn update_batch(&self, batch: &Batch) -> PersistenceResult<()> {
+            let result = sqlx::query(r#"
+                UPDATE batches SET
+                    name = ?, description = ?, config = ?, status = ?, sessions = ?,
+                    started_at = ?, completed_at = ?, metrics = ?
+                WHERE id = ?
+            "#)
+            .bind(&batch.name)
+            .bind(&batch.description)
+            .bind(to_json(&batch.config)?)
+            .bind(batch_status_str(&batch.status))
+            .bind(to_json(&batch.sessions)?)
+            .bind(batch.started_at.as_ref().map(|at| at.to_rfc3339()))
+            .bind(batch.completed_at.as_ref().map(|at| at.to_rfc3339()))
+            .bind(to_json(&batch.metrics)?)
+            .bind(&batch.id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            if result.rows_affected() == 0 {
+                return Err(PersistenceError::RecordNotFound {
+                    table: "batches".to_string(),
+                    id: batch.id.clone(),
+                });
+            }
+
+            Ok(())
         }
 
-        async fn delete_batch(&self, _batch_id: &BatchId) -> PersistenceResult<()> {
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn delete_batch(&self, batch_id: &BatchId) -> PersistenceResult<()> {
+            let result = sqlx::query("DELETE FROM batches WHERE id = ?")
+                .bind(batch_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            if result.rows_affected() == 0 {
+                return Err(PersistenceError::RecordNotFound {
+                    table: "batches".to_string(),
+                    id: batch_id.to_string(),
+                });
+            }
+
+            Ok(())
         }
 
-        async fn list_batches(&self) -> PersistenceResult<Vec<crate::domain::Batch>> {
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn list_batches(&self) -> PersistenceResult<Vec<Batch>> {
+            let rows = sqlx::query(&format!("SELECT {} FROM batches ORDER BY created_at DESC", BATCH_COLUMNS))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            rows.into_iter().map(|row| self.row_to_batch(row)).collect()
         }
 
-        async fn list_batches_by_status(&self, _status: &crate::domain::BatchStatus) -> PersistenceResult<Vec<crate::domain::Batch>> {
-            Err(PersistenceError::NotImplemented("Batch operations not yet implemented".to_string()))
+        async fn list_batches_by_status(&self, status: &crate::domain::BatchStatus) -> PersistenceResult<Vec<Batch>> {
+            let rows = sqlx::query(&format!("SELECT {} FROM batches WHERE status = ? ORDER BY created_at DESC", BATCH_COLUMNS))
+                .bind(batch_status_str(status))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            rows.into_iter().map(|row| self.row_to_batch(row)).collect()
         }
     }
 
     #[async_trait]
     impl BenchmarkStore for SqliteStore {
-        async fn create_benchmark(&self, _benchmark: &crate::domain::Benchmark) -> PersistenceResult<()> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn create_benchmark(&self, benchmark: &Benchmark) -> PersistenceResult<()> {
+            sqlx::query(r#"
+                INSERT INTO benchmarks (
+                    id, name, description, benchmark_type, dataset_info,
+                    evaluation_config, results, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#)
+            .bind(&benchmark.id)
+            .bind(&benchmark.name)
+            .bind(&benchmark.description)
+            .bind(benchmark_type_str(&benchmark.benchmark_type))
+            .bind(to_json(&benchmark.dataset_info)?)
+            .bind(to_json(&benchmark.evaluation_config)?)
+            .bind(to_json(&benchmark.results)?)
+            .bind(benchmark.created_at.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| insert_error(e, "Benchmark", &benchmark.id))?;
+
+            Ok(())
         }
 
-        async fn get_benchmark(&self, _benchmark_id: &BenchmarkId) -> PersistenceResult<Option<crate::domain::Benchmark>> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn get_benchmark(&self, benchmark_id: &BenchmarkId) -> PersistenceResult<Option<Benchmark>> {
+            let row = sqlx::query(&format!("SELECT {} FROM benchmarks WHERE id = ?", BENCHMARK_COLUMNS))
+                .bind(benchmark_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            row.map(|row| self.row_to_benchmark(row)).transpose()
         }
 
-        async fn update_benchmark(&self, _benchmark: &crate::domain::Benchmark) -> PersistenceResult<()> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn update_benchmark(&self, benchmark: &Benchmark) -> PersistenceResult<()> {
+            let result = sqlx::query(r#"
+                UPDATE benchmarks SET
+                    name = ?, description = ?, benchmark_type = ?, dataset_info = ?,
+                    evaluation_config = ?, results = ?
+                WHERE id = ?
+            "#)
+            .bind(&benchmark.name)
+            .bind(&benchmark.description)
+            .bind(benchmark_type_str(&benchmark.benchmark_type))
+            .bind(to_json(&benchmark.dataset_info)?)
+            .bind(to_json(&benchmark.evaluation_config)?)
+            .bind(to_json(&benchmark.results)?)
+            .bind(&benchmark.id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            if result.rows_affected() == 0 {
+                return Err(PersistenceError::RecordNotFound {
+                    table: "benchmarks".to_string(),
+                    id: benchmark.id.clone(),
+                });
+            }
+
+            Ok(())
         }
 
-        async fn delete_benchmark(&self, _benchmark_id: &BenchmarkId) -> PersistenceResult<()> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn delete_benchmark(&self, benchmark_id: &BenchmarkId) -> PersistenceResult<()> {
+            let result = sqlx::query("DELETE FROM benchmarks WHERE id = ?")
+                .bind(benchmark_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            if result.rows_affected() == 0 {
+                return Err(PersistenceError::RecordNotFound {
+                    table: "benchmarks".to_string(),
+                    id: benchmark_id.to_string(),
+                });
+            }
+
+            Ok(())
         }
 
-        async fn list_benchmarks(&self) -> PersistenceResult<Vec<crate::domain::Benchmark>> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn list_benchmarks(&self) -> PersistenceResult<Vec<Benchmark>> {
+            let rows = sqlx::query(&format!("SELECT {} FROM benchmarks ORDER BY created_at DESC", BENCHMARK_COLUMNS))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            rows.into_iter().map(|row| self.row_to_benchmark(row)).collect()
         }
 
-        async fn list_benchmarks_by_type(&self, _benchmark_type: &crate::domain::BenchmarkType) -> PersistenceResult<Vec<crate::domain::Benchmark>> {
-            Err(PersistenceError::NotImplemented("Benchmark operations not yet implemented".to_string()))
+        async fn list_benchmarks_by_type(&self, benchmark_type: &crate::domain::BenchmarkType) -> PersistenceResult<Vec<Benchmark>> {
+            let rows = sqlx::query(&format!("SELECT {} FROM benchmarks WHERE benchmark_type = ? ORDER BY created_at DESC", BENCHMARK_COLUMNS))
+                .bind(benchmark_type_str(benchmark_type))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| PersistenceError::Database(e.to_string()))?;
+
+            rows.into_iter().map(|row| self.row_to_benchmark(row)).collect()
+        }
+    }
+
+    impl SqliteStore {
+        /// Helper method to convert a SQLite row to a Batch
+        fn row_to_batch(&self, row: sqlx::sqlite::SqliteRow) -> PersistenceResult<Batch> {
+            use sqlx::Row;
+
+            let status_str: String = row.get("status");
+            let status = match status_str.as_str() {
+                "pending" => crate::domain::BatchStatus::Pending,
+                "running" => crate::domain::BatchStatus::Running,
+                "completed" => crate::domain::BatchStatus::Completed,
+                "failed" => crate::domain::BatchStatus::Failed,
+                "cancelled" => crate::domain::BatchStatus::Cancelled,
+                _ => return Err(PersistenceError::DeserializationError(format!("Unknown batch status: {}", status_str))),
+            };
+
+            Ok(Batch {
+                id: row.get("id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                config: from_json(&row, "config")?,
+                status,
+                sessions: from_json(&row, "sessions")?,
+                created_at: parse_timestamp(&row.get::<String, _>("created_at"))?,
+                started_at: row.get::<Option<String>, _>("started_at").as_deref().map(parse_timestamp).transpose()?,
+                completed_at: row.get::<Option<String>, _>("completed_at").as_deref().map(parse_timestamp).transpose()?,
+                metrics: from_json(&row, "metrics")?,
+            })
+        }
+
+        /// Helper method to convert a SQLite row to a Benchmark
+        fn row_to_benchmark(&self, row: sqlx::sqlite::SqliteRow) -> PersistenceResult<Benchmark> {
+            use sqlx::Row;
+
+            let type_str: String = row.get("benchmark_type");
+            let benchmark_type = match type_str.as_str() {
+                "swe_bench" => crate::domain::BenchmarkType::SweBench,
+                "custom" => crate::domain::BenchmarkType::Custom,
+                "performance" => crate::domain::BenchmarkType::Performance,
+                _ => return Err(PersistenceError::DeserializationError(format!("Unknown benchmark type: {}", type_str))),
+            };
+
+            Ok(Benchmark {
+                id: row.get("id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                benchmark_type,
+                dataset_info: from_json(&row, "dataset_info")?,
+                evaluation_config: from_json(&row, "evaluation_config")?,
+                results: from_json(&row, "results")?,
+                created_at: parse_timestamp(&row.get::<String, _>("created_at"))?,
+            })
         }
     }
 
@@ -867,4 +1128,94 @@ mod tests {
         let benchmarks = store.list_benchmarks().await.unwrap();
         assert_eq!(benchmarks.len(), 1);
     }
+
+    #[cfg(feature = "persistence")]
+    async fn open_sqlite_store(path: &std::path::Path) -> SqliteStore {
+        let options = sqlx::sqlite::SqliteConnectOptions::new().filename(path).create_if_missing(true);
+        let store = SqliteStore::new(sqlx::SqlitePool::connect_with(options).await.unwrap());
+        store.initialize().await.unwrap();
+        store
+    }
+
+    #[cfg(feature = "persistence")]
+    #[tokio::test]
+    async fn test_sqlite_batch_store_survives_reopening() {
+        use crate::domain::BatchStatus;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("store.db");
+        let store = open_sqlite_store(&db_path).await;
+
+        let batch_config = crate::domain::BatchConfig {
+            concurrency_limit: 2,
+            timeout: std::time::Duration::from_secs(600),
+            retry_policy: crate::domain::RetryPolicy {
+                max_attempts: 2,
+                backoff_ms: 500,
+                retry_on_failure: true,
+            },
+            environment: crate::domain::EnvironmentConfig {
+                amp_server_url: Some("http://localhost:7002".to_string()),
+                amp_cli_path: None,
+                agent_modes: vec![],
+                toolbox_paths: vec![],
+            },
+            tasks: vec![],
+        };
+        let mut batch = Batch::new("Nightly".to_string(), batch_config);
+        batch.sessions = vec!["s1".to_string(), "s2".to_string()];
+        store.create_batch(&batch).await.unwrap();
+        assert!(matches!(store.create_batch(&batch).await, Err(PersistenceError::ConstraintViolation { .. })));
+
+        batch.status = BatchStatus::Running;
+        batch.started_at = Some(chrono::Utc::now());
+        batch.metrics.completed_sessions = 1;
+        store.update_batch(&batch).await.unwrap();
+        drop(store);
+
+        let store = open_sqlite_store(&db_path).await;
+        let retrieved = store.get_batch(&batch.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.status, BatchStatus::Running);
+        assert_eq!(retrieved.sessions, batch.sessions);
+        assert_eq!(retrieved.started_at, batch.started_at);
+        assert_eq!(retrieved.created_at, batch.created_at);
+        assert_eq!(retrieved.metrics.completed_sessions, 1);
+        assert_eq!(retrieved.config.timeout, std::time::Duration::from_secs(600));
+        assert_eq!(retrieved.config.environment.amp_server_url.as_deref(), Some("http://localhost:7002"));
+        assert_eq!(store.list_batches_by_status(&BatchStatus::Running).await.unwrap().len(), 1);
+        assert!(store.list_batches_by_status(&BatchStatus::Pending).await.unwrap().is_empty());
+
+        store.delete_batch(&batch.id).await.unwrap();
+        assert!(store.list_batches().await.unwrap().is_empty());
+        assert!(matches!(store.delete_batch(&batch.id).await, Err(PersistenceError::RecordNotFound { .. })));
+        assert!(matches!(store.update_batch(&batch).await, Err(PersistenceError::RecordNotFound { .. })));
+    }
+
+    #[cfg(feature = "persistence")]
+    #[tokio::test]
+    async fn test_sqlite_benchmark_store() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = open_sqlite_store(&dir.path().join("store.db")).await;
+
+        let mut swe = Benchmark::new("SWE-bench lite".to_string(), BenchmarkType::SweBench);
+        swe.description = Some("300 cases".to_string());
+        swe.dataset_info.total_cases = 300;
+        let perf = Benchmark::new("Startup".to_string(), BenchmarkType::Performance);
+        store.create_benchmark(&swe).await.unwrap();
+        store.create_benchmark(&perf).await.unwrap();
+
+        let by_type = store.list_benchmarks_by_type(&BenchmarkType::SweBench).await.unwrap();
+        assert_eq!(by_type.len(), 1);
+        assert_eq!(by_type[0].description.as_deref(), Some("300 cases"));
+        assert_eq!(by_type[0].dataset_info.total_cases, 300);
+        assert!(store.list_benchmarks_by_type(&BenchmarkType::Custom).await.unwrap().is_empty());
+
+        swe.benchmark_type = BenchmarkType::Custom;
+        store.update_benchmark(&swe).await.unwrap();
+        assert_eq!(store.get_benchmark(&swe.id).await.unwrap().unwrap().benchmark_type, BenchmarkType::Custom);
+        assert_eq!(store.list_benchmarks().await.unwrap().len(), 2);
+
+        store.delete_benchmark(&perf.id).await.unwrap();
+        assert!(store.get_benchmark(&perf.id).await.unwrap().is_none());
+    }
 }
@@ -0,0 +1,170 @@
+//! Enforcing `ProcessLimits` on agent processes
+//!
+//! The open-file limit is set with setrlimit in the child before it execs.
+//! Memory is limited on resident size, sampled from `/proc`, so only on Linux;
+//! elsewhere the memory limit is not enforced. An address-space rlimit would
+//! stop node from starting at all, since V8 reserves far more than it uses.
+//! `watch` checks memory and execution time and returns the first limit
+//! passed; the caller kills the process. CPU share is not enforced. A limit of
+//! zero means none.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use crate::domain::ProcessLimits;
+
+/// How often `watch` samples the process
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "limit", rename_all = "snake_case")]
+pub enum LimitExceeded {
+    Memory { used_mb: u64, limit_mb: u64 },
+    ExecutionTime { limit_secs: u64 },
+}
+
+impl LimitExceeded {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LimitExceeded::Memory { .. } => "memory",
+            LimitExceeded::ExecutionTime { .. } => "execution_time",
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Memory { used_mb, limit_mb } => write!(f, "Used {}MB of memory, over the {}MB limit", used_mb, limit_mb),
+            LimitExceeded::ExecutionTime { limit_secs } => write!(f, "Timed out after {}s", limit_secs),
+        }
+    }
+}
+
+/// Lower the child's open-file limit to `max_open_files`, or its current hard limit if lower
+#[cfg(unix)]
+pub fn apply_rlimits(command: &mut Command, limits: &ProcessLimits) {
+    if limits.max_open_files == 0 {
+        return;
+    }
+    let max_open_files = limits.max_open_files as libc::rlim_t;
+    // SAFETY: the closure only calls getrlimit and setrlimit, which are async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            if libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let max = max_open_files.min(limit.rlim_max);
+            let limit = libc::rlimit { rlim_cur: max, rlim_max: max };
+            if libc::setrlimit(libc::RLIMIT_NOFILE, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply_rlimits(_command: &mut Command, _limits: &ProcessLimits) {}
+
+/// Resident memory of a running process
+#[cfg(target_os = "linux")]
+pub fn resident_memory_mb(pid: u32) -> Option<u64> {
+    let statm = std::fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf only reads its argument
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * page_size.max(0) as u64 / (1024 * 1024))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_memory_mb(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// The limit a process `elapsed` into its run and using `resident_mb` has passed
+pub fn check(limits: &ProcessLimits, elapsed: Duration, resident_mb: Option<u64>) -> Option<LimitExceeded> {
+    if !limits.max_execution_time.is_zero() && elapsed >= limits.max_execution_time {
+        return Some(LimitExceeded::ExecutionTime { limit_secs: limits.max_execution_time.as_secs() });
+    }
+    match resident_mb {
+        Some(used_mb) if limits.max_memory_mb > 0 && used_mb > limits.max_memory_mb => {
+            Some(LimitExceeded::Memory { used_mb, limit_mb: limits.max_memory_mb })
+        }
+        _ => None,
+    }
+}
+
+/// Wait until process `pid`, started now, passes one of `limits`. Never returns
+/// for a process within them; race it against the process ending.
+pub async fn watch(pid: Option<u32>, limits: &ProcessLimits) -> LimitExceeded {
+    watch_unless_paused(pid, limits, || false).await
+}
+
+/// `watch` for a process that can be suspended: while `paused` returns true its
+/// memory isn't sampled and the time doesn't count toward its execution time
+pub async fn watch_unless_paused(pid: Option<u32>, limits: &ProcessLimits, paused: impl Fn() -> bool) -> LimitExceeded {
+    let mut elapsed = Duration::ZERO;
+    let mut last_poll = Instant::now();
+    let mut was_paused = false;
+    loop {
+        let now = Instant::now();
+        // The time since the last poll counts if the process was running then
+        if !was_paused {
+            elapsed += now - last_poll;
+        }
+        last_poll = now;
+        was_paused = paused();
+        if !was_paused {
+            if let Some(exceeded) = check(limits, elapsed, pid.and_then(resident_memory_mb)) {
+                return exceeded;
+            }
+        }
+        let until_timeout = limits.max_execution_time.checked_sub(elapsed).filter(|d| !d.is_zero());
+        tokio::time::sleep(until_timeout.map_or(POLL_INTERVAL, |d| d.min(POLL_INTERVAL))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_limits(memory_mb: u64, secs: u64) -> ProcessLimits {
+        ProcessLimits { max_memory_mb: memory_mb, max_execution_time: Duration::from_secs(secs), ..ProcessLimits::default() }
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = with_limits(100, 60);
+        assert_eq!(check(&limits, Duration::from_secs(10), Some(50)), None);
+        assert_eq!(check(&limits, Duration::from_secs(10), None), None);
+        assert_eq!(check(&limits, Duration::from_secs(10), Some(150)), Some(LimitExceeded::Memory { used_mb: 150, limit_mb: 100 }));
+        assert_eq!(check(&limits, Duration::from_secs(60), Some(50)), Some(LimitExceeded::ExecutionTime { limit_secs: 60 }));
+        assert_eq!(check(&with_limits(0, 0), Duration::from_secs(86400), Some(1 << 20)), None);
+        assert_eq!(LimitExceeded::ExecutionTime { limit_secs: 60 }.to_string(), "Timed out after 60s");
+    }
+
+    #[tokio::test]
+    async fn test_paused_time_does_not_count() {
+        let limits = ProcessLimits { max_execution_time: Duration::from_millis(200), ..with_limits(0, 0) };
+        let suspended = tokio::time::timeout(Duration::from_millis(600), watch_unless_paused(None, &limits, || true)).await;
+        assert!(suspended.is_err());
+
+        let start = Instant::now();
+        let exceeded = watch_unless_paused(None, &limits, || start.elapsed() < Duration::from_millis(400)).await;
+        assert_eq!(exceeded, LimitExceeded::ExecutionTime { limit_secs: 0 });
+        assert!(start.elapsed() >= Duration::from_millis(600));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_open_file_limit_applies_to_child() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "ulimit -n"]);
+        apply_rlimits(&mut command, &ProcessLimits { max_open_files: 64, ..ProcessLimits::default() });
+        let output = command.output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "64");
+    }
+}
@@ -83,6 +83,7 @@ mod domain_tests {
             SessionStatus::Initializing,
             SessionStatus::Idle,
             SessionStatus::Running,
+            SessionStatus::Suspended,
             SessionStatus::AwaitingInput,
             SessionStatus::Evaluating,
             SessionStatus::Error("Test error".to_string()),
@@ -95,6 +96,71 @@ mod domain_tests {
             assert_eq!(status, deserialized);
         }
     }
+
+    #[test]
+    fn test_runtime_config_environment_lint() {
+        use crate::env_schema::EnvProblemKind;
+
+        let mut config = RuntimeConfig::default();
+        assert!(config.lint_environment().is_empty());
+
+        for (name, value) in [
+            ("AMP_TOOLBOX_PATH", "/opt/toolbox"),
+            ("AMP_TOOLBOX_MAX_MB", "ten"),
+            ("AMP_URL", "localhost:7002"),
+            ("AMP_DEBUG", "true"),
+            ("AMP_SOMETHING_ELSE_ENTIRELY", "1"),
+            ("RUST_LOG", "debug"),
+        ] {
+            config.environment_variables.insert(name.to_string(), value.to_string());
+        }
+
+        let problems = config.lint_environment();
+        let summary: Vec<(&str, EnvProblemKind, Option<&str>)> = problems
+            .iter()
+            .map(|p| (p.variable.as_str(), p.kind, p.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("AMP_SOMETHING_ELSE_ENTIRELY", EnvProblemKind::UnknownVariable, None),
+                ("AMP_TOOLBOX_MAX_MB", EnvProblemKind::InvalidValue, None),
+                ("AMP_TOOLBOX_PATH", EnvProblemKind::UnknownVariable, Some("AMP_TOOLBOX_PATHS")),
+                ("AMP_URL", EnvProblemKind::InvalidValue, None),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_strict_env_isolation_leaks_no_inherited_variables() {
+        use crate::env_schema::ISOLATED_ENV_ALLOWLIST;
+        use std::collections::HashMap;
+
+        std::env::set_var("UNIFIED_CORE_LEAK_CANARY", "1");
+        let spawned_env = |config: &RuntimeConfig| -> HashMap<String, String> {
+            let output = std::process::Command::new("env").env_clear().envs(config.process_env()).output().unwrap();
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        let mut config = RuntimeConfig::default();
+        config.environment_variables.insert("AMP_MODEL".to_string(), "smart".to_string());
+        assert!(spawned_env(&config).contains_key("UNIFIED_CORE_LEAK_CANARY"));
+
+        config.strict_env_isolation = true;
+        let env = spawned_env(&config);
+        assert_eq!(env.get("AMP_MODEL").map(String::as_str), Some("smart"));
+        assert_eq!(env.get("PATH"), std::env::var("PATH").ok().as_ref());
+        let unexpected: Vec<&String> = env
+            .keys()
+            .filter(|key| *key != "AMP_MODEL" && !ISOLATED_ENV_ALLOWLIST.contains(&key.as_str()))
+            .collect();
+        assert!(unexpected.is_empty(), "unexpected variables reached the process: {:?}", unexpected);
+    }
 }
 
 #[cfg(test)]
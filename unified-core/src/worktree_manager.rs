@@ -4,14 +4,15 @@
 //! to provide session-aware worktree management with proper error handling,
 //! metrics collection, and database integration.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::domain::{SessionId, WorktreeInfo};
 use crate::error::{GitError, PersistenceError};
-use crate::git::{GitBackend, create_git_backend};
+use crate::git::{estimate_checkout, ignore_progress, CheckoutEstimate, CheckoutOptions, GitBackend, ProgressFn, WorktreeProgress, create_git_backend_with_checkout};
+use tokio_util::sync::CancellationToken;
 use crate::persistence::Store;
 
 /// Specific error types for WorktreeManager operations
@@ -31,6 +32,9 @@ pub enum WorktreeError {
     
     #[error("Agent context initialization failed: {reason}")]
     AgentContextFailed { reason: String },
+
+    #[error("Worktree creation cancelled: {session_id}")]
+    Cancelled { session_id: SessionId },
     
     #[error("Git operation failed: {0}")]
     Git(#[from] GitError),
@@ -44,14 +48,28 @@ pub enum WorktreeError {
 
 pub type WorktreeResult<T> = std::result::Result<T, WorktreeError>;
 
+/// Where a repository keeps the template for its worktrees' AGENT_CONTEXT
+pub const REPO_CONTEXT_TEMPLATE_DIR: &str = ".amp-orchestra/context-template";
+
+/// Template files larger than this are left out of AGENT_CONTEXT
+const MAX_TEMPLATE_FILE_BYTES: u64 = 1024 * 1024;
+const MAX_TEMPLATE_FILES: usize = 200;
+
 /// Configuration for WorktreeManager
 #[derive(Debug, Clone)]
 pub struct WorktreeManagerConfig {
     pub repo_root: PathBuf,
     pub worktrees_base_dir: PathBuf,
+    /// Directory rendered into each new worktree's AGENT_CONTEXT. A relative path
+    /// is read from the new worktree, so the template comes from the base branch,
+    /// or else from `repo_root`.
     pub agent_context_template_dir: Option<PathBuf>,
     pub auto_cleanup_orphans: bool,
     pub max_concurrent_operations: usize,
+    /// Identity written into each worktree's git config; `None` leaves the user's own
+    pub git_identity: Option<GitIdentityConfig>,
+    /// Sparse-checkout and partial clone settings for this repository's worktrees
+    pub checkout: CheckoutOptions,
 }
 
 impl Default for WorktreeManagerConfig {
@@ -59,13 +77,94 @@ impl Default for WorktreeManagerConfig {
         Self {
             repo_root: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             worktrees_base_dir: PathBuf::from(".worktrees"),
-            agent_context_template_dir: None,
+            agent_context_template_dir: Some(PathBuf::from(REPO_CONTEXT_TEMPLATE_DIR)),
             auto_cleanup_orphans: true,
             max_concurrent_operations: 10,
+            git_identity: Some(GitIdentityConfig::default()),
+            checkout: CheckoutOptions::default(),
+        }
+    }
+}
+
+/// Git author identity for commits made inside a session worktree
+///
+/// `{session_id}` in either template is replaced with the session id. The values
+/// are written with `git config --worktree`, so the main checkout and other
+/// worktrees keep the user's identity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitIdentityConfig {
+    pub name_template: String,
+    pub email_template: String,
+    /// Turn off commit and tag signing so agent commits don't prompt for a key
+    pub disable_signing: bool,
+}
+
+impl Default for GitIdentityConfig {
+    fn default() -> Self {
+        Self {
+            name_template: "Amp Session {session_id}".to_string(),
+            email_template: "amp-session-{session_id}@localhost".to_string(),
+            disable_signing: true,
         }
     }
 }
 
+impl GitIdentityConfig {
+    pub fn name_for(&self, session_id: &str) -> String {
+        self.name_template.replace("{session_id}", session_id)
+    }
+
+    pub fn email_for(&self, session_id: &str) -> String {
+        self.email_template.replace("{session_id}", session_id)
+    }
+
+    /// `git` argument lists to run inside the worktree, in order
+    pub fn git_config_commands(&self, session_id: &str) -> Vec<Vec<String>> {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        let mut commands = vec![
+            // Per-worktree config has to be switched on for the repository first
+            args(&["config", "extensions.worktreeConfig", "true"]),
+            args(&["config", "--worktree", "user.name", &self.name_for(session_id)]),
+            args(&["config", "--worktree", "user.email", &self.email_for(session_id)]),
+        ];
+        if self.disable_signing {
+            commands.push(args(&["config", "--worktree", "commit.gpgsign", "false"]));
+            commands.push(args(&["config", "--worktree", "tag.gpgsign", "false"]));
+        }
+        commands
+    }
+}
+
+/// Values substituted into AGENT_CONTEXT templates
+///
+/// `{session_id}`, `{base_branch}`, `{branch_name}`, `{prompt}`, `{repo_name}` and
+/// `{created_at}` in a template file are replaced; other braces are left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentContextVariables {
+    pub session_id: String,
+    pub base_branch: String,
+    pub branch_name: String,
+    pub prompt: String,
+    pub repo_name: String,
+    pub created_at: String,
+}
+
+impl AgentContextVariables {
+    pub fn render(&self, template: &str) -> String {
+        [
+            ("{session_id}", &self.session_id),
+            ("{base_branch}", &self.base_branch),
+            ("{branch_name}", &self.branch_name),
+            ("{repo_name}", &self.repo_name),
+            ("{created_at}", &self.created_at),
+            // Last, so braces in the prompt itself are not substituted
+            ("{prompt}", &self.prompt),
+        ]
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| text.replace(name, value))
+    }
+}
+
 /// Metrics for worktree operations
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WorktreeMetrics {
@@ -98,7 +197,7 @@ impl WorktreeManager {
         config: WorktreeManagerConfig,
         store: Arc<dyn Store>,
     ) -> WorktreeResult<Self> {
-        let git_backend = Arc::new(create_git_backend(config.repo_root.clone())
+        let git_backend = Arc::new(create_git_backend_with_checkout(config.repo_root.clone(), config.checkout.clone())
             .map_err(WorktreeError::Git)?);
         
         // Initialize Git backend
@@ -136,6 +235,39 @@ impl WorktreeManager {
         &self,
         session_id: &str,
         base_branch: &str,
+    ) -> WorktreeResult<WorktreeInfo> {
+        self.create_session_worktree_with_prompt(session_id, base_branch, None).await
+    }
+
+    /// Create a session worktree, rendering `prompt` into its AGENT_CONTEXT
+    /// templates. Without one, the stored session's prompt is used.
+    pub async fn create_session_worktree_with_prompt(
+        &self,
+        session_id: &str,
+        base_branch: &str,
+        prompt: Option<&str>,
+    ) -> WorktreeResult<WorktreeInfo> {
+        self.create_session_worktree_with_progress(session_id, base_branch, prompt, &ignore_progress(), &CancellationToken::new())
+            .await
+    }
+
+    /// Files and bytes a new worktree from `base_branch` would check out
+    pub async fn estimate_session_worktree(&self, base_branch: &str) -> WorktreeResult<CheckoutEstimate> {
+        estimate_checkout(&self.config.repo_root, base_branch, &self.config.checkout)
+            .await
+            .map_err(WorktreeError::Git)
+    }
+
+    /// `create_session_worktree_with_prompt` reporting each stage to `progress`,
+    /// starting with a pre-flight estimate. When `cancel` fires, the partial
+    /// worktree and its branch are removed and `WorktreeError::Cancelled` returned.
+    pub async fn create_session_worktree_with_progress(
+        &self,
+        session_id: &str,
+        base_branch: &str,
+        prompt: Option<&str>,
+        progress: &ProgressFn,
+        cancel: &CancellationToken,
     ) -> WorktreeResult<WorktreeInfo> {
         let _permit = self.operation_semaphore.acquire().await
             .map_err(|_| WorktreeError::AgentContextFailed {
@@ -152,27 +284,55 @@ impl WorktreeManager {
         }
         
         // Check if session already has a worktree
-        if let Ok(existing_session) = self.store.get_session(&session_id.to_string()).await {
-            if let Some(session) = existing_session {
-                if session.worktree_path.exists() {
-                    return Err(WorktreeError::SessionWorktreeExists {
-                        session_id: session_id.to_string(),
-                    });
-                }
-            }
+        let existing_session = self.store.get_session(&session_id.to_string()).await.ok().flatten();
+        if existing_session.as_ref().is_some_and(|session| session.worktree_path.exists()) {
+            return Err(WorktreeError::SessionWorktreeExists {
+                session_id: session_id.to_string(),
+            });
         }
         
         // Generate unique branch name
         let branch_name = self.generate_branch_name(session_id);
+
+        // An estimate that fails, e.g. for a base branch that only exists remotely, is skipped
+        match self.estimate_session_worktree(base_branch).await {
+            Ok(estimate) => progress(WorktreeProgress::Estimated { files: estimate.files, bytes: estimate.bytes }),
+            Err(e) => log::debug!("No checkout estimate for session {}: {}", session_id, e),
+        }
         
         // Create worktree using GitBackend
         let mut worktree_info = self.git_backend
-            .create_worktree(&session_id.to_string(), base_branch, &branch_name)
+            .create_worktree_with_progress(&session_id.to_string(), base_branch, &branch_name, progress, cancel)
             .await
-            .map_err(WorktreeError::Git)?;
+            .map_err(|e| match e {
+                GitError::Cancelled { .. } => WorktreeError::Cancelled { session_id: session_id.to_string() },
+                e => WorktreeError::Git(e),
+            })?;
         
+        // Attribute commits made in the worktree to the session
+        if let Err(e) = self.apply_git_identity(session_id, &worktree_info.worktree_path).await {
+            let _ = self.git_backend.cleanup_worktree(&session_id.to_string()).await;
+            return Err(e);
+        }
+
         // Initialize AGENT_CONTEXT directory with templates if available
-        self.initialize_agent_context(&worktree_info.worktree_path).await?;
+        let variables = AgentContextVariables {
+            session_id: session_id.to_string(),
+            base_branch: base_branch.to_string(),
+            branch_name: worktree_info.branch_name.clone(),
+            prompt: prompt
+                .map(str::to_string)
+                .or_else(|| existing_session.map(|session| session.prompt))
+                .unwrap_or_default(),
+            repo_name: self.config.repo_root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.initialize_agent_context(&worktree_info.worktree_path, &variables).await?;
+        if cancel.is_cancelled() {
+            let _ = self.git_backend.cleanup_worktree(&session_id.to_string()).await;
+            return Err(WorktreeError::Cancelled { session_id: session_id.to_string() });
+        }
+        progress(WorktreeProgress::HooksRun);
         
         // Update session in store with the worktree path
         if let Ok(Some(mut session)) = self.store.get_session(&session_id.to_string()).await {
@@ -193,10 +353,38 @@ impl WorktreeManager {
             "Created worktree for session {} at path: {:?} ({}ms)",
             session_id, worktree_info.worktree_path, creation_time_ms as u64
         );
+        progress(WorktreeProgress::Ready);
         
         Ok(worktree_info)
     }
 
+    async fn apply_git_identity(&self, session_id: &str, worktree_path: &std::path::Path) -> WorktreeResult<()> {
+        let Some(identity) = &self.config.git_identity else {
+            return Ok(());
+        };
+        // A linked worktree has a `.git` file. Without one, `git config --worktree`
+        // would edit the enclosing repository's identity instead.
+        if !worktree_path.join(".git").is_file() {
+            log::debug!("Skipping git identity for {:?}: not a linked worktree", worktree_path);
+            return Ok(());
+        }
+        // Run through the git CLI since not every backend supports raw commands
+        for args in identity.git_config_commands(session_id) {
+            let output = tokio::process::Command::new("git")
+                .current_dir(worktree_path)
+                .args(&args)
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(WorktreeError::Git(GitError::OperationFailed {
+                    operation: format!("git {}", args.join(" ")),
+                    reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
     /// Clean up a session worktree safely
     /// 
     /// This method:
@@ -320,7 +508,7 @@ impl WorktreeManager {
     }
 
     /// Initialize AGENT_CONTEXT directory with optional templates
-    async fn initialize_agent_context(&self, worktree_path: &PathBuf) -> WorktreeResult<()> {
+    async fn initialize_agent_context(&self, worktree_path: &PathBuf, variables: &AgentContextVariables) -> WorktreeResult<()> {
         let agent_context_path = worktree_path.join("AGENT_CONTEXT");
         
         // Create directory
@@ -330,10 +518,15 @@ impl WorktreeManager {
                 reason: e.to_string(),
             })?;
 
-        // Copy template files if template directory is specified
+        // Render template files if template directory is specified
         if let Some(template_dir) = &self.config.agent_context_template_dir {
-            if template_dir.exists() {
-                self.copy_template_files(template_dir, &agent_context_path).await?;
+            // Backends that don't check files out leave only the main checkout's copy
+            let template_dir = [worktree_path, &self.config.repo_root]
+                .iter()
+                .map(|root| root.join(template_dir))
+                .find(|dir| dir.is_dir());
+            if let Some(template_dir) = template_dir {
+                self.copy_template_files(&template_dir, &agent_context_path, variables).await?;
             }
         }
 
@@ -353,27 +546,51 @@ impl WorktreeManager {
         Ok(())
     }
 
-    /// Copy template files to AGENT_CONTEXT directory
-    async fn copy_template_files(&self, template_dir: &PathBuf, target_dir: &PathBuf) -> WorktreeResult<()> {
-        let mut entries = tokio::fs::read_dir(template_dir).await
-            .map_err(|e| WorktreeError::AgentContextFailed {
-                reason: format!("Failed to read template directory: {}", e),
-            })?;
+    /// Copy the template tree to AGENT_CONTEXT, rendering text files with `variables`
+    async fn copy_template_files(
+        &self,
+        template_dir: &Path,
+        target_dir: &Path,
+        variables: &AgentContextVariables,
+    ) -> WorktreeResult<()> {
+        let failed = |action: &str, path: &Path, e: std::io::Error| WorktreeError::AgentContextFailed {
+            reason: format!("Failed to {} {:?}: {}", action, path, e),
+        };
+        let mut pending = vec![(template_dir.to_path_buf(), target_dir.to_path_buf())];
+        let mut copied = 0;
+        while let Some((source_dir, target_dir)) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&source_dir).await
+                .map_err(|e| failed("read template directory", &source_dir, e))?;
+
+            while let Some(entry) = entries.next_entry().await
+                .map_err(|e| failed("iterate template directory", &source_dir, e))? {
+                let source_path = entry.path();
+                let target_path = target_dir.join(entry.file_name());
+                // Symlinks are not followed, so a template cannot pull in files from elsewhere
+                let file_type = entry.file_type().await
+                    .map_err(|e| failed("inspect template file", &source_path, e))?;
 
-        while let Some(entry) = entries.next_entry().await
-            .map_err(|e| WorktreeError::AgentContextFailed {
-                reason: format!("Failed to iterate template directory: {}", e),
-            })? {
-            
-            let source_path = entry.path();
-            let file_name = entry.file_name();
-            let target_path = target_dir.join(&file_name);
-
-            if source_path.is_file() {
-                tokio::fs::copy(&source_path, &target_path).await
-                    .map_err(|e| WorktreeError::AgentContextFailed {
-                        reason: format!("Failed to copy template file {:?}: {}", file_name, e),
-                    })?;
+                if file_type.is_dir() {
+                    tokio::fs::create_dir_all(&target_path).await
+                        .map_err(|e| failed("create", &target_path, e))?;
+                    pending.push((source_path, target_path));
+                } else if file_type.is_file() {
+                    let size = entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+                    if size > MAX_TEMPLATE_FILE_BYTES || copied >= MAX_TEMPLATE_FILES {
+                        log::warn!("Skipping agent context template {:?}: too large or too many files", source_path);
+                        continue;
+                    }
+                    let content = tokio::fs::read(&source_path).await
+                        .map_err(|e| failed("read template file", &source_path, e))?;
+                    // Binary files are copied as they are
+                    let content = match String::from_utf8(content) {
+                        Ok(text) => variables.render(&text).into_bytes(),
+                        Err(e) => e.into_bytes(),
+                    };
+                    tokio::fs::write(&target_path, content).await
+                        .map_err(|e| failed("write template file", &target_path, e))?;
+                    copied += 1;
+                }
             }
         }
 
@@ -495,6 +712,8 @@ mod tests {
             agent_context_template_dir: None,
             auto_cleanup_orphans: false, // Disable for controlled testing
             max_concurrent_operations: 5,
+            git_identity: Some(GitIdentityConfig::default()),
+            checkout: CheckoutOptions::default(),
         };
         
         let store = Arc::new(InMemoryStore::new());
@@ -560,6 +779,57 @@ mod tests {
         assert!(metrics.average_creation_time_ms > 0.0);
     }
 
+    #[test]
+    fn test_agent_context_variables_render() {
+        let variables = AgentContextVariables {
+            session_id: "abc12345".to_string(),
+            base_branch: "main".to_string(),
+            prompt: "Fix {branch_name}".to_string(),
+            ..AgentContextVariables::default()
+        };
+        assert_eq!(
+            variables.render("Session {session_id} from {base_branch}: {prompt} {unknown}"),
+            "Session abc12345 from main: Fix {branch_name} {unknown}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repo_context_template_rendered_into_worktree() {
+        let (temp_dir, repo_path) = create_test_repo().await;
+        let template_dir = repo_path.join(REPO_CONTEXT_TEMPLATE_DIR);
+        tokio::fs::create_dir_all(template_dir.join("notes")).await.unwrap();
+        tokio::fs::write(template_dir.join("README.md"), "# {repo_name}\nSession {session_id} on {branch_name} from {base_branch}\n\n{prompt}\n").await.unwrap();
+        tokio::fs::write(template_dir.join("notes/config.json"), "{\"created\": \"{created_at}\"}").await.unwrap();
+        tokio::fs::write(template_dir.join("logo.bin"), [0xffu8, 0xfe, 0x00]).await.unwrap();
+        Command::new("git").current_dir(&repo_path).args(["add", "-A"]).status().unwrap();
+        Command::new("git").current_dir(&repo_path).args(["commit", "-m", "Add context template"]).status().unwrap();
+
+        let config = WorktreeManagerConfig {
+            repo_root: repo_path.clone(),
+            worktrees_base_dir: temp_dir.path().join(".worktrees"),
+            auto_cleanup_orphans: false,
+            ..WorktreeManagerConfig::default()
+        };
+        let manager = WorktreeManager::new(config, Arc::new(InMemoryStore::new())).await.unwrap();
+        Command::new("git").current_dir(&repo_path).args(["commit", "-am", "Initialize worktree management", "--allow-empty"]).status().unwrap();
+        let session_id = "template-session-1234";
+        let info = manager
+            .create_session_worktree_with_prompt(session_id, "main", Some("Add a {feature} flag"))
+            .await
+            .unwrap();
+
+        let context = info.worktree_path.join("AGENT_CONTEXT");
+        let readme = tokio::fs::read_to_string(context.join("README.md")).await.unwrap();
+        let repo_name = repo_path.file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            readme,
+            format!("# {}\nSession {} on {} from main\n\nAdd a {{feature}} flag\n", repo_name, session_id, info.branch_name)
+        );
+        let config = tokio::fs::read_to_string(context.join("notes/config.json")).await.unwrap();
+        assert!(!config.contains("{created_at}") && config.starts_with("{\"created\": \""));
+        assert_eq!(tokio::fs::read(context.join("logo.bin")).await.unwrap(), vec![0xff, 0xfe, 0x00]);
+    }
+
     #[tokio::test]
     async fn test_create_worktree_duplicate_session() {
         let (_temp_dir, manager) = create_test_manager().await;
@@ -598,6 +868,36 @@ mod tests {
         assert!(matches!(result, Err(WorktreeError::InvalidSessionId { .. })));
     }
 
+    #[tokio::test]
+    async fn test_git_identity_is_scoped_to_worktree() {
+        let (temp_dir, manager) = create_test_manager().await;
+        let session_id = "identity-session-1234";
+        let worktree_path = temp_dir.path().join("linked");
+        let status = Command::new("git")
+            .current_dir(&manager.config.repo_root)
+            .args(["worktree", "add", "-b", "identity-branch", worktree_path.to_str().unwrap()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        manager.apply_git_identity(session_id, &worktree_path).await.unwrap();
+
+        let git_config = |dir: &std::path::Path, key: &str| {
+            let output = Command::new("git").current_dir(dir).args(["config", key]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        assert_eq!(git_config(&worktree_path, "user.name"), format!("Amp Session {}", session_id));
+        assert_eq!(git_config(&worktree_path, "user.email"), format!("amp-session-{}@localhost", session_id));
+        assert_eq!(git_config(&worktree_path, "commit.gpgsign"), "false");
+        assert_eq!(git_config(&manager.config.repo_root, "user.name"), "Test User");
+
+        // A plain directory inside the repository must not touch the repository's identity
+        let plain = manager.config.repo_root.join("plain");
+        std::fs::create_dir_all(&plain).unwrap();
+        manager.apply_git_identity(session_id, &plain).await.unwrap();
+        assert_eq!(git_config(&manager.config.repo_root, "user.name"), "Test User");
+    }
+
     #[tokio::test] 
     async fn test_cleanup_worktree_success() {
         let (_temp_dir, manager) = create_test_manager().await;